@@ -0,0 +1,54 @@
+//! Frame buffer allocation the way the capture does it: one buffer per frame,
+//! with a few frames in flight to the encoder before they are dropped.
+
+use std::collections::VecDeque;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use fjarsyn::utils::{
+    buffer_arena::BufferArena,
+    pixel_format::PixelFormat,
+    test_support::{FramePattern, RESOLUTIONS, SyntheticFrames},
+};
+
+// The frames queued for the encoder at a time.
+const IN_FLIGHT: usize = 3;
+// The size the capture starts its arena at.
+const ARENA_SIZE: usize = 128_000;
+
+fn capture_allocation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("capture_allocation");
+    for &(name, size) in RESOLUTIONS {
+        let source =
+            SyntheticFrames::new(size, PixelFormat::BGRA8, FramePattern::Noise).next_bitmap();
+        group.throughput(Throughput::Bytes(source.len() as u64));
+
+        let arena = BufferArena::init(ARENA_SIZE);
+        let mut in_flight = VecDeque::new();
+        group.bench_function(format!("arena_{}", name), |b| {
+            b.iter(|| {
+                let mut buffer = arena.get(source.len());
+                buffer.copy_from_slice(&source);
+                in_flight.push_back(buffer);
+                if in_flight.len() > IN_FLIGHT {
+                    in_flight.pop_front();
+                }
+            })
+        });
+
+        let mut in_flight = VecDeque::new();
+        group.bench_function(format!("vec_{}", name), |b| {
+            b.iter(|| {
+                let mut buffer = Vec::with_capacity(source.len());
+                buffer.extend_from_slice(&source);
+                in_flight.push_back(buffer);
+                if in_flight.len() > IN_FLIGHT {
+                    in_flight.pop_front();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, capture_allocation);
+criterion_main!(benches);
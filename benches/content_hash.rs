@@ -0,0 +1,27 @@
+//! The content check every frame goes through before it is encoded, when unchanged frames are skipped.
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use fjarsyn::utils::{
+    content_hash::{row_stride, sampled_hash},
+    pixel_format::PixelFormat,
+    test_support::{FramePattern, RESOLUTIONS, SyntheticFrames},
+};
+
+fn content_hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("content_hash");
+    for &(name, size) in RESOLUTIONS {
+        let mut frames = SyntheticFrames::new(size, PixelFormat::RGBA8, FramePattern::Noise);
+        let bitmap = frames.next_bitmap();
+
+        // What is actually read, which the stride keeps within the budget.
+        let rows = (size.y as usize).div_ceil(row_stride(size, 4));
+        group.throughput(Throughput::Bytes((rows * size.x as usize * 4) as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| sampled_hash(std::hint::black_box(&bitmap), size, 4))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, content_hash);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+//! Decoder throughput on a stream encoded up front.
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use fjarsyn::{
+    media::ffmpeg::{DecodeAccel, FFmpegDecoder, FFmpegEncoder, FFmpegTranscodeType, RateControl},
+    utils::{
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, RESOLUTIONS, SyntheticFrames},
+    },
+};
+
+const TRANSCODING_TYPE: FFmpegTranscodeType = FFmpegTranscodeType::H264Software;
+// Measures FFmpeg itself, rather than whichever GPU the machine has.
+const DECODE_ACCEL: DecodeAccel = DecodeAccel::Software;
+const FRAME_COUNT: usize = 120;
+
+// Encoded with the software encoder, so the stream is the same on every machine.
+fn encode_stream(pattern: FramePattern) -> Vec<Vec<u8>> {
+    let (_, size) = RESOLUTIONS[0];
+    let mut frames = SyntheticFrames::new(size, PixelFormat::RGBA8, pattern);
+    let mut encoder =
+        FFmpegEncoder::new(8_000_000, 60.0, PixelFormat::RGBA8, None, 120, RateControl::Variable)
+            .expect("Failed to create encoder");
+
+    let mut packets = Vec::new();
+    for _ in 0..FRAME_COUNT {
+        let bitmap = frames.next_bitmap();
+        packets.extend(
+            encoder.encode(&bitmap, TRANSCODING_TYPE, size.x, size.y).expect("Failed to encode"),
+        );
+    }
+    packets.extend(encoder.flush().expect("Failed to flush encoder"));
+    packets
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(FRAME_COUNT as u64));
+    group.sample_size(20);
+
+    for &pattern in FramePattern::ALL {
+        let packets = encode_stream(pattern);
+        group.bench_function(pattern.name(), |b| {
+            b.iter_batched(
+                || {
+                    FFmpegDecoder::new(TRANSCODING_TYPE, DECODE_ACCEL)
+                        .expect("Failed to create decoder")
+                },
+                |mut decoder| {
+                    for packet in &packets {
+                        decoder.decode(packet).expect("Failed to decode");
+                    }
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
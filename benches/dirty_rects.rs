@@ -0,0 +1,57 @@
+//! Dirty rect handling per captured frame: coalescing what WGC reports on a busy frame,
+//! and carrying the result along compared to carrying all of the rects.
+
+use std::hint::black_box;
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use fjarsyn::utils::{
+    dirty_rects::{self, MAX_DIRTY_RECTS},
+    rect::Rect,
+};
+
+// How many rects WGC reports for e.g. scrolling text, all of them a line or a few glyphs.
+const BUSY_FRAME_RECTS: &[usize] = &[16, 100, 400];
+// The stages the rects are handed on through, from the capture to the encoder.
+const HOPS: usize = 3;
+
+// Short runs of glyphs scattered over a 1080p frame, some next to each other.
+fn busy_frame(count: usize) -> Vec<Rect<i32>> {
+    (0..count as i32).map(|i| Rect::new((i * 37) % 1900, (i * 53) % 1060, 8 + i % 24, 16)).collect()
+}
+
+fn dirty_rects(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dirty_rects");
+    for &count in BUSY_FRAME_RECTS {
+        let rects = busy_frame(count);
+        group.throughput(Throughput::Elements(count as u64));
+
+        group.bench_function(format!("coalesce_{}", count), |b| {
+            b.iter_batched(
+                || rects.clone(),
+                |rects| dirty_rects::coalesce(rects, MAX_DIRTY_RECTS),
+                BatchSize::SmallInput,
+            )
+        });
+
+        group.bench_function(format!("carry_raw_{}", count), |b| {
+            b.iter(|| {
+                for _ in 0..HOPS {
+                    black_box(rects.clone());
+                }
+            })
+        });
+
+        let coalesced = dirty_rects::coalesce(rects.iter().copied(), MAX_DIRTY_RECTS);
+        group.bench_function(format!("carry_coalesced_{}", count), |b| {
+            b.iter(|| {
+                for _ in 0..HOPS {
+                    black_box(coalesced.clone());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dirty_rects);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+//! Encoder throughput on frames of varying complexity.
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use fjarsyn::{
+    media::ffmpeg::{FFmpegEncoder, FFmpegTranscodeType, RateControl},
+    utils::{
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, RESOLUTIONS, SyntheticFrames},
+    },
+};
+
+const BITRATE: u32 = 8_000_000;
+const FRAMERATE_HZ: f32 = 60.0;
+const GOP: u32 = 120;
+// Cycled through, so the encoder sees motion without generating frames while measuring.
+const FRAME_COUNT: usize = 30;
+
+fn encode(c: &mut Criterion) {
+    let (_, size) = RESOLUTIONS[0];
+    for &transcoding_type in FFmpegTranscodeType::ALL {
+        let mut group = c.benchmark_group(format!("encode_{}", transcoding_type));
+        group.throughput(Throughput::Elements(1));
+
+        for &pattern in FramePattern::ALL {
+            let mut frames = SyntheticFrames::new(size, PixelFormat::RGBA8, pattern);
+            let bitmaps: Vec<_> = (0..FRAME_COUNT).map(|_| frames.next_bitmap()).collect();
+
+            let mut encoder = FFmpegEncoder::new(
+                BITRATE,
+                FRAMERATE_HZ,
+                PixelFormat::RGBA8,
+                None,
+                GOP,
+                RateControl::Variable,
+            )
+            .expect("Failed to create encoder");
+            // Hardware encoders are only there on some machines.
+            if let Err(e) = encoder.encode(&bitmaps[0], transcoding_type, size.x, size.y) {
+                eprintln!("Skipping {}: {}", transcoding_type, e);
+                break;
+            }
+
+            let mut next = bitmaps.iter().cycle();
+            group.bench_function(pattern.name(), |b| {
+                b.iter(|| {
+                    encoder
+                        .encode(next.next().unwrap(), transcoding_type, size.x, size.y)
+                        .expect("Failed to encode frame")
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, encode);
+criterion_main!(benches);
@@ -0,0 +1,78 @@
+//! Messages the way the update loop sees them: queued by the runtime, cloned on their way
+//! to a screen, and moved through `App::update`. The size of `Message` is most of the cost.
+
+use std::{collections::VecDeque, hint::black_box, sync::Arc, time::Instant};
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use fjarsyn::{
+    networking::webrtc::TrackId,
+    ui::{app::App, message::Message, screens::call::CallMessage, test_support::home_state},
+    utils::{
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+use iced::Program;
+
+// About a second of a call at 60 fps: a captured and a decoded frame per tick.
+const BATCH: usize = 60;
+
+fn call_messages() -> Vec<Message> {
+    let frame = Arc::new(
+        SyntheticFrames::new(Vector2::new(64, 64), PixelFormat::BGRA8, FramePattern::Gradient)
+            .next_frame(),
+    );
+    (0..BATCH)
+        .flat_map(|i| {
+            let mut messages = vec![
+                Message::Tick(Instant::now()),
+                Message::Call(CallMessage::FrameCaptured(frame.clone())),
+                Message::Call(CallMessage::DecodedFrameReady(TrackId::default(), frame.clone())),
+            ];
+            // The connection stats come once a second.
+            if i == 0 {
+                messages.push(Message::Call(CallMessage::ConnectionStatsReady(Box::default())));
+            }
+            messages
+        })
+        .collect()
+}
+
+fn message_dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("message_dispatch");
+    let messages = call_messages();
+    group.throughput(Throughput::Elements(messages.len() as u64));
+
+    group.bench_function("queue_and_clone", |b| {
+        let mut queue = VecDeque::with_capacity(messages.len());
+        b.iter(|| {
+            queue.extend(messages.iter().cloned());
+            while let Some(message) = queue.pop_front() {
+                black_box(message.clone());
+                black_box(message);
+            }
+        })
+    });
+
+    // Ticks are left out, as on the home screen they can start connecting.
+    let calls: Vec<_> = messages.into_iter().filter(|m| matches!(m, Message::Call(_))).collect();
+    let mut state = home_state();
+    let app = App::default();
+    group.bench_function("update", |b| {
+        b.iter_batched(
+            || calls.clone(),
+            |calls| {
+                for message in calls {
+                    let _ = black_box(app.update(&mut state, message));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, message_dispatch);
+criterion_main!(benches);
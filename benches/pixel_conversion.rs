@@ -0,0 +1,44 @@
+//! The BGRA to RGBA conversion every captured frame goes through, and the tone mapping of HDR ones.
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use fjarsyn::utils::{
+    bitmap_utils::{bgra8_to_rgba8, rgba16f_to_rgba8},
+    pixel_format::PixelFormat,
+    test_support::{FramePattern, RESOLUTIONS, SyntheticFrames},
+};
+
+fn bgra_to_rgba(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bgra_to_rgba");
+    for &(name, size) in RESOLUTIONS {
+        let mut frames = SyntheticFrames::new(size, PixelFormat::BGRA8, FramePattern::Noise);
+        let mut bitmap = frames.next_bitmap();
+
+        group.throughput(Throughput::Bytes(bitmap.len() as u64));
+        // Converting in place swaps the channels back and forth, which costs the same either way.
+        group
+            .bench_function(name, |b| b.iter(|| bgra8_to_rgba8(std::hint::black_box(&mut bitmap))));
+    }
+    group.finish();
+}
+
+fn rgba16f_to_rgba(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rgba16f_to_rgba");
+    for &(name, size) in RESOLUTIONS {
+        let mut frames = SyntheticFrames::new(size, PixelFormat::RGBA16, FramePattern::Gradient);
+        let bitmap = frames.next_bitmap();
+
+        group.throughput(Throughput::Bytes(bitmap.len() as u64));
+        // Converting in place leaves half floats behind, so every run gets a fresh copy.
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || bitmap.clone(),
+                |bitmap| rgba16f_to_rgba8(std::hint::black_box(bitmap)).len(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bgra_to_rgba, rgba16f_to_rgba);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+//! Relaying between many peers connected to a server on a local port, as a burst of trickled candidates would.
+
+use bifrost::SignalingServer;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use fjarsyn_shared::{IdentityPayload, PROTOCOL_VERSION, SignalingMessage, SignalingType};
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, tungstenite::Message};
+
+const PEER_COUNTS: &[usize] = &[16, 128];
+
+struct Peer {
+    id: String,
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Peer {
+    async fn connect(url: &str) -> Self {
+        let (ws, _) = tokio_tungstenite::connect_async_with_config(url, None, true).await.unwrap();
+        let mut peer = Self { id: String::new(), ws };
+        peer.id = IdentityPayload::parse(&peer.recv().await.data).uuid;
+        peer
+    }
+
+    async fn send(&mut self, to: &str) {
+        let msg = SignalingMessage {
+            to: to.to_owned(),
+            from: self.id.clone(),
+            sig_type: SignalingType::Candidate,
+            data: "candidate:1 1 udp 2122260223 192.168.1.2 54321 typ host".to_owned(),
+            protocol_version: PROTOCOL_VERSION,
+            sealed: false,
+            mode: None,
+        };
+        self.ws.send(Message::text(serde_json::to_string(&msg).unwrap())).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> SignalingMessage {
+        match self.ws.next().await.unwrap().unwrap() {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("Expected a text message, got {:?}", other),
+        }
+    }
+}
+
+async fn connect(count: usize) -> Vec<Peer> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+    tokio::spawn(async move { SignalingServer::new().serve(listener).await });
+
+    let mut peers = Vec::with_capacity(count);
+    for _ in 0..count {
+        peers.push(Peer::connect(&url).await);
+    }
+    peers
+}
+
+// Every peer sends a candidate to the next one, and the first also to everyone, which each waits for.
+async fn round(peers: &mut [Peer]) {
+    let ids: Vec<String> = peers.iter().map(|peer| peer.id.clone()).collect();
+    for (i, peer) in peers.iter_mut().enumerate() {
+        peer.send(&ids[(i + 1) % ids.len()]).await;
+    }
+    peers[0].send("").await;
+
+    for (i, peer) in peers.iter_mut().enumerate() {
+        let expected = if i == 0 { 1 } else { 2 };
+        for _ in 0..expected {
+            peer.recv().await;
+        }
+    }
+}
+
+fn relay(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("relay");
+    for &count in PEER_COUNTS {
+        let mut peers = runtime.block_on(connect(count));
+
+        // A unicast for each peer, and the broadcast to all but one.
+        group.throughput(Throughput::Elements(2 * count as u64 - 1));
+        group.bench_function(format!("{}_peers", count), |b| {
+            b.iter(|| runtime.block_on(round(&mut peers)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, relay);
+criterion_main!(benches);
@@ -0,0 +1,5 @@
+mod short_codes;
+mod signaling_server;
+
+pub use short_codes::ShortCodes;
+pub use signaling_server::SignalingServer;
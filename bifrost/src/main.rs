@@ -1,6 +1,4 @@
-use crate::signaling_server::SignalingServer;
-
-mod signaling_server;
+use bifrost::SignalingServer;
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,70 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Short codes for the connected peers, which are easier to read out than their IDs.
+/// A code isn't handed out again for a while after its peer leaves,
+/// so someone still calling the old code doesn't reach whoever gets it next.
+#[derive(Debug, Default)]
+pub struct ShortCodes {
+    // Code to peer ID.
+    codes: HashMap<String, String>,
+    // Codes whose peer left, and when.
+    released: HashMap<String, Instant>,
+}
+
+impl ShortCodes {
+    pub const LEN: usize = 6;
+    pub const REUSE_DELAY: Duration = Duration::from_secs(60 * 60);
+    // Crockford's base32, which leaves out the letters easily mistaken for digits.
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+    /// Assigns the peer a random code that isn't in use or recently released.
+    pub fn assign(&mut self, peer_id: &str, now: Instant) -> String {
+        self.assign_with(peer_id, now, Self::random_code)
+    }
+
+    /// Like `assign`, drawing codes from `generate` until one is free.
+    pub fn assign_with(
+        &mut self,
+        peer_id: &str,
+        now: Instant,
+        mut generate: impl FnMut() -> String,
+    ) -> String {
+        self.released.retain(|_, released| now.duration_since(*released) < Self::REUSE_DELAY);
+        loop {
+            let code = generate();
+            if !self.codes.contains_key(&code) && !self.released.contains_key(&code) {
+                self.codes.insert(code.clone(), peer_id.to_owned());
+                return code;
+            }
+        }
+    }
+
+    /// Points the code at the peer's new ID, e.g. once it proved who it is.
+    pub fn reassign(&mut self, code: &str, peer_id: &str) {
+        if let Some(assigned) = self.codes.get_mut(code) {
+            *assigned = peer_id.to_owned();
+        }
+    }
+
+    pub fn release(&mut self, code: &str, now: Instant) {
+        if self.codes.remove(code).is_some() {
+            self.released.insert(code.to_owned(), now);
+        }
+    }
+
+    /// The peer ID the code belongs to. Codes are matched regardless of case.
+    pub fn resolve(&self, code: &str) -> Option<&str> {
+        self.codes.get(&code.to_ascii_uppercase()).map(String::as_str)
+    }
+
+    fn random_code() -> String {
+        // A v4 UUID is 122 random bits, more than a code needs.
+        uuid::Uuid::new_v4().as_bytes()[..Self::LEN]
+            .iter()
+            .map(|byte| Self::ALPHABET[(byte % 32) as usize] as char)
+            .collect()
+    }
+}
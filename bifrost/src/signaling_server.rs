@@ -1,24 +1,52 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Instant};
 
 use axum::{
     Router,
+    body::Bytes,
     extract::{
         State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
     response::IntoResponse,
     routing::get,
+    serve::ListenerExt,
+};
+use fjarsyn_shared::{
+    AuthenticatePayload, IdentityPayload, MAX_MESSAGE_SIZE, PROTOCOL_VERSION, SignalingMessage,
+    SignalingType, decode_binary_frame, encode_binary_frame,
 };
-use fjarsyn_shared::{SignalingMessage, SignalingType};
 use futures::{sink::SinkExt, stream::StreamExt};
 use tokio::{
     net::{TcpListener, ToSocketAddrs},
-    sync::{RwLock, mpsc},
+    sync::{
+        RwLock,
+        mpsc::{self, error::TrySendError},
+    },
 };
 
-#[derive(Debug)]
+use crate::short_codes::ShortCodes;
+
+/// A message on its way to a peer.
+#[derive(Debug, Clone)]
+enum Outgoing {
+    Signaling(SignalingMessage),
+    /// A binary frame, already addressed from the sender.
+    Binary(Bytes),
+}
+
+#[derive(Debug, Default)]
 struct SignalingState {
-    peers: HashMap<String, mpsc::Sender<SignalingMessage>>,
+    peers: HashMap<String, mpsc::Sender<Outgoing>>,
+    short_codes: ShortCodes,
+}
+
+impl SignalingState {
+    /// The ID and channel of the peer a message is addressed to, by its ID or its short code.
+    fn resolve(&self, to: &str) -> Option<(String, mpsc::Sender<Outgoing>)> {
+        let peer_id =
+            if self.peers.contains_key(to) { to } else { self.short_codes.resolve(to)? };
+        Some((peer_id.to_owned(), self.peers.get(peer_id)?.clone()))
+    }
 }
 
 #[derive(Debug)]
@@ -26,17 +54,33 @@ pub struct SignalingServer {
     state: Arc<RwLock<SignalingState>>,
 }
 
+impl Default for SignalingServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SignalingServer {
     pub fn new() -> Self {
-        Self { state: Arc::new(RwLock::new(SignalingState { peers: HashMap::new() })) }
+        Self { state: Arc::new(RwLock::new(SignalingState::default())) }
     }
 
     pub async fn listen(&self, listen_addr: impl ToSocketAddrs + std::fmt::Debug) {
-        let router =
-            Router::new().route("/ws", get(Self::ws_handler)).with_state(self.state.clone());
         tracing::info!("Signaling server listening on {:?}", listen_addr);
-
         let listener = TcpListener::bind(listen_addr).await.unwrap();
+        self.serve(listener).await;
+    }
+
+    /// Serves on a listener that is already bound, e.g. to an ephemeral port.
+    pub async fn serve(&self, listener: TcpListener) {
+        let router =
+            Router::new().route("/ws", get(Self::ws_handler)).with_state(self.state.clone());
+        // Signaling is many small messages, e.g. trickled candidates, which Nagle's algorithm would hold back.
+        let listener = listener.tap_io(|stream| {
+            if let Err(e) = stream.set_nodelay(true) {
+                tracing::debug!("Failed to set TCP_NODELAY: {}", e);
+            }
+        });
         axum::serve(listener, router).await.unwrap();
     }
 
@@ -44,12 +88,16 @@ impl SignalingServer {
         ws: WebSocketUpgrade,
         State(state): State<Arc<RwLock<SignalingState>>>,
     ) -> impl IntoResponse {
-        ws.on_upgrade(|socket| Self::handle_socket(socket, state))
+        // Larger messages close the connection, whether they are text or binary.
+        ws.max_message_size(MAX_MESSAGE_SIZE)
+            .on_upgrade(|socket| Self::handle_socket(socket, state))
     }
 
     async fn handle_socket(socket: WebSocket, state: Arc<RwLock<SignalingState>>) {
-        // For simplicity, we'll use a random string to identify peers
-        let peer_id = uuid::Uuid::new_v4().to_string();
+        // A random ID, until the peer proves it holds the key of another.
+        let mut peer_id = uuid::Uuid::new_v4().to_string();
+        // Clients without a key ignore it, and keep the random ID.
+        let mut nonce = Some(uuid::Uuid::new_v4().simple().to_string());
         tracing::info!("New WebSocket connection with ID: {}", peer_id);
 
         // Split the socket into a sender and receiver.
@@ -59,63 +107,106 @@ impl SignalingServer {
         const PEER_MSG_BUF: usize = 100;
         let (tx, mut rx) = mpsc::channel(PEER_MSG_BUF);
 
-        {
+        let short_code = {
             let mut state = state.write().await;
             // Add the peer to the state
             state.peers.insert(peer_id.clone(), tx.clone());
-        }
+            state.short_codes.assign(&peer_id, Instant::now())
+        };
+        tracing::info!("Assigned short code {} to {}", short_code, peer_id);
 
         // Send the identity message to the client
-        let identity_msg = SignalingMessage {
-            to: peer_id.clone(),
-            from: "server".to_owned(),
-            sig_type: SignalingType::Identity,
-            data: peer_id.clone(),
+        let identity = IdentityPayload {
+            uuid: peer_id.clone(),
+            short_code: Some(short_code.clone()),
+            nonce: nonce.clone(),
         };
-        if let Err(e) = tx.send(identity_msg).await {
+        let identity = Self::server_message(
+            &peer_id,
+            SignalingType::Identity,
+            serde_json::to_string(&identity).unwrap(),
+        );
+        if let Err(e) = tx.send(identity).await {
             tracing::error!("Failed to send identity message: {}", e);
         }
 
         // This task will listen for messages on the channel and send them to the client
         tokio::spawn(async move {
             while let Some(msg) = rx.recv().await {
-                match serde_json::to_string(&msg) {
-                    Ok(json) => {
-                        if sender.send(Message::Text(json.into())).await.is_err() {
-                            break;
+                let message = match msg {
+                    Outgoing::Signaling(msg) => match serde_json::to_string(&msg) {
+                        Ok(json) => Message::Text(json.into()),
+                        Err(e) => {
+                            tracing::error!("Failed to serialize signaling message: {}", e);
+                            continue;
                         }
-                    }
-                    Err(e) => tracing::error!("Failed to serialize signaling message: {}", e),
+                    },
+                    Outgoing::Binary(frame) => Message::Binary(frame),
+                };
+                if sender.send(message).await.is_err() {
+                    break;
                 }
             }
         });
 
         // This loop will listen for messages from the client
         while let Some(Ok(msg)) = receiver.next().await {
-            let Message::Text(text) = msg else {
-                continue;
+            let text = match msg {
+                Message::Text(text) => text,
+                Message::Binary(frame) => {
+                    Self::relay_binary(&state, &peer_id, &frame).await;
+                    continue;
+                }
+                _ => continue,
             };
+            // Types the server doesn't know parse as Unknown, and are relayed as they are for peers that do.
             match serde_json::from_str::<SignalingMessage>(&text) {
+                Ok(sig_msg) if sig_msg.sig_type == SignalingType::Authenticate => {
+                    // Only once, with the nonce of the identity it was sent.
+                    let Some(nonce) = nonce.take() else {
+                        tracing::warn!("Ignoring repeated authentication from {}", peer_id);
+                        continue;
+                    };
+                    match Self::authenticate(&state, &peer_id, &short_code, &nonce, &sig_msg.data)
+                        .await
+                    {
+                        Ok(derived_id) => {
+                            tracing::info!("Peer {} authenticated as {}", peer_id, derived_id);
+                            peer_id = derived_id;
+                            let identity = IdentityPayload {
+                                uuid: peer_id.clone(),
+                                short_code: Some(short_code.clone()),
+                                nonce: None,
+                            };
+                            let identity = Self::server_message(
+                                &peer_id,
+                                SignalingType::Identity,
+                                serde_json::to_string(&identity).unwrap(),
+                            );
+                            let _ = tx.send(identity).await;
+                        }
+                        Err(reason) => {
+                            tracing::warn!("Rejected authentication from {}: {}", peer_id, reason);
+                            let rejected =
+                                Self::server_message(&peer_id, SignalingType::Rejected, reason);
+                            let _ = tx.send(rejected).await;
+                            break;
+                        }
+                    }
+                }
                 Ok(mut sig_msg) => {
                     // Overwrite the 'from' field with the actual peer ID to ensure authenticity
                     sig_msg.from = peer_id.clone();
 
-                    let peers = {
-                        let state = state.read().await;
-                        state.peers.clone()
-                    };
-
                     if sig_msg.to.is_empty() {
-                        // Broadcast to all other peers
-                        for (id, tx) in peers {
-                            if id != peer_id {
-                                let _ = tx.send(sig_msg.clone()).await;
-                            }
-                        }
+                        Self::broadcast(&state, &peer_id, sig_msg).await;
                     } else {
                         // Send to specific peer
-                        if let Some(tx) = peers.get(&sig_msg.to) {
-                            let _ = tx.send(sig_msg).await;
+                        let target = state.read().await.resolve(&sig_msg.to);
+                        if let Some((target_id, tx)) = target {
+                            // The receiver only knows itself by its ID.
+                            sig_msg.to = target_id;
+                            let _ = tx.send(Outgoing::Signaling(sig_msg)).await;
                         } else {
                             tracing::warn!("Target peer {} not found", sig_msg.to);
                         }
@@ -130,6 +221,87 @@ impl SignalingServer {
         {
             let mut state = state.write().await;
             state.peers.remove(&peer_id);
+            // Held back from reuse for a while, so late calls to it don't reach someone else.
+            state.short_codes.release(&short_code, Instant::now());
         }
     }
+
+    // Moves the peer to the ID derived from its key, if it signed the nonce with it and the ID is free.
+    async fn authenticate(
+        state: &RwLock<SignalingState>,
+        peer_id: &str,
+        short_code: &str,
+        nonce: &str,
+        data: &str,
+    ) -> Result<String, String> {
+        let payload = serde_json::from_str::<AuthenticatePayload>(data)
+            .map_err(|e| format!("Malformed authentication: {}", e))?;
+        let derived_id = payload.verify(nonce).ok_or("Invalid signature")?;
+
+        let mut state = state.write().await;
+        // Someone else can't have the key, so that is the same client connected twice.
+        if state.peers.contains_key(&derived_id) {
+            return Err(format!("{} is already connected", derived_id));
+        }
+        let Some(tx) = state.peers.remove(peer_id) else {
+            return Err("Not connected".to_owned());
+        };
+        state.peers.insert(derived_id.clone(), tx);
+        state.short_codes.reassign(short_code, &derived_id);
+        Ok(derived_id)
+    }
+
+    /// Sends the message to every peer but the sender, without copying the peers.
+    /// The peers whose channel is full are waited for once the lock is released, so a slow one doesn't hold up the
+    /// peers connecting or leaving in the meantime.
+    async fn broadcast(state: &RwLock<SignalingState>, from: &str, message: SignalingMessage) {
+        let mut full = Vec::new();
+        {
+            let state = state.read().await;
+            for (id, tx) in &state.peers {
+                if id == from {
+                    continue;
+                }
+                // A closed channel is a peer that is leaving.
+                if let Err(TrySendError::Full(outgoing)) =
+                    tx.try_send(Outgoing::Signaling(message.clone()))
+                {
+                    full.push((tx.clone(), outgoing));
+                }
+            }
+        }
+        for (tx, outgoing) in full {
+            let _ = tx.send(outgoing).await;
+        }
+    }
+
+    fn server_message(to: &str, sig_type: SignalingType, data: String) -> Outgoing {
+        Outgoing::Signaling(SignalingMessage {
+            to: to.to_owned(),
+            from: "server".to_owned(),
+            sig_type,
+            data,
+            protocol_version: PROTOCOL_VERSION,
+            sealed: false,
+            mode: None,
+        })
+    }
+
+    /// Relays a binary frame to the peer in its header, with the header rewritten to the sender.
+    /// The payload is passed on without being looked at.
+    async fn relay_binary(state: &RwLock<SignalingState>, peer_id: &str, frame: &[u8]) {
+        let Some((to, payload)) = decode_binary_frame(frame) else {
+            tracing::warn!("Dropping malformed binary message from {}", peer_id);
+            return;
+        };
+        let Some((_, tx)) = state.read().await.resolve(to) else {
+            tracing::warn!("Target peer {} not found", to);
+            return;
+        };
+        // Peer IDs are UUIDs, which always fit the header.
+        let Some(relayed) = encode_binary_frame(peer_id, payload) else {
+            return;
+        };
+        let _ = tx.send(Outgoing::Binary(relayed.into())).await;
+    }
 }
@@ -0,0 +1,471 @@
+//! Routing between peers connected to a server on a local port.
+
+use std::time::Duration;
+
+use bifrost::{ShortCodes, SignalingServer};
+use ed25519_dalek::{Signer, SigningKey};
+use fjarsyn_shared::{
+    AuthenticatePayload, IdentityPayload, MAX_MESSAGE_SIZE, PROTOCOL_VERSION, SignalingMessage,
+    SignalingType, auth_message, decode_binary_frame, encode_binary_frame, peer_id_from_public_key,
+};
+use futures::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, tungstenite::Message};
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { SignalingServer::new().serve(listener).await });
+    format!("ws://{}/ws", addr)
+}
+
+struct Peer {
+    id: String,
+    short_code: String,
+    nonce: Option<String>,
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl Peer {
+    // Waits for the identity, after which the server routes messages to the peer.
+    async fn connect(url: &str) -> Self {
+        let (ws, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let mut peer = Self { id: String::new(), short_code: String::new(), nonce: None, ws };
+        let identity = peer.recv().await;
+        assert_eq!(identity.sig_type, SignalingType::Identity);
+        assert_eq!(identity.from, "server");
+        let identity = IdentityPayload::parse(&identity.data);
+        peer.id = identity.uuid;
+        peer.short_code = identity.short_code.expect("No short code assigned");
+        peer.nonce = identity.nonce;
+        peer
+    }
+
+    async fn send_raw(&mut self, text: &str) {
+        self.ws.send(Message::text(text)).await.unwrap();
+    }
+
+    async fn send(&mut self, to: &str, sig_type: SignalingType, data: &str) {
+        let msg = SignalingMessage {
+            to: to.to_owned(),
+            from: self.id.clone(),
+            sig_type,
+            data: data.to_owned(),
+            protocol_version: PROTOCOL_VERSION,
+            sealed: false,
+            mode: None,
+        };
+        self.send_raw(&serde_json::to_string(&msg).unwrap()).await;
+    }
+
+    // Signs the nonce, returning what the server answers.
+    async fn authenticate(&mut self, key: &SigningKey, nonce: &str) -> SignalingMessage {
+        let signature = key.sign(&auth_message(nonce));
+        let payload =
+            AuthenticatePayload::new(&key.verifying_key().to_bytes(), &signature.to_bytes());
+        self.send("", SignalingType::Authenticate, &serde_json::to_string(&payload).unwrap()).await;
+        self.recv().await
+    }
+
+    async fn send_binary(&mut self, to: &str, payload: &[u8]) {
+        let frame = encode_binary_frame(to, payload).unwrap();
+        self.ws.send(Message::binary(frame)).await.unwrap();
+    }
+
+    async fn recv_message(&mut self) -> Message {
+        tokio::time::timeout(RECV_TIMEOUT, self.ws.next())
+            .await
+            .expect("Timed out waiting for a message")
+            .expect("Connection closed")
+            .unwrap()
+    }
+
+    async fn recv(&mut self) -> SignalingMessage {
+        match self.recv_message().await {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            other => panic!("Expected a text message, got {:?}", other),
+        }
+    }
+
+    // The sender and the payload.
+    async fn recv_binary(&mut self) -> (String, Vec<u8>) {
+        match self.recv_message().await {
+            Message::Binary(frame) => {
+                let (from, payload) = decode_binary_frame(&frame).unwrap();
+                (from.to_owned(), payload.to_vec())
+            }
+            other => panic!("Expected a binary message, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn delivers_to_target() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    a.send(&b.id, SignalingType::Offer, "sdp").await;
+
+    let msg = b.recv().await;
+    assert_eq!(msg.sig_type, SignalingType::Offer);
+    assert_eq!(msg.from, a.id);
+    assert_eq!(msg.to, b.id);
+    assert_eq!(msg.data, "sdp");
+}
+
+#[tokio::test]
+async fn broadcasts_on_empty_target() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+    let mut c = Peer::connect(&url).await;
+
+    a.send("", SignalingType::Bye, "").await;
+
+    for peer in [&mut b, &mut c] {
+        let msg = peer.recv().await;
+        assert_eq!(msg.sig_type, SignalingType::Bye);
+        assert_eq!(msg.from, a.id);
+    }
+}
+
+// Each connection's messages are routed in order, so the message after a broadcast
+// is the next one the sender gets if the broadcast skipped it.
+#[tokio::test]
+async fn broadcast_excludes_sender() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let _b = Peer::connect(&url).await;
+
+    a.send("", SignalingType::Bye, "broadcast").await;
+    let own_id = a.id.clone();
+    a.send(&own_id, SignalingType::Candidate, "marker").await;
+
+    assert_eq!(a.recv().await.data, "marker");
+}
+
+#[tokio::test]
+async fn drops_unknown_target_and_keeps_connection() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    a.send("nobody", SignalingType::Offer, "lost").await;
+    a.send(&b.id, SignalingType::Offer, "delivered").await;
+
+    assert_eq!(b.recv().await.data, "delivered");
+}
+
+#[tokio::test]
+async fn overwrites_spoofed_sender() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    let spoofed = SignalingMessage {
+        to: b.id.clone(),
+        from: "server".to_owned(),
+        sig_type: SignalingType::Identity,
+        data: "not-b".to_owned(),
+        protocol_version: PROTOCOL_VERSION,
+        sealed: false,
+        mode: None,
+    };
+    a.send_raw(&serde_json::to_string(&spoofed).unwrap()).await;
+
+    assert_eq!(b.recv().await.from, a.id);
+}
+
+// A newer client may send types this server doesn't know, which are relayed for peers that do.
+#[tokio::test]
+async fn relays_unknown_type_untouched() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    a.send_raw(&format!(r#"{{"to":"{}","from":"","sig_type":"Renegotiate","data":"x"}}"#, b.id))
+        .await;
+
+    let msg = b.recv().await;
+    assert_eq!(msg.sig_type, SignalingType::Unknown("Renegotiate".to_owned()));
+    assert_eq!(msg.from, a.id);
+    assert_eq!(msg.data, "x");
+}
+
+// The public keys of the peers, which the server passes on like any other message.
+#[tokio::test]
+async fn relays_key_exchange() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    a.send(&b.id, SignalingType::KeyExchange, "cHVibGlj").await;
+
+    let msg = b.recv().await;
+    assert_eq!(msg.sig_type, SignalingType::KeyExchange);
+    assert_eq!(msg.from, a.id);
+    assert_eq!(msg.data, "cHVibGlj");
+}
+
+// Builds before the protocol version still get through to newer peers.
+#[tokio::test]
+async fn relays_messages_without_version() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    a.send_raw(&format!(r#"{{"to":"{}","from":"","sig_type":"Offer","data":"sdp"}}"#, b.id)).await;
+
+    let msg = b.recv().await;
+    assert_eq!(msg.sig_type, SignalingType::Offer);
+    assert_eq!(msg.protocol_version, 0);
+}
+
+#[tokio::test]
+async fn exchanges_binary_blobs() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    let blob: Vec<u8> = (0..=255).collect();
+    a.send_binary(&b.id, &blob).await;
+    assert_eq!(b.recv_binary().await, (a.id.clone(), blob.clone()));
+
+    b.send_binary(&a.id, &[]).await;
+    assert_eq!(a.recv_binary().await, (b.id.clone(), Vec::new()));
+}
+
+// The header names the target on the way in, and is rewritten to the real sender on the way out.
+#[tokio::test]
+async fn binary_header_is_rewritten_to_sender() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    a.send_binary(&b.id, b"payload").await;
+
+    let (from, payload) = b.recv_binary().await;
+    assert_eq!(from, a.id);
+    assert_eq!(payload, b"payload");
+}
+
+#[tokio::test]
+async fn drops_malformed_binary_and_keeps_connection() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    // Claims a longer ID than the frame holds.
+    a.ws.send(Message::binary(vec![200, b'x'])).await.unwrap();
+    a.send_binary("nobody", b"lost").await;
+    a.send_binary(&b.id, b"delivered").await;
+
+    assert_eq!(b.recv_binary().await.1, b"delivered");
+}
+
+#[tokio::test]
+async fn closes_connection_on_oversized_message() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let b = Peer::connect(&url).await;
+
+    a.send_binary(&b.id, &vec![0; MAX_MESSAGE_SIZE]).await;
+
+    let closed = tokio::time::timeout(RECV_TIMEOUT, async {
+        loop {
+            match a.ws.next().await {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => break,
+                Some(Ok(_)) => continue,
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "Connection stayed open");
+}
+
+#[tokio::test]
+async fn assigns_short_codes() {
+    let url = start_server().await;
+    let a = Peer::connect(&url).await;
+    let b = Peer::connect(&url).await;
+
+    assert_eq!(a.short_code.len(), ShortCodes::LEN);
+    assert!(a.short_code.chars().all(|c| c.is_ascii_alphanumeric()));
+    assert_ne!(a.short_code, b.short_code);
+}
+
+#[tokio::test]
+async fn delivers_by_short_code() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    a.send(&b.short_code.to_ascii_lowercase(), SignalingType::Offer, "sdp").await;
+
+    let msg = b.recv().await;
+    assert_eq!(msg.data, "sdp");
+    // Rewritten to the ID, which is all the receiver knows itself by.
+    assert_eq!(msg.to, b.id);
+
+    b.send_binary(&a.short_code, b"blob").await;
+    assert_eq!(a.recv_binary().await, (b.id.clone(), b"blob".to_vec()));
+}
+
+#[tokio::test]
+async fn short_code_stops_routing_after_disconnect() {
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let b = Peer::connect(&url).await;
+    let old_code = b.short_code.clone();
+    drop(b);
+
+    // The next peer can't get the code that was just released.
+    let mut c = Peer::connect(&url).await;
+    assert_ne!(c.short_code, old_code);
+
+    a.send(&old_code, SignalingType::Offer, "stale").await;
+    let own_id = a.id.clone();
+    a.send(&own_id, SignalingType::Candidate, "marker").await;
+    assert_eq!(a.recv().await.data, "marker");
+
+    // The message to the old code didn't reach the peer that connected after.
+    let own_id = c.id.clone();
+    c.send(&own_id, SignalingType::Candidate, "own").await;
+    assert_eq!(c.recv().await.data, "own");
+}
+
+#[tokio::test]
+async fn authenticated_peer_gets_the_id_of_its_key() {
+    let url = start_server().await;
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    let nonce = a.nonce.clone().expect("No nonce sent");
+    let identity = a.authenticate(&key, &nonce).await;
+    assert_eq!(identity.sig_type, SignalingType::Identity);
+    let identity = IdentityPayload::parse(&identity.data);
+    assert_eq!(identity.uuid, peer_id_from_public_key(&key.verifying_key().to_bytes()));
+    assert_eq!(identity.short_code.as_deref(), Some(a.short_code.as_str()));
+    assert_eq!(identity.nonce, None);
+
+    // The random ID is gone, and the new one and the code reach the peer.
+    b.send(&a.id, SignalingType::Offer, "lost").await;
+    b.send(&identity.uuid, SignalingType::Offer, "by id").await;
+    b.send(&a.short_code, SignalingType::Offer, "by code").await;
+    assert_eq!(a.recv().await.data, "by id");
+    let msg = a.recv().await;
+    assert_eq!(msg.data, "by code");
+    assert_eq!(msg.to, identity.uuid);
+
+    a.send(&b.id, SignalingType::Answer, "sdp").await;
+    assert_eq!(b.recv().await.from, identity.uuid);
+}
+
+#[tokio::test]
+async fn keeps_the_id_across_connections() {
+    let url = start_server().await;
+    let key = SigningKey::from_bytes(&[1; 32]);
+
+    let mut first = Peer::connect(&url).await;
+    let nonce = first.nonce.clone().unwrap();
+    let first_id = IdentityPayload::parse(&first.authenticate(&key, &nonce).await.data).uuid;
+    drop(first);
+
+    // The server may not have noticed the first connection closing yet.
+    let second_id = loop {
+        let mut second = Peer::connect(&url).await;
+        let nonce = second.nonce.clone().unwrap();
+        let reply = second.authenticate(&key, &nonce).await;
+        if reply.sig_type == SignalingType::Identity {
+            break IdentityPayload::parse(&reply.data).uuid;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    assert_eq!(first_id, second_id);
+}
+
+#[tokio::test]
+async fn rejects_a_signature_of_another_nonce() {
+    let url = start_server().await;
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let mut a = Peer::connect(&url).await;
+
+    let reply = a.authenticate(&key, "not the nonce").await;
+    assert_eq!(reply.sig_type, SignalingType::Rejected);
+    assert_eq!(reply.from, "server");
+    let next =
+        tokio::time::timeout(RECV_TIMEOUT, a.ws.next()).await.expect("Connection stayed open");
+    assert!(next.is_none_or(|message| !matches!(message, Ok(Message::Text(_)))));
+}
+
+#[tokio::test]
+async fn rejects_a_second_connection_with_the_same_key() {
+    let url = start_server().await;
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let mut a = Peer::connect(&url).await;
+    let mut b = Peer::connect(&url).await;
+
+    let nonce = a.nonce.clone().unwrap();
+    assert_eq!(a.authenticate(&key, &nonce).await.sig_type, SignalingType::Identity);
+    let nonce = b.nonce.clone().unwrap();
+    assert_eq!(b.authenticate(&key, &nonce).await.sig_type, SignalingType::Rejected);
+}
+
+// A peer that doesn't read fills its channel, which broadcasts then wait on. Peers keep connecting, calling and
+// leaving in the meantime, and the others get every broadcast once the stuck peer is gone.
+#[tokio::test]
+async fn peers_come_and_go_while_a_broadcast_waits() {
+    const BROADCASTS: usize = 1024;
+    let url = start_server().await;
+    let mut a = Peer::connect(&url).await;
+    let mut witness = Peer::connect(&url).await;
+    let stuck = Peer::connect(&url).await;
+    let leaving = Peer::connect(&url).await;
+
+    // Far more than the socket buffers and the channel of the stuck peer hold.
+    let data = "x".repeat(64 * 1024);
+    let witness_id = witness.id.clone();
+    let sending = tokio::spawn(async move {
+        for _ in 0..BROADCASTS {
+            a.send("", SignalingType::Candidate, &data).await;
+        }
+        a.send(&witness_id, SignalingType::Bye, "done").await;
+    });
+
+    // Once the broadcasts stop coming, the server is waiting on the stuck peer.
+    let mut received = 0;
+    while let Ok(next) = tokio::time::timeout(Duration::from_millis(200), witness.ws.next()).await {
+        assert!(matches!(next, Some(Ok(Message::Text(_)))));
+        received += 1;
+    }
+    assert!(received < BROADCASTS, "The stuck peer took every broadcast");
+
+    drop(leaving);
+    let mut b = Peer::connect(&url).await;
+    let mut c = Peer::connect(&url).await;
+    b.send(&c.id, SignalingType::Offer, "sdp").await;
+    // The broadcasts the leaving peer held up may carry on to the new ones.
+    let offer = loop {
+        let msg = c.recv().await;
+        if msg.sig_type == SignalingType::Offer {
+            break msg;
+        }
+    };
+    assert_eq!(offer.from, b.id);
+
+    // Either would be stuck too, as they don't read.
+    drop((b, c, stuck));
+    loop {
+        let msg = witness.recv().await;
+        if msg.sig_type == SignalingType::Bye {
+            break;
+        }
+        received += 1;
+    }
+    assert_eq!(received, BROADCASTS);
+    sending.await.unwrap();
+}
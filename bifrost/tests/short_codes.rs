@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use bifrost::ShortCodes;
+
+// Hands out the given codes in order.
+fn codes(list: &[&str]) -> impl FnMut() -> String {
+    let mut list = list.iter().map(|code| code.to_string()).collect::<Vec<_>>().into_iter();
+    move || list.next().expect("Ran out of codes")
+}
+
+#[test]
+fn skips_codes_in_use() {
+    let mut short_codes = ShortCodes::default();
+    let now = Instant::now();
+
+    assert_eq!(short_codes.assign_with("a", now, codes(&["AAAAAA"])), "AAAAAA");
+    assert_eq!(short_codes.assign_with("b", now, codes(&["AAAAAA", "BBBBBB"])), "BBBBBB");
+    assert_eq!(short_codes.resolve("AAAAAA"), Some("a"));
+    assert_eq!(short_codes.resolve("BBBBBB"), Some("b"));
+}
+
+#[test]
+fn resolves_regardless_of_case() {
+    let mut short_codes = ShortCodes::default();
+    short_codes.assign_with("a", Instant::now(), codes(&["AB12CD"]));
+
+    assert_eq!(short_codes.resolve("ab12cd"), Some("a"));
+}
+
+#[test]
+fn reassigned_code_resolves_to_the_new_id() {
+    let mut short_codes = ShortCodes::default();
+    short_codes.assign_with("a", Instant::now(), codes(&["AAAAAA"]));
+
+    short_codes.reassign("AAAAAA", "b");
+    short_codes.reassign("BBBBBB", "c");
+
+    assert_eq!(short_codes.resolve("AAAAAA"), Some("b"));
+    assert_eq!(short_codes.resolve("BBBBBB"), None);
+}
+
+#[test]
+fn released_code_stops_resolving() {
+    let mut short_codes = ShortCodes::default();
+    let now = Instant::now();
+    short_codes.assign_with("a", now, codes(&["AAAAAA"]));
+
+    short_codes.release("AAAAAA", now);
+
+    assert_eq!(short_codes.resolve("AAAAAA"), None);
+}
+
+#[test]
+fn released_code_is_not_reused_within_delay() {
+    let mut short_codes = ShortCodes::default();
+    let now = Instant::now();
+    short_codes.assign_with("a", now, codes(&["AAAAAA"]));
+    short_codes.release("AAAAAA", now);
+
+    let later = now + ShortCodes::REUSE_DELAY - Duration::from_secs(1);
+    assert_eq!(short_codes.assign_with("b", later, codes(&["AAAAAA", "BBBBBB"])), "BBBBBB");
+    assert_eq!(short_codes.resolve("AAAAAA"), None);
+}
+
+#[test]
+fn released_code_is_reused_after_delay() {
+    let mut short_codes = ShortCodes::default();
+    let now = Instant::now();
+    short_codes.assign_with("a", now, codes(&["AAAAAA"]));
+    short_codes.release("AAAAAA", now);
+
+    let later = now + ShortCodes::REUSE_DELAY;
+    assert_eq!(short_codes.assign_with("b", later, codes(&["AAAAAA"])), "AAAAAA");
+    assert_eq!(short_codes.resolve("AAAAAA"), Some("b"));
+}
+
+#[test]
+fn random_codes_are_short_and_distinct() {
+    let mut short_codes = ShortCodes::default();
+    let now = Instant::now();
+    let a = short_codes.assign("a", now);
+    let b = short_codes.assign("b", now);
+
+    assert_eq!(a.len(), ShortCodes::LEN);
+    assert!(a.chars().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase()));
+    assert_ne!(a, b);
+}
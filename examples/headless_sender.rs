@@ -0,0 +1,105 @@
+//! Calls a peer and shares a synthetic capture with it, without the UI.
+//!
+//! Start a signaling server with `cargo run -p bifrost`, open the app on the receiving side, and run:
+//!
+//! ```sh
+//! cargo run --example headless_sender -- [--screen] <peer code or ID> [signaling URL]
+//! ```
+//!
+//! With `--screen`, the primary monitor is shared instead.
+
+use std::sync::Arc;
+
+use fjarsyn::{
+    capture_providers::{
+        CaptureProvider, CaptureProviderExt, PlatformCaptureProvider,
+        create_platform_capture_provider,
+    },
+    config::Config,
+    media::encoder_worker::EncoderConfig,
+    networking::webrtc::WebRTCEvent,
+    session::{CallOptions, CallSession, CaptureSession},
+    utils::{
+        metrics::Metrics,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+use futures::StreamExt;
+use tokio::sync::RwLock;
+
+const FRAME_SIZE: Vector2<i32> = Vector2 { x: 1280, y: 720 };
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+
+    let (screen, args): (Vec<_>, Vec<_>) =
+        std::env::args().skip(1).partition(|arg| arg == "--screen");
+    let screen = !screen.is_empty();
+    let mut args = args.into_iter();
+    let Some(peer) = args.next() else {
+        eprintln!("Usage: headless_sender [--screen] <peer code or ID> [signaling URL]");
+        std::process::exit(2);
+    };
+    let config = Config::default();
+    let signaling_url = args.next().unwrap_or(config.server_url.clone());
+
+    let call =
+        CallSession::connect(signaling_url, &peer, CallOptions::from_config(&config)).await?;
+    tracing::info!(
+        "Calling {} as {}",
+        peer,
+        call.webrtc().get_local_short_code().or(call.webrtc().get_local_id()).unwrap_or_default()
+    );
+    if !call.wait_connected().await {
+        tracing::error!("The call ended before it connected");
+        call.close().await?;
+        return Ok(());
+    }
+
+    let encoder_config = EncoderConfig::from_config(&config);
+    let capture = if screen {
+        // Creating the graphics device blocks.
+        let (pixel_format, session_options) =
+            (encoder_config.input_format, config.capture_session_options);
+        let capture = tokio::task::spawn_blocking(move || {
+            create_platform_capture_provider(pixel_format, session_options, Metrics::default())
+        })
+        .await??;
+        let capture = Arc::new(RwLock::new(capture));
+        capture.write().await.start_capture()?;
+        Some(capture)
+    } else {
+        None
+    };
+    let frames = match &capture {
+        Some(capture) => PlatformCaptureProvider::stream(capture, config.framerate).boxed(),
+        None => {
+            SyntheticFrames::new(FRAME_SIZE, encoder_config.input_format, FramePattern::Gradient)
+                .into_stream(encoder_config.target_fps_hz)
+                .boxed()
+        }
+    };
+    let session = CaptureSession::start(frames, encoder_config, call.webrtc().clone())?;
+    tracing::info!("Sharing, press Ctrl+C to hang up");
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = async {
+            while let Some(event) = call.next_event().await {
+                if let WebRTCEvent::Disconnected = event {
+                    tracing::info!("The peer hung up");
+                    break;
+                }
+            }
+        } => (),
+    }
+
+    session.stop().await?;
+    if let Some(capture) = capture {
+        capture.write().await.stop_capture()?;
+    }
+    call.close().await?;
+    Ok(())
+}
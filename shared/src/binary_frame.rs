@@ -0,0 +1,22 @@
+/// The largest message the server relays, text or binary.
+pub const MAX_MESSAGE_SIZE: usize = 256 * 1024;
+
+/// Builds a binary signaling frame: a byte with the length of the peer ID, the ID, and then the opaque payload.
+/// The ID is the target when sent to the server, and the sender when relayed by it.
+/// None if the ID doesn't fit in the length byte.
+pub fn encode_binary_frame(peer_id: &str, payload: &[u8]) -> Option<Vec<u8>> {
+    let id_len = u8::try_from(peer_id.len()).ok()?;
+    let mut frame = Vec::with_capacity(1 + peer_id.len() + payload.len());
+    frame.push(id_len);
+    frame.extend_from_slice(peer_id.as_bytes());
+    frame.extend_from_slice(payload);
+    Some(frame)
+}
+
+/// Splits a binary signaling frame into the peer ID and the payload.
+/// None if the frame is too short for its ID, or the ID isn't UTF-8.
+pub fn decode_binary_frame(frame: &[u8]) -> Option<(&str, &[u8])> {
+    let (&id_len, rest) = frame.split_first()?;
+    let (peer_id, payload) = rest.split_at_checked(id_len as usize)?;
+    Some((std::str::from_utf8(peer_id).ok()?, payload))
+}
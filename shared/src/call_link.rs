@@ -0,0 +1,37 @@
+/// The scheme of the links that start a call, as in `fjarsyn://call/7FQ2-K9PL`.
+pub const CALL_LINK_SCHEME: &str = "fjarsyn";
+/// The longest link that is parsed at all. Links come from browsers and chats, so anything can be in them.
+pub const MAX_CALL_LINK_LEN: usize = 256;
+/// The longest peer ID or short code. IDs are shaped like UUIDs, and codes are shorter.
+pub const MAX_PEER_ID_LEN: usize = 64;
+
+/// The link that calls the peer, or None if the ID couldn't be parsed back out of it.
+pub fn call_link(peer_id: &str) -> Option<String> {
+    is_valid_peer_id(peer_id).then(|| format!("{}://call/{}", CALL_LINK_SCHEME, peer_id))
+}
+
+/// The peer ID or short code in a `fjarsyn://call/<id>` link.
+/// None if it is too long, isn't a call link, or the ID isn't one.
+pub fn parse_call_link(link: &str) -> Option<&str> {
+    let link = link.trim();
+    if link.len() > MAX_CALL_LINK_LEN {
+        return None;
+    }
+    let (scheme, rest) = link.split_once("://")?;
+    // Schemes are case insensitive, and some apps change the case of the links they open.
+    if !scheme.eq_ignore_ascii_case(CALL_LINK_SCHEME) {
+        return None;
+    }
+    let (kind, peer_id) = rest.split_once('/')?;
+    // Browsers add a slash at the end of links without a path.
+    let peer_id = peer_id.strip_suffix('/').unwrap_or(peer_id);
+    (kind.eq_ignore_ascii_case("call") && is_valid_peer_id(peer_id)).then_some(peer_id)
+}
+
+/// Whether this could be a peer ID or short code: letters, digits and dashes, and not too long.
+/// Leaves anything that would need escaping in a link or a line out.
+pub fn is_valid_peer_id(peer_id: &str) -> bool {
+    !peer_id.is_empty()
+        && peer_id.len() <= MAX_PEER_ID_LEN
+        && peer_id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// What one end of a call does. Chosen by each end for itself, when calling or before being called.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CallMode {
+    /// Shares its screen whenever it wants to, and views the peer's.
+    #[default]
+    ShareAndView,
+    /// Only views the peer's screen, so it never captures or encodes anything.
+    ViewOnly,
+}
+
+impl CallMode {
+    pub const ALL: [Self; 2] = [Self::ShareAndView, Self::ViewOnly];
+
+    pub fn can_share(self) -> bool {
+        self == Self::ShareAndView
+    }
+
+    /// The mode of the end that made the session description, from the direction of its video.
+    /// An end that doesn't send video only views. One without video at all is taken to share, as before modes.
+    pub fn from_sdp(sdp: &str) -> Self {
+        let mut in_video = false;
+        for line in sdp.lines() {
+            if let Some(media) = line.strip_prefix("m=") {
+                // Only the first video section counts, as the ones after are added when the video is replaced.
+                if in_video {
+                    break;
+                }
+                in_video = media.starts_with("video ");
+            } else if in_video && matches!(line.trim_end(), "a=recvonly" | "a=inactive") {
+                return Self::ViewOnly;
+            }
+        }
+        Self::ShareAndView
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+use crate::InputEvent;
+
+/// A cursor position, normalized to 0..1 within the shared capture item.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CursorPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Caps on what the presenter sends, asked for by the viewer, e.g. on a metered connection.
+/// The presenter sends the lower of these and its own settings. None leaves that setting up to the presenter.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QualityRequest {
+    /// In bits per second.
+    pub max_bitrate: Option<u32>,
+    pub max_fps: Option<u32>,
+    /// The longest side of the video, in pixels.
+    pub max_dimension: Option<u32>,
+}
+
+/// Whether a peer is recording the call, which the other is told about every time it starts or stops.
+/// Only as honest as the peer's build, as a modified one can record without sending it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RecordingState {
+    pub active: bool,
+}
+
+// Messages sent between peers over the control data channel.
+// Variant names are kept short, since cursor updates are sent many times a second.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ControlMessage {
+    /// The presenter's cursor. None when the cursor is outside the shared region.
+    #[serde(rename = "c")]
+    Cursor(Option<CursorPosition>),
+    /// Input from the viewer, only acted upon while remote control is granted.
+    #[serde(rename = "i")]
+    Input(InputEvent),
+    /// Sent by the presenter when remote control is granted or revoked, or when asking for it was declined.
+    #[serde(rename = "r")]
+    RemoteControl(bool),
+    /// Sent by the viewer to ask the presenter for remote control, which they have to agree to.
+    #[serde(rename = "rq")]
+    RemoteControlRequest,
+    /// Sent by the viewer when it can't decode the stream anymore, so the presenter sends a keyframe.
+    #[serde(rename = "k")]
+    KeyframeRequest,
+    /// Sent by the viewer to cap the quality of the stream, or with None to leave it up to the presenter again.
+    #[serde(rename = "q")]
+    QualityRequest(Option<QualityRequest>),
+    /// Sent by either peer when it starts or stops recording the call.
+    #[serde(rename = "rec")]
+    RecordingState(RecordingState),
+}
@@ -0,0 +1,58 @@
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// Signed along with the nonce, so the signature can't be passed off as one over anything else.
+const AUTH_CONTEXT: &[u8] = b"fjarsyn signaling authentication\0";
+
+/// What a client signs to prove it holds its key: the nonce the server sent with the identity.
+pub fn auth_message(nonce: &str) -> Vec<u8> {
+    [AUTH_CONTEXT, nonce.as_bytes()].concat()
+}
+
+/// The peer ID of a public key. Shaped like the UUIDs handed out to clients without a key,
+/// and a hash, so nobody can pick a key for an ID someone else has.
+pub fn peer_id_from_public_key(public_key: &[u8; 32]) -> String {
+    let hex = to_hex(&Sha256::digest(public_key)[..16]);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// The data of an Authenticate message, in hex.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatePayload {
+    /// An Ed25519 public key.
+    pub public_key: String,
+    /// The signature of [`auth_message`] for the nonce.
+    pub signature: String,
+}
+
+impl AuthenticatePayload {
+    pub fn new(public_key: &[u8; 32], signature: &[u8; 64]) -> Self {
+        Self { public_key: to_hex(public_key), signature: to_hex(signature) }
+    }
+
+    /// The peer ID derived from the public key, if the signature of the nonce is valid.
+    pub fn verify(&self, nonce: &str) -> Option<String> {
+        let public_key = from_hex(&self.public_key)?.try_into().ok()?;
+        let signature: [u8; 64] = from_hex(&self.signature)?.try_into().ok()?;
+        VerifyingKey::from_bytes(&public_key)
+            .ok()?
+            .verify_strict(&auth_message(nonce), &Signature::from_bytes(&signature))
+            .ok()?;
+        Some(peer_id_from_public_key(&public_key))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::CursorPosition;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+/// Input from a viewer controlling the presenter's machine.
+/// Positions are normalized to the shared capture item, like the cursor.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    MouseMove(CursorPosition),
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    /// Scroll amount in lines.
+    MouseScroll {
+        x: f32,
+        y: f32,
+    },
+}
@@ -1,18 +1,18 @@
-use serde::{Deserialize, Serialize};
+mod binary_frame;
+mod call_link;
+mod call_mode;
+mod control;
+mod identity;
+mod input;
+mod signaling;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum SignalingType {
-    Offer,
-    Answer,
-    Candidate,
-    Identity,
-}
-
-// Our signaling message format
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct SignalingMessage {
-    pub to: String,
-    pub from: String,
-    pub sig_type: SignalingType,
-    pub data: String,
-}
+pub use binary_frame::{MAX_MESSAGE_SIZE, decode_binary_frame, encode_binary_frame};
+pub use call_link::{
+    CALL_LINK_SCHEME, MAX_CALL_LINK_LEN, MAX_PEER_ID_LEN, call_link, is_valid_peer_id,
+    parse_call_link,
+};
+pub use call_mode::CallMode;
+pub use control::{ControlMessage, CursorPosition, QualityRequest, RecordingState};
+pub use identity::{AuthenticatePayload, auth_message, peer_id_from_public_key};
+pub use input::{InputEvent, MouseButton};
+pub use signaling::{IdentityPayload, PROTOCOL_VERSION, SignalingMessage, SignalingType};
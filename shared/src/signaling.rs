@@ -0,0 +1,112 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::CallMode;
+
+/// The version of the signaling protocol this build speaks.
+/// Messages from builds before the version was sent are version 0.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Serialized as its name, so types added later still parse on older builds, as `Unknown`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignalingType {
+    Offer,
+    Answer,
+    Candidate,
+    Identity,
+    /// The sender hung up, so the receiver doesn't have to wait for the connection to time out.
+    Bye,
+    /// Sent to the server, proving the client holds the key its ID is derived from by signing the nonce of the identity.
+    Authenticate,
+    /// The server turned down an Authenticate, with the reason as the data. It closes the connection after.
+    Rejected,
+    /// The sender's public key for sealing the signaling without a passphrase, in base64.
+    /// The receiver sends its own back, unless it is the reply to one it sent.
+    KeyExchange,
+    /// A type from a newer build. Relayed as is, and otherwise ignored.
+    Unknown(String),
+}
+
+impl SignalingType {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Offer => "Offer",
+            Self::Answer => "Answer",
+            Self::Candidate => "Candidate",
+            Self::Identity => "Identity",
+            Self::Bye => "Bye",
+            Self::Authenticate => "Authenticate",
+            Self::Rejected => "Rejected",
+            Self::KeyExchange => "KeyExchange",
+            Self::Unknown(name) => name,
+        }
+    }
+}
+
+impl From<String> for SignalingType {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "Offer" => Self::Offer,
+            "Answer" => Self::Answer,
+            "Candidate" => Self::Candidate,
+            "Identity" => Self::Identity,
+            "Bye" => Self::Bye,
+            "Authenticate" => Self::Authenticate,
+            "Rejected" => Self::Rejected,
+            "KeyExchange" => Self::KeyExchange,
+            _ => Self::Unknown(name),
+        }
+    }
+}
+
+impl Serialize for SignalingType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for SignalingType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+// Our signaling message format
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignalingMessage {
+    pub to: String,
+    pub from: String,
+    pub sig_type: SignalingType,
+    pub data: String,
+    /// The protocol version of the sender, so a receiver can tell what it may send back.
+    #[serde(default)]
+    pub protocol_version: u8,
+    /// Whether the data is encrypted with a key the peers share, from a passphrase or a key exchange, which the server doesn't know.
+    /// Left out when not, so nothing changes for builds that don't know it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sealed: bool,
+    /// What the sender does in the call, on offers and answers. Left out by builds before call modes,
+    /// in which case the direction of the video in the data tells.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<CallMode>,
+}
+
+/// The data of an Identity message: the ID the server assigned, and a short code that reaches the same peer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IdentityPayload {
+    pub uuid: String,
+    pub short_code: Option<String>,
+    /// For the client to sign, from servers that give clients with a key the ID derived from it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+impl IdentityPayload {
+    /// Servers before short codes sent the ID as is, which parses as an ID without a code.
+    pub fn parse(data: &str) -> Self {
+        serde_json::from_str(data).unwrap_or_else(|_| Self {
+            uuid: data.to_owned(),
+            short_code: None,
+            nonce: None,
+        })
+    }
+}
@@ -0,0 +1,27 @@
+use fjarsyn_shared::{decode_binary_frame, encode_binary_frame};
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn frame_round_trips(peer_id in ".{0,64}", payload in proptest::collection::vec(any::<u8>(), 0..1024)) {
+        prop_assume!(peer_id.len() <= u8::MAX as usize);
+        let frame = encode_binary_frame(&peer_id, &payload).unwrap();
+        prop_assert_eq!(decode_binary_frame(&frame), Some((peer_id.as_str(), payload.as_slice())));
+    }
+
+    #[test]
+    fn decoding_never_panics(frame in proptest::collection::vec(any::<u8>(), 0..512)) {
+        let _ = decode_binary_frame(&frame);
+    }
+}
+
+#[test]
+fn rejects_ids_longer_than_the_header_allows() {
+    assert!(encode_binary_frame(&"x".repeat(256), b"").is_none());
+}
+
+#[test]
+fn rejects_frames_shorter_than_their_id() {
+    assert_eq!(decode_binary_frame(&[]), None);
+    assert_eq!(decode_binary_frame(&[4, b'a', b'b']), None);
+}
@@ -0,0 +1,79 @@
+use fjarsyn_shared::{
+    MAX_CALL_LINK_LEN, MAX_PEER_ID_LEN, call_link, is_valid_peer_id, parse_call_link,
+};
+use proptest::prelude::*;
+
+#[test]
+fn parses_the_peer_out_of_a_link() {
+    assert_eq!(parse_call_link("fjarsyn://call/7FQ2-K9PL"), Some("7FQ2-K9PL"));
+    let id = "0f8e1c2a-3b4d-4e5f-8a9b-0c1d2e3f4a5b";
+    assert_eq!(parse_call_link(&format!("fjarsyn://call/{}", id)), Some(id));
+}
+
+#[test]
+fn takes_links_as_apps_hand_them_over() {
+    for link in
+        ["FJARSYN://Call/7FQ2-K9PL", "fjarsyn://call/7FQ2-K9PL/", "  fjarsyn://call/7FQ2-K9PL\n"]
+    {
+        assert_eq!(parse_call_link(link), Some("7FQ2-K9PL"), "{:?}", link);
+    }
+}
+
+#[test]
+fn rejects_malformed_links() {
+    for link in [
+        "",
+        "fjarsyn:",
+        "fjarsyn://",
+        "fjarsyn://call",
+        "fjarsyn://call/",
+        "fjarsyn://call//",
+        "fjarsyn:call/7FQ2-K9PL",
+        "fjarsyn://settings/7FQ2-K9PL",
+        "fjarsyn://call/7FQ2/K9PL",
+        "fjarsyn://call/7FQ2-K9PL?auto=1",
+        "fjarsyn://call/7FQ2%20K9PL",
+        "fjarsyn://call/7FQ2 K9PL",
+        "fjarsyn://call/7FQ2-K9PL\ncall other",
+        "fjarsyn://call/ÞÆÖ",
+        "https://call/7FQ2-K9PL",
+        "7FQ2-K9PL",
+    ] {
+        assert_eq!(parse_call_link(link), None, "{:?}", link);
+    }
+}
+
+#[test]
+fn rejects_oversized_links() {
+    let longest = "A".repeat(MAX_PEER_ID_LEN);
+    assert_eq!(parse_call_link(&format!("fjarsyn://call/{}", longest)), Some(longest.as_str()));
+    assert_eq!(parse_call_link(&format!("fjarsyn://call/{}A", longest)), None);
+
+    // Rejected before it is looked at, whatever it holds.
+    assert_eq!(parse_call_link(&"fjarsyn://call/".repeat(MAX_CALL_LINK_LEN)), None);
+    assert_eq!(parse_call_link(&"A".repeat(1 << 20)), None);
+}
+
+#[test]
+fn only_valid_peers_make_links() {
+    assert_eq!(call_link("7FQ2-K9PL").as_deref(), Some("fjarsyn://call/7FQ2-K9PL"));
+    for peer_id in ["", "7FQ2 K9PL", "a/b", "ÞÆÖ"] {
+        assert!(!is_valid_peer_id(peer_id), "{:?}", peer_id);
+        assert_eq!(call_link(peer_id), None);
+    }
+}
+
+proptest! {
+    #[test]
+    fn links_round_trip(peer_id in "[A-Za-z0-9-]{1,64}") {
+        let link = call_link(&peer_id).unwrap();
+        prop_assert_eq!(parse_call_link(&link), Some(peer_id.as_str()));
+    }
+
+    #[test]
+    fn parsing_anything_returns_a_valid_peer(link in any::<String>()) {
+        if let Some(peer_id) = parse_call_link(&link) {
+            prop_assert!(is_valid_peer_id(peer_id));
+        }
+    }
+}
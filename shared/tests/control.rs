@@ -0,0 +1,50 @@
+//! Control messages go between builds of different versions too.
+
+use fjarsyn_shared::{ControlMessage, QualityRequest, RecordingState};
+
+#[test]
+fn quality_request_round_trips() {
+    let request =
+        QualityRequest { max_bitrate: Some(1_000_000), max_fps: Some(15), max_dimension: None };
+    for message in
+        [ControlMessage::QualityRequest(Some(request)), ControlMessage::QualityRequest(None)]
+    {
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(serde_json::from_str::<ControlMessage>(&json).unwrap(), message);
+    }
+}
+
+#[test]
+fn quality_request_format_is_stable() {
+    let message = ControlMessage::QualityRequest(Some(QualityRequest {
+        max_bitrate: Some(1_000_000),
+        max_fps: Some(15),
+        max_dimension: None,
+    }));
+    assert_eq!(
+        serde_json::to_string(&message).unwrap(),
+        r#"{"q":{"max_bitrate":1000000,"max_fps":15,"max_dimension":null}}"#
+    );
+    assert_eq!(
+        serde_json::to_string(&ControlMessage::QualityRequest(None)).unwrap(),
+        r#"{"q":null}"#
+    );
+}
+
+#[test]
+fn remote_control_request_format_is_stable() {
+    let json = serde_json::to_string(&ControlMessage::RemoteControlRequest).unwrap();
+    assert_eq!(json, r#""rq""#);
+    assert_eq!(
+        serde_json::from_str::<ControlMessage>(&json).unwrap(),
+        ControlMessage::RemoteControlRequest
+    );
+}
+
+#[test]
+fn recording_state_format_is_stable() {
+    let message = ControlMessage::RecordingState(RecordingState { active: true });
+    let json = serde_json::to_string(&message).unwrap();
+    assert_eq!(json, r#"{"rec":{"active":true}}"#);
+    assert_eq!(serde_json::from_str::<ControlMessage>(&json).unwrap(), message);
+}
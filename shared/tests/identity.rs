@@ -0,0 +1,68 @@
+use ed25519_dalek::{Signer, SigningKey};
+use fjarsyn_shared::{AuthenticatePayload, auth_message, peer_id_from_public_key};
+
+const NONCE: &str = "5f0e2c7a9b1d4e3f8a6c0b2d4e6f8a1c";
+
+fn signing_key(fill: u8) -> SigningKey {
+    SigningKey::from_bytes(&[fill; 32])
+}
+
+fn payload(key: &SigningKey, nonce: &str) -> AuthenticatePayload {
+    let signature = key.sign(&auth_message(nonce));
+    AuthenticatePayload::new(&key.verifying_key().to_bytes(), &signature.to_bytes())
+}
+
+#[test]
+fn peer_id_is_shaped_like_a_uuid() {
+    let id = peer_id_from_public_key(&[7; 32]);
+    let groups: Vec<_> = id.split('-').map(str::len).collect();
+    assert_eq!(groups, vec![8, 4, 4, 4, 12]);
+    assert!(id.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+}
+
+#[test]
+fn peer_id_depends_only_on_the_key() {
+    assert_eq!(peer_id_from_public_key(&[7; 32]), peer_id_from_public_key(&[7; 32]));
+    assert_ne!(peer_id_from_public_key(&[7; 32]), peer_id_from_public_key(&[8; 32]));
+}
+
+#[test]
+fn verifies_to_the_id_of_the_key() {
+    let key = signing_key(1);
+    let id = payload(&key, NONCE).verify(NONCE);
+    assert_eq!(id, Some(peer_id_from_public_key(&key.verifying_key().to_bytes())));
+}
+
+#[test]
+fn rejects_a_signature_for_another_nonce() {
+    assert_eq!(payload(&signing_key(1), "an old nonce").verify(NONCE), None);
+}
+
+#[test]
+fn rejects_a_signature_by_another_key() {
+    let mut forged = payload(&signing_key(1), NONCE);
+    forged.public_key = payload(&signing_key(2), NONCE).public_key;
+    assert_eq!(forged.verify(NONCE), None);
+}
+
+#[test]
+fn rejects_a_bare_signature_of_the_nonce() {
+    // Without the context, a signature made for anything else could be passed off as one.
+    let key = signing_key(1);
+    let signature = key.sign(NONCE.as_bytes());
+    let payload = AuthenticatePayload::new(&key.verifying_key().to_bytes(), &signature.to_bytes());
+    assert_eq!(payload.verify(NONCE), None);
+}
+
+#[test]
+fn rejects_malformed_hex() {
+    let valid = payload(&signing_key(1), NONCE);
+    for (public_key, signature) in [
+        ("zz".to_owned(), valid.signature.clone()),
+        (valid.public_key.clone(), valid.signature[1..].to_owned()),
+        (valid.public_key[..62].to_owned(), valid.signature.clone()),
+        (format!("+{}", &valid.public_key[1..]), valid.signature.clone()),
+    ] {
+        assert_eq!(AuthenticatePayload { public_key, signature }.verify(NONCE), None);
+    }
+}
@@ -0,0 +1,191 @@
+//! The signaling messages are JSON between builds of different versions, so their format must not change by accident.
+
+use fjarsyn_shared::{
+    CallMode, IdentityPayload, PROTOCOL_VERSION, SignalingMessage, SignalingType,
+};
+use proptest::prelude::*;
+
+const KNOWN_TYPES: &[&str] =
+    &["Offer", "Answer", "Candidate", "Identity", "Bye", "Authenticate", "Rejected", "KeyExchange"];
+
+// Captured from builds before the protocol version was sent.
+const V0_OFFER: &str = r#"{"to":"3f1c","from":"","sig_type":"Offer","data":"v=0\r\n"}"#;
+const V0_IDENTITY: &str = r#"{"to":"3f1c","from":"server","sig_type":"Identity","data":"3f1c"}"#;
+const V0_BYE: &str = r#"{"to":"3f1c","from":"9a0b","sig_type":"Bye","data":""}"#;
+
+fn unknown_type_name() -> impl Strategy<Value = String> {
+    "[A-Z][a-zA-Z]{0,16}".prop_filter("known type", |name| !KNOWN_TYPES.contains(&name.as_str()))
+}
+
+fn signaling_type() -> impl Strategy<Value = SignalingType> {
+    prop_oneof![
+        Just(SignalingType::Offer),
+        Just(SignalingType::Answer),
+        Just(SignalingType::Candidate),
+        Just(SignalingType::Identity),
+        Just(SignalingType::Bye),
+        Just(SignalingType::Authenticate),
+        Just(SignalingType::Rejected),
+        Just(SignalingType::KeyExchange),
+        unknown_type_name().prop_map(SignalingType::Unknown),
+    ]
+}
+
+fn signaling_message() -> impl Strategy<Value = SignalingMessage> {
+    (
+        any::<String>(),
+        any::<String>(),
+        signaling_type(),
+        any::<String>(),
+        any::<u8>(),
+        any::<bool>(),
+        prop::option::of(prop::sample::select(CallMode::ALL.to_vec())),
+    )
+        .prop_map(|(to, from, sig_type, data, protocol_version, sealed, mode)| {
+            SignalingMessage { to, from, sig_type, data, protocol_version, sealed, mode }
+        })
+}
+
+proptest! {
+    #[test]
+    fn message_round_trips(msg in signaling_message()) {
+        let json = serde_json::to_string(&msg).unwrap();
+        prop_assert_eq!(serde_json::from_str::<SignalingMessage>(&json).unwrap(), msg);
+    }
+
+    #[test]
+    fn type_round_trips(sig_type in signaling_type()) {
+        let json = serde_json::to_string(&sig_type).unwrap();
+        prop_assert_eq!(serde_json::from_str::<SignalingType>(&json).unwrap(), sig_type);
+    }
+
+    #[test]
+    fn unknown_type_parses_as_unknown(name in unknown_type_name()) {
+        let json = format!(r#"{{"to":"","from":"","sig_type":"{}","data":""}}"#, name);
+        let msg = serde_json::from_str::<SignalingMessage>(&json).unwrap();
+        prop_assert_eq!(msg.sig_type, SignalingType::Unknown(name));
+    }
+}
+
+#[test]
+fn parses_messages_from_before_the_version() {
+    let offer = serde_json::from_str::<SignalingMessage>(V0_OFFER).unwrap();
+    assert_eq!(offer.sig_type, SignalingType::Offer);
+    assert_eq!(offer.data, "v=0\r\n");
+    assert_eq!(offer.protocol_version, 0);
+
+    let identity = serde_json::from_str::<SignalingMessage>(V0_IDENTITY).unwrap();
+    assert_eq!(identity.sig_type, SignalingType::Identity);
+    assert_eq!(identity.from, "server");
+
+    let bye = serde_json::from_str::<SignalingMessage>(V0_BYE).unwrap();
+    assert_eq!(bye.sig_type, SignalingType::Bye);
+}
+
+// Builds before the version ignore the field, so only the new field is added to what they expect.
+#[test]
+fn wire_format_is_stable() {
+    let msg = SignalingMessage {
+        to: "a".to_owned(),
+        from: "b".to_owned(),
+        sig_type: SignalingType::Offer,
+        data: "sdp".to_owned(),
+        protocol_version: PROTOCOL_VERSION,
+        sealed: false,
+        mode: None,
+    };
+    assert_eq!(
+        serde_json::to_string(&msg).unwrap(),
+        format!(
+            r#"{{"to":"a","from":"b","sig_type":"Offer","data":"sdp","protocol_version":{}}}"#,
+            PROTOCOL_VERSION
+        )
+    );
+}
+
+#[test]
+fn sealed_flag_is_sent_only_when_set() {
+    let msg = SignalingMessage {
+        to: "a".to_owned(),
+        from: "b".to_owned(),
+        sig_type: SignalingType::Offer,
+        data: "sealed".to_owned(),
+        protocol_version: PROTOCOL_VERSION,
+        sealed: true,
+        mode: None,
+    };
+    let json = serde_json::to_string(&msg).unwrap();
+    assert!(json.ends_with(r#","sealed":true}"#));
+
+    // Nothing before the flag sent it, so those messages are plain.
+    let msg = serde_json::from_str::<SignalingMessage>(V0_OFFER).unwrap();
+    assert!(!msg.sealed);
+}
+
+#[test]
+fn mode_is_sent_only_when_set() {
+    let msg = SignalingMessage {
+        to: "a".to_owned(),
+        from: "b".to_owned(),
+        sig_type: SignalingType::Answer,
+        data: "sdp".to_owned(),
+        protocol_version: PROTOCOL_VERSION,
+        sealed: false,
+        mode: Some(CallMode::ViewOnly),
+    };
+    let json = serde_json::to_string(&msg).unwrap();
+    assert!(json.ends_with(r#","mode":"ViewOnly"}"#));
+
+    let msg = serde_json::from_str::<SignalingMessage>(V0_OFFER).unwrap();
+    assert_eq!(msg.mode, None);
+}
+
+#[test]
+fn mode_follows_the_direction_of_the_video() {
+    let offer = |direction: &str| {
+        format!(
+            "v=0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\na=mid:0\r\na={}\r\n\
+             m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\na=sendrecv\r\n",
+            direction
+        )
+    };
+    assert_eq!(CallMode::from_sdp(&offer("sendrecv")), CallMode::ShareAndView);
+    assert_eq!(CallMode::from_sdp(&offer("sendonly")), CallMode::ShareAndView);
+    assert_eq!(CallMode::from_sdp(&offer("recvonly")), CallMode::ViewOnly);
+    assert_eq!(CallMode::from_sdp(&offer("inactive")), CallMode::ViewOnly);
+    // Nothing to tell by, as with a description from before modes.
+    assert_eq!(CallMode::from_sdp("v=0\r\n"), CallMode::ShareAndView);
+
+    // A video section added later, for a replaced track, doesn't change what the call started as.
+    let renegotiated =
+        format!("{}m=video 9 UDP/TLS/RTP/SAVPF 98\r\na=recvonly\r\n", offer("sendrecv"));
+    assert_eq!(CallMode::from_sdp(&renegotiated), CallMode::ShareAndView);
+}
+
+#[test]
+fn unknown_type_serializes_as_its_name() {
+    let json = serde_json::to_string(&SignalingType::Unknown("Renegotiate".to_owned())).unwrap();
+    assert_eq!(json, r#""Renegotiate""#);
+}
+
+#[test]
+fn parses_identity_with_short_code() {
+    let identity = IdentityPayload::parse(r#"{"uuid":"3f1c","short_code":"AB12CD"}"#);
+    assert_eq!(identity.uuid, "3f1c");
+    assert_eq!(identity.short_code.as_deref(), Some("AB12CD"));
+    assert_eq!(identity.nonce, None);
+}
+
+#[test]
+fn parses_identity_with_nonce() {
+    let identity = IdentityPayload::parse(r#"{"uuid":"3f1c","short_code":"AB12CD","nonce":"n0"}"#);
+    assert_eq!(identity.nonce.as_deref(), Some("n0"));
+}
+
+#[test]
+fn parses_identity_from_before_short_codes() {
+    let identity = serde_json::from_str::<SignalingMessage>(V0_IDENTITY).unwrap();
+    let identity = IdentityPayload::parse(&identity.data);
+    assert_eq!(identity.uuid, "3f1c");
+    assert_eq!(identity.short_code, None);
+}
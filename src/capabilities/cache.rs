@@ -0,0 +1,112 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capabilities::Capabilities,
+    capture_providers::shared::GraphicsAdapter,
+    config::Config,
+    utils::atomic_file::{self, Loaded},
+};
+
+const CACHE_FILE: &str = "capabilities.json";
+const UNKNOWN: &str = "unknown";
+
+/// What the probe results depend on. They are probed again when any of it changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityKey {
+    /// The PCI vendor and device ids of the GPU, e.g. 10de:2684.
+    pub gpu: String,
+    pub driver: String,
+    /// A hash of the FFmpeg version and build configuration, which decide the encoders and decoders there are.
+    pub ffmpeg: String,
+}
+
+impl CapabilityKey {
+    /// The key of the adapter, or of an unknown one, with FFmpeg built as described.
+    pub fn new(adapter: Option<&GraphicsAdapter>, ffmpeg_build: &str) -> Self {
+        Self {
+            gpu: adapter.map_or_else(
+                || UNKNOWN.to_owned(),
+                |adapter| format!("{:04x}:{:04x}", adapter.vendor_id, adapter.device_id),
+            ),
+            driver: adapter
+                .and_then(GraphicsAdapter::driver_version_string)
+                .unwrap_or_else(|| UNKNOWN.to_owned()),
+            ffmpeg: ffmpeg_build_hash(ffmpeg_build),
+        }
+    }
+}
+
+/// A short hash of how FFmpeg was built. FNV-1a, which is stable across runs and Rust versions, unlike the std hasher.
+pub fn ffmpeg_build_hash(build: &str) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash =
+        build.bytes().fold(OFFSET_BASIS, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME));
+    format!("{:016x}", hash)
+}
+
+/// The capabilities as probed, with what they were probed on, and when.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedCapabilities {
+    pub key: CapabilityKey,
+    /// In seconds since the Unix epoch.
+    pub probed_at: u64,
+    pub capabilities: Capabilities,
+}
+
+impl CachedCapabilities {
+    /// How long the results are trusted. Not all that changes them is in the key, e.g. a codec pack or a GPU
+    /// being disabled.
+    pub const TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+    pub fn new(key: CapabilityKey, capabilities: Capabilities, now: SystemTime) -> Self {
+        let probed_at = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Self { key, probed_at, capabilities }
+    }
+
+    /// How long ago they were probed, or None if it was after now, as when the clock was turned back.
+    pub fn age(&self, now: SystemTime) -> Option<Duration> {
+        now.duration_since(UNIX_EPOCH + Duration::from_secs(self.probed_at)).ok()
+    }
+
+    /// Whether they can be used instead of probing on the machine with the key.
+    pub fn is_valid_for(&self, key: &CapabilityKey, now: SystemTime) -> bool {
+        self.key == *key && self.age(now).is_some_and(|age| age < Self::TTL)
+    }
+}
+
+/// Where the cache is kept, next to the config.
+pub fn cache_path() -> Option<PathBuf> {
+    Config::dir().map(|dir| dir.join(CACHE_FILE))
+}
+
+/// The cached capabilities, if there are any that are still valid for the machine with the key.
+pub fn lookup(path: &Path, key: &CapabilityKey, now: SystemTime) -> Option<CachedCapabilities> {
+    let cached = match atomic_file::load_json::<CachedCapabilities>(path) {
+        Loaded::Intact(cached) | Loaded::Recovered { value: cached, .. } => cached,
+        Loaded::Missing => return None,
+        Loaded::Broken(e) => {
+            tracing::warn!("Failed to load the cached capabilities: {}", e);
+            return None;
+        }
+    };
+    if cached.key != *key {
+        tracing::info!("The cached capabilities are for {:?}, this is {:?}", cached.key, key);
+        return None;
+    }
+    if !cached.is_valid_for(key, now) {
+        tracing::info!("The cached capabilities are out of date");
+        return None;
+    }
+    Some(cached)
+}
+
+pub fn store(path: &Path, cached: &CachedCapabilities) -> io::Result<()> {
+    atomic_file::save_json(path, cached)
+}
@@ -0,0 +1,76 @@
+//! What this machine can capture, encode and decode with. Finding out opens every encoder and decoder, which takes
+//! seconds, so the results are cached next to the config until the GPU, its driver or FFmpeg change, or they get old.
+
+mod cache;
+mod probe;
+
+use std::time::{Duration, SystemTime};
+
+pub use cache::{CachedCapabilities, CapabilityKey, cache_path, ffmpeg_build_hash, lookup, store};
+pub use probe::{Capabilities, ProbeOutcome, current_key, probe};
+
+/// The capabilities from the cache, if it is still valid for this machine, or else probed and cached anew.
+/// Reprobing ignores the cache, e.g. after installing something the key doesn't cover.
+pub fn load(reprobe: bool) -> CachedCapabilities {
+    let key = current_key();
+    let now = SystemTime::now();
+    let path = cache_path();
+    if !reprobe && let Some(cached) = path.as_deref().and_then(|path| lookup(path, &key, now)) {
+        return cached;
+    }
+
+    tracing::info!("Probing the capabilities...");
+    let cached = CachedCapabilities::new(key, probe(), now);
+    if let Some(path) = &path
+        && let Err(e) = store(path, &cached)
+    {
+        tracing::warn!("Failed to cache the capabilities: {}", e);
+    }
+    cached
+}
+
+/// The capabilities as text, for the log and bug reports.
+pub fn report(cached: &CachedCapabilities, now: SystemTime) -> String {
+    let CachedCapabilities { key, capabilities, .. } = cached;
+    let mut report = format!(
+        "Capabilities, probed {}\nGPU {}, driver {}, FFmpeg build {}\n",
+        describe_age(cached.age(now)),
+        key.gpu,
+        key.driver,
+        key.ffmpeg
+    );
+    report.push_str(&format!(
+        "Graphics device: {}\n",
+        capabilities.graphics_device.as_deref().unwrap_or("unknown")
+    ));
+    let capture = match (capabilities.capture_supported, capabilities.session_options.as_slice()) {
+        (false, _) => "not supported".to_owned(),
+        (true, []) => "supported, with no session options".to_owned(),
+        (true, options) => format!("supported, with {}", options.join(", ")),
+    };
+    report.push_str(&format!("Capture: {}\n", capture));
+    for (kind, outcomes) in
+        [("Encoder", &capabilities.encoders), ("Decoder", &capabilities.decoders)]
+    {
+        for outcome in outcomes {
+            match &outcome.error {
+                None => report.push_str(&format!("{} {}: works\n", kind, outcome.name)),
+                Some(e) => report.push_str(&format!("{} {}: fails, {}\n", kind, outcome.name, e)),
+            }
+        }
+    }
+    report
+}
+
+fn describe_age(age: Option<Duration>) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    match age.map(|age| age.as_secs()) {
+        None => "in the future, going by the clock".to_owned(),
+        Some(secs) if secs < MINUTE => "just now".to_owned(),
+        Some(secs) if secs < HOUR => format!("{} min ago", secs / MINUTE),
+        Some(secs) if secs < DAY => format!("{} h ago", secs / HOUR),
+        Some(secs) => format!("{} days ago", secs / DAY),
+    }
+}
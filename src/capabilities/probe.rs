@@ -0,0 +1,133 @@
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capabilities::CapabilityKey,
+    capture_providers::{
+        describe_platform_graphics_device, is_platform_capture_supported,
+        platform_graphics_adapter, platform_session_options,
+    },
+    media::ffmpeg::{DecodeAccel, FFmpegDecoder, FFmpegEncoder, FFmpegTranscodeType, RateControl},
+    utils::{
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+
+// Small, as only whether it works matters. Hardware encoders have a minimum size, which this is well above.
+const PROBE_FRAME_SIZE: Vector2<i32> = Vector2 { x: 320, y: 180 };
+const PROBE_FRAMES: usize = 3;
+// The ones worth probing, as Auto is the first of them that works.
+const PROBED_ACCELS: &[DecodeAccel] =
+    &[DecodeAccel::D3D11VA, DecodeAccel::Vulkan, DecodeAccel::Software];
+
+/// Whether an encoder or decoder works here.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeOutcome {
+    pub name: String,
+    /// Why it doesn't work, or None if it does.
+    pub error: Option<String>,
+}
+
+impl ProbeOutcome {
+    fn of(name: impl Into<String>, result: Result<(), String>) -> Self {
+        Self { name: name.into(), error: result.err() }
+    }
+
+    pub fn works(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// What this machine can capture, encode and decode with.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub graphics_device: Option<String>,
+    pub capture_supported: bool,
+    /// The session options this version of Windows has.
+    pub session_options: Vec<String>,
+    /// One for each transcode type.
+    pub encoders: Vec<ProbeOutcome>,
+    /// One for each codec and acceleration.
+    pub decoders: Vec<ProbeOutcome>,
+}
+
+/// The key of this machine, as it is now.
+pub fn current_key() -> CapabilityKey {
+    let adapter = platform_graphics_adapter()
+        .inspect_err(|e| tracing::warn!("Failed to identify the graphics adapter: {}", e))
+        .ok();
+    let ffmpeg_build = format!(
+        "avutil {} avcodec {} {}",
+        ffmpeg_next::util::version(),
+        ffmpeg_next::codec::version(),
+        ffmpeg_next::codec::configuration()
+    );
+    CapabilityKey::new(adapter.as_ref(), &ffmpeg_build)
+}
+
+/// Tries every encoder and decoder, which takes a few seconds, as each opens a device of its own.
+pub fn probe() -> Capabilities {
+    let started = Instant::now();
+    let capture_supported = is_platform_capture_supported()
+        .inspect_err(|e| tracing::warn!("Failed to find out whether capture is supported: {}", e))
+        .unwrap_or(false);
+
+    let mut mime_types = FFmpegTranscodeType::ALL.iter().map(|t| t.mime_type()).collect::<Vec<_>>();
+    mime_types.dedup();
+    let decoders = mime_types
+        .iter()
+        .flat_map(|&mime_type| {
+            PROBED_ACCELS.iter().map(move |&accel| {
+                let codec = mime_type.trim_start_matches("video/");
+                ProbeOutcome::of(format!("{} {}", codec, accel), probe_decoder(mime_type, accel))
+            })
+        })
+        .collect();
+
+    let capabilities = Capabilities {
+        graphics_device: describe_platform_graphics_device()
+            .inspect_err(|e| tracing::warn!("Failed to describe the graphics device: {}", e))
+            .ok(),
+        capture_supported,
+        session_options: match capture_supported {
+            true => platform_session_options().into_iter().map(str::to_owned).collect(),
+            false => Vec::new(),
+        },
+        encoders: FFmpegTranscodeType::ALL
+            .iter()
+            .map(|&transcoding_type| {
+                ProbeOutcome::of(transcoding_type.to_string(), probe_encoder(transcoding_type))
+            })
+            .collect(),
+        decoders,
+    };
+    tracing::info!("Probed the capabilities in {} ms", started.elapsed().as_millis());
+    capabilities
+}
+
+fn probe_encoder(transcoding_type: FFmpegTranscodeType) -> Result<(), String> {
+    let mut encoder =
+        FFmpegEncoder::new(1_000_000, 30.0, PixelFormat::RGBA8, None, 30, RateControl::Variable)
+            .map_err(|e| e.to_string())?;
+    let mut frames =
+        SyntheticFrames::new(PROBE_FRAME_SIZE, PixelFormat::RGBA8, FramePattern::Gradient);
+    let mut packets = 0;
+    for _ in 0..PROBE_FRAMES {
+        packets += encoder
+            .encode(&frames.next_bitmap(), transcoding_type, PROBE_FRAME_SIZE.x, PROBE_FRAME_SIZE.y)
+            .map_err(|e| e.to_string())?
+            .len();
+    }
+    packets += encoder.flush().map_err(|e| e.to_string())?.len();
+    if packets == 0 {
+        return Err("Nothing was encoded".to_owned());
+    }
+    Ok(())
+}
+
+fn probe_decoder(mime_type: &str, accel: DecodeAccel) -> Result<(), String> {
+    FFmpegDecoder::without_fallback(mime_type, accel).map(drop).map_err(|e| e.to_string())
+}
@@ -1,13 +1,65 @@
-use crate::capture_providers::shared::CaptureFramerate;
+use std::sync::Arc;
+
+use futures::Stream;
+use tokio::sync::{RwLock, watch};
+
+use crate::{
+    capture_providers::shared::{CaptureFramerate, CaptureItemInfo, CaptureState, FrameMeta},
+    utils::{frame::Frame, locked_stream::stream_when_unlocked},
+};
 
 pub trait CaptureProvider {
-    type Result<T>;
+    type Error: std::error::Error;
     type Stream;
     type CaptureItem;
 
-    fn create_stream(&mut self, framerate: CaptureFramerate) -> Self::Result<Self::Stream>;
-    fn set_capture_item(&mut self, capture_item: Self::CaptureItem) -> Self::Result<()>;
-    fn start_capture(&mut self) -> Self::Result<()>;
-    fn stop_capture(&mut self) -> Self::Result<()>;
+    /// Creates the stream the frames are sent to. There is only one at a time, so this fails while another is active,
+    /// unless `replace` is set, in which case the previous stream is ended.
+    fn create_stream(
+        &mut self,
+        framerate: CaptureFramerate,
+        replace: bool,
+    ) -> Result<Self::Stream, Self::Error>;
+    fn set_capture_item(&mut self, capture_item: Self::CaptureItem) -> Result<(), Self::Error>;
+    fn start_capture(&mut self) -> Result<(), Self::Error>;
+    fn stop_capture(&mut self) -> Result<(), Self::Error>;
+    /// Gets an [interrupted](CaptureState::Interrupted) capture going again on the same source,
+    /// and returns the item it now captures. Fails if the source is gone.
+    fn recover_capture(&mut self) -> Result<Self::CaptureItem, Self::Error>;
     fn is_capturing(&self) -> bool;
+    /// Describes the current capture item, or None if there is none.
+    fn capture_item_info(&self) -> Option<CaptureItemInfo>;
+    /// Receives the state every time it changes, including changes the caller didn't cause.
+    fn subscribe_state(&self) -> watch::Receiver<CaptureState>;
+    /// Receives what is known about each captured frame without its pixels, for when those aren't needed.
+    fn subscribe_frame_meta(&self) -> watch::Receiver<FrameMeta>;
+}
+
+/// For a provider shared with the rest of the app, which needs no UI runtime to be streamed from.
+pub trait CaptureProviderExt: CaptureProvider {
+    /// The frames of a new stream, which replaces the one before it. The stream is made once the provider is unlocked,
+    /// so this doesn't wait for the lock, and the stream can be polled from any tokio task.
+    /// Ends without frames if the stream can't be made.
+    fn stream(
+        provider: &Arc<RwLock<Self>>,
+        framerate: CaptureFramerate,
+    ) -> impl Stream<Item = Frame> + Send + 'static;
+}
+
+impl<P> CaptureProviderExt for P
+where
+    P: CaptureProvider + Send + Sync + 'static,
+    P::Stream: Stream<Item = Frame> + Send + 'static,
+{
+    fn stream(
+        provider: &Arc<RwLock<Self>>,
+        framerate: CaptureFramerate,
+    ) -> impl Stream<Item = Frame> + Send + 'static {
+        stream_when_unlocked(provider.clone(), move |provider| {
+            provider
+                .create_stream(framerate, true)
+                .inspect_err(|e| tracing::error!("Failed to create frame stream: {}", e))
+                .ok()
+        })
+    }
 }
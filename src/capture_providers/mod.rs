@@ -2,7 +2,11 @@ mod capture_provider;
 pub mod shared;
 pub mod windows;
 
-pub use capture_provider::CaptureProvider;
+pub use capture_provider::{CaptureProvider, CaptureProviderExt};
+pub use shared::SourceKind;
+
+#[cfg(target_os = "windows")]
+use crate::utils::{metrics::Metrics, pixel_format::PixelFormat};
 
 #[derive(Debug, thiserror::Error)]
 pub enum CaptureError {
@@ -11,12 +15,61 @@ pub enum CaptureError {
     WindowsCaptureError(#[from] windows::error::WindowsCaptureError),
 }
 
+#[cfg(target_os = "windows")]
+pub use windows::CursorTracker as PlatformCursorTracker;
+#[cfg(target_os = "windows")]
+pub use windows::Screenshotter as PlatformScreenshotter;
+#[cfg(target_os = "windows")]
+pub use windows::SourceDescriptor;
 #[cfg(target_os = "windows")]
 pub use windows::WgcCaptureProvider as PlatformCaptureProvider;
 #[cfg(target_os = "windows")]
+pub use windows::WgcCaptureProviderBuilderError as PlatformCaptureProviderError;
+#[cfg(target_os = "windows")]
 pub use windows::WindowsCaptureStream as PlatformCaptureStream;
 #[cfg(target_os = "windows")]
+pub use windows::create_capture_item_for_primary_monitor as create_platform_capture_item_for_primary_monitor;
+#[cfg(target_os = "windows")]
+pub use windows::describe_d3d_device as describe_platform_graphics_device;
+#[cfg(target_os = "windows")]
+pub use windows::enumerate_sources as enumerate_platform_sources;
+#[cfg(target_os = "windows")]
+pub use windows::exclude_window_from_capture as exclude_platform_window_from_capture;
+#[cfg(target_os = "windows")]
+pub use windows::find_source as find_platform_source;
+#[cfg(target_os = "windows")]
+pub use windows::graphics_adapter as platform_graphics_adapter;
+#[cfg(target_os = "windows")]
+pub use windows::is_capture_supported as is_platform_capture_supported;
+#[cfg(target_os = "windows")]
+pub use windows::monitor_geometry as platform_monitor_geometry;
+#[cfg(target_os = "windows")]
+pub use windows::monitor_layout as platform_monitor_layout;
+#[cfg(target_os = "windows")]
+pub use windows::saved_capture_item as saved_platform_capture_item;
+#[cfg(target_os = "windows")]
+pub use windows::supported_session_options as platform_session_options;
+#[cfg(target_os = "windows")]
 pub use windows::user_pick_capture_item as user_pick_platform_capture_item;
+#[cfg(target_os = "windows")]
+pub use windows::window_bounds as platform_window_bounds;
 
 #[cfg(target_os = "windows")]
 pub type PlatformCaptureItem = <PlatformCaptureProvider as CaptureProvider>::CaptureItem;
+
+/// Creates the platform capture provider on its default device.
+/// This blocks while the graphics device is created, so keep it off the UI thread.
+/// The provider keeps its stats in the metrics.
+#[cfg(target_os = "windows")]
+pub fn create_platform_capture_provider(
+    pixel_format: PixelFormat,
+    session_options: shared::WgcSessionOptions,
+    metrics: Metrics,
+) -> Result<PlatformCaptureProvider, PlatformCaptureProviderError> {
+    windows::WgcCaptureProviderBuilder::new(pixel_format)
+        .with_session_options(session_options)
+        .with_metrics(metrics)
+        .with_default_device()?
+        .with_default_capture_item()?
+        .build()
+}
@@ -2,7 +2,9 @@ use std::{fmt::Display, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Serialize, Deserialize)]
+use crate::utils::framerate::Framerate;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
 pub enum CaptureFramerate {
     FPS5,
     FPS24,
@@ -36,8 +38,14 @@ impl CaptureFramerate {
         }
     }
 
+    /// The rate as an exact fraction, which everything it is converted to goes through.
+    pub const fn to_framerate(&self) -> Framerate {
+        Framerate::whole(self.to_hz() as u32)
+    }
+
+    /// The time between frames, rounded to the nearest nanosecond.
     pub fn to_frametime(&self) -> Duration {
-        Duration::from_secs_f32(1.0 / self.to_hz())
+        self.to_framerate().frametime()
     }
 }
 
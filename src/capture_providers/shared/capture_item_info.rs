@@ -0,0 +1,28 @@
+use super::{SessionOption, WindowIcon};
+use crate::utils::{orientation::Orientation, vector2::Vector2};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceKind {
+    Monitor,
+    Window,
+    /// A fixed region of the virtual desktop, which may span several monitors.
+    Region,
+}
+
+/// What is being captured, for showing to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureItemInfo {
+    pub name: String,
+    /// Once rotated upright.
+    pub size: Vector2<i32>,
+    /// How the frames are rotated to turn them upright. Only ever set for a monitor.
+    pub orientation: Orientation,
+    pub kind: SourceKind,
+    /// The session options that took effect, which leaves out those this version of Windows doesn't have.
+    pub applied_options: Vec<SessionOption>,
+    /// The executable of the process the shared window belongs to, e.g. "WINWORD.EXE". None for other sources, or if
+    /// the window wasn't found.
+    pub process_name: Option<String>,
+    /// The small icon of the shared window. None if it has none, or for other sources.
+    pub icon: Option<WindowIcon>,
+}
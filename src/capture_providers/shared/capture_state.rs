@@ -0,0 +1,13 @@
+/// What a capture provider is doing, as seen by the UI.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CaptureState {
+    #[default]
+    Idle,
+    Starting,
+    Capturing,
+    /// Frames stopped coming without the capture ending, e.g. as the displays changed and took the graphics device
+    /// with them, until the capture is recovered with [`recover_capture`](crate::capture_providers::CaptureProvider::recover_capture).
+    Interrupted(String),
+    /// The capture ended without being stopped, e.g. because the shared window was closed.
+    Error(String),
+}
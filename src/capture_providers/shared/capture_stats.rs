@@ -0,0 +1,173 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::utils::metrics::{
+    Counter, Gauge, Histogram, HistogramSnapshot, Metrics, MetricsSnapshot,
+};
+
+/// How evenly the frames arrive from the OS, to tell capture judder apart from encoding or network trouble.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CaptureStats {
+    /// The frames arriving per second, going by the average interval.
+    pub arrival_fps: f32,
+    pub mean_interval: Duration,
+    /// The standard deviation of the intervals. Near zero when the frames arrive like clockwork.
+    pub jitter: Duration,
+    /// Rounded up to the bucket of the interval histogram it falls into.
+    pub max_interval: Duration,
+    /// The frames dropped because their copy from the GPU wasn't done when they were due to be read.
+    pub skipped_readbacks: u64,
+    /// The staging buffers in use, which grows when the copies keep falling behind.
+    pub readback_depth: usize,
+}
+
+/// Records the intervals between the frames that arrive, in microseconds. Only takes atomic operations, as it is
+/// called from the capture callback.
+#[derive(Debug)]
+pub struct FrameIntervals {
+    histogram: Arc<Histogram>,
+    // What the arrivals are measured from, so they fit into an atomic.
+    epoch: Instant,
+    // In microseconds since the epoch, plus one, so zero is no arrival yet.
+    last_arrival: AtomicU64,
+    target_interval_us: AtomicU64,
+}
+
+impl FrameIntervals {
+    // The OS only sends frames when the content changes, so a longer gap is the screen standing still, not judder.
+    const IDLE_GAP_FACTOR: u64 = 4;
+
+    pub fn new(histogram: Arc<Histogram>, target_interval: Duration) -> Self {
+        Self {
+            histogram,
+            epoch: Instant::now(),
+            last_arrival: AtomicU64::new(0),
+            target_interval_us: AtomicU64::new(target_interval.as_micros() as u64),
+        }
+    }
+
+    /// The bounds of the histogram, in microseconds. Finer around the intervals of the usual framerates, and up to the
+    /// longest gap that isn't taken for the screen standing still at 1 fps.
+    pub fn bounds() -> Vec<u64> {
+        let steps = [
+            (20_000, 1_000),
+            (50_000, 2_000),
+            (100_000, 5_000),
+            (500_000, 25_000),
+            (5_000_000, 500_000),
+        ];
+        let mut bounds = Vec::new();
+        let mut bound = 0;
+        for (until, step) in steps {
+            while bound < until {
+                bound += step;
+                bounds.push(bound);
+            }
+        }
+        bounds
+    }
+
+    pub fn record(&self, arrived: Instant) {
+        let arrival = arrived.saturating_duration_since(self.epoch).as_micros() as u64 + 1;
+        let last = self.last_arrival.swap(arrival, Ordering::Relaxed);
+        // Frames arriving on several threads at once may come out of order, which isn't an interval either.
+        if last == 0 || last > arrival {
+            return;
+        }
+        let interval = arrival - last;
+        if interval > self.target_interval_us.load(Ordering::Relaxed) * Self::IDLE_GAP_FACTOR {
+            return;
+        }
+        self.histogram.record(interval);
+    }
+
+    /// Starts over, e.g. when the framerate or the source changes.
+    pub fn reset(&self, target_interval: Duration) {
+        self.target_interval_us.store(target_interval.as_micros() as u64, Ordering::Relaxed);
+        self.last_arrival.store(0, Ordering::Relaxed);
+        self.histogram.reset();
+    }
+}
+
+/// The metrics a capture provider keeps its [`CaptureStats`] in.
+#[derive(Debug, Clone)]
+pub struct CaptureMetrics {
+    pub intervals: Arc<FrameIntervals>,
+    pub skipped_readbacks: Arc<Counter>,
+    /// The deepest of the staging rings, as the monitors of a region are read back on their own.
+    pub readback_depth: Arc<Gauge>,
+}
+
+impl CaptureMetrics {
+    const FRAME_INTERVAL: &str = "capture.frame_interval_us";
+    const SKIPPED_READBACKS: &str = "capture.skipped_readbacks";
+    const READBACK_DEPTH: &str = "capture.readback_depth";
+
+    pub fn register(metrics: &Metrics, target_interval: Duration) -> Self {
+        let histogram = metrics.histogram(Self::FRAME_INTERVAL, &FrameIntervals::bounds());
+        Self {
+            intervals: Arc::new(FrameIntervals::new(histogram, target_interval)),
+            skipped_readbacks: metrics.counter(Self::SKIPPED_READBACKS),
+            readback_depth: metrics.gauge(Self::READBACK_DEPTH),
+        }
+    }
+}
+
+/// Works out the [`CaptureStats`] of the last frames from snapshots of the metrics, as the histogram counts every
+/// interval since the stream started.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureStatsWindow {
+    // The stats cover what was recorded since the older one. The newer one takes its place once as many intervals
+    // were recorded since it, so there are always between one and two windows' worth.
+    older: HistogramSnapshot,
+    newer: HistogramSnapshot,
+}
+
+impl CaptureStatsWindow {
+    const INTERVALS: u64 = 120;
+
+    pub fn stats(&mut self, snapshot: &MetricsSnapshot) -> CaptureStats {
+        let readbacks = CaptureStats {
+            skipped_readbacks: snapshot
+                .counter(CaptureMetrics::SKIPPED_READBACKS)
+                .unwrap_or_default(),
+            readback_depth: snapshot.gauge(CaptureMetrics::READBACK_DEPTH).unwrap_or_default()
+                as usize,
+            ..Default::default()
+        };
+        let Some(histogram) = snapshot.histogram(CaptureMetrics::FRAME_INTERVAL) else {
+            return readbacks;
+        };
+        // Reset with the stream.
+        if histogram.count < self.newer.count {
+            self.reset();
+        }
+        if histogram.since(&self.newer).count >= Self::INTERVALS {
+            self.older = std::mem::replace(&mut self.newer, histogram.clone());
+        }
+
+        let recent = histogram.since(&self.older);
+        let (Some(mean), Some(std_dev), Some(max)) =
+            (recent.mean(), recent.std_dev(), recent.max())
+        else {
+            return readbacks;
+        };
+        CaptureStats {
+            arrival_fps: if mean > 0.0 { (1_000_000.0 / mean) as f32 } else { 0.0 },
+            mean_interval: Duration::from_secs_f64(mean / 1_000_000.0),
+            jitter: Duration::from_secs_f64(std_dev / 1_000_000.0),
+            max_interval: Duration::from_micros(max),
+            ..readbacks
+        }
+    }
+
+    /// Starts over, e.g. when the capture stops.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
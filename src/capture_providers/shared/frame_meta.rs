@@ -0,0 +1,24 @@
+use crate::utils::{frame::Frame, vector2::Vector2};
+
+/// What is known about the last captured frame without its pixels, for consumers that only show information about it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameMeta {
+    /// Counts the frames captured, so skipped ones show up as gaps.
+    pub sequence: u64,
+    pub size: Vector2<i32>,
+    pub dirty_rects: usize,
+    /// The part of the frame the dirty rects cover, from 0 to 1.
+    pub dirty_fraction: f32,
+}
+
+impl FrameMeta {
+    /// Describes the frame following this one.
+    pub fn next(&self, frame: &Frame) -> Self {
+        Self {
+            sequence: self.sequence + 1,
+            size: frame.size,
+            dirty_rects: frame.dirty_rects.as_ref().map_or(0, Vec::len),
+            dirty_fraction: frame.dirty_area_fraction,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use arc_swap::{ArcSwapOption, Guard};
+use tokio::sync::mpsc;
+
+use crate::utils::frame::Frame;
+
+/// Where the capture callback sends its frames: the sender of the active stream, if there is one.
+/// Loading it is lock-free, so the callback never waits on a stream being swapped or ended.
+#[derive(Debug, Clone, Default)]
+pub struct FrameSenderSlot(Arc<ArcSwapOption<mpsc::Sender<Frame>>>);
+
+impl FrameSenderSlot {
+    /// The sender of the active stream, as it was when loaded.
+    pub fn load(&self) -> Guard<Option<Arc<mpsc::Sender<Frame>>>> {
+        self.0.load()
+    }
+
+    /// Makes the sender the active one. The previous stream ends once no callback holds on to its sender any more.
+    pub fn replace(&self, sender: mpsc::Sender<Frame>) {
+        self.0.store(Some(Arc::new(sender)));
+    }
+
+    /// Ends the active stream, the same way replacing it does.
+    pub fn end(&self) {
+        self.0.store(None);
+    }
+}
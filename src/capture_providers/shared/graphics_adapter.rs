@@ -0,0 +1,24 @@
+/// Which GPU the capture runs on, and the version of its driver, as reported by DXGI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphicsAdapter {
+    /// The PCI ids, e.g. 0x10de for NVIDIA.
+    pub vendor_id: u32,
+    pub device_id: u32,
+    /// The version of the user mode driver, four 16 bit parts from the most significant, or None if unknown.
+    pub driver_version: Option<u64>,
+}
+
+impl GraphicsAdapter {
+    /// The driver version the way Device Manager shows it, e.g. 32.0.15.6094.
+    pub fn driver_version_string(&self) -> Option<String> {
+        self.driver_version.map(|version| {
+            format!(
+                "{}.{}.{}.{}",
+                version >> 48,
+                (version >> 32) & 0xffff,
+                (version >> 16) & 0xffff,
+                version & 0xffff
+            )
+        })
+    }
+}
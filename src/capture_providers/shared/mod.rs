@@ -1,3 +1,29 @@
 mod capture_framerate;
+mod capture_item_info;
+mod capture_state;
+mod capture_stats;
+mod frame_meta;
+mod frame_sender_slot;
+mod graphics_adapter;
+mod monitor_geometry;
+mod readback_ring;
+mod region_composite;
+mod rotation_correction;
+mod saved_capture_source;
+mod session_options;
+mod window_icon;
 
 pub use capture_framerate::*;
+pub use capture_item_info::*;
+pub use capture_state::*;
+pub use capture_stats::*;
+pub use frame_meta::*;
+pub use frame_sender_slot::*;
+pub use graphics_adapter::*;
+pub use monitor_geometry::*;
+pub use readback_ring::*;
+pub use region_composite::*;
+pub use rotation_correction::*;
+pub use saved_capture_source::*;
+pub use session_options::*;
+pub use window_icon::*;
@@ -0,0 +1,9 @@
+use crate::utils::rect::Rect;
+
+/// Where a monitor is on the virtual desktop, and how much its content is scaled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitorGeometry {
+    /// In physical pixels, which may be negative on multi-monitor setups.
+    pub bounds: Rect<i32>,
+    pub scale_factor: f32,
+}
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+/// Keeps track of a ring of staging buffers that frames are copied into on the GPU and read back from on the CPU,
+/// so the readback can skip a frame whose copy isn't done instead of waiting for it.
+#[derive(Debug, Clone)]
+pub struct ReadbackRing {
+    depth: usize,
+    max_depth: usize,
+    next_write: usize,
+    // The slots copied into but not read back yet, oldest first.
+    pending: VecDeque<usize>,
+    consecutive_skips: u32,
+    skipped: u64,
+    delivered: u64,
+}
+
+impl ReadbackRing {
+    // Skipping this many readbacks in a row means the copies need more time than the ring gives them.
+    const GROW_AFTER_SKIPS: u32 = 3;
+
+    pub fn new(depth: usize, max_depth: usize) -> Self {
+        let depth = depth.max(1);
+        Self {
+            depth,
+            max_depth: max_depth.max(depth),
+            next_write: 0,
+            pending: VecDeque::new(),
+            consecutive_skips: 0,
+            skipped: 0,
+            delivered: 0,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The readbacks skipped because no copy was done yet.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// The readbacks done, e.g. to tell whether there is a frame to show at all yet.
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    /// The slot to copy the next frame into.
+    /// If every slot is still waiting to be read, the oldest is overwritten and its frame lost.
+    pub fn write_slot(&mut self) -> usize {
+        let slot = self.next_write;
+        self.pending.retain(|&pending| pending != slot);
+        self.pending.push_back(slot);
+        self.next_write = (slot + 1) % self.depth;
+        slot
+    }
+
+    /// The newest slot whose copy is done, or None if none is, which counts as a skipped readback.
+    /// Copies finish in the order they were issued, so the older slots are stale by then and dropped.
+    /// The first readback takes the newest slot whether it is done or not, as there is no earlier frame to send.
+    pub fn read_slot(&mut self, is_ready: impl Fn(usize) -> bool) -> Option<usize> {
+        let first = self.delivered == 0;
+        let Some(position) = self.pending.iter().rposition(|&slot| first || is_ready(slot)) else {
+            self.consecutive_skips += 1;
+            self.skipped += 1;
+            return None;
+        };
+
+        let slot = self.pending[position];
+        self.pending.drain(..=position);
+        self.consecutive_skips = 0;
+        self.delivered += 1;
+        Some(slot)
+    }
+
+    /// Whether readbacks keep being skipped, and there is room for another slot to give the copies more time.
+    pub fn should_grow(&self) -> bool {
+        self.consecutive_skips >= Self::GROW_AFTER_SKIPS && self.depth < self.max_depth
+    }
+
+    /// Adds a slot after the others, and returns its index.
+    pub fn grow(&mut self) -> usize {
+        let slot = self.depth;
+        self.depth += 1;
+        self.consecutive_skips = 0;
+        slot
+    }
+}
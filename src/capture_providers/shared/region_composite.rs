@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use crate::utils::{
+    bitmap_utils::blit,
+    dirty_rects::{self, MAX_DIRTY_RECTS},
+    rect::Rect,
+    vector2::Vector2,
+};
+
+/// Puts the frames of the monitors a region of the virtual desktop spans together into frames of the whole region.
+/// A composite is done once every monitor has delivered a frame since the previous one, so it goes at the slowest one's cadence.
+/// Monitors only deliver when something on them changes though, so it doesn't wait longer than `max_wait` for an idle one.
+#[derive(Debug, Clone)]
+pub struct RegionComposite {
+    region: Rect<i32>,
+    // The bounds of each monitor, in virtual desktop coordinates.
+    monitors: Vec<Rect<i32>>,
+    bytes_per_pixel: usize,
+    max_wait: Duration,
+    // Which monitors delivered since the previous composite.
+    arrived: Vec<bool>,
+    // Whether every monitor delivered at least once, as the composite has holes until then.
+    filled: bool,
+    // When the first monitor delivered since the previous composite.
+    pending_since: Option<Instant>,
+    // What changed since the previous composite, relative to the region.
+    dirty_rects: Vec<Rect<i32>>,
+    buffer: Vec<u8>,
+}
+
+impl RegionComposite {
+    /// The monitors are the ones the region spans, in the order their frames are submitted by.
+    pub fn new(
+        region: Rect<i32>,
+        monitors: impl IntoIterator<Item = Rect<i32>>,
+        bytes_per_pixel: usize,
+        max_wait: Duration,
+    ) -> Self {
+        let monitors: Vec<_> = monitors.into_iter().collect();
+        Self {
+            region,
+            arrived: vec![false; monitors.len()],
+            monitors,
+            bytes_per_pixel,
+            max_wait,
+            filled: false,
+            pending_since: None,
+            dirty_rects: Vec::new(),
+            buffer: vec![0; region.area() as usize * bytes_per_pixel],
+        }
+    }
+
+    pub fn region(&self) -> Rect<i32> {
+        self.region
+    }
+
+    pub fn monitors(&self) -> &[Rect<i32>] {
+        &self.monitors
+    }
+
+    /// The size of the composite frames, which is the size of the region.
+    pub fn size(&self) -> Vector2<i32> {
+        self.region.size
+    }
+
+    /// The length of the composite frames in bytes.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn set_max_wait(&mut self, max_wait: Duration) {
+        self.max_wait = max_wait;
+    }
+
+    /// Copies a frame of the monitor into its part of the composite.
+    /// The dirty rects are relative to the frame, and None if they aren't known.
+    /// True once the composite is done, and should be finished.
+    pub fn submit(
+        &mut self,
+        monitor: usize,
+        data: &[u8],
+        size: Vector2<i32>,
+        dirty_rects: Option<&[Rect<i32>]>,
+        now: Instant,
+    ) -> bool {
+        let Some(bounds) = self.monitors.get(monitor) else {
+            tracing::warn!("Frame submitted for unknown monitor {} of the region", monitor);
+            return false;
+        };
+
+        let offset = Vector2::new(
+            bounds.position.x - self.region.position.x,
+            bounds.position.y - self.region.position.y,
+        );
+        // The frame's own size wins over the monitor's, in case it changed resolution since.
+        blit(data, size, &mut self.buffer, self.region.size, offset, self.bytes_per_pixel);
+
+        let region_bounds = Rect::new(0, 0, self.region.size.x, self.region.size.y);
+        let frame_bounds = Rect::new(0, 0, size.x, size.y);
+        let changed = dirty_rects.unwrap_or(std::slice::from_ref(&frame_bounds));
+        self.dirty_rects.extend(
+            changed.iter().filter_map(|rect| rect.translated(offset).intersection(&region_bounds)),
+        );
+        // A fast monitor keeps adding rects while a slow one is waited for.
+        if self.dirty_rects.len() > MAX_DIRTY_RECTS * 4 {
+            self.dirty_rects = dirty_rects::coalesce(self.dirty_rects.drain(..), MAX_DIRTY_RECTS);
+        }
+
+        self.arrived[monitor] = true;
+        let pending_since = *self.pending_since.get_or_insert(now);
+        let all_arrived = self.arrived.iter().all(|&arrived| arrived);
+        self.filled |= all_arrived;
+
+        self.filled
+            && (all_arrived || now.saturating_duration_since(pending_since) >= self.max_wait)
+    }
+
+    /// Copies the composite out, and starts on the next one.
+    /// Returns what changed since the previous composite, relative to the region.
+    pub fn finish(&mut self, out: &mut [u8]) -> Vec<Rect<i32>> {
+        out[..self.buffer.len()].copy_from_slice(&self.buffer);
+        self.arrived.fill(false);
+        self.pending_since = None;
+        dirty_rects::coalesce(self.dirty_rects.drain(..), MAX_DIRTY_RECTS)
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{orientation::Orientation, vector2::Vector2};
+
+/// Whether the frames of a rotated display are turned upright before they are sent.
+/// Some drivers have them arrive in the shape of the panel, others already rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum RotationCorrection {
+    /// Only those that arrive in the shape of the panel, which can't be told of a display turned upside down.
+    #[default]
+    Auto,
+    /// Every frame of a rotated display, for drivers that leave even those upside down.
+    Always,
+    Off,
+}
+
+impl RotationCorrection {
+    pub const ALL: &[RotationCorrection] =
+        &[RotationCorrection::Auto, RotationCorrection::Always, RotationCorrection::Off];
+
+    /// The rotation that turns a frame of `frame_size` upright, of a display rotated by `display`
+    /// whose size on the desktop is `desktop_size`.
+    pub fn orientation(
+        self,
+        display: Orientation,
+        desktop_size: Vector2<i32>,
+        frame_size: Vector2<i32>,
+    ) -> Orientation {
+        match self {
+            Self::Off => Orientation::Upright,
+            Self::Always => display,
+            // Already upright if it has the size of the desktop, and any other size is left alone too.
+            Self::Auto
+                if display.is_transposed()
+                    && frame_size != desktop_size
+                    && display.rotated_size(frame_size) == desktop_size =>
+            {
+                display
+            }
+            Self::Auto => Orientation::Upright,
+        }
+    }
+}
+
+impl Display for RotationCorrection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => f.write_str("Auto"),
+            Self::Always => f.write_str("Always"),
+            Self::Off => f.write_str("Off"),
+        }
+    }
+}
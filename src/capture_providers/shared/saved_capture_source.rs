@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+/// Identifies a capture source across restarts, as the handles it was captured through don't survive them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptureSourceId {
+    /// The device name of the monitor, e.g. `\\.\DISPLAY2`, which stays the same while it is plugged into the same port.
+    Monitor { device_name: String },
+    /// The executable of the window's process, and the start of its title.
+    /// Titles often end with what the window currently shows, so only the start is compared.
+    Window { process_name: String, title_prefix: String },
+}
+
+impl CaptureSourceId {
+    const TITLE_PREFIX_LEN: usize = 24;
+
+    pub fn monitor(device_name: impl Into<String>) -> Self {
+        Self::Monitor { device_name: device_name.into() }
+    }
+
+    pub fn window(process_name: impl Into<String>, title: &str) -> Self {
+        Self::Window {
+            process_name: process_name.into(),
+            title_prefix: title.chars().take(Self::TITLE_PREFIX_LEN).collect(),
+        }
+    }
+
+    /// Whether a currently available source, described by its own id, is the one this id was saved for.
+    /// Process and device names are compared case-insensitively, as Windows treats them that way too.
+    pub fn matches(&self, candidate: &CaptureSourceId) -> bool {
+        match (self, candidate) {
+            (Self::Monitor { device_name }, Self::Monitor { device_name: candidate }) => {
+                device_name.eq_ignore_ascii_case(candidate)
+            }
+            (
+                Self::Window { process_name, title_prefix },
+                Self::Window { process_name: candidate_process, title_prefix: candidate_title },
+            ) => {
+                process_name.eq_ignore_ascii_case(candidate_process)
+                    && candidate_title.starts_with(title_prefix.as_str())
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The source that was shared last, so it can be offered again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedCaptureSource {
+    /// What the source was called when it was saved, for showing to the user.
+    pub name: String,
+    pub id: CaptureSourceId,
+}
+
+/// Finds the first candidate the saved id matches, in the order they are given.
+pub fn find_saved_source<T>(
+    saved: &CaptureSourceId,
+    candidates: impl IntoIterator<Item = (CaptureSourceId, T)>,
+) -> Option<T> {
+    candidates.into_iter().find(|(id, _)| saved.matches(id)).map(|(_, source)| source)
+}
@@ -0,0 +1,67 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// How the changed parts of a frame are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum DirtyRegionMode {
+    /// Only reported, the whole frame is drawn as usual.
+    #[default]
+    ReportOnly,
+    /// Reported, and only the changed parts are drawn, which saves the GPU work on mostly static content.
+    ReportAndRender,
+}
+
+impl DirtyRegionMode {
+    pub const ALL: &[DirtyRegionMode] =
+        &[DirtyRegionMode::ReportOnly, DirtyRegionMode::ReportAndRender];
+}
+
+impl Display for DirtyRegionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReportOnly => f.write_str("Report only"),
+            Self::ReportAndRender => f.write_str("Report and render"),
+        }
+    }
+}
+
+/// Options of the WGC capture session. Each is only set where the version of Windows has it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WgcSessionOptions {
+    /// Draws the yellow border around what is captured.
+    pub border_required: bool,
+    /// Includes the menus, tooltips and dialogs of a captured window, which are windows of their own.
+    pub include_secondary_windows: bool,
+    pub dirty_region_mode: DirtyRegionMode,
+}
+
+impl Default for WgcSessionOptions {
+    fn default() -> Self {
+        Self {
+            border_required: true,
+            include_secondary_windows: true,
+            dirty_region_mode: DirtyRegionMode::ReportOnly,
+        }
+    }
+}
+
+/// A session option as it was set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionOption {
+    BorderRequired(bool),
+    IncludeSecondaryWindows(bool),
+    DirtyRegionMode(DirtyRegionMode),
+}
+
+// Named after the properties of the session, as that is what to look up when one is missing.
+impl Display for SessionOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BorderRequired(value) => write!(f, "IsBorderRequired={}", value),
+            Self::IncludeSecondaryWindows(value) => write!(f, "IncludeSecondaryWindows={}", value),
+            Self::DirtyRegionMode(mode) => write!(f, "DirtyRegionMode={:?}", mode),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use crate::utils::vector2::Vector2;
+
+/// The small icon of a window, for showing next to its title.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowIcon {
+    pub size: Vector2<i32>,
+    /// Straight, not premultiplied, alpha.
+    pub rgba: Arc<[u8]>,
+}
+
+impl WindowIcon {
+    /// Icons bigger than this are not what a window hands out as its small icon, and are left out.
+    pub const MAX_SIZE: i32 = 256;
+
+    /// From the color and mask bitmaps of an icon, both read as top-down rows of 32-bit BGRA pixels.
+    /// Icons without alpha take their transparency from the mask instead, which is white where transparent.
+    /// None if the bitmaps don't match the size.
+    pub fn from_bitmaps(size: Vector2<i32>, color: &[u8], mask: Option<&[u8]>) -> Option<Self> {
+        if size.x <= 0 || size.y <= 0 || size.x > Self::MAX_SIZE || size.y > Self::MAX_SIZE {
+            return None;
+        }
+        let len = size.x as usize * size.y as usize * 4;
+        if color.len() != len || mask.is_some_and(|mask| mask.len() != len) {
+            return None;
+        }
+
+        // Older icons leave the alpha at zero everywhere, which would make them invisible.
+        let has_alpha = color.chunks_exact(4).any(|pixel| pixel[3] != 0);
+        let mut rgba = Vec::with_capacity(len);
+        for (index, pixel) in color.chunks_exact(4).enumerate() {
+            let alpha = match mask {
+                _ if has_alpha => pixel[3],
+                Some(mask) if mask[index * 4] != 0 => 0,
+                _ => u8::MAX,
+            };
+            rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], alpha]);
+        }
+        Some(Self { size, rgba: rgba.into() })
+    }
+}
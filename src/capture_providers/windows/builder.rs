@@ -56,6 +56,6 @@ impl WindowsCaptureProviderBuilder {
             tracing::error!("Attempted to build WindowsCaptureProvider without a device");
             BuilderError::MissingDevice
         })?;
-        Ok(WindowsCaptureProvider::new(device, self.capture_item))
+        Ok(WindowsCaptureProvider::new(&device, self.capture_item)?)
     }
 }
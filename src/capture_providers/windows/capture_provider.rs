@@ -5,7 +5,7 @@ use std::{
 };
 
 use windows::{
-    Foundation::TypedEventHandler,
+    Foundation::{TimeSpan, TypedEventHandler},
     Graphics::{Capture::*, DirectX::Direct3D11::*},
     Win32::{
         Foundation::{LPARAM, WPARAM},
@@ -40,9 +40,17 @@ use crate::{
             error::WindowsCaptureError,
         },
     },
-    utils::UnsafeSendWrapper,
+    utils::agile_ref::AgileRef,
 };
 
+/// The MTA usage cookie is an opaque token that is only handed back to `CoDecrementMTAUsage`, never dereferenced.
+#[derive(Debug)]
+struct MtaUsageCookie(CO_MTA_USAGE_COOKIE);
+
+// SAFETY: see MtaUsageCookie, the token itself carries no thread affinity.
+unsafe impl Send for MtaUsageCookie {}
+unsafe impl Sync for MtaUsageCookie {}
+
 #[derive(Debug, Default)]
 struct StagingState {
     textures: Vec<ID3D11Texture2D>,
@@ -59,7 +67,7 @@ struct CaptureThreadInfo {
 
 #[derive(Debug)]
 pub struct WindowsCaptureProvider {
-    device: IDirect3DDevice,
+    device: AgileRef<IDirect3DDevice>,
     capture_item: Option<GraphicsCaptureItem>,
     staging_state: Arc<RwLock<StagingState>>,
 
@@ -78,20 +86,25 @@ impl WindowsCaptureProvider {
     const PIPELINE_DEPTH: usize = 2;
     const TX_QUEUE_SIZE: usize = 2;
 
-    pub fn new(device: IDirect3DDevice, item: Option<GraphicsCaptureItem>) -> Self {
-        Self {
+    pub fn new(
+        device: &IDirect3DDevice,
+        item: Option<GraphicsCaptureItem>,
+    ) -> super::Result<Self> {
+        let device =
+            AgileRef::new(device).map_err(WindowsCaptureError::FailedToCreateAgileReference)?;
+        Ok(Self {
             device,
             capture_item: item,
             staging_state: Arc::new(RwLock::new(StagingState::default())),
             frame_sender: Arc::new(Mutex::new(None)),
             capture_thread: None,
             capturing: false,
-        }
+        })
     }
 
     /// This function runs on the dedicated capture thread.
     fn capture_loop(
-        device: UnsafeSendWrapper<IDirect3DDevice>,
+        device: AgileRef<IDirect3DDevice>,
         item: GraphicsCaptureItem,
         framerate: CaptureFramerate,
         staging_state: Arc<RwLock<StagingState>>,
@@ -99,9 +112,9 @@ impl WindowsCaptureProvider {
         ready_tx: std::sync::mpsc::Sender<super::Result<u32>>,
     ) {
         unsafe {
-            static INIT_MTA: OnceLock<UnsafeSendWrapper<CO_MTA_USAGE_COOKIE>> = OnceLock::new();
+            static INIT_MTA: OnceLock<MtaUsageCookie> = OnceLock::new();
             INIT_MTA.get_or_init(|| {
-                UnsafeSendWrapper(CoIncrementMTAUsage().expect("Failed to increment MTA usage"))
+                MtaUsageCookie(CoIncrementMTAUsage().expect("Failed to increment MTA usage"))
             });
 
             // Initialize WinRT for this thread
@@ -127,6 +140,17 @@ impl WindowsCaptureProvider {
                 }
             };
 
+            // Resolve the device into this thread's apartment
+            let device = match device.resolve() {
+                Ok(d) => d,
+                Err(e) => {
+                    ready_tx
+                        .send(Err(WindowsCaptureError::FailedToResolveAgileReference(e)))
+                        .ok();
+                    return;
+                }
+            };
+
             let size = match item.Size() {
                 Ok(s) => s,
                 Err(e) => {
@@ -137,7 +161,7 @@ impl WindowsCaptureProvider {
 
             // Create FramePool on this thread
             let frame_pool = match Direct3D11CaptureFramePool::Create(
-                &device.0,
+                &device,
                 Self::PIXEL_FORMAT.to_directx_pixel_format(),
                 Self::WGC_FRAME_BUFFERS,
                 size,
@@ -159,7 +183,10 @@ impl WindowsCaptureProvider {
             };
             session.SetIsCursorCaptureEnabled(true).ok();
             session.SetIsBorderRequired(true).ok();
-            session.SetMinUpdateInterval(framerate.to_frametime().into()).ok();
+            session.SetMinUpdateInterval(TimeSpan {
+                Duration: framerate.to_framerate().frametime_ticks(),
+            })
+            .ok();
 
             // Set up Frame Arrived Handler
             let staging_state_clone = staging_state.clone();
@@ -226,7 +253,7 @@ impl WindowsCaptureProvider {
             };
 
             if let Some(mta_cookie) = INIT_MTA.get() {
-                if let Err(err) = CoDecrementMTAUsage(**mta_cookie) {
+                if let Err(err) = CoDecrementMTAUsage(mta_cookie.0) {
                     tracing::error!("Failed to decrement MTA usage: {}", err);
                 }
             }
@@ -275,7 +302,13 @@ impl WindowsCaptureProvider {
             }
         };
 
-        tracing::trace!("Frame: {} x {}, ptr={:?}", size.Width, size.Height, texture.as_raw());
+        crate::log_throttled!(
+            tracing::Level::TRACE,
+            "Frame: {} x {}, ptr={:?}",
+            size.Width,
+            size.Height,
+            texture.as_raw()
+        );
 
         let device = unsafe {
             match texture.GetDevice() {
@@ -414,7 +447,7 @@ impl WindowsCaptureProvider {
                     tracing::warn!("Frame sender closed whilst trying to send frame.");
                 }
                 Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
-                    tracing::debug!("Frame channel full, dropping frame.");
+                    crate::log_throttled!(tracing::Level::DEBUG, "Frame channel full, dropping frame.");
                 }
             }
         }
@@ -470,7 +503,7 @@ impl CaptureProvider for WindowsCaptureProvider {
             }
         };
 
-        let device = UnsafeSendWrapper(self.device.clone());
+        let device = self.device.clone();
         let staging_state = self.staging_state.clone();
         let frame_sender = self.frame_sender.clone();
 
@@ -567,6 +600,4 @@ impl Drop for WindowsCaptureProvider {
     }
 }
 
-// WindowsCaptureProvider holds agile COM objects that are thread-safe.
-unsafe impl Send for WindowsCaptureProvider {}
-unsafe impl Sync for WindowsCaptureProvider {}
+// No unsafe Send/Sync impls needed: the device is held through an AgileRef and the rest is agile.
@@ -0,0 +1,29 @@
+use windows::Graphics::Capture::GraphicsCaptureItem;
+
+use crate::utils::rect::Rect;
+
+/// What to capture: a single monitor or window, or a fixed region of the virtual desktop.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaptureSource {
+    Item(GraphicsCaptureItem),
+    /// In virtual desktop coordinates. Captured from every monitor it spans, and put back together.
+    VirtualRegion(Rect<i32>),
+}
+
+impl CaptureSource {
+    /// What the source is called, for showing to the user.
+    pub fn name(&self) -> String {
+        match self {
+            Self::Item(item) => item.DisplayName().map(|name| name.to_string()).unwrap_or_default(),
+            Self::VirtualRegion(region) => {
+                format!("Desktop region ({}x{})", region.size.x, region.size.y)
+            }
+        }
+    }
+}
+
+impl From<GraphicsCaptureItem> for CaptureSource {
+    fn from(item: GraphicsCaptureItem) -> Self {
+        Self::Item(item)
+    }
+}
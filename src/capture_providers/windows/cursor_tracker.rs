@@ -0,0 +1,159 @@
+use fjarsyn_shared::CursorPosition;
+use windows::{
+    Graphics::Capture::GraphicsCaptureItem,
+    Win32::{
+        Foundation::{HWND, RECT},
+        Graphics::Gdi::{GetMonitorInfoW, MONITORINFO},
+        System::WinRT::Graphics::Capture::IGraphicsCaptureItemInterop,
+        UI::WindowsAndMessaging::{
+            CURSOR_SHOWING, CURSORINFO, GetCursorInfo, GetWindowRect, IsWindowVisible,
+        },
+    },
+};
+
+use super::{
+    CaptureSource, Result, WindowsCaptureError,
+    sources::{monitor_handles, monitor_layout, window_handles, window_title},
+};
+use crate::utils::{
+    dpi::{self, CaptureTransform, DisplayLayout},
+    rect::Rect,
+    vector2::Vector2,
+};
+
+// What the capture item refers to, so we know where it is on the virtual desktop.
+#[derive(Debug, Clone, Copy)]
+enum CursorTarget {
+    // Monitors don't move, so their bounds are resolved once.
+    Monitor(RECT),
+    // Neither do regions of the desktop.
+    Region(RECT),
+    // Windows do, so we keep the handle instead.
+    // Stored as its raw value, as a handle is just an identifier and this keeps the tracker Send.
+    Window(usize),
+}
+
+/// Tracks the system cursor relative to a capture item.
+#[derive(Debug, Clone)]
+pub struct CursorTracker {
+    target: CursorTarget,
+    // Like the monitors, resolved once.
+    layout: DisplayLayout,
+}
+
+impl CursorTracker {
+    /// Resolves the monitor or window behind the capture item.
+    /// The picker doesn't tell us which one was picked, so it is matched by name and size.
+    pub fn new(source: &CaptureSource) -> Result<Self> {
+        let layout = DisplayLayout::new(monitor_layout()?, dpi::screen_space());
+        let item = match source {
+            CaptureSource::Item(item) => item,
+            CaptureSource::VirtualRegion(region) => {
+                let target = CursorTarget::Region(RECT {
+                    left: region.position.x,
+                    top: region.position.y,
+                    right: region.right(),
+                    bottom: region.bottom(),
+                });
+                return Ok(Self { target, layout });
+            }
+        };
+
+        let target = if let Some(monitor_rect) = find_monitor(item)? {
+            CursorTarget::Monitor(monitor_rect)
+        } else if let Some(window) = find_window(item)? {
+            CursorTarget::Window(window.0 as usize)
+        } else {
+            return Err(WindowsCaptureError::CaptureItemTargetNotFound);
+        };
+
+        tracing::debug!("Tracking cursor for capture item target: {:?}", target);
+        Ok(Self { target, layout })
+    }
+
+    /// Returns the cursor position normalized to the capture item, or None if it is hidden or outside of it.
+    pub fn poll(&self) -> Option<CursorPosition> {
+        let mut info =
+            CURSORINFO { cbSize: std::mem::size_of::<CURSORINFO>() as u32, ..Default::default() };
+        unsafe { GetCursorInfo(&mut info) }.ok()?;
+        if info.flags.0 & CURSOR_SHOWING.0 == 0 {
+            return None;
+        }
+
+        let point = Vector2::new(info.ptScreenPos.x, info.ptScreenPos.y);
+        let position = self.transform()?.to_capture(point)?;
+        Some(CursorPosition { x: position.x, y: position.y })
+    }
+
+    /// Maps positions in the captured content to the screen and back, for where the capture item is now.
+    pub fn transform(&self) -> Option<CaptureTransform> {
+        Some(CaptureTransform::new(self.capture_bounds()?, self.layout.clone()))
+    }
+
+    /// The captured region in virtual desktop coordinates.
+    pub fn capture_bounds(&self) -> Option<Rect<i32>> {
+        let rect = self.target_rect()?;
+        Some(Rect {
+            position: Vector2::new(rect.left, rect.top),
+            size: Vector2::new(rect.right - rect.left, rect.bottom - rect.top),
+        })
+    }
+
+    fn target_rect(&self) -> Option<RECT> {
+        match self.target {
+            CursorTarget::Monitor(rect) | CursorTarget::Region(rect) => Some(rect),
+            CursorTarget::Window(handle) => {
+                // Includes the invisible resize borders, so this is off by a few pixels at the edges.
+                let mut rect = RECT::default();
+                unsafe { GetWindowRect(HWND(handle as *mut core::ffi::c_void), &mut rect) }.ok()?;
+                Some(rect)
+            }
+        }
+    }
+}
+
+fn is_same_item(item: &GraphicsCaptureItem, candidate: &GraphicsCaptureItem) -> Result<bool> {
+    Ok(item.DisplayName()? == candidate.DisplayName()? && item.Size()? == candidate.Size()?)
+}
+
+fn find_monitor(item: &GraphicsCaptureItem) -> Result<Option<RECT>> {
+    let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+    for monitor in monitor_handles()? {
+        let candidate: GraphicsCaptureItem = unsafe { interop.CreateForMonitor(monitor)? };
+        if !is_same_item(item, &candidate)? {
+            continue;
+        }
+
+        let mut info =
+            MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+        unsafe { GetMonitorInfoW(monitor, &mut info) }.ok()?;
+        return Ok(Some(info.rcMonitor));
+    }
+
+    Ok(None)
+}
+
+pub(super) fn find_window(item: &GraphicsCaptureItem) -> Result<Option<HWND>> {
+    let name = item.DisplayName()?.to_string_lossy();
+    let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+    for window in window_handles()? {
+        if !unsafe { IsWindowVisible(window) }.as_bool() {
+            continue;
+        }
+
+        if window_title(window) != name {
+            continue;
+        }
+
+        // Not every window can be captured, so skip the ones that fail.
+        let Ok(candidate) = (unsafe { interop.CreateForWindow::<GraphicsCaptureItem>(window) })
+        else {
+            continue;
+        };
+        if is_same_item(item, &candidate)? {
+            return Ok(Some(window));
+        }
+    }
+
+    Ok(None)
+}
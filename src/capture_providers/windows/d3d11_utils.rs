@@ -1,36 +1,57 @@
-use std::mem::MaybeUninit;
+use std::{mem::MaybeUninit, sync::OnceLock};
 
 use windows::{
     Graphics::{
-        Capture::{GraphicsCaptureItem, GraphicsCapturePicker},
+        Capture::{GraphicsCaptureItem, GraphicsCapturePicker, GraphicsCaptureSession},
         DirectX::Direct3D11::IDirect3DDevice,
     },
     Win32::{
-        Foundation::{HMODULE, HWND},
+        Foundation::{E_POINTER, HMODULE, HWND},
         Graphics::{
             Direct3D::{
-                D3D_DRIVER_TYPE_HARDWARE, D3D_FEATURE_LEVEL, D3D_FEATURE_LEVEL_10_0,
-                D3D_FEATURE_LEVEL_10_1, D3D_FEATURE_LEVEL_11_0, D3D_FEATURE_LEVEL_11_1,
+                D3D_DRIVER_TYPE, D3D_DRIVER_TYPE_HARDWARE, D3D_DRIVER_TYPE_WARP, D3D_FEATURE_LEVEL,
+                D3D_FEATURE_LEVEL_10_0, D3D_FEATURE_LEVEL_10_1, D3D_FEATURE_LEVEL_11_0,
+                D3D_FEATURE_LEVEL_11_1,
             },
             Direct3D11::{
-                D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_MAP_READ, D3D11_SDK_VERSION,
-                D3D11_TEXTURE2D_DESC, D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext,
-                ID3D11Texture2D,
+                D3D11_ASYNC_GETDATA_DONOTFLUSH, D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_MAP_READ,
+                D3D11_QUERY_DESC, D3D11_QUERY_EVENT, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC,
+                D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Query, ID3D11Texture2D,
             },
             Dxgi::IDXGIDevice,
             Gdi::{MONITOR_DEFAULTTOPRIMARY, MonitorFromWindow},
         },
-        System::WinRT::{
-            Direct3D11::{CreateDirect3D11DeviceFromDXGIDevice, IDirect3DDxgiInterfaceAccess},
-            Graphics::Capture::IGraphicsCaptureItemInterop,
+        System::{
+            Com::{
+                APTTYPE, APTTYPEQUALIFIER, CO_MTA_USAGE_COOKIE, CoDecrementMTAUsage,
+                CoGetApartmentType, CoIncrementMTAUsage,
+            },
+            WinRT::{
+                Direct3D11::{CreateDirect3D11DeviceFromDXGIDevice, IDirect3DDxgiInterfaceAccess},
+                Graphics::Capture::IGraphicsCaptureItemInterop,
+            },
         },
         UI::Shell::IInitializeWithWindow,
     },
 };
 use windows_core::*;
 
+use crate::{
+    capture_providers::shared::GraphicsAdapter,
+    utils::{bitmap_utils, orientation::Orientation, vector2::Vector2},
+};
+
+/// Creates a D3D11 device on the default hardware adapter,
+/// falling back to the WARP software adapter if no hardware device can be created (remote sessions, broken drivers, VMs).
 pub(super) fn create_d3d_device() -> Result<ID3D11Device> {
-    tracing::debug!("Creating D3D11 device...");
+    create_d3d_device_with_driver(D3D_DRIVER_TYPE_HARDWARE).or_else(|err| {
+        tracing::warn!("Failed to create hardware D3D11 device, falling back to WARP: {}", err);
+        create_d3d_device_with_driver(D3D_DRIVER_TYPE_WARP)
+    })
+}
+
+fn create_d3d_device_with_driver(driver_type: D3D_DRIVER_TYPE) -> Result<ID3D11Device> {
+    tracing::debug!("Creating D3D11 device with driver type {:?}...", driver_type);
     const FEATURE_LEVELS: &[D3D_FEATURE_LEVEL] = &[
         D3D_FEATURE_LEVEL_11_1,
         D3D_FEATURE_LEVEL_11_0,
@@ -45,7 +66,7 @@ pub(super) fn create_d3d_device() -> Result<ID3D11Device> {
     unsafe {
         D3D11CreateDevice(
             None, // adapter
-            D3D_DRIVER_TYPE_HARDWARE,
+            driver_type,
             HMODULE(std::ptr::null_mut()),    // no software rasterizer
             D3D11_CREATE_DEVICE_BGRA_SUPPORT, // flags
             Some(FEATURE_LEVELS),             // feature levels
@@ -56,23 +77,56 @@ pub(super) fn create_d3d_device() -> Result<ID3D11Device> {
         )?;
     }
 
-    let device = device.expect("ID3D11Device");
-    let dxgi_device: IDXGIDevice = device.cast()?;
-    let adapter = unsafe { dxgi_device.GetAdapter()? };
-
-    let desc = unsafe { adapter.GetDesc()? };
-    let description = String::from_utf16_lossy(&desc.Description);
-    let description = description.trim_matches(char::from(0));
-
+    let device = device.ok_or_else(|| Error::from(E_POINTER))?;
     tracing::info!(
         "D3D11 device created successfully on adapter: '{}' with feature level: {:?}",
-        description,
+        adapter_description(&device)?,
         chosen_level
     );
 
     Ok(device)
 }
 
+fn adapter_description(device: &ID3D11Device) -> Result<String> {
+    let dxgi_device: IDXGIDevice = device.cast()?;
+    let adapter = unsafe { dxgi_device.GetAdapter()? };
+    let desc = unsafe { adapter.GetDesc()? };
+    Ok(String::from_utf16_lossy(&desc.Description).trim_matches(char::from(0)).to_owned())
+}
+
+/// Creates a device the way the capture does, and describes the adapter it ended up on, e.g. for diagnostics.
+pub fn describe_d3d_device() -> Result<String> {
+    let device = create_d3d_device()?;
+    // E.g. 0xb100 for 11_1.
+    let level = unsafe { device.GetFeatureLevel() }.0;
+    Ok(format!(
+        "{}, feature level {}_{}",
+        adapter_description(&device)?,
+        level >> 12,
+        (level >> 8) & 0xf
+    ))
+}
+
+/// Creates a device the way the capture does, and identifies the adapter and driver it ended up on.
+pub fn graphics_adapter() -> Result<GraphicsAdapter> {
+    let device = create_d3d_device()?;
+    let dxgi_device: IDXGIDevice = device.cast()?;
+    let adapter = unsafe { dxgi_device.GetAdapter()? };
+    let desc = unsafe { adapter.GetDesc()? };
+    // Asked about IDXGIDevice, which every adapter supports, this gives the version of the user mode driver.
+    let driver_version = unsafe { adapter.CheckInterfaceSupport(&IDXGIDevice::IID) }
+        .inspect_err(|e| tracing::debug!("Failed to get the driver version: {}", e))
+        .ok()
+        .map(|version| version as u64);
+    Ok(GraphicsAdapter { vendor_id: desc.VendorId, device_id: desc.DeviceId, driver_version })
+}
+
+/// Whether this version of Windows supports Windows.Graphics.Capture.
+pub fn is_capture_supported() -> Result<bool> {
+    ensure_mta()?;
+    GraphicsCaptureSession::IsSupported()
+}
+
 pub(super) fn native_to_winrt_d3d11device(device: &ID3D11Device) -> Result<IDirect3DDevice> {
     tracing::trace!("Converting native D3D11 device to WinRT D3D11 device");
     let dxgi_device: IDXGIDevice = device.cast()?;
@@ -89,6 +143,46 @@ pub(super) fn winrt_to_native_d3d11device(device: &IDirect3DDevice) -> Result<ID
     }
 }
 
+/// The MTA usage cookie is an opaque token that is only handed back to `CoDecrementMTAUsage`, never dereferenced.
+#[derive(Debug)]
+struct MtaUsageCookie(CO_MTA_USAGE_COOKIE);
+
+// SAFETY: see MtaUsageCookie, the token itself carries no thread affinity.
+unsafe impl Send for MtaUsageCookie {}
+unsafe impl Sync for MtaUsageCookie {}
+
+/// Makes sure the calling thread can use COM, by keeping the process-wide MTA alive.
+/// Threads without an explicit apartment (like tokio workers) then run in the implicit MTA.
+/// The MTA is only kept alive once, and never released, so this lasts for the rest of the process.
+pub(super) fn ensure_mta() -> Result<()> {
+    static MTA_USAGE: OnceLock<MtaUsageCookie> = OnceLock::new();
+    if MTA_USAGE.get().is_some() {
+        return Ok(());
+    }
+    let cookie = unsafe { CoIncrementMTAUsage() }?;
+    // Another thread got there first, so this usage isn't needed.
+    if let Err(MtaUsageCookie(cookie)) = MTA_USAGE.set(MtaUsageCookie(cookie)) {
+        unsafe { CoDecrementMTAUsage(cookie) }?;
+    }
+    Ok(())
+}
+
+/// Asserts (in debug builds) that the calling thread belongs to a COM apartment.
+/// WinRT capture objects resolved or created on a thread without one are a latent marshalling bug.
+pub(super) fn debug_assert_com_apartment() {
+    if cfg!(debug_assertions) {
+        let mut apartment_type = APTTYPE::default();
+        let mut qualifier = APTTYPEQUALIFIER::default();
+        let result = unsafe { CoGetApartmentType(&mut apartment_type, &mut qualifier) };
+        debug_assert!(
+            result.is_ok(),
+            "Capture objects used outside of a COM apartment: {:?}",
+            result
+        );
+        tracing::trace!("Current COM apartment: {:?} ({:?})", apartment_type, qualifier);
+    }
+}
+
 pub trait IntoHWND {
     fn into_hwnd(self) -> HWND;
 }
@@ -151,7 +245,40 @@ pub(super) fn copy_texture(
     }
 }
 
-// Map the staging texture for reading and copy the data to memory.
+/// Creates a query that signals once the GPU has finished the commands issued before it.
+pub(super) fn create_event_query(device: &ID3D11Device) -> super::Result<ID3D11Query> {
+    let desc = D3D11_QUERY_DESC { Query: D3D11_QUERY_EVENT, MiscFlags: 0 };
+    let mut query = None;
+    unsafe { device.CreateQuery(&desc, Some(&mut query)) }.map_err(|e| {
+        super::WindowsCaptureError::from_device(e, super::WindowsCaptureError::FailedToCreateQuery)
+    })?;
+    Ok(query.expect("CreateQuery succeeded without a query"))
+}
+
+// Marks the end of the commands issued so far, e.g. a copy, and submits them so the GPU gets going on them right away.
+pub(super) fn end_query(context: &ID3D11DeviceContext, query: &ID3D11Query) {
+    unsafe {
+        context.End(query);
+        context.Flush();
+    }
+}
+
+// Whether the GPU has passed the query, without waiting for it.
+pub(super) fn is_query_done(context: &ID3D11DeviceContext, query: &ID3D11Query) -> bool {
+    let mut done = BOOL(0);
+    // S_FALSE, which means not done yet, is a success too, so the data has to be checked.
+    let result = unsafe {
+        context.GetData(
+            query,
+            Some((&mut done as *mut BOOL).cast()),
+            std::mem::size_of::<BOOL>() as u32,
+            D3D11_ASYNC_GETDATA_DONOTFLUSH.0 as u32,
+        )
+    };
+    result.is_ok() && done.as_bool()
+}
+
+// Map the staging texture for reading and copy the data to memory, rotated by `orientation`.
 // This operation happens on the CPU.
 pub(super) fn map_read_texture(
     memory: &mut [u8],
@@ -159,12 +286,18 @@ pub(super) fn map_read_texture(
     staging_tex: &ID3D11Texture2D,
     tex_desc: &D3D11_TEXTURE2D_DESC,
     bytes_per_pixel: u32,
+    orientation: Orientation,
 ) -> super::Result<()> {
     let start = std::time::Instant::now();
     unsafe {
         let map_start = std::time::Instant::now();
         let mut mapped = MaybeUninit::uninit();
-        context.Map(staging_tex, 0, D3D11_MAP_READ, 0, Some(mapped.as_mut_ptr()))?;
+        context.Map(staging_tex, 0, D3D11_MAP_READ, 0, Some(mapped.as_mut_ptr())).map_err(|e| {
+            super::WindowsCaptureError::from_device(
+                e,
+                super::WindowsCaptureError::FailedToMapTexture,
+            )
+        })?;
         let mapped = mapped.assume_init_ref();
         let map_duration = map_start.elapsed();
 
@@ -174,7 +307,20 @@ pub(super) fn map_read_texture(
         let total_bytes = bytes_per_row * height;
 
         let copy_start = std::time::Instant::now();
-        if row_pitch == bytes_per_row {
+        if !orientation.is_upright() {
+            // Every pixel goes somewhere else, so there are no rows to copy whole.
+            let size = Vector2::new(tex_desc.Width as i32, height as i32);
+            let src_len = row_pitch * (height - 1) + bytes_per_row;
+            let src = std::slice::from_raw_parts(mapped.pData.cast::<u8>(), src_len);
+            bitmap_utils::rotate(
+                src,
+                row_pitch,
+                size,
+                &mut memory[..total_bytes],
+                orientation,
+                bytes_per_pixel as usize,
+            );
+        } else if row_pitch == bytes_per_row {
             // Optimization: If the pitch matches the width, we can copy the entire buffer in one go.
             std::ptr::copy_nonoverlapping(mapped.pData.cast(), memory.as_mut_ptr(), total_bytes);
         } else {
@@ -214,5 +360,5 @@ pub(super) fn fetch_texture(
     bytes_per_pixel: u32,
 ) -> super::Result<()> {
     copy_texture(context, &source_tex, &staging_tex);
-    map_read_texture(dest, context, &staging_tex, tex_desc, bytes_per_pixel)
+    map_read_texture(dest, context, &staging_tex, tex_desc, bytes_per_pixel, Orientation::Upright)
 }
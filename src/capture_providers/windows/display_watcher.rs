@@ -0,0 +1,144 @@
+use std::{
+    cell::RefCell,
+    sync::Once,
+    thread::{self, JoinHandle},
+};
+
+use windows::{
+    Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+        System::{LibraryLoader::GetModuleHandleW, Threading::GetCurrentThreadId},
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW, MSG,
+            PostThreadMessageW, RegisterClassW, WINDOW_EX_STYLE, WINDOW_STYLE, WM_DISPLAYCHANGE,
+            WM_QUIT, WNDCLASSW,
+        },
+    },
+    core::{PCWSTR, w},
+};
+
+use super::{Result, WindowsCaptureError};
+
+const CLASS_NAME: PCWSTR = w!("FjarsynDisplayWatcher");
+
+thread_local! {
+    // Set on the watcher's thread, which the window procedure runs on.
+    static ON_CHANGE: RefCell<Option<Box<dyn Fn()>>> = const { RefCell::new(None) };
+}
+
+/// Calls back, on a thread of its own, every time a display is added or removed or changes resolution, until dropped.
+/// Only top-level windows are told, so it has a hidden one.
+#[derive(Debug)]
+pub(super) struct DisplayWatcher {
+    thread_id: u32,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DisplayWatcher {
+    pub(super) fn new(on_change: impl Fn() + Send + 'static) -> Result<Self> {
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("display-watcher".to_owned())
+            .spawn(move || {
+                let window = match create_window() {
+                    Ok(window) => window,
+                    Err(e) => {
+                        ready_tx.send(Err(e)).ok();
+                        return;
+                    }
+                };
+                ON_CHANGE.set(Some(Box::new(on_change)));
+                ready_tx.send(Ok(unsafe { GetCurrentThreadId() })).ok();
+
+                // Until the WM_QUIT posted when the watcher is dropped.
+                let mut message = MSG::default();
+                while unsafe { GetMessageW(&mut message, None, 0, 0) }.as_bool() {
+                    unsafe { DispatchMessageW(&message) };
+                }
+                if let Err(e) = unsafe { DestroyWindow(window) } {
+                    tracing::warn!("Failed to destroy the display watcher window: {}", e);
+                }
+            })
+            .expect("failed to spawn display watcher thread");
+
+        match ready_rx.recv() {
+            Ok(Ok(thread_id)) => Ok(Self { thread_id, thread: Some(thread) }),
+            Ok(Err(e)) => {
+                thread.join().ok();
+                Err(e)
+            }
+            Err(_) => {
+                thread.join().ok();
+                Err(WindowsCaptureError::DisplayWatcherFailed)
+            }
+        }
+    }
+}
+
+impl Drop for DisplayWatcher {
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0)) }
+        {
+            tracing::error!("Failed to stop the display watcher: {}", e);
+            // Joining would wait forever.
+            return;
+        }
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            tracing::error!("The display watcher panicked");
+        }
+    }
+}
+
+fn create_window() -> Result<HWND> {
+    let instance = unsafe { GetModuleHandleW(None) }?;
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance.into(),
+            lpszClassName: CLASS_NAME,
+            ..Default::default()
+        };
+        // Creating the window fails too if this did, with the error that matters.
+        if unsafe { RegisterClassW(&class) } == 0 {
+            tracing::warn!("Failed to register the display watcher window class");
+        }
+    });
+
+    let window = unsafe {
+        CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            CLASS_NAME,
+            w!(""),
+            WINDOW_STYLE(0),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            Some(instance.into()),
+            None,
+        )
+    }?;
+    Ok(window)
+}
+
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if message == WM_DISPLAYCHANGE {
+        tracing::debug!("Displays changed");
+        ON_CHANGE.with_borrow(|on_change| {
+            if let Some(on_change) = on_change {
+                on_change();
+            }
+        });
+    }
+    unsafe { DefWindowProcW(window, message, wparam, lparam) }
+}
@@ -1,9 +1,16 @@
+use windows::Win32::Graphics::Dxgi::{
+    DXGI_ERROR_DEVICE_HUNG, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET,
+    DXGI_ERROR_DRIVER_INTERNAL_ERROR,
+};
+
 pub type Result<T> = std::result::Result<T, WindowsCaptureError>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum WindowsCaptureError {
     #[error("Already capturing")]
     AlreadyCapturing,
+    #[error("A stream is already active")]
+    StreamAlreadyActive,
     #[error("Not capturing")]
     NotCapturing,
     #[error("No frame pool available")]
@@ -36,6 +43,8 @@ pub enum WindowsCaptureError {
     DuplicateOutputFailed(windows_core::Error),
     #[error("Failed to create texture: {0}")]
     FailedToCreateTexture(windows_core::Error),
+    #[error("Failed to create query: {0}")]
+    FailedToCreateQuery(windows_core::Error),
     #[error("Failed to map texture: {0}")]
     FailedToMapTexture(windows_core::Error),
     #[error("Failed to get monitor output: {0}")]
@@ -46,8 +55,24 @@ pub enum WindowsCaptureError {
     FailedToStartCapture(windows_core::Error),
     #[error("Failed to process frame")]
     FailedToProcessFrame(Box<WindowsCaptureError>),
+    #[error("Failed to create agile reference: {0}")]
+    FailedToCreateAgileReference(windows_core::Error),
+    #[error("Failed to resolve agile reference: {0}")]
+    FailedToResolveAgileReference(windows_core::Error),
     #[error("Windows smart pointer cast failed: {0}")]
     CastFailed(windows_core::Error),
+    #[error("The region to capture is outside of every monitor")]
+    RegionOutsideMonitors,
+    #[error("Could not find the monitor or window behind the capture item")]
+    CaptureItemTargetNotFound,
+    #[error("The shared display was disconnected")]
+    DisplayDisconnected,
+    #[error("The graphics device was lost: {0}")]
+    DeviceLost(windows_core::Error),
+    #[error("Failed to start watching the displays")]
+    DisplayWatcherFailed,
+    #[error("Timed out waiting for a frame of the capture item")]
+    ScreenshotTimedOut,
     #[error("Invalid staging depth, staging depth can't be less than 1")]
     InvalidStagingDepth,
     #[error("Frame sender closed")]
@@ -55,3 +80,20 @@ pub enum WindowsCaptureError {
     #[error("Unknown Windows error: {0}")]
     UnknownWindowsError(#[from] windows_core::Error),
 }
+
+impl WindowsCaptureError {
+    /// The error of a call on the device, as [`Self::DeviceLost`] if it failed because the device is gone,
+    /// e.g. after a display change or a driver update, as only a new device gets the capture going again.
+    pub fn from_device(
+        error: windows_core::Error,
+        otherwise: fn(windows_core::Error) -> Self,
+    ) -> Self {
+        let lost = [
+            DXGI_ERROR_DEVICE_REMOVED,
+            DXGI_ERROR_DEVICE_RESET,
+            DXGI_ERROR_DEVICE_HUNG,
+            DXGI_ERROR_DRIVER_INTERNAL_ERROR,
+        ];
+        if lost.contains(&error.code()) { Self::DeviceLost(error) } else { otherwise(error) }
+    }
+}
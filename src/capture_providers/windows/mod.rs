@@ -1,15 +1,33 @@
 //mod builder;
 //mod capture_provider;
+mod capture_source;
 mod capture_stream;
+mod cursor_tracker;
 mod d3d11_utils;
+mod display_watcher;
 pub(super) mod error;
+mod screenshot;
+mod session_options;
+mod sources;
+mod thread_priority;
 mod wgc_capture_provider;
 mod wgc_capture_provider_builder;
 
 //pub use builder::{BuilderError, WindowsCaptureProviderBuilder};
 //pub use capture_provider::WindowsCaptureProvider;
+pub use capture_source::CaptureSource;
 pub use capture_stream::WindowsCaptureStream;
-pub use d3d11_utils::{create_capture_item_for_primary_monitor, user_pick_capture_item};
+pub use cursor_tracker::CursorTracker;
+pub use d3d11_utils::{
+    create_capture_item_for_primary_monitor, describe_d3d_device, graphics_adapter,
+    is_capture_supported, user_pick_capture_item,
+};
 pub(self) use error::{Result, WindowsCaptureError};
+pub use screenshot::Screenshotter;
+pub use session_options::supported_session_options;
+pub use sources::{
+    SourceDescriptor, enumerate_sources, exclude_window_from_capture, find_source,
+    monitor_geometry, monitor_layout, saved_capture_item, window_bounds,
+};
 pub use wgc_capture_provider::WgcCaptureProvider;
 pub use wgc_capture_provider_builder::{WgcCaptureProviderBuilder, WgcCaptureProviderBuilderError};
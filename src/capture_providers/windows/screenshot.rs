@@ -0,0 +1,202 @@
+use std::{
+    mem::MaybeUninit,
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use windows::{
+    Foundation::TypedEventHandler,
+    Graphics::{
+        Capture::{Direct3D11CaptureFrame, Direct3D11CaptureFramePool, GraphicsCaptureItem},
+        DirectX::Direct3D11::IDirect3DDevice,
+    },
+    Win32::{
+        Foundation::E_POINTER,
+        Graphics::Direct3D11::{
+            D3D11_CPU_ACCESS_READ, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING, ID3D11Device,
+            ID3D11Texture2D,
+        },
+        System::WinRT::Direct3D11::IDirect3DDxgiInterfaceAccess,
+    },
+};
+use windows_core::Interface;
+
+use super::{
+    CaptureSource, Result, WindowsCaptureError,
+    d3d11_utils::{
+        copy_texture, create_d3d_device, debug_assert_com_apartment, ensure_mta, map_read_texture,
+        native_to_winrt_d3d11device,
+    },
+    sources::region_monitors,
+};
+use crate::{
+    capture_providers::shared::RegionComposite,
+    utils::{
+        bitmap_utils::{fit_size, resize_nearest},
+        buffer_arena::BufferRef,
+        frame::Frame,
+        orientation::Orientation,
+        pixel_format::PixelFormat,
+        vector2::Vector2,
+    },
+};
+
+/// Grabs single frames of capture items, e.g. for previews.
+/// Uses its own device, so it can be used on any thread while a capture is running.
+#[derive(Debug)]
+pub struct Screenshotter {
+    native_device: ID3D11Device,
+    device: IDirect3DDevice,
+}
+
+impl Screenshotter {
+    // Windows that are minimized never produce a frame, so don't wait for them forever.
+    const FRAME_TIMEOUT: Duration = Duration::from_secs(1);
+    const PIXEL_FORMAT: PixelFormat = PixelFormat::BGRA8;
+
+    /// Creates the graphics device. This blocks, so keep it off the UI thread.
+    pub fn new() -> Result<Self> {
+        ensure_mta()?;
+        let native_device = create_d3d_device()?;
+        let device = native_to_winrt_d3d11device(&native_device)?;
+        Ok(Self { native_device, device })
+    }
+
+    /// Captures the next frame of the source, downscaled to fit within `max_size`.
+    /// Blocks until the frame arrives, or until a frame of every monitor a region spans has.
+    pub fn capture(&self, source: &CaptureSource, max_size: Vector2<i32>) -> Result<Frame> {
+        let (full, full_size) = match source {
+            CaptureSource::Item(item) => self.capture_item(item)?,
+            CaptureSource::VirtualRegion(region) => {
+                let (region, monitors) = region_monitors(*region)?;
+                let mut composite = RegionComposite::new(
+                    region,
+                    monitors.iter().map(|(_, bounds)| *bounds),
+                    Self::PIXEL_FORMAT.bytes_per_pixel() as usize,
+                    Duration::ZERO,
+                );
+                for (index, (item, _)) in monitors.iter().enumerate() {
+                    let (data, size) = self.capture_item(item)?;
+                    composite.submit(index, &data, size, None, Instant::now());
+                }
+                let mut full = vec![0u8; composite.len()];
+                composite.finish(&mut full);
+                (full, composite.size())
+            }
+        };
+
+        let bytes_per_pixel = Self::PIXEL_FORMAT.bytes_per_pixel() as usize;
+        let size = fit_size(full_size, max_size);
+        let mut data = BytesMut::zeroed(size.x as usize * size.y as usize * bytes_per_pixel);
+        resize_nearest(&full, full_size, &mut data, size, bytes_per_pixel);
+
+        Ok(Frame::new_ensure_rgba(BufferRef::detached(data), Self::PIXEL_FORMAT, size, None, None))
+    }
+
+    // The next frame of the item at full size.
+    fn capture_item(&self, item: &GraphicsCaptureItem) -> Result<(Vec<u8>, Vector2<i32>)> {
+        debug_assert_com_apartment();
+
+        let size = item.Size().map_err(WindowsCaptureError::FailedToGetCaptureItemSize)?;
+        let frame_pool = Direct3D11CaptureFramePool::CreateFreeThreaded(
+            &self.device,
+            Self::PIXEL_FORMAT.to_directx_pixel_format(),
+            1,
+            size,
+        )
+        .map_err(WindowsCaptureError::FailedToCreateFramePool)?;
+
+        let session = frame_pool
+            .CreateCaptureSession(item)
+            .map_err(WindowsCaptureError::FailedToCreateCaptureSession)?;
+
+        // Neither belongs in a preview, and without the border there is no flash around the source.
+        if let Err(e) = session.SetIsBorderRequired(false) {
+            tracing::debug!("Failed to set IsBorderRequired: {}", e);
+        }
+        if let Err(e) = session.SetIsCursorCaptureEnabled(false) {
+            tracing::debug!("Failed to set IsCursorCaptureEnabled: {}", e);
+        }
+
+        let (tx, rx) = std::sync::mpsc::sync_channel::<Direct3D11CaptureFrame>(1);
+        frame_pool
+            .FrameArrived(&TypedEventHandler::new(move |sender, _| {
+                let sender: &Direct3D11CaptureFramePool = match &*sender {
+                    Some(s) => s,
+                    None => return Ok(()),
+                };
+
+                if let Ok(frame) = sender.TryGetNextFrame() {
+                    // Only the first frame is needed.
+                    let _ = tx.try_send(frame);
+                }
+                Ok(())
+            }))
+            .map_err(WindowsCaptureError::FailedToSetFrameArrivedHandler)?;
+
+        session.StartCapture().map_err(WindowsCaptureError::FailedToStartCapture)?;
+        let frame = rx.recv_timeout(Self::FRAME_TIMEOUT);
+
+        if let Err(e) = session.Close() {
+            tracing::debug!("Failed to close screenshot session: {}", e);
+        }
+        if let Err(e) = frame_pool.Close() {
+            tracing::debug!("Failed to close screenshot frame pool: {}", e);
+        }
+
+        let frame = frame.map_err(|_| WindowsCaptureError::ScreenshotTimedOut)?;
+        self.read_frame(&frame)
+    }
+
+    fn read_frame(&self, frame: &Direct3D11CaptureFrame) -> Result<(Vec<u8>, Vector2<i32>)> {
+        let surface = frame.Surface().map_err(WindowsCaptureError::FailedToGetSurface)?;
+        let access: IDirect3DDxgiInterfaceAccess =
+            surface.cast().map_err(WindowsCaptureError::CastFailed)?;
+        let texture: ID3D11Texture2D =
+            unsafe { access.GetInterface().map_err(WindowsCaptureError::FailedToGetInterface)? };
+
+        let desc = unsafe {
+            let mut d = std::mem::zeroed::<D3D11_TEXTURE2D_DESC>();
+            texture.GetDesc(&mut d);
+            d.BindFlags = 0;
+            d.MiscFlags = 0;
+            d.CPUAccessFlags = D3D11_CPU_ACCESS_READ.0 as u32;
+            d.Usage = D3D11_USAGE_STAGING;
+            d.MipLevels = 1;
+            d.ArraySize = 1;
+            d.SampleDesc.Count = 1;
+            d.SampleDesc.Quality = 0;
+            d
+        };
+
+        let staging_tex = unsafe {
+            let mut tex = MaybeUninit::<Option<ID3D11Texture2D>>::uninit();
+            self.native_device
+                .CreateTexture2D(&desc, None, Some(tex.as_mut_ptr()))
+                .map_err(WindowsCaptureError::FailedToCreateTexture)?;
+            tex.assume_init().ok_or(WindowsCaptureError::FailedToCreateTexture(E_POINTER.into()))?
+        };
+
+        let context = unsafe {
+            self.native_device
+                .GetImmediateContext()
+                .map_err(WindowsCaptureError::FailedToGetImmediateContext)?
+        };
+
+        let bytes_per_pixel = Self::PIXEL_FORMAT.bytes_per_pixel();
+        let full_size = Vector2::new(desc.Width as i32, desc.Height as i32);
+        let mut full =
+            vec![0u8; desc.Width as usize * desc.Height as usize * bytes_per_pixel as usize];
+        copy_texture(&context, &texture, &staging_tex);
+        // Thumbnails are only glanced at, so a rotated display's is left as it arrived.
+        map_read_texture(
+            &mut full,
+            &context,
+            &staging_tex,
+            &desc,
+            bytes_per_pixel,
+            Orientation::Upright,
+        )?;
+        Ok((full, full_size))
+    }
+}
@@ -0,0 +1,65 @@
+use windows::{
+    Foundation::Metadata::ApiInformation,
+    Graphics::Capture::{GraphicsCaptureDirtyRegionMode, GraphicsCaptureSession},
+    core::{HSTRING, h},
+};
+
+use crate::capture_providers::shared::{DirtyRegionMode, SessionOption, WgcSessionOptions};
+
+// The properties are looked up by name, as older versions of Windows fail to set the ones they don't have.
+const BORDER_REQUIRED: &str = "IsBorderRequired";
+const INCLUDE_SECONDARY_WINDOWS: &str = "IncludeSecondaryWindows";
+const DIRTY_REGION_MODE: &str = "DirtyRegionMode";
+
+fn is_property_present(property: &str) -> bool {
+    ApiInformation::IsPropertyPresent(
+        h!("Windows.Graphics.Capture.GraphicsCaptureSession"),
+        &HSTRING::from(property),
+    )
+    .inspect_err(|e| tracing::debug!("Failed to look up {}: {}", property, e))
+    .unwrap_or(false)
+}
+
+/// The session options this version of Windows has.
+pub fn supported_session_options() -> Vec<&'static str> {
+    [BORDER_REQUIRED, INCLUDE_SECONDARY_WINDOWS, DIRTY_REGION_MODE]
+        .into_iter()
+        .filter(|property| is_property_present(property))
+        .collect()
+}
+
+/// Sets the options the session has, and returns those that took effect.
+pub(super) fn apply_session_options(
+    session: &GraphicsCaptureSession,
+    options: &WgcSessionOptions,
+) -> Vec<SessionOption> {
+    let mut applied = Vec::new();
+    let mut apply =
+        |property: &str, option: SessionOption, set: &dyn Fn() -> windows::core::Result<()>| {
+            if !is_property_present(property) {
+                tracing::debug!("{} isn't available on this version of Windows", property);
+                return;
+            }
+            match set() {
+                Ok(()) => applied.push(option),
+                Err(e) => tracing::warn!("Failed to set {}: {}", option, e),
+            }
+        };
+
+    apply(BORDER_REQUIRED, SessionOption::BorderRequired(options.border_required), &|| {
+        session.SetIsBorderRequired(options.border_required)
+    });
+    apply(
+        INCLUDE_SECONDARY_WINDOWS,
+        SessionOption::IncludeSecondaryWindows(options.include_secondary_windows),
+        &|| session.SetIncludeSecondaryWindows(options.include_secondary_windows),
+    );
+    apply(DIRTY_REGION_MODE, SessionOption::DirtyRegionMode(options.dirty_region_mode), &|| {
+        session.SetDirtyRegionMode(match options.dirty_region_mode {
+            DirtyRegionMode::ReportOnly => GraphicsCaptureDirtyRegionMode::ReportOnly,
+            DirtyRegionMode::ReportAndRender => GraphicsCaptureDirtyRegionMode::ReportAndRender,
+        })
+    });
+
+    applied
+}
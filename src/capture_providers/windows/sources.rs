@@ -0,0 +1,608 @@
+use windows::{
+    Graphics::Capture::GraphicsCaptureItem,
+    Win32::{
+        Foundation::{CloseHandle, HWND, LPARAM, RECT, WPARAM},
+        Graphics::{
+            Dwm::{DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS, DwmGetWindowAttribute},
+            Dxgi::{
+                Common::{
+                    DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020, DXGI_MODE_ROTATION_ROTATE90,
+                    DXGI_MODE_ROTATION_ROTATE180, DXGI_MODE_ROTATION_ROTATE270,
+                },
+                CreateDXGIFactory1, DXGI_OUTPUT_DESC1, IDXGIFactory1, IDXGIOutput6,
+            },
+            Gdi::{
+                BI_RGB, BITMAP, BITMAPINFO, BITMAPINFOHEADER, CreateCompatibleDC, DIB_RGB_COLORS,
+                DeleteDC, DeleteObject, EnumDisplayMonitors, GetDIBits, GetMonitorInfoW,
+                GetObjectW, HBITMAP, HDC, HMONITOR, MONITOR_DEFAULTTONEAREST, MONITORINFO,
+                MONITORINFOEXW, MonitorFromRect, MonitorFromWindow,
+            },
+        },
+        System::{
+            Threading::{
+                GetCurrentProcessId, OpenProcess, PROCESS_NAME_WIN32,
+                PROCESS_QUERY_LIMITED_INFORMATION, QueryFullProcessImageNameW,
+            },
+            WinRT::Graphics::Capture::IGraphicsCaptureItemInterop,
+        },
+        UI::WindowsAndMessaging::{
+            EnumWindows, GCLP_HICON, GCLP_HICONSM, GWL_EXSTYLE, GetClassLongPtrW, GetIconInfo,
+            GetWindowLongW, GetWindowTextW, GetWindowThreadProcessId, HICON, ICON_SMALL,
+            ICON_SMALL2, ICONINFO, IsIconic, IsWindowVisible, MONITORINFOF_PRIMARY,
+            SMTO_ABORTIFHUNG, SendMessageTimeoutW, SetWindowDisplayAffinity,
+            WDA_EXCLUDEFROMCAPTURE, WM_GETICON, WS_EX_TOOLWINDOW,
+        },
+    },
+};
+use windows_core::{BOOL, Interface, PWSTR};
+
+use super::{CaptureSource, Result};
+use crate::{
+    capture_providers::shared::{
+        CaptureSourceId, MonitorGeometry, SavedCaptureSource, SourceKind, WindowIcon,
+        find_saved_source,
+    },
+    utils::{
+        dpi::{self, DisplayLayout, PhysicalCoordinates},
+        orientation::Orientation,
+        rect::Rect,
+        vector2::Vector2,
+    },
+};
+
+/// A monitor, window or region of the desktop that can be captured.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceDescriptor {
+    pub kind: SourceKind,
+    pub name: String,
+    /// The executable of a window's process, e.g. "WINWORD.EXE", to tell windows with the same title apart.
+    pub process_name: Option<String>,
+    // The HMONITOR or HWND, stored as its raw value to keep the descriptor Send.
+    handle: usize,
+    // The bounds of a region, in virtual desktop coordinates. Empty for other sources.
+    region: Rect<i32>,
+}
+
+impl SourceDescriptor {
+    pub fn create_capture_item(&self) -> Result<CaptureSource> {
+        let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+        let item: GraphicsCaptureItem = match self.kind {
+            SourceKind::Monitor => unsafe {
+                interop.CreateForMonitor(HMONITOR(self.handle as *mut core::ffi::c_void))?
+            },
+            SourceKind::Window => unsafe {
+                interop.CreateForWindow(HWND(self.handle as *mut core::ffi::c_void))?
+            },
+            SourceKind::Region => return Ok(CaptureSource::VirtualRegion(self.region)),
+        };
+        Ok(item.into())
+    }
+
+    /// The raw HWND of a window, which stays the same while the window is open. None for other sources.
+    pub fn window_handle(&self) -> Option<u64> {
+        (self.kind == SourceKind::Window).then_some(self.handle as u64)
+    }
+
+    /// Where a monitor is on the virtual desktop, in physical pixels. None for other sources.
+    pub fn monitor_bounds(&self) -> Option<Rect<i32>> {
+        if self.kind != SourceKind::Monitor {
+            return None;
+        }
+        let _physical = PhysicalCoordinates::enter();
+        geometry(HMONITOR(self.handle as *mut core::ffi::c_void)).ok().map(|monitor| monitor.bounds)
+    }
+
+    /// The small icon of a window, or None if it has none or doesn't hand it out in time, or for other sources.
+    pub fn icon(&self) -> Option<WindowIcon> {
+        let window = HWND(self.window_handle()? as usize as *mut core::ffi::c_void);
+        window_icon(window)
+    }
+
+    /// Describes the source in a way that still finds it after a restart, or None if it is already gone.
+    pub fn to_saved(&self) -> Option<SavedCaptureSource> {
+        self.source_id().map(|id| SavedCaptureSource { name: self.name.clone(), id })
+    }
+
+    fn source_id(&self) -> Option<CaptureSourceId> {
+        match self.kind {
+            SourceKind::Monitor => {
+                monitor_device_name(HMONITOR(self.handle as *mut core::ffi::c_void))
+                    .map(CaptureSourceId::monitor)
+            }
+            SourceKind::Window => {
+                let window = HWND(self.handle as *mut core::ffi::c_void);
+                window_process_name(window)
+                    .map(|process_name| CaptureSourceId::window(process_name, &self.name))
+            }
+            // Regions are picked anew every time.
+            SourceKind::Region => None,
+        }
+    }
+}
+
+/// Looks for a source that is currently available and matches the saved id.
+/// Windows are enumerated front to back, so the topmost of several matching windows is found.
+pub fn find_source(saved: &CaptureSourceId) -> Result<Option<SourceDescriptor>> {
+    let sources = enumerate_sources()?
+        .into_iter()
+        .filter(|source| match saved {
+            CaptureSourceId::Monitor { .. } => source.kind == SourceKind::Monitor,
+            CaptureSourceId::Window { .. } => source.kind == SourceKind::Window,
+        })
+        .filter_map(|source| source.source_id().map(|id| (id, source)));
+    Ok(find_saved_source(saved, sources))
+}
+
+/// Describes an item from the system picker like [`SourceDescriptor::to_saved`] does.
+/// The item doesn't expose its handle, so the window is found by its title and the monitor by its size,
+/// which gives up if several monitors are the same size.
+pub fn saved_capture_item(source: &CaptureSource) -> Option<SavedCaptureSource> {
+    let CaptureSource::Item(item) = source else {
+        return None;
+    };
+    let name = item.DisplayName().ok()?.to_string();
+    let id = capture_item_source_id(item, &name)?;
+    Some(SavedCaptureSource { name, id })
+}
+
+fn capture_item_source_id(item: &GraphicsCaptureItem, name: &str) -> Option<CaptureSourceId> {
+    match capture_item_kind(item) {
+        SourceKind::Window => {
+            let window =
+                window_handles().ok()?.into_iter().find(|window| window_title(*window) == name)?;
+            window_process_name(window)
+                .map(|process_name| CaptureSourceId::window(process_name, name))
+        }
+        SourceKind::Monitor => {
+            let size = item.Size().ok()?;
+            let mut monitors = monitor_handles().ok()?.into_iter().filter(|monitor| {
+                let mut info = MONITORINFO {
+                    cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                    ..Default::default()
+                };
+                unsafe { GetMonitorInfoW(*monitor, &mut info) }.as_bool() && {
+                    let rect = info.rcMonitor;
+                    rect.right - rect.left == size.Width && rect.bottom - rect.top == size.Height
+                }
+            });
+            let monitor = monitors.next()?;
+            if monitors.next().is_some() {
+                return None;
+            }
+            monitor_device_name(monitor).map(CaptureSourceId::monitor)
+        }
+        SourceKind::Region => None,
+    }
+}
+
+/// Lists the monitors, all of them at once if there are several, and the windows that are worth capturing, in that order.
+pub fn enumerate_sources() -> Result<Vec<SourceDescriptor>> {
+    let mut sources = Vec::new();
+    let mut desktop: Option<Rect<i32>> = None;
+
+    for (index, monitor) in monitor_handles()?.into_iter().enumerate() {
+        let mut info =
+            MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+        unsafe { GetMonitorInfoW(monitor, &mut info) }.ok()?;
+
+        let rect = info.rcMonitor;
+        let mut name = format!(
+            "Display {} ({}x{})",
+            index + 1,
+            rect.right - rect.left,
+            rect.bottom - rect.top
+        );
+        if info.dwFlags & MONITORINFOF_PRIMARY != 0 {
+            name.push_str(" - Primary");
+        }
+
+        let bounds = Rect::from(rect);
+        desktop = Some(desktop.map_or(bounds, |desktop| desktop.union(&bounds)));
+        sources.push(SourceDescriptor {
+            kind: SourceKind::Monitor,
+            name,
+            process_name: None,
+            handle: monitor.0 as usize,
+            region: Rect::default(),
+        });
+    }
+
+    if let Some(desktop) = desktop
+        && sources.len() > 1
+    {
+        sources.push(SourceDescriptor {
+            kind: SourceKind::Region,
+            name: format!("All displays ({}x{})", desktop.size.x, desktop.size.y),
+            process_name: None,
+            handle: 0,
+            region: desktop,
+        });
+    }
+
+    for window in window_handles()? {
+        let Some(title) = capturable_window_title(window) else {
+            continue;
+        };
+        sources.push(SourceDescriptor {
+            kind: SourceKind::Window,
+            name: title,
+            process_name: window_process_name(window),
+            handle: window.0 as usize,
+            region: Rect::default(),
+        });
+    }
+
+    tracing::debug!("Enumerated {} capture sources", sources.len());
+    Ok(sources)
+}
+
+pub(super) fn monitor_handles() -> Result<Vec<HMONITOR>> {
+    unsafe extern "system" fn collect(
+        monitor: HMONITOR,
+        _hdc: HDC,
+        _rect: *mut RECT,
+        monitors: LPARAM,
+    ) -> BOOL {
+        let monitors = unsafe { &mut *(monitors.0 as *mut Vec<HMONITOR>) };
+        monitors.push(monitor);
+        true.into()
+    }
+
+    let mut monitors = Vec::<HMONITOR>::new();
+    unsafe {
+        EnumDisplayMonitors(None, None, Some(collect), LPARAM(&mut monitors as *mut _ as isize))
+    }
+    .ok()?;
+    Ok(monitors)
+}
+
+/// The monitor most of the rect is on, or the nearest one if it is on none.
+pub fn monitor_geometry(rect: Rect<i32>) -> Result<MonitorGeometry> {
+    let monitor = unsafe { MonitorFromRect(&rect_to_win32(rect), MONITOR_DEFAULTTONEAREST) };
+    geometry(monitor)
+}
+
+/// Every monitor, in physical pixels whatever the DPI awareness of the thread.
+pub fn monitor_layout() -> Result<Vec<MonitorGeometry>> {
+    let _physical = PhysicalCoordinates::enter();
+    monitor_handles()?.into_iter().map(geometry).collect()
+}
+
+fn geometry(monitor: HMONITOR) -> Result<MonitorGeometry> {
+    let mut info =
+        MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+    unsafe { GetMonitorInfoW(monitor, &mut info) }.ok()?;
+    Ok(MonitorGeometry {
+        bounds: Rect::from(info.rcMonitor),
+        scale_factor: dpi::monitor_scale(monitor)?,
+    })
+}
+
+/// Where the window is drawn on the virtual desktop, in physical pixels, without the invisible resize borders.
+/// None if it is minimized, or hidden.
+pub fn window_bounds(window: u64) -> Result<Option<Rect<i32>>> {
+    let window = HWND(window as *mut core::ffi::c_void);
+    if !unsafe { IsWindowVisible(window) }.as_bool() || unsafe { IsIconic(window) }.as_bool() {
+        return Ok(None);
+    }
+
+    let mut rect = RECT::default();
+    unsafe {
+        DwmGetWindowAttribute(
+            window,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut _ as *mut core::ffi::c_void,
+            std::mem::size_of::<RECT>() as u32,
+        )
+    }?;
+    Ok(Some(Rect::from(rect)))
+}
+
+/// Keeps the window out of every capture, including ours, e.g. for overlays that are only meant for the user.
+pub fn exclude_window_from_capture(window: u64) -> Result<()> {
+    unsafe {
+        SetWindowDisplayAffinity(HWND(window as *mut core::ffi::c_void), WDA_EXCLUDEFROMCAPTURE)
+    }?;
+    Ok(())
+}
+
+fn rect_to_win32(rect: Rect<i32>) -> RECT {
+    RECT { left: rect.position.x, top: rect.position.y, right: rect.right(), bottom: rect.bottom() }
+}
+
+/// The device name of the monitor an item was created for, e.g. `\\.\DISPLAY1`, which stays the same when its
+/// display mode changes. Items don't say, so the monitor is found by the name and size of an item created for it.
+pub(super) fn monitor_item_device_name(item: &GraphicsCaptureItem) -> Option<String> {
+    let interop =
+        windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>().ok()?;
+    let (name, size) = (item.DisplayName().ok()?, item.Size().ok()?);
+    monitor_handles().ok()?.into_iter().find_map(|monitor| {
+        let candidate: GraphicsCaptureItem = unsafe { interop.CreateForMonitor(monitor) }.ok()?;
+        (candidate.DisplayName().ok()? == name && candidate.Size().ok()? == size)
+            .then(|| monitor_device_name(monitor))
+            .flatten()
+    })
+}
+
+/// A new item for the monitor with the device name, or None if it isn't connected anymore.
+pub(super) fn monitor_item(device_name: &str) -> Result<Option<GraphicsCaptureItem>> {
+    let Some(monitor) = monitor_handles()?
+        .into_iter()
+        .find(|monitor| monitor_device_name(*monitor).as_deref() == Some(device_name))
+    else {
+        return Ok(None);
+    };
+    let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+    Ok(Some(unsafe { interop.CreateForMonitor(monitor)? }))
+}
+
+/// A monitor and its bounds.
+pub(super) type RegionMonitor = (GraphicsCaptureItem, Rect<i32>);
+
+/// The region and the monitors it spans, all in physical pixels like the frames of the monitors.
+pub(super) fn region_monitors(region: Rect<i32>) -> Result<(Rect<i32>, Vec<RegionMonitor>)> {
+    // The region is in the coordinates of the thread, which are scaled unless it is per monitor aware.
+    let space = dpi::screen_space();
+    let region = DisplayLayout::new(monitor_layout()?, space).rect_to_physical(region);
+
+    let interop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()?;
+    let _physical = PhysicalCoordinates::enter();
+    let mut monitors = Vec::new();
+    for monitor in monitor_handles()? {
+        let mut info =
+            MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+        unsafe { GetMonitorInfoW(monitor, &mut info) }.ok()?;
+
+        let bounds = Rect::from(info.rcMonitor);
+        if bounds.intersection(&region).is_none() {
+            continue;
+        }
+        let item: GraphicsCaptureItem = unsafe { interop.CreateForMonitor(monitor)? };
+        monitors.push((item, bounds));
+    }
+    Ok((region, monitors))
+}
+
+pub(super) fn window_handles() -> Result<Vec<HWND>> {
+    unsafe extern "system" fn collect(window: HWND, handles: LPARAM) -> BOOL {
+        let handles = unsafe { &mut *(handles.0 as *mut Vec<HWND>) };
+        handles.push(window);
+        true.into()
+    }
+
+    let mut handles = Vec::<HWND>::new();
+    unsafe { EnumWindows(Some(collect), LPARAM(&mut handles as *mut _ as isize)) }?;
+    Ok(handles)
+}
+
+/// Capture items don't say what they were created for, but a window item is named after its window.
+pub(super) fn capture_item_kind(item: &GraphicsCaptureItem) -> SourceKind {
+    let Ok(name) = item.DisplayName().map(|name| name.to_string()) else {
+        return SourceKind::Window;
+    };
+
+    let is_window = window_handles()
+        .map(|windows| windows.into_iter().any(|window| window_title(window) == name))
+        .unwrap_or(true);
+    if is_window { SourceKind::Window } else { SourceKind::Monitor }
+}
+
+pub(super) fn window_title(window: HWND) -> String {
+    let mut title = [0u16; 512];
+    let len = unsafe { GetWindowTextW(window, &mut title) };
+    String::from_utf16_lossy(&title[..len.max(0) as usize])
+}
+
+/// The device name of the monitor most of the window is on.
+pub(super) fn window_monitor_device_name(window: HWND) -> Option<String> {
+    monitor_device_name(unsafe { MonitorFromWindow(window, MONITOR_DEFAULTTONEAREST) })
+}
+
+/// Whether the monitor with the device name is in HDR mode, in which it is composed in scRGB floats.
+/// False if it can't be told, as capturing in RGBA8 works on any monitor.
+pub(super) fn is_hdr_monitor(device_name: &str) -> bool {
+    output_desc(device_name)
+        .is_some_and(|desc| desc.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020)
+}
+
+/// How the monitor with the device name is rotated, and its size on the desktop, which is already rotated.
+/// None if it can't be told, in which case its frames are taken as they arrive.
+pub(super) fn monitor_rotation(device_name: &str) -> Option<(Orientation, Vector2<i32>)> {
+    let desc = output_desc(device_name)?;
+    let orientation = match desc.Rotation {
+        DXGI_MODE_ROTATION_ROTATE90 => Orientation::Rotated90,
+        DXGI_MODE_ROTATION_ROTATE180 => Orientation::Rotated180,
+        DXGI_MODE_ROTATION_ROTATE270 => Orientation::Rotated270,
+        _ => Orientation::Upright,
+    };
+    let bounds = desc.DesktopCoordinates;
+    Some((orientation, Vector2::new(bounds.right - bounds.left, bounds.bottom - bounds.top)))
+}
+
+// The DXGI output of the monitor with the device name.
+fn output_desc(device_name: &str) -> Option<DXGI_OUTPUT_DESC1> {
+    let factory = unsafe { CreateDXGIFactory1::<IDXGIFactory1>() }.ok()?;
+    (0..)
+        .map_while(|adapter| unsafe { factory.EnumAdapters1(adapter) }.ok())
+        .flat_map(|adapter| {
+            (0..).map_while(move |output| unsafe { adapter.EnumOutputs(output) }.ok())
+        })
+        .filter_map(|output| output.cast::<IDXGIOutput6>().ok())
+        .filter_map(|output| unsafe { output.GetDesc1() }.ok())
+        .find(|desc| {
+            let len = desc.DeviceName.iter().position(|c| *c == 0).unwrap_or(desc.DeviceName.len());
+            String::from_utf16_lossy(&desc.DeviceName[..len]) == device_name
+        })
+}
+
+fn monitor_device_name(monitor: HMONITOR) -> Option<String> {
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    if !unsafe { GetMonitorInfoW(monitor, &mut info as *mut _ as *mut MONITORINFO) }.as_bool() {
+        return None;
+    }
+
+    let len = info.szDevice.iter().position(|c| *c == 0).unwrap_or(info.szDevice.len());
+    Some(String::from_utf16_lossy(&info.szDevice[..len]))
+}
+
+/// The file name of the executable, e.g. "firefox.exe".
+pub(super) fn window_process_name(window: HWND) -> Option<String> {
+    let mut process_id = 0u32;
+    unsafe { GetWindowThreadProcessId(window, Some(&mut process_id)) };
+    if process_id == 0 {
+        return None;
+    }
+
+    let process =
+        unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id) }.ok()?;
+    let mut path = [0u16; 1024];
+    let mut len = path.len() as u32;
+    let result = unsafe {
+        QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, PWSTR(path.as_mut_ptr()), &mut len)
+    };
+    let _ = unsafe { CloseHandle(process) };
+    result.ok()?;
+
+    let path = String::from_utf16_lossy(&path[..len as usize]);
+    path.rsplit('\\').next().map(str::to_string)
+}
+
+/// The small icon of the window, falling back to the one of its class. None if it has neither,
+/// or doesn't answer in time.
+pub(super) fn window_icon(window: HWND) -> Option<WindowIcon> {
+    // A hung window would otherwise hold up whoever asks.
+    const ANSWER_TIMEOUT_MS: u32 = 50;
+
+    let ask = |kind: u32| {
+        let mut icon = 0usize;
+        let answered = unsafe {
+            SendMessageTimeoutW(
+                window,
+                WM_GETICON,
+                WPARAM(kind as usize),
+                LPARAM(0),
+                SMTO_ABORTIFHUNG,
+                ANSWER_TIMEOUT_MS,
+                Some(&mut icon),
+            )
+        };
+        (answered.0 != 0 && icon != 0).then_some(icon)
+    };
+    let class_icon =
+        |index| Some(unsafe { GetClassLongPtrW(window, index) }).filter(|icon| *icon != 0);
+
+    let icon = ask(ICON_SMALL2)
+        .or_else(|| ask(ICON_SMALL))
+        .or_else(|| class_icon(GCLP_HICONSM))
+        .or_else(|| class_icon(GCLP_HICON))?;
+    icon_pixels(HICON(icon as *mut core::ffi::c_void))
+}
+
+fn icon_pixels(icon: HICON) -> Option<WindowIcon> {
+    let mut info = ICONINFO::default();
+    unsafe { GetIconInfo(icon, &mut info) }.ok()?;
+
+    // Monochrome icons keep both of their halves in the mask, which isn't worth the trouble.
+    let pixels = (!info.hbmColor.is_invalid())
+        .then(|| {
+            let mut bitmap = BITMAP::default();
+            let len = unsafe {
+                GetObjectW(
+                    info.hbmColor.into(),
+                    std::mem::size_of::<BITMAP>() as i32,
+                    Some(&mut bitmap as *mut _ as *mut core::ffi::c_void),
+                )
+            };
+            if len == 0 {
+                return None;
+            }
+            let size = Vector2::new(bitmap.bmWidth, bitmap.bmHeight);
+
+            let dc = unsafe { CreateCompatibleDC(None) };
+            let color = read_bitmap(dc, info.hbmColor, size);
+            let mask = read_bitmap(dc, info.hbmMask, size);
+            let _ = unsafe { DeleteDC(dc) };
+            WindowIcon::from_bitmaps(size, &color?, mask.as_deref())
+        })
+        .flatten();
+
+    // The bitmaps are copies made for us.
+    unsafe {
+        let _ = DeleteObject(info.hbmColor.into());
+        let _ = DeleteObject(info.hbmMask.into());
+    }
+    pixels
+}
+
+// Top-down rows of 32-bit BGRA pixels, whatever the bitmap's own format.
+fn read_bitmap(dc: HDC, bitmap: HBITMAP, size: Vector2<i32>) -> Option<Vec<u8>> {
+    if size.x <= 0 || size.y <= 0 || size.x > WindowIcon::MAX_SIZE || size.y > WindowIcon::MAX_SIZE
+    {
+        return None;
+    }
+    let mut info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: size.x,
+            // Negative for top-down rows.
+            biHeight: -size.y,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut pixels = vec![0u8; size.x as usize * size.y as usize * 4];
+    let rows = unsafe {
+        GetDIBits(
+            dc,
+            bitmap,
+            0,
+            size.y as u32,
+            Some(pixels.as_mut_ptr() as *mut core::ffi::c_void),
+            &mut info,
+            DIB_RGB_COLORS,
+        )
+    };
+    (rows == size.y).then_some(pixels)
+}
+
+// Filters out the windows the OS picker wouldn't show either, as well as our own.
+fn capturable_window_title(window: HWND) -> Option<String> {
+    if !unsafe { IsWindowVisible(window) }.as_bool() {
+        return None;
+    }
+
+    let ex_style = unsafe { GetWindowLongW(window, GWL_EXSTYLE) } as u32;
+    if ex_style & WS_EX_TOOLWINDOW.0 != 0 {
+        return None;
+    }
+
+    // Windows on other virtual desktops and suspended UWP apps are "visible", but cloaked.
+    let mut cloaked = 0u32;
+    let cloaked_result = unsafe {
+        DwmGetWindowAttribute(
+            window,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut _ as *mut core::ffi::c_void,
+            std::mem::size_of::<u32>() as u32,
+        )
+    };
+    if cloaked_result.is_ok() && cloaked != 0 {
+        return None;
+    }
+
+    let mut process_id = 0u32;
+    unsafe { GetWindowThreadProcessId(window, Some(&mut process_id)) };
+    if process_id == unsafe { GetCurrentProcessId() } {
+        return None;
+    }
+
+    let title = window_title(window);
+    if title.trim().is_empty() {
+        return None;
+    }
+    Some(title)
+}
@@ -0,0 +1,53 @@
+use std::cell::Cell;
+
+use windows::{
+    Win32::{
+        Foundation::HANDLE,
+        System::Threading::{
+            AvRevertMmThreadCharacteristics, AvSetMmThreadCharacteristicsW, GetCurrentThread,
+            SetThreadPriority, THREAD_PRIORITY, THREAD_PRIORITY_ABOVE_NORMAL,
+            THREAD_PRIORITY_NORMAL,
+        },
+    },
+    core::w,
+};
+
+thread_local! {
+    static ELEVATED: Cell<bool> = const { Cell::new(false) };
+    // The MMCSS registration, which has to be reverted with the handle it returned.
+    static MMCSS_TASK: Cell<Option<HANDLE>> = const { Cell::new(None) };
+}
+
+/// Raises or restores the priority of the current thread, if it isn't already.
+/// Frames are delivered on threads from the OS thread pool, so this is called on every frame,
+/// and only does anything the first time a thread sees a change in the setting.
+/// Both the priority and the MMCSS registration may be refused, which only costs the latency they would have saved.
+pub fn ensure_current_thread_elevated(elevated: bool) {
+    if ELEVATED.get() == elevated {
+        return;
+    }
+    ELEVATED.set(elevated);
+
+    if elevated {
+        set_priority(THREAD_PRIORITY_ABOVE_NORMAL);
+        // MMCSS schedules the thread ahead of ordinary work, the same way it does for audio.
+        let mut task_index = 0;
+        match unsafe { AvSetMmThreadCharacteristicsW(w!("Capture"), &mut task_index) } {
+            Ok(task) => MMCSS_TASK.set(Some(task)),
+            Err(e) => tracing::warn!("Failed to register capture thread with MMCSS: {}", e),
+        }
+    } else {
+        if let Some(task) = MMCSS_TASK.take()
+            && let Err(e) = unsafe { AvRevertMmThreadCharacteristics(task) }
+        {
+            tracing::warn!("Failed to unregister capture thread from MMCSS: {}", e);
+        }
+        set_priority(THREAD_PRIORITY_NORMAL);
+    }
+}
+
+fn set_priority(priority: THREAD_PRIORITY) {
+    if let Err(e) = unsafe { SetThreadPriority(GetCurrentThread(), priority) } {
+        tracing::warn!("Failed to set capture thread priority to {}: {}", priority.0, e);
+    }
+}
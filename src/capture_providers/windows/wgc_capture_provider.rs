@@ -1,11 +1,17 @@
 use std::{
     iter::IntoIterator,
     mem::MaybeUninit,
-    sync::{Arc, RwLock},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
+#[cfg(feature = "gpu-frames")]
+use windows::Win32::Graphics::Direct3D11::{D3D11_BOX, D3D11_USAGE_DEFAULT, ID3D11Multithread};
 use windows::{
-    Foundation::TypedEventHandler,
+    Foundation::{TimeSpan, TypedEventHandler},
     Graphics::{
         Capture::{
             Direct3D11CaptureFrame, Direct3D11CaptureFramePool, GraphicsCaptureItem,
@@ -14,81 +20,507 @@ use windows::{
         DirectX::Direct3D11::IDirect3DDevice,
     },
     Win32::{
-        Graphics::Direct3D11::{
-            D3D11_CPU_ACCESS_READ, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING, ID3D11Device,
-            ID3D11Texture2D,
+        Graphics::{
+            Direct3D11::{
+                D3D11_CPU_ACCESS_READ, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING, ID3D11Device,
+                ID3D11DeviceContext, ID3D11Query, ID3D11Texture2D,
+            },
+            Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_UNKNOWN},
         },
         System::WinRT::Direct3D11::IDirect3DDxgiInterfaceAccess,
     },
+    core::IInspectable,
 };
 use windows_core::Interface;
 
+#[cfg(feature = "gpu-frames")]
+use crate::utils::gpu_frame::{GpuFrame, GpuTexturePool};
 use crate::{
     capture_providers::{
         CaptureProvider,
+        shared::{
+            CaptureFramerate, CaptureItemInfo, CaptureMetrics, CaptureState, FrameMeta,
+            FrameSenderSlot, ReadbackRing, RegionComposite, RotationCorrection, SessionOption,
+            SourceKind, WgcSessionOptions, WindowIcon,
+        },
         windows::{
-            WindowsCaptureError, WindowsCaptureStream,
-            d3d11_utils::{copy_texture, map_read_texture},
+            CaptureSource, WindowsCaptureError, WindowsCaptureStream,
+            cursor_tracker::find_window,
+            d3d11_utils::{
+                copy_texture, create_d3d_device, create_event_query, debug_assert_com_apartment,
+                end_query, ensure_mta, is_query_done, map_read_texture,
+                native_to_winrt_d3d11device, winrt_to_native_d3d11device,
+            },
+            display_watcher::DisplayWatcher,
+            session_options::apply_session_options,
+            sources::{
+                capture_item_kind, is_hdr_monitor, monitor_item, monitor_item_device_name,
+                monitor_rotation, region_monitors, window_icon, window_monitor_device_name,
+                window_process_name,
+            },
+            thread_priority::ensure_current_thread_elevated,
         },
     },
     utils::{
+        agile_ref::AgileRef,
         buffer_arena::{BufferArena, BufferRef},
+        dirty_rects::{self, MAX_DIRTY_RECTS},
         frame::Frame,
+        metrics::Metrics,
+        orientation::Orientation,
+        panic_guard,
         pixel_format::PixelFormat,
+        rect::Rect,
         vector2::Vector2,
     },
 };
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 struct Staging {
     textures: Vec<ID3D11Texture2D>,
-    frame_count: u64,
+    // Signals when the copy into the texture of the same index is done.
+    queries: Vec<ID3D11Query>,
+    ring: ReadbackRing,
     width: u32,
     height: u32,
+    format: DXGI_FORMAT,
+    metrics: CaptureMetrics,
+}
+
+impl Staging {
+    fn new(metrics: &CaptureMetrics) -> Self {
+        Self {
+            textures: Vec::new(),
+            queries: Vec::new(),
+            ring: ReadbackRing::new(
+                WgcCaptureProvider::PIPELINE_DEPTH,
+                WgcCaptureProvider::MAX_PIPELINE_DEPTH,
+            ),
+            width: 0,
+            height: 0,
+            format: DXGI_FORMAT_UNKNOWN,
+            metrics: metrics.clone(),
+        }
+    }
+}
+
+// The frame pool and session of one of the items being captured.
+#[derive(Debug)]
+struct ItemSession {
+    frame_pool: Direct3D11CaptureFramePool,
+    session: GraphicsCaptureSession,
+    frame_arrived_token: i64,
+    // The session options that took effect, the same for every session.
+    applied_options: Vec<SessionOption>,
+}
+
+// The monitor a session captures of a region, and the composite its frames go into.
+#[derive(Debug, Clone)]
+struct RegionPart {
+    monitor: usize,
+    composite: Arc<Mutex<RegionComposite>>,
 }
 
 // Windows Graphics Capture (WGC) Provider
 #[derive(Debug)]
 pub struct WgcCaptureProvider {
-    device: IDirect3DDevice,
-    capture_item: Option<GraphicsCaptureItem>,
+    device: AgileRef<IDirect3DDevice>,
+    capture_source: Option<CaptureSource>,
+    // What the sessions are created for: the source's own item, or the monitors its region spans.
+    capture_items: Vec<GraphicsCaptureItem>,
+    // Puts the frames of the monitors back together, when capturing a region.
+    region: Option<Arc<Mutex<RegionComposite>>>,
+    // Looked up once, as it means going through every window.
+    capture_item_kind: SourceKind,
+    // The process and icon of the shared window, looked up once for the same reason.
+    capture_process_name: Option<String>,
+    capture_icon: Option<WindowIcon>,
+    // The device name of the shared monitor, to find it again after the displays changed.
+    capture_monitor: Option<String>,
+    // Whether the shared monitor, or the one the shared window is on, is in HDR mode. Looked up once, so a window
+    // moved to another monitor keeps the format it was captured in.
+    capture_hdr: bool,
+    // How the shared monitor is rotated, and its size on the desktop. Looked up once too, as the displays changing
+    // recovers the capture, which looks it up again.
+    capture_rotation: Option<(Orientation, Vector2<i32>)>,
+    // Which frames of a rotated monitor are turned upright as they are read back.
+    rotation_correction: RotationCorrection,
+    item_closed_handlers: Vec<(GraphicsCaptureItem, i64)>,
     pixel_format: PixelFormat,
-    staging_state: Arc<RwLock<Staging>>,
+    // Captures items on a monitor in HDR mode in its own format, to be tone-mapped once read back.
+    hdr_capture: bool,
+    // One for each capture item, as they are read back on their own.
+    staging_states: Vec<Arc<RwLock<Staging>>>,
     buffer_pool: BufferArena,
 
-    frame_pool: Option<Direct3D11CaptureFramePool>,
-    session: Option<GraphicsCaptureSession>,
-    stream_tokens: Vec<i64>,
+    sessions: Vec<ItemSession>,
+    // The sender of the current stream, shared with the FrameArrived handler.
+    // The handler only loads it, so swapping or ending the stream never blocks the capture callback.
+    frame_sender: FrameSenderSlot,
+    // The framerate of the current stream, so the session can be recreated for another item without ending the stream.
+    stream_framerate: Option<CaptureFramerate>,
+    capture_metrics: CaptureMetrics,
+    // Whether the threads the frames arrive on are given a higher priority, to keep up at high framerates.
+    elevate_threads: Arc<AtomicBool>,
+    // Whether frames are sent on as textures instead of being read back, for encoders that take them.
+    #[cfg(feature = "gpu-frames")]
+    gpu_output: Arc<AtomicBool>,
+    #[cfg(feature = "gpu-frames")]
+    gpu_textures: GpuTexturePool,
+    session_options: WgcSessionOptions,
+    // Counts the streams created, to tell them apart in the logs.
+    stream_generation: u64,
     capturing: bool,
+    // Interrupts the capture when the displays change, which the sessions may not survive. Only there while capturing.
+    display_watcher: Option<DisplayWatcher>,
+    state: tokio::sync::watch::Sender<CaptureState>,
+    frame_meta: tokio::sync::watch::Sender<FrameMeta>,
 }
 
 impl WgcCaptureProvider {
     const WGC_FRAME_BUFFERS: i32 = 2;
     const PIPELINE_DEPTH: usize = 2;
-    const TX_QUEUE_SIZE: usize = 2;
+    // How far the staging ring may grow when the copies keep taking longer than a frame.
+    const MAX_PIPELINE_DEPTH: usize = 4;
     const BUFFER_ARENA_SIZE: usize = 128000;
 
-    pub fn new(device: IDirect3DDevice, pixel_format: PixelFormat) -> Self {
-        Self {
+    /// Keeps its stats in the metrics.
+    pub fn new(
+        device: &IDirect3DDevice,
+        pixel_format: PixelFormat,
+        metrics: &Metrics,
+    ) -> super::Result<Self> {
+        let device = AgileRef::new(device).map_err(|e| {
+            tracing::error!("Failed to create agile reference to device! {}", e);
+            WindowsCaptureError::FailedToCreateAgileReference(e)
+        })?;
+
+        Ok(Self {
             device,
-            capture_item: None,
+            capture_source: None,
+            capture_items: Vec::new(),
+            region: None,
+            capture_item_kind: SourceKind::Monitor,
+            capture_process_name: None,
+            capture_icon: None,
+            capture_monitor: None,
+            capture_hdr: false,
+            capture_rotation: None,
+            rotation_correction: RotationCorrection::default(),
+            item_closed_handlers: Vec::new(),
             pixel_format,
-            staging_state: Arc::new(RwLock::new(Staging::default())),
+            hdr_capture: false,
+            staging_states: Vec::new(),
             buffer_pool: BufferArena::init(Self::BUFFER_ARENA_SIZE),
-            frame_pool: None,
-            session: None,
-            stream_tokens: Vec::new(),
+            sessions: Vec::new(),
+            frame_sender: FrameSenderSlot::default(),
+            stream_framerate: None,
+            capture_metrics: CaptureMetrics::register(
+                metrics,
+                CaptureFramerate::FPS30.to_frametime(),
+            ),
+            elevate_threads: Arc::new(AtomicBool::new(false)),
+            #[cfg(feature = "gpu-frames")]
+            gpu_output: Arc::new(AtomicBool::new(false)),
+            #[cfg(feature = "gpu-frames")]
+            gpu_textures: GpuTexturePool::default(),
+            session_options: WgcSessionOptions::default(),
+            stream_generation: 0,
             capturing: false,
+            display_watcher: None,
+            state: tokio::sync::watch::Sender::new(CaptureState::Idle),
+            frame_meta: tokio::sync::watch::Sender::new(FrameMeta::default()),
+        })
+    }
+
+    /// Sets the session options, on the running sessions too.
+    pub fn set_session_options(&mut self, options: WgcSessionOptions) {
+        self.session_options = options;
+        for session in &mut self.sessions {
+            session.applied_options = apply_session_options(&session.session, &options);
+        }
+    }
+
+    /// Raises the priority of the threads the frames are captured on, and registers them with MMCSS,
+    /// so the readback isn't held up by the encoder at high framerates.
+    pub fn set_elevated_thread_priority(&self, elevated: bool) {
+        self.elevate_threads.store(elevated, Ordering::Relaxed);
+    }
+
+    /// Captures items on a monitor in HDR mode as scRGB floats, which are tone-mapped to RGBA8 once read back,
+    /// instead of having Windows clip them to SDR. Recreates the running sessions if their format changes.
+    pub fn set_hdr_capture(&mut self, enabled: bool) -> super::Result<()> {
+        let format = self.capture_format();
+        self.hdr_capture = enabled;
+        if self.capture_format() != format
+            && !self.sessions.is_empty()
+            && let Some(framerate) = self.stream_framerate
+        {
+            tracing::debug!("Recreating capture session in {:?}", self.capture_format());
+            self.close_session();
+            return self.create_session(framerate);
+        }
+        Ok(())
+    }
+
+    /// Sets which frames of a rotated monitor are turned upright. Recreates the running sessions if it changes
+    /// what is captured.
+    pub fn set_rotation_correction(&mut self, correction: RotationCorrection) -> super::Result<()> {
+        let previous = std::mem::replace(&mut self.rotation_correction, correction);
+        let rotated = self.capture_rotation.is_some_and(|(display, _)| !display.is_upright());
+        if previous != correction
+            && rotated
+            && !self.sessions.is_empty()
+            && let Some(framerate) = self.stream_framerate
+        {
+            tracing::debug!("Recreating capture session with rotation correction {}", correction);
+            self.close_session();
+            return self.create_session(framerate);
+        }
+        Ok(())
+    }
+
+    // The format the sessions capture in. A region is put together from several monitors, so it never is in HDR.
+    fn capture_format(&self) -> PixelFormat {
+        if self.hdr_capture && self.capture_hdr && self.region.is_none() {
+            PixelFormat::RGBA16
+        } else {
+            self.pixel_format
         }
     }
 
+    /// Sends frames on as textures instead of reading them back, when the encoder can take them.
+    /// The encoder uses the device from its own thread, so it is made multithread protected first.
+    #[cfg(feature = "gpu-frames")]
+    pub fn set_gpu_output(&self, enabled: bool) -> super::Result<()> {
+        if enabled {
+            let device = self.device.resolve().map_err(|e| {
+                tracing::error!("Failed to resolve device on the current thread! {}", e);
+                WindowsCaptureError::FailedToResolveAgileReference(e)
+            })?;
+            let device = winrt_to_native_d3d11device(&device).map_err(|e| {
+                tracing::error!("Failed to get native device! {}", e);
+                WindowsCaptureError::FailedToGetDevice(e)
+            })?;
+            let multithread: ID3D11Multithread = device.cast().map_err(|e| {
+                tracing::error!("Failed to cast device to ID3D11Multithread! {}", e);
+                WindowsCaptureError::CastFailed(e)
+            })?;
+            unsafe {
+                let _ = multithread.SetMultithreadProtected(true);
+            }
+        }
+        self.gpu_output.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
     fn process_frame(
         mut frame_buffer: BufferRef,
         frame: Direct3D11CaptureFrame,
-        staging_state_arc: Arc<RwLock<Staging>>,
+        staging_state_arc: &Arc<RwLock<Staging>>,
+        pixel_format: PixelFormat,
+        tx: &tokio::sync::mpsc::Sender<Frame>,
+        frame_meta: &tokio::sync::watch::Sender<FrameMeta>,
+        orientation: Orientation,
+    ) -> super::Result<()> {
+        let Some(frame_size) = Self::read_back(
+            &mut frame_buffer,
+            &frame,
+            staging_state_arc,
+            pixel_format,
+            orientation,
+        )?
+        else {
+            return Ok(());
+        };
+        let (frame_duration, dirty_regions) = Self::frame_details(&frame);
+        // The size the frame arrived in, which the dirty rects are of.
+        let arrived_size = orientation.rotated_size(frame_size);
+        let dirty_regions = dirty_regions
+            .into_iter()
+            .map(|rect| orientation.rotate_rect(rect, arrived_size))
+            .collect();
+
+        let frame = Frame::new_ensure_rgba(
+            frame_buffer,
+            pixel_format,
+            frame_size,
+            Some(frame_duration),
+            Some(dirty_regions),
+        );
+        let frame = Frame { orientation, ..frame };
+        let sequence = Self::publish_frame_meta(frame_meta, &frame);
+        Self::send_frame(tx, frame.with_sequence(sequence))
+    }
+
+    // Reads the monitor's frame back into its part of the region, and returns the composite once it is done.
+    fn process_region_frame(
+        frame: Direct3D11CaptureFrame,
+        part: &RegionPart,
+        staging_state_arc: &Arc<RwLock<Staging>>,
+        pixel_format: PixelFormat,
+        buffer_pool: &BufferArena,
+        arrived: Instant,
+    ) -> super::Result<Option<Frame>> {
+        let content_size = frame.ContentSize().map_err(|e| {
+            tracing::error!("Failed to get frame ContentSize! {}", e);
+            WindowsCaptureError::FailedToGetContentSize(e)
+        })?;
+        let buffer_size = content_size.Width as usize
+            * content_size.Height as usize
+            * pixel_format.bytes_per_pixel() as usize;
+        let mut buffer = buffer_pool.get(buffer_size);
+        unsafe {
+            buffer.set_len(buffer_size);
+        }
+
+        let Some(size) = Self::read_back(
+            &mut buffer,
+            &frame,
+            staging_state_arc,
+            pixel_format,
+            Orientation::Upright,
+        )?
+        else {
+            return Ok(None);
+        };
+        let (frame_duration, dirty_regions) = Self::frame_details(&frame);
+
+        let mut composite = part.composite.lock().unwrap();
+        if !composite.submit(part.monitor, &buffer, size, Some(&dirty_regions), arrived) {
+            return Ok(None);
+        }
+
+        let mut composite_buffer = buffer_pool.get(composite.len());
+        unsafe {
+            composite_buffer.set_len(composite.len());
+        }
+        let dirty_regions = composite.finish(&mut composite_buffer);
+        Ok(Some(Frame::new_ensure_rgba(
+            composite_buffer,
+            pixel_format,
+            composite.size(),
+            Some(frame_duration),
+            Some(dirty_regions),
+        )))
+    }
+
+    // Copies the frame into the staging ring, and reads the newest copy that is done back into the buffer, rotated
+    // by `orientation`. Returns the size of the frame once rotated, or None if no copy was done yet so nothing was read.
+    fn read_back(
+        buffer: &mut [u8],
+        frame: &Direct3D11CaptureFrame,
+        staging_state_arc: &Arc<RwLock<Staging>>,
+        pixel_format: PixelFormat,
+        orientation: Orientation,
+    ) -> super::Result<Option<Vector2<i32>>> {
+        let (texture, size) = Self::frame_texture(frame)?;
+        let (device, context) = Self::texture_device(&texture)?;
+
+        let desc = unsafe {
+            let mut d = std::mem::zeroed::<D3D11_TEXTURE2D_DESC>();
+            texture.GetDesc(&mut d);
+            d.BindFlags = 0;
+            d.MiscFlags = 0;
+            d.CPUAccessFlags = D3D11_CPU_ACCESS_READ.0 as u32;
+            d.Usage = D3D11_USAGE_STAGING;
+            d.MipLevels = 1;
+            d.ArraySize = 1;
+            d.SampleDesc.Count = 1;
+            d.SampleDesc.Quality = 0;
+            d
+        };
+
+        let mut staging_guard = Self::ensure_staging_state(&device, staging_state_arc, desc)?;
+        let staging = &mut *staging_guard;
+        let write_idx = staging.ring.write_slot();
+
+        // 1. Copy current frame to the current ("write") staging texture (GPU operation, async)
+        copy_texture(&context, &texture, &staging.textures[write_idx]);
+        end_query(&context, &staging.queries[write_idx]);
+
+        // 2. Read from the newest staging texture whose copy is done (CPU operation).
+        // Right after (re)initialization there is no previous frame, so the ring waits for the current one instead.
+        let queries = &staging.queries;
+        let Some(read_idx) = staging.ring.read_slot(|slot| is_query_done(&context, &queries[slot]))
+        else {
+            // Mapping now would block until the copy is done, which is the stall the ring is there to hide.
+            crate::log_throttled!(
+                tracing::Level::TRACE,
+                "No staging copy done yet, skipping readback"
+            );
+            staging.metrics.skipped_readbacks.increment();
+            if staging.ring.should_grow() {
+                Self::grow_staging_ring(&device, staging, desc)?;
+            }
+            return Ok(None);
+        };
+
+        map_read_texture(
+            buffer,
+            &context,
+            &staging.textures[read_idx],
+            &desc,
+            pixel_format.bytes_per_pixel(),
+            orientation,
+        )?;
+        Ok(Some(orientation.rotated_size(Vector2 { x: size.Width, y: size.Height })))
+    }
+
+    // Copies the frame into a texture of its own, and sends it on without reading it back.
+    #[cfg(feature = "gpu-frames")]
+    fn process_gpu_frame(
+        frame: Direct3D11CaptureFrame,
+        textures: &GpuTexturePool,
         pixel_format: PixelFormat,
-        tx: tokio::sync::mpsc::Sender<Frame>,
+        tx: &tokio::sync::mpsc::Sender<Frame>,
+        frame_meta: &tokio::sync::watch::Sender<FrameMeta>,
     ) -> super::Result<()> {
+        let (texture, size) = Self::frame_texture(&frame)?;
+        let (device, context) = Self::texture_device(&texture)?;
+
+        let desc = unsafe {
+            let mut d = std::mem::zeroed::<D3D11_TEXTURE2D_DESC>();
+            texture.GetDesc(&mut d);
+            // The frame pool's textures can be larger than what is captured into them.
+            d.Width = size.Width as u32;
+            d.Height = size.Height as u32;
+            d.BindFlags = 0;
+            d.MiscFlags = 0;
+            d.CPUAccessFlags = 0;
+            d.Usage = D3D11_USAGE_DEFAULT;
+            d.MipLevels = 1;
+            d.ArraySize = 1;
+            d
+        };
+        let gpu_texture = textures.take(&device, &desc).map_err(|e| {
+            tracing::error!("Failed to create GPU frame texture: {}", e);
+            WindowsCaptureError::from_device(e, WindowsCaptureError::FailedToCreateTexture)
+        })?;
+        let region = D3D11_BOX {
+            left: 0,
+            top: 0,
+            front: 0,
+            right: desc.Width,
+            bottom: desc.Height,
+            back: 1,
+        };
+        unsafe {
+            context.CopySubresourceRegion(&gpu_texture, 0, 0, 0, 0, &texture, 0, Some(&region));
+        }
+
+        let (frame_duration, dirty_regions) = Self::frame_details(&frame);
+        let frame_size = Vector2 { x: size.Width, y: size.Height };
+
+        let gpu_frame = GpuFrame::new(gpu_texture, textures.clone(), pixel_format, frame_size);
+        let frame = Frame::new_gpu(gpu_frame, Some(frame_duration), Some(dirty_regions));
+        let sequence = Self::publish_frame_meta(frame_meta, &frame);
+        Self::send_frame(tx, frame.with_sequence(sequence))
+    }
+
+    fn frame_texture(
+        frame: &Direct3D11CaptureFrame,
+    ) -> super::Result<(ID3D11Texture2D, windows::Graphics::SizeInt32)> {
         let surface = frame.Surface().map_err(|e| {
             tracing::error!("Failed to get surface! {}", e);
             WindowsCaptureError::FailedToGetSurface(e)
@@ -111,8 +543,19 @@ impl WgcCaptureProvider {
             WindowsCaptureError::FailedToGetContentSize(e)
         })?;
 
-        tracing::trace!("Frame: {} x {}, ptr={:?}", size.Width, size.Height, texture.as_raw());
+        crate::log_throttled!(
+            tracing::Level::TRACE,
+            "Frame: {} x {}, ptr={:?}",
+            size.Width,
+            size.Height,
+            texture.as_raw()
+        );
+        Ok((texture, size))
+    }
 
+    fn texture_device(
+        texture: &ID3D11Texture2D,
+    ) -> super::Result<(ID3D11Device, ID3D11DeviceContext)> {
         let device = unsafe {
             texture.GetDevice().map_err(|e| {
                 tracing::error!("Failed to get device: {}", e);
@@ -127,40 +570,11 @@ impl WgcCaptureProvider {
             })?
         };
 
-        let desc = unsafe {
-            let mut d = std::mem::zeroed::<D3D11_TEXTURE2D_DESC>();
-            texture.GetDesc(&mut d);
-            d.BindFlags = 0;
-            d.MiscFlags = 0;
-            d.CPUAccessFlags = D3D11_CPU_ACCESS_READ.0 as u32;
-            d.Usage = D3D11_USAGE_STAGING;
-            d.MipLevels = 1;
-            d.ArraySize = 1;
-            d.SampleDesc.Count = 1;
-            d.SampleDesc.Quality = 0;
-            d
-        };
-
-        let mut staging = Self::ensure_staging_state(&device, &staging_state_arc, desc)?;
-        let write_idx = (staging.frame_count % Self::PIPELINE_DEPTH as u64) as usize;
-        let write_tex = &staging.textures[write_idx];
-
-        // 1. Copy current frame to the current ("write") staging texture (GPU operation, async)
-        copy_texture(&context, &texture, write_tex);
-
-        // 2. Read from the previous ("read") staging texture (CPU operation, ideally finished by now)
-        let index = (staging.frame_count.wrapping_sub(1)) as usize % Self::PIPELINE_DEPTH;
-        let read_tex = &staging.textures[index];
-        map_read_texture(
-            &mut frame_buffer,
-            &context,
-            read_tex,
-            &desc,
-            pixel_format.bytes_per_pixel(),
-        )?;
-
-        staging.frame_count += 1;
+        Ok((device, context))
+    }
 
+    // The duration of the frame, and the regions that changed since the previous one.
+    fn frame_details(frame: &Direct3D11CaptureFrame) -> (std::time::Duration, Vec<Rect<i32>>) {
         let rel_time = frame
             .SystemRelativeTime()
             .map_err(|e| {
@@ -171,22 +585,35 @@ impl WgcCaptureProvider {
 
         let frame_duration = std::time::Duration::from_nanos((rel_time.Duration / 100) as u64);
 
+        // Coalesced, as busy frames come with hundreds of tiny rects that every consumer would have to go through.
         let dirty_regions = match frame.DirtyRegions() {
-            Ok(regions) => regions.into_iter().map(Into::into).collect(),
+            Ok(regions) => {
+                dirty_rects::coalesce(regions.into_iter().map(Into::into), MAX_DIRTY_RECTS)
+            }
             Err(err) => {
                 tracing::warn!("Failed to get frame dirty regions: {}", err);
                 Vec::new()
             }
         };
 
-        let frame = Frame::new_ensure_rgba(
-            frame_buffer,
-            pixel_format,
-            Vector2 { x: size.Width, y: size.Height },
-            Some(frame_duration),
-            Some(dirty_regions),
-        );
+        (frame_duration, dirty_regions)
+    }
 
+    // Publishes what is known about the frame about to be sent, and returns its sequence.
+    fn publish_frame_meta(
+        frame_meta: &tokio::sync::watch::Sender<FrameMeta>,
+        frame: &Frame,
+    ) -> u64 {
+        let mut sequence = 0;
+        frame_meta.send_modify(|meta| {
+            *meta = meta.next(frame);
+            sequence = meta.sequence;
+        });
+        sequence
+    }
+
+    fn send_frame(tx: &tokio::sync::mpsc::Sender<Frame>, frame: Frame) -> super::Result<()> {
+        tracing::trace!(frame = frame.sequence, "Captured frame");
         match tx.try_send(frame) {
             Ok(_) => (),
             Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
@@ -194,85 +621,69 @@ impl WgcCaptureProvider {
                 return Err(WindowsCaptureError::FrameSenderClosed);
             }
             Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
-                tracing::debug!("Frame channel full, dropping frame.");
+                crate::log_throttled!(tracing::Level::DEBUG, "Frame channel full, dropping frame.");
             }
         }
 
         Ok(())
     }
 
-    fn ensure_staging_state<'a>(
-        device: &'a ID3D11Device,
-        staging_state_arc: &'a Arc<RwLock<Staging>>,
-        desc: D3D11_TEXTURE2D_DESC,
-    ) -> super::Result<std::sync::RwLockWriteGuard<'a, Staging>> {
-        let mut staging = staging_state_arc.write().unwrap();
+    // Rounded down, so the sessions are never held back below the framerate.
+    fn min_update_interval(framerate: CaptureFramerate) -> TimeSpan {
+        TimeSpan { Duration: framerate.to_framerate().frametime_ticks() }
+    }
 
-        // Initialize or re-initialize the staging pool if needed
-        if staging.textures.is_empty()
-            || staging.width != desc.Width
-            || staging.height != desc.Height
-        {
-            tracing::info!(
-                "Initializing staging pool with depth {} for size {}x{}",
-                Self::TX_QUEUE_SIZE,
-                desc.Width,
-                desc.Height
-            );
+    // How long a composite waits for a monitor that hasn't delivered, which it doesn't while nothing on it changes.
+    // A little over a frame, so the monitors that are busy are always waited for.
+    fn region_max_wait(framerate: CaptureFramerate) -> Duration {
+        framerate.to_frametime().mul_f32(1.5)
+    }
 
-            // Clear existing textures since we are possibly resizing
-            staging.textures.clear();
-            staging.width = desc.Width;
-            staging.height = desc.Height;
-            staging.frame_count = 0; // Reset pipeline state
+    // Creates a frame pool and session for each of the capture items, sending their frames to the current stream.
+    fn create_session(&mut self, framerate: CaptureFramerate) -> super::Result<()> {
+        if self.capture_items.is_empty() {
+            tracing::error!("No capture item set!");
+            return Err(WindowsCaptureError::NoCaptureItem);
+        }
 
-            for _ in 0..Self::PIPELINE_DEPTH {
-                let staging_tex = unsafe {
-                    let mut tex = MaybeUninit::<Option<ID3D11Texture2D>>::uninit();
-                    match device.CreateTexture2D(&desc, None, Some(tex.as_mut_ptr())) {
-                        Ok(_) => (),
-                        Err(err) => {
-                            tracing::error!("Failed to create staging texture: {}", err);
-                            return Err(WindowsCaptureError::FailedToCreateTexture(err));
-                        }
-                    }
-                    tex.assume_init().expect("Failed to create staging texture!")
-                };
-                staging.textures.push(staging_tex);
+        debug_assert_com_apartment();
+        let device = self.device.resolve().map_err(|e| {
+            tracing::error!("Failed to resolve device on the current thread! {}", e);
+            WindowsCaptureError::FailedToResolveAgileReference(e)
+        })?;
+
+        if let Some(region) = &self.region {
+            region.lock().unwrap().set_max_wait(Self::region_max_wait(framerate));
+        }
+        for index in 0..self.capture_items.len() {
+            match self.create_item_session(&device, index, framerate) {
+                Ok(session) => self.sessions.push(session),
+                Err(e) => {
+                    self.close_session();
+                    return Err(e);
+                }
             }
         }
 
-        Ok(staging)
+        Ok(())
     }
-}
-
-impl CaptureProvider for WgcCaptureProvider {
-    type Result<T> = super::Result<T>;
-    type Stream = WindowsCaptureStream;
-    type CaptureItem = GraphicsCaptureItem;
-
-    fn create_stream(
-        &mut self,
-        framerate: crate::capture_providers::shared::CaptureFramerate,
-    ) -> Self::Result<Self::Stream> {
-        let (tx, rx) = tokio::sync::mpsc::channel(Self::PIPELINE_DEPTH);
-
-        let capture_item = self.capture_item.as_ref().ok_or_else(|| {
-            tracing::error!("No capture item set!");
-            WindowsCaptureError::NoCaptureItem
-        })?;
-
-        let device = self.device.clone();
-        let staging_state_arc = self.staging_state.clone();
 
+    fn create_item_session(
+        &self,
+        device: &IDirect3DDevice,
+        index: usize,
+        framerate: CaptureFramerate,
+    ) -> super::Result<ItemSession> {
+        let capture_item = &self.capture_items[index];
         let size = capture_item.Size().map_err(|e| {
             tracing::error!("Failed to get size of capture item! {}", e);
             WindowsCaptureError::FailedToGetCaptureItemSize(e)
         })?;
 
+        let pixel_format = self.capture_format();
         let frame_pool = Direct3D11CaptureFramePool::CreateFreeThreaded(
-            &device,
-            self.pixel_format.to_directx_pixel_format(),
+            device,
+            pixel_format.to_directx_pixel_format(),
             Self::WGC_FRAME_BUFFERS,
             size,
         )
@@ -289,61 +700,151 @@ impl CaptureProvider for WgcCaptureProvider {
         if let Err(e) = session.SetIsCursorCaptureEnabled(true) {
             tracing::warn!("Failed to set IsCursorCaptureEnabled: {}", e);
         }
-        if let Err(e) = session.SetIsBorderRequired(true) {
-            tracing::warn!("Failed to set IsBorderRequired: {}", e);
-        }
+        let applied_options = apply_session_options(&session, &self.session_options);
 
-        session.SetMinUpdateInterval(framerate.to_frametime().into()).map_err(|e| {
+        session.SetMinUpdateInterval(Self::min_update_interval(framerate)).map_err(|e| {
             tracing::error!("Failed to set MinUpdateInterval: {}", e);
             WindowsCaptureError::FailedToSetMinUpdateInterval(e)
         })?;
 
         let buffer_pool = self.buffer_pool.clone();
-        let staging_state_arc = staging_state_arc.clone();
-        let pixel_format = self.pixel_format.clone();
+        let staging_state_arc = self.staging_states[index].clone();
+        let part = self.region.clone().map(|composite| RegionPart { monitor: index, composite });
+        let frame_sender = self.frame_sender.clone();
+        let frame_intervals = self.capture_metrics.intervals.clone();
+        let elevate_threads = self.elevate_threads.clone();
+        let frame_meta = self.frame_meta.clone();
+        let state = self.state.clone();
+        let rotation = self.capture_rotation;
+        let rotation_correction = self.rotation_correction;
+        #[cfg(feature = "gpu-frames")]
+        let gpu_output = self.gpu_output.clone();
+        #[cfg(feature = "gpu-frames")]
+        let gpu_textures = self.gpu_textures.clone();
 
         let token = frame_pool
             .FrameArrived(&TypedEventHandler::new(move |sender, _| {
-                let sender: &Direct3D11CaptureFramePool = match &*sender {
-                    Some(s) => s,
-                    None => return Ok(()),
-                };
-
-                match sender.TryGetNextFrame() {
-                    Ok(frame) => {
-                        let content_size = frame.ContentSize().unwrap_or(size);
-                        let buffer_size = content_size.Width as usize
-                            * content_size.Height as usize
-                            * pixel_format.bytes_per_pixel() as usize;
-
-                        if buffer_size == 0 {
-                            tracing::warn!("Frame content size is 0, skipping frame.");
-                            return Ok(());
-                        }
+                // Unwinding into the OS would take the whole app down, so a panic only ends the capture.
+                let handled = panic_guard::catch_panic("Capture frame handler", || {
+                    let sender: &Direct3D11CaptureFramePool = match &*sender {
+                        Some(s) => s,
+                        None => return,
+                    };
 
-                        let mut buffer = buffer_pool.get(buffer_size);
-                        unsafe {
-                            buffer.set_len(buffer_size);
+                    let arrived = Instant::now();
+                    ensure_current_thread_elevated(elevate_threads.load(Ordering::Relaxed));
+
+                    // Lock-free, so this never waits on the stream being swapped or ended.
+                    let tx = frame_sender.load();
+                    let Some(tx) = tx.as_deref() else {
+                        return;
+                    };
+                    // The frames of a region are counted once they are put together.
+                    if part.is_none() {
+                        frame_intervals.record(arrived);
+                    }
+
+                    let frame = match sender.TryGetNextFrame() {
+                        Ok(frame) => frame,
+                        Err(e) => {
+                            tracing::error!("Failed to get next frame: {}", e);
+                            return;
                         }
+                    };
 
-                        match Self::process_frame(
-                            buffer,
+                    let orientation =
+                        rotation.map_or(Orientation::Upright, |(display, desktop)| {
+                            let content_size = frame.ContentSize().unwrap_or(size);
+                            let content_size =
+                                Vector2::new(content_size.Width, content_size.Height);
+                            rotation_correction.orientation(display, desktop, content_size)
+                        });
+
+                    let result = match &part {
+                        // The composite is put together on the CPU, so the frames of a region are always read back.
+                        Some(part) => Self::process_region_frame(
                             frame,
-                            staging_state_arc.clone(),
+                            part,
+                            &staging_state_arc,
                             pixel_format,
-                            tx.clone(),
-                        ) {
-                            Ok(()) => (),
-                            Err(WindowsCaptureError::FrameSenderClosed) => (),
-                            Err(e) => {
-                                tracing::error!("Failed to process frame: {}", e);
-                                // We can't return a custom error here
+                            &buffer_pool,
+                            arrived,
+                        )
+                        .and_then(|composite| {
+                            let Some(composite) = composite else {
+                                return Ok(());
+                            };
+                            frame_intervals.record(arrived);
+                            let sequence = Self::publish_frame_meta(&frame_meta, &composite);
+                            Self::send_frame(tx, composite.with_sequence(sequence))
+                        }),
+                        // Floats are tone-mapped and rotated frames turned upright on the CPU, so they are always
+                        // read back too.
+                        #[cfg(feature = "gpu-frames")]
+                        None if gpu_output.load(Ordering::Relaxed)
+                            && pixel_format != PixelFormat::RGBA16
+                            && orientation.is_upright() =>
+                        {
+                            Self::process_gpu_frame(
+                                frame,
+                                &gpu_textures,
+                                pixel_format,
+                                tx,
+                                &frame_meta,
+                            )
+                        }
+                        None => {
+                            let content_size = frame.ContentSize().unwrap_or(size);
+                            let buffer_size = content_size.Width as usize
+                                * content_size.Height as usize
+                                * pixel_format.bytes_per_pixel() as usize;
+
+                            if buffer_size == 0 {
+                                tracing::warn!("Frame content size is 0, skipping frame.");
+                                return;
                             }
+
+                            let mut buffer = buffer_pool.get(buffer_size);
+                            unsafe {
+                                buffer.set_len(buffer_size);
+                            }
+
+                            Self::process_frame(
+                                buffer,
+                                frame,
+                                &staging_state_arc,
+                                pixel_format,
+                                tx,
+                                &frame_meta,
+                                orientation,
+                            )
                         }
+                    };
+
+                    match result {
+                        Ok(()) => (),
+                        Err(WindowsCaptureError::FrameSenderClosed) => (),
+                        // Nothing works on the device anymore, so every frame ends up here until it is recreated.
+                        Err(WindowsCaptureError::DeviceLost(e)) => {
+                            crate::log_throttled!(
+                                tracing::Level::WARN,
+                                "Lost the graphics device: {}",
+                                e
+                            );
+                            Self::report_interrupted(
+                                &state,
+                                format!("The graphics device was lost: {}", e),
+                            );
+                        }
+                        // We can't return a custom error here
+                        Err(e) => tracing::error!("Failed to process frame: {}", e),
                     }
-                    Err(e) => tracing::error!("Failed to get next frame: {}", e),
+                });
+                if let Err(message) = handled {
+                    // The next frames would likely panic too, so the stream is ended here.
+                    frame_sender.end();
+                    Self::report_error(&state, format!("The capture crashed: {}", message));
                 }
-
                 Ok(())
             }))
             .map_err(|e| {
@@ -351,7 +852,6 @@ impl CaptureProvider for WgcCaptureProvider {
                 WindowsCaptureError::FailedToSetFrameArrivedHandler(e)
             })?;
         tracing::debug!("Added frame arrived handler with token: {}", token);
-        self.stream_tokens.push(token);
 
         if self.capturing {
             session.StartCapture().map_err(|e| {
@@ -360,84 +860,469 @@ impl CaptureProvider for WgcCaptureProvider {
             })?;
         }
 
-        self.frame_pool = Some(frame_pool);
-        self.session = Some(session);
+        Ok(ItemSession { frame_pool, session, frame_arrived_token: token, applied_options })
+    }
 
-        Ok(WindowsCaptureStream::new(rx))
+    fn close_session(&mut self) {
+        for ItemSession { frame_pool, session, frame_arrived_token, .. } in self.sessions.drain(..)
+        {
+            session.Close().ok();
+            tracing::debug!("Removing frame arrived handler: {}", frame_arrived_token);
+            frame_pool.RemoveFrameArrived(frame_arrived_token).ok();
+            frame_pool.Close().ok();
+        }
+    }
+
+    // The stream just stops when an item goes away, so tell the UI why.
+    fn watch_items_closed(&mut self) {
+        self.unwatch_items_closed();
+
+        let reason = match self.capture_source {
+            Some(CaptureSource::VirtualRegion(_)) => {
+                "A display of the shared region was disconnected"
+            }
+            _ if self.capture_monitor.is_some() => "The shared display was disconnected",
+            _ => "The shared screen or window was closed",
+        };
+        for item in &self.capture_items {
+            let state = self.state.clone();
+            let token = item.Closed(&TypedEventHandler::<GraphicsCaptureItem, IInspectable>::new(
+                move |_, _| {
+                    tracing::info!("Capture item was closed");
+                    Self::report_error(&state, reason.to_owned());
+                    Ok(())
+                },
+            ));
+
+            match token {
+                Ok(token) => self.item_closed_handlers.push((item.clone(), token)),
+                Err(e) => tracing::warn!("Failed to set Closed handler on capture item: {}", e),
+            }
+        }
+    }
+
+    // Only a running capture can fail, a stopped one has nothing left to report.
+    fn report_error(state: &tokio::sync::watch::Sender<CaptureState>, reason: String) {
+        state.send_if_modified(|state| {
+            if *state != CaptureState::Capturing {
+                return false;
+            }
+            *state = CaptureState::Error(reason);
+            true
+        });
+    }
+
+    // Only a running capture can be interrupted, and it stays so until it is recovered.
+    fn report_interrupted(state: &tokio::sync::watch::Sender<CaptureState>, reason: String) {
+        state.send_if_modified(|state| {
+            if *state != CaptureState::Capturing {
+                return false;
+            }
+            tracing::info!("Capture interrupted: {}", reason);
+            *state = CaptureState::Interrupted(reason);
+            true
+        });
+    }
+
+    // Whether the device is gone, e.g. as the adapter of the displays was reset, so nothing created on it works anymore.
+    fn is_device_lost(&self) -> bool {
+        let device = self.device.resolve().and_then(|device| winrt_to_native_d3d11device(&device));
+        match device {
+            Ok(device) => unsafe { device.GetDeviceRemovedReason() }.is_err(),
+            Err(e) => {
+                tracing::warn!("Failed to get the device to check on it: {}", e);
+                true
+            }
+        }
+    }
+
+    fn recreate_device(&mut self) -> super::Result<()> {
+        tracing::info!("Creating a new device for the capture");
+        ensure_mta()?;
+        let device = native_to_winrt_d3d11device(&create_d3d_device()?)?;
+        self.device = AgileRef::new(&device).map_err(|e| {
+            tracing::error!("Failed to create agile reference to device! {}", e);
+            WindowsCaptureError::FailedToCreateAgileReference(e)
+        })?;
+
+        // The pooled textures belong to the old device.
+        #[cfg(feature = "gpu-frames")]
+        {
+            self.gpu_textures = GpuTexturePool::default();
+            if self.gpu_output.load(Ordering::Relaxed) {
+                self.set_gpu_output(true)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn unwatch_items_closed(&mut self) {
+        for (item, token) in self.item_closed_handlers.drain(..) {
+            item.RemoveClosed(token).ok();
+        }
+    }
+
+    fn ensure_staging_state<'a>(
+        device: &'a ID3D11Device,
+        staging_state_arc: &'a Arc<RwLock<Staging>>,
+        desc: D3D11_TEXTURE2D_DESC,
+    ) -> super::Result<std::sync::RwLockWriteGuard<'a, Staging>> {
+        let mut staging = staging_state_arc.write().unwrap();
+
+        // Initialize or re-initialize the staging pool if needed
+        if staging.textures.is_empty()
+            || staging.width != desc.Width
+            || staging.height != desc.Height
+            || staging.format != desc.Format
+        {
+            tracing::info!(
+                "Initializing staging pool with depth {} for size {}x{}",
+                Self::PIPELINE_DEPTH,
+                desc.Width,
+                desc.Height
+            );
+
+            // Clear existing textures since we are possibly resizing
+            staging.textures.clear();
+            staging.queries.clear();
+            staging.width = desc.Width;
+            staging.height = desc.Height;
+            staging.format = desc.Format;
+            staging.ring = ReadbackRing::new(Self::PIPELINE_DEPTH, Self::MAX_PIPELINE_DEPTH); // Reset pipeline state
+            staging.metrics.readback_depth.set_max(Self::PIPELINE_DEPTH as f64);
+
+            for _ in 0..Self::PIPELINE_DEPTH {
+                let (staging_tex, query) = Self::create_staging_slot(device, desc)?;
+                staging.textures.push(staging_tex);
+                staging.queries.push(query);
+            }
+        }
+
+        Ok(staging)
     }
 
-    fn set_capture_item(&mut self, capture_item: Self::CaptureItem) -> Self::Result<()> {
+    fn grow_staging_ring(
+        device: &ID3D11Device,
+        staging: &mut Staging,
+        desc: D3D11_TEXTURE2D_DESC,
+    ) -> super::Result<()> {
+        let (staging_tex, query) = Self::create_staging_slot(device, desc)?;
+        staging.textures.push(staging_tex);
+        staging.queries.push(query);
+        let slot = staging.ring.grow();
+        staging.metrics.readback_depth.set_max((slot + 1) as f64);
         tracing::info!(
-            "Setting capture item: {}",
-            capture_item.DisplayName().unwrap_or("<no name>".into())
+            "Staging copies keep falling behind, grew staging pool to depth {}",
+            slot + 1
         );
-        self.capture_item = Some(capture_item);
+        Ok(())
+    }
 
-        // Reset staging state
+    fn create_staging_slot(
+        device: &ID3D11Device,
+        desc: D3D11_TEXTURE2D_DESC,
+    ) -> super::Result<(ID3D11Texture2D, ID3D11Query)> {
+        let staging_tex = unsafe {
+            let mut tex = MaybeUninit::<Option<ID3D11Texture2D>>::uninit();
+            match device.CreateTexture2D(&desc, None, Some(tex.as_mut_ptr())) {
+                Ok(_) => (),
+                Err(err) => {
+                    tracing::error!("Failed to create staging texture: {}", err);
+                    return Err(WindowsCaptureError::from_device(
+                        err,
+                        WindowsCaptureError::FailedToCreateTexture,
+                    ));
+                }
+            }
+            tex.assume_init().expect("Failed to create staging texture!")
+        };
+        let query = create_event_query(device).inspect_err(|e| {
+            tracing::error!("Failed to create staging query: {}", e);
+        })?;
+        Ok((staging_tex, query))
+    }
+}
+
+impl CaptureProvider for WgcCaptureProvider {
+    type Error = WindowsCaptureError;
+    type Stream = WindowsCaptureStream;
+    type CaptureItem = CaptureSource;
+
+    fn create_stream(
+        &mut self,
+        framerate: CaptureFramerate,
+        replace: bool,
+    ) -> super::Result<Self::Stream> {
+        if self.stream_framerate.is_some() && !replace {
+            tracing::warn!(
+                "Tried to create a stream while stream {} is active",
+                self.stream_generation
+            );
+            return Err(WindowsCaptureError::StreamAlreadyActive);
+        }
+
+        let (tx, rx) = tokio::sync::mpsc::channel(Self::PIPELINE_DEPTH);
+
+        if self.sessions.is_empty() {
+            self.frame_sender.replace(tx);
+            if let Err(e) = self.create_session(framerate) {
+                self.frame_sender.end();
+                return Err(e);
+            }
+        } else {
+            // Swapping the sender ends the previous stream once the handler is done with it,
+            // and the sessions carry on feeding the new one.
+            tracing::debug!("Replacing stream {}", self.stream_generation);
+            for ItemSession { session, .. } in &self.sessions {
+                session.SetMinUpdateInterval(Self::min_update_interval(framerate)).map_err(
+                    |e| {
+                        tracing::error!("Failed to set MinUpdateInterval: {}", e);
+                        WindowsCaptureError::FailedToSetMinUpdateInterval(e)
+                    },
+                )?;
+            }
+            if let Some(region) = &self.region {
+                region.lock().unwrap().set_max_wait(Self::region_max_wait(framerate));
+            }
+            self.frame_sender.replace(tx);
+        }
+        self.stream_framerate = Some(framerate);
+        self.capture_metrics.intervals.reset(framerate.to_frametime());
+        self.stream_generation += 1;
+        tracing::debug!("Created stream {} at {}", self.stream_generation, framerate);
+
+        Ok(WindowsCaptureStream::new(rx))
+    }
+
+    fn set_capture_item(&mut self, capture_item: Self::CaptureItem) -> super::Result<()> {
+        tracing::info!("Setting capture item: {}", capture_item.name());
+        let (capture_items, region, kind) = match &capture_item {
+            CaptureSource::Item(item) => (vec![item.clone()], None, capture_item_kind(item)),
+            CaptureSource::VirtualRegion(region) => {
+                let (region, monitors) = region_monitors(*region).inspect_err(|e| {
+                    tracing::error!("Failed to find the monitors of the region: {}", e);
+                })?;
+                if monitors.is_empty() {
+                    tracing::error!("Region {:?} is outside of every monitor", region);
+                    return Err(WindowsCaptureError::RegionOutsideMonitors);
+                }
+                tracing::debug!("Capturing region {:?} from {} monitors", region, monitors.len());
+
+                let composite = RegionComposite::new(
+                    region,
+                    monitors.iter().map(|(_, bounds)| *bounds),
+                    self.pixel_format.bytes_per_pixel() as usize,
+                    Self::region_max_wait(self.stream_framerate.unwrap_or(CaptureFramerate::FPS30)),
+                );
+                let items = monitors.into_iter().map(|(item, _)| item).collect();
+                (items, Some(Arc::new(Mutex::new(composite))), SourceKind::Region)
+            }
+        };
+        self.unwatch_items_closed();
+        self.capture_items = capture_items;
+        self.region = region;
+        self.capture_item_kind = kind;
+        let window = match &capture_item {
+            CaptureSource::Item(item) if kind == SourceKind::Window => find_window(item)
+                .inspect_err(|e| tracing::debug!("Failed to find the shared window: {}", e))
+                .ok()
+                .flatten(),
+            _ => None,
+        };
+        self.capture_process_name = window.and_then(window_process_name);
+        self.capture_icon = window.and_then(window_icon);
+        self.capture_monitor = match &capture_item {
+            CaptureSource::Item(item) if kind == SourceKind::Monitor => {
+                monitor_item_device_name(item)
+            }
+            _ => None,
+        };
+        let hdr_monitor = match kind {
+            SourceKind::Monitor => self.capture_monitor.clone(),
+            SourceKind::Window => window.and_then(window_monitor_device_name),
+            SourceKind::Region => None,
+        };
+        self.capture_hdr = hdr_monitor.is_some_and(|monitor| is_hdr_monitor(&monitor));
+        self.capture_rotation = self.capture_monitor.as_deref().and_then(monitor_rotation);
+        if let Some((rotation, _)) = self.capture_rotation
+            && !rotation.is_upright()
+        {
+            tracing::debug!("The shared monitor is rotated: {:?}", rotation);
+        }
+        self.capture_source = Some(capture_item);
+        self.watch_items_closed();
+        let metrics = &self.capture_metrics;
+        self.staging_states
+            .resize_with(self.capture_items.len(), || Arc::new(RwLock::new(Staging::new(metrics))));
+
+        // Swap live sessions over to the new item in place, so the stream carries on without a gap.
+        // The staging state is kept, and reinitializes by itself if the size changed.
+        if !self.sessions.is_empty()
+            && let Some(framerate) = self.stream_framerate
         {
-            let mut state = self.staging_state.write().unwrap();
+            tracing::debug!("Recreating capture session for the new capture item");
+            self.close_session();
+            return self.create_session(framerate);
+        }
+
+        // Reset staging state
+        for state in &self.staging_states {
+            let mut state = state.write().unwrap();
             state.textures.clear();
-            state.frame_count = 0;
+            state.queries.clear();
         }
+        // The rings are started over at their first frames.
+        self.capture_metrics.readback_depth.set(0.0);
 
         Ok(())
     }
 
-    fn start_capture(&mut self) -> Self::Result<()> {
+    fn start_capture(&mut self) -> super::Result<()> {
         if self.capturing {
             tracing::warn!("Tried to start capture, but was already capturing.");
             return Err(WindowsCaptureError::AlreadyCapturing);
         }
 
-        if self.capture_item.is_none() {
+        if self.capture_source.is_none() {
             tracing::error!("No capture item set!");
             return Err(WindowsCaptureError::NoCaptureItem);
         }
 
-        if let Some(session) = &self.session {
-            session.StartCapture().map_err(|e| {
+        self.state.send_replace(CaptureState::Starting);
+        for ItemSession { session, .. } in &self.sessions {
+            if let Err(e) = session.StartCapture() {
                 tracing::error!("Failed to start capture! {}", e);
-                WindowsCaptureError::FailedToStartCapture(e)
-            })?;
+                self.state.send_replace(CaptureState::Error(e.message()));
+                return Err(WindowsCaptureError::FailedToStartCapture(e));
+            }
         }
 
         self.capturing = true;
+        self.state.send_replace(CaptureState::Capturing);
+
+        let state = self.state.clone();
+        self.display_watcher = DisplayWatcher::new(move || {
+            Self::report_interrupted(&state, "The displays changed".to_owned());
+        })
+        .inspect_err(|e| tracing::warn!("Failed to watch for display changes: {}", e))
+        .ok();
         Ok(())
     }
 
-    fn stop_capture(&mut self) -> Self::Result<()> {
+    fn stop_capture(&mut self) -> super::Result<()> {
         if !self.capturing {
+            // Clears an error from a start that failed.
+            self.state.send_if_modified(|state| {
+                let changed = *state != CaptureState::Idle;
+                *state = CaptureState::Idle;
+                changed
+            });
             return Ok(());
         }
 
-        if let Some(session) = &self.session {
-            session.Close().ok();
+        self.display_watcher = None;
+        self.close_session();
+        // Ends the stream.
+        self.frame_sender.end();
+        self.stream_framerate = None;
+        self.capturing = false;
+        self.state.send_replace(CaptureState::Idle);
+        Ok(())
+    }
+
+    fn recover_capture(&mut self) -> super::Result<Self::CaptureItem> {
+        if !self.capturing {
+            tracing::warn!("Tried to recover capture, but wasn't capturing.");
+            return Err(WindowsCaptureError::NotCapturing);
         }
-        if let Some(frame_pool) = &self.frame_pool {
-            for token in self.stream_tokens.drain(..) {
-                tracing::debug!("Removing frame arrived handler: {}", token);
-                frame_pool.RemoveFrameArrived(token).ok();
-            }
-            frame_pool.Close().ok();
+        let source = self.capture_source.clone().ok_or(WindowsCaptureError::NoCaptureItem)?;
+        tracing::info!("Recovering the capture of {}", source.name());
+
+        // The sessions go first, as they may hold on to the old device.
+        self.close_session();
+        if self.is_device_lost() {
+            self.recreate_device()?;
         }
 
-        self.session = None;
-        self.frame_pool = None;
-        self.capturing = false;
-        Ok(())
+        let source = match (source, &self.capture_monitor) {
+            // A monitor's item only delivers frames in the display mode it was created in.
+            (CaptureSource::Item(_), Some(device_name)) => match monitor_item(device_name)? {
+                Some(item) => CaptureSource::Item(item),
+                None => {
+                    tracing::info!("The shared display {} is gone", device_name);
+                    return Err(WindowsCaptureError::DisplayDisconnected);
+                }
+            },
+            (source, _) => source,
+        };
+
+        // Without sessions, this also clears the staging textures, which may be of the old device or size.
+        self.set_capture_item(source.clone())?;
+        if let Some(framerate) = self.stream_framerate {
+            self.create_session(framerate)?;
+        }
+        self.state.send_replace(CaptureState::Capturing);
+        Ok(source)
     }
 
     fn is_capturing(&self) -> bool {
         self.capturing
     }
+
+    fn capture_item_info(&self) -> Option<CaptureItemInfo> {
+        let source = self.capture_source.as_ref()?;
+        let size = match source {
+            // Windows can be resized, so the size is read every time.
+            CaptureSource::Item(item) => {
+                let size = item
+                    .Size()
+                    .inspect_err(|e| tracing::debug!("Failed to get item size: {}", e))
+                    .ok()?;
+                Vector2::new(size.Width, size.Height)
+            }
+            CaptureSource::VirtualRegion(region) => region.size,
+        };
+        let orientation =
+            self.capture_rotation.map_or(Orientation::Upright, |(display, desktop)| {
+                self.rotation_correction.orientation(display, desktop, size)
+            });
+        let applied_options = self
+            .sessions
+            .first()
+            .map(|session| session.applied_options.clone())
+            .unwrap_or_default();
+        Some(CaptureItemInfo {
+            name: source.name(),
+            size: orientation.rotated_size(size),
+            orientation,
+            kind: self.capture_item_kind,
+            applied_options,
+            process_name: self.capture_process_name.clone(),
+            icon: self.capture_icon.clone(),
+        })
+    }
+
+    fn subscribe_state(&self) -> tokio::sync::watch::Receiver<CaptureState> {
+        self.state.subscribe()
+    }
+
+    fn subscribe_frame_meta(&self) -> tokio::sync::watch::Receiver<FrameMeta> {
+        self.frame_meta.subscribe()
+    }
 }
 
 impl Drop for WgcCaptureProvider {
     fn drop(&mut self) {
         self.stop_capture().ok();
+        self.unwatch_items_closed();
     }
 }
 
-// WgcCaptureProvider holds agile COM objects that are thread-safe.
-unsafe impl Send for WgcCaptureProvider {}
-unsafe impl Sync for WgcCaptureProvider {}
+// WgcCaptureProvider is Send + Sync without any unsafe impls:
+// the device is only reachable through an AgileRef, and the remaining WinRT objects
+// (capture items, free-threaded frame pools, sessions) and D3D11 resources are agile.
+// Keep it that way, so a non-agile interface sneaking in is a compile error instead of a runtime bug.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<WgcCaptureProvider>();
+};
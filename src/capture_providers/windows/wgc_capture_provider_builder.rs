@@ -3,12 +3,13 @@ use windows::Graphics::{Capture::GraphicsCaptureItem, DirectX::Direct3D11::IDire
 use crate::{
     capture_providers::{
         CaptureProvider,
+        shared::WgcSessionOptions,
         windows::{
             WgcCaptureProvider, WindowsCaptureError,
-            d3d11_utils::{create_d3d_device, native_to_winrt_d3d11device},
+            d3d11_utils::{create_d3d_device, ensure_mta, native_to_winrt_d3d11device},
         },
     },
-    utils::pixel_format::PixelFormat,
+    utils::{metrics::Metrics, pixel_format::PixelFormat},
 };
 
 type Result<T> = std::result::Result<T, WgcCaptureProviderBuilderError>;
@@ -21,17 +22,38 @@ pub enum WgcCaptureProviderBuilderError {
     InitializationError(#[from] WindowsCaptureError),
     #[error("Windows error: {0}")]
     WindowsError(#[from] windows::core::Error),
+    #[error("Creating the capture provider panicked: {0}")]
+    Panicked(String),
 }
 
 pub struct WgcCaptureProviderBuilder {
     device: Option<IDirect3DDevice>,
     capture_item: Option<GraphicsCaptureItem>,
     pixel_format: PixelFormat,
+    session_options: WgcSessionOptions,
+    metrics: Metrics,
 }
 
 impl WgcCaptureProviderBuilder {
     pub fn new(pixel_format: PixelFormat) -> Self {
-        WgcCaptureProviderBuilder { device: None, capture_item: None, pixel_format }
+        WgcCaptureProviderBuilder {
+            device: None,
+            capture_item: None,
+            pixel_format,
+            session_options: WgcSessionOptions::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    pub fn with_session_options(mut self, session_options: WgcSessionOptions) -> Self {
+        self.session_options = session_options;
+        self
+    }
+
+    /// The metrics the provider keeps its stats in. It keeps them to itself otherwise.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
     }
 
     #[allow(dead_code)]
@@ -43,6 +65,7 @@ impl WgcCaptureProviderBuilder {
 
     pub fn with_default_device(mut self) -> Result<Self> {
         tracing::debug!("Initializing default capture device for WindowsCaptureProviderBuilder");
+        ensure_mta()?;
         let d3d_device = create_d3d_device()?;
         let winrt_device = native_to_winrt_d3d11device(&d3d_device)?;
         self.device = Some(winrt_device);
@@ -55,7 +78,7 @@ impl WgcCaptureProviderBuilder {
         Ok(self)
     }
 
-    /// Must be called from the main thread.
+    /// May be called from any thread, as the provider only holds agile references.
     pub fn build(self) -> Result<WgcCaptureProvider> {
         tracing::info!("Building WindowsCaptureProvider");
         let device = self.device.ok_or_else(|| {
@@ -63,9 +86,10 @@ impl WgcCaptureProviderBuilder {
             WgcCaptureProviderBuilderError::MissingDevice
         })?;
 
-        let mut capture = WgcCaptureProvider::new(device, self.pixel_format);
+        let mut capture = WgcCaptureProvider::new(&device, self.pixel_format, &self.metrics)?;
+        capture.set_session_options(self.session_options);
         if let Some(capture_item) = self.capture_item {
-            capture.set_capture_item(capture_item)?;
+            capture.set_capture_item(capture_item.into())?;
         }
         Ok(capture)
     }
@@ -1,56 +1,330 @@
-use std::{fs, path::PathBuf};
+use std::{fmt::Display, path::PathBuf, time::Duration};
 
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    capture_providers::shared::CaptureFramerate, media::ffmpeg::FFmpegTranscodeType,
-    utils::pixel_format::PixelFormat,
+    capture_providers::shared::{
+        CaptureFramerate, CaptureSourceId, RotationCorrection, SavedCaptureSource,
+        WgcSessionOptions,
+    },
+    media::ffmpeg::{ContentHint, DecodeAccel, FFmpegTranscodeType, RateControl},
+    networking::webrtc::Fingerprint,
+    ui::{i18n::Language, privacy_regions::SavedPrivacyRegions},
+    utils::{
+        atomic_file::{self, Loaded},
+        bitmap_utils::RedactionStyle,
+        pixel_format::PixelFormat,
+        rect::Rect,
+    },
 };
 
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigImportError {
+    #[error("Failed to read settings: {0}")]
+    ParseError(#[from] serde_json::Error),
+    #[error("Invalid {field}: {reason}")]
+    InvalidField { field: &'static str, reason: &'static str },
+}
+
+/// Settings read from an export, checked and compared to the current ones, but not applied yet.
+#[derive(Debug, Clone)]
+pub struct ConfigImport {
+    /// The current config with the imported settings in place.
+    pub config: Config,
+    /// The fields the import changes.
+    pub changed: Vec<String>,
+    /// Fields in the file that this version doesn't know, which are left out.
+    pub unknown: Vec<String>,
+}
+
+/// A peer the user confirmed they have a direct connection to, by comparing security codes with them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifiedPeer {
+    pub peer_id: String,
+    /// The fingerprint of the peer's certificate at the time.
+    pub fingerprint: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+// Fields missing from older config files are filled in from the default.
+#[serde(default)]
 pub struct Config {
     pub onboarding_done: bool,
+    // The language the UI is shown in, the OS's by default.
+    pub language: Language,
     pub server_url: String,
     pub bitrate: u32,
+    // In bits per second. What is sent never goes over this, whatever the bitrate or the peer ask for, e.g. on a metered connection.
+    pub bandwidth_cap: Option<u32>,
     pub framerate: CaptureFramerate,
     pub pixel_format: PixelFormat,
     pub max_depacket_latency: u16,
+    // Waits only as long as the drops call for, up to the max depacket latency.
+    pub auto_depacket_latency: bool,
+    // The received packets that can wait for the decoder before the connection waits for it too.
+    pub receive_buffer: usize,
+    // Once more packets than this wait for the decoder, it skips ahead to the latest keyframe among them.
+    pub max_receive_queue: usize,
     pub transcoding_type: FFmpegTranscodeType,
+    // What decodes the received video, falling back to software if it can't.
+    pub decode_hw_accel: DecodeAccel,
+    // Frames larger than this in either dimension are scaled down before encoding.
+    pub max_encode_dimension: Option<u32>,
+    // The frames between keyframes. Longer saves bitrate, but takes longer to recover from packet loss.
+    pub gop: u32,
+    pub rate_control: RateControl,
+    // Picks the encoder options that suit what is shared, e.g. sharper text.
+    pub content_hint: ContentHint,
+    // Refreshes the picture gradually instead of with big keyframes, where the encoder can.
+    pub intra_refresh: bool,
+    // Skips frames that look the same as the last one sent, which some apps keep redrawing.
+    pub skip_unchanged_frames: bool,
+    // Offered again when sharing, so the picker can be skipped.
+    pub last_capture_source: Option<SavedCaptureSource>,
+    // The parts of each source hidden from the peer, hidden again whenever it is shared.
+    pub privacy_regions: Vec<SavedPrivacyRegions>,
+    pub redaction_style: RedactionStyle,
+    // Whether incoming calls are announced through the OS while the window isn't focused.
+    pub native_notifications: bool,
+    // Whether `fjarsyn://call/<id>` links open the app, which is registered with the OS for them.
+    pub call_links: bool,
+    // Raises the priority of the capture threads, for high framerates where the readback competes with the encoder.
+    pub capture_thread_priority: bool,
+    // Captures monitors in HDR mode in their own float format and tone-maps it, instead of having Windows clip it.
+    pub hdr_capture: bool,
+    // Which frames of a monitor in portrait, or upside down, are turned upright before they are sent.
+    pub rotation_correction: RotationCorrection,
+    // Set on the capture session where the version of Windows has them.
+    pub capture_session_options: WgcSessionOptions,
+    // The local preview only needs a glimpse of what is shared, so it is shown at a lower rate than it is captured at.
+    pub preview_fps: u32,
+    // How long the remote video goes without a new frame before it says the content is static. Zero never does.
+    pub remote_idle_after: Duration,
+    // Saves a snapshot of the remote video every so often while viewing, to look back at during the call.
+    pub filmstrip: bool,
+    pub filmstrip_interval: Duration,
+    // The most disk space the snapshots of a call take up, in megabytes. The oldest are deleted to stay within it.
+    pub filmstrip_budget_mb: u64,
+    pub verified_peers: Vec<VerifiedPeer>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             onboarding_done: false,
+            language: Language::System,
             bitrate: 8_000_000,
+            bandwidth_cap: None,
             framerate: CaptureFramerate::FPS30,
             server_url: "ws://127.0.0.1:30000/ws".to_string(),
             pixel_format: PixelFormat::RGBA8,
             max_depacket_latency: 1000,
+            auto_depacket_latency: false,
+            receive_buffer: 100,
+            max_receive_queue: 10,
             transcoding_type: FFmpegTranscodeType::default(),
+            decode_hw_accel: DecodeAccel::Auto,
+            max_encode_dimension: None,
+            gop: 120,
+            rate_control: RateControl::Variable,
+            content_hint: ContentHint::Auto,
+            intra_refresh: false,
+            skip_unchanged_frames: false,
+            last_capture_source: None,
+            privacy_regions: Vec::new(),
+            redaction_style: RedactionStyle::default(),
+            native_notifications: true,
+            call_links: true,
+            capture_thread_priority: false,
+            hdr_capture: false,
+            rotation_correction: RotationCorrection::default(),
+            capture_session_options: WgcSessionOptions::default(),
+            preview_fps: 10,
+            remote_idle_after: Duration::from_secs(5),
+            filmstrip: false,
+            filmstrip_interval: Duration::from_secs(30),
+            filmstrip_budget_mb: 200,
+            verified_peers: Vec::new(),
         }
     }
 }
 
 impl Config {
+    /// Only make sense on the machine they were set on, so they are left out of exports and kept on import.
+    pub const MACHINE_FIELDS: &[&str] = &[
+        "onboarding_done",
+        "last_capture_source",
+        "privacy_regions",
+        "verified_peers",
+        "call_links",
+    ];
+
+    /// Whether the peer with this certificate was verified, under any ID, as the server hands out new ones.
+    pub fn is_verified(&self, fingerprint: &Fingerprint) -> bool {
+        let fingerprint = fingerprint.to_string();
+        self.verified_peers.iter().any(|peer| peer.fingerprint == fingerprint)
+    }
+
+    /// Whether the peer was verified with another certificate than this one, which may be someone in between.
+    pub fn fingerprint_changed(&self, peer_id: &str, fingerprint: &Fingerprint) -> bool {
+        let fingerprint = fingerprint.to_string();
+        self.verified_peers
+            .iter()
+            .any(|peer| peer.peer_id == peer_id && peer.fingerprint != fingerprint)
+    }
+
+    pub fn set_verified(&mut self, peer_id: &str, fingerprint: &Fingerprint, verified: bool) {
+        let fingerprint = fingerprint.to_string();
+        self.verified_peers
+            .retain(|peer| peer.peer_id != peer_id && peer.fingerprint != fingerprint);
+        if verified {
+            self.verified_peers.push(VerifiedPeer { peer_id: peer_id.to_owned(), fingerprint });
+        }
+    }
+
+    /// The privacy regions kept for the source, if any were.
+    pub fn privacy_regions(&self, source: &CaptureSourceId) -> Option<&[Rect<f32>]> {
+        self.privacy_regions
+            .iter()
+            .find(|saved| saved.source.matches(source))
+            .map(|saved| saved.regions.as_slice())
+    }
+
+    /// Keeps the privacy regions for the source, replacing those kept before. None are kept if there are none.
+    pub fn set_privacy_regions(&mut self, source: &CaptureSourceId, regions: &[Rect<f32>]) {
+        self.privacy_regions.retain(|saved| !saved.source.matches(source));
+        if !regions.is_empty() {
+            self.privacy_regions
+                .push(SavedPrivacyRegions { source: source.clone(), regions: regions.to_vec() });
+        }
+    }
+
+    /// The bitrate, lowered to the bandwidth cap if there is one.
+    pub fn capped_bitrate(&self, bitrate: u32) -> u32 {
+        self.bandwidth_cap.map_or(bitrate, |cap| bitrate.min(cap))
+    }
+
+    pub fn encoding_settings(&self) -> EncodingSettings {
+        EncodingSettings {
+            bitrate: self.bitrate,
+            framerate: self.framerate,
+            gop: self.gop,
+            rate_control: self.rate_control,
+            max_encode_dimension: self.max_encode_dimension,
+        }
+    }
+
+    pub fn apply_preset(&mut self, preset: QualityPreset) {
+        let settings = preset.settings();
+        self.bitrate = settings.bitrate;
+        self.framerate = settings.framerate;
+        self.gop = settings.gop;
+        self.rate_control = settings.rate_control;
+        self.max_encode_dimension = settings.max_encode_dimension;
+    }
+
+    /// The preset the encoding settings match, or None if they were customized.
+    pub fn quality_preset(&self) -> Option<QualityPreset> {
+        let settings = self.encoding_settings();
+        QualityPreset::ALL.iter().copied().find(|preset| preset.settings() == settings)
+    }
+
+    /// The settings worth taking to another machine, as pretty JSON.
+    pub fn export(&self) -> serde_json::Result<String> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(fields) = value.as_object_mut() {
+            for field in Self::MACHINE_FIELDS {
+                fields.remove(*field);
+            }
+        }
+        serde_json::to_string_pretty(&value)
+    }
+
+    /// Reads exported settings on top of these. Fields the file doesn't have are kept as they are,
+    /// the same way older config files are filled in.
+    pub fn import(&self, content: &str) -> Result<ConfigImport, ConfigImportError> {
+        let imported: serde_json::Map<String, serde_json::Value> = serde_json::from_str(content)?;
+        let current = serde_json::to_value(self)?;
+        let mut merged = current.as_object().cloned().unwrap_or_default();
+
+        let mut unknown = Vec::new();
+        for (field, value) in imported {
+            if Self::MACHINE_FIELDS.contains(&field.as_str()) {
+                continue;
+            }
+            match merged.get_mut(&field) {
+                Some(merged_value) => *merged_value = value,
+                None => unknown.push(field),
+            }
+        }
+        if !unknown.is_empty() {
+            tracing::warn!("Ignoring unknown settings in import: {}", unknown.join(", "));
+        }
+
+        let config: Config = serde_json::from_value(serde_json::Value::Object(merged))?;
+        config.validate()?;
+
+        // Compared after the round trip, so values that are written differently but mean the same don't count.
+        let imported = serde_json::to_value(&config)?;
+        let changed = current
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(field, value)| imported.get(field.as_str()) != Some(*value))
+            .map(|(field, _)| field.clone())
+            .collect();
+
+        Ok(ConfigImport { config, changed, unknown })
+    }
+
+    /// Checks what the types alone don't, so an edited file can't break the app.
+    pub fn validate(&self) -> Result<(), ConfigImportError> {
+        let invalid = |field, reason| Err(ConfigImportError::InvalidField { field, reason });
+        if !(self.server_url.starts_with("ws://") || self.server_url.starts_with("wss://")) {
+            return invalid("server_url", "must start with ws:// or wss://");
+        }
+        if self.bitrate == 0 {
+            return invalid("bitrate", "must be above 0");
+        }
+        if self.bandwidth_cap == Some(0) {
+            return invalid("bandwidth_cap", "must be above 0");
+        }
+        if self.gop == 0 {
+            return invalid("gop", "must be above 0");
+        }
+        if self.max_encode_dimension == Some(0) {
+            return invalid("max_encode_dimension", "must be above 0");
+        }
+        if self.receive_buffer == 0 {
+            return invalid("receive_buffer", "must be above 0");
+        }
+        if self.preview_fps == 0 {
+            return invalid("preview_fps", "must be above 0");
+        }
+        if self.filmstrip_interval.is_zero() {
+            return invalid("filmstrip_interval", "must be above 0");
+        }
+        Ok(())
+    }
+
+    /// Where the config is stored, along with whatever else is kept across runs.
+    pub fn dir() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "fjarsyn").map(|proj_dirs| proj_dirs.config_dir().to_owned())
+    }
+
     fn get_config_path() -> Option<PathBuf> {
-        ProjectDirs::from("", "", "fjarsyn")
-            .map(|proj_dirs| proj_dirs.config_dir().join("config.json"))
+        Self::dir().map(|dir| dir.join("config.json"))
     }
 
-    pub fn load() -> Self {
+    /// The config, and the copy it was recovered from if the file was broken.
+    pub fn load() -> (Self, Option<PathBuf>) {
         tracing::info!("Loading config");
-        if let Some(path) = Self::get_config_path() {
-            if path.exists() {
-                match fs::read(&path) {
-                    Ok(content) => match serde_json::from_slice(&content) {
-                        Ok(config) => return config,
-                        Err(e) => tracing::error!("Failed to parse config file: {}", e),
-                    },
-                    Err(e) => tracing::error!("Failed to read config file: {}", e),
-                }
-            }
+        match Self::get_config_path().map(|path| atomic_file::load_json(&path)) {
+            Some(Loaded::Intact(config)) => return (config, None),
+            Some(Loaded::Recovered { value, from }) => return (value, Some(from)),
+            Some(Loaded::Broken(e)) => tracing::error!("{}", e),
+            Some(Loaded::Missing) | None => {}
         }
 
         tracing::info!("No config file could be loaded, using default config.");
@@ -58,17 +332,78 @@ impl Config {
         if let Err(e) = default.save() {
             tracing::error!("Failed to save default config: {}", e);
         }
-        default
+        (default, None)
     }
 
     pub fn save(&self) -> std::io::Result<()> {
-        if let Some(path) = Self::get_config_path() {
-            if let Some(parent) = path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            let content = serde_json::to_string_pretty(self)?;
-            fs::write(path, content)?;
+        match Self::get_config_path() {
+            Some(path) => atomic_file::save_json(&path, self),
+            None => Ok(()),
         }
-        Ok(())
+    }
+}
+
+/// The encoding settings a [`QualityPreset`] stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingSettings {
+    pub bitrate: u32,
+    pub framerate: CaptureFramerate,
+    pub gop: u32,
+    pub rate_control: RateControl,
+    pub max_encode_dimension: Option<u32>,
+}
+
+/// Bundles of encoding settings for what is being shared, for users who don't want to tune them by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPreset {
+    Text,
+    Balanced,
+    Motion,
+}
+
+impl QualityPreset {
+    pub const ALL: &[QualityPreset] =
+        &[QualityPreset::Text, QualityPreset::Balanced, QualityPreset::Motion];
+
+    pub const fn settings(&self) -> EncodingSettings {
+        match self {
+            // Full resolution for sharp text. Little changes, so keyframes are rarely needed.
+            Self::Text => EncodingSettings {
+                bitrate: 6_000_000,
+                framerate: CaptureFramerate::FPS24,
+                gop: 240,
+                rate_control: RateControl::Variable,
+                max_encode_dimension: None,
+            },
+            Self::Balanced => EncodingSettings {
+                bitrate: 8_000_000,
+                framerate: CaptureFramerate::FPS30,
+                gop: 120,
+                rate_control: RateControl::Variable,
+                max_encode_dimension: None,
+            },
+            // Smoothness over sharpness, with a steady rate so motion doesn't cause latency spikes.
+            Self::Motion => EncodingSettings {
+                bitrate: 12_000_000,
+                framerate: CaptureFramerate::FPS60,
+                gop: 60,
+                rate_control: RateControl::Constant,
+                max_encode_dimension: Some(1920),
+            },
+        }
+    }
+
+    pub const fn description(&self) -> &'static str {
+        match self {
+            Self::Text => "For documents and code. Sharp, at 24 fps.",
+            Self::Balanced => "Good for most things. 30 fps.",
+            Self::Motion => "For videos and games. Smooth 60 fps at up to 1080p.",
+        }
+    }
+}
+
+impl Display for QualityPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
     }
 }
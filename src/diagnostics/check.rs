@@ -0,0 +1,95 @@
+use std::{fmt::Display, time::Duration};
+
+/// A part of the pipeline the diagnostics test, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    GraphicsDevice,
+    CaptureSupport,
+    CaptureFrame,
+    SoftwareEncoder,
+    HardwareEncoder,
+    DecoderRoundTrip,
+    SignalingServer,
+    Stun,
+    TimerResolution,
+}
+
+impl Check {
+    pub const ALL: &[Check] = &[
+        Check::GraphicsDevice,
+        Check::CaptureSupport,
+        Check::CaptureFrame,
+        Check::SoftwareEncoder,
+        Check::HardwareEncoder,
+        Check::DecoderRoundTrip,
+        Check::SignalingServer,
+        Check::Stun,
+        Check::TimerResolution,
+    ];
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GraphicsDevice => write!(f, "Graphics device"),
+            Self::CaptureSupport => write!(f, "Screen capture support"),
+            Self::CaptureFrame => write!(f, "Capture a frame"),
+            Self::SoftwareEncoder => write!(f, "Software encoder"),
+            Self::HardwareEncoder => write!(f, "Selected encoder"),
+            Self::DecoderRoundTrip => write!(f, "Decoder round trip"),
+            Self::SignalingServer => write!(f, "Signaling server"),
+            Self::Stun => write!(f, "Public address (STUN)"),
+            Self::TimerResolution => write!(f, "Timer resolution"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    /// Works, but not as well as it should, e.g. a coarse timer.
+    Warning,
+    Failed,
+    /// Doesn't apply, e.g. the hardware encoder when the software one is selected.
+    Skipped,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Passed => write!(f, "PASS"),
+            Self::Warning => write!(f, "WARN"),
+            Self::Failed => write!(f, "FAIL"),
+            Self::Skipped => write!(f, "SKIP"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub check: Check,
+    pub status: CheckStatus,
+    /// What was found, or why it failed.
+    pub detail: String,
+    pub duration: Duration,
+}
+
+/// The results as plain text, to paste into a bug report.
+pub fn report(results: &[CheckResult]) -> String {
+    let mut report = format!(
+        "Fjarsyn {} diagnostics ({} {})\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    for result in results {
+        report.push_str(&format!(
+            "[{}] {}: {} ({} ms)\n",
+            result.status,
+            result.check,
+            result.detail,
+            result.duration.as_millis()
+        ));
+    }
+    report
+}
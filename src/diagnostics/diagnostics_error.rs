@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use crate::{
+    capture_providers::CaptureError,
+    media::ffmpeg::{FFmpegDecoderError, FFmpegEncoderError},
+    networking::signaling_error::SignalingError,
+};
+
+pub type DiagnosticsResult<T> = Result<T, DiagnosticsError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiagnosticsError {
+    #[error("Graphics error: {0}")]
+    GraphicsError(#[from] windows_core::Error),
+    #[error("Capture error: {0}")]
+    CaptureError(#[from] CaptureError),
+    #[error("Encoder error: {0}")]
+    EncoderError(#[from] FFmpegEncoderError),
+    #[error("Decoder error: {0}")]
+    DecoderError(#[from] FFmpegDecoderError),
+    #[error("Signaling error: {0}")]
+    SignalingError(#[from] SignalingError),
+    #[error("WebRTC error: {0}")]
+    WebRTCError(#[from] webrtc::Error),
+    #[error("No answer within {0:?}")]
+    Timeout(Duration),
+    #[error("The encoder produced no packets")]
+    NothingEncoded,
+    #[error("The decoder produced no frames")]
+    NothingDecoded,
+    #[error("The STUN server didn't tell our public address")]
+    NoReflexiveCandidate,
+    #[error("The check panicked")]
+    Panicked,
+}
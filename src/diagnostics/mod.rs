@@ -0,0 +1,7 @@
+mod check;
+mod diagnostics_error;
+mod runner;
+
+pub use check::{Check, CheckResult, CheckStatus, report};
+pub use diagnostics_error::{DiagnosticsError, DiagnosticsResult};
+pub use runner::{DiagnosticsOptions, run, timer_resolution};
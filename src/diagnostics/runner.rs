@@ -0,0 +1,284 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use webrtc::{
+    api::APIBuilder,
+    ice_transport::{
+        ice_candidate::RTCIceCandidate, ice_candidate_type::RTCIceCandidateType,
+        ice_server::RTCIceServer,
+    },
+    peer_connection::configuration::RTCConfiguration,
+};
+
+use crate::{
+    capture_providers::{
+        CaptureError, PlatformScreenshotter, create_platform_capture_item_for_primary_monitor,
+        describe_platform_graphics_device, is_platform_capture_supported, platform_session_options,
+        shared::SessionOption,
+    },
+    config::Config,
+    diagnostics::{Check, CheckResult, CheckStatus, DiagnosticsError, DiagnosticsResult},
+    media::ffmpeg::{DecodeAccel, FFmpegDecoder, FFmpegEncoder, FFmpegTranscodeType, RateControl},
+    networking::{signaling, webrtc::WebRTC},
+    utils::{
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+
+// The frames encoded and decoded. Small, as only whether it works matters.
+const TEST_FRAME_SIZE: Vector2<i32> = Vector2 { x: 640, y: 360 };
+const TEST_FRAMES: usize = 5;
+const CAPTURE_SIZE: Vector2<i32> = Vector2 { x: 320, y: 180 };
+const SIGNALING_TIMEOUT: Duration = Duration::from_secs(5);
+const STUN_TIMEOUT: Duration = Duration::from_secs(5);
+const TIMER_SAMPLES: usize = 20;
+const TIMER_SLEEP: Duration = Duration::from_millis(1);
+// Frames are paced by sleeping, which overshoots by up to the timer period.
+// Windows defaults to 15.6 ms unless something asks for finer, which is a whole frame at 60 fps.
+const COARSE_TIMER: Duration = Duration::from_millis(4);
+
+/// What the checks run with, as set up in the config.
+#[derive(Debug, Clone)]
+pub struct DiagnosticsOptions {
+    pub server_url: String,
+    pub transcoding_type: FFmpegTranscodeType,
+    pub decode_accel: DecodeAccel,
+    /// The session options of the running capture, if any.
+    pub applied_session_options: Option<Vec<SessionOption>>,
+}
+
+impl DiagnosticsOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            server_url: config.server_url.clone(),
+            transcoding_type: config.transcoding_type,
+            decode_accel: config.decode_hw_accel,
+            applied_session_options: None,
+        }
+    }
+}
+
+/// Runs the check, which doesn't fail itself, but reports what failed in the result.
+pub async fn run(check: Check, options: &DiagnosticsOptions) -> CheckResult {
+    let started = Instant::now();
+    let outcome = match check {
+        Check::GraphicsDevice => {
+            blocking(|| Ok(passed(describe_platform_graphics_device()?))).await
+        }
+        Check::CaptureSupport => {
+            let applied = options.applied_session_options.clone();
+            blocking(move || match is_platform_capture_supported()? {
+                true => {
+                    Ok(passed(capture_support(&platform_session_options(), applied.as_deref())))
+                }
+                false => Ok((
+                    CheckStatus::Failed,
+                    "Windows.Graphics.Capture isn't supported on this version of Windows"
+                        .to_owned(),
+                )),
+            })
+            .await
+        }
+        Check::CaptureFrame => blocking(capture_frame).await,
+        Check::SoftwareEncoder => {
+            blocking(|| encode_test_frames(FFmpegTranscodeType::H264Software)).await
+        }
+        Check::HardwareEncoder => match options.transcoding_type {
+            FFmpegTranscodeType::H264Software => {
+                Ok((CheckStatus::Skipped, "The software encoder is selected".to_owned()))
+            }
+            transcoding_type => blocking(move || encode_test_frames(transcoding_type)).await,
+        },
+        Check::DecoderRoundTrip => {
+            let accel = options.decode_accel;
+            blocking(move || decode_round_trip(accel)).await
+        }
+        Check::SignalingServer => probe_signaling(&options.server_url).await,
+        Check::Stun => gather_public_address().await,
+        Check::TimerResolution => {
+            blocking(|| {
+                let samples = (0..TIMER_SAMPLES)
+                    .map(|_| {
+                        let started = Instant::now();
+                        std::thread::sleep(TIMER_SLEEP);
+                        started.elapsed()
+                    })
+                    .collect::<Vec<_>>();
+                Ok(timer_resolution(&samples))
+            })
+            .await
+        }
+    };
+
+    let (status, detail) = outcome.unwrap_or_else(|e| (CheckStatus::Failed, e.to_string()));
+    CheckResult { check, status, detail, duration: started.elapsed() }
+}
+
+/// Rates how long sleeping for a millisecond took.
+pub fn timer_resolution(samples: &[Duration]) -> (CheckStatus, String) {
+    if samples.is_empty() {
+        return (CheckStatus::Skipped, "Nothing was measured".to_owned());
+    }
+    let mean = samples.iter().sum::<Duration>() / samples.len() as u32;
+    let max = samples.iter().max().copied().unwrap_or_default();
+    let detail = format!(
+        "Sleeping {} ms takes {:.1} ms on average, at most {:.1} ms",
+        TIMER_SLEEP.as_millis(),
+        mean.as_secs_f64() * 1000.0,
+        max.as_secs_f64() * 1000.0
+    );
+    if mean < COARSE_TIMER {
+        (CheckStatus::Passed, detail)
+    } else {
+        (CheckStatus::Warning, format!("{}, which can make the framerate uneven", detail))
+    }
+}
+
+fn passed(detail: impl Into<String>) -> (CheckStatus, String) {
+    (CheckStatus::Passed, detail.into())
+}
+
+// The devices, encoders and decoders block while they are set up.
+async fn blocking(
+    check: impl FnOnce() -> DiagnosticsResult<(CheckStatus, String)> + Send + 'static,
+) -> DiagnosticsResult<(CheckStatus, String)> {
+    tokio::task::spawn_blocking(check).await.map_err(|_| DiagnosticsError::Panicked)?
+}
+
+fn capture_support(supported: &[&str], applied: Option<&[SessionOption]>) -> String {
+    let supported = match supported {
+        [] => "no session options".to_owned(),
+        supported => supported.join(", "),
+    };
+    let mut detail = format!("Windows.Graphics.Capture is supported, with {}", supported);
+    if let Some(applied) = applied {
+        let applied = applied.iter().map(ToString::to_string).collect::<Vec<_>>();
+        detail.push_str(&format!(". Sharing with {}", applied.join(", ")));
+    }
+    detail
+}
+
+fn capture_frame() -> DiagnosticsResult<(CheckStatus, String)> {
+    let screenshotter = PlatformScreenshotter::new().map_err(CaptureError::from)?;
+    let item = create_platform_capture_item_for_primary_monitor()?;
+    let frame = screenshotter.capture(&item.into(), CAPTURE_SIZE).map_err(CaptureError::from)?;
+    Ok(passed(format!("Captured the primary monitor, scaled to {}x{}", frame.size.x, frame.size.y)))
+}
+
+fn test_frames() -> impl Iterator<Item = Vec<u8>> {
+    let mut frames =
+        SyntheticFrames::new(TEST_FRAME_SIZE, PixelFormat::RGBA8, FramePattern::Gradient);
+    std::iter::repeat_with(move || frames.next_bitmap()).take(TEST_FRAMES)
+}
+
+fn encode(transcoding_type: FFmpegTranscodeType) -> DiagnosticsResult<Vec<Vec<u8>>> {
+    let mut encoder =
+        FFmpegEncoder::new(2_000_000, 30.0, PixelFormat::RGBA8, None, 30, RateControl::Variable)?;
+    let mut packets = Vec::new();
+    for bitmap in test_frames() {
+        packets.extend(encoder.encode(
+            &bitmap,
+            transcoding_type,
+            TEST_FRAME_SIZE.x,
+            TEST_FRAME_SIZE.y,
+        )?);
+    }
+    packets.extend(encoder.flush()?);
+    if packets.is_empty() {
+        return Err(DiagnosticsError::NothingEncoded);
+    }
+    Ok(packets)
+}
+
+fn encode_test_frames(
+    transcoding_type: FFmpegTranscodeType,
+) -> DiagnosticsResult<(CheckStatus, String)> {
+    let packets = encode(transcoding_type)?;
+    let bytes = packets.iter().map(Vec::len).sum::<usize>();
+    Ok(passed(format!(
+        "{} encoded {} frames into {} packets, {} bytes",
+        transcoding_type,
+        TEST_FRAMES,
+        packets.len(),
+        bytes
+    )))
+}
+
+// Decodes what the software encoder made, as that is what most peers send.
+fn decode_round_trip(accel: DecodeAccel) -> DiagnosticsResult<(CheckStatus, String)> {
+    let packets = encode(FFmpegTranscodeType::H264Software)?;
+    let mut decoder = FFmpegDecoder::new(FFmpegTranscodeType::H264Software, accel)?;
+    let mut decoded = 0;
+    for packet in &packets {
+        if decoder.decode(packet)?.is_some() {
+            decoded += 1;
+        }
+    }
+    if decoded == 0 {
+        return Err(DiagnosticsError::NothingDecoded);
+    }
+
+    let detail = format!("Decoded {} of {} frames with {}", decoded, TEST_FRAMES, decoder.accel());
+    // Falling back to software works, but costs CPU the user wanted to save.
+    if accel.is_hardware() && !decoder.accel().is_hardware() {
+        return Ok((CheckStatus::Warning, format!("{}, as {} didn't work", detail, accel)));
+    }
+    Ok(passed(detail))
+}
+
+async fn probe_signaling(url: &str) -> DiagnosticsResult<(CheckStatus, String)> {
+    let identity = tokio::time::timeout(SIGNALING_TIMEOUT, signaling::probe(url))
+        .await
+        .map_err(|_| DiagnosticsError::Timeout(SIGNALING_TIMEOUT))??;
+    Ok(passed(match identity.short_code {
+        Some(short_code) => format!("{} answered, assigning short code {}", url, short_code),
+        None => format!("{} answered", url),
+    }))
+}
+
+// Gathers candidates until the server reflexive one, which is the address the STUN server saw us at.
+async fn gather_public_address() -> DiagnosticsResult<(CheckStatus, String)> {
+    let config = RTCConfiguration {
+        ice_servers: vec![RTCIceServer {
+            urls: vec![WebRTC::STUN_SERVER.to_owned()],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let peer_connection = APIBuilder::new().build().new_peer_connection(config).await?;
+    let (candidate_tx, mut candidates) = mpsc::unbounded_channel();
+    peer_connection.on_ice_candidate(Box::new(move |candidate: Option<RTCIceCandidate>| {
+        let _ = candidate_tx.send(candidate);
+        Box::pin(async {})
+    }));
+
+    // Nothing is gathered without something to connect.
+    let gathered = async {
+        peer_connection.create_data_channel("diagnostics", None).await?;
+        let offer = peer_connection.create_offer(None).await?;
+        peer_connection.set_local_description(offer).await?;
+        // None once gathering is done.
+        while let Some(Some(candidate)) = candidates.recv().await {
+            if candidate.typ == RTCIceCandidateType::Srflx {
+                return Ok(candidate);
+            }
+        }
+        Err(DiagnosticsError::NoReflexiveCandidate)
+    };
+    let gathered = tokio::time::timeout(STUN_TIMEOUT, gathered)
+        .await
+        .unwrap_or(Err(DiagnosticsError::Timeout(STUN_TIMEOUT)));
+    if let Err(e) = peer_connection.close().await {
+        tracing::debug!("Failed to close diagnostics peer connection: {}", e);
+    }
+
+    let candidate = gathered?;
+    Ok(passed(format!(
+        "{} sees us at {}:{}",
+        WebRTC::STUN_SERVER,
+        candidate.address,
+        candidate.port
+    )))
+}
@@ -1,7 +1,11 @@
+pub mod capabilities;
 pub mod capture_providers;
 pub mod config;
+pub mod diagnostics;
 pub mod media;
 pub mod networking;
+pub mod platform;
+pub mod session;
 pub mod ui;
 pub mod utils;
 
@@ -1,35 +1,85 @@
-use std::sync::Arc;
+use std::time::SystemTime;
 
-use fjarsyn::{Result, capture_providers, ui};
-use tokio::sync::RwLock;
+use fjarsyn::{
+    Result, capabilities,
+    config::Config,
+    platform::single_instance::{self, Instance, InstanceMessage, InstanceMessages},
+    ui,
+    utils::panic_guard,
+};
 use tracing::Level;
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
 #[cfg(debug_assertions)]
-const LOG_LEVEL: Level = Level::TRACE;
+const LOG_LEVEL: Level = Level::DEBUG;
 #[cfg(not(debug_assertions))]
 const LOG_LEVEL: Level = Level::INFO;
 
+// Takes the usual filter directives, e.g. `fjarsyn::media=trace` to follow the frames through the pipeline.
+const LOG_ENV: &str = "FJARSYN_LOG";
+// Probes the encoders and decoders again instead of using the cached results.
+const REPROBE_ARG: &str = "--reprobe";
+// Starts even if another instance is running, for testing with separate config dirs.
+const ALLOW_MULTIPLE_ARG: &str = "--allow-multiple";
+
+// Hands over to the instance that is running already, if there is one, in which case this one exits.
+// Runs without the guard if it can't be set up, as that is better than not starting at all.
+fn single_instance(message: &InstanceMessage) -> Option<Option<InstanceMessages>> {
+    let Some(dir) = Config::dir() else {
+        tracing::warn!("No config directory, not checking for other instances");
+        return Some(None);
+    };
+    match single_instance::acquire(&dir, message) {
+        Ok(Instance::Primary(listener)) => {
+            tracing::info!("Listening for other instances on port {}", listener.port());
+            Some(Some(listener.spawn()))
+        }
+        Ok(Instance::Secondary) => None,
+        Err(e) => {
+            tracing::error!("Failed to check for other instances: {}", e);
+            Some(None)
+        }
+    }
+}
+
 fn main() -> Result<()> {
-    let subscriber = FmtSubscriber::builder().with_max_level(LOG_LEVEL).finish();
+    let filter =
+        EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| EnvFilter::new(LOG_LEVEL.to_string()));
+    let subscriber = FmtSubscriber::builder().with_env_filter(filter).finish();
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+    panic_guard::install_panic_hook();
 
     tracing::info!("Starting up...");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let message = InstanceMessage::from_args(args.iter().cloned());
+    if message == InstanceMessage::Focus
+        && let Some(link) = args.iter().find(|arg| arg.contains("://"))
+    {
+        tracing::warn!("Ignoring a link that isn't a call link: {:.64}", link);
+    }
+    let instance = if args.iter().any(|arg| arg == ALLOW_MULTIPLE_ARG) {
+        None
+    } else {
+        match single_instance(&message) {
+            Some(instance) => instance,
+            None => {
+                tracing::info!("Another instance is running, handed over to it.");
+                return Ok(());
+            }
+        }
+    };
 
-    let start_config = fjarsyn::config::Config::load();
-
-    tracing::info!("Initializing windows capture provider...");
-    let windows_capture =
-        capture_providers::windows::WgcCaptureProviderBuilder::new(start_config.pixel_format)
-            .with_default_device()?
-            .with_default_capture_item()?
-            .build()?;
-    let windows_capture = Arc::new(RwLock::new(windows_capture));
-    tracing::info!("Windows capture provider initialized.");
+    let reprobe = args.iter().any(|arg| arg == REPROBE_ARG);
+    let capabilities = capabilities::load(reprobe);
+    tracing::info!("{}", capabilities::report(&capabilities, SystemTime::now()));
 
     tracing::info!("Initializing UI...");
-    let app = ui::app::App::new(windows_capture)?;
+    let call_target = match message {
+        InstanceMessage::Call(target) => Some(target),
+        InstanceMessage::Focus => None,
+    };
+    let app = ui::app::App::new(instance, call_target)?;
     tracing::info!("UI initialized.");
 
     tracing::info!("Running app...");
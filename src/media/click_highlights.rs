@@ -0,0 +1,115 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn_shared::MouseButton;
+
+use crate::utils::{bitmap_utils, vector2::Vector2};
+
+/// A click shown to the viewers, normalized to the captured content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Click {
+    pub position: Vector2<f32>,
+    pub button: MouseButton,
+    pub at: Instant,
+}
+
+/// Where the sharer's cursor is and where they clicked lately, drawn into the shared frames so the viewers can follow
+/// along. Positions are normalized to the captured content, so they follow it through changes of resolution.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClickHighlights {
+    cursor: Option<Vector2<f32>>,
+    // Oldest first.
+    clicks: Vec<Click>,
+}
+
+impl ClickHighlights {
+    /// How long a click takes to fade out.
+    pub const FADE: Duration = Duration::from_millis(600);
+    // The sizes are for 1080p, and scale with the frame.
+    const REFERENCE_HEIGHT: f32 = 1080.0;
+    const HALO_RADIUS: f32 = 28.0;
+    const HALO_COLOR: [u8; 3] = [255, 220, 0];
+    const HALO_OPACITY: f32 = 0.3;
+    const CLICK_START_RADIUS: f32 = 12.0;
+    const CLICK_END_RADIUS: f32 = 40.0;
+    const CLICK_OPACITY: f32 = 0.6;
+
+    pub fn cursor(&self) -> Option<Vector2<f32>> {
+        self.cursor
+    }
+
+    /// Moves the halo to where the cursor is, or hides it if it is outside of the captured content.
+    /// Returns whether it moved.
+    pub fn move_cursor(&mut self, cursor: Option<Vector2<f32>>) -> bool {
+        let moved = self.cursor != cursor;
+        self.cursor = cursor;
+        moved
+    }
+
+    /// Adds a click, unless it is outside of the captured content.
+    pub fn click(&mut self, click: Click) {
+        let Vector2 { x, y } = click.position;
+        if (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y) {
+            self.clicks.push(click);
+        }
+    }
+
+    /// Drops the clicks that have faded out. Returns whether there were any.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let before = self.clicks.len();
+        self.clicks.retain(|click| now.saturating_duration_since(click.at) < Self::FADE);
+        self.clicks.len() != before
+    }
+
+    /// The clicks that haven't faded out yet.
+    pub fn clicks(&self, now: Instant) -> impl Iterator<Item = &Click> {
+        self.clicks.iter().filter(move |click| now.saturating_duration_since(click.at) < Self::FADE)
+    }
+
+    /// Whether a click is still fading out, so the frames change even if what was captured doesn't.
+    pub fn is_animating(&self, now: Instant) -> bool {
+        self.clicks(now).next().is_some()
+    }
+
+    /// Draws the halo around the cursor and the clicks as they are at `now` into an RGBA8 bitmap of `size`.
+    /// A click grows as it fades, and the halo is drawn under them.
+    pub fn draw(&self, bitmap: &mut [u8], size: Vector2<i32>, now: Instant) {
+        let scale = size.y as f32 / Self::REFERENCE_HEIGHT;
+        let to_pixels = |position: Vector2<f32>| {
+            Vector2::new(position.x * size.x as f32, position.y * size.y as f32)
+        };
+
+        if let Some(cursor) = self.cursor {
+            bitmap_utils::blend_circle(
+                bitmap,
+                size,
+                to_pixels(cursor),
+                Self::HALO_RADIUS * scale,
+                Self::HALO_COLOR,
+                Self::HALO_OPACITY,
+            );
+        }
+        for click in self.clicks(now) {
+            let t =
+                now.saturating_duration_since(click.at).as_secs_f32() / Self::FADE.as_secs_f32();
+            let radius =
+                Self::CLICK_START_RADIUS + (Self::CLICK_END_RADIUS - Self::CLICK_START_RADIUS) * t;
+            bitmap_utils::blend_circle(
+                bitmap,
+                size,
+                to_pixels(click.position),
+                radius * scale,
+                Self::color(click.button),
+                Self::CLICK_OPACITY * (1.0 - t),
+            );
+        }
+    }
+
+    /// Tells the buttons apart, as e.g. a right click opens a menu where a left one wouldn't.
+    pub fn color(button: MouseButton) -> [u8; 3] {
+        match button {
+            MouseButton::Left => [255, 64, 64],
+            MouseButton::Right => [64, 128, 255],
+            MouseButton::Middle => [64, 200, 64],
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use crate::media::ffmpeg::{ContentHint, ContentType};
+
+/// How quickly the average follows the dirty fraction. About two thirds of a change shows after this long.
+const SMOOTHING: Duration = Duration::from_secs(1);
+/// The average above which the content counts as motion.
+pub const MOTION_ABOVE: f32 = 0.25;
+/// The average below which the content counts as text again. Well below the other, so it doesn't flap in between.
+pub const TEXT_BELOW: f32 = 0.05;
+/// How long the average has to stay past a threshold before switching, as the encoder restarts with a keyframe on every switch.
+pub const HOLD: Duration = Duration::from_secs(3);
+
+/// Picks the content the encoder is tuned for. With [`ContentHint::Auto`] it follows how much of each frame changes,
+/// averaged over time: mostly still frames are text, busy ones are motion.
+#[derive(Debug, Clone)]
+pub struct ContentSwitcher {
+    hint: ContentHint,
+    content: ContentType,
+    // The dirty fraction averaged over time, from 0 to 1.
+    average: f32,
+    last_frame_at: Option<Instant>,
+    // Since when the average has been past the threshold for the other content.
+    crossed_at: Option<Instant>,
+}
+
+impl ContentSwitcher {
+    pub fn new(hint: ContentHint) -> Self {
+        Self {
+            hint,
+            content: Self::initial_content(hint),
+            average: 0.0,
+            last_frame_at: None,
+            crossed_at: None,
+        }
+    }
+
+    // Sharing mostly starts on a still desktop, so Auto starts out as text.
+    fn initial_content(hint: ContentHint) -> ContentType {
+        match hint {
+            ContentHint::Auto | ContentHint::Text => ContentType::Text,
+            ContentHint::Motion => ContentType::Motion,
+        }
+    }
+
+    pub fn content(&self) -> ContentType {
+        self.content
+    }
+
+    pub fn hint(&self) -> ContentHint {
+        self.hint
+    }
+
+    /// Switches to what the hint asks for. Returns the new content if it changed.
+    /// Switching to Auto keeps the current content until the frames say otherwise.
+    pub fn set_hint(&mut self, hint: ContentHint) -> Option<ContentType> {
+        if self.hint == hint {
+            return None;
+        }
+        self.hint = hint;
+        self.crossed_at = None;
+        match hint {
+            ContentHint::Auto => None,
+            hint => self.switch_to(Self::initial_content(hint)),
+        }
+    }
+
+    /// Records the dirty fraction of a frame. Returns the new content if it changed.
+    pub fn observe(&mut self, now: Instant, dirty_fraction: f32) -> Option<ContentType> {
+        // Weighed by the time since the last frame, as frames only come when something changed.
+        let elapsed =
+            self.last_frame_at.map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.last_frame_at = Some(now);
+        let weight = 1.0 - (-elapsed.as_secs_f32() / SMOOTHING.as_secs_f32()).exp();
+        self.average += (dirty_fraction.clamp(0.0, 1.0) - self.average) * weight;
+
+        if self.hint != ContentHint::Auto {
+            return None;
+        }
+
+        let (crossed, other) = match self.content {
+            ContentType::Text => (self.average > MOTION_ABOVE, ContentType::Motion),
+            ContentType::Motion => (self.average < TEXT_BELOW, ContentType::Text),
+        };
+        if !crossed {
+            self.crossed_at = None;
+            return None;
+        }
+        let crossed_at = *self.crossed_at.get_or_insert(now);
+        if now.saturating_duration_since(crossed_at) < HOLD {
+            return None;
+        }
+        self.crossed_at = None;
+        self.switch_to(other)
+    }
+
+    fn switch_to(&mut self, content: ContentType) -> Option<ContentType> {
+        if self.content == content {
+            return None;
+        }
+        self.content = content;
+        Some(content)
+    }
+}
@@ -0,0 +1,348 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use fjarsyn_shared::ControlMessage;
+use tokio::sync::{Mutex, mpsc, watch};
+use tracing::Instrument;
+
+use crate::{
+    media::{
+        ffmpeg::{DecodeAccel, FFmpegDecoder, FFmpegDecoderError},
+        nal::{self, NalCodec},
+        recorder::RecordingTap,
+        stats::DecoderStats,
+    },
+    networking::webrtc::{TrackId, WebRTC},
+    utils::{
+        abort_on_drop::AbortOnDrop,
+        frame::Frame,
+        panic_guard::{self, PanicSlot},
+    },
+};
+
+/// A handle to a running decoder worker, and the frames it decodes.
+/// The worker stops once the last handle is dropped.
+#[derive(Debug, Clone)]
+pub struct DecoderHandle {
+    track: TrackId,
+    frames: Arc<Mutex<mpsc::Receiver<Arc<Frame>>>>,
+    stats: watch::Receiver<DecoderStats>,
+    recording: Arc<StdMutex<Option<RecordingTap>>>,
+    panic: PanicSlot,
+    _task: Arc<AbortOnDrop>,
+}
+
+impl DecoderHandle {
+    /// The track the worker decodes the packets of.
+    pub fn track(&self) -> TrackId {
+        self.track
+    }
+
+    /// The decoded frames, in the order their packets arrived.
+    pub fn frames(&self) -> Arc<Mutex<mpsc::Receiver<Arc<Frame>>>> {
+        self.frames.clone()
+    }
+
+    pub fn stats(&self) -> DecoderStats {
+        *self.stats.borrow()
+    }
+
+    /// Passes the packets decoded from now on to the recorder too, or stops passing them on with None.
+    pub fn record(&self, tap: Option<RecordingTap>) {
+        *self.recording.lock().unwrap() = tap;
+    }
+
+    /// The message of the panic that stopped the worker, if it panicked.
+    pub fn panic(&self) -> Option<&str> {
+        self.panic.get()
+    }
+}
+
+// Identity based, so a subscription to the frames restarts when the worker is replaced.
+impl Hash for DecoderHandle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.frames).hash(state);
+    }
+}
+
+impl PartialEq for DecoderHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.frames, &other.frames)
+    }
+}
+
+impl Eq for DecoderHandle {}
+
+/// When the worker skips queued packets to catch up, rather than drifting further and further behind the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatchUp {
+    /// The packets that can be waiting before skipping ahead.
+    pub max_queued: usize,
+    /// How keyframes are told apart, as only skipping to one keeps the picture intact.
+    pub codec: NalCodec,
+}
+
+impl CatchUp {
+    /// None if keyframes of the codec can't be told apart, in which case nothing can be skipped.
+    pub fn for_mime_type(mime_type: &str, max_queued: usize) -> Option<Self> {
+        NalCodec::from_mime_type(mime_type).map(|codec| Self { max_queued, codec })
+    }
+}
+
+type DecoderFactory = Box<dyn Fn(DecodeAccel) -> Result<FFmpegDecoder, FFmpegDecoderError> + Send>;
+
+/// Decodes the received packets one at a time on a background task, so they are always decoded in order.
+/// Recovers from corrupt packets by itself, asking the presenter for a keyframe and recreating the decoder if needed.
+/// A hardware decoder that fails before decoding anything is replaced with a software one.
+pub struct DecoderWorker {
+    decoder: FFmpegDecoder,
+    create_decoder: DecoderFactory,
+    // What new decoders are created with, which is Software once hardware decoding has failed.
+    accel: DecodeAccel,
+    // Whether the current decoder has decoded a frame, which proves it works on this machine.
+    has_decoded: bool,
+    frames: mpsc::Sender<Arc<Frame>>,
+    stats: watch::Sender<DecoderStats>,
+    recording: Arc<StdMutex<Option<RecordingTap>>>,
+    catch_up: Option<CatchUp>,
+    // Where keyframes are requested from. Without it, recovery waits for the next regular keyframe.
+    webrtc: Option<WebRTC>,
+    consecutive_failures: u32,
+    last_keyframe_request: Option<Instant>,
+    // The frames decoded so far, to tell them apart in the logs. The sender's sequence isn't sent along.
+    decoded: u64,
+    // The frame overflows of the decoders that were replaced.
+    earlier_overflows: u64,
+}
+
+impl DecoderWorker {
+    // Only the latest frame is shown, so there is no point in queueing up more.
+    const FRAME_QUEUE_SIZE: usize = 2;
+    // About a second of video. A keyframe should have arrived by then, so the decoder itself is likely stuck.
+    const MAX_CONSECUTIVE_FAILURES: u32 = 30;
+    // Every packet fails until the keyframe arrives, which shouldn't cause a request each.
+    const KEYFRAME_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Creates a decoder and starts decoding the packets of the track from the receiver with it.
+    /// The receiver is held for as long as the worker runs, so a replacement worker picks up where this one stopped.
+    /// Without a catch up policy, every packet is decoded however far behind the worker falls.
+    pub fn spawn(
+        create_decoder: impl Fn(DecodeAccel) -> Result<FFmpegDecoder, FFmpegDecoderError>
+        + Send
+        + 'static,
+        accel: DecodeAccel,
+        track: TrackId,
+        packets: Arc<Mutex<mpsc::Receiver<Bytes>>>,
+        webrtc: Option<WebRTC>,
+        catch_up: Option<CatchUp>,
+    ) -> Result<DecoderHandle, FFmpegDecoderError> {
+        let decoder = create_decoder(accel)?;
+        let (frames_tx, frames_rx) = mpsc::channel(Self::FRAME_QUEUE_SIZE);
+        let (stats_tx, stats_rx) =
+            watch::channel(DecoderStats { accel: decoder.accel(), ..DecoderStats::default() });
+        let recording = Arc::new(StdMutex::new(None));
+        let worker = Self {
+            decoder,
+            create_decoder: Box::new(create_decoder),
+            accel,
+            has_decoded: false,
+            frames: frames_tx,
+            stats: stats_tx,
+            recording: recording.clone(),
+            catch_up,
+            webrtc,
+            consecutive_failures: 0,
+            last_keyframe_request: None,
+            decoded: 0,
+            earlier_overflows: 0,
+        };
+
+        let panic = PanicSlot::new();
+        let task = tokio::spawn(
+            panic_guard::catch_panics("Decoder worker", panic.clone(), worker.run(packets))
+                .instrument(tracing::info_span!("decoder", track = track.0)),
+        );
+        Ok(DecoderHandle {
+            track,
+            frames: Arc::new(Mutex::new(frames_rx)),
+            stats: stats_rx,
+            recording,
+            panic,
+            _task: Arc::new(AbortOnDrop(task.abort_handle())),
+        })
+    }
+
+    async fn run(mut self, packets: Arc<Mutex<mpsc::Receiver<Bytes>>>) {
+        tracing::debug!("Decoder worker waiting for the packet receiver...");
+        let mut packets = packets.lock().await;
+        tracing::info!("Decoder worker started.");
+
+        'packets: while let Some(packet) = packets.recv().await {
+            let queued = packets.len();
+            self.stats.send_modify(|stats| stats.queue_len = queued);
+
+            let backlog = match self.catch_up {
+                Some(catch_up) if queued > catch_up.max_queued => {
+                    let mut backlog = vec![packet];
+                    while let Ok(packet) = packets.try_recv() {
+                        backlog.push(packet);
+                    }
+                    self.skip_to_keyframe(backlog, catch_up.codec).await
+                }
+                _ => vec![packet],
+            };
+
+            for packet in backlog {
+                if let Some(tap) = &*self.recording.lock().unwrap() {
+                    tap.push(&packet);
+                }
+                if !self.decode(&packet).await {
+                    break 'packets;
+                }
+            }
+        }
+
+        tracing::info!("Decoder worker finished.");
+    }
+
+    // Decodes the packet, passing on the frame if there is one. False once nothing takes the frames anymore.
+    async fn decode(&mut self, packet: &[u8]) -> bool {
+        let start = Instant::now();
+        let frame = match self.decoder.decode(packet) {
+            Ok(frame) => {
+                if self.consecutive_failures > 0 {
+                    tracing::info!(
+                        "Decoder recovered after {} failed packets",
+                        self.consecutive_failures
+                    );
+                    self.consecutive_failures = 0;
+                }
+                match frame {
+                    Some(frame) => frame,
+                    None => return true,
+                }
+            }
+            Err(e) => {
+                self.recover(e).await;
+                return true;
+            }
+        };
+        self.decoded += 1;
+        self.has_decoded = true;
+        let frame_overflows = self.earlier_overflows + self.decoder.frame_overflows();
+        self.stats.send_modify(|stats| {
+            stats.decoded = self.decoded;
+            stats.frame_overflows = frame_overflows;
+        });
+        tracing::trace!(frame = self.decoded, "Decoded frame in {:?}", start.elapsed());
+
+        // Every packet has to be decoded to keep the references intact, but frames can be skipped.
+        match self.frames.try_send(frame) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                crate::log_throttled!(
+                    tracing::Level::DEBUG,
+                    frame = self.decoded,
+                    "Decoded frame queue full, dropping frame"
+                );
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+
+    // Drops the packets before the latest keyframe among them, as nothing after it refers to them.
+    // Without a keyframe, every packet is still needed, so one is requested to catch up with instead.
+    async fn skip_to_keyframe(&mut self, mut backlog: Vec<Bytes>, codec: NalCodec) -> Vec<Bytes> {
+        match backlog.iter().rposition(|packet| nal::is_keyframe(packet, codec)) {
+            Some(0) => {}
+            Some(keyframe) => {
+                backlog.drain(..keyframe);
+                tracing::info!(
+                    "Decoder fell behind, skipped {} packets ahead to a keyframe",
+                    keyframe
+                );
+                self.stats.send_modify(|stats| {
+                    stats.dropped_packets += keyframe as u64;
+                    stats.catch_ups += 1;
+                });
+            }
+            None => {
+                tracing::debug!(
+                    "Decoder fell behind by {} packets, waiting for a keyframe to skip to",
+                    backlog.len()
+                );
+                self.request_keyframe().await;
+            }
+        }
+        backlog
+    }
+
+    async fn recover(&mut self, error: FFmpegDecoderError) {
+        self.consecutive_failures += 1;
+        tracing::warn!(
+            "Failed to decode frame ({} in a row): {}",
+            self.consecutive_failures,
+            error
+        );
+
+        if !error.is_corrupt_input() || self.consecutive_failures >= Self::MAX_CONSECUTIVE_FAILURES
+        {
+            if self.decoder.accel().is_hardware() && !self.has_decoded {
+                self.fall_back_to_software();
+            } else {
+                self.recreate_decoder();
+            }
+        } else if self.consecutive_failures == 1 {
+            // Only the first failure flushes, as the packets after it fail anyway until the keyframe arrives.
+            self.decoder.flush();
+        }
+
+        self.request_keyframe().await;
+    }
+
+    fn recreate_decoder(&mut self) {
+        tracing::warn!("Recreating decoder");
+        match (self.create_decoder)(self.accel) {
+            Ok(decoder) => {
+                let accel = decoder.accel();
+                self.earlier_overflows += self.decoder.frame_overflows();
+                self.decoder = decoder;
+                self.consecutive_failures = 0;
+                self.has_decoded = false;
+                self.stats.send_modify(|stats| stats.accel = accel);
+            }
+            // Keep going with the old one, it may still recover.
+            Err(e) => tracing::error!("Failed to recreate decoder: {}", e),
+        }
+    }
+
+    // Some drivers open a hardware decoder fine, but can't decode the stream with it.
+    fn fall_back_to_software(&mut self) {
+        tracing::warn!("{} decoding failed before decoding anything", self.decoder.accel());
+        self.accel = DecodeAccel::Software;
+        self.recreate_decoder();
+    }
+
+    async fn request_keyframe(&mut self) {
+        let Some(webrtc) = &self.webrtc else {
+            return;
+        };
+        if self
+            .last_keyframe_request
+            .is_some_and(|requested| requested.elapsed() < Self::KEYFRAME_REQUEST_INTERVAL)
+        {
+            return;
+        }
+
+        self.last_keyframe_request = Some(Instant::now());
+        tracing::debug!("Requesting keyframe from presenter");
+        if let Err(e) = webrtc.send_control(&ControlMessage::KeyframeRequest).await {
+            tracing::warn!("Failed to request keyframe: {}", e);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use crate::utils::vector2::Vector2;
+
+/// The size a frame is encoded at: scaled down to fit the max dimension keeping the aspect ratio,
+/// and rounded down to even numbers, which the chroma subsampling needs.
+pub fn capped_size(size: Vector2<i32>, max_dimension: Option<u32>) -> Vector2<i32> {
+    let (mut width, mut height) = (size.x, size.y);
+    if let Some(max_dimension) = max_dimension.map(|d| d as i32)
+        && (width > max_dimension || height > max_dimension)
+    {
+        let scale = max_dimension as f64 / width.max(height) as f64;
+        width = ((width as f64 * scale).round() as i32).max(2);
+        height = ((height as f64 * scale).round() as i32).max(2);
+    }
+    Vector2::new(width & !1, height & !1)
+}
@@ -0,0 +1,868 @@
+use std::{
+    panic,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::Instrument;
+
+use crate::{
+    config::Config,
+    media::{
+        click_highlights::ClickHighlights,
+        content_switcher::ContentSwitcher,
+        ffmpeg::{
+            ContentHint, ContentTuning, FFmpegEncoder, FFmpegEncoderError, FFmpegTranscodeType,
+            RateControl,
+        },
+        frame_pacer::FramePacer,
+        nal::{self, NalCodec},
+        stats::{EncoderMetrics, RollingWindow},
+        watchdog::{WatchdogError, Watched},
+    },
+    networking::webrtc::{WebRTC, WebRTCError},
+    utils::{
+        bitmap_utils::{self, RedactionStyle},
+        content_hash::{self, UnchangedFrames},
+        frame::Frame,
+        frame_ring::FrameRing,
+        framerate::Framerate,
+        metrics::{Counter, Metrics},
+        panic_guard::{self, PanicSlot},
+        pixel_format::PixelFormat,
+        rate_limiter::{Admission, RateLimiter},
+        rect::Rect,
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncoderWorkerError {
+    #[error("Encoder queue is full")]
+    QueueFull,
+    #[error("Encoder worker has shut down")]
+    Closed,
+    #[error("Encoder worker panicked: {0}")]
+    Panicked(String),
+}
+
+/// Where the worker writes the encoded samples.
+pub trait SampleSink: Send + Sync + 'static {
+    type Error: std::fmt::Display;
+
+    fn write_sample(
+        &self,
+        data: Vec<u8>,
+        duration: Duration,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+impl SampleSink for WebRTC {
+    type Error = WebRTCError;
+
+    async fn write_sample(&self, data: Vec<u8>, duration: Duration) -> Result<(), Self::Error> {
+        WebRTC::write_sample(self, data, duration).await
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncoderConfig {
+    pub bitrate: u32,
+    pub target_fps_hz: f32,
+    pub transcoding_type: FFmpegTranscodeType,
+    pub input_format: PixelFormat,
+    pub max_dimension: Option<u32>,
+    pub gop: u32,
+    pub rate_control: RateControl,
+    pub content_hint: ContentHint,
+    pub intra_refresh: bool,
+    /// Skips frames that look the same as the last one encoded, whatever their dirty rects say.
+    pub skip_unchanged: bool,
+    /// In bits per second. The samples are held back or dropped to stay within it, whatever the encoder makes.
+    pub bandwidth_cap: Option<u32>,
+}
+
+impl EncoderConfig {
+    /// The encoder the config asks for, fed with frames at its framerate.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            bitrate: config.capped_bitrate(config.bitrate),
+            target_fps_hz: config.framerate.to_hz(),
+            transcoding_type: config.transcoding_type,
+            // Captured frames are converted to RGBA8 before they get here, whatever they were captured in.
+            input_format: PixelFormat::RGBA8,
+            max_dimension: config.max_encode_dimension,
+            gop: config.gop,
+            rate_control: config.rate_control,
+            content_hint: config.content_hint,
+            intra_refresh: config.intra_refresh,
+            skip_unchanged: config.skip_unchanged_frames,
+            bandwidth_cap: config.bandwidth_cap,
+        }
+    }
+}
+
+/// The parts of the frames hidden before they are encoded, e.g. where notifications pop up.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Redaction {
+    /// Normalized to the frame, so they follow it through changes of resolution.
+    pub regions: Vec<Rect<f32>>,
+    pub style: RedactionStyle,
+}
+
+#[derive(Debug)]
+enum EncoderCommand {
+    SetBitrate(u32),
+    SetMaxDimension(Option<u32>),
+    SetGop(u32),
+    SetRateControl(RateControl),
+    SetContentHint(ContentHint),
+    SetIntraRefresh(bool),
+    SetSkipUnchanged(bool),
+    SetBandwidthCap(Option<u32>),
+    SetRedaction(Redaction),
+    SetClickHighlights(Option<watch::Receiver<ClickHighlights>>),
+    RequestKeyframe,
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// A handle to a running encoder worker.
+/// The worker shuts down by itself once the last handle is dropped.
+#[derive(Debug, Clone)]
+pub struct EncoderHandle {
+    frames: mpsc::Sender<Arc<Frame>>,
+    commands: mpsc::UnboundedSender<EncoderCommand>,
+    // Cleared by the worker once it finds it can't encode frames from the GPU after all.
+    gpu_input: Arc<AtomicBool>,
+    // The config as last sent, so only the changes are sent.
+    config: EncoderConfig,
+    redaction: Redaction,
+    click_highlights: Option<watch::Receiver<ClickHighlights>>,
+    panic: PanicSlot,
+}
+
+impl EncoderHandle {
+    /// Queues a frame for encoding. Frames are dropped rather than queued up if the encoder can't keep up.
+    pub fn send_frame(&self, frame: Arc<Frame>) -> Result<(), EncoderWorkerError> {
+        self.frames.try_send(frame).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => EncoderWorkerError::QueueFull,
+            mpsc::error::TrySendError::Closed(_) => self.closed_error(),
+        })
+    }
+
+    /// The message of the panic that stopped the worker, if it panicked.
+    pub fn panic(&self) -> Option<&str> {
+        self.panic.get()
+    }
+
+    // The worker only goes away early if it panicked, which is worth telling apart from a shutdown.
+    fn closed_error(&self) -> EncoderWorkerError {
+        match self.panic.get() {
+            Some(message) => EncoderWorkerError::Panicked(message.to_owned()),
+            None => EncoderWorkerError::Closed,
+        }
+    }
+
+    pub fn set_bitrate(&mut self, bitrate: u32) -> Result<(), EncoderWorkerError> {
+        self.config.bitrate = bitrate;
+        self.send_command(EncoderCommand::SetBitrate(bitrate))
+    }
+
+    /// Caps the encode resolution, independent of the captured one. Does nothing if the cap didn't change.
+    pub fn set_max_dimension(
+        &mut self,
+        max_dimension: Option<u32>,
+    ) -> Result<(), EncoderWorkerError> {
+        if self.config.max_dimension == max_dimension {
+            return Ok(());
+        }
+        self.config.max_dimension = max_dimension;
+        self.send_command(EncoderCommand::SetMaxDimension(max_dimension))
+    }
+
+    /// Whether the running worker can take on the config, or has to be replaced with a new one.
+    pub fn can_reconfigure(&self, config: &EncoderConfig) -> bool {
+        self.config.target_fps_hz == config.target_fps_hz
+            && self.config.transcoding_type == config.transcoding_type
+            && self.config.input_format == config.input_format
+    }
+
+    /// Sends whatever changed in the config to the worker. See [`Self::can_reconfigure`] for what can't be changed.
+    pub fn reconfigure(&mut self, config: EncoderConfig) -> Result<(), EncoderWorkerError> {
+        if self.config == config {
+            return Ok(());
+        }
+
+        if self.config.bitrate != config.bitrate {
+            self.send_command(EncoderCommand::SetBitrate(config.bitrate))?;
+        }
+        self.set_max_dimension(config.max_dimension)?;
+        if self.config.gop != config.gop {
+            self.send_command(EncoderCommand::SetGop(config.gop))?;
+        }
+        if self.config.rate_control != config.rate_control {
+            self.send_command(EncoderCommand::SetRateControl(config.rate_control))?;
+        }
+        if self.config.content_hint != config.content_hint {
+            self.send_command(EncoderCommand::SetContentHint(config.content_hint))?;
+        }
+        if self.config.intra_refresh != config.intra_refresh {
+            self.send_command(EncoderCommand::SetIntraRefresh(config.intra_refresh))?;
+        }
+        if self.config.skip_unchanged != config.skip_unchanged {
+            self.send_command(EncoderCommand::SetSkipUnchanged(config.skip_unchanged))?;
+        }
+        if self.config.bandwidth_cap != config.bandwidth_cap {
+            self.send_command(EncoderCommand::SetBandwidthCap(config.bandwidth_cap))?;
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Hides the regions of the frames queued from now on. Does nothing if they didn't change.
+    pub fn set_redaction(
+        &mut self,
+        regions: &[Rect<f32>],
+        style: RedactionStyle,
+    ) -> Result<(), EncoderWorkerError> {
+        if self.redaction.regions == regions && self.redaction.style == style {
+            return Ok(());
+        }
+        self.redaction = Redaction { regions: regions.to_vec(), style };
+        self.send_command(EncoderCommand::SetRedaction(self.redaction.clone()))
+    }
+
+    /// Draws the cursor halo and the clicks into the frames queued from now on, as they are when each is encoded, or
+    /// stops if None. Does nothing if it is the same channel as before.
+    pub fn set_click_highlights(
+        &mut self,
+        highlights: Option<watch::Receiver<ClickHighlights>>,
+    ) -> Result<(), EncoderWorkerError> {
+        let same = match (&self.click_highlights, &highlights) {
+            (Some(current), Some(new)) => current.same_channel(new),
+            (current, new) => current.is_none() && new.is_none(),
+        };
+        if same {
+            return Ok(());
+        }
+        self.click_highlights = highlights.clone();
+        self.send_command(EncoderCommand::SetClickHighlights(highlights))
+    }
+
+    /// Whether frames can be left on the GPU for this encoder, instead of being read back into memory.
+    /// Never while anything is redacted or clicks are highlighted, which is done in memory.
+    pub fn accepts_gpu_frames(&self) -> bool {
+        self.gpu_input.load(Ordering::Relaxed)
+            && self.redaction.regions.is_empty()
+            && self.click_highlights.is_none()
+    }
+
+    /// Makes the next encoded frame a keyframe.
+    pub fn request_keyframe(&self) -> Result<(), EncoderWorkerError> {
+        self.send_command(EncoderCommand::RequestKeyframe)
+    }
+
+    /// Stops the worker once the frames already queued are encoded, and waits for the rest to be flushed to the sink.
+    pub async fn shutdown(&self) -> Result<(), EncoderWorkerError> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command(EncoderCommand::Shutdown(tx))?;
+        rx.await.map_err(|_| self.closed_error())
+    }
+
+    fn send_command(&self, command: EncoderCommand) -> Result<(), EncoderWorkerError> {
+        self.commands.send(command).map_err(|_| self.closed_error())
+    }
+}
+
+// Holds the samples written to the bandwidth cap, if there is one.
+struct BandwidthCap {
+    limiter: Option<RateLimiter>,
+    // None if keyframes can't be told apart, in which case every sample is taken for one, and only held back.
+    codec: Option<NalCodec>,
+    max_delay: Duration,
+    sent_1s: RollingWindow,
+    drops: Arc<Counter>,
+    // Whether the encoder was asked for a keyframe since the samples started being dropped.
+    keyframe_requested: bool,
+}
+
+impl BandwidthCap {
+    // The cap holds over any second, so a keyframe can take up to a second's worth at once.
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    fn new(config: &EncoderConfig, drops: Arc<Counter>) -> Self {
+        // Half a frame, so waiting for the cap doesn't hold up the next frame.
+        let max_delay = Framerate::from_hz(config.target_fps_hz as f64).frametime() / 2;
+        Self {
+            limiter: config.bandwidth_cap.map(|cap| Self::limiter(cap, max_delay)),
+            codec: NalCodec::from_mime_type(config.transcoding_type.mime_type()),
+            max_delay,
+            sent_1s: RollingWindow::new(Duration::from_secs(1)),
+            drops,
+            keyframe_requested: false,
+        }
+    }
+
+    fn limiter(cap: u32, max_delay: Duration) -> RateLimiter {
+        RateLimiter::new(cap as u64 / 8, Self::WINDOW, max_delay)
+    }
+
+    fn set_cap(&mut self, cap: Option<u32>) {
+        match (&mut self.limiter, cap) {
+            (Some(limiter), Some(cap)) => limiter.set_rate(cap as u64 / 8, Instant::now()),
+            (limiter, cap) => *limiter = cap.map(|cap| Self::limiter(cap, self.max_delay)),
+        }
+    }
+
+    // Waits until the sample can be sent within the cap. Returns false if it has to be dropped instead.
+    async fn admit(&mut self, sample: &[u8]) -> bool {
+        if let Some(limiter) = &mut self.limiter {
+            let keyframe = self.codec.is_none_or(|codec| nal::is_keyframe(sample, codec));
+            match limiter.admit(sample.len(), keyframe, Instant::now()) {
+                Admission::Send => (),
+                Admission::Delay(delay) => tokio::time::sleep(delay).await,
+                Admission::Drop => {
+                    self.drops.increment();
+                    return false;
+                }
+            }
+        }
+        self.sent_1s.record(Instant::now(), sample.len() as u64);
+        true
+    }
+
+    // Whether to ask the encoder for a keyframe, which is once for every run of dropped samples.
+    fn wants_keyframe(&mut self) -> bool {
+        let awaiting = self.limiter.as_ref().is_some_and(RateLimiter::is_awaiting_keyframe);
+        !std::mem::replace(&mut self.keyframe_requested, awaiting) && awaiting
+    }
+}
+
+// Creates the encoders for the config, whose transcode type is the one the encoder will be fed, which changes from
+// the one configured when falling back.
+type EncoderFactory =
+    Box<dyn Fn(&EncoderConfig) -> Result<FFmpegEncoder, FFmpegEncoderError> + Send>;
+
+/// Encodes captured frames on a background task, and writes the samples to a sink.
+pub struct EncoderWorker<S: SampleSink> {
+    config: EncoderConfig,
+    sink: S,
+    create_encoder: EncoderFactory,
+    cap: BandwidthCap,
+    // On a thread of its own, so an encode stuck in the driver can be given up on.
+    encoder: Watched<FFmpegEncoder>,
+    frames: mpsc::Receiver<Arc<Frame>>,
+    commands: mpsc::UnboundedReceiver<EncoderCommand>,
+    pacer: FramePacer,
+    content: ContentSwitcher,
+    metrics: EncoderMetrics,
+    gpu_input: Arc<AtomicBool>,
+    bytes_1s: RollingWindow,
+    bytes_10s: RollingWindow,
+    frames_10s: RollingWindow,
+    // The duration of the last frame, used for the samples flushed on shutdown.
+    last_duration: Duration,
+    // Whether the encoder has produced anything yet, as falling back only makes sense before it has.
+    has_output: bool,
+    redaction: Redaction,
+    click_highlights: Option<watch::Receiver<ClickHighlights>>,
+    // The captured frames are shared with the local preview, so they are redacted and drawn on in copies of their own.
+    composited_frames: FrameRing,
+    unchanged: UnchangedFrames,
+}
+
+impl<S: SampleSink> EncoderWorker<S> {
+    const FRAME_QUEUE_SIZE: usize = 10;
+    // The frame being encoded, and the one after it.
+    const COMPOSITED_FRAMES: usize = 2;
+    // Encodes take milliseconds, so one that has taken this long is stuck in the driver, and won't come back.
+    const WEDGED_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Keeps its stats in the metrics, replacing those of the worker before.
+    pub fn spawn(
+        config: EncoderConfig,
+        sink: S,
+        metrics: &Metrics,
+    ) -> Result<EncoderHandle, FFmpegEncoderError> {
+        Self::spawn_with(Self::new_encoder, config, sink, metrics)
+    }
+
+    /// Like [`EncoderWorker::spawn`], with the encoders created by the factory, including those it falls back to.
+    pub fn spawn_with(
+        create_encoder: impl Fn(&EncoderConfig) -> Result<FFmpegEncoder, FFmpegEncoderError>
+        + Send
+        + 'static,
+        config: EncoderConfig,
+        sink: S,
+        metrics: &Metrics,
+    ) -> Result<EncoderHandle, FFmpegEncoderError> {
+        let create_encoder: EncoderFactory = Box::new(create_encoder);
+        let content = ContentSwitcher::new(config.content_hint);
+        let encoder =
+            Self::watched_encoder(&create_encoder, &config, Self::tuning(&config, &content))?;
+
+        let (frames_tx, frames) = mpsc::channel(Self::FRAME_QUEUE_SIZE);
+        let (commands_tx, commands) = mpsc::unbounded_channel();
+        let metrics = EncoderMetrics::register(metrics);
+        metrics.target_fps.set(config.target_fps_hz as f64);
+        metrics.output_fps.set(config.target_fps_hz as f64);
+
+        let gpu_input = Arc::new(AtomicBool::new(config.transcoding_type.accepts_d3d11_frames()));
+
+        tracing::debug!(
+            "Starting encoder worker. target_fps_hz: {}, bitrate: {}",
+            config.target_fps_hz,
+            config.bitrate
+        );
+        let worker = Self {
+            config,
+            sink,
+            create_encoder,
+            cap: BandwidthCap::new(&config, metrics.capped_drops.clone()),
+            encoder,
+            frames,
+            commands,
+            pacer: FramePacer::new(config.target_fps_hz),
+            content,
+            metrics,
+            gpu_input: gpu_input.clone(),
+            bytes_1s: RollingWindow::new(Duration::from_secs(1)),
+            bytes_10s: RollingWindow::new(Duration::from_secs(10)),
+            frames_10s: RollingWindow::new(Duration::from_secs(10)),
+            last_duration: Duration::ZERO,
+            has_output: false,
+            redaction: Redaction::default(),
+            click_highlights: None,
+            composited_frames: FrameRing::new(Self::COMPOSITED_FRAMES),
+            unchanged: UnchangedFrames::default(),
+        };
+        // Runs in the span it was spawned from, which is the call's if there is one.
+        let panic = PanicSlot::new();
+        tokio::spawn(
+            panic_guard::catch_panics("Encoder worker", panic.clone(), worker.run())
+                .in_current_span(),
+        );
+
+        Ok(EncoderHandle {
+            frames: frames_tx,
+            commands: commands_tx,
+            gpu_input,
+            config,
+            redaction: Redaction::default(),
+            click_highlights: None,
+            panic,
+        })
+    }
+
+    /// The encoder the config asks for. The transcode type is passed along with each frame.
+    pub fn new_encoder(config: &EncoderConfig) -> Result<FFmpegEncoder, FFmpegEncoderError> {
+        FFmpegEncoder::new(
+            config.bitrate,
+            config.target_fps_hz,
+            config.input_format,
+            config.max_dimension,
+            config.gop,
+            config.rate_control,
+        )
+    }
+
+    fn watched_encoder(
+        create_encoder: &EncoderFactory,
+        config: &EncoderConfig,
+        tuning: ContentTuning,
+    ) -> Result<Watched<FFmpegEncoder>, FFmpegEncoderError> {
+        let mut encoder = create_encoder(config)?;
+        encoder.set_tuning(tuning);
+        Ok(Watched::spawn("encoder", encoder, Self::WEDGED_TIMEOUT))
+    }
+
+    // An encoder for the current config with another transcode type.
+    fn create_encoder_for(
+        &self,
+        transcoding_type: FFmpegTranscodeType,
+    ) -> Result<Watched<FFmpegEncoder>, FFmpegEncoderError> {
+        let config = EncoderConfig { transcoding_type, ..self.config };
+        Self::watched_encoder(&self.create_encoder, &config, Self::tuning(&config, &self.content))
+    }
+
+    fn tuning(config: &EncoderConfig, content: &ContentSwitcher) -> ContentTuning {
+        ContentTuning { content: content.content(), intra_refresh: config.intra_refresh }
+    }
+
+    // The encoder restarts with a keyframe to take the new options, so only call this when they changed.
+    async fn retune(&mut self) {
+        let tuning = Self::tuning(&self.config, &self.content);
+        tracing::info!(
+            "Tuning the encoder for {} content, intra refresh: {}",
+            tuning.content,
+            tuning.intra_refresh
+        );
+        self.call_encoder(move |encoder| encoder.set_tuning(tuning)).await;
+    }
+
+    // Runs the call on the encoder's thread. None if the encoder got stuck, in which case it was replaced.
+    async fn call_encoder<R: Send + 'static>(
+        &mut self,
+        f: impl FnOnce(&mut FFmpegEncoder) -> R + Send + 'static,
+    ) -> Option<R> {
+        match self.encoder.call(f).await {
+            Ok(result) => Some(result),
+            // Raised again here, so the worker stops the same way it would have with the encoder on its own task.
+            Err(WatchdogError::Panicked(message)) => panic::resume_unwind(Box::new(message)),
+            Err(e) => {
+                tracing::error!("Encoder stopped responding: {}", e);
+                self.restart_wedged();
+                None
+            }
+        }
+    }
+
+    async fn run(mut self) {
+        let shutdown_reply = loop {
+            tokio::select! {
+                // Commands go first, so a keyframe request applies to the very next frame.
+                biased;
+
+                command = self.commands.recv() => match command {
+                    Some(EncoderCommand::SetBitrate(bitrate)) => {
+                        tracing::info!("Setting encoder bitrate to {}", bitrate);
+                        self.config.bitrate = bitrate;
+                        self.call_encoder(move |encoder| encoder.set_bitrate(bitrate)).await;
+                    }
+                    Some(EncoderCommand::SetMaxDimension(max_dimension)) => {
+                        tracing::info!("Setting max encode dimension to {:?}", max_dimension);
+                        self.config.max_dimension = max_dimension;
+                        self.call_encoder(move |encoder| encoder.set_max_dimension(max_dimension))
+                            .await;
+                    }
+                    Some(EncoderCommand::SetGop(gop)) => {
+                        tracing::info!("Setting encoder GOP to {}", gop);
+                        self.config.gop = gop;
+                        self.call_encoder(move |encoder| encoder.set_gop(gop)).await;
+                    }
+                    Some(EncoderCommand::SetRateControl(rate_control)) => {
+                        tracing::info!("Setting encoder rate control to {}", rate_control);
+                        self.config.rate_control = rate_control;
+                        self.call_encoder(move |encoder| encoder.set_rate_control(rate_control))
+                            .await;
+                    }
+                    Some(EncoderCommand::SetContentHint(hint)) => {
+                        tracing::info!("Setting encoder content hint to {}", hint);
+                        self.config.content_hint = hint;
+                        if self.content.set_hint(hint).is_some() {
+                            self.retune().await;
+                        }
+                    }
+                    Some(EncoderCommand::SetIntraRefresh(intra_refresh)) => {
+                        self.config.intra_refresh = intra_refresh;
+                        self.retune().await;
+                    }
+                    Some(EncoderCommand::SetSkipUnchanged(skip)) => {
+                        tracing::info!("Skipping unchanged frames: {}", skip);
+                        self.config.skip_unchanged = skip;
+                        self.unchanged.reset();
+                    }
+                    Some(EncoderCommand::SetBandwidthCap(cap)) => {
+                        tracing::info!("Setting bandwidth cap to {:?}", cap);
+                        self.config.bandwidth_cap = cap;
+                        self.cap.set_cap(cap);
+                    }
+                    Some(EncoderCommand::SetRedaction(redaction)) => {
+                        tracing::info!(
+                            "Redacting {} region(s) with {}",
+                            redaction.regions.len(),
+                            redaction.style
+                        );
+                        self.redaction = redaction;
+                        self.unchanged.reset();
+                    }
+                    Some(EncoderCommand::SetClickHighlights(highlights)) => {
+                        tracing::info!("Highlighting clicks: {}", highlights.is_some());
+                        self.click_highlights = highlights;
+                        self.unchanged.reset();
+                    }
+                    Some(EncoderCommand::RequestKeyframe) => {
+                        self.unchanged.reset();
+                        self.call_encoder(FFmpegEncoder::request_keyframe).await;
+                    }
+                    Some(EncoderCommand::Shutdown(reply)) => break Some(reply),
+                    None => break None,
+                },
+
+                frame = self.frames.recv() => match frame {
+                    Some(frame) => self.encode(frame).await,
+                    None => break None,
+                },
+            }
+        };
+
+        // Encode what was queued before the shutdown, then drain the encoder itself.
+        self.frames.close();
+        while let Some(frame) = self.frames.recv().await {
+            self.encode(frame).await;
+        }
+        match self.call_encoder(FFmpegEncoder::flush).await {
+            Some(Ok(nal_units)) => {
+                Self::write_samples(&self.sink, &mut self.cap, nal_units, self.last_duration).await;
+            }
+            Some(Err(e)) => tracing::warn!("Failed to flush encoder: {}", e),
+            None => (),
+        }
+
+        if let Some(reply) = shutdown_reply {
+            let _ = reply.send(());
+        }
+        tracing::info!("Encoder worker finished.");
+    }
+
+    async fn encode(&mut self, frame: Arc<Frame>) {
+        let Some(frame_duration) = frame.duration else {
+            tracing::error!("Frame duration is None!");
+            return;
+        };
+
+        // The encoder would take it for its own format, which for RGBA16 is twice the size.
+        if frame.gpu.is_none() && frame.format != self.config.input_format {
+            crate::log_throttled!(
+                tracing::Level::ERROR,
+                "Dropping a {:?} frame, as the encoder takes {:?}",
+                frame.format,
+                self.config.input_format
+            );
+            return;
+        }
+
+        // Skipped frames count too, as they are still what is being shared.
+        if self.content.observe(Instant::now(), frame.dirty_area_fraction).is_some() {
+            self.retune().await;
+        }
+
+        // Frames without any dirty rects have nothing new, so they are the first to go when decimating.
+        let dirty = frame.dirty_area_fraction > 0.0;
+        let Some(sample_duration) = self.pacer.admit(frame_duration, dirty) else {
+            tracing::trace!(
+                frame = frame.sequence,
+                "Skipping frame to keep up with the frame interval"
+            );
+            return;
+        };
+        self.last_duration = sample_duration;
+
+        if self.is_unchanged(&frame) {
+            tracing::trace!(
+                frame = frame.sequence,
+                "Skipping frame that looks the same as the last one"
+            );
+            self.pacer.defer(sample_duration);
+            self.metrics.unchanged_skips.increment();
+            return;
+        }
+
+        let Some(encoded) = self.composited(&frame) else {
+            return;
+        };
+        let start = Instant::now();
+        let transcoding_type = self.config.transcoding_type;
+        let Some(result) = self
+            .call_encoder(move |encoder| match &encoded.gpu {
+                Some(gpu_frame) => encoder.encode_gpu(gpu_frame, transcoding_type),
+                None => {
+                    encoder.encode(&encoded.data, transcoding_type, encoded.size.x, encoded.size.y)
+                }
+            })
+            .await
+        else {
+            return;
+        };
+        let nal_units = match result {
+            Ok(nal_units) => nal_units,
+            // The capture switches to frames in memory once it sees this, so only the frames in flight are lost.
+            Err(e) if frame.gpu.is_some() => {
+                tracing::warn!("Can't encode frames from the GPU, falling back to memory: {}", e);
+                self.gpu_input.store(false, Ordering::Relaxed);
+                return;
+            }
+            Err(e) => {
+                tracing::error!("Encoding failed: {}", e);
+                self.try_fallback();
+                return;
+            }
+        };
+
+        let encode_time = start.elapsed();
+        if let Some(decimation) = self.pacer.record_encode(encode_time) {
+            self.report_decimation(decimation);
+        }
+        self.record_stats(encode_time, nal_units.iter().map(Vec::len).sum());
+
+        tracing::trace!(frame = frame.sequence, ?encode_time, "Encoded frame");
+        self.has_output |= !nal_units.is_empty();
+        Self::write_samples(&self.sink, &mut self.cap, nal_units, sample_duration).await;
+
+        self.metrics.sent_bitrate_1s.set(self.cap.sent_1s.per_second() * 8.0);
+        // What was dropped leaves the peer unable to decode anything until the next keyframe.
+        if self.cap.wants_keyframe() {
+            tracing::debug!(
+                "Dropped samples to stay within the bandwidth cap, requesting a keyframe"
+            );
+            self.unchanged.reset();
+            self.call_encoder(FFmpegEncoder::request_keyframe).await;
+        }
+    }
+
+    // Whether the frame looks the same as the last one encoded. Checked on what was captured, as what is redacted only
+    // changes along with the regions, which starts over. The clicks change what is drawn as they fade, so frames
+    // aren't skipped while they do.
+    fn is_unchanged(&mut self, frame: &Frame) -> bool {
+        let animating = self
+            .click_highlights
+            .as_ref()
+            .is_some_and(|highlights| highlights.borrow().is_animating(Instant::now()));
+        // Frames on the GPU can't be read cheaply, so they are always encoded, and so is the next one in memory.
+        if !self.config.skip_unchanged || frame.gpu.is_some() || animating {
+            self.unchanged.reset();
+            return false;
+        }
+        let bytes_per_pixel = frame.format.bytes_per_pixel() as usize;
+        let hash = content_hash::sampled_hash(&frame.data, frame.size, bytes_per_pixel);
+        self.unchanged.skip(hash, Instant::now())
+    }
+
+    // The frame with the redacted regions hidden and the clicks drawn on top, or the frame itself if there is
+    // neither. None if the frame can't be drawn on, as it is on the GPU, which it only is for the frames in flight when
+    // either was set.
+    fn composited(&mut self, frame: &Arc<Frame>) -> Option<Arc<Frame>> {
+        if self.redaction.regions.is_empty() && self.click_highlights.is_none() {
+            return Some(frame.clone());
+        }
+        if frame.gpu.is_some() {
+            crate::log_throttled!(
+                tracing::Level::DEBUG,
+                "Dropping a frame on the GPU, as it can't be redacted or drawn on"
+            );
+            return None;
+        }
+
+        // A copy, so the channel isn't locked while drawing.
+        let highlights =
+            self.click_highlights.as_ref().map(|highlights| highlights.borrow().clone());
+        let Redaction { regions, style } = &self.redaction;
+        Some(self.composited_frames.fill(frame.size, frame.format, |data| {
+            data.copy_from_slice(&frame.data);
+            bitmap_utils::redact(data, frame.size, regions, *style);
+            if let Some(highlights) = highlights {
+                highlights.draw(data, frame.size, Instant::now());
+            }
+        }))
+    }
+
+    fn report_decimation(&self, decimation: u32) {
+        let output_fps = self.config.target_fps_hz / decimation as f32;
+        tracing::info!(
+            "Encoding every {} frame(s), sending {:.0} of {:.0} fps",
+            decimation,
+            output_fps,
+            self.config.target_fps_hz
+        );
+        self.metrics.output_fps.set(output_fps as f64);
+    }
+
+    fn record_stats(&mut self, encode_time: Duration, bytes: usize) {
+        let now = Instant::now();
+        self.bytes_1s.record(now, bytes as u64);
+        self.bytes_10s.record(now, bytes as u64);
+        self.frames_10s.record(now, 1);
+
+        self.metrics.last_encode_time.set(encode_time.as_secs_f64());
+        self.metrics.queue_len.set(self.frames.len() as f64);
+        self.metrics.bitrate_1s.set(self.bytes_1s.per_second() * 8.0);
+        self.metrics.bitrate_10s.set(self.bytes_10s.per_second() * 8.0);
+        self.metrics.encoded_fps.set(self.frames_10s.per_second());
+        self.metrics.encoded.increment();
+    }
+
+    // Takes the sink rather than self, as the encoder isn't Sync and can't be borrowed across awaits.
+    // The rest of a frame is useless once part of it failed, so the remaining samples are dropped.
+    async fn write_samples(
+        sink: &S,
+        cap: &mut BandwidthCap,
+        nal_units: Vec<Vec<u8>>,
+        duration: Duration,
+    ) {
+        for nal in nal_units {
+            if !cap.admit(&nal).await {
+                tracing::trace!(
+                    "Dropped a sample of {} bytes to stay within the bandwidth cap",
+                    nal.len()
+                );
+                continue;
+            }
+            if let Err(e) = sink.write_sample(nal, duration).await {
+                tracing::error!("Failed to write sample: {}", e);
+                break;
+            }
+        }
+    }
+
+    // Hardware encoders can fail to initialize on machines that nominally support them,
+    // so fall back to a software encoder producing the same codec, as the negotiated track can't change.
+    fn try_fallback(&mut self) {
+        if self.has_output {
+            return;
+        }
+
+        let current = self.config.transcoding_type;
+        let Some(fallback) = self.software_fallback() else {
+            return;
+        };
+
+        tracing::warn!("Falling back from {} to {} encoding", current, fallback);
+        match self.create_encoder_for(fallback) {
+            Ok(encoder) => self.replace_encoder(encoder, fallback),
+            Err(e) => tracing::error!("Failed to create fallback encoder: {}", e),
+        }
+    }
+
+    // The stuck call may never return, so its thread is left behind along with the encoder, and a new one takes over.
+    // It's the hardware drivers that hang, so the software encoder is preferred from here on.
+    // If the new one can't be created, the next call gets stuck behind the old one and tries again.
+    fn restart_wedged(&mut self) {
+        let transcoding_type = self.software_fallback().unwrap_or(self.config.transcoding_type);
+        tracing::warn!("Restarting the encoder with {} encoding", transcoding_type);
+        match self.create_encoder_for(transcoding_type) {
+            Ok(encoder) => {
+                self.replace_encoder(encoder, transcoding_type);
+                self.metrics.restarts.increment();
+            }
+            Err(e) => tracing::error!("Failed to restart encoder: {}", e),
+        }
+    }
+
+    // A software encoder producing the same codec, as the negotiated track can't change.
+    fn software_fallback(&self) -> Option<FFmpegTranscodeType> {
+        let current = self.config.transcoding_type;
+        FFmpegTranscodeType::ALL
+            .iter()
+            .copied()
+            .find(|t| t.hw_accel_name().is_none() && t.mime_type() == current.mime_type())
+            .filter(|fallback| *fallback != current)
+    }
+
+    fn replace_encoder(
+        &mut self,
+        encoder: Watched<FFmpegEncoder>,
+        transcoding_type: FFmpegTranscodeType,
+    ) {
+        self.encoder = encoder;
+        self.config.transcoding_type = transcoding_type;
+        self.gpu_input.fetch_and(transcoding_type.accepts_d3d11_frames(), Ordering::Relaxed);
+        // The timings of the old encoder say nothing about the new one.
+        self.pacer = FramePacer::new(self.config.target_fps_hz);
+        self.report_decimation(self.pacer.decimation());
+        // The new encoder starts with a keyframe, which can't wait for the picture to change.
+        self.unchanged.reset();
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// What is being shared, which picks the encoder options that suit it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ContentHint {
+    /// Picked from how much of the screen changes, see [`crate::media::content_switcher::ContentSwitcher`].
+    #[default]
+    Auto,
+    /// Mostly still text and UI, which should stay sharp even at low bitrates.
+    Text,
+    /// Video, games and scrolling, which should stay smooth.
+    Motion,
+}
+
+impl ContentHint {
+    pub const ALL: &[ContentHint] = &[ContentHint::Auto, ContentHint::Text, ContentHint::Motion];
+}
+
+impl Display for ContentHint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => f.write_str("Auto"),
+            Self::Text => f.write_str("Text"),
+            Self::Motion => f.write_str("Motion"),
+        }
+    }
+}
+
+/// The content the encoder is tuned for at the moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContentType {
+    Text,
+    /// Close to the generic options, so it is what the encoder starts with unless told otherwise.
+    #[default]
+    Motion,
+}
+
+impl Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => f.write_str("Text"),
+            Self::Motion => f.write_str("Motion"),
+        }
+    }
+}
+
+/// The options on top of the ones every encoder of a transcode type gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ContentTuning {
+    pub content: ContentType,
+    /// Refreshes the picture a column at a time instead of with keyframes, which evens out the bitrate.
+    pub intra_refresh: bool,
+}
@@ -0,0 +1,174 @@
+use ffmpeg::{format, frame, sys};
+use ffmpeg_next as ffmpeg;
+use windows::Win32::Graphics::Direct3D11::{
+    D3D11_BIND_RENDER_TARGET, D3D11_BOX, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D,
+};
+use windows_core::Interface;
+
+use crate::media::ffmpeg::FFmpegEncoderError;
+
+// From libavutil/hwcontext_d3d11va.h, which the ffmpeg bindings don't include.
+#[repr(C)]
+struct AVD3D11VADeviceContext {
+    device: *mut core::ffi::c_void,
+    device_context: *mut core::ffi::c_void,
+    video_device: *mut core::ffi::c_void,
+    video_context: *mut core::ffi::c_void,
+    lock: Option<unsafe extern "C" fn(*mut core::ffi::c_void)>,
+    unlock: Option<unsafe extern "C" fn(*mut core::ffi::c_void)>,
+    lock_ctx: *mut core::ffi::c_void,
+}
+
+#[repr(C)]
+struct AVD3D11VAFramesContext {
+    texture: *mut core::ffi::c_void,
+    bind_flags: u32,
+    misc_flags: u32,
+    texture_infos: *mut core::ffi::c_void,
+}
+
+/// The hardware contexts for encoding D3D11 textures, on the device the textures were captured with.
+pub struct D3D11Frames {
+    device_ctx: *mut sys::AVBufferRef,
+    frames_ctx: *mut sys::AVBufferRef,
+    context: ID3D11DeviceContext,
+    width: i32,
+    height: i32,
+}
+
+impl D3D11Frames {
+    const POOL_SIZE: i32 = 8;
+
+    /// Creates the contexts for textures like the given one.
+    /// The device has to be multithread protected, as the capture keeps using it on its own thread.
+    pub fn new(
+        texture: &ID3D11Texture2D,
+        sw_format: format::Pixel,
+        width: i32,
+        height: i32,
+    ) -> Result<Self, FFmpegEncoderError> {
+        let device: ID3D11Device = unsafe { texture.GetDevice() }
+            .map_err(|_| FFmpegEncoderError::HWDeviceError(ffmpeg::Error::Unknown))?;
+        let context = unsafe { device.GetImmediateContext() }
+            .map_err(|_| FFmpegEncoderError::HWDeviceError(ffmpeg::Error::Unknown))?;
+
+        unsafe {
+            let device_ctx =
+                sys::av_hwdevice_ctx_alloc(sys::AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA);
+            if device_ctx.is_null() {
+                return Err(FFmpegEncoderError::HWDeviceError(ffmpeg::Error::Unknown));
+            }
+            // Owned from here on, so the contexts are freed on any error below.
+            let mut frames =
+                Self { device_ctx, frames_ctx: std::ptr::null_mut(), context, width, height };
+
+            let device_hwctx = (*((*device_ctx).data as *mut sys::AVHWDeviceContext)).hwctx
+                as *mut AVD3D11VADeviceContext;
+            // FFmpeg releases the device along with the context, so it gets its own reference.
+            (*device_hwctx).device = device.into_raw();
+            let ret = sys::av_hwdevice_ctx_init(device_ctx);
+            if ret < 0 {
+                return Err(FFmpegEncoderError::HWDeviceError(ffmpeg::Error::from(ret)));
+            }
+
+            let frames_ctx = sys::av_hwframe_ctx_alloc(device_ctx);
+            if frames_ctx.is_null() {
+                return Err(FFmpegEncoderError::HWFramesError(ffmpeg::Error::Unknown));
+            }
+            frames.frames_ctx = frames_ctx;
+
+            let frames_hwctx = (*frames_ctx).data as *mut sys::AVHWFramesContext;
+            (*frames_hwctx).format = format::Pixel::D3D11.into();
+            (*frames_hwctx).sw_format = sw_format.into();
+            (*frames_hwctx).width = width;
+            (*frames_hwctx).height = height;
+            (*frames_hwctx).initial_pool_size = Self::POOL_SIZE;
+            // Encoders read the textures as render targets.
+            (*((*frames_hwctx).hwctx as *mut AVD3D11VAFramesContext)).bind_flags =
+                D3D11_BIND_RENDER_TARGET.0 as u32;
+
+            let ret = sys::av_hwframe_ctx_init(frames_ctx);
+            if ret < 0 {
+                return Err(FFmpegEncoderError::HWFramesError(ffmpeg::Error::from(ret)));
+            }
+
+            Ok(frames)
+        }
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    /// Whether the texture is on the device of the contexts, which it isn't once the capture had to create a new one.
+    pub fn is_on_device_of(&self, texture: &ID3D11Texture2D) -> bool {
+        let device = unsafe { self.context.GetDevice() }.ok();
+        device.is_some() && unsafe { texture.GetDevice() }.ok() == device
+    }
+
+    /// Attaches the contexts to an encoder that hasn't been opened yet.
+    pub fn attach(&self, context: *mut sys::AVCodecContext) -> Result<(), FFmpegEncoderError> {
+        unsafe {
+            let frames_ref = sys::av_buffer_ref(self.frames_ctx);
+            if frames_ref.is_null() {
+                return Err(FFmpegEncoderError::HWFramesError(ffmpeg::Error::Unknown));
+            }
+            (*context).hw_frames_ctx = frames_ref;
+            (*context).hw_device_ctx = sys::av_buffer_ref(self.device_ctx);
+        }
+        Ok(())
+    }
+
+    /// Copies the texture into a frame from the pool, on the GPU.
+    pub fn upload(
+        &self,
+        texture: &ID3D11Texture2D,
+        hw_frame: &mut frame::Video,
+    ) -> Result<(), FFmpegEncoderError> {
+        unsafe {
+            let ret = sys::av_hwframe_get_buffer(self.frames_ctx, hw_frame.as_mut_ptr(), 0);
+            if ret < 0 {
+                return Err(FFmpegEncoderError::HWUploadError(ffmpeg::Error::from(ret)));
+            }
+
+            // D3D11 frames are a slice of a texture array: the texture, and the index into it.
+            let data = (*hw_frame.as_mut_ptr()).data;
+            let raw = data[0] as *mut core::ffi::c_void;
+            let Some(pool_texture) = ID3D11Texture2D::from_raw_borrowed(&raw) else {
+                return Err(FFmpegEncoderError::HWUploadError(ffmpeg::Error::Unknown));
+            };
+            let index = data[1] as usize as u32;
+
+            let region = D3D11_BOX {
+                left: 0,
+                top: 0,
+                front: 0,
+                right: self.width as u32,
+                bottom: self.height as u32,
+                back: 1,
+            };
+            self.context.CopySubresourceRegion(
+                pool_texture,
+                index,
+                0,
+                0,
+                0,
+                texture,
+                0,
+                Some(&region),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for D3D11Frames {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.frames_ctx.is_null() {
+                sys::av_buffer_unref(&mut self.frames_ctx);
+            }
+            sys::av_buffer_unref(&mut self.device_ctx);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt::Display;
+
+use ffmpeg_next::{format::Pixel, sys::AVHWDeviceType};
+use serde::{Deserialize, Serialize};
+
+/// What decodes the received video. Hardware decoding leaves the CPU free, but isn't available everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DecodeAccel {
+    /// The first of the ones below that works.
+    #[default]
+    Auto,
+    D3D11VA,
+    Vulkan,
+    Software,
+}
+
+impl DecodeAccel {
+    pub const ALL: &[DecodeAccel] =
+        &[DecodeAccel::Auto, DecodeAccel::D3D11VA, DecodeAccel::Vulkan, DecodeAccel::Software];
+
+    /// The decoders to try, in order. Software always comes last, as it works everywhere.
+    /// D3D11VA comes first for Auto, as every Windows GPU driver has it, while Vulkan video is a lot newer.
+    pub fn probe_order(self) -> &'static [DecodeAccel] {
+        match self {
+            Self::Auto => &[Self::D3D11VA, Self::Vulkan, Self::Software],
+            Self::D3D11VA => &[Self::D3D11VA, Self::Software],
+            Self::Vulkan => &[Self::Vulkan, Self::Software],
+            Self::Software => &[Self::Software],
+        }
+    }
+
+    pub fn is_hardware(self) -> bool {
+        self.hw_device_type().is_some()
+    }
+
+    /// The device to decode on, or None for software decoding. Auto has none until it settles on one.
+    pub fn hw_device_type(self) -> Option<AVHWDeviceType> {
+        match self {
+            Self::D3D11VA => Some(AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA),
+            Self::Vulkan => Some(AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN),
+            Self::Auto | Self::Software => None,
+        }
+    }
+
+    /// The format of the decoded frames while they are still on the device.
+    pub fn hw_pixel_format(self) -> Option<Pixel> {
+        match self {
+            Self::D3D11VA => Some(Pixel::D3D11),
+            Self::Vulkan => Some(Pixel::VULKAN),
+            Self::Auto | Self::Software => None,
+        }
+    }
+}
+
+impl Display for DecodeAccel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => f.write_str("Auto"),
+            Self::D3D11VA => f.write_str("D3D11VA"),
+            Self::Vulkan => f.write_str("Vulkan"),
+            Self::Software => f.write_str("Software"),
+        }
+    }
+}
@@ -8,8 +8,8 @@ use ffmpeg::{
 use ffmpeg_next as ffmpeg;
 
 use crate::{
-    media::ffmpeg::FFmpegTranscodeType,
-    utils::{buffer_arena::BufferArena, frame::Frame, pixel_format::PixelFormat, vector2::Vector2},
+    media::ffmpeg::{DecodeAccel, FFmpegTranscodeType},
+    utils::{frame::Frame, frame_ring::FrameRing, pixel_format::PixelFormat, vector2::Vector2},
 };
 
 type Result<T> = std::result::Result<T, FFmpegDecoderError>;
@@ -28,55 +28,157 @@ pub enum FFmpegDecoderError {
     HWDeviceError(ffmpeg::Error),
     #[error("Hardware transfer failed: {0}")]
     HWTransferError(ffmpeg::Error),
+    #[error("{1} can't decode {0}")]
+    HWAccelUnsupported(String, DecodeAccel),
+    #[error("Unsupported codec: {0}")]
+    UnsupportedCodec(String),
+}
+
+impl FFmpegDecoderError {
+    /// Whether the packet was bad, which the decoder recovers from once a keyframe arrives.
+    /// Anything else means the decoder itself is in a bad state.
+    pub fn is_corrupt_input(&self) -> bool {
+        matches!(self, Self::DecodeError(_))
+    }
 }
 
 pub struct FFmpegDecoder {
     decoder: decoder::Video,
     scaler: Option<Scaler>,
-    decoding_pool: BufferArena,
+    // What the scaler converts into, which is reused along with it.
+    rgb_frame: frame::Video,
+    frames: FrameRing,
     cached_dims: (u32, u32),
+    accel: DecodeAccel,
     hw_pixel_format: Option<ffmpeg::format::Pixel>,
 }
 
 impl FFmpegDecoder {
-    const POOL_SIZE: usize = 128000;
+    // The frames queued for the UI, the one it shows, and the one being decoded.
+    const RING_LEN: usize = 4;
     const DST_FORMAT: PixelFormat = PixelFormat::RGBA8;
     const SCALING_MODE: scaling::Flags = scaling::Flags::BILINEAR;
+    // AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX, which is in an anonymous enum the bindings can't name.
+    const HW_CONFIG_METHOD_HW_DEVICE_CTX: i32 = 0x01;
+
+    /// Creates a decoder for what the transcode type produces, before the codec negotiated with the peer is known.
+    pub fn new(transcoding_type: FFmpegTranscodeType, accel: DecodeAccel) -> Result<Self> {
+        Self::for_mime_type(transcoding_type.mime_type(), accel)
+    }
+
+    /// Creates a decoder for the codec negotiated with the peer, with the first acceleration in line that works.
+    pub fn for_mime_type(mime_type: &str, accel: DecodeAccel) -> Result<Self> {
+        let decoder_name = Self::decoder_name(mime_type)?;
+        let mut last_error = None;
+        for &candidate in accel.probe_order() {
+            match Self::open(decoder_name, candidate) {
+                Ok(decoder) => return Ok(decoder),
+                Err(e) => {
+                    tracing::warn!("Can't decode {} with {}: {}", mime_type, candidate, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| FFmpegDecoderError::UnsupportedCodec(mime_type.to_owned())))
+    }
+
+    /// Creates a decoder with exactly this acceleration, without falling back, e.g. to find out whether it works.
+    pub fn without_fallback(mime_type: &str, accel: DecodeAccel) -> Result<Self> {
+        Self::open(Self::decoder_name(mime_type)?, accel)
+    }
 
-    pub fn new(transcoding_type: FFmpegTranscodeType) -> Result<Self> {
+    fn decoder_name(mime_type: &str) -> Result<&'static str> {
+        match mime_type.to_ascii_lowercase().as_str() {
+            "video/h264" => Ok("h264"),
+            "video/h265" => Ok("hevc"),
+            _ => Err(FFmpegDecoderError::UnsupportedCodec(mime_type.to_owned())),
+        }
+    }
+
+    fn open(decoder_name: &str, accel: DecodeAccel) -> Result<Self> {
         ffmpeg::init().map_err(FFmpegDecoderError::CreateDecoderError)?;
 
-        let decoder_name = transcoding_type.to_decoder_name();
         let codec = codec::decoder::find_by_name(decoder_name)
             .ok_or(FFmpegDecoderError::CreateDecoderError(ffmpeg::Error::DecoderNotFound))?;
 
         let mut context = codec::context::Context::new_with_codec(codec);
         context.set_flags(codec::Flags::LOW_DELAY);
 
-        let input_format = transcoding_type.get_input_format();
-        let mut opts = ffmpeg::Dictionary::new();
-        let mut hw_pixel_format = None;
-        if let Some(hwaccel_name) = transcoding_type.hw_accel_name() {
-            opts.set("hwaccel", hwaccel_name);
-            hw_pixel_format = Some(input_format);
-            tracing::info!("Enabling HW Accel with option: hwaccel={}", hwaccel_name);
-        }
+        Self::attach_hw_device(&mut context, codec, accel)?;
 
         let decoder = context
             .decoder()
-            .open_as_with(codec, opts)
+            .open_as(codec)
             .and_then(|d| d.video())
             .map_err(FFmpegDecoderError::CreateDecoderError)?;
+        tracing::info!("Decoding {} with {}", decoder_name, accel);
 
         Ok(Self {
             decoder,
             scaler: None,
-            decoding_pool: BufferArena::init(Self::POOL_SIZE),
+            rgb_frame: frame::Video::empty(),
+            frames: FrameRing::new(Self::RING_LEN),
             cached_dims: (0, 0),
-            hw_pixel_format,
+            accel,
+            hw_pixel_format: accel.hw_pixel_format(),
         })
     }
 
+    // Has the decoder decode on a device for the acceleration, which the default format negotiation then picks.
+    // Fails if the codec can't be decoded on such a device, as FFmpeg would quietly decode in software instead.
+    fn attach_hw_device(
+        context: &mut codec::context::Context,
+        codec: codec::Codec,
+        accel: DecodeAccel,
+    ) -> Result<()> {
+        let Some(device_type) = accel.hw_device_type() else {
+            return Ok(());
+        };
+        unsafe {
+            let supported = (0..)
+                .map_while(|i| sys::avcodec_get_hw_config(codec.as_ptr(), i).as_ref())
+                .any(|config| {
+                    config.device_type == device_type
+                        && config.methods & Self::HW_CONFIG_METHOD_HW_DEVICE_CTX != 0
+                });
+            if !supported {
+                return Err(FFmpegDecoderError::HWAccelUnsupported(codec.name().to_owned(), accel));
+            }
+
+            let mut device = std::ptr::null_mut();
+            let ret = sys::av_hwdevice_ctx_create(
+                &mut device,
+                device_type,
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                0,
+            );
+            if ret < 0 {
+                return Err(FFmpegDecoderError::HWDeviceError(ffmpeg::Error::from(ret)));
+            }
+            // The context takes its own reference, which it releases when it is freed.
+            (*context.as_mut_ptr()).hw_device_ctx = sys::av_buffer_ref(device);
+            sys::av_buffer_unref(&mut device);
+        }
+        Ok(())
+    }
+
+    /// What the decoder decodes with, which is never Auto.
+    pub fn accel(&self) -> DecodeAccel {
+        self.accel
+    }
+
+    /// The frames that had to be allocated because the consumer still held every frame of the ring.
+    pub fn frame_overflows(&self) -> u64 {
+        self.frames.overflows()
+    }
+
+    /// Drops the buffered frames and references, which a corrupt packet may have poisoned.
+    pub fn flush(&mut self) {
+        self.decoder.flush();
+    }
+
     pub fn decode(&mut self, packet_data: &[u8]) -> Result<Option<Arc<Frame>>> {
         let packet = Packet::borrow(packet_data);
         self.decoder.send_packet(&packet).map_err(FFmpegDecoderError::DecodeError)?;
@@ -87,7 +189,8 @@ impl FFmpegDecoder {
                 // Check if the frame format matches our expected HW format.
                 // If so, we must transfer the data from GPU memory to system memory.
                 let final_frame = if self.hw_pixel_format == Some(decoded_frame.format()) {
-                    tracing::trace!(
+                    crate::log_throttled!(
+                        tracing::Level::TRACE,
                         "Frame format {:?} matches HW format, attempting transfer...",
                         decoded_frame.format()
                     );
@@ -130,47 +233,41 @@ impl FFmpegDecoder {
                     .map_err(FFmpegDecoderError::ScalerError)?;
 
                     self.scaler = Some(scaler);
+                    self.rgb_frame =
+                        frame::Video::new(Self::DST_FORMAT.to_ffmpeg_pixel_format(), width, height);
                     self.cached_dims = (width, height);
                 }
 
                 let scaler = self.scaler.as_mut().unwrap();
-                let mut rgb_frame =
-                    frame::Video::new(Self::DST_FORMAT.to_ffmpeg_pixel_format(), width, height);
-
                 scaler
-                    .run(&final_frame, &mut rgb_frame)
+                    .run(&final_frame, &mut self.rgb_frame)
                     .map_err(FFmpegDecoderError::ConversionError)?;
 
-                let size = (width * height * 4) as usize;
-                let mut framebuf = self.decoding_pool.get(size);
-
                 // Copy from the first plane
-                let data = rgb_frame.data(0);
-                let linesize = rgb_frame.stride(0);
-
-                // Copy row by row to handle stride
-                let dest_stride = (width * 4) as usize;
-                for i in 0..height as usize {
-                    let src_start = i * linesize;
-                    let src_end = src_start + dest_stride;
-                    let dest_start = i * dest_stride;
-                    let dest_end = dest_start + dest_stride;
-
-                    framebuf[dest_start..dest_end].copy_from_slice(&data[src_start..src_end]);
-                }
+                let data = self.rgb_frame.data(0);
+                let linesize = self.rgb_frame.stride(0);
+                let size = Vector2::<i32>::new(width as i32, height as i32);
+                let frame = self.frames.fill(size, Self::DST_FORMAT, |framebuf| {
+                    // Copy row by row to handle stride
+                    let dest_stride = (width * 4) as usize;
+                    for i in 0..height as usize {
+                        let src_start = i * linesize;
+                        let src_end = src_start + dest_stride;
+                        let dest_start = i * dest_stride;
+                        let dest_end = dest_start + dest_stride;
 
-                let frame = Arc::new(Frame::new_raw(
-                    framebuf,
-                    PixelFormat::RGBA8,
-                    Vector2::<i32>::new(width as i32, height as i32),
-                    None,
-                    None,
-                ));
+                        framebuf[dest_start..dest_end].copy_from_slice(&data[src_start..src_end]);
+                    }
+                });
 
                 Ok(Some(frame))
             }
             Err(ffmpeg::Error::Other { errno: ffmpeg::error::EAGAIN }) => {
                 // Need more data
+                crate::log_throttled!(
+                    tracing::Level::TRACE,
+                    "Decoder needs more packets for a frame"
+                );
                 Ok(None)
             }
             Err(ffmpeg::Error::Eof) => Ok(None),
@@ -183,7 +280,8 @@ impl std::fmt::Debug for FFmpegDecoder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("H264Decoder")
             .field("decoder", &"<Decoder>".to_owned())
-            .field("decoding_pool", &self.decoding_pool)
+            .field("accel", &self.accel)
+            .field("frames", &self.frames)
             .finish()
     }
 }
@@ -1,11 +1,19 @@
 use ffmpeg::{
-    Packet, Rational, codec, encoder, format, frame,
+    Packet, Rational, codec, encoder, format, frame, picture,
     software::scaling::{self, Context as Scaler},
     sys,
 };
 use ffmpeg_next as ffmpeg;
 
-use crate::{media::ffmpeg::FFmpegTranscodeType, utils::pixel_format::PixelFormat};
+use crate::{
+    media::{
+        encode_size,
+        ffmpeg::{ContentTuning, FFmpegTranscodeType, RateControl, d3d11_frames::D3D11Frames},
+    },
+    utils::{
+        framerate::Framerate, gpu_frame::GpuFrame, pixel_format::PixelFormat, vector2::Vector2,
+    },
+};
 
 type Result<T> = std::result::Result<T, FFmpegEncoderError>;
 
@@ -29,59 +37,100 @@ pub enum FFmpegEncoderError {
     HWFramesError(ffmpeg::Error),
     #[error("Hardware upload failed: {0}")]
     HWUploadError(ffmpeg::Error),
+    #[error("The encoder can't take this frame from the GPU")]
+    GpuInputUnsupported,
 }
 
 pub struct FFmpegEncoder {
     input_format: PixelFormat,
     encoder: Option<encoder::Video>,
     scaler: Option<Scaler>,
+    max_dimension: Option<u32>,
     bitrate: u32,
     target_framerate_hz: f32,
+    // The frames between keyframes.
+    gop: u32,
+    rate_control: RateControl,
+    tuning: ContentTuning,
     frame_count: i64,
+    force_keyframe: bool,
     hw_device_ctx: Option<*mut sys::AVBufferRef>,
     hw_frames_ctx: Option<*mut sys::AVBufferRef>,
+    // Set while encoding frames straight from D3D11 textures.
+    gpu_frames: Option<D3D11Frames>,
 }
 
 impl Drop for FFmpegEncoder {
     fn drop(&mut self) {
-        unsafe {
-            if let Some(mut ctx) = self.hw_frames_ctx {
-                sys::av_buffer_unref(&mut ctx);
-            }
-            if let Some(mut ctx) = self.hw_device_ctx {
-                sys::av_buffer_unref(&mut ctx);
-            }
-        }
+        self.release_hw_contexts();
     }
 }
 
 impl FFmpegEncoder {
     const DST_FORMAT: format::Pixel = format::Pixel::NV12;
-    const GOP_VALUE: u32 = 120;
     const B_FRAMES_VALUE: usize = 0;
     const SCALING_MODE: scaling::Flags = scaling::Flags::BILINEAR;
 
-    pub fn new(bitrate: u32, target_framerate_hz: f32, input_format: PixelFormat) -> Result<Self> {
+    pub fn new(
+        bitrate: u32,
+        target_framerate_hz: f32,
+        input_format: PixelFormat,
+        max_dimension: Option<u32>,
+        gop: u32,
+        rate_control: RateControl,
+    ) -> Result<Self> {
         ffmpeg::init().map_err(FFmpegEncoderError::CreateEncoderError)?;
 
         Ok(Self {
             input_format,
             encoder: None,
             scaler: None,
+            max_dimension,
             bitrate,
             target_framerate_hz,
+            gop,
+            rate_control,
+            tuning: ContentTuning::default(),
             frame_count: 0,
+            force_keyframe: false,
             hw_device_ctx: None,
             hw_frames_ctx: None,
+            gpu_frames: None,
         })
     }
 
-    fn init_encoder(
-        &mut self,
+    // The encoder holds its own references, so this is safe to call while it is still alive.
+    fn release_hw_contexts(&mut self) {
+        self.gpu_frames = None;
+        unsafe {
+            if let Some(mut ctx) = self.hw_frames_ctx.take() {
+                sys::av_buffer_unref(&mut ctx);
+            }
+            if let Some(mut ctx) = self.hw_device_ctx.take() {
+                sys::av_buffer_unref(&mut ctx);
+            }
+        }
+    }
+
+    /// Changes the largest dimension frames are encoded at.
+    /// The encoder is re-initialized with the new size on the next frame, which starts with a keyframe.
+    pub fn set_max_dimension(&mut self, max_dimension: Option<u32>) {
+        if self.max_dimension == max_dimension {
+            return;
+        }
+        self.max_dimension = max_dimension;
+        self.encoder = None;
+        self.scaler = None;
+    }
+
+    // Sets up an encoder with the current settings, leaving the hardware contexts and opening it to the caller.
+    fn create_context(
+        &self,
         transcoding_type: FFmpegTranscodeType,
-        width: i32,
-        height: i32,
-    ) -> Result<()> {
+        aligned_width: i32,
+        aligned_height: i32,
+        format: format::Pixel,
+    ) -> Result<encoder::video::Video> {
         let codec = encoder::find_by_name(transcoding_type.to_encoder_name())
             .or_else(|| {
                 tracing::info!("Specified encoder not found, using fallback.");
@@ -96,22 +145,47 @@ impl FFmpegEncoder {
             .video()
             .map_err(FFmpegEncoderError::CreateEncoderError)?;
 
-        // Align resolution to even numbers
-        let aligned_width = width & !1;
-        let aligned_height = height & !1;
-
         context.set_width(aligned_width as u32);
         context.set_height(aligned_height as u32);
-        context.set_format(transcoding_type.get_input_format());
+        context.set_format(format);
         context.set_bit_rate(self.bitrate as usize);
+        if self.rate_control == RateControl::Constant {
+            context.set_max_bit_rate(self.bitrate as usize);
+            unsafe {
+                let context = context.as_mut_ptr();
+                (*context).rc_min_rate = self.bitrate as i64;
+                // A second's worth of buffer, which lets the rate vary within a second but not beyond.
+                (*context).rc_buffer_size = self.bitrate as i32;
+            }
+        }
 
-        let time_base = Rational(1, self.target_framerate_hz as i32);
-        context.set_time_base(time_base);
-        context.set_frame_rate(Some(Rational(self.target_framerate_hz as i32, 1)));
+        // Every frame is a tick of the time base, so the timestamps are the frame count.
+        let framerate = Framerate::from_hz(self.target_framerate_hz as f64);
+        context.set_time_base(Rational::from(framerate.time_base()));
+        context.set_frame_rate(Some(Rational::from(framerate.frame_rate())));
 
-        context.set_gop(Self::GOP_VALUE);
+        context.set_gop(self.gop);
         context.set_max_b_frames(Self::B_FRAMES_VALUE);
 
+        Ok(context)
+    }
+
+    fn init_encoder(
+        &mut self,
+        transcoding_type: FFmpegTranscodeType,
+        aligned_width: i32,
+        aligned_height: i32,
+    ) -> Result<()> {
+        // Re-initializing on a resolution change would otherwise leak the previous contexts.
+        self.release_hw_contexts();
+
+        let mut context = self.create_context(
+            transcoding_type,
+            aligned_width,
+            aligned_height,
+            transcoding_type.get_input_format(),
+        )?;
+
         // Hardware Context Initialization
         if let Some(device_type) = transcoding_type.hw_accel_name() {
             unsafe {
@@ -164,18 +238,71 @@ impl FFmpegEncoder {
         }
 
         let mut opts = ffmpeg::Dictionary::new();
-        transcoding_type.set_encoder_options(&mut opts);
+        transcoding_type.set_encoder_options(&mut opts, self.tuning);
+
+        let encoder = context.open_with(opts).map_err(FFmpegEncoderError::CreateEncoderError)?;
+        self.encoder = Some(encoder);
+
+        Ok(())
+    }
+
+    // Encodes straight from D3D11 textures in the captured format, which the encoder converts itself.
+    fn init_gpu_encoder(
+        &mut self,
+        transcoding_type: FFmpegTranscodeType,
+        frame: &GpuFrame,
+    ) -> Result<()> {
+        self.release_hw_contexts();
+        self.scaler = None;
+
+        let gpu_frames = D3D11Frames::new(
+            frame.texture(),
+            frame.format.to_ffmpeg_pixel_format(),
+            frame.size.x,
+            frame.size.y,
+        )?;
+        let mut context = self.create_context(
+            transcoding_type,
+            frame.size.x,
+            frame.size.y,
+            format::Pixel::D3D11,
+        )?;
+        gpu_frames.attach(unsafe { context.as_mut_ptr() })?;
+
+        let mut opts = ffmpeg::Dictionary::new();
+        transcoding_type.set_encoder_options(&mut opts, self.tuning);
 
         let encoder = context.open_with(opts).map_err(FFmpegEncoderError::CreateEncoderError)?;
         self.encoder = Some(encoder);
+        self.gpu_frames = Some(gpu_frames);
+
+        Ok(())
+    }
+
+    // Converts to the encoder's pixel format, and scales down to the encode size in the same pass.
+    fn ensure_scaler(
+        &mut self,
+        width: i32,
+        height: i32,
+        dst_width: i32,
+        dst_height: i32,
+    ) -> Result<()> {
+        // Differing input sizes can share an encode size, so both have to match.
+        if let Some(scaler) = &self.scaler
+            && (scaler.input().width, scaler.input().height) == (width as u32, height as u32)
+            && (scaler.output().width, scaler.output().height)
+                == (dst_width as u32, dst_height as u32)
+        {
+            return Ok(());
+        }
 
         let scaler = scaling::Context::get(
             self.input_format.to_ffmpeg_pixel_format(),
             width as u32,
             height as u32,
             Self::DST_FORMAT,
-            aligned_width as u32,
-            aligned_height as u32,
+            dst_width as u32,
+            dst_height as u32,
             Self::SCALING_MODE,
         )
         .map_err(FFmpegEncoderError::ScalerError)?;
@@ -184,6 +311,11 @@ impl FFmpegEncoder {
         Ok(())
     }
 
+    /// Makes the next encoded frame a keyframe, so the peer can start decoding from it.
+    pub fn request_keyframe(&mut self) {
+        self.force_keyframe = true;
+    }
+
     /// Encodes a raw RGBA8 bitmap into a list of H.264 NAL units (as packets).
     pub fn encode(
         &mut self,
@@ -192,20 +324,17 @@ impl FFmpegEncoder {
         width: i32,
         height: i32,
     ) -> Result<Vec<Vec<u8>>> {
-        if self.encoder.is_none() {
-            self.init_encoder(transcoding_type, width, height)?;
-        }
-
-        // Align resolution to even numbers
-        let aligned_width = width & !1;
-        let aligned_height = height & !1;
-
-        if let Some(enc) = &self.encoder {
-            if enc.width() != aligned_width as u32 || enc.height() != aligned_height as u32 {
-                // Re-init
-                self.init_encoder(transcoding_type, width, height)?;
-            }
+        let Vector2 { x: aligned_width, y: aligned_height } =
+            encode_size::capped_size(Vector2::new(width, height), self.max_dimension);
+
+        let size_changed = self.encoder.as_ref().is_some_and(|enc| {
+            enc.width() != aligned_width as u32 || enc.height() != aligned_height as u32
+        });
+        // An encoder set up for GPU frames can't take them from memory.
+        if self.encoder.is_none() || size_changed || self.gpu_frames.is_some() {
+            self.init_encoder(transcoding_type, aligned_width, aligned_height)?;
         }
+        self.ensure_scaler(width, height, aligned_width, aligned_height)?;
 
         let encoder = self.encoder.as_mut().unwrap();
         let scaler = self.scaler.as_mut().unwrap();
@@ -224,6 +353,10 @@ impl FFmpegEncoder {
         dst_frame.set_pts(Some(self.frame_count));
         self.frame_count += 1;
 
+        if std::mem::take(&mut self.force_keyframe) {
+            dst_frame.set_kind(picture::Type::I);
+        }
+
         if let Some(frames_ctx_ref) = self.hw_frames_ctx {
             unsafe {
                 let mut hw_frame = frame::Video::empty();
@@ -240,8 +373,9 @@ impl FFmpegEncoder {
                     return Err(FFmpegEncoderError::HWUploadError(ffmpeg::Error::from(ret)));
                 }
 
-                // Copy PTS
+                // Copy PTS and the forced picture type
                 (*hw_frame.as_mut_ptr()).pts = dst_frame.pts().unwrap_or(0);
+                (*hw_frame.as_mut_ptr()).pict_type = (*dst_frame.as_ptr()).pict_type;
 
                 encoder.send_frame(&hw_frame).map_err(FFmpegEncoderError::EncodeError)?;
             }
@@ -249,6 +383,96 @@ impl FFmpegEncoder {
             encoder.send_frame(&dst_frame).map_err(FFmpegEncoderError::EncodeError)?;
         }
 
+        Ok(Self::receive_packets(encoder))
+    }
+
+    /// Encodes a frame that was left on the GPU, without it ever passing through memory.
+    /// Only some encoders can, and only at the captured size, so the caller has to fall back to frames in memory
+    /// when this fails.
+    pub fn encode_gpu(
+        &mut self,
+        frame: &GpuFrame,
+        transcoding_type: FFmpegTranscodeType,
+    ) -> Result<Vec<Vec<u8>>> {
+        let (width, height) = (frame.size.x, frame.size.y);
+        if !transcoding_type.accepts_d3d11_frames()
+            || encode_size::capped_size(frame.size, self.max_dimension) != frame.size
+        {
+            return Err(FFmpegEncoderError::GpuInputUnsupported);
+        }
+
+        if self.encoder.is_none()
+            || self.gpu_frames.as_ref().is_none_or(|frames| {
+                frames.size() != (width, height) || !frames.is_on_device_of(frame.texture())
+            })
+        {
+            self.init_gpu_encoder(transcoding_type, frame)?;
+        }
+
+        let encoder = self.encoder.as_mut().unwrap();
+        let gpu_frames = self.gpu_frames.as_ref().unwrap();
+
+        let mut hw_frame = frame::Video::empty();
+        gpu_frames.upload(frame.texture(), &mut hw_frame)?;
+
+        hw_frame.set_pts(Some(self.frame_count));
+        self.frame_count += 1;
+        if std::mem::take(&mut self.force_keyframe) {
+            hw_frame.set_kind(picture::Type::I);
+        }
+
+        encoder.send_frame(&hw_frame).map_err(FFmpegEncoderError::EncodeError)?;
+        Ok(Self::receive_packets(encoder))
+    }
+
+    /// Changes the target bitrate. The encoder is re-initialized with it on the next frame.
+    pub fn set_bitrate(&mut self, bitrate: u32) {
+        if self.bitrate == bitrate {
+            return;
+        }
+        self.bitrate = bitrate;
+        self.encoder = None;
+    }
+
+    /// Changes the frames between keyframes. The encoder is re-initialized with it on the next frame.
+    pub fn set_gop(&mut self, gop: u32) {
+        if self.gop == gop {
+            return;
+        }
+        self.gop = gop;
+        self.encoder = None;
+    }
+
+    /// Changes how the bitrate is spent. The encoder is re-initialized with it on the next frame.
+    pub fn set_rate_control(&mut self, rate_control: RateControl) {
+        if self.rate_control == rate_control {
+            return;
+        }
+        self.rate_control = rate_control;
+        self.encoder = None;
+    }
+
+    /// Changes the options for the content. The encoder is re-initialized with them on the next frame.
+    pub fn set_tuning(&mut self, tuning: ContentTuning) {
+        if self.tuning == tuning {
+            return;
+        }
+        self.tuning = tuning;
+        self.encoder = None;
+    }
+
+    /// Drains the packets still buffered in the encoder.
+    /// The encoder is re-initialized if any more frames are encoded afterwards.
+    pub fn flush(&mut self) -> Result<Vec<Vec<u8>>> {
+        let Some(mut encoder) = self.encoder.take() else {
+            return Ok(Vec::new());
+        };
+
+        encoder.send_eof().map_err(FFmpegEncoderError::EncodeError)?;
+        Ok(Self::receive_packets(&mut encoder))
+    }
+
+    fn receive_packets(encoder: &mut encoder::Video) -> Vec<Vec<u8>> {
         let mut nal_units = Vec::new();
         let mut packet = Packet::empty();
         while encoder.receive_packet(&mut packet).is_ok() {
@@ -257,8 +481,7 @@ impl FFmpegEncoder {
                 nal_units.push(data.to_vec());
             }
         }
-
-        Ok(nal_units)
+        nal_units
     }
 }
 
@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use webrtc::api::media_engine::{MIME_TYPE_H264, MIME_TYPE_HEVC};
+
+use crate::media::ffmpeg::{ContentTuning, ContentType};
+
+type EncoderOptions = &'static [(&'static str, &'static str)];
 
 macro_rules! define_ffmpeg_transcode_types {
     (
@@ -6,9 +11,14 @@ macro_rules! define_ffmpeg_transcode_types {
             $variant:ident $( => $def:tt )? {
                 encoder_name: $encoder_name:expr,
                 set_encoder_options: $set_encoder_options:expr,
+                text_options: $text_options:expr,
+                motion_options: $motion_options:expr,
+                intra_refresh_options: $intra_refresh_options:expr,
                 decoder_name: $decoder_name:expr,
                 input_format: $input_format:expr,
                 hw_accel_name: $hw_accel_name:expr,
+                mime_type: $mime_type:expr,
+                d3d11_input: $d3d11_input:expr,
             }
         ),* $(,)?
     ) => {
@@ -52,7 +62,12 @@ macro_rules! define_ffmpeg_transcode_types {
                 }
             }
 
-            pub fn set_encoder_options(&self, opts: &mut ffmpeg_next::Dictionary) {
+            /// Sets the options every encoder of this type gets, then the ones for the tuning on top.
+            pub fn set_encoder_options(
+                &self,
+                opts: &mut ffmpeg_next::Dictionary,
+                tuning: ContentTuning,
+            ) {
                 match self {
                     $(
                         FFmpegTranscodeType::$variant => {
@@ -60,6 +75,30 @@ macro_rules! define_ffmpeg_transcode_types {
                         }
                     )*
                 }
+                let intra_refresh =
+                    if tuning.intra_refresh { self.intra_refresh_options() } else { &[] };
+                for (key, value) in self.content_options(tuning.content).iter().chain(intra_refresh) {
+                    opts.set(key, value);
+                }
+            }
+
+            /// The options that suit the content, which override the ones every encoder of this type gets.
+            pub fn content_options(&self, content: ContentType) -> EncoderOptions {
+                match (self, content) {
+                    $(
+                        (FFmpegTranscodeType::$variant, ContentType::Text) => $text_options,
+                        (FFmpegTranscodeType::$variant, ContentType::Motion) => $motion_options,
+                    )*
+                }
+            }
+
+            /// The options that refresh the picture gradually instead of with keyframes. Empty if the encoder can't.
+            pub fn intra_refresh_options(&self) -> EncoderOptions {
+                match self {
+                    $(
+                        FFmpegTranscodeType::$variant => $intra_refresh_options,
+                    )*
+                }
             }
 
             pub fn to_decoder_name(&self) -> &'static str {
@@ -78,6 +117,15 @@ macro_rules! define_ffmpeg_transcode_types {
                 }
             }
 
+            /// The WebRTC mime type of the codec this produces.
+            pub fn mime_type(&self) -> &'static str {
+                match self {
+                    $(
+                        FFmpegTranscodeType::$variant => $mime_type,
+                    )*
+                }
+            }
+
             pub fn get_input_format(&self) -> ffmpeg_next::format::Pixel {
                 match self {
                     $(
@@ -85,6 +133,15 @@ macro_rules! define_ffmpeg_transcode_types {
                     )*
                 }
             }
+
+            /// Whether the encoder takes D3D11 textures directly, so captured frames can stay on the GPU.
+            pub fn accepts_d3d11_frames(&self) -> bool {
+                match self {
+                    $(
+                        FFmpegTranscodeType::$variant => $d3d11_input,
+                    )*
+                }
+            }
         }
 
         impl std::fmt::Display for FFmpegTranscodeType {
@@ -102,9 +159,15 @@ define_ffmpeg_transcode_types! {
             opts.set("preset", "ultrafast");
             opts.set("tune", "zerolatency");
         },
+        // Adaptive quantization spends bits on the flat areas around text, and weaker deblocking keeps its edges.
+        text_options: &[("tune", "stillimage,zerolatency"), ("x264-params", "aq-mode=2:deblock=-1,-1")],
+        motion_options: &[("tune", "zerolatency")],
+        intra_refresh_options: &[("intra-refresh", "1")],
         decoder_name: "h264",
         input_format: ffmpeg_next::format::Pixel::YUV420P,
         hw_accel_name: None,
+        mime_type: MIME_TYPE_H264,
+        d3d11_input: false,
     },
     H264Vulkan {
         encoder_name: "h264_vulkan",
@@ -113,10 +176,16 @@ define_ffmpeg_transcode_types! {
             opts.set("usage", "conference");
             opts.set("content", "desktop");
         },
+        text_options: &[("content", "desktop")],
+        motion_options: &[("content", "rendered")],
+        intra_refresh_options: &[],
         decoder_name: "h264",
         input_format: ffmpeg_next::format::Pixel::VULKAN,
         hw_accel_name: Some("vulkan"),
+        mime_type: MIME_TYPE_H264,
+        d3d11_input: false,
     },
+    // There is no HEVC encoder here that has the screen content coding profiles, so it is tuned like H.264.
     H265Vulkan {
         encoder_name: "hevc_vulkan",
         set_encoder_options: |opts: &mut ffmpeg_next::Dictionary| {
@@ -124,8 +193,30 @@ define_ffmpeg_transcode_types! {
             opts.set("usage", "conference");
             opts.set("content", "desktop");
         },
+        text_options: &[("content", "desktop")],
+        motion_options: &[("content", "rendered")],
+        intra_refresh_options: &[],
         decoder_name: "hevc",
         input_format: ffmpeg_next::format::Pixel::VULKAN,
         hw_accel_name: Some("vulkan"),
+        mime_type: MIME_TYPE_HEVC,
+        d3d11_input: false,
+    },
+    // Takes frames from memory like the software encoder, or D3D11 textures straight from the capture.
+    H264Nvenc {
+        encoder_name: "h264_nvenc",
+        set_encoder_options: |opts: &mut ffmpeg_next::Dictionary| {
+            opts.set("preset", "p1");
+            opts.set("tune", "ull");
+            opts.set("zerolatency", "1");
+        },
+        text_options: &[("spatial-aq", "1")],
+        motion_options: &[("spatial-aq", "0")],
+        intra_refresh_options: &[("intra-refresh", "1")],
+        decoder_name: "h264",
+        input_format: ffmpeg_next::format::Pixel::NV12,
+        hw_accel_name: None,
+        mime_type: MIME_TYPE_H264,
+        d3d11_input: true,
     },
 }
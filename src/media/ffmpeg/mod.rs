@@ -1,7 +1,14 @@
+mod content_hint;
+mod d3d11_frames;
+mod decode_accel;
 mod ffmpeg_decoder;
 mod ffmpeg_encoder;
 mod ffmpeg_transcode_type;
+mod rate_control;
 
-pub use ffmpeg_decoder::FFmpegDecoder;
-pub use ffmpeg_encoder::FFmpegEncoder;
+pub use content_hint::{ContentHint, ContentTuning, ContentType};
+pub use decode_accel::DecodeAccel;
+pub use ffmpeg_decoder::{FFmpegDecoder, FFmpegDecoderError};
+pub use ffmpeg_encoder::{FFmpegEncoder, FFmpegEncoderError};
 pub use ffmpeg_transcode_type::FFmpegTranscodeType;
+pub use rate_control::RateControl;
@@ -0,0 +1,26 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// How the encoder spends the bitrate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RateControl {
+    /// Spends less on still content, and up to the bitrate on motion.
+    #[default]
+    Variable,
+    /// Spends the bitrate steadily, which keeps the latency even on busy content.
+    Constant,
+}
+
+impl RateControl {
+    pub const ALL: &[RateControl] = &[RateControl::Variable, RateControl::Constant];
+}
+
+impl Display for RateControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Variable => f.write_str("Variable"),
+            Self::Constant => f.write_str("Constant"),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::utils::framerate::Framerate;
+
+/// How many of the latest encode durations the decimation is based on.
+const ENCODE_HISTORY_LEN: usize = 30;
+/// The first frames are slow while the encoder warms up, so don't judge it on those alone.
+const MIN_ENCODE_HISTORY_LEN: usize = 10;
+/// Below a quarter of the target framerate the stream is unusable anyway, so don't go further.
+const MAX_DECIMATION: u32 = 4;
+/// How much faster than needed encoding has to be before the decimation is lowered again, to avoid flapping.
+const RECOVERY_HEADROOM: f64 = 0.9;
+
+/// Picks how many frames to advance per encoded frame, so the encoder keeps up with the frame interval.
+/// `current` is the decimation in use, which is only lowered once the encoder comfortably fits the lower one.
+pub fn decimation_factor(
+    frame_interval: Duration,
+    recent_encode_durations: &[Duration],
+    current: u32,
+) -> u32 {
+    if recent_encode_durations.is_empty() || frame_interval.is_zero() {
+        return current.clamp(1, MAX_DECIMATION);
+    }
+
+    let average = recent_encode_durations.iter().sum::<Duration>().as_secs_f64()
+        / recent_encode_durations.len() as f64;
+    let needed = average / frame_interval.as_secs_f64();
+    let factor = (needed.ceil() as u32).clamp(1, MAX_DECIMATION);
+
+    if factor < current && needed > (current - 1) as f64 * RECOVERY_HEADROOM {
+        return current;
+    }
+    factor
+}
+
+/// Whether to encode the frame at `position` in a window of `decimation` frames.
+/// One frame is encoded per window, preferring the first with dirty content, and otherwise the last.
+pub fn should_encode(decimation: u32, position: u32, encoded_in_window: bool, dirty: bool) -> bool {
+    !encoded_in_window && (dirty || position + 1 >= decimation)
+}
+
+/// Decimates the frames deterministically when the encoder can't keep up,
+/// instead of leaving it to whichever frames happen to overflow the queue.
+#[derive(Debug)]
+pub struct FramePacer {
+    frame_interval: Duration,
+    encode_durations: VecDeque<Duration>,
+    decimation: u32,
+    position: u32,
+    encoded_in_window: bool,
+    // The skipped frames are folded into the next sample, so the timestamps still add up.
+    pending_duration: Duration,
+}
+
+impl FramePacer {
+    pub fn new(target_fps_hz: f32) -> Self {
+        Self {
+            frame_interval: Framerate::from_hz(target_fps_hz as f64).frametime(),
+            encode_durations: VecDeque::with_capacity(ENCODE_HISTORY_LEN),
+            decimation: 1,
+            position: 0,
+            encoded_in_window: false,
+            pending_duration: Duration::ZERO,
+        }
+    }
+
+    /// Returns the duration the sample should cover if the frame is to be encoded, or None if it should be skipped.
+    pub fn admit(&mut self, frame_duration: Duration, dirty: bool) -> Option<Duration> {
+        let encode = should_encode(self.decimation, self.position, self.encoded_in_window, dirty);
+        self.pending_duration += frame_duration;
+
+        self.position += 1;
+        self.encoded_in_window |= encode;
+        if self.position >= self.decimation {
+            self.position = 0;
+            self.encoded_in_window = false;
+        }
+
+        encode.then(|| std::mem::take(&mut self.pending_duration))
+    }
+
+    /// Gives back the duration of a sample that was admitted but not encoded after all, so the next one covers it.
+    pub fn defer(&mut self, duration: Duration) {
+        self.pending_duration += duration;
+    }
+
+    /// Records how long a frame took to encode. Returns the new decimation if it changed.
+    pub fn record_encode(&mut self, duration: Duration) -> Option<u32> {
+        if self.encode_durations.len() == ENCODE_HISTORY_LEN {
+            self.encode_durations.pop_front();
+        }
+        self.encode_durations.push_back(duration);
+        if self.encode_durations.len() < MIN_ENCODE_HISTORY_LEN {
+            return None;
+        }
+
+        let decimation = decimation_factor(
+            self.frame_interval,
+            self.encode_durations.make_contiguous(),
+            self.decimation,
+        );
+        if decimation == self.decimation {
+            return None;
+        }
+
+        self.decimation = decimation;
+        Some(decimation)
+    }
+
+    pub fn decimation(&self) -> u32 {
+        self.decimation
+    }
+}
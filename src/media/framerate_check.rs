@@ -0,0 +1,78 @@
+use std::time::{Duration, Instant};
+
+/// How long the pipeline runs before it is judged, as it needs to warm up and fill the stats window.
+const WARMUP: Duration = Duration::from_secs(10);
+/// How far below the target the achieved framerate has to be before it is worth telling the user.
+const MAX_SHORTFALL: f32 = 0.3;
+
+/// How far the achieved framerate falls short of the target, as a fraction of the target.
+/// None while warming up, or if it is within what is acceptable.
+pub fn framerate_shortfall(
+    target_fps: f32,
+    achieved_fps: f32,
+    running_for: Duration,
+) -> Option<f32> {
+    if running_for < WARMUP || target_fps <= 0.0 {
+        return None;
+    }
+
+    let shortfall = 1.0 - achieved_fps / target_fps;
+    (shortfall > MAX_SHORTFALL).then_some(shortfall)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FramerateCheckEvent {
+    /// The pipeline can't keep up with the target, and the user hasn't been told about it yet.
+    Shortfall { target_fps: f32, achieved_fps: f32 },
+    /// The pipeline has caught up again.
+    Recovered,
+}
+
+/// Checks that the pipeline sustains the target framerate, and tells when it doesn't.
+/// The user is only warned once per target framerate, so they aren't nagged about something they chose to live with.
+#[derive(Debug, Clone, Default)]
+pub struct FramerateCheck {
+    started: Option<Instant>,
+    falling_short: bool,
+    warned_target_fps: Option<f32>,
+}
+
+impl FramerateCheck {
+    /// Starts over with the warm-up, e.g. when the encoder restarts.
+    pub fn restart(&mut self, now: Instant) {
+        self.started = Some(now);
+        self.falling_short = false;
+    }
+
+    pub fn stop(&mut self) {
+        self.started = None;
+        self.falling_short = false;
+    }
+
+    pub fn evaluate(
+        &mut self,
+        now: Instant,
+        target_fps: f32,
+        achieved_fps: f32,
+    ) -> Option<FramerateCheckEvent> {
+        let started = self.started?;
+        let falling_short =
+            framerate_shortfall(target_fps, achieved_fps, now.duration_since(started)).is_some();
+
+        match (self.falling_short, falling_short) {
+            (false, true) => {
+                self.falling_short = true;
+                if self.warned_target_fps == Some(target_fps) {
+                    return None;
+                }
+                self.warned_target_fps = Some(target_fps);
+                Some(FramerateCheckEvent::Shortfall { target_fps, achieved_fps })
+            }
+            (true, false) => {
+                self.falling_short = false;
+                Some(FramerateCheckEvent::Recovered)
+            }
+            _ => None,
+        }
+    }
+}
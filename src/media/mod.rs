@@ -1 +1,13 @@
+pub mod click_highlights;
+pub mod content_switcher;
+pub mod decoder_worker;
+pub mod encode_size;
+pub mod encoder_worker;
 pub mod ffmpeg;
+pub mod frame_pacer;
+pub mod framerate_check;
+pub mod nal;
+pub mod quality_request;
+pub mod recorder;
+pub mod stats;
+pub mod watchdog;
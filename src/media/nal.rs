@@ -0,0 +1,126 @@
+/// What a NAL unit holds, as far as the app cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NalKind {
+    /// A slice of a picture that can be decoded on its own. For H.265, that is any intra random access point.
+    Idr,
+    /// A slice of a picture that refers to the ones before it.
+    NonIdr,
+    /// Video parameter set, which only H.265 has.
+    Vps,
+    Sps,
+    Pps,
+    /// Supplemental enhancement information, like timing, which decoding doesn't depend on.
+    Sei,
+    /// Anything else, by its type.
+    Other(u8),
+}
+
+/// The codecs whose packets are annex B byte streams of NAL units, which is what the encoders produce for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NalCodec {
+    H264,
+    Hevc,
+}
+
+impl NalCodec {
+    /// The codec of the negotiated track, or None if its packets aren't made of NAL units, like VP8 and VP9.
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        match mime_type.to_ascii_lowercase().as_str() {
+            "video/h264" => Some(Self::H264),
+            "video/h265" | "video/hevc" => Some(Self::Hevc),
+            _ => None,
+        }
+    }
+
+    /// The type of the NAL unit, from the first byte(s) of its header.
+    pub fn unit_type(self, unit: &[u8]) -> Option<u8> {
+        let header = *unit.first()?;
+        match self {
+            Self::H264 => Some(header & 0x1F),
+            Self::Hevc => Some((header >> 1) & 0x3F),
+        }
+    }
+
+    /// What the NAL unit holds, or None if it is empty.
+    pub fn kind(self, unit: &[u8]) -> Option<NalKind> {
+        let unit_type = self.unit_type(unit)?;
+        Some(match (self, unit_type) {
+            (Self::H264, 5) => NalKind::Idr,
+            // Slices, including the data partitions of one.
+            (Self::H264, 1..=4) => NalKind::NonIdr,
+            (Self::H264, 6) => NalKind::Sei,
+            (Self::H264, 7) => NalKind::Sps,
+            (Self::H264, 8) => NalKind::Pps,
+            // BLA, IDR and CRA pictures.
+            (Self::Hevc, 16..=21) => NalKind::Idr,
+            (Self::Hevc, 0..=9) => NalKind::NonIdr,
+            (Self::Hevc, 32) => NalKind::Vps,
+            (Self::Hevc, 33) => NalKind::Sps,
+            (Self::Hevc, 34) => NalKind::Pps,
+            // Prefix and suffix SEI.
+            (Self::Hevc, 39 | 40) => NalKind::Sei,
+            (_, other) => NalKind::Other(other),
+        })
+    }
+
+    /// Whether the NAL unit starts a picture that can be decoded without the ones before it.
+    pub fn is_keyframe_unit(self, unit: &[u8]) -> bool {
+        self.kind(unit) == Some(NalKind::Idr)
+    }
+}
+
+/// The NAL units of an annex B byte stream, without their start codes.
+pub fn units(stream: &[u8]) -> NalUnits<'_> {
+    NalUnits { rest: skip_start_code(stream) }
+}
+
+/// Whether the packet holds a keyframe, so it can be decoded on its own, along with everything after it.
+pub fn is_keyframe(packet: &[u8], codec: NalCodec) -> bool {
+    units(packet).any(|unit| codec.is_keyframe_unit(unit))
+}
+
+/// Iterator over the NAL units of an annex B byte stream.
+#[derive(Debug, Clone)]
+pub struct NalUnits<'a> {
+    rest: Option<&'a [u8]>,
+}
+
+impl<'a> Iterator for NalUnits<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rest = self.rest?;
+            let (unit, next) = match find_start_code(rest) {
+                Some((start, len)) => (&rest[..start], Some(&rest[start + len..])),
+                None => (rest, None),
+            };
+            self.rest = next;
+            // The zero byte of a four byte start code is trailing the previous unit.
+            let unit = trim_trailing_zeros(unit);
+            if !unit.is_empty() {
+                return Some(unit);
+            }
+        }
+    }
+}
+
+// None if the stream doesn't start with a start code, in which case it isn't annex B.
+fn skip_start_code(stream: &[u8]) -> Option<&[u8]> {
+    match find_start_code(stream) {
+        Some((start, len)) if stream[..start].iter().all(|&byte| byte == 0) => {
+            Some(&stream[start + len..])
+        }
+        _ => None,
+    }
+}
+
+// The offset and length of the first three byte start code.
+fn find_start_code(stream: &[u8]) -> Option<(usize, usize)> {
+    stream.windows(3).position(|window| window == [0, 0, 1]).map(|start| (start, 3))
+}
+
+fn trim_trailing_zeros(unit: &[u8]) -> &[u8] {
+    let end = unit.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+    &unit[..end]
+}
@@ -0,0 +1,88 @@
+use std::fmt::Display;
+
+use fjarsyn_shared::QualityRequest;
+
+use crate::{capture_providers::shared::CaptureFramerate, config::EncodingSettings};
+
+/// The settings to send with, which are the lower of the local ones and what the viewer asked for.
+pub fn clamp_settings(settings: EncodingSettings, request: &QualityRequest) -> EncodingSettings {
+    EncodingSettings {
+        bitrate: request.max_bitrate.map_or(settings.bitrate, |max| settings.bitrate.min(max)),
+        framerate: request
+            .max_fps
+            .map_or(settings.framerate, |max| settings.framerate.min(framerate_within(max))),
+        max_encode_dimension: match (settings.max_encode_dimension, request.max_dimension) {
+            (Some(local), Some(max)) => Some(local.min(max)),
+            (local, max) => local.or(max),
+        },
+        ..settings
+    }
+}
+
+/// Whether the request lowers any of the settings, which is when it's worth telling the presenter about.
+pub fn reduces(settings: EncodingSettings, request: &QualityRequest) -> bool {
+    clamp_settings(settings, request) != settings
+}
+
+// The highest framerate that can be captured at, within the limit. The lowest one if they are all above it.
+fn framerate_within(max_fps: u32) -> CaptureFramerate {
+    CaptureFramerate::ALL
+        .iter()
+        .rev()
+        .copied()
+        .find(|framerate| framerate.to_hz() <= max_fps as f32)
+        .unwrap_or(CaptureFramerate::ALL[0])
+}
+
+/// The qualities the viewer can ask the presenter for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViewerQuality {
+    /// Whatever the presenter has set.
+    #[default]
+    Presenter,
+    DataSaver,
+    Low,
+    Medium,
+}
+
+impl ViewerQuality {
+    pub const ALL: &[ViewerQuality] = &[
+        ViewerQuality::Presenter,
+        ViewerQuality::DataSaver,
+        ViewerQuality::Low,
+        ViewerQuality::Medium,
+    ];
+
+    /// What to ask the presenter for, or None to leave it up to them.
+    pub const fn request(&self) -> Option<QualityRequest> {
+        match self {
+            Self::Presenter => None,
+            Self::DataSaver => Some(QualityRequest {
+                max_bitrate: Some(1_000_000),
+                max_fps: Some(15),
+                max_dimension: Some(1280),
+            }),
+            Self::Low => Some(QualityRequest {
+                max_bitrate: Some(3_000_000),
+                max_fps: Some(30),
+                max_dimension: Some(1280),
+            }),
+            Self::Medium => Some(QualityRequest {
+                max_bitrate: Some(6_000_000),
+                max_fps: Some(30),
+                max_dimension: Some(1920),
+            }),
+        }
+    }
+}
+
+impl Display for ViewerQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Presenter => write!(f, "Presenter's quality"),
+            Self::DataSaver => write!(f, "Data saver (1 Mbps, 15 fps)"),
+            Self::Low => write!(f, "Low (3 Mbps, 30 fps)"),
+            Self::Medium => write!(f, "Medium (6 Mbps, 30 fps)"),
+        }
+    }
+}
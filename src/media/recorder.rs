@@ -0,0 +1,180 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use bytes::Bytes;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::{
+    config::Config,
+    media::nal::{self, NalCodec},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecorderError {
+    #[error("Only H.264 and HEVC video can be recorded, not {0}")]
+    UnsupportedCodec(String),
+    #[error("Failed to write {0}: {1}")]
+    Write(PathBuf, io::Error),
+    #[error("Recording stopped unexpectedly: {0}")]
+    Writer(tokio::task::JoinError),
+    #[error("The recording was stopped already")]
+    Stopped,
+}
+
+/// Where the recordings of calls are saved.
+pub fn dir() -> Option<PathBuf> {
+    Config::dir().map(|dir| dir.join("recordings"))
+}
+
+/// The extension of a recording of the codec, which players tell the raw stream in it by.
+pub fn file_extension(codec: NalCodec) -> &'static str {
+    match codec {
+        NalCodec::H264 => "h264",
+        NalCodec::Hevc => "hevc",
+    }
+}
+
+/// Writes the received packets of a track as they are, from its first keyframe on.
+/// What it writes is the raw H.264 or HEVC stream, which e.g. ffplay and VLC play as is.
+#[derive(Debug)]
+pub struct RecordingWriter<W> {
+    out: W,
+    codec: NalCodec,
+    // Until a keyframe, the packets refer to pictures that aren't in the recording.
+    waiting_for_keyframe: bool,
+    bytes: u64,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    pub fn new(out: W, codec: NalCodec) -> Self {
+        Self { out, codec, waiting_for_keyframe: true, bytes: 0 }
+    }
+
+    /// Writes the packet, unless it waits for a keyframe still. Returns whether it was written.
+    pub fn write(&mut self, packet: &[u8]) -> io::Result<bool> {
+        if self.waiting_for_keyframe {
+            if !nal::is_keyframe(packet, self.codec) {
+                return Ok(false);
+            }
+            self.waiting_for_keyframe = false;
+        }
+        self.out.write_all(packet)?;
+        self.bytes += packet.len() as u64;
+        Ok(true)
+    }
+
+    /// Skips ahead to the next keyframe, as packets went missing and the ones after may refer to them.
+    pub fn resync(&mut self) {
+        self.waiting_for_keyframe = true;
+    }
+
+    /// The bytes written so far.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Where the decoder worker of the recorded track passes its packets on to the [`Recorder`].
+#[derive(Debug, Clone)]
+pub struct RecordingTap {
+    // Taken once the recording stops, which ends the writer.
+    packets: Arc<Mutex<Option<mpsc::Sender<Bytes>>>>,
+    // Set when a packet didn't fit in the queue, so the writer skips ahead to the next keyframe.
+    gap: Arc<AtomicBool>,
+}
+
+impl RecordingTap {
+    /// Queues the packet to be written, without waiting for the disk.
+    /// One that doesn't fit is dropped, and the recording goes on from the next keyframe.
+    pub fn push(&self, packet: &Bytes) {
+        let packets = self.packets.lock().unwrap();
+        let Some(packets) = packets.as_ref() else {
+            return;
+        };
+        if packets.try_send(packet.clone()).is_err() {
+            self.gap.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn close(&self) {
+        self.packets.lock().unwrap().take();
+    }
+}
+
+type WriterHandle = JoinHandle<Result<u64, RecorderError>>;
+
+/// Records the received video of a call to a file, on a thread of its own so a slow disk doesn't hold up decoding.
+/// The decoder worker of the track feeds it through its [`RecordingTap`]. Clones are handles to the same recording.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    path: PathBuf,
+    tap: RecordingTap,
+    // Taken by the handle that stops it.
+    writer: Arc<Mutex<Option<WriterHandle>>>,
+}
+
+impl Recorder {
+    // A few seconds of video, for when the disk is slow to keep up.
+    const QUEUE_SIZE: usize = 256;
+
+    /// Creates the recording in the folder, named after the codec of the track, and starts writing what the tap gets.
+    pub fn start(folder: &Path, name: &str, mime_type: &str) -> Result<Self, RecorderError> {
+        let codec = NalCodec::from_mime_type(mime_type)
+            .ok_or_else(|| RecorderError::UnsupportedCodec(mime_type.to_owned()))?;
+        fs::create_dir_all(folder).map_err(|e| RecorderError::Write(folder.to_owned(), e))?;
+        let path = folder.join(format!("{}.{}", name, file_extension(codec)));
+        let file = File::create(&path).map_err(|e| RecorderError::Write(path.clone(), e))?;
+
+        let (packets_tx, mut packets) = mpsc::channel::<Bytes>(Self::QUEUE_SIZE);
+        let tap =
+            RecordingTap { packets: Arc::new(Mutex::new(Some(packets_tx))), gap: Arc::default() };
+        let gap = tap.gap.clone();
+        let writer_path = path.clone();
+        let writer = tokio::task::spawn_blocking(move || {
+            let write_error = |e| RecorderError::Write(writer_path.clone(), e);
+            let mut writer = RecordingWriter::new(BufWriter::new(file), codec);
+            while let Some(packet) = packets.blocking_recv() {
+                if gap.swap(false, Ordering::Relaxed) {
+                    tracing::debug!("Recording fell behind, skipping ahead to the next keyframe");
+                    writer.resync();
+                }
+                writer.write(&packet).map_err(write_error)?;
+            }
+            let bytes = writer.bytes();
+            writer.into_inner().flush().map_err(write_error)?;
+            Ok(bytes)
+        });
+        Ok(Self { path, tap, writer: Arc::new(Mutex::new(Some(writer))) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn tap(&self) -> RecordingTap {
+        self.tap.clone()
+    }
+
+    /// Whether the writer stopped by itself, which only happens when it failed.
+    pub fn is_finished(&self) -> bool {
+        self.writer.lock().unwrap().as_ref().is_some_and(JoinHandle::is_finished)
+    }
+
+    /// Stops taking packets, and waits for those queued to be written. Returns the bytes written.
+    pub async fn stop(self) -> Result<u64, RecorderError> {
+        self.tap.close();
+        let writer = self.writer.lock().unwrap().take().ok_or(RecorderError::Stopped)?;
+        writer.await.map_err(RecorderError::Writer)?
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    media::ffmpeg::DecodeAccel,
+    utils::metrics::{Counter, Gauge, Metrics, MetricsSnapshot},
+};
+
+/// What the encoder is doing, for tuning and for telling the user when it can't keep up.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EncoderStats {
+    pub target_fps: f32,
+    /// Lower than the target when frames are decimated.
+    pub output_fps: f32,
+    /// The frames actually encoded per second over the last ten seconds.
+    pub encoded_fps: f32,
+    pub last_encode_time: Duration,
+    /// The frames waiting to be encoded.
+    pub queue_len: usize,
+    /// Bits per second produced over the last second.
+    pub bitrate_1s: f64,
+    /// Bits per second produced over the last ten seconds, which is steadier.
+    pub bitrate_10s: f64,
+    /// Bits per second actually sent over the last second, which the bandwidth cap may hold below what was produced.
+    pub sent_bitrate_1s: f64,
+    /// The samples dropped to stay within the bandwidth cap.
+    pub capped_drops: u64,
+    /// The times the encoder stopped responding and was replaced.
+    pub restarts: u32,
+    /// The frames encoded so far.
+    pub encoded: u64,
+    /// The frames that weren't encoded, as they looked the same as the one before.
+    pub unchanged_skips: u64,
+}
+
+impl EncoderStats {
+    /// What the metrics of the current encoder worker say, or None if there is none.
+    pub fn from_snapshot(snapshot: &MetricsSnapshot) -> Option<Self> {
+        let gauge = |name| snapshot.gauge(name).unwrap_or_default();
+        let counter = |name| snapshot.counter(name).unwrap_or_default();
+        Some(Self {
+            target_fps: snapshot.gauge(EncoderMetrics::TARGET_FPS)? as f32,
+            output_fps: gauge(EncoderMetrics::OUTPUT_FPS) as f32,
+            encoded_fps: gauge(EncoderMetrics::ENCODED_FPS) as f32,
+            last_encode_time: Duration::from_secs_f64(gauge(EncoderMetrics::LAST_ENCODE_TIME)),
+            queue_len: gauge(EncoderMetrics::QUEUE_LEN) as usize,
+            bitrate_1s: gauge(EncoderMetrics::BITRATE_1S),
+            bitrate_10s: gauge(EncoderMetrics::BITRATE_10S),
+            sent_bitrate_1s: gauge(EncoderMetrics::SENT_BITRATE_1S),
+            capped_drops: counter(EncoderMetrics::CAPPED_DROPS),
+            restarts: counter(EncoderMetrics::RESTARTS) as u32,
+            encoded: counter(EncoderMetrics::ENCODED),
+            unchanged_skips: counter(EncoderMetrics::UNCHANGED_SKIPS),
+        })
+    }
+}
+
+/// The metrics the encoder worker keeps its [`EncoderStats`] in. There is only one worker at a time, so a new one
+/// takes them over from zero.
+#[derive(Debug, Clone)]
+pub struct EncoderMetrics {
+    pub target_fps: Arc<Gauge>,
+    pub output_fps: Arc<Gauge>,
+    pub encoded_fps: Arc<Gauge>,
+    /// In seconds.
+    pub last_encode_time: Arc<Gauge>,
+    pub queue_len: Arc<Gauge>,
+    pub bitrate_1s: Arc<Gauge>,
+    pub bitrate_10s: Arc<Gauge>,
+    pub sent_bitrate_1s: Arc<Gauge>,
+    pub capped_drops: Arc<Counter>,
+    pub restarts: Arc<Counter>,
+    pub encoded: Arc<Counter>,
+    pub unchanged_skips: Arc<Counter>,
+}
+
+impl EncoderMetrics {
+    pub const PREFIX: &str = "encoder.";
+    const TARGET_FPS: &str = "encoder.target_fps";
+    const OUTPUT_FPS: &str = "encoder.output_fps";
+    const ENCODED_FPS: &str = "encoder.encoded_fps";
+    const LAST_ENCODE_TIME: &str = "encoder.last_encode_time";
+    const QUEUE_LEN: &str = "encoder.queue_len";
+    const BITRATE_1S: &str = "encoder.bitrate_1s";
+    const BITRATE_10S: &str = "encoder.bitrate_10s";
+    const SENT_BITRATE_1S: &str = "encoder.sent_bitrate_1s";
+    const CAPPED_DROPS: &str = "encoder.capped_drops";
+    const RESTARTS: &str = "encoder.restarts";
+    const ENCODED: &str = "encoder.encoded";
+    const UNCHANGED_SKIPS: &str = "encoder.unchanged_skips";
+
+    /// Registers the metrics anew, dropping those of the worker before.
+    pub fn register(metrics: &Metrics) -> Self {
+        metrics.remove(Self::PREFIX);
+        Self {
+            target_fps: metrics.gauge(Self::TARGET_FPS),
+            output_fps: metrics.gauge(Self::OUTPUT_FPS),
+            encoded_fps: metrics.gauge(Self::ENCODED_FPS),
+            last_encode_time: metrics.gauge(Self::LAST_ENCODE_TIME),
+            queue_len: metrics.gauge(Self::QUEUE_LEN),
+            bitrate_1s: metrics.gauge(Self::BITRATE_1S),
+            bitrate_10s: metrics.gauge(Self::BITRATE_10S),
+            sent_bitrate_1s: metrics.gauge(Self::SENT_BITRATE_1S),
+            capped_drops: metrics.counter(Self::CAPPED_DROPS),
+            restarts: metrics.counter(Self::RESTARTS),
+            encoded: metrics.counter(Self::ENCODED),
+            unchanged_skips: metrics.counter(Self::UNCHANGED_SKIPS),
+        }
+    }
+}
+
+/// What the decoder is doing, mostly how much it had to skip to keep up with the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecoderStats {
+    pub decoded: u64,
+    /// The packets skipped to catch up, rather than decoded late.
+    pub dropped_packets: u64,
+    /// The times the decoder skipped ahead to a keyframe.
+    pub catch_ups: u32,
+    /// The packets waiting to be decoded, as of the last one taken.
+    pub queue_len: usize,
+    /// What the current decoder decodes with.
+    pub accel: DecodeAccel,
+    /// The frames that were allocated rather than reused, as the UI still held the ones that could have been.
+    pub frame_overflows: u64,
+}
+
+/// Sums values over a sliding window of time, e.g. the bytes sent in the last second.
+#[derive(Debug)]
+pub struct RollingWindow {
+    window: Duration,
+    samples: VecDeque<(Instant, u64)>,
+    total: u64,
+}
+
+impl RollingWindow {
+    pub fn new(window: Duration) -> Self {
+        Self { window, samples: VecDeque::new(), total: 0 }
+    }
+
+    pub fn record(&mut self, now: Instant, value: u64) {
+        self.samples.push_back((now, value));
+        self.total += value;
+        self.evict(now);
+    }
+
+    // Drops the samples that have slid out of the window.
+    fn evict(&mut self, now: Instant) {
+        while let Some(&(time, value)) = self.samples.front() {
+            if now.duration_since(time) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+            self.total -= value;
+        }
+    }
+
+    /// The sum of the values in the window, as of the last sample.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The average per second over the whole window.
+    pub fn per_second(&self) -> f64 {
+        self.total as f64 / self.window.as_secs_f64()
+    }
+}
+
+/// How long received samples may be held back, waiting for the packets missing from them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DepacketStats {
+    /// The packets waited for in place, which lags behind the setting until the next keyframe.
+    pub max_latency: u16,
+    /// The packets received per second.
+    pub packet_rate: f32,
+    /// Whether the wait is tuned to the drops, up to the one set.
+    pub auto_tuned: bool,
+    pub packets: u64,
+    pub samples: u64,
+    /// The packets dropped as the rest of their sample didn't come in time.
+    pub dropped_packets: u64,
+}
+
+impl DepacketStats {
+    /// The longest a sample is held back when a packet of it is lost, at the current packet rate.
+    pub fn added_latency(&self) -> Option<Duration> {
+        (self.packet_rate > 0.0)
+            .then(|| Duration::from_secs_f32(self.max_latency as f32 / self.packet_rate))
+    }
+}
+
+/// How the connection fared over the last interval, to rate it by.
+/// What isn't known, e.g. before the peer reported back, is None.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ConnectionStats {
+    pub round_trip_time: Option<Duration>,
+    /// The share of packets lost, from 0 to 1, whichever way it is worse.
+    pub packet_loss: Option<f32>,
+    /// Bits per second the connection is estimated to take.
+    pub available_bitrate: Option<f64>,
+    /// Bits per second the encoder aims for, when sharing.
+    pub target_bitrate: Option<f64>,
+    /// The times the received video froze until the next keyframe.
+    pub freezes: u32,
+    /// The bytes sent over the connection so far.
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+}
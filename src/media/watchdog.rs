@@ -0,0 +1,154 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc as std_mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::oneshot;
+
+use crate::utils::panic_guard;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WatchdogError {
+    #[error("Call has been stuck for {0:?}")]
+    Wedged(Duration),
+    #[error("Call panicked: {0}")]
+    Panicked(String),
+    #[error("Thread has stopped")]
+    Stopped,
+}
+
+/// When the call in flight on a watched thread started, for the supervisor to tell how long it has been stuck.
+#[derive(Debug)]
+pub struct Heartbeat {
+    epoch: Instant,
+    // Microseconds since the epoch plus one, so that zero can mean idle.
+    busy_since: AtomicU64,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self { epoch: Instant::now(), busy_since: AtomicU64::new(0) }
+    }
+
+    pub fn begin(&self) {
+        let since = self.epoch.elapsed().as_micros() as u64 + 1;
+        self.busy_since.store(since, Ordering::Release);
+    }
+
+    pub fn end(&self) {
+        self.busy_since.store(0, Ordering::Release);
+    }
+
+    /// How long the call in flight has taken so far, or None if there isn't one.
+    pub fn busy_for(&self) -> Option<Duration> {
+        match self.busy_since.load(Ordering::Acquire) {
+            0 => None,
+            since => {
+                let started = Duration::from_micros(since - 1);
+                Some(self.epoch.elapsed().saturating_sub(started))
+            }
+        }
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type Job<T> = Box<dyn FnOnce(&mut T) + Send>;
+
+/// Owns a value on a thread of its own, and runs calls on it there,
+/// so a call that never returns, like a hung driver, can be noticed and given up on instead of blocking the caller.
+/// Dropping it abandons the thread, which exits once it is done with the call it is stuck in, if ever.
+pub struct Watched<T> {
+    jobs: std_mpsc::Sender<Job<T>>,
+    heartbeat: Arc<Heartbeat>,
+    timeout: Duration,
+    check_interval: Duration,
+}
+
+impl<T: Send + 'static> Watched<T> {
+    /// How often the supervisor looks at the heartbeat while waiting on a call, at most.
+    pub const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Moves the value to a new thread. Calls stuck for longer than the timeout are given up on.
+    pub fn spawn(name: &str, value: T, timeout: Duration) -> Self {
+        let (jobs, jobs_rx) = std_mpsc::channel::<Job<T>>();
+        thread::Builder::new()
+            .name(name.to_owned())
+            .spawn(move || {
+                let mut value = value;
+                while let Ok(job) = jobs_rx.recv() {
+                    job(&mut value);
+                }
+            })
+            .expect("failed to spawn watched thread");
+
+        Self {
+            jobs,
+            heartbeat: Arc::new(Heartbeat::new()),
+            timeout,
+            check_interval: timeout.min(Self::CHECK_INTERVAL),
+        }
+    }
+
+    /// Runs the call on the value, and waits for it to return, unless it gets stuck.
+    /// Once a call is stuck, so is the thread, and every call after it will be too.
+    pub async fn call<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut T) -> R + Send + 'static,
+    ) -> Result<R, WatchdogError> {
+        let (reply_tx, mut reply_rx) = oneshot::channel();
+        let heartbeat = self.heartbeat.clone();
+        let job: Job<T> = Box::new(move |value| {
+            heartbeat.begin();
+            // Caught here so the thread survives to report it, the caller decides what to do about it.
+            let result = panic_guard::catch_panic("Watched call", || f(value));
+            heartbeat.end();
+            let _ = reply_tx.send(result);
+        });
+        self.jobs.send(job).map_err(|_| WatchdogError::Stopped)?;
+
+        let mut check = tokio::time::interval(self.check_interval);
+        check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                reply = &mut reply_rx => {
+                    return match reply {
+                        Ok(Ok(result)) => Ok(result),
+                        Ok(Err(message)) => Err(WatchdogError::Panicked(message)),
+                        Err(_) => Err(WatchdogError::Stopped),
+                    };
+                }
+                _ = check.tick() => {
+                    if let Some(busy_for) = self.heartbeat.busy_for()
+                        && busy_for >= self.timeout
+                    {
+                        return Err(WatchdogError::Wedged(busy_for));
+                    }
+                }
+            }
+        }
+    }
+
+    /// How long the call in flight has taken so far, or None if the thread is idle.
+    pub fn busy_for(&self) -> Option<Duration> {
+        self.heartbeat.busy_for()
+    }
+}
+
+impl<T> std::fmt::Debug for Watched<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watched")
+            .field("heartbeat", &self.heartbeat)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use ed25519_dalek::{Signer, SigningKey};
+use fjarsyn_shared::{AuthenticatePayload, auth_message, peer_id_from_public_key};
+use rand_core::OsRng;
+
+/// The key this client is known by. Servers that support it give the client the ID derived from it,
+/// so the ID stays the same across runs, along with what was saved for it.
+pub struct IdentityKey {
+    signing_key: SigningKey,
+}
+
+impl IdentityKey {
+    pub fn generate() -> Self {
+        Self { signing_key: SigningKey::generate(&mut OsRng) }
+    }
+
+    pub fn from_bytes(secret: &[u8; 32]) -> Self {
+        Self { signing_key: SigningKey::from_bytes(secret) }
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.signing_key.verifying_key().to_bytes()
+    }
+
+    /// The ID the server gives the client once it authenticated.
+    pub fn peer_id(&self) -> String {
+        peer_id_from_public_key(&self.public_key())
+    }
+
+    /// Signs the nonce the server sent with the identity.
+    pub fn authenticate(&self, nonce: &str) -> AuthenticatePayload {
+        let signature = self.signing_key.sign(&auth_message(nonce));
+        AuthenticatePayload::new(&self.public_key(), &signature.to_bytes())
+    }
+
+    /// The key stored at the path. Creates and stores one if there is none yet.
+    /// None if it can't be stored, as a key that doesn't last gives nothing over the ID the server picks.
+    pub fn load_or_create(path: &Path) -> Option<Self> {
+        match fs::read(path) {
+            Ok(secret) => match <[u8; 32]>::try_from(secret.as_slice()) {
+                Ok(secret) => return Some(Self::from_bytes(&secret)),
+                Err(_) => tracing::warn!("Identity key is malformed, creating a new one"),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => {
+                // Replacing it would change the ID for good over what may not last.
+                tracing::error!("Failed to read identity key: {}", e);
+                return None;
+            }
+        }
+
+        let key = Self::generate();
+        if let Err(e) = key.store(path) {
+            tracing::error!("Failed to store identity key: {}", e);
+            return None;
+        }
+        tracing::info!("Created identity key for ID {}", key.peer_id());
+        Some(key)
+    }
+
+    // Only readable by the user. On Windows, the config directory already is, and the file inherits that.
+    fn store(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(path)?.write_all(self.signing_key.as_bytes())
+    }
+}
+
+// Leaves out the secret.
+impl std::fmt::Debug for IdentityKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdentityKey").field("peer_id", &self.peer_id()).finish_non_exhaustive()
+    }
+}
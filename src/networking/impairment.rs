@@ -0,0 +1,322 @@
+//! Makes the network worse on purpose, to try out how calls cope with loss, latency and little bandwidth
+//! without tools outside of the app. Only set up in debug builds, from the `FJARSYN_IMPAIR_*` environment variables.
+
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::{sync::mpsc, task::AbortHandle};
+
+/// Packets that would wait longer than this for the bandwidth are dropped instead, like a router's queue overflowing.
+pub const MAX_QUEUE_DELAY: Duration = Duration::from_secs(1);
+/// How much the bandwidth cap lets through at once after being idle.
+const BUCKET_BURST: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImpairmentConfig {
+    /// The share of packets dropped, from 0 to 1.
+    pub loss: f32,
+    /// How many packets are dropped in a row on average, 1 for independent drops.
+    pub burst_length: f32,
+    /// Added to every packet.
+    pub latency: Duration,
+    /// Up to this much more or less latency per packet. Packets are still delivered in order.
+    pub jitter: Duration,
+    /// In bits per second.
+    pub bandwidth: Option<u32>,
+    /// The same seed drops and delays the same packets, so runs can be repeated.
+    pub seed: u64,
+    /// Whether it applies to the video sent, and to the video received.
+    pub send: bool,
+    pub receive: bool,
+}
+
+impl ImpairmentConfig {
+    /// Read from `FJARSYN_IMPAIR_LOSS` (0 to 1), `_BURST` (packets), `_LATENCY` and `_JITTER` (ms), `_BANDWIDTH` (bits per second),
+    /// `_SEED` and `_DIRECTION` (`send`, `receive` or `both`, receive by default). None if none of them make anything worse.
+    pub fn from_env() -> Option<Self> {
+        Self::from_vars(|name| std::env::var(format!("FJARSYN_IMPAIR_{}", name)).ok())
+    }
+
+    /// Like [`Self::from_env`], with the variables looked up by the name after the prefix. Those that don't parse are ignored.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        fn parse<T: std::str::FromStr>(
+            var: &impl Fn(&str) -> Option<String>,
+            name: &str,
+        ) -> Option<T> {
+            let value = var(name)?;
+            value
+                .trim()
+                .parse()
+                .inspect_err(|_| tracing::warn!("Ignoring FJARSYN_IMPAIR_{}={}", name, value))
+                .ok()
+        }
+
+        let (send, receive) = match var("DIRECTION").as_deref().map(str::trim) {
+            Some("send") => (true, false),
+            Some("both") => (true, true),
+            _ => (false, true),
+        };
+        let config = Self {
+            loss: parse::<f32>(&var, "LOSS").unwrap_or(0.0).clamp(0.0, 1.0),
+            burst_length: parse::<f32>(&var, "BURST").unwrap_or(1.0).max(1.0),
+            latency: Duration::from_millis(parse(&var, "LATENCY").unwrap_or(0)),
+            jitter: Duration::from_millis(parse(&var, "JITTER").unwrap_or(0)),
+            bandwidth: parse::<u32>(&var, "BANDWIDTH").filter(|bandwidth| *bandwidth > 0),
+            seed: parse(&var, "SEED").unwrap_or(0),
+            send,
+            receive,
+        };
+        config.is_active().then_some(config)
+    }
+
+    /// Which of the videos it applies to, e.g. "sent and received".
+    pub fn direction(&self) -> &'static str {
+        match (self.send, self.receive) {
+            (true, true) => "sent and received",
+            (true, false) => "sent",
+            (false, true) => "received",
+            (false, false) => "neither sent nor received",
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.loss > 0.0
+            || !self.latency.is_zero()
+            || !self.jitter.is_zero()
+            || self.bandwidth.is_some()
+    }
+}
+
+impl Display for ImpairmentConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1}% loss", self.loss * 100.0)?;
+        if self.burst_length > 1.0 {
+            write!(f, " in bursts of {:.1}", self.burst_length)?;
+        }
+        write!(f, ", {} ms", self.latency.as_millis())?;
+        if !self.jitter.is_zero() {
+            write!(f, " \u{b1} {} ms", self.jitter.as_millis())?;
+        }
+        if let Some(bandwidth) = self.bandwidth {
+            write!(f, ", {:.2} Mbps cap", bandwidth as f64 / 1_000_000.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// A small generator that gives the same numbers for the same seed (SplitMix64).
+#[derive(Debug, Clone)]
+pub struct SeededRng(u64);
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// From 0 up to, but not including, 1.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Drops packets in bursts (the Gilbert model): every packet is dropped while in the bad state, none in the good one.
+#[derive(Debug, Clone)]
+pub struct LossPattern {
+    // The chances of going bad after a delivered packet, and of recovering after a dropped one.
+    enter_loss: f64,
+    leave_loss: f64,
+    losing: bool,
+}
+
+impl LossPattern {
+    /// Drops `loss` of the packets in the long run, `burst_length` in a row on average.
+    pub fn new(loss: f32, burst_length: f32) -> Self {
+        let loss = (loss as f64).clamp(0.0, 1.0);
+        let burst_length = (burst_length as f64).max(1.0);
+        if loss >= 1.0 {
+            return Self { enter_loss: 1.0, leave_loss: 0.0, losing: true };
+        }
+        let leave_loss = 1.0 / burst_length;
+        let enter_loss = (loss * leave_loss / (1.0 - loss)).min(1.0);
+        Self { enter_loss, leave_loss, losing: false }
+    }
+
+    pub fn drops(&mut self, rng: &mut SeededRng) -> bool {
+        let change = if self.losing { self.leave_loss } else { self.enter_loss };
+        if rng.next_f64() < change {
+            self.losing = !self.losing;
+        }
+        self.losing
+    }
+}
+
+/// Caps the bandwidth, by holding back what goes over it until the bucket fills up again.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    // In bytes, and bytes per second.
+    rate: f64,
+    capacity: f64,
+    // Negative while packets are queued.
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(bits_per_second: u32, now: Instant) -> Self {
+        let rate = bits_per_second as f64 / 8.0;
+        let capacity = rate * BUCKET_BURST.as_secs_f64();
+        Self { rate, capacity, tokens: capacity, last: now }
+    }
+
+    /// How long a packet of `len` bytes waits before it goes out at the capped rate.
+    pub fn delay(&mut self, len: usize, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = self.last.max(now);
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity) - len as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    /// Gives back the tokens of a packet that was dropped rather than queued.
+    pub fn refund(&mut self, len: usize) {
+        self.tokens = (self.tokens + len as f64).min(self.capacity);
+    }
+}
+
+/// What the impairment did so far, for the stats overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImpairmentStats {
+    pub config: ImpairmentConfig,
+    pub passed: u64,
+    /// Dropped by the loss pattern.
+    pub lost: u64,
+    /// Dropped as they would have waited too long for the bandwidth.
+    pub overflowed: u64,
+    /// How long the last packet passed was held back.
+    pub last_delay: Duration,
+}
+
+/// Decides for each packet whether it is dropped, and how long it is held back otherwise.
+#[derive(Debug, Clone)]
+pub struct Impairment {
+    rng: SeededRng,
+    loss: LossPattern,
+    bucket: Option<TokenBucket>,
+    // When the last packet passed is delivered, which the next ones can't come before.
+    last_due: Option<Instant>,
+    stats: ImpairmentStats,
+}
+
+impl Impairment {
+    pub fn new(config: ImpairmentConfig, now: Instant) -> Self {
+        Self {
+            rng: SeededRng::new(config.seed),
+            loss: LossPattern::new(config.loss, config.burst_length),
+            bucket: config.bandwidth.map(|bandwidth| TokenBucket::new(bandwidth, now)),
+            last_due: None,
+            stats: ImpairmentStats { config, ..Default::default() },
+        }
+    }
+
+    /// When a packet of `len` bytes arriving now is delivered, or None if it is dropped.
+    pub fn admit(&mut self, len: usize, now: Instant) -> Option<Instant> {
+        if self.loss.drops(&mut self.rng) {
+            self.stats.lost += 1;
+            return None;
+        }
+
+        let queued = self.bucket.as_mut().map_or(Duration::ZERO, |bucket| bucket.delay(len, now));
+        if queued > MAX_QUEUE_DELAY {
+            if let Some(bucket) = &mut self.bucket {
+                bucket.refund(len);
+            }
+            self.stats.overflowed += 1;
+            return None;
+        }
+
+        let config = &self.stats.config;
+        let jitter = config.jitter.as_secs_f64() * (self.rng.next_f64() * 2.0 - 1.0);
+        let latency = Duration::from_secs_f64((config.latency.as_secs_f64() + jitter).max(0.0));
+        let due = (now + queued + latency).max(self.last_due.unwrap_or(now));
+        self.last_due = Some(due);
+
+        self.stats.passed += 1;
+        self.stats.last_delay = due - now;
+        Some(due)
+    }
+
+    pub fn stats(&self) -> ImpairmentStats {
+        self.stats
+    }
+}
+
+/// Passes what is sent through an impairment, delivering what isn't dropped on a task of its own once it is due.
+#[derive(Debug)]
+pub struct ImpairedLink<T> {
+    impairment: Arc<Mutex<Impairment>>,
+    queue: mpsc::UnboundedSender<(Instant, T)>,
+    task: AbortHandle,
+}
+
+// Derived, it would need what is sent to be Clone too.
+impl<T> Clone for ImpairedLink<T> {
+    fn clone(&self) -> Self {
+        Self {
+            impairment: self.impairment.clone(),
+            queue: self.queue.clone(),
+            task: self.task.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> ImpairedLink<T> {
+    /// `deliver` returns false once nothing more can be delivered, which ends the task.
+    pub fn spawn<F, Fut>(impairment: Impairment, mut deliver: F) -> Self
+    where
+        F: FnMut(T) -> Fut + Send + 'static,
+        Fut: Future<Output = bool> + Send,
+    {
+        let (queue, mut due) = mpsc::unbounded_channel::<(Instant, T)>();
+        let task = tokio::spawn(async move {
+            while let Some((at, item)) = due.recv().await {
+                tokio::time::sleep_until(at.into()).await;
+                if !deliver(item).await {
+                    break;
+                }
+            }
+        });
+        Self { impairment: Arc::new(Mutex::new(impairment)), queue, task: task.abort_handle() }
+    }
+
+    /// Returns false if the delivering task ended.
+    pub fn send(&self, item: T, len: usize) -> bool {
+        let Some(due) = self.impairment.lock().unwrap().admit(len, Instant::now()) else {
+            return !self.queue.is_closed();
+        };
+        self.queue.send((due, item)).is_ok()
+    }
+
+    pub fn stats(&self) -> ImpairmentStats {
+        self.impairment.lock().unwrap().stats()
+    }
+
+    /// Stops delivering, dropping what is still held back.
+    pub fn close(&self) {
+        self.task.abort();
+    }
+}
@@ -1,3 +1,7 @@
+pub mod identity;
+pub mod impairment;
+pub mod sealed_signaling;
+pub mod sealed_signaling_error;
 pub mod signaling;
 pub mod signaling_error;
 pub mod webrtc;
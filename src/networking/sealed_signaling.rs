@@ -0,0 +1,283 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use argon2::Argon2;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, Payload, rand_core::RngCore},
+};
+use fjarsyn_shared::{SignalingMessage, SignalingType};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use tokio::sync::OnceCell;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::networking::sealed_signaling_error::{SealedSignalingError, SealedSignalingResult};
+
+type Salt = [u8; SignalingSeal::SALT_LEN];
+
+// What the key is derived from: a passphrase, for each salt, or a key exchange, which agreed on the key itself.
+enum Secret {
+    Passphrase(String),
+    Agreed(Key),
+}
+
+/// Encrypts the signaling payloads that set up the connection, with a key derived from a passphrase the peers share,
+/// or agreed on in a [`KeyExchange`].
+/// The server relays them without being able to read or swap them, e.g. to put itself between the peers.
+pub struct SignalingSeal {
+    secret: Secret,
+    // Picked per seal, so the passphrase can't be tried against tables made up front.
+    salt: Salt,
+    // The key of our own salt, which everything we seal uses.
+    key: OnceCell<Key>,
+    // The keys derived so far for each peer by salt, as the peer seals with its own.
+    peer_keys: Mutex<HashMap<String, Vec<(Salt, Key)>>>,
+}
+
+impl SignalingSeal {
+    pub const SALT_LEN: usize = 16;
+    const VERSION: u8 = 1;
+    const NONCE_LEN: usize = 12;
+    // Each salt costs a derivation, so a peer, or a relay passing itself off as one, can't make us derive without end.
+    // A peer picks a new one when it connects again, or its passphrase changes.
+    pub const MAX_SALTS_PER_PEER: usize = 4;
+
+    pub fn new(passphrase: &str) -> Self {
+        let mut salt = [0; Self::SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::with_salt(passphrase, salt)
+    }
+
+    pub fn with_salt(passphrase: &str, salt: [u8; Self::SALT_LEN]) -> Self {
+        Self::with_secret(Secret::Passphrase(passphrase.to_owned()), salt)
+    }
+
+    // The salt is still sent, so the format stays the same, but the key doesn't depend on it.
+    fn with_agreed_key(key: Key) -> Self {
+        let mut salt = [0; Self::SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::with_secret(Secret::Agreed(key), salt)
+    }
+
+    fn with_secret(secret: Secret, salt: Salt) -> Self {
+        Self { secret, salt, key: OnceCell::new(), peer_keys: Mutex::new(HashMap::new()) }
+    }
+
+    /// The passphrase the keys are derived from. None if they were agreed on in a key exchange.
+    pub fn passphrase(&self) -> Option<&str> {
+        match &self.secret {
+            Secret::Passphrase(passphrase) => Some(passphrase),
+            Secret::Agreed(_) => None,
+        }
+    }
+
+    /// Whether messages of the type are sealed. Only those describing the connection are,
+    /// as the server has to read the rest.
+    pub fn seals(sig_type: &SignalingType) -> bool {
+        matches!(sig_type, SignalingType::Offer | SignalingType::Answer | SignalingType::Candidate)
+    }
+
+    /// Encrypts the data for a message of the type, which it can't be passed off as another type of.
+    /// The result is the version, salt, nonce and ciphertext in base64.
+    pub async fn seal(
+        &self,
+        sig_type: &SignalingType,
+        plaintext: &str,
+    ) -> SealedSignalingResult<String> {
+        let key = self.key.get_or_try_init(|| self.derive_key(self.salt)).await?;
+        let cipher = ChaCha20Poly1305::new(key);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload { msg: plaintext.as_bytes(), aad: sig_type.name().as_bytes() };
+        let ciphertext =
+            cipher.encrypt(&nonce, payload).map_err(|_| SealedSignalingError::SealFailed)?;
+
+        let mut sealed =
+            Vec::with_capacity(1 + Self::SALT_LEN + Self::NONCE_LEN + ciphertext.len());
+        sealed.push(Self::VERSION);
+        sealed.extend_from_slice(&self.salt);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    /// Decrypts what [`Self::seal`] made for a message of the type, sent by the peer.
+    pub async fn open(
+        &self,
+        peer: &str,
+        sig_type: &SignalingType,
+        data: &str,
+    ) -> SealedSignalingResult<String> {
+        let sealed = STANDARD.decode(data).map_err(|_| SealedSignalingError::Malformed)?;
+        let (&version, rest) = sealed.split_first().ok_or(SealedSignalingError::Malformed)?;
+        if version != Self::VERSION {
+            return Err(SealedSignalingError::UnsupportedVersion(version));
+        }
+        if rest.len() < Self::SALT_LEN + Self::NONCE_LEN {
+            return Err(SealedSignalingError::Malformed);
+        }
+        let (salt, rest) = rest.split_at(Self::SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(Self::NONCE_LEN);
+
+        let cipher = ChaCha20Poly1305::new(&self.peer_key(peer, salt.try_into().unwrap()).await?);
+        let payload = Payload { msg: ciphertext, aad: sig_type.name().as_bytes() };
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| SealedSignalingError::OpenFailed)?;
+        String::from_utf8(plaintext).map_err(|_| SealedSignalingError::Malformed)
+    }
+
+    /// Seals the data of a message to send, if there is a seal and its type is one to seal.
+    pub async fn seal_message(
+        seal: Option<&Self>,
+        mut msg: SignalingMessage,
+    ) -> SealedSignalingResult<SignalingMessage> {
+        if let Some(seal) = seal
+            && Self::seals(&msg.sig_type)
+        {
+            msg.data = seal.seal(&msg.sig_type, &msg.data).await?;
+            msg.sealed = true;
+        }
+        Ok(msg)
+    }
+
+    /// Opens the data of a received message. Fails if only one of the peers seals,
+    /// so a server leaving out or adding the seal can't make a peer go without.
+    pub async fn open_message(
+        seal: Option<&Self>,
+        mut msg: SignalingMessage,
+    ) -> SealedSignalingResult<SignalingMessage> {
+        if !Self::seals(&msg.sig_type) {
+            return Ok(msg);
+        }
+        match (seal, msg.sealed) {
+            (Some(seal), true) => {
+                msg.data = seal.open(&msg.from, &msg.sig_type, &msg.data).await?;
+                Ok(msg)
+            }
+            (None, true) => Err(SealedSignalingError::PeerRequiresEncryption),
+            (Some(_), false) => Err(SealedSignalingError::PeerNotEncrypted),
+            (None, false) => Ok(msg),
+        }
+    }
+
+    // The key of a salt the peer sealed with, derived the first time.
+    // The lock is only held to look the key up and to keep it, not while it is derived.
+    async fn peer_key(&self, peer: &str, salt: Salt) -> SealedSignalingResult<Key> {
+        if let Secret::Agreed(key) = &self.secret {
+            return Ok(*key);
+        }
+        {
+            let peer_keys = self.peer_keys.lock().unwrap();
+            let salts = peer_keys.get(peer).map(Vec::as_slice).unwrap_or_default();
+            if let Some((_, key)) = salts.iter().find(|(known, _)| *known == salt) {
+                return Ok(*key);
+            }
+            if salts.len() >= Self::MAX_SALTS_PER_PEER {
+                return Err(SealedSignalingError::TooManySalts);
+            }
+        }
+
+        let key = self.derive_key(salt).await?;
+        let mut peer_keys = self.peer_keys.lock().unwrap();
+        let salts = peer_keys.entry(peer.to_owned()).or_default();
+        // Another message with the salt may have derived it meanwhile.
+        if salts.len() < Self::MAX_SALTS_PER_PEER && !salts.iter().any(|(known, _)| *known == salt)
+        {
+            salts.push((salt, key));
+        }
+        Ok(key)
+    }
+
+    // Argon2 takes a while on purpose, so it runs on a thread for blocking work, rather than holding up the signaling.
+    async fn derive_key(&self, salt: Salt) -> SealedSignalingResult<Key> {
+        let passphrase = match &self.secret {
+            Secret::Passphrase(passphrase) => passphrase.clone(),
+            Secret::Agreed(key) => return Ok(*key),
+        };
+        tokio::task::spawn_blocking(move || {
+            let mut key = Key::default();
+            Argon2::default()
+                .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+                .map_err(SealedSignalingError::KeyDerivationError)?;
+            Ok(key)
+        })
+        .await
+        .map_err(SealedSignalingError::KeyDerivationFailed)?
+    }
+}
+
+// Leaves out the passphrase and keys.
+impl std::fmt::Debug for SignalingSeal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignalingSeal").finish_non_exhaustive()
+    }
+}
+
+/// Our end of an X25519 key exchange, which agrees on the key of a [`SignalingSeal`] with a peer without a passphrase.
+/// The public keys are sent in [`SignalingType::KeyExchange`] messages. The server can't read what is sealed then,
+/// but could take part in the exchange itself, which comparing the security code of the call shows.
+pub struct KeyExchange {
+    secret: StaticSecret,
+    public: PublicKey,
+}
+
+impl KeyExchange {
+    pub const PUBLIC_KEY_LEN: usize = 32;
+    // Sets the agreed keys apart from any other use of the same exchange.
+    const KEY_INFO: &[u8] = b"fjarsyn signaling seal";
+
+    pub fn new() -> Self {
+        let mut secret = [0; 32];
+        OsRng.fill_bytes(&mut secret);
+        Self::with_secret(secret)
+    }
+
+    pub fn with_secret(secret: [u8; 32]) -> Self {
+        let secret = StaticSecret::from(secret);
+        Self { public: PublicKey::from(&secret), secret }
+    }
+
+    /// Our public key in base64, as the data of a [`SignalingType::KeyExchange`] message.
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.public.as_bytes())
+    }
+
+    /// The seal of the key agreed with the peer whose public key it is, in base64.
+    /// Both ends get the same key, whichever sent its public key first.
+    pub fn agree(&self, peer_public_key: &str) -> SealedSignalingResult<SignalingSeal> {
+        let peer_public = STANDARD
+            .decode(peer_public_key)
+            .ok()
+            .and_then(|bytes| <[u8; Self::PUBLIC_KEY_LEN]>::try_from(bytes).ok())
+            .map(PublicKey::from)
+            .ok_or(SealedSignalingError::InvalidPublicKey)?;
+        let shared = self.secret.diffie_hellman(&peer_public);
+        // A key of low order makes the shared secret one the server knows as well.
+        if !shared.was_contributory() {
+            return Err(SealedSignalingError::InvalidPublicKey);
+        }
+
+        // Both public keys go in, in the same order on both ends, so the key belongs to this pair alone.
+        let mut salt = [self.public.to_bytes(), peer_public.to_bytes()];
+        salt.sort();
+        let mut key = Key::default();
+        Hkdf::<Sha256>::new(Some(salt.as_flattened()), shared.as_bytes())
+            .expand(Self::KEY_INFO, &mut key)
+            .map_err(|_| SealedSignalingError::SealFailed)?;
+        Ok(SignalingSeal::with_agreed_key(key))
+    }
+}
+
+impl Default for KeyExchange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Leaves out the secret.
+impl std::fmt::Debug for KeyExchange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyExchange").field("public", &self.public_key()).finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,33 @@
+pub type SealedSignalingResult<T> = Result<T, SealedSignalingError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SealedSignalingError {
+    #[error("Peer requires encrypted signaling, enter the passphrase they use")]
+    PeerRequiresEncryption,
+    #[error("Peer doesn't use encrypted signaling, but a passphrase is set")]
+    PeerNotEncrypted,
+    #[error("Sealed payload is malformed")]
+    Malformed,
+    #[error("Sealed payload has unsupported version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Sealed payload could not be opened, the passphrases differ or it was tampered with")]
+    OpenFailed,
+    #[error("Sealing failed")]
+    SealFailed,
+    #[error("Key derivation error: {0}")]
+    KeyDerivationError(argon2::Error),
+    #[error("Key derivation didn't finish: {0}")]
+    KeyDerivationFailed(tokio::task::JoinError),
+    #[error("Peer sealed with too many different salts")]
+    TooManySalts,
+    #[error("Peer sent an invalid key for the key exchange")]
+    InvalidPublicKey,
+    #[error(
+        "Peer didn't answer the key exchange, it may not support encrypted signaling without a passphrase"
+    )]
+    KeyExchangeTimedOut,
+    #[error("No key was agreed with the peer, so its signaling can't be opened")]
+    NoAgreedKey,
+    #[error("Peer encrypts signaling with a key exchange, turn it on instead of a passphrase")]
+    PeerRequiresKeyExchange,
+}
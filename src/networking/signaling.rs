@@ -1,58 +1,249 @@
-use fjarsyn_shared::SignalingMessage;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use fjarsyn_shared::{
+    IdentityPayload, MAX_MESSAGE_SIZE, PROTOCOL_VERSION, SignalingMessage, SignalingType,
+    decode_binary_frame, encode_binary_frame,
+};
 use futures_util::{
     SinkExt, StreamExt,
     stream::{SplitSink, SplitStream},
 };
-use tokio::{net::TcpStream, sync::mpsc};
+use tokio::{
+    net::TcpStream,
+    sync::{Notify, mpsc},
+};
 use tokio_tungstenite::{
     MaybeTlsStream, WebSocketStream, connect_async, tungstenite::protocol::Message,
 };
 
-use crate::networking::signaling_error::SignalingError;
+use crate::networking::{identity::IdentityKey, signaling_error::SignalingError};
 
 type Result<T> = std::result::Result<T, SignalingError>;
+type WsWrite = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+type WsRead = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+
+/// Called with the sender and the payload of every binary message relayed by the server.
+pub type BinaryCallback = Box<dyn Fn(String, Bytes) + Send + Sync>;
 
-/// Connects to the signaling server, returning a channel sender to send
+/// Sends opaque blobs to a peer through the server, which relays them without parsing them.
+#[derive(Debug, Clone)]
+pub struct BinarySender {
+    // Frames already addressed to their target.
+    tx: mpsc::Sender<Bytes>,
+}
+
+impl BinarySender {
+    pub async fn send_binary(&self, to: &str, payload: Bytes) -> Result<()> {
+        let frame = encode_binary_frame(to, &payload)
+            .ok_or_else(|| SignalingError::InvalidPeerId(to.to_owned()))?;
+        // The server closes the connection on larger messages.
+        if frame.len() > MAX_MESSAGE_SIZE {
+            return Err(SignalingError::MessageTooLarge(frame.len()));
+        }
+        self.tx.send(frame.into()).await.map_err(|_| SignalingError::Closed)
+    }
+}
+
+/// A connection to the signaling server.
+#[derive(Debug)]
+pub struct SignalingConnection {
+    pub messages: mpsc::Sender<SignalingMessage>,
+    pub binary: BinarySender,
+    /// The ID and short code the server assigned us.
+    pub identity: IdentityPayload,
+}
+
+/// Connects to the signaling server, returning the channels to send
 /// messages to the server. Incoming messages from the server will be sent
-/// to the `to_webrtc_tx` channel.
+/// to the `to_webrtc_tx` channel, and binary ones to `on_binary`.
+/// With an identity key, servers that support it give us the ID derived from it instead of a random one.
+/// Notifying `close` closes the connection, once the messages already sent are written.
 pub async fn connect(
     url: String,
+    identity_key: Option<&IdentityKey>,
     to_webrtc_tx: mpsc::Sender<SignalingMessage>,
-) -> Result<(mpsc::Sender<SignalingMessage>, String)> {
+    on_binary: Option<BinaryCallback>,
+    close: Arc<Notify>,
+) -> Result<SignalingConnection> {
     let (ws_stream, _) = connect_async(url).await.map_err(SignalingError::ConnectionFailed)?;
-    let (write, mut read) = ws_stream.split();
+    let (mut write, mut read) = ws_stream.split();
 
     tracing::info!("Successfully connected to signaling server. Waiting for ID response...");
 
-    let id = match read
-        .next()
-        .await
-        .ok_or(SignalingError::IdResponseError("No response".to_string()))??
-    {
-        Message::Text(body) => {
-            let msg: SignalingMessage = serde_json::from_str(&body)
-                .map_err(|e| SignalingError::IdResponseError(e.to_string()))?;
-            msg.data
-        }
-        _ => return Err(SignalingError::IdResponseError("Invalid response content".to_string())),
-    };
+    let mut identity = read_identity(&mut read).await?;
+    // Servers that don't derive IDs from keys send no nonce.
+    if let (Some(nonce), Some(identity_key)) = (identity.nonce.take(), identity_key) {
+        identity = authenticate(&mut write, &mut read, identity_key, &nonce).await?;
+    }
 
-    tracing::info!("Got ID: {}", id);
+    tracing::info!("Got ID: {}, short code: {:?}", identity.uuid, identity.short_code);
 
     // Channel for sending messages to the server's writer task
     let (to_server_tx, to_server_rx) = mpsc::channel::<SignalingMessage>(100);
-    spawn_writer_task(to_server_rx, write);
-    spawn_reader_task(to_webrtc_tx, read);
+    let (binary_tx, binary_rx) = mpsc::channel::<Bytes>(100);
+    spawn_writer_task(to_server_rx, binary_rx, write, close);
+    spawn_reader_task(to_webrtc_tx, on_binary, read);
+
+    Ok(SignalingConnection {
+        messages: to_server_tx,
+        binary: BinarySender { tx: binary_tx },
+        identity,
+    })
+}
+
+/// The IDs of the two ends of a [`loopback`], the caller first.
+pub const LOOPBACK_IDS: [&str; 2] = ["loopback-caller", "loopback-callee"];
+
+/// Two connections that only reach each other, without a server, e.g. to call yourself.
+/// What is sent on one comes out of the other's `to_webrtc_tx`, from the ID the server would have filled in.
+/// Binary messages aren't passed on, as nothing sends them yet.
+/// Notifying the `close` of either end closes both, once the messages already sent are passed on,
+/// as there is nobody left to talk to.
+pub fn loopback(
+    to_webrtc_tx: [mpsc::Sender<SignalingMessage>; 2],
+    close: [Arc<Notify>; 2],
+) -> [SignalingConnection; 2] {
+    let [caller_tx, callee_tx] = to_webrtc_tx;
+    let [caller_close, callee_close] = close;
+    let [caller_id, callee_id] = LOOPBACK_IDS;
+    let (caller, mut from_caller, mut caller_binary) = loopback_end(caller_id);
+    let (callee, mut from_callee, mut callee_binary) = loopback_end(callee_id);
+
+    tokio::spawn(async move {
+        loop {
+            let (mut message, from, to, to_tx) = tokio::select! {
+                // Messages go first, so a goodbye sent right before closing still makes it over.
+                biased;
+
+                Some(message) = from_caller.recv() => (message, caller_id, callee_id, &callee_tx),
+                Some(message) = from_callee.recv() => (message, callee_id, caller_id, &caller_tx),
+                Some(_) = caller_binary.recv() => continue,
+                Some(_) = callee_binary.recv() => continue,
+                _ = caller_close.notified() => break,
+                _ = callee_close.notified() => break,
+            };
+
+            if message.to != to {
+                tracing::debug!(
+                    "Dropping {} to unknown peer {}",
+                    message.sig_type.name(),
+                    message.to
+                );
+                continue;
+            }
+            message.from = from.to_owned();
+            if to_tx.send(message).await.is_err() {
+                tracing::debug!("Dropping message to {}, which is gone", to);
+            }
+        }
+        tracing::info!("Loopback signaling closed.");
+    });
+
+    [caller, callee]
+}
 
-    Ok((to_server_tx, id))
+// One end of a loopback, and what it sends.
+fn loopback_end(
+    id: &str,
+) -> (SignalingConnection, mpsc::Receiver<SignalingMessage>, mpsc::Receiver<Bytes>) {
+    let (messages, messages_rx) = mpsc::channel(100);
+    let (binary_tx, binary_rx) = mpsc::channel(100);
+    let connection = SignalingConnection {
+        messages,
+        binary: BinarySender { tx: binary_tx },
+        identity: IdentityPayload { uuid: id.to_owned(), short_code: None, nonce: None },
+    };
+    (connection, messages_rx, binary_rx)
+}
+
+/// Connects to the signaling server only to see whether it answers, returning the identity it assigned.
+/// Closes the connection right after, so the identity is gone again.
+pub async fn probe(url: &str) -> Result<IdentityPayload> {
+    let (ws_stream, _) = connect_async(url).await.map_err(SignalingError::ConnectionFailed)?;
+    let (mut write, mut read) = ws_stream.split();
+    let identity = read_identity(&mut read).await?;
+    if let Err(e) = write.close().await {
+        tracing::debug!("Failed to close probe connection: {}", e);
+    }
+    Ok(identity)
+}
+
+// Waits for the server to tell us our identity.
+async fn read_identity(read: &mut WsRead) -> Result<IdentityPayload> {
+    loop {
+        let message = read
+            .next()
+            .await
+            .ok_or(SignalingError::IdResponseError("No response".to_string()))??;
+        let Message::Text(body) = message else {
+            return Err(SignalingError::IdResponseError("Invalid response content".to_string()));
+        };
+        let msg: SignalingMessage = serde_json::from_str(&body)
+            .map_err(|e| SignalingError::IdResponseError(e.to_string()))?;
+        match msg.sig_type {
+            SignalingType::Identity => return Ok(IdentityPayload::parse(&msg.data)),
+            SignalingType::Rejected => return Err(SignalingError::IdentityRejected(msg.data)),
+            // E.g. a broadcast, which isn't for us before we know who we are.
+            _ => tracing::debug!("Ignoring {} message before identity", msg.sig_type.name()),
+        }
+    }
+}
+
+// Proves we hold the key, after which the server sends the identity derived from it.
+async fn authenticate(
+    write: &mut WsWrite,
+    read: &mut WsRead,
+    identity_key: &IdentityKey,
+    nonce: &str,
+) -> Result<IdentityPayload> {
+    let msg = SignalingMessage {
+        to: String::new(),
+        from: String::new(),
+        sig_type: SignalingType::Authenticate,
+        data: serde_json::to_string(&identity_key.authenticate(nonce))?,
+        protocol_version: PROTOCOL_VERSION,
+        sealed: false,
+        mode: None,
+    };
+    write.send(Message::Text(serde_json::to_string(&msg)?.into())).await?;
+    read_identity(read).await
 }
 
 fn spawn_writer_task(
     mut to_server_rx: mpsc::Receiver<SignalingMessage>,
-    mut write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    mut binary_rx: mpsc::Receiver<Bytes>,
+    mut write: WsWrite,
+    close: Arc<Notify>,
 ) {
     tokio::spawn(async move {
-        while let Some(message) = to_server_rx.recv().await {
+        loop {
+            let message = tokio::select! {
+                // Messages go first, so a goodbye sent right before closing still makes it out.
+                biased;
+
+                message = to_server_rx.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                Some(frame) = binary_rx.recv() => {
+                    if write.send(Message::Binary(frame)).await.is_err() {
+                        tracing::error!(
+                            "Failed to send message to signaling server. WebSocket connection closed."
+                        );
+                        break;
+                    }
+                    continue;
+                }
+                _ = close.notified() => {
+                    tracing::info!("Closing signaling WebSocket connection.");
+                    if let Err(e) = write.close().await {
+                        tracing::debug!("Failed to close signaling WebSocket: {}", e);
+                    }
+                    break;
+                }
+            };
+
             match serde_json::to_string(&message) {
                 Ok(json) => {
                     if write.send(Message::Text(json.into())).await.is_err() {
@@ -73,11 +264,13 @@ fn spawn_writer_task(
 
 fn spawn_reader_task(
     to_webrtc_tx: mpsc::Sender<SignalingMessage>,
-    mut read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    on_binary: Option<BinaryCallback>,
+    mut read: WsRead,
 ) {
     tokio::spawn(async move {
         while let Some(message) = read.next().await {
             match message {
+                Ok(Message::Binary(frame)) => handle_binary(&frame, on_binary.as_ref()),
                 Ok(msg) => {
                     if let Message::Text(text) = msg {
                         match serde_json::from_str::<SignalingMessage>(&text) {
@@ -104,3 +297,17 @@ fn spawn_reader_task(
         tracing::info!("Signaling WebSocket reader task finished.");
     });
 }
+
+fn handle_binary(frame: &Bytes, on_binary: Option<&BinaryCallback>) {
+    let Some((from, payload)) = decode_binary_frame(frame) else {
+        tracing::warn!("Dropping malformed binary signaling message");
+        return;
+    };
+    let Some(on_binary) = on_binary else {
+        tracing::debug!("Ignoring binary signaling message from {}", from);
+        return;
+    };
+    // The payload is the tail of the frame, so it is passed on without copying.
+    let payload = frame.slice(frame.len() - payload.len()..);
+    on_binary(from.to_owned(), payload);
+}
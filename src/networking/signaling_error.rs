@@ -6,4 +6,12 @@ pub enum SignalingError {
     DeserializeError(#[from] serde_json::Error),
     #[error("ID response error: {0}")]
     IdResponseError(String),
+    #[error("Server rejected the identity: {0}")]
+    IdentityRejected(String),
+    #[error("Invalid peer ID: {0}")]
+    InvalidPeerId(String),
+    #[error("Message too large: {0} bytes")]
+    MessageTooLarge(usize),
+    #[error("Connection closed")]
+    Closed,
 }
@@ -0,0 +1,38 @@
+use std::{
+    fs, io,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use webrtc::{dtls, peer_connection::certificate::RTCCertificate};
+
+// The peer connection refuses expired certificates, and a new one invalidates what the peers verified.
+const VALIDITY: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// The certificate the DTLS handshakes are made with, kept across runs so the peers can verify its fingerprint once.
+/// Creates and stores one if there is none yet. None if it can't even be created, in which case each connection makes its own.
+pub fn load_or_create(path: &Path) -> Option<RTCCertificate> {
+    match fs::read_to_string(path) {
+        Ok(pem) => match RTCCertificate::from_pem(&pem) {
+            Ok(certificate) => return Some(certificate),
+            Err(e) => tracing::warn!("Failed to parse DTLS certificate, creating a new one: {}", e),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => tracing::warn!("Failed to read DTLS certificate, creating a new one: {}", e),
+    }
+
+    let certificate = dtls::crypto::Certificate::generate_self_signed(vec!["fjarsyn".to_owned()])
+        .inspect_err(|e| tracing::error!("Failed to create DTLS certificate: {}", e))
+        .ok()?;
+    let certificate = RTCCertificate::from_existing(certificate, SystemTime::now() + VALIDITY);
+
+    // Still good for this run if it can't be stored, only the fingerprint changes on the next.
+    let stored = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(path, certificate.serialize_pem()));
+    if let Err(e) = stored {
+        tracing::warn!("Failed to store DTLS certificate: {}", e);
+    }
+    Some(certificate)
+}
@@ -0,0 +1,91 @@
+use webrtc::{
+    api::media_engine::MediaEngine,
+    rtp_transceiver::{
+        RTCPFeedback,
+        rtp_codec::{RTCRtpCodecCapability, RTCRtpCodecParameters, RTPCodecType},
+    },
+};
+
+use crate::media::ffmpeg::FFmpegTranscodeType;
+
+const VIDEO_CLOCK_RATE: u32 = 90000;
+
+fn video_rtcp_feedback() -> Vec<RTCPFeedback> {
+    [("goog-remb", ""), ("ccm", "fir"), ("nack", ""), ("nack", "pli")]
+        .into_iter()
+        .map(|(typ, parameter)| RTCPFeedback {
+            typ: typ.to_owned(),
+            parameter: parameter.to_owned(),
+        })
+        .collect()
+}
+
+// The H.264 profiles any of the decoders can take, whichever encoder the peer uses, with their payload types. These
+// match the ones used by the default media engine for the same parameters.
+const H264_CODECS: [(u8, &str); 3] = [
+    // Constrained baseline.
+    (125, "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f"),
+    // Baseline.
+    (102, "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42001f"),
+    // High.
+    (123, "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=640032"),
+];
+// Main profile.
+const HEVC_CODECS: [(u8, &str); 1] = [(126, "profile-id=1")];
+
+/// The fmtp line describing the bitstream the transcode type's encoder produces.
+fn sdp_fmtp_line(transcode_type: FFmpegTranscodeType) -> &'static str {
+    match transcode_type {
+        // x264 with the ultrafast preset produces constrained baseline.
+        FFmpegTranscodeType::H264Software => H264_CODECS[0].1,
+        // Hardware encoders default to high profile.
+        FFmpegTranscodeType::H264Vulkan | FFmpegTranscodeType::H264Nvenc => H264_CODECS[2].1,
+        FFmpegTranscodeType::H265Vulkan => HEVC_CODECS[0].1,
+    }
+}
+
+/// The capability of the local video track, which always advertises what we will actually send.
+pub fn local_video_capability(transcode_type: FFmpegTranscodeType) -> RTCRtpCodecCapability {
+    RTCRtpCodecCapability {
+        mime_type: transcode_type.mime_type().to_owned(),
+        clock_rate: VIDEO_CLOCK_RATE,
+        channels: 0,
+        sdp_fmtp_line: sdp_fmtp_line(transcode_type).to_owned(),
+        rtcp_feedback: video_rtcp_feedback(),
+    }
+}
+
+/// The video codecs we offer for a transcode type: every profile of its codec we can decode, so peers with other
+/// encoders can still agree on one. The profile we send comes first, so it is the one preferred.
+pub fn video_codecs(transcode_type: FFmpegTranscodeType) -> Vec<RTCRtpCodecParameters> {
+    let codecs: &[(u8, &str)] = match transcode_type {
+        FFmpegTranscodeType::H264Software
+        | FFmpegTranscodeType::H264Vulkan
+        | FFmpegTranscodeType::H264Nvenc => &H264_CODECS,
+        FFmpegTranscodeType::H265Vulkan => &HEVC_CODECS,
+    };
+    let local = sdp_fmtp_line(transcode_type);
+    let (sent, others): (Vec<_>, Vec<_>) = codecs.iter().partition(|(_, fmtp)| *fmtp == local);
+    sent.into_iter()
+        .chain(others)
+        .map(|&(payload_type, fmtp)| RTCRtpCodecParameters {
+            capability: RTCRtpCodecCapability {
+                sdp_fmtp_line: fmtp.to_owned(),
+                ..local_video_capability(transcode_type)
+            },
+            payload_type,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Registers only the video codecs usable with the transcode type, so the peers can't negotiate one we can't handle.
+pub fn register_video_codecs(
+    media_engine: &mut MediaEngine,
+    transcode_type: FFmpegTranscodeType,
+) -> Result<(), webrtc::Error> {
+    for codec in video_codecs(transcode_type) {
+        media_engine.register_codec(codec, RTPCodecType::Video)?;
+    }
+    Ok(())
+}
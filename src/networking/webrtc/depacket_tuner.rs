@@ -0,0 +1,74 @@
+/// Picks how many packets the sample builder waits for, from how many it had to drop.
+/// Starts at the least, and doubles the wait when too many packets are dropped, up to the most.
+/// Halves it again once nothing was dropped for a while, as the wait holds up every frame missing a packet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepacketTuner {
+    min: u16,
+    max: u16,
+    latency: u16,
+    // The intervals in a row without drops.
+    clean_intervals: u32,
+    // The intervals left to wait out after a change, as it only takes effect at the next keyframe.
+    cooldown: u32,
+}
+
+impl DepacketTuner {
+    /// The share of the packets dropped in an interval that makes the wait longer.
+    pub const FAILURE_THRESHOLD: f64 = 0.005;
+    /// The intervals without drops that make the wait shorter.
+    pub const CLEAN_INTERVALS: u32 = 30;
+    /// The intervals after a change before it can grow again.
+    pub const COOLDOWN_INTERVALS: u32 = 3;
+
+    pub fn new(min: u16, max: u16) -> Self {
+        let min = min.clamp(1, max.max(1));
+        Self { min, max: max.max(min), latency: min, clean_intervals: 0, cooldown: 0 }
+    }
+
+    pub fn latency(&self) -> u16 {
+        self.latency
+    }
+
+    /// Caps the wait, shortening it right away if it is above.
+    pub fn set_max(&mut self, max: u16) {
+        self.max = max.max(1);
+        self.min = self.min.min(self.max);
+        self.latency = self.latency.clamp(self.min, self.max);
+    }
+
+    /// Feeds the packets received and dropped over an interval. Returns the new wait when it changes.
+    pub fn update(&mut self, received: u64, dropped: u64) -> Option<u16> {
+        let cooling_down = self.cooldown > 0;
+        self.cooldown = self.cooldown.saturating_sub(1);
+        // Nothing to go by while no video comes in.
+        if received == 0 {
+            return None;
+        }
+
+        if dropped == 0 {
+            self.clean_intervals += 1;
+            if self.clean_intervals < Self::CLEAN_INTERVALS {
+                return None;
+            }
+            self.clean_intervals = 0;
+            return self.change((self.latency / 2).max(self.min));
+        }
+
+        self.clean_intervals = 0;
+        let failure_rate = dropped as f64 / (received + dropped) as f64;
+        if failure_rate <= Self::FAILURE_THRESHOLD || cooling_down {
+            return None;
+        }
+        self.cooldown = Self::COOLDOWN_INTERVALS;
+        self.change(self.latency.saturating_mul(2).min(self.max))
+    }
+
+    fn change(&mut self, latency: u16) -> Option<u16> {
+        if latency == self.latency {
+            return None;
+        }
+        tracing::debug!("Tuning depacket latency from {} to {} packets", self.latency, latency);
+        self.latency = latency;
+        Some(latency)
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt::Display;
+
+/// A DTLS certificate fingerprint, as a session description announces it in its `a=fingerprint` line.
+/// Whoever relays the session descriptions could swap it for their own, so the peers compare them out of band.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    /// The hash function, like "sha-256", in lowercase.
+    pub algorithm: String,
+    pub bytes: Vec<u8>,
+}
+
+impl Fingerprint {
+    /// Parses the value of an `a=fingerprint` attribute, like "sha-256 AB:CD:...".
+    pub fn parse(value: &str) -> Option<Self> {
+        let (algorithm, hex) = value.trim().split_once(' ')?;
+        let bytes = hex
+            .trim()
+            .split(':')
+            .map(|byte| match byte.len() {
+                2 => u8::from_str_radix(byte, 16).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if algorithm.is_empty() || bytes.is_empty() {
+            return None;
+        }
+        Some(Self { algorithm: algorithm.to_ascii_lowercase(), bytes })
+    }
+
+    /// The first fingerprint of the session description, be it for the session or one of its media.
+    /// WebRTC uses the same certificate for all of them.
+    pub fn from_sdp(sdp: &str) -> Option<Self> {
+        sdp.lines()
+            .find_map(|line| line.trim().strip_prefix("a=fingerprint:").and_then(Self::parse))
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex: Vec<_> = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        write!(f, "{} {}", self.algorithm, hex.join(":"))
+    }
+}
+
+/// The fingerprints of both ends of a call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallFingerprints {
+    pub local: Fingerprint,
+    pub remote: Fingerprint,
+}
+
+impl CallFingerprints {
+    // Without the letters that are easily mistaken for digits when read aloud or off a screen.
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+    const CODE_LEN: usize = 8;
+
+    /// A short code for the users to read to each other, like "7FQ2-K9PL".
+    /// Both ends get the same one, as it doesn't depend on which end is local.
+    /// Anyone in between has their own certificate on each side, which changes the code on both.
+    pub fn short_auth_string(&self) -> String {
+        // The fingerprints are hashes already, so combining them keeps them as unpredictable.
+        let bits: Vec<u8> = self
+            .local
+            .bytes
+            .iter()
+            .zip(&self.remote.bytes)
+            .map(|(local, remote)| local ^ remote)
+            .collect();
+
+        let mut code = String::with_capacity(Self::CODE_LEN + 1);
+        for index in 0..Self::CODE_LEN {
+            if index == Self::CODE_LEN / 2 {
+                code.push('-');
+            }
+            // Five bits per character.
+            let bit = index * 5;
+            let pair = u16::from_be_bytes([
+                bits.get(bit / 8).copied().unwrap_or(0),
+                bits.get(bit / 8 + 1).copied().unwrap_or(0),
+            ]);
+            let value = (pair >> (11 - bit % 8)) & 0x1F;
+            code.push(Self::ALPHABET[value as usize] as char);
+        }
+        code
+    }
+}
@@ -0,0 +1,15 @@
+/// What an end does with its own offer when both peers call each other at once, each offer sent before the
+/// other arrived. Neither end can answer while its own offer is pending, so one of them has to give it up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlareRole {
+    /// Keeps its offer and ignores the peer's, waiting for the answer to its own.
+    Keep,
+    /// Takes its offer back and answers the peer's instead.
+    Yield,
+}
+
+/// The role of this end, from its ID and the peer's. Both ends compare the same two IDs,
+/// so the one with the greater ID keeps its offer and the other always yields.
+pub fn glare_role(local_id: &str, remote_id: &str) -> GlareRole {
+    if local_id > remote_id { GlareRole::Keep } else { GlareRole::Yield }
+}
@@ -0,0 +1,34 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// Which connection something happened on. Each [`WebRTC`](super::WebRTC) connects to a single peer,
+/// and gets an ID no other connection in the process has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct PeerId(pub u32);
+
+impl PeerId {
+    /// An ID no connection had before.
+    pub fn next() -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Display for PeerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "peer {}", self.0)
+    }
+}
+
+/// Which of a connection's remote tracks a packet or event belongs to, numbered in the order they started.
+/// The default is the first track, which is the only one of a call with a single video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct TrackId(pub u32);
+
+impl Display for TrackId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "track {}", self.0)
+    }
+}
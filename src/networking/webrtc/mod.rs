@@ -1,5 +1,21 @@
+mod certificate;
+pub mod codecs;
+mod depacket_tuner;
+mod fingerprint;
+mod glare;
+mod ids;
+mod packet_router;
+mod sample_reassembler;
+mod source_gate;
 pub mod webrtc;
 mod webrtc_error;
 
-pub use webrtc::{WebRTC, WebRTCEvent};
+pub use depacket_tuner::DepacketTuner;
+pub use fingerprint::{CallFingerprints, Fingerprint};
+pub use glare::{GlareRole, glare_role};
+pub use ids::{PeerId, TrackId};
+pub use packet_router::PacketRouter;
+pub use sample_reassembler::{ReassemblyCounts, SampleReassembler};
+pub use source_gate::{Admit, SourceGate};
+pub use webrtc::{PeerEvent, SourceReplacement, WebRTC, WebRTCEvent, WebRTCReceivers};
 pub use webrtc_error::WebRTCError;
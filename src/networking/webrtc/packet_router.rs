@@ -0,0 +1,75 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use bytes::Bytes;
+use tokio::sync::{Mutex, mpsc};
+use tracing::Instrument;
+
+use crate::networking::webrtc::TrackId;
+
+type TrackReceiver = Arc<Mutex<mpsc::Receiver<Bytes>>>;
+
+#[derive(Debug, Default)]
+struct Tracks {
+    channels: HashMap<TrackId, (mpsc::Sender<Bytes>, TrackReceiver)>,
+    // Once the packets ended, the tracks' channels are closed as soon as they are made.
+    closed: bool,
+}
+
+/// Splits the packets of a connection by the track they were received on, so each track can have a decoder of its own.
+/// A track's packets wait in a channel of their own from the first one on, whether or not anything reads them yet.
+#[derive(Debug, Clone)]
+pub struct PacketRouter {
+    tracks: Arc<StdMutex<Tracks>>,
+    buffer: usize,
+}
+
+impl PacketRouter {
+    /// Starts routing the packets from the receiver, with as many waiting for each track as it holds.
+    /// The tracks' channels close once it does.
+    pub fn spawn(mut packets: mpsc::Receiver<(TrackId, Bytes)>) -> Self {
+        let router = Self { tracks: Arc::default(), buffer: packets.max_capacity() };
+        let routing = router.clone();
+        tokio::spawn(
+            async move {
+                while let Some((track, packet)) = packets.recv().await {
+                    let sender = routing.channel(track).0;
+                    // As slow as the slowest track, the same as a single track waits for its decoder.
+                    if sender.send(packet).await.is_err() {
+                        tracing::debug!("Dropped a packet of {}, which was closed", track);
+                    }
+                }
+                let mut tracks = routing.tracks.lock().unwrap();
+                tracks.channels.clear();
+                tracks.closed = true;
+            }
+            .in_current_span(),
+        );
+        router
+    }
+
+    fn channel(&self, track: TrackId) -> (mpsc::Sender<Bytes>, TrackReceiver) {
+        let new_channel = || {
+            let (sender, receiver) = mpsc::channel(self.buffer);
+            (sender, Arc::new(Mutex::new(receiver)))
+        };
+        let mut tracks = self.tracks.lock().unwrap();
+        if tracks.closed {
+            return new_channel();
+        }
+        tracks.channels.entry(track).or_insert_with(new_channel).clone()
+    }
+
+    /// The packets of the track, for a [`DecoderWorker`](crate::media::decoder_worker::DecoderWorker) to decode.
+    /// Closed already if the connection is.
+    pub fn track(&self, track: TrackId) -> TrackReceiver {
+        self.channel(track).1
+    }
+
+    /// Drops what is left of a track that ended, so its packets don't wait for a decoder that never comes.
+    pub fn remove(&self, track: TrackId) {
+        self.tracks.lock().unwrap().channels.remove(&track);
+    }
+}
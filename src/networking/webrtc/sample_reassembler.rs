@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+
+use webrtc::{
+    media::{Sample, io::sample_builder::SampleBuilder},
+    rtp::{packet::Packet, packetizer::Depacketizer},
+};
+
+use crate::media::nal::{self, NalCodec};
+
+/// What a [`SampleReassembler`] did so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReassemblyCounts {
+    pub packets: u64,
+    pub samples: u64,
+    /// The packets the builder gave up on, as the rest of their sample didn't come in time.
+    pub dropped_packets: u64,
+}
+
+/// Reassembles RTP packets into samples, waiting up to `max_late` packets for the ones missing.
+/// `max_late` can be changed while the packets keep coming. A builder with the new value is fed alongside the current one,
+/// and takes over at the next keyframe, so no sample is lost or repeated on the way.
+pub struct SampleReassembler<D: Depacketizer + Default> {
+    builder: SampleBuilder<D>,
+    max_late: u16,
+    clock_rate: u32,
+    // Recognizes keyframes by their NAL units. Without, every sample counts as one.
+    codec: Option<NalCodec>,
+    next: Option<NextBuilder<D>>,
+    // The samples the next builder completed before it took over, which come after the one it took over at.
+    backlog: VecDeque<Sample>,
+    // The timestamp of the sample the next builder took over at, until it has moved past it.
+    switched_at: Option<u32>,
+    counts: ReassemblyCounts,
+}
+
+struct NextBuilder<D: Depacketizer> {
+    builder: SampleBuilder<D>,
+    max_late: u16,
+    completed: VecDeque<(Sample, u32)>,
+    // The samples popped since, to give up on waiting for a keyframe eventually.
+    waited: usize,
+}
+
+impl<D: Depacketizer + Default> SampleReassembler<D> {
+    // Keyframes may be a long way apart, or not recognized at all, e.g. for samples without start codes.
+    const MAX_SWITCH_WAIT: usize = 600;
+    // The samples kept from the next builder, which only needs the ones completed since the last keyframe.
+    const MAX_BACKLOG: usize = 64;
+
+    pub fn new(max_late: u16, clock_rate: u32, codec: Option<NalCodec>) -> Self {
+        Self {
+            builder: SampleBuilder::new(max_late, D::default(), clock_rate),
+            max_late,
+            clock_rate,
+            codec,
+            next: None,
+            backlog: VecDeque::new(),
+            switched_at: None,
+            counts: ReassemblyCounts::default(),
+        }
+    }
+
+    pub fn counts(&self) -> ReassemblyCounts {
+        self.counts
+    }
+
+    /// The packets the current builder waits for, which lags behind the last one set until the next keyframe.
+    pub fn max_late(&self) -> u16 {
+        self.max_late
+    }
+
+    /// Waits up to this many packets from the next keyframe on.
+    pub fn set_max_late(&mut self, max_late: u16) {
+        match &self.next {
+            Some(next) if next.max_late == max_late => return,
+            None if self.max_late == max_late => return,
+            _ => (),
+        }
+        if self.max_late == max_late {
+            // Back to what is in use already, so there is nothing to switch to.
+            self.next = None;
+            return;
+        }
+
+        tracing::debug!("Switching max depacket latency from {} to {}", self.max_late, max_late);
+        self.next = Some(NextBuilder {
+            builder: SampleBuilder::new(max_late, D::default(), self.clock_rate),
+            max_late,
+            completed: VecDeque::new(),
+            waited: 0,
+        });
+    }
+
+    pub fn push(&mut self, packet: Packet) {
+        self.counts.packets += 1;
+        if let Some(next) = &mut self.next {
+            next.builder.push(packet.clone());
+            while let Some(completed) = next.builder.pop_with_timestamp() {
+                if next.completed.len() == Self::MAX_BACKLOG {
+                    next.completed.pop_front();
+                }
+                next.completed.push_back(completed);
+            }
+        }
+        self.builder.push(packet);
+    }
+
+    /// The next sample that is complete, in order.
+    pub fn pop(&mut self) -> Option<Sample> {
+        let sample = self.next_sample()?;
+        self.counts.samples += 1;
+        // Padding carries no media, so nothing is lost with it.
+        self.counts.dropped_packets +=
+            u64::from(sample.prev_dropped_packets.saturating_sub(sample.prev_padding_packets));
+        Some(sample)
+    }
+
+    fn next_sample(&mut self) -> Option<Sample> {
+        if let Some(sample) = self.backlog.pop_front() {
+            return Some(sample);
+        }
+
+        loop {
+            let (sample, timestamp) = self.builder.pop_with_timestamp()?;
+            // The builder that took over may still complete samples that were already popped from the one before.
+            if let Some(switched_at) = self.switched_at {
+                if !is_newer(timestamp, switched_at) {
+                    continue;
+                }
+                self.switched_at = None;
+            }
+
+            if self.is_switch_point(&sample) {
+                self.switch(timestamp);
+            }
+            return Some(sample);
+        }
+    }
+
+    fn is_switch_point(&mut self, sample: &Sample) -> bool {
+        let Some(next) = &mut self.next else {
+            return false;
+        };
+        next.waited += 1;
+        next.waited >= Self::MAX_SWITCH_WAIT
+            || self.codec.is_none_or(|codec| nal::is_keyframe(&sample.data, codec))
+    }
+
+    // Hands over to the next builder after the sample at the timestamp, which was popped from the current one.
+    fn switch(&mut self, timestamp: u32) {
+        let Some(next) = self.next.take() else {
+            return;
+        };
+        tracing::debug!("Max depacket latency is now {}", next.max_late);
+        self.builder = next.builder;
+        self.max_late = next.max_late;
+        self.backlog = next
+            .completed
+            .into_iter()
+            .filter(|(_, completed_at)| is_newer(*completed_at, timestamp))
+            .map(|(sample, _)| sample)
+            .collect();
+        self.switched_at = Some(timestamp);
+    }
+}
+
+// RTP timestamps wrap around, so the difference tells which one is ahead.
+fn is_newer(timestamp: u32, than: u32) -> bool {
+    (timestamp.wrapping_sub(than) as i32) > 0
+}
@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+
+use crate::media::nal::{self, NalCodec};
+
+/// What happens to a sample written to the video track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admit {
+    /// Dropped, as the peer couldn't decode it without what came before it.
+    Drop,
+    /// Written, after moving the timestamps on by the gap, if any, so they match the time the video was gone.
+    Write { gap: Option<Duration> },
+}
+
+/// Lets the samples through to the video track, and starts a new video source cleanly.
+/// Once restarted, the samples are dropped until a keyframe, which the encoders send along with the parameter sets,
+/// so the peer doesn't decode the new source with the old source's. The timestamps then jump by the time the video
+/// was gone, rather than carrying on as if the new source followed the old one straight away.
+#[derive(Debug, Clone)]
+pub struct SourceGate {
+    // None if the samples can't be told apart, and are all let through.
+    codec: Option<NalCodec>,
+    restarting: bool,
+    // When the next sample is due, going by the duration of the last one written.
+    next_due: Option<Instant>,
+}
+
+impl SourceGate {
+    pub fn new(codec: Option<NalCodec>) -> Self {
+        Self { codec, restarting: false, next_due: None }
+    }
+
+    /// A new source takes over from the one before, from its next keyframe.
+    pub fn restart(&mut self) {
+        self.restarting = true;
+    }
+
+    pub fn is_restarting(&self) -> bool {
+        self.restarting
+    }
+
+    pub fn admit(&mut self, sample: &[u8], duration: Duration, now: Instant) -> Admit {
+        let mut gap = None;
+        if self.restarting {
+            if self.codec.is_some_and(|codec| !nal::is_keyframe(sample, codec)) {
+                return Admit::Drop;
+            }
+            self.restarting = false;
+            gap = self
+                .next_due
+                .map(|due| now.saturating_duration_since(due))
+                .filter(|gap| !gap.is_zero());
+        }
+        self.next_due = Some(now + duration);
+        Admit::Write { gap }
+    }
+}
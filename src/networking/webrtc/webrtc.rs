@@ -1,122 +1,479 @@
 use std::{
-    sync::{Arc, RwLock},
-    time::Duration,
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use bytes::Bytes;
-use fjarsyn_shared::{SignalingMessage, SignalingType};
-use tokio::sync::mpsc;
+use fjarsyn_shared::{
+    CallMode, ControlMessage, IdentityPayload, PROTOCOL_VERSION, SignalingMessage, SignalingType,
+};
+use tokio::{
+    sync::{Notify, broadcast, mpsc, oneshot},
+    task::JoinSet,
+};
+use tracing::{Instrument, Span};
 #[cfg(debug_assertions)]
 use webrtc::ice_transport::ice_connection_state::RTCIceConnectionState;
 use webrtc::{
     api::{
-        APIBuilder,
-        media_engine::{MIME_TYPE_H264, MediaEngine},
+        API, APIBuilder,
+        media_engine::{MIME_TYPE_H264, MIME_TYPE_HEVC, MediaEngine},
+    },
+    data_channel::{
+        RTCDataChannel, data_channel_init::RTCDataChannelInit,
+        data_channel_message::DataChannelMessage,
     },
     ice_transport::{
         ice_candidate::{RTCIceCandidate, RTCIceCandidateInit},
         ice_server::RTCIceServer,
     },
-    media::{Sample, io::sample_builder::SampleBuilder},
+    media::Sample,
     peer_connection::{
-        RTCPeerConnection, configuration::RTCConfiguration,
+        RTCPeerConnection,
+        certificate::RTCCertificate,
+        configuration::RTCConfiguration,
         peer_connection_state::RTCPeerConnectionState,
-        sdp::session_description::RTCSessionDescription,
+        sdp::{sdp_type::RTCSdpType, session_description::RTCSessionDescription},
+        signaling_state::RTCSignalingState,
     },
     rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication,
-    rtp_transceiver::rtp_codec::{RTCRtpCodecCapability, RTPCodecType},
-    track::track_local::track_local_static_sample::TrackLocalStaticSample,
+    rtp::{
+        codecs::{h264::H264Packet, h265::H265Packet},
+        packetizer::Depacketizer,
+    },
+    rtp_transceiver::{
+        RTCRtpTransceiverInit, rtp_codec::RTPCodecType, rtp_sender::RTCRtpSender,
+        rtp_transceiver_direction::RTCRtpTransceiverDirection,
+    },
+    stats::StatsReportType,
+    track::{
+        track_local::track_local_static_sample::TrackLocalStaticSample, track_remote::TrackRemote,
+    },
 };
 
-use crate::networking::{
-    signaling,
-    webrtc::{WebRTCError, webrtc_error::WebRTCResult},
+use crate::{
+    config::Config,
+    media::{
+        ffmpeg::FFmpegTranscodeType,
+        nal::NalCodec,
+        stats::{ConnectionStats, DepacketStats},
+    },
+    networking::{
+        identity::IdentityKey,
+        impairment::{ImpairedLink, Impairment, ImpairmentConfig, ImpairmentStats},
+        sealed_signaling::{KeyExchange, SignalingSeal},
+        sealed_signaling_error::{SealedSignalingError, SealedSignalingResult},
+        signaling::{self, BinarySender, SignalingConnection},
+        webrtc::{
+            Admit, CallFingerprints, DepacketTuner, Fingerprint, GlareRole, PeerId,
+            ReassemblyCounts, SampleReassembler, SourceGate, TrackId, WebRTCError, certificate,
+            codecs, glare_role, webrtc_error::WebRTCResult,
+        },
+    },
+    utils::call_span::{CallDirection, call_span},
 };
 
+/// How [`WebRTC::replace_video_source`] handed the video over to the new source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceReplacement {
+    /// The new source carries on on the same track, from its first keyframe.
+    Reset,
+    /// The new source needs a track of its own, which the peer is told about in a new offer.
+    Renegotiated,
+}
+
+// The local video track, which a new source can replace, along with what sends it.
+// It is only sent once a call starts, and not at all when only viewing.
+#[derive(Debug)]
+struct VideoOutput {
+    track: RwLock<Arc<TrackLocalStaticSample>>,
+    sender: RwLock<Option<Arc<RTCRtpSender>>>,
+    gate: Mutex<SourceGate>,
+    mode: RwLock<CallMode>,
+}
+
+impl VideoOutput {
+    fn new(track: Arc<TrackLocalStaticSample>) -> Self {
+        let gate = SourceGate::new(NalCodec::from_mime_type(&track.codec().mime_type));
+        Self {
+            track: RwLock::new(track),
+            sender: RwLock::new(None),
+            gate: Mutex::new(gate),
+            mode: RwLock::new(CallMode::default()),
+        }
+    }
+
+    fn track(&self) -> Arc<TrackLocalStaticSample> {
+        self.track.read().unwrap().clone()
+    }
+
+    fn sender(&self) -> Option<Arc<RTCRtpSender>> {
+        self.sender.read().unwrap().clone()
+    }
+
+    fn mode(&self) -> CallMode {
+        *self.mode.read().unwrap()
+    }
+
+    // Takes the samples of the new source from its first keyframe.
+    // A track that isn't sent yet drops what is written to it, until it is.
+    fn replace_track(&self, track: Arc<TrackLocalStaticSample>) {
+        let mut gate = SourceGate::new(NalCodec::from_mime_type(&track.codec().mime_type));
+        gate.restart();
+        *self.gate.lock().unwrap() = gate;
+        *self.track.write().unwrap() = track;
+    }
+
+    // A new peer connection doesn't send the track yet, so it is added to it again.
+    fn forget_sender(&self) {
+        *self.sender.write().unwrap() = None;
+    }
+
+    // The keyframe may have gone to the track before it was sent, so it waits for another.
+    fn replace_sender(&self, sender: Arc<RTCRtpSender>) {
+        *self.sender.write().unwrap() = Some(sender);
+        self.gate.lock().unwrap().restart();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum WebRTCEvent {
+    /// The peer answered the call we made, in the mode they chose, and the connection to them is being made.
+    Answered(CallMode),
     Connected,
+    /// The connection to the peer dropped, and may come back on its own.
+    Interrupted,
+    /// The call is over, as the peer hung up or the connection to them failed for good.
     Disconnected,
-    IncomingCall(String),
+    /// The peer calls us, in the mode they chose. It rings until [`WebRTC::accept_call`] or [`WebRTC::decline_call`].
+    IncomingCall(String, CallMode),
+    /// We called the peer just as they called us, and answer their call in place of ours.
+    /// It comes instead of [`Self::IncomingCall`], as the call is the one already ringing.
+    CallsCrossed(String, CallMode),
+    /// A remote video track started, with the codec that was negotiated for it.
+    TrackStarted {
+        track: TrackId,
+        mime_type: String,
+    },
+    /// A remote video track ended, e.g. as the peer replaced it with one for another codec.
+    TrackEnded {
+        track: TrackId,
+    },
+    Control(ControlMessage),
+    /// A call couldn't be set up, as only one of the peers seals its signaling, or they use different passphrases.
+    SignalingRejected(String),
+}
+
+/// An event, and the connection it happened on.
+#[derive(Debug, Clone)]
+pub struct PeerEvent {
+    pub peer: PeerId,
+    pub event: WebRTCEvent,
+}
+
+// Sends the events of a connection, tagged with its ID.
+#[derive(Debug, Clone)]
+struct EventSink {
+    peer: PeerId,
+    tx: mpsc::Sender<PeerEvent>,
+}
+
+impl EventSink {
+    async fn send(&self, event: WebRTCEvent) -> Result<(), mpsc::error::SendError<PeerEvent>> {
+        self.tx.send(PeerEvent { peer: self.peer, event }).await
+    }
+}
+
+/// Where a [`WebRTC`] delivers what it receives. Each connection has its own,
+/// so nothing a replaced connection still receives can end up with the one replacing it.
+#[derive(Debug)]
+pub struct WebRTCReceivers {
+    /// The received video packets, reassembled into samples, with the track they were received on.
+    pub packets: mpsc::Receiver<(TrackId, Bytes)>,
+    pub events: mpsc::Receiver<PeerEvent>,
 }
 
 /// Holds the state for the WebRTC connection
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct WebRTC {
-    pub peer_connection: Arc<RTCPeerConnection>,
+    peer_id: PeerId,
+    // Replaced when a call has to start over on a new peer connection.
+    connection: Arc<RwLock<Connection>>,
+    factory: Arc<ConnectionFactory>,
     pub signaling_tx: mpsc::Sender<SignalingMessage>,
-    pub video_track: Arc<TrackLocalStaticSample>,
+    pub signaling_binary: BinarySender,
+    video: Arc<VideoOutput>,
     pub remote_peer_id: Arc<RwLock<Option<String>>>,
-    pub local_peer_id: Arc<RwLock<Option<String>>>,
+    pub local_identity: Arc<RwLock<Option<IdentityPayload>>>,
+    // The peer whose offer waits for the user to accept or decline it.
+    ringing: Arc<RwLock<Option<String>>>,
+    // The span of the current call, which is disabled between calls.
+    call_span: Arc<RwLock<Span>>,
+    signaling_close: Arc<Notify>,
+    // The tasks spawned for the connection, which shutting it down waits for.
+    tasks: Arc<Mutex<JoinSet<()>>>,
+    // The packets to wait for when reassembling samples, which tracks starting later pick up.
+    depacket_latency: Arc<AtomicU16>,
+    // Whether the tracks tune the depacket latency themselves, up to the one set.
+    depacket_auto_tune: Arc<AtomicBool>,
+    // Tells the running tracks that the depacket latency changed.
+    depacket_control: broadcast::Sender<()>,
+    // The depacket latency in effect on each remote track, and what it adds.
+    depacket_stats: Arc<Mutex<HashMap<TrackId, DepacketStats>>>,
+    sealed_signaling: SealedSender,
+    // Only in debug builds, when the network is made worse on purpose.
+    send_impairment: Option<ImpairedLink<Sample>>,
+    receive_impairment: Option<ImpairedLink<(TrackId, Bytes)>>,
+}
+
+// RTCDataChannel doesn't implement Debug.
+impl std::fmt::Debug for WebRTC {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebRTC")
+            .field("peer_id", &self.peer_id)
+            .field("peer_connection", &self.peer_connection())
+            .field("signaling_tx", &self.signaling_tx)
+            .field("signaling_binary", &self.signaling_binary)
+            .field("video", &self.video)
+            .field("control_channel", &self.control_channel().label())
+            .field("remote_peer_id", &self.remote_peer_id)
+            .field("local_identity", &self.local_identity)
+            .field("ringing", &self.ringing)
+            .field("call_span", &self.call_span)
+            .field("signaling_close", &self.signaling_close)
+            .field("tasks", &self.tasks.lock().unwrap().len())
+            .field("depacket_latency", &self.depacket_latency)
+            .field("depacket_auto_tune", &self.depacket_auto_tune)
+            .field("depacket_stats", &self.depacket_stats)
+            .field("sealed_signaling", &self.sealed_signaling)
+            .field("send_impairment", &self.send_impairment)
+            .field("receive_impairment", &self.receive_impairment)
+            .finish()
+    }
 }
 
 impl WebRTC {
     const STREAM_ID: &str = "fjarsyn-webrtc";
+    const CONTROL_CHANNEL_LABEL: &str = "control";
+    // Both peers create the control channel up front with this id, instead of announcing it in-band.
+    const CONTROL_CHANNEL_ID: u16 = 0;
+    const EVENT_BUFFER: usize = 100;
+    const SIGNALING_BUFFER: usize = 100;
+    const CERTIFICATE_FILE: &str = "dtls_certificate.pem";
+    const IDENTITY_KEY_FILE: &str = "identity_key";
+    // Only whether something changed matters, not how often.
+    const DEPACKET_CONTROL_BUFFER: usize = 1;
+    // Tasks still running after this long are aborted, e.g. one waiting out its interval.
+    const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+    /// The server that tells the peers their public addresses.
+    pub const STUN_SERVER: &str = "stun:stun.l.google.com:19302";
 
+    /// Connects to the signaling server, returning the connection along with the receivers of what it receives.
     pub async fn init(
         signaling_url: String,
-        packet_sink: mpsc::Sender<Bytes>,
-        event_tx: mpsc::Sender<WebRTCEvent>,
         max_depacket_latency: u16,
-    ) -> WebRTCResult<Self> {
-        let (signal_tx, mut signal_rx) = mpsc::channel(100);
-        let (signaling_tx, id) = signaling::connect(signaling_url, signal_tx).await?;
+        auto_depacket_latency: bool,
+        packet_buffer: usize,
+        transcode_type: FFmpegTranscodeType,
+    ) -> WebRTCResult<(Self, WebRTCReceivers)> {
+        let (signal_tx, signal_rx) = mpsc::channel(Self::SIGNALING_BUFFER);
+        let signaling_close = Arc::new(Notify::new());
+        let identity_key = Config::dir()
+            .and_then(|dir| IdentityKey::load_or_create(&dir.join(Self::IDENTITY_KEY_FILE)));
+        // Nothing is sent as binary yet, so whatever arrives is only logged.
+        let connection = signaling::connect(
+            signaling_url,
+            identity_key.as_ref(),
+            signal_tx,
+            None,
+            signaling_close.clone(),
+        )
+        .await?;
+        let certificate = Config::dir()
+            .and_then(|dir| certificate::load_or_create(&dir.join(Self::CERTIFICATE_FILE)));
+
+        let signaling = Signaling { connection, incoming: signal_rx, close: signaling_close };
+        Self::with_signaling(
+            signaling,
+            certificate,
+            max_depacket_latency,
+            auto_depacket_latency,
+            packet_buffer,
+            transcode_type,
+        )
+        .await
+    }
+
+    /// Two connections that only reach each other, without a signaling server, the caller first.
+    /// Neither calls yet, the caller has to call the callee by its ID in [`signaling::LOOPBACK_IDS`].
+    pub async fn loopback(
+        max_depacket_latency: u16,
+        auto_depacket_latency: bool,
+        packet_buffer: usize,
+        transcode_type: FFmpegTranscodeType,
+    ) -> WebRTCResult<[(Self, WebRTCReceivers); 2]> {
+        let (caller_tx, caller_rx) = mpsc::channel(Self::SIGNALING_BUFFER);
+        let (callee_tx, callee_rx) = mpsc::channel(Self::SIGNALING_BUFFER);
+        let [caller_close, callee_close] = [Arc::new(Notify::new()), Arc::new(Notify::new())];
+        let [caller, callee] = signaling::loopback(
+            [caller_tx, callee_tx],
+            [caller_close.clone(), callee_close.clone()],
+        );
+
+        // Each end makes its own certificate, like two machines would, and the stored one stays for real calls.
+        let caller = Self::with_signaling(
+            Signaling { connection: caller, incoming: caller_rx, close: caller_close },
+            None,
+            max_depacket_latency,
+            auto_depacket_latency,
+            packet_buffer,
+            transcode_type,
+        )
+        .await?;
+        let callee = Self::with_signaling(
+            Signaling { connection: callee, incoming: callee_rx, close: callee_close },
+            None,
+            max_depacket_latency,
+            auto_depacket_latency,
+            packet_buffer,
+            transcode_type,
+        )
+        .await?;
+        Ok([caller, callee])
+    }
+
+    // Sets up the peer connection, signaling over the connection given.
+    async fn with_signaling(
+        signaling: Signaling,
+        certificate: Option<RTCCertificate>,
+        max_depacket_latency: u16,
+        auto_depacket_latency: bool,
+        packet_buffer: usize,
+        transcode_type: FFmpegTranscodeType,
+    ) -> WebRTCResult<(Self, WebRTCReceivers)> {
+        let Signaling {
+            connection:
+                SignalingConnection { messages: signaling_tx, binary: signaling_binary, identity },
+            incoming: mut signal_rx,
+            close: signaling_close,
+        } = signaling;
+
+        // A zero sized channel panics, and the packets have to wait somewhere.
+        let (packet_sink, packets) = mpsc::channel(packet_buffer.max(1));
+        let peer_id = PeerId::next();
+        let (event_tx, events) = mpsc::channel(Self::EVENT_BUFFER);
+        let event_tx = EventSink { peer: peer_id, tx: event_tx };
+        let tasks = Arc::new(Mutex::new(JoinSet::new()));
+        let depacket_latency = Arc::new(AtomicU16::new(max_depacket_latency));
+        let depacket_auto_tune = Arc::new(AtomicBool::new(auto_depacket_latency));
+        let (depacket_control, _) = broadcast::channel(Self::DEPACKET_CONTROL_BUFFER);
+        let depacket_stats = Arc::new(Mutex::new(HashMap::new()));
 
         let mut m = MediaEngine::default();
-        m.register_default_codecs().map_err(WebRTCError::CodecError)?;
+        codecs::register_video_codecs(&mut m, transcode_type).map_err(WebRTCError::CodecError)?;
         let api = APIBuilder::new().with_media_engine(m).build();
         let config = RTCConfiguration {
             ice_servers: vec![RTCIceServer {
-                urls: vec!["stun:stun.l.google.com:19302".to_owned()],
+                urls: vec![Self::STUN_SERVER.to_owned()],
                 ..Default::default()
             }],
+            certificates: certificate.into_iter().collect(),
             ..Default::default()
         };
-        let peer_connection =
-            api.new_peer_connection(config).await.map_err(WebRTCError::PeerConnectionError)?;
-        let peer_connection = Arc::new(peer_connection);
 
         let video_track = Arc::new(TrackLocalStaticSample::new(
-            RTCRtpCodecCapability { mime_type: MIME_TYPE_H264.to_owned(), ..Default::default() },
+            codecs::local_video_capability(transcode_type),
             "video".to_owned(),
             Self::STREAM_ID.to_owned(),
         ));
-        let rtc_rtp_sender = peer_connection
-            .add_track(Arc::clone(&video_track)
-                as Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync>)
-            .await
-            .map_err(WebRTCError::PeerConnectionError)?;
+        let impairment = cfg!(debug_assertions).then(ImpairmentConfig::from_env).flatten();
+        if let Some(config) = impairment {
+            tracing::warn!(
+                "Impairing the network on purpose, for the video {}: {}",
+                config.direction(),
+                config
+            );
+        }
+        let video = Arc::new(VideoOutput::new(video_track));
 
-        tokio::spawn(async move {
-            let Ok((packets, _attributes)) = rtc_rtp_sender.read_rtcp().await else {
-                tracing::error!("Error reading RTCP packets");
-                return;
-            };
-            for packet in packets {
-                tracing::debug!("Received RTCP packet: {:?}", packet);
-            }
+        let send_impairment = impairment.filter(|config| config.send).map(|config| {
+            let video = video.clone();
+            ImpairedLink::spawn(Impairment::new(config, Instant::now()), move |sample: Sample| {
+                let video_track = video.track();
+                async move {
+                    if let Err(e) = video_track.write_sample(&sample).await {
+                        tracing::error!("Failed to write impaired sample: {}", e);
+                    }
+                    true
+                }
+            })
+        });
+        let receive_impairment = impairment.filter(|config| config.receive).map(|config| {
+            let packet_sink = packet_sink.clone();
+            ImpairedLink::spawn(Impairment::new(config, Instant::now()), move |data| {
+                let packet_sink = packet_sink.clone();
+                async move { packet_sink.send(data).await.is_ok() }
+            })
         });
+        let sample_sink = match receive_impairment.clone() {
+            Some(link) => SampleSink::Impaired(link),
+            None => SampleSink::Direct(packet_sink),
+        };
 
         let remote_peer_id = Arc::new(RwLock::<Option<String>>::new(None));
-        let local_peer_id = Arc::new(RwLock::<Option<String>>::new(Some(id)));
+        let local_identity = Arc::new(RwLock::new(Some(identity)));
+        let ringing = Arc::new(RwLock::new(None));
+        let call_span = Arc::new(RwLock::new(Span::none()));
+        let sealed_signaling = SealedSender::new(signaling_tx.clone());
+
+        let factory = Arc::new(ConnectionFactory {
+            api,
+            config,
+            event_sink: event_tx.clone(),
+            remote_peer_id: remote_peer_id.clone(),
+            sealed_signaling: sealed_signaling.clone(),
+            sample_sink,
+            call_span: call_span.clone(),
+            tasks: tasks.clone(),
+            depacket_latency: depacket_latency.clone(),
+            depacket_auto_tune: depacket_auto_tune.clone(),
+            depacket_control: depacket_control.clone(),
+            depacket_stats: depacket_stats.clone(),
+            next_track: Arc::new(AtomicU32::new(0)),
+        });
+        let connection = Arc::new(RwLock::new(factory.create().await?));
 
         // Task to handle incoming signaling messages
-        let pc_reader_clone = Arc::clone(&peer_connection);
-        let remote_peer_id_clone = remote_peer_id.clone();
-        let local_peer_id_clone = local_peer_id.clone();
-        let signaling_tx_reader = signaling_tx.clone();
+        let media_reader = CallMedia {
+            connection: connection.clone(),
+            factory: factory.clone(),
+            video: video.clone(),
+            tasks: tasks.clone(),
+        };
+        let call_reader = CallState {
+            remote_peer_id: remote_peer_id.clone(),
+            local_identity: local_identity.clone(),
+            ringing: ringing.clone(),
+            current_call: call_span.clone(),
+        };
         let event_sink_reader = event_tx.clone();
+        let sealed_signaling_reader = sealed_signaling.clone();
 
-        tokio::spawn(async move {
+        tasks.lock().unwrap().spawn(async move {
             while let Some(msg) = signal_rx.recv().await {
+                let span = call_reader.current_call.read().unwrap().clone();
                 if let Err(e) = handle_signaling_message(
                     msg,
-                    pc_reader_clone.clone(),
-                    remote_peer_id_clone.clone(),
-                    local_peer_id_clone.clone(),
-                    signaling_tx_reader.clone(),
+                    media_reader.clone(),
+                    call_reader.clone(),
+                    sealed_signaling_reader.clone(),
                     event_sink_reader.clone(),
                 )
+                .instrument(span)
                 .await
                 {
                     tracing::error!("Error handling signaling message: {}", e);
@@ -125,11 +482,492 @@ impl WebRTC {
             tracing::info!("WebRTC signaling reader task finished.");
         });
 
+        let webrtc = Self {
+            peer_id,
+            connection,
+            factory,
+            signaling_tx,
+            signaling_binary,
+            video,
+            remote_peer_id,
+            local_identity,
+            ringing,
+            call_span,
+            signaling_close,
+            tasks,
+            depacket_latency,
+            depacket_auto_tune,
+            depacket_control,
+            depacket_stats,
+            sealed_signaling,
+            send_impairment,
+            receive_impairment,
+        };
+        Ok((webrtc, WebRTCReceivers { packets, events }))
+    }
+
+    /// The peer connection of the current call. One that had to start over is on a new one.
+    pub fn peer_connection(&self) -> Arc<RTCPeerConnection> {
+        self.connection.read().unwrap().peer_connection.clone()
+    }
+
+    pub fn control_channel(&self) -> Arc<RTCDataChannel> {
+        self.connection.read().unwrap().control_channel.clone()
+    }
+
+    fn media(&self) -> CallMedia {
+        CallMedia {
+            connection: self.connection.clone(),
+            factory: self.factory.clone(),
+            video: self.video.clone(),
+            tasks: self.tasks.clone(),
+        }
+    }
+
+    pub fn get_local_id(&self) -> Option<String> {
+        self.local_identity.read().unwrap().as_ref().map(|identity| identity.uuid.clone())
+    }
+
+    /// The short code the server assigned, which is easier to pass on than the ID.
+    /// None if the server doesn't hand out codes.
+    pub fn get_local_short_code(&self) -> Option<String> {
+        self.local_identity
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|identity| identity.short_code.clone())
+    }
+
+    pub fn get_remote_id(&self) -> Option<String> {
+        self.remote_peer_id.read().unwrap().clone()
+    }
+
+    /// The span of the current call, to run the work done for it in. Disabled when there is no call.
+    pub fn call_span(&self) -> Span {
+        self.call_span.read().unwrap().clone()
+    }
+
+    /// Sets the packets to wait for when reassembling samples, or the most to wait for when tuned to the drops.
+    /// Tracks already running switch over at their next keyframe.
+    pub fn set_depacket_latency(&self, max_depacket_latency: u16, auto_tune: bool) {
+        let latency_changed = self.depacket_latency.swap(max_depacket_latency, Ordering::Relaxed)
+            != max_depacket_latency;
+        let auto_tune_changed =
+            self.depacket_auto_tune.swap(auto_tune, Ordering::Relaxed) != auto_tune;
+        if latency_changed || auto_tune_changed {
+            // Nobody listens when no track is running.
+            let _ = self.depacket_control.send(());
+        }
+    }
+
+    /// Which connection this is, as its events say.
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    /// The depacket latency in effect on the remote track, and what it adds.
+    /// Only the latency is known until the track has run for a while.
+    pub fn depacket_stats(&self, track: TrackId) -> DepacketStats {
+        self.depacket_stats.lock().unwrap().get(&track).copied().unwrap_or_else(|| DepacketStats {
+            max_latency: self.depacket_latency.load(Ordering::Relaxed),
+            ..Default::default()
+        })
+    }
+
+    /// What the impairment did to the video sent and to the video received, if the network is made worse on purpose.
+    pub fn impairment_stats(&self) -> [Option<ImpairmentStats>; 2] {
+        [
+            self.send_impairment.as_ref().map(ImpairedLink::stats),
+            self.receive_impairment.as_ref().map(ImpairedLink::stats),
+        ]
+    }
+
+    /// The round trip time, the loss the peer reports on the video sent, the bandwidth estimate and the bytes so far,
+    /// as far as known.
+    /// Leaves out what only the caller knows, i.e. the target bitrate, the loss received and freezes.
+    pub async fn connection_stats(&self) -> ConnectionStats {
+        let mut stats = ConnectionStats::default();
+        for report in self.peer_connection().get_stats().await.reports.into_values() {
+            match report {
+                StatsReportType::CandidatePair(pair) if pair.nominated => {
+                    if pair.current_round_trip_time > 0.0 {
+                        stats.round_trip_time =
+                            Some(Duration::from_secs_f64(pair.current_round_trip_time));
+                    }
+                    // Zero when there is no estimate.
+                    if pair.available_outgoing_bitrate > 0.0 {
+                        stats.available_bitrate = Some(pair.available_outgoing_bitrate);
+                    }
+                    stats.bytes_sent = Some(pair.bytes_sent);
+                    stats.bytes_received = Some(pair.bytes_received);
+                }
+                StatsReportType::RemoteInboundRTP(remote) if remote.kind == "video" => {
+                    stats.packet_loss = Some(remote.fraction_lost as f32);
+                    if stats.round_trip_time.is_none() {
+                        stats.round_trip_time = remote.round_trip_time.map(Duration::from_secs_f64);
+                    }
+                }
+                _ => (),
+            }
+        }
+        stats
+    }
+
+    /// Seals the signaling of the calls from now on with a key derived from the passphrase, or stops sealing it if empty.
+    /// The peer has to use the same passphrase.
+    pub fn set_signaling_passphrase(&self, passphrase: &str) {
+        let mut seal = self.sealed_signaling.seal.write().unwrap();
+        // A new seal derives its keys again, which takes a while.
+        if seal.as_deref().and_then(SignalingSeal::passphrase).unwrap_or("") == passphrase {
+            return;
+        }
+        *seal = (!passphrase.is_empty()).then(|| Arc::new(SignalingSeal::new(passphrase)));
+    }
+
+    /// Seals the signaling of the calls from now on with a key agreed with each peer, while no passphrase is set.
+    /// The peer has to turn it on as well. The server could take part in the exchange, which the security code shows.
+    pub fn set_signaling_key_exchange(&self, enabled: bool) {
+        let mut key_exchange = self.sealed_signaling.key_exchange.write().unwrap();
+        if key_exchange.is_some() == enabled {
+            return;
+        }
+        *key_exchange = enabled.then(|| Arc::new(KeyExchange::new()));
+        self.sealed_signaling.agreed.lock().unwrap().clear();
+    }
+
+    /// What our end does in the calls made or answered from now on. One that only views never sends video,
+    /// so the peer connection is set up without a track for it.
+    pub fn set_call_mode(&self, mode: CallMode) {
+        *self.video.mode.write().unwrap() = mode;
+    }
+
+    pub fn call_mode(&self) -> CallMode {
+        self.video.mode()
+    }
+
+    pub fn is_signaling_sealed(&self) -> bool {
+        self.sealed_signaling.seal().is_some() || self.sealed_signaling.key_exchange().is_some()
+    }
+
+    /// The certificate fingerprints of both ends, once the session descriptions have been exchanged.
+    pub async fn fingerprints(&self) -> Option<CallFingerprints> {
+        let peer_connection = self.peer_connection();
+        let local = peer_connection.local_description().await?;
+        let remote = peer_connection.remote_description().await?;
+        Some(CallFingerprints {
+            local: Fingerprint::from_sdp(&local.sdp)?,
+            remote: Fingerprint::from_sdp(&remote.sdp)?,
+        })
+    }
+
+    pub async fn write_sample(&self, data: Vec<u8>, duration: Duration) -> WebRTCResult<()> {
+        let admit = self.video.gate.lock().unwrap().admit(&data, duration, Instant::now());
+        let gap = match admit {
+            Admit::Drop => {
+                tracing::trace!("Dropping sample until the new source's keyframe");
+                return Ok(());
+            }
+            Admit::Write { gap } => gap,
+        };
+        // An empty sample sends nothing, but moves the timestamps on by its duration.
+        if let Some(gap) = gap {
+            tracing::debug!("Video source resumed after {:?}", gap);
+            self.send_sample(Sample { duration: gap, ..Default::default() }).await?;
+        }
+        self.send_sample(Sample { data: data.into(), duration, ..Default::default() }).await
+    }
+
+    async fn send_sample(&self, sample: Sample) -> WebRTCResult<()> {
+        if let Some(impairment) = &self.send_impairment {
+            let len = sample.data.len();
+            impairment.send(sample, len);
+            return Ok(());
+        }
+        self.video.track().write_sample(&sample).await.map_err(WebRTCError::WriteRTPError)
+    }
+
+    /// Hands the video over to a new source, e.g. when sharing restarts within a call, before it writes its first sample.
+    /// A source with the codec of the track carries on on it from its first keyframe. One with another codec gets a new track,
+    /// which the peer is offered in the background, as the peers have to agree on it first.
+    pub fn replace_video_source(&self, codec: FFmpegTranscodeType) -> SourceReplacement {
+        let track = self.video.track();
+        if track.codec().mime_type.eq_ignore_ascii_case(codec.mime_type()) {
+            self.video.gate.lock().unwrap().restart();
+            return SourceReplacement::Reset;
+        }
+
+        tracing::info!(
+            "Replacing the {} video track with a {} one",
+            track.codec().mime_type,
+            codec.mime_type()
+        );
+        let track = Arc::new(TrackLocalStaticSample::new(
+            codecs::local_video_capability(codec),
+            "video".to_owned(),
+            Self::STREAM_ID.to_owned(),
+        ));
+        self.video.replace_track(track.clone());
+        let webrtc = self.clone();
+        self.tasks.lock().unwrap().spawn(
+            async move {
+                if let Err(e) = webrtc.renegotiate_video(track).await {
+                    tracing::error!("Failed to renegotiate the video track: {}", e);
+                }
+            }
+            .instrument(self.call_span()),
+        );
+        SourceReplacement::Renegotiated
+    }
+
+    // Sends the track in place of the previous one, and offers it to the peer.
+    async fn renegotiate_video(&self, track: Arc<TrackLocalStaticSample>) -> WebRTCResult<()> {
+        let peer_connection = self.peer_connection();
+        if let Some(previous) = self.video.sender() {
+            peer_connection
+                .remove_track(&previous)
+                .await
+                .map_err(WebRTCError::PeerConnectionError)?;
+        }
+        let sender = peer_connection
+            .add_track(
+                Arc::clone(&track) as Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync>
+            )
+            .await
+            .map_err(WebRTCError::PeerConnectionError)?;
+        self.tasks.lock().unwrap().spawn(read_rtcp(sender.clone()));
+        self.video.replace_sender(sender);
+
+        let Some(remote_peer_id) = self.get_remote_id() else {
+            return Ok(());
+        };
+        self.send_offer(remote_peer_id).await
+    }
+
+    /// Answers the call that is ringing, if any.
+    pub async fn accept_call(&self) -> WebRTCResult<()> {
+        let Some(peer_id) = self.ringing.write().unwrap().take() else {
+            return Ok(());
+        };
+        send_answer(peer_id, &self.media(), &self.sealed_signaling)
+            .instrument(self.call_span())
+            .await
+    }
+
+    /// Turns down the call that is ringing, if any, so the peer stops ringing, and the next call can come in.
+    pub async fn decline_call(&self) -> WebRTCResult<()> {
+        let Some(peer_id) = self.ringing.write().unwrap().take() else {
+            return Ok(());
+        };
+        tracing::info!("Declining the call from {}", peer_id);
+        {
+            let mut remote_peer_id = self.remote_peer_id.write().unwrap();
+            if remote_peer_id.as_deref() == Some(peer_id.as_str()) {
+                *remote_peer_id = None;
+            }
+        }
+        *self.call_span.write().unwrap() = Span::none();
+
+        if let Err(e) = send_bye(&peer_id, &self.sealed_signaling).await {
+            tracing::warn!("Failed to send Bye to the declined peer: {}", e);
+        }
+        roll_back_remote_offer(&self.peer_connection()).await
+    }
+
+    pub async fn send_control(&self, message: &ControlMessage) -> WebRTCResult<()> {
+        let data = serde_json::to_vec(message).map_err(WebRTCError::SerializeError)?;
+        self.control_channel()
+            .send(&Bytes::from(data))
+            .await
+            .map_err(WebRTCError::DataChannelError)?;
+        Ok(())
+    }
+
+    pub async fn create_offer(&self, target_id: String) -> WebRTCResult<()> {
+        let span = call_span(&target_id, CallDirection::Outgoing);
+        *self.call_span.write().unwrap() = span.clone();
+        async {
+            self.sealed_signaling.exchange_keys(&target_id).await?;
+            self.send_offer(target_id).await
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn send_offer(&self, target_id: String) -> WebRTCResult<()> {
+        let peer_connection = self.peer_connection();
+        set_up_video(&peer_connection, &self.video, &self.tasks, true).await?;
+        let offer =
+            peer_connection.create_offer(None).await.map_err(WebRTCError::PeerConnectionError)?;
+
+        let sdp = offer.sdp.clone();
+        peer_connection
+            .set_local_description(offer)
+            .await
+            .map_err(WebRTCError::PeerConnectionError)?;
+
+        // Update remote ID since we are initiating call to them
+        *self.remote_peer_id.write().unwrap() = Some(target_id.clone());
+
+        let msg = SignalingMessage {
+            to: target_id,
+            from: String::new(),
+            sig_type: SignalingType::Offer,
+            data: sdp,
+            protocol_version: PROTOCOL_VERSION,
+            sealed: false,
+            mode: Some(self.video.mode()),
+        };
+        self.sealed_signaling.send(msg).await
+    }
+
+    /// Hangs up, telling the peer so it doesn't have to wait for the connection to time out.
+    pub async fn disconnect(&self) -> WebRTCResult<()> {
+        let remote_peer_id = self.remote_peer_id.write().unwrap().take();
+        if let Some(remote_peer_id) = remote_peer_id {
+            let msg = SignalingMessage {
+                to: remote_peer_id,
+                from: String::new(),
+                sig_type: SignalingType::Bye,
+                data: String::new(),
+                protocol_version: PROTOCOL_VERSION,
+                sealed: false,
+                mode: None,
+            };
+            // The peer still notices the connection dropping, just later.
+            if let Err(e) = self.signaling_tx.send(msg).await {
+                tracing::warn!("Failed to send Bye to peer: {}", e);
+            }
+        }
+
+        self.peer_connection().close().await.map_err(WebRTCError::PeerConnectionError)?;
+        *self.call_span.write().unwrap() = Span::none();
+        Ok(())
+    }
+
+    /// Closes the connection to the signaling server, after the messages already sent.
+    pub fn close_signaling(&self) {
+        self.signaling_close.notify_one();
+    }
+
+    /// Hangs up, closes the connection to the signaling server, and waits for the tasks of the connection to end.
+    /// Once it returns, nothing is sent to the receivers of the connection anymore.
+    pub async fn shutdown(&self) -> WebRTCResult<()> {
+        let result = self.disconnect().await;
+        self.close_signaling();
+
+        self.connection.read().unwrap().detach();
+
+        if let Some(impairment) = &self.send_impairment {
+            impairment.close();
+        }
+        if let Some(impairment) = &self.receive_impairment {
+            impairment.close();
+        }
+
+        let mut tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        let drained = tokio::time::timeout(Self::SHUTDOWN_TIMEOUT, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            tracing::warn!("{} WebRTC tasks didn't end in time, aborting them", tasks.len());
+            tasks.shutdown().await;
+        }
+        tracing::info!("WebRTC connection shut down.");
+        result
+    }
+}
+
+// Where a connection signals its calls: what it sends them with, what it receives them on, and what closes it.
+struct Signaling {
+    connection: SignalingConnection,
+    incoming: mpsc::Receiver<SignalingMessage>,
+    close: Arc<Notify>,
+}
+
+// The peer connection of the calls, and the control channel on it.
+#[derive(Clone)]
+struct Connection {
+    peer_connection: Arc<RTCPeerConnection>,
+    control_channel: Arc<RTCDataChannel>,
+}
+
+impl Connection {
+    // The handlers hold on to the senders for as long as the peer connection is around, which may be a while yet.
+    fn detach(&self) {
+        self.peer_connection.on_track(Box::new(|_, _, _| Box::pin(async {})));
+        self.peer_connection.on_peer_connection_state_change(Box::new(|_| Box::pin(async {})));
+        self.peer_connection.on_ice_candidate(Box::new(|_| Box::pin(async {})));
+        self.control_channel.on_message(Box::new(|_| Box::pin(async {})));
+    }
+
+    // Closes the peer connection without anything of it reaching the events, as the call goes on on another.
+    async fn discard(self) -> WebRTCResult<()> {
+        self.detach();
+        self.peer_connection.close().await.map_err(WebRTCError::PeerConnectionError)
+    }
+}
+
+// Creates the peer connections of the calls, with the handlers that pass on what they receive.
+// A call that has to start over gets a new one, as webrtc 0.14 can't take back an offer we made.
+struct ConnectionFactory {
+    api: API,
+    config: RTCConfiguration,
+    event_sink: EventSink,
+    remote_peer_id: Arc<RwLock<Option<String>>>,
+    sealed_signaling: SealedSender,
+    sample_sink: SampleSink,
+    call_span: Arc<RwLock<Span>>,
+    tasks: Arc<Mutex<JoinSet<()>>>,
+    depacket_latency: Arc<AtomicU16>,
+    depacket_auto_tune: Arc<AtomicBool>,
+    depacket_control: broadcast::Sender<()>,
+    depacket_stats: Arc<Mutex<HashMap<TrackId, DepacketStats>>>,
+    // Counted across the peer connections, so the tracks of a new one aren't taken for those of the last.
+    next_track: Arc<AtomicU32>,
+}
+
+impl ConnectionFactory {
+    async fn create(&self) -> WebRTCResult<Connection> {
+        let peer_connection = self
+            .api
+            .new_peer_connection(self.config.clone())
+            .await
+            .map_err(WebRTCError::PeerConnectionError)?;
+        let peer_connection = Arc::new(peer_connection);
+
+        let control_channel = peer_connection
+            .create_data_channel(
+                WebRTC::CONTROL_CHANNEL_LABEL,
+                Some(RTCDataChannelInit {
+                    ordered: Some(true),
+                    negotiated: Some(WebRTC::CONTROL_CHANNEL_ID),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .map_err(WebRTCError::DataChannelError)?;
+
+        let event_sink_control = self.event_sink.clone();
+        control_channel.on_message(Box::new(move |msg: DataChannelMessage| {
+            let event_sink = event_sink_control.clone();
+            Box::pin(async move {
+                match serde_json::from_slice::<ControlMessage>(&msg.data) {
+                    Ok(message) => {
+                        if let Err(e) = event_sink.send(WebRTCEvent::Control(message)).await {
+                            tracing::error!("Failed to send Control event: {}", e);
+                        }
+                    }
+                    Err(e) => tracing::warn!("Received invalid control message: {}", e),
+                }
+            })
+        }));
+
         // ICE candidate handling
-        let signaling_tx_clone = signaling_tx.clone();
-        let remote_peer_id_ice = remote_peer_id.clone();
+        let sealed_signaling_ice = self.sealed_signaling.clone();
+        let remote_peer_id_ice = self.remote_peer_id.clone();
         peer_connection.on_ice_candidate(Box::new(move |c: Option<RTCIceCandidate>| {
-            let signaling_tx = signaling_tx_clone.clone();
+            let sealed_signaling = sealed_signaling_ice.clone();
             let remote_peer_id = remote_peer_id_ice.clone();
             Box::pin(async move {
                 let Some(candidate) = c else {
@@ -146,8 +984,11 @@ impl WebRTC {
                             from: String::new(),
                             sig_type: SignalingType::Candidate,
                             data: candidate_str,
+                            protocol_version: PROTOCOL_VERSION,
+                            sealed: false,
+                            mode: None,
                         };
-                        if let Err(e) = signaling_tx.send(msg).await {
+                        if let Err(e) = sealed_signaling.send(msg).await {
                             tracing::error!("Failed to send ICE candidate: {}", e);
                         }
                     }
@@ -158,20 +999,23 @@ impl WebRTC {
             })
         }));
 
-        let event_sink_state = event_tx.clone();
+        let event_sink_state = self.event_sink.clone();
         peer_connection.on_peer_connection_state_change(Box::new(
             move |s: RTCPeerConnectionState| {
                 tracing::debug!("Peer Connection State has changed: {}", s);
                 let event_sink = event_sink_state.clone();
                 Box::pin(async move {
-                    if s == RTCPeerConnectionState::Connected {
-                        if let Err(e) = event_sink.send(WebRTCEvent::Connected).await {
-                            tracing::error!("Failed to send Connected event: {}", e);
+                    let event = match s {
+                        RTCPeerConnectionState::Connected => WebRTCEvent::Connected,
+                        // ICE keeps trying until it fails, so this may still recover.
+                        RTCPeerConnectionState::Disconnected => WebRTCEvent::Interrupted,
+                        RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed => {
+                            WebRTCEvent::Disconnected
                         }
-                    } else if s == RTCPeerConnectionState::Disconnected
-                        || s == RTCPeerConnectionState::Closed
-                    {
-                        let _ = event_sink.send(WebRTCEvent::Disconnected).await;
+                        _ => return,
+                    };
+                    if let Err(e) = event_sink.send(event).await {
+                        tracing::debug!("Failed to send connection state event: {}", e);
                     }
                 })
             },
@@ -184,19 +1028,40 @@ impl WebRTC {
         }));
 
         let pc = Arc::downgrade(&peer_connection);
+        let event_sink_track = self.event_sink.clone();
+        let call_span_track = self.call_span.clone();
+        let tasks_track = self.tasks.clone();
+        let depacket_latency_track = self.depacket_latency.clone();
+        let depacket_auto_tune_track = self.depacket_auto_tune.clone();
+        let depacket_control_track = self.depacket_control.clone();
+        let depacket_stats_track = self.depacket_stats.clone();
+        let next_track = self.next_track.clone();
+        let sample_sink = self.sample_sink.clone();
         peer_connection.on_track(Box::new(move |track, _rtp_receiver, rtp_transceiver| {
+            let span = call_span_track.read().unwrap().clone();
+            let _entered = span.enter();
             tracing::debug!("Received track: {}", track.id());
 
             let media_ssrc = track.ssrc();
 
             match track.kind() {
                 RTPCodecType::Video => {
+                    let track_id = TrackId(next_track.fetch_add(1, Ordering::Relaxed));
                     let pc = pc.clone();
-                    let packet_sink = packet_sink.clone();
+                    let sample_sink = sample_sink.clone();
+                    let event_sink = event_sink_track.clone();
                     let rtp_transceiver = rtp_transceiver.clone();
+                    let depacketing = Depacketing {
+                        track: track_id,
+                        latency: depacket_latency_track.clone(),
+                        auto_tune: depacket_auto_tune_track.clone(),
+                        control: depacket_control_track.subscribe(),
+                        stats: depacket_stats_track.clone(),
+                    };
 
+                    let mut tasks = tasks_track.lock().unwrap();
                     // We just send a PLI every 3 seconds for now.
-                    tokio::spawn(async move {
+                    tasks.spawn(async move {
                         // Get the local SSRC from the transceiver
                         let sender = rtp_transceiver.sender().await;
                         let params = sender.get_parameters().await;
@@ -218,26 +1083,28 @@ impl WebRTC {
                                 }
                             };
                         }
-                    });
+                    }.in_current_span());
 
-                    tokio::spawn(async move {
-                        tracing::debug!("Track with type '{}' starting...", track.codec().capability.mime_type);
+                    tasks.spawn(async move {
+                        let mime_type = track.codec().capability.mime_type;
+                        tracing::debug!("Track {} with type '{}' starting...", track_id.0, mime_type);
 
-                        let depacketizer = webrtc::rtp::codecs::h264::H264Packet::default();
-                        let mut sample_builder = SampleBuilder::new(max_depacket_latency, depacketizer, track.codec().capability.clock_rate);
+                        if let Err(e) = event_sink.send(WebRTCEvent::TrackStarted { track: track_id, mime_type: mime_type.clone() }).await {
+                            tracing::error!("Failed to send TrackStarted event: {}", e);
+                        }
 
-                        while let Ok((rtp, _attributes)) = track.read_rtp().await {
-                            sample_builder.push(rtp);
-                            while let Some(sample) = sample_builder.pop() {
-                                if let Err(e) = packet_sink.send(sample.data).await {
-                                    tracing::error!("Failed to send received frame to sink: {}", e);
-                                    return;
-                                }
-                            }
+                        // The depacketizer has to match the negotiated codec, or the decoder only gets garbage.
+                        match mime_type.to_ascii_lowercase() {
+                            m if m == MIME_TYPE_H264.to_ascii_lowercase() => forward_samples::<H264Packet>(&track, depacketing, &sample_sink).await,
+                            m if m == MIME_TYPE_HEVC.to_ascii_lowercase() => forward_samples::<H265Packet>(&track, depacketing, &sample_sink).await,
+                            _ => tracing::error!("No depacketizer for codec '{}', ignoring track", mime_type),
                         }
 
-                        tracing::debug!("Track with type '{}' finished.", track.codec().capability.mime_type);
-                    });
+                        tracing::debug!("Track {} with type '{}' finished.", track_id.0, mime_type);
+                        if let Err(e) = event_sink.send(WebRTCEvent::TrackEnded { track: track_id }).await {
+                            tracing::debug!("Failed to send TrackEnded event: {}", e);
+                        }
+                    }.in_current_span());
 
                 }
                 _ => {
@@ -249,119 +1116,490 @@ impl WebRTC {
         }));
 
         tracing::info!("Peer connection created.");
+        Ok(Connection { peer_connection, control_channel })
+    }
+}
+
+// Sends the signaling of the calls, sealed if a passphrase is set, or with the key agreed with the peer
+// when sealing by key exchange instead.
+#[derive(Debug, Clone)]
+struct SealedSender {
+    tx: mpsc::Sender<SignalingMessage>,
+    seal: Arc<RwLock<Option<Arc<SignalingSeal>>>>,
+    key_exchange: Arc<RwLock<Option<Arc<KeyExchange>>>>,
+    // The seals agreed with the peers so far, by the ID or code they were reached by.
+    agreed: Arc<Mutex<HashMap<String, Arc<SignalingSeal>>>>,
+    awaiting_key: Arc<Mutex<Option<AwaitedKey>>>,
+}
+
+// The peer our public key went to, and what to tell once its own came back.
+#[derive(Debug)]
+struct AwaitedKey {
+    peer: String,
+    agreed: oneshot::Sender<()>,
+}
+
+impl SealedSender {
+    const KEY_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(10);
 
-        Ok(Self { peer_connection, signaling_tx, video_track, remote_peer_id, local_peer_id })
+    fn new(tx: mpsc::Sender<SignalingMessage>) -> Self {
+        Self {
+            tx,
+            seal: Arc::new(RwLock::new(None)),
+            key_exchange: Arc::new(RwLock::new(None)),
+            agreed: Arc::new(Mutex::new(HashMap::new())),
+            awaiting_key: Arc::new(Mutex::new(None)),
+        }
     }
 
-    pub fn get_local_id(&self) -> Option<String> {
-        self.local_peer_id.read().unwrap().clone()
+    fn seal(&self) -> Option<Arc<SignalingSeal>> {
+        self.seal.read().unwrap().clone()
     }
 
-    pub fn get_remote_id(&self) -> Option<String> {
-        self.remote_peer_id.read().unwrap().clone()
+    // Only when no passphrase is set, which takes its place otherwise.
+    fn key_exchange(&self) -> Option<Arc<KeyExchange>> {
+        self.key_exchange.read().unwrap().clone().filter(|_| self.seal().is_none())
     }
 
-    pub async fn write_sample(&self, data: Vec<u8>, duration: Duration) -> WebRTCResult<()> {
-        let sample = Sample { data: data.into(), duration, ..Default::default() };
-        self.video_track.write_sample(&sample).await.map_err(WebRTCError::WriteRTPError)?;
-        Ok(())
+    // The seal of the signaling with the peer, which is an error when sealing by key exchange before a key was agreed.
+    fn seal_for(&self, peer: &str) -> SealedSignalingResult<Option<Arc<SignalingSeal>>> {
+        if let Some(seal) = self.seal() {
+            return Ok(Some(seal));
+        }
+        if self.key_exchange().is_none() {
+            return Ok(None);
+        }
+        let seal = self.agreed.lock().unwrap().get(peer).cloned();
+        seal.map(Some).ok_or(SealedSignalingError::NoAgreedKey)
     }
 
-    pub async fn create_offer(&self, target_id: String) -> WebRTCResult<()> {
-        let offer = self
-            .peer_connection
-            .create_offer(None)
-            .await
-            .map_err(WebRTCError::PeerConnectionError)?;
+    async fn send(&self, msg: SignalingMessage) -> WebRTCResult<()> {
+        let msg = if SignalingSeal::seals(&msg.sig_type) {
+            let seal = self.seal_for(&msg.to)?;
+            SignalingSeal::seal_message(seal.as_deref(), msg).await?
+        } else {
+            msg
+        };
+        self.tx.send(msg).await.map_err(WebRTCError::SendError)
+    }
 
-        let sdp = offer.sdp.clone();
-        self.peer_connection
-            .set_local_description(offer)
-            .await
-            .map_err(WebRTCError::PeerConnectionError)?;
+    async fn open(&self, msg: SignalingMessage) -> SealedSignalingResult<SignalingMessage> {
+        if !SignalingSeal::seals(&msg.sig_type) {
+            return Ok(msg);
+        }
+        let seal = match self.seal_for(&msg.from) {
+            Ok(seal) => seal,
+            // That the peer doesn't seal at all tells more than the key that is missing.
+            Err(_) if !msg.sealed => return Err(SealedSignalingError::PeerNotEncrypted),
+            Err(e) => return Err(e),
+        };
+        SignalingSeal::open_message(seal.as_deref(), msg).await
+    }
 
-        // Update remote ID since we are initiating call to them
-        *self.remote_peer_id.write().unwrap() = Some(target_id.clone());
+    // Sends our public key to the peer, and waits for its own, so the call to it can be sealed.
+    // Done for each call, as the peer may have a new key since the last one.
+    async fn exchange_keys(&self, peer: &str) -> WebRTCResult<()> {
+        let Some(key_exchange) = self.key_exchange() else {
+            return Ok(());
+        };
+        let (done, agreed) = oneshot::channel();
+        self.agreed.lock().unwrap().remove(peer);
+        *self.awaiting_key.lock().unwrap() =
+            Some(AwaitedKey { peer: peer.to_owned(), agreed: done });
+
+        tracing::debug!("Exchanging keys with {}", peer);
+        self.send_public_key(peer, &key_exchange).await?;
+        match tokio::time::timeout(Self::KEY_EXCHANGE_TIMEOUT, agreed).await {
+            Ok(Ok(())) => Ok(()),
+            _ => {
+                self.awaiting_key.lock().unwrap().take();
+                Err(SealedSignalingError::KeyExchangeTimedOut.into())
+            }
+        }
+    }
 
+    // Agrees on a key with the peer that sent its public key, and sends ours back unless it answers ours.
+    // Keys that cross on the way are taken as the answer on both ends, which agree on the same key all the same.
+    async fn take_public_key(&self, peer: &str, public_key: &str) -> WebRTCResult<()> {
+        let Some(key_exchange) = self.key_exchange() else {
+            return Err(SealedSignalingError::PeerRequiresKeyExchange.into());
+        };
+        let seal = Arc::new(key_exchange.agree(public_key)?);
+
+        let awaiting = self.awaiting_key.lock().unwrap().take();
+        let answered = {
+            let mut agreed = self.agreed.lock().unwrap();
+            agreed.insert(peer.to_owned(), seal.clone());
+            match awaiting {
+                // Called by its code, the peer answers with its ID, so the seal goes by both.
+                Some(AwaitedKey { peer: called, agreed: done }) => {
+                    agreed.insert(called, seal);
+                    let _ = done.send(());
+                    true
+                }
+                None => false,
+            }
+        };
+        if answered {
+            return Ok(());
+        }
+        self.send_public_key(peer, &key_exchange).await
+    }
+
+    async fn send_public_key(&self, peer: &str, key_exchange: &KeyExchange) -> WebRTCResult<()> {
         let msg = SignalingMessage {
-            to: target_id,
+            to: peer.to_owned(),
             from: String::new(),
-            sig_type: SignalingType::Offer,
-            data: sdp,
+            sig_type: SignalingType::KeyExchange,
+            data: key_exchange.public_key(),
+            protocol_version: PROTOCOL_VERSION,
+            sealed: false,
+            mode: None,
         };
+        self.tx.send(msg).await.map_err(WebRTCError::SendError)
+    }
+}
 
-        self.signaling_tx.send(msg).await.map_err(WebRTCError::SendError)?;
+// What a track needs to follow the depacket latency set, and to report the one in effect.
+struct Depacketing {
+    track: TrackId,
+    latency: Arc<AtomicU16>,
+    auto_tune: Arc<AtomicBool>,
+    control: broadcast::Receiver<()>,
+    stats: Arc<Mutex<HashMap<TrackId, DepacketStats>>>,
+}
 
-        Ok(())
+impl Depacketing {
+    // The least the tuner waits for. Keyframes span more packets than this, so it soon grows when they do.
+    const MIN_AUTO_LATENCY: u16 = 64;
+
+    // The latency to use as set, starting or stopping the tuner, which then picks it up to the one set.
+    fn latency(&self, tuner: &mut Option<DepacketTuner>) -> u16 {
+        let max = self.latency.load(Ordering::Relaxed);
+        if !self.auto_tune.load(Ordering::Relaxed) {
+            *tuner = None;
+            return max;
+        }
+        let tuner = tuner.get_or_insert_with(|| DepacketTuner::new(Self::MIN_AUTO_LATENCY, max));
+        tuner.set_max(max);
+        tuner.latency()
     }
+}
 
-    pub async fn disconnect(&self) -> WebRTCResult<()> {
-        self.peer_connection.close().await.map_err(WebRTCError::PeerConnectionError)?;
-        *self.remote_peer_id.write().unwrap() = None;
-        Ok(())
+// Where the received samples go, held back and dropped first if the network is made worse on purpose.
+#[derive(Clone)]
+enum SampleSink {
+    Direct(mpsc::Sender<(TrackId, Bytes)>),
+    Impaired(ImpairedLink<(TrackId, Bytes)>),
+}
+
+impl SampleSink {
+    // False once the samples can't be delivered anymore.
+    async fn send(&self, track: TrackId, data: Bytes) -> bool {
+        match self {
+            Self::Direct(sink) => sink
+                .send((track, data))
+                .await
+                .inspect_err(|e| tracing::error!("Failed to send received frame to sink: {}", e))
+                .is_ok(),
+            Self::Impaired(link) => {
+                let len = data.len();
+                link.send((track, data), len)
+            }
+        }
     }
 }
 
-async fn handle_signaling_message(
-    msg: SignalingMessage,
-    peer_connection: Arc<RTCPeerConnection>,
-    remote_peer_id: Arc<RwLock<Option<String>>>,
-    local_peer_id: Arc<RwLock<Option<String>>>,
-    signaling_tx: mpsc::Sender<SignalingMessage>,
-    event_sink: mpsc::Sender<WebRTCEvent>,
-) -> WebRTCResult<()> {
-    match msg.sig_type {
-        SignalingType::Identity => {
-            tracing::info!("Server assigned identity: {}", msg.data);
-            *local_peer_id.write().unwrap() = Some(msg.data);
+// Reassembles the track's RTP packets into samples, and forwards them to the sink until the track ends.
+async fn forward_samples<D: Depacketizer + Default>(
+    track: &TrackRemote,
+    mut depacketing: Depacketing,
+    sample_sink: &SampleSink,
+) {
+    const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+    let codec = track.codec().capability;
+    let mut tuner = None;
+    let mut reassembler = SampleReassembler::<D>::new(
+        depacketing.latency(&mut tuner),
+        codec.clock_rate,
+        NalCodec::from_mime_type(&codec.mime_type),
+    );
+    let mut last_counts = ReassemblyCounts::default();
+    let mut last_stats = Instant::now();
+
+    while let Ok((rtp, _attributes)) = track.read_rtp().await {
+        // Lagging behind only means it changed more than once.
+        if matches!(
+            depacketing.control.try_recv(),
+            Ok(()) | Err(broadcast::error::TryRecvError::Lagged(_))
+        ) {
+            reassembler.set_max_late(depacketing.latency(&mut tuner));
         }
-        SignalingType::Offer => {
-            // Lock onto the sender
-            *remote_peer_id.write().unwrap() = Some(msg.from.clone());
-            tracing::info!("Received Offer from {}", msg.from);
 
-            // Notify UI of incoming call
-            if let Err(e) = event_sink.send(WebRTCEvent::IncomingCall(msg.from.clone())).await {
-                tracing::error!("Failed to send IncomingCall event: {}", e);
+        let now = Instant::now();
+        let elapsed = now.duration_since(last_stats);
+        if elapsed >= STATS_INTERVAL {
+            last_stats = now;
+            let counts = reassembler.counts();
+            let packets = counts.packets - last_counts.packets;
+            let dropped = counts.dropped_packets - last_counts.dropped_packets;
+            last_counts = counts;
+
+            if let Some(latency) = tuner.as_mut().and_then(|tuner| tuner.update(packets, dropped)) {
+                reassembler.set_max_late(latency);
             }
+            depacketing.stats.lock().unwrap().insert(
+                depacketing.track,
+                DepacketStats {
+                    max_latency: reassembler.max_late(),
+                    packet_rate: (packets as f64 / elapsed.as_secs_f64()) as f32,
+                    auto_tuned: tuner.is_some(),
+                    packets: counts.packets,
+                    samples: counts.samples,
+                    dropped_packets: counts.dropped_packets,
+                },
+            );
+        }
 
-            let sdp = RTCSessionDescription::offer(msg.data).map_err(WebRTCError::SdpError)?;
-            peer_connection
-                .set_remote_description(sdp)
-                .await
-                .map_err(WebRTCError::PeerConnectionError)?;
+        reassembler.push(rtp);
+        while let Some(sample) = reassembler.pop() {
+            if !sample_sink.send(depacketing.track, sample.data).await {
+                return;
+            }
+        }
+    }
+}
+
+// What the signaling sets up a call on: the peer connection, our video, and the tasks it spawns for it.
+#[derive(Clone)]
+struct CallMedia {
+    connection: Arc<RwLock<Connection>>,
+    factory: Arc<ConnectionFactory>,
+    video: Arc<VideoOutput>,
+    tasks: Arc<Mutex<JoinSet<()>>>,
+}
 
-            // Auto-Answer logic
-            let answer = peer_connection
-                .create_answer(None)
+impl CallMedia {
+    fn peer_connection(&self) -> Arc<RTCPeerConnection> {
+        self.connection.read().unwrap().peer_connection.clone()
+    }
+
+    // Starts over on a new peer connection, discarding the current one along with the offer we made on it.
+    async fn replace_connection(&self) -> WebRTCResult<()> {
+        let replacement = self.factory.create().await?;
+        let previous = std::mem::replace(&mut *self.connection.write().unwrap(), replacement);
+        self.video.forget_sender();
+        previous.discard().await
+    }
+}
+
+// Sends our video track, or when only viewing, just asks for the peer's in our offer.
+// Only the first description of the connection sets it up, the renegotiations after replace the track.
+async fn set_up_video(
+    peer_connection: &RTCPeerConnection,
+    video: &VideoOutput,
+    tasks: &Mutex<JoinSet<()>>,
+    offering: bool,
+) -> WebRTCResult<()> {
+    if video.sender().is_some() {
+        return Ok(());
+    }
+    match video.mode() {
+        CallMode::ShareAndView => {
+            let sender = peer_connection
+                .add_track(
+                    video.track() as Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync>
+                )
                 .await
                 .map_err(WebRTCError::PeerConnectionError)?;
-
-            let answer_sdp = answer.sdp.clone();
+            tasks.lock().unwrap().spawn(read_rtcp(sender.clone()));
+            *video.sender.write().unwrap() = Some(sender);
+        }
+        // Otherwise the offer wouldn't have any video in it.
+        CallMode::ViewOnly if offering && peer_connection.get_transceivers().await.is_empty() => {
             peer_connection
-                .set_local_description(answer)
+                .add_transceiver_from_kind(
+                    RTPCodecType::Video,
+                    Some(RTCRtpTransceiverInit {
+                        direction: RTCRtpTransceiverDirection::Recvonly,
+                        send_encodings: Vec::new(),
+                    }),
+                )
                 .await
                 .map_err(WebRTCError::PeerConnectionError)?;
+        }
+        // The video of the peer's offer gets a transceiver that only receives, as there is no track for it.
+        CallMode::ViewOnly => (),
+    }
+    Ok(())
+}
 
-            let response_msg = SignalingMessage {
-                to: msg.from,
-                from: String::new(),
-                sig_type: SignalingType::Answer,
-                data: answer_sdp,
-            };
+// What the peer does in the call, as it says, or as the direction of its video tells if it doesn't say.
+fn peer_mode(msg: &SignalingMessage) -> CallMode {
+    msg.mode.unwrap_or_else(|| CallMode::from_sdp(&msg.data))
+}
+
+// Reads what the peer reports about the video it receives, which only matters for the logs.
+async fn read_rtcp(sender: Arc<RTCRtpSender>) {
+    let Ok((packets, _attributes)) = sender.read_rtcp().await else {
+        tracing::error!("Error reading RTCP packets");
+        return;
+    };
+    for packet in packets {
+        tracing::debug!("Received RTCP packet: {:?}", packet);
+    }
+}
+
+// Who the call is with, and what it runs in, as the signaling changes it.
+#[derive(Clone)]
+struct CallState {
+    remote_peer_id: Arc<RwLock<Option<String>>>,
+    local_identity: Arc<RwLock<Option<IdentityPayload>>>,
+    ringing: Arc<RwLock<Option<String>>>,
+    current_call: Arc<RwLock<Span>>,
+}
+
+async fn handle_signaling_message(
+    msg: SignalingMessage,
+    media: CallMedia,
+    call: CallState,
+    sealed_signaling: SealedSender,
+    event_sink: EventSink,
+) -> WebRTCResult<()> {
+    let CallState { remote_peer_id, local_identity, ringing, current_call } = call;
+    let sig_type = msg.sig_type.clone();
+    let msg = match sealed_signaling.open(msg).await {
+        Ok(msg) => msg,
+        Err(e) => {
+            // Otherwise the call just never starts. Candidates follow the offer or answer, so those tell already.
+            if matches!(sig_type, SignalingType::Offer | SignalingType::Answer)
+                && let Err(e) = event_sink.send(WebRTCEvent::SignalingRejected(e.to_string())).await
+            {
+                tracing::error!("Failed to send SignalingRejected event: {}", e);
+            }
+            return Err(e.into());
+        }
+    };
+    let peer_connection = media.peer_connection();
+
+    match msg.sig_type {
+        SignalingType::Identity => {
+            tracing::info!("Server assigned identity: {}", msg.data);
+            *local_identity.write().unwrap() = Some(IdentityPayload::parse(&msg.data));
+        }
+        SignalingType::Offer => {
+            // The peer of the call offering a new track, as its video source changed, isn't another call.
+            let current_peer = remote_peer_id.read().unwrap().clone();
+            let from_peer = current_peer.as_deref() == Some(msg.from.as_str());
+            if from_peer
+                && peer_connection.signaling_state() == RTCSignalingState::Stable
+                && peer_connection.current_remote_description().await.is_some()
+            {
+                tracing::info!("Renegotiating the call with {}", msg.from);
+                return answer_offer(msg, None, &media, &sealed_signaling, &event_sink).await;
+            }
+
+            // Our own offer to the peer is still waiting for an answer, as the peer called us before it arrived.
+            // A peer called by its short code answers with its ID, which we don't know yet, so its call is turned
+            // down like any other while we are in one.
+            let crossed =
+                from_peer && peer_connection.signaling_state() == RTCSignalingState::HaveLocalOffer;
+            if let Some(current_peer) = current_peer
+                && !crossed
+            {
+                tracing::info!(
+                    "Turning down the call from {}, busy with {}",
+                    msg.from,
+                    current_peer
+                );
+                if let Err(e) = send_bye(&msg.from, &sealed_signaling).await {
+                    tracing::warn!("Failed to send Bye to {}: {}", msg.from, e);
+                }
+                return Ok(());
+            }
+            if crossed {
+                let local_id = local_identity
+                    .read()
+                    .unwrap()
+                    .as_ref()
+                    .map(|identity| identity.uuid.clone())
+                    .unwrap_or_default();
+                match glare_role(&local_id, &msg.from) {
+                    GlareRole::Keep => {
+                        tracing::info!("Calls with {} crossed, waiting for the answer", msg.from);
+                        return Ok(());
+                    }
+                    GlareRole::Yield => {
+                        tracing::info!("Calls with {} crossed, answering theirs", msg.from);
+                        if let Err(e) = media.replace_connection().await {
+                            hang_up_crossed(&msg.from, &sealed_signaling, &event_sink).await;
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+
+            // Lock onto the sender
+            *remote_peer_id.write().unwrap() = Some(msg.from.clone());
+            let span = call_span(&msg.from, CallDirection::Incoming);
+            *current_call.write().unwrap() = span.clone();
+            let mode = peer_mode(&msg);
+            if crossed {
+                let announce = WebRTCEvent::CallsCrossed(msg.from.clone(), mode);
+                return answer_offer(msg, Some(announce), &media, &sealed_signaling, &event_sink)
+                    .instrument(span)
+                    .await;
+            }
 
-            signaling_tx.send(response_msg).await.map_err(WebRTCError::SendError)?;
+            // The offer is taken right away, so the candidates that follow it can be, but only answered once the user accepts.
+            let from = msg.from.clone();
+            take_offer(msg, &media).instrument(span).await?;
+            *ringing.write().unwrap() = Some(from.clone());
+            if let Err(e) = event_sink.send(WebRTCEvent::IncomingCall(from, mode)).await {
+                tracing::error!("Failed to send incoming call event: {}", e);
+            }
         }
         SignalingType::Answer => {
             // Lock onto the sender (if not already?)
             *remote_peer_id.write().unwrap() = Some(msg.from.clone());
             tracing::info!("Received Answer from {}", msg.from);
 
+            // An answer to a renegotiation, in a call that was answered already.
+            let renegotiated = peer_connection.current_remote_description().await.is_some();
+            let mode = peer_mode(&msg);
             let sdp = RTCSessionDescription::answer(msg.data).map_err(WebRTCError::SdpError)?;
             peer_connection
                 .set_remote_description(sdp)
                 .await
                 .map_err(WebRTCError::PeerConnectionError)?;
+            if !renegotiated && let Err(e) = event_sink.send(WebRTCEvent::Answered(mode)).await {
+                tracing::error!("Failed to send Answered event: {}", e);
+            }
+        }
+        SignalingType::Bye => {
+            tracing::info!("Received Bye from {}", msg.from);
+            // Only the current peer can hang up, and it doesn't need a Bye back.
+            let is_current_peer = {
+                let mut remote_peer_id = remote_peer_id.write().unwrap();
+                let is_current_peer = remote_peer_id.as_deref() == Some(msg.from.as_str());
+                if is_current_peer {
+                    *remote_peer_id = None;
+                }
+                is_current_peer
+            };
+
+            if is_current_peer {
+                *current_call.write().unwrap() = Span::none();
+                // The peer gave up before the user answered, so the offer is taken back for the next call.
+                let was_ringing = ringing.write().unwrap().take().is_some();
+                if was_ringing && let Err(e) = roll_back_remote_offer(&peer_connection).await {
+                    tracing::warn!("Failed to take back the offer of the call that rang: {}", e);
+                }
+                if let Err(e) = event_sink.send(WebRTCEvent::Disconnected).await {
+                    tracing::error!("Failed to send Disconnected event: {}", e);
+                }
+            }
         }
         SignalingType::Candidate => {
             let candidate: RTCIceCandidateInit =
@@ -371,6 +1609,117 @@ async fn handle_signaling_message(
                 .await
                 .map_err(WebRTCError::PeerConnectionError)?;
         }
+        SignalingType::KeyExchange => {
+            if let Err(e) = sealed_signaling.take_public_key(&msg.from, &msg.data).await {
+                // Otherwise the call just never starts, as the offer that follows can't be opened.
+                if let Err(e) = event_sink.send(WebRTCEvent::SignalingRejected(e.to_string())).await
+                {
+                    tracing::error!("Failed to send SignalingRejected event: {}", e);
+                }
+                return Err(e);
+            }
+        }
+        // Only between a client and the server, while connecting.
+        SignalingType::Authenticate | SignalingType::Rejected => {
+            tracing::debug!("Ignoring {} from {}", msg.sig_type.name(), msg.from);
+        }
+        SignalingType::Unknown(name) => {
+            tracing::debug!("Ignoring unknown signaling message {} from {}", name, msg.from);
+        }
     }
     Ok(())
 }
+
+// Takes back the peer's offer, which was never answered, so the connection can take the next one.
+async fn roll_back_remote_offer(peer_connection: &RTCPeerConnection) -> WebRTCResult<()> {
+    let Some(offer) = peer_connection.pending_remote_description().await else {
+        return Ok(());
+    };
+    let mut rollback = RTCSessionDescription::default();
+    rollback.sdp_type = RTCSdpType::Rollback;
+    rollback.sdp = offer.sdp;
+    peer_connection.set_remote_description(rollback).await.map_err(WebRTCError::PeerConnectionError)
+}
+
+// Ends both crossed calls when ours can't be taken back, as the peer would otherwise wait on an answer forever.
+async fn hang_up_crossed(peer_id: &str, sealed_signaling: &SealedSender, event_sink: &EventSink) {
+    if let Err(e) = send_bye(peer_id, sealed_signaling).await {
+        tracing::warn!("Failed to send Bye to {}: {}", peer_id, e);
+    }
+    if let Err(e) = event_sink.send(WebRTCEvent::Disconnected).await {
+        tracing::error!("Failed to send Disconnected event: {}", e);
+    }
+}
+
+// Tells the peer that the call with it is over, or won't start.
+async fn send_bye(peer_id: &str, sealed_signaling: &SealedSender) -> WebRTCResult<()> {
+    let bye = SignalingMessage {
+        to: peer_id.to_owned(),
+        from: String::new(),
+        sig_type: SignalingType::Bye,
+        data: String::new(),
+        protocol_version: PROTOCOL_VERSION,
+        sealed: false,
+        mode: None,
+    };
+    sealed_signaling.send(bye).await
+}
+
+// Answers the offer right away, in the span of the call it starts, announcing the call with the event.
+// A crossed call is already on screen, and a renegotiated one already connected, so they aren't announced as incoming.
+async fn answer_offer(
+    msg: SignalingMessage,
+    announce: Option<WebRTCEvent>,
+    media: &CallMedia,
+    sealed_signaling: &SealedSender,
+    event_sink: &EventSink,
+) -> WebRTCResult<()> {
+    if let Some(event) = announce
+        && let Err(e) = event_sink.send(event).await
+    {
+        tracing::error!("Failed to send incoming call event: {}", e);
+    }
+
+    let from = msg.from.clone();
+    take_offer(msg, media).await?;
+    send_answer(from, media, sealed_signaling).await
+}
+
+// Sets the peer's offer as the remote description, with our video set up to answer it.
+async fn take_offer(msg: SignalingMessage, media: &CallMedia) -> WebRTCResult<()> {
+    let peer_connection = media.peer_connection();
+    tracing::info!("Received Offer from {}", msg.from);
+
+    // Before the offer is taken, so the video it asks for is the track we send.
+    set_up_video(&peer_connection, &media.video, &media.tasks, false).await?;
+    let sdp = RTCSessionDescription::offer(msg.data).map_err(WebRTCError::SdpError)?;
+    peer_connection.set_remote_description(sdp).await.map_err(WebRTCError::PeerConnectionError)
+}
+
+// Answers the offer taken from the peer, in the mode set.
+async fn send_answer(
+    peer_id: String,
+    media: &CallMedia,
+    sealed_signaling: &SealedSender,
+) -> WebRTCResult<()> {
+    let peer_connection = media.peer_connection();
+    let answer =
+        peer_connection.create_answer(None).await.map_err(WebRTCError::PeerConnectionError)?;
+
+    let answer_sdp = answer.sdp.clone();
+    peer_connection
+        .set_local_description(answer)
+        .await
+        .map_err(WebRTCError::PeerConnectionError)?;
+
+    let response_msg = SignalingMessage {
+        to: peer_id,
+        from: String::new(),
+        sig_type: SignalingType::Answer,
+        data: answer_sdp,
+        protocol_version: PROTOCOL_VERSION,
+        sealed: false,
+        mode: Some(media.video.mode()),
+    };
+    sealed_signaling.send(response_msg).await
+}
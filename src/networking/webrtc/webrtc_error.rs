@@ -1,4 +1,6 @@
-use crate::networking::signaling_error::SignalingError;
+use crate::networking::{
+    sealed_signaling_error::SealedSignalingError, signaling_error::SignalingError,
+};
 
 pub type WebRTCResult<T> = Result<T, WebRTCError>;
 
@@ -14,8 +16,14 @@ pub enum WebRTCError {
     SendError(tokio::sync::mpsc::error::SendError<fjarsyn_shared::SignalingMessage>),
     #[error("Deserialize error: {0}")]
     DeserializeError(serde_json::Error),
+    #[error("Serialize error: {0}")]
+    SerializeError(serde_json::Error),
+    #[error("Data channel error: {0}")]
+    DataChannelError(webrtc::Error),
     #[error("Signaling error: {0}")]
     SignalingError(#[from] SignalingError),
+    #[error("Sealed signaling error: {0}")]
+    SealedSignalingError(#[from] SealedSignalingError),
     #[error("Media error: {0}")]
     MediaError(#[from] webrtc::media::Error),
     #[error("Write RTP error: {0}")]
@@ -0,0 +1,161 @@
+//! Watches the mouse buttons system-wide with a low-level hook, to show the viewers where the sharer clicks.
+//! Only which button was pressed where is passed on, and the hook is removed as soon as it is dropped.
+
+use std::{
+    cell::RefCell,
+    io,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use fjarsyn_shared::MouseButton;
+use windows::Win32::{
+    Foundation::{LPARAM, LRESULT, WPARAM},
+    System::{LibraryLoader::GetModuleHandleW, Threading::GetCurrentThreadId},
+    UI::WindowsAndMessaging::{
+        CallNextHookEx, GetMessageW, HC_ACTION, MSG, MSLLHOOKSTRUCT, PM_NOREMOVE, PeekMessageW,
+        PostThreadMessageW, SetWindowsHookExW, UnhookWindowsHookEx, WH_MOUSE_LL, WM_LBUTTONDOWN,
+        WM_MBUTTONDOWN, WM_QUIT, WM_RBUTTONDOWN,
+    },
+};
+
+use crate::utils::vector2::Vector2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InputHookError {
+    #[error("Failed to install the mouse hook: {0}")]
+    Install(windows_core::Error),
+    #[error("Failed to start the mouse hook thread: {0}")]
+    Thread(io::Error),
+    #[error("The mouse hook thread exited before installing the hook")]
+    ThreadExited,
+}
+
+pub type Result<T> = std::result::Result<T, InputHookError>;
+
+/// A mouse button pressed anywhere on the desktop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MouseClick {
+    /// In screen coordinates, in physical pixels.
+    pub position: Vector2<i32>,
+    pub button: MouseButton,
+    pub at: Instant,
+}
+
+type OnClick = Box<dyn Fn(MouseClick)>;
+
+thread_local! {
+    // Set on the hook's thread, which the hook procedure runs on.
+    static ON_CLICK: RefCell<Option<OnClick>> = const { RefCell::new(None) };
+}
+
+// The hooks installed, across threads.
+static INSTALLED: AtomicUsize = AtomicUsize::new(0);
+
+/// How many mouse hooks are installed right now, which is none once every [`MouseHook`] is dropped.
+pub fn installed() -> usize {
+    INSTALLED.load(Ordering::SeqCst)
+}
+
+/// Calls back, on a thread of its own, every time a mouse button is pressed, until dropped.
+/// A low-level hook runs on the thread that installed it, which has to keep pumping messages, or the system skips it
+/// and every click waits on it in the meantime. So the callback should only pass the click on.
+#[derive(Debug)]
+pub struct MouseHook {
+    thread_id: u32,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MouseHook {
+    pub fn install(on_click: impl Fn(MouseClick) + Send + 'static) -> Result<Self> {
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("mouse-hook".to_owned())
+            .spawn(move || {
+                let hook = unsafe { GetModuleHandleW(None) }.and_then(|instance| unsafe {
+                    SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), Some(instance.into()), 0)
+                });
+                let hook = match hook {
+                    Ok(hook) => hook,
+                    Err(e) => {
+                        ready_tx.send(Err(InputHookError::Install(e))).ok();
+                        return;
+                    }
+                };
+                INSTALLED.fetch_add(1, Ordering::SeqCst);
+                ON_CLICK.set(Some(Box::new(on_click)));
+
+                // Creates the message queue, so the WM_QUIT posted on drop can't get lost if it comes early.
+                let mut message = MSG::default();
+                let _ = unsafe { PeekMessageW(&mut message, None, 0, 0, PM_NOREMOVE) };
+                ready_tx.send(Ok(unsafe { GetCurrentThreadId() })).ok();
+
+                // The hook is called while waiting here, until the WM_QUIT posted when it is dropped.
+                while unsafe { GetMessageW(&mut message, None, 0, 0) }.0 > 0 {}
+
+                ON_CLICK.set(None);
+                if let Err(e) = unsafe { UnhookWindowsHookEx(hook) } {
+                    tracing::warn!("Failed to remove the mouse hook: {}", e);
+                }
+                INSTALLED.fetch_sub(1, Ordering::SeqCst);
+                tracing::debug!("Removed the mouse hook");
+            })
+            .map_err(InputHookError::Thread)?;
+
+        match ready_rx.recv() {
+            Ok(Ok(thread_id)) => {
+                tracing::debug!("Installed the mouse hook");
+                Ok(Self { thread_id, thread: Some(thread) })
+            }
+            Ok(Err(e)) => {
+                thread.join().ok();
+                Err(e)
+            }
+            Err(_) => {
+                thread.join().ok();
+                Err(InputHookError::ThreadExited)
+            }
+        }
+    }
+}
+
+impl Drop for MouseHook {
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0)) }
+        {
+            tracing::error!("Failed to stop the mouse hook: {}", e);
+            // Joining would wait forever.
+            return;
+        }
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            tracing::error!("The mouse hook panicked");
+        }
+    }
+}
+
+unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let button = match wparam.0 as u32 {
+        WM_LBUTTONDOWN => Some(MouseButton::Left),
+        WM_RBUTTONDOWN => Some(MouseButton::Right),
+        WM_MBUTTONDOWN => Some(MouseButton::Middle),
+        _ => None,
+    };
+    if code == HC_ACTION as i32
+        && let Some(button) = button
+    {
+        // Only valid for the call, so it is copied out right away.
+        let info = unsafe { *(lparam.0 as *const MSLLHOOKSTRUCT) };
+        let click =
+            MouseClick { position: Vector2::new(info.pt.x, info.pt.y), button, at: Instant::now() };
+        ON_CLICK.with_borrow(|on_click| {
+            if let Some(on_click) = on_click {
+                on_click(click);
+            }
+        });
+    }
+    // The click goes on to wherever it was going, whatever we do with it.
+    unsafe { CallNextHookEx(None, code, wparam, lparam) }
+}
@@ -0,0 +1,116 @@
+use fjarsyn_shared::{InputEvent, MouseButton};
+use windows::Win32::UI::{
+    Input::KeyboardAndMouse::{
+        GetAsyncKeyState, INPUT, INPUT_0, INPUT_MOUSE, MOUSE_EVENT_FLAGS, MOUSEEVENTF_ABSOLUTE,
+        MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN,
+        MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP,
+        MOUSEEVENTF_VIRTUALDESK, MOUSEEVENTF_WHEEL, MOUSEINPUT, SendInput, VIRTUAL_KEY, VK_CONTROL,
+        VK_END, VK_MENU,
+    },
+    WindowsAndMessaging::{
+        GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
+        SM_YVIRTUALSCREEN, WHEEL_DELTA,
+    },
+};
+
+use crate::utils::{dpi::CaptureTransform, vector2::Vector2};
+
+pub type Result<T> = std::result::Result<T, InputInjectionError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InputInjectionError {
+    #[error("Input was blocked by the system: {0}")]
+    Blocked(windows_core::Error),
+}
+
+/// Injects input from a remote viewer.
+/// `transform` maps the event's normalized positions in the captured content to the screen.
+pub fn inject(event: InputEvent, transform: &CaptureTransform) -> Result<()> {
+    let input = match event {
+        InputEvent::MouseMove(position) => {
+            let point = transform.to_screen(Vector2::new(position.x, position.y));
+            let (dx, dy) = to_absolute_coordinates(point.x, point.y);
+            mouse_input(
+                MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK,
+                dx,
+                dy,
+                0,
+            )
+        }
+        InputEvent::MouseButton { button, pressed } => {
+            let flags = match (button, pressed) {
+                (MouseButton::Left, true) => MOUSEEVENTF_LEFTDOWN,
+                (MouseButton::Left, false) => MOUSEEVENTF_LEFTUP,
+                (MouseButton::Right, true) => MOUSEEVENTF_RIGHTDOWN,
+                (MouseButton::Right, false) => MOUSEEVENTF_RIGHTUP,
+                (MouseButton::Middle, true) => MOUSEEVENTF_MIDDLEDOWN,
+                (MouseButton::Middle, false) => MOUSEEVENTF_MIDDLEUP,
+            };
+            mouse_input(flags, 0, 0, 0)
+        }
+        InputEvent::MouseScroll { x, y } => {
+            // Vertical and horizontal scrolling have to be sent as separate inputs.
+            if x != 0.0 {
+                send(&[mouse_input(MOUSEEVENTF_HWHEEL, 0, 0, lines_to_wheel_data(x))])?;
+            }
+            if y == 0.0 {
+                return Ok(());
+            }
+            mouse_input(MOUSEEVENTF_WHEEL, 0, 0, lines_to_wheel_data(y))
+        }
+    };
+
+    send(&[input])
+}
+
+/// Whether the panic hotkey (Ctrl+Alt+End) is held down, which revokes remote control.
+/// This checks the physical key state, so it works regardless of which window is focused.
+pub fn is_panic_hotkey_pressed() -> bool {
+    fn is_down(key: VIRTUAL_KEY) -> bool {
+        // The most significant bit is set if the key is down.
+        let state = unsafe { GetAsyncKeyState(key.0 as i32) };
+        state < 0
+    }
+
+    is_down(VK_CONTROL) && is_down(VK_MENU) && is_down(VK_END)
+}
+
+// Absolute mouse coordinates are normalized to 0..65535 over the whole virtual desktop.
+fn to_absolute_coordinates(x: i32, y: i32) -> (i32, i32) {
+    let (left, top, width, height) = unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        )
+    };
+
+    let normalize = |value: i32, origin: i32, extent: i32| {
+        ((value - origin) as i64 * 65535 / (extent - 1).max(1) as i64) as i32
+    };
+    (normalize(x, left, width), normalize(y, top, height))
+}
+
+fn lines_to_wheel_data(lines: f32) -> u32 {
+    // Negative amounts are passed as the two's complement, as mouseData is unsigned.
+    (lines * WHEEL_DELTA as f32) as i32 as u32
+}
+
+fn mouse_input(flags: MOUSE_EVENT_FLAGS, dx: i32, dy: i32, data: u32) -> INPUT {
+    INPUT {
+        r#type: INPUT_MOUSE,
+        Anonymous: INPUT_0 {
+            mi: MOUSEINPUT { dx, dy, mouseData: data, dwFlags: flags, time: 0, dwExtraInfo: 0 },
+        },
+    }
+}
+
+fn send(inputs: &[INPUT]) -> Result<()> {
+    let sent = unsafe { SendInput(inputs, std::mem::size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        // Typically caused by UIPI, when the foreground window runs with higher privileges than us.
+        return Err(InputInjectionError::Blocked(windows_core::Error::from_thread()));
+    }
+    Ok(())
+}
@@ -0,0 +1,7 @@
+#[cfg(target_os = "windows")]
+pub mod input_hook;
+#[cfg(target_os = "windows")]
+pub mod input_injection;
+pub mod single_instance;
+#[cfg(target_os = "windows")]
+pub mod url_protocol;
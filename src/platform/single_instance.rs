@@ -0,0 +1,208 @@
+//! Keeps to one running instance per config directory. The first one listens on a localhost port, which it writes to
+//! a lock file in the directory. Later ones find it there, tell it what they were started for, and exit.
+
+use std::{
+    fs::{self, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use fjarsyn_shared::{is_valid_peer_id, parse_call_link};
+use tokio::sync::{Mutex, mpsc};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SingleInstanceError {
+    #[error("Failed to listen for other instances: {0}")]
+    Listen(io::Error),
+    #[error("Failed to write the lock file {0}: {1}")]
+    LockFile(PathBuf, io::Error),
+}
+
+type Result<T> = std::result::Result<T, SingleInstanceError>;
+
+/// What another instance was started for, which the running one does instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstanceMessage {
+    /// Brings the window to the front.
+    Focus,
+    /// Brings the window to the front, with the id or code filled in to call.
+    Call(String),
+}
+
+impl InstanceMessage {
+    /// A call if one of the arguments is a link to one, otherwise just the focus.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        args.into_iter()
+            .find_map(|arg| parse_call_link(&arg).map(str::to_owned))
+            .map_or(Self::Focus, Self::Call)
+    }
+
+    /// One line, without the line break.
+    pub fn to_line(&self) -> String {
+        match self {
+            Self::Focus => "focus".to_owned(),
+            Self::Call(target) => format!("call {}", target),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        match line.trim_end().split_once(' ') {
+            None if line.trim_end() == "focus" => Some(Self::Focus),
+            Some(("call", target)) if is_valid_peer_id(target) => {
+                Some(Self::Call(target.to_owned()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether this is the first instance, or another one was told what to do instead.
+#[derive(Debug)]
+pub enum Instance {
+    Primary(InstanceListener),
+    Secondary,
+}
+
+/// Receives the messages of the instances started after this one, as long as the lock is held.
+#[derive(Debug)]
+pub struct InstanceListener {
+    listener: TcpListener,
+    lock: InstanceLock,
+}
+
+impl InstanceListener {
+    pub fn port(&self) -> u16 {
+        self.lock.port
+    }
+
+    /// Accepts the other instances on a thread of its own, as this happens before there is a runtime.
+    pub fn spawn(self) -> InstanceMessages {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let Self { listener, lock } = self;
+        let spawned =
+            std::thread::Builder::new().name("fjarsyn-instance".to_owned()).spawn(move || {
+                for stream in listener.incoming() {
+                    match stream.map(receive) {
+                        Ok(Ok(Some(message))) => {
+                            if tx.send(message).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(Ok(None)) => tracing::warn!("Another instance sent an unknown message"),
+                        Ok(Err(e)) | Err(e) => {
+                            tracing::warn!("Failed to receive from another instance: {}", e)
+                        }
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            tracing::error!("Failed to listen for other instances: {}", e);
+        }
+        InstanceMessages { messages: Arc::new(Mutex::new(rx)), _lock: Arc::new(lock) }
+    }
+}
+
+/// What the other instances asked for. The lock is held until every clone is dropped.
+#[derive(Debug, Clone)]
+pub struct InstanceMessages {
+    messages: Arc<Mutex<mpsc::UnboundedReceiver<InstanceMessage>>>,
+    _lock: Arc<InstanceLock>,
+}
+
+impl InstanceMessages {
+    pub fn receiver(&self) -> Arc<Mutex<mpsc::UnboundedReceiver<InstanceMessage>>> {
+        self.messages.clone()
+    }
+}
+
+// Identity based, so the subscription to the messages stays the same across updates.
+impl Hash for InstanceMessages {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.messages) as *const ()).hash(state);
+    }
+}
+
+// The lock file, which is removed once the instance that wrote it is done.
+#[derive(Debug)]
+struct InstanceLock {
+    path: PathBuf,
+    port: u16,
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Left alone if another instance took it over in the meantime.
+        if read_port(&self.path) == Some(self.port) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+// Sent by the first instance to every connection, so a port that was reused by something else isn't mistaken for it.
+const GREETING: &str = "fjarsyn instance 1";
+// How long a stuck peer can hold up either side.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The lock file in the directory.
+pub fn lock_path(dir: &Path) -> PathBuf {
+    dir.join("instance.lock")
+}
+
+/// Becomes the first instance for the directory, or tells the running one what this one was started for.
+/// A lock file left behind by an instance that is gone is taken over.
+pub fn acquire(dir: &Path, message: &InstanceMessage) -> Result<Instance> {
+    let path = lock_path(dir);
+    if let Some(port) = read_port(&path) {
+        match send(port, message) {
+            Ok(()) => return Ok(Instance::Secondary),
+            Err(e) => tracing::info!("Taking over the lock of an instance that is gone: {}", e),
+        }
+    }
+
+    let listener =
+        TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(SingleInstanceError::Listen)?;
+    let port = listener.local_addr().map_err(SingleInstanceError::Listen)?.port();
+    write_port(&path, port).map_err(|e| SingleInstanceError::LockFile(path.clone(), e))?;
+    Ok(Instance::Primary(InstanceListener { listener, lock: InstanceLock { path, port } }))
+}
+
+fn read_port(path: &Path) -> Option<u16> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn write_port(path: &Path, port: u16) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
+    writeln!(file, "{}", port)
+}
+
+fn send(port: u16, message: &InstanceMessage) -> io::Result<()> {
+    let stream =
+        TcpStream::connect_timeout(&SocketAddr::from((Ipv4Addr::LOCALHOST, port)), TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting)?;
+    if greeting.trim_end() != GREETING {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a Fjarsyn instance"));
+    }
+    writeln!(&stream, "{}", message.to_line())
+}
+
+fn receive(stream: TcpStream) -> io::Result<Option<InstanceMessage>> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    writeln!(&stream, "{}", GREETING)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    Ok(InstanceMessage::parse(&line))
+}
@@ -0,0 +1,135 @@
+//! Registers the app as the handler of `fjarsyn://` links for the current user, so clicking one in a browser or a
+//! chat starts it with the link as an argument. Registering for the current user doesn't need elevation.
+
+use std::{io, path::Path};
+
+use fjarsyn_shared::CALL_LINK_SCHEME;
+use windows::{
+    Win32::{
+        Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS, WIN32_ERROR},
+        System::Registry::{
+            HKEY_CURRENT_USER, REG_SZ, RRF_RT_REG_SZ, RegDeleteTreeW, RegGetValueW, RegSetKeyValueW,
+        },
+    },
+    core::{HSTRING, PCWSTR},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum UrlProtocolError {
+    #[error("Failed to write {0} to the registry: {1:?}")]
+    WriteFailed(String, WIN32_ERROR),
+    #[error("Failed to remove the registration: {0:?}")]
+    RemoveFailed(WIN32_ERROR),
+    #[error("Failed to find the executable: {0}")]
+    CurrentExe(io::Error),
+}
+
+type Result<T> = std::result::Result<T, UrlProtocolError>;
+
+const DISPLAY_NAME: &str = "URL:Fjarsyn";
+
+fn class_key() -> String {
+    format!(r"Software\Classes\{}", CALL_LINK_SCHEME)
+}
+
+fn command_key() -> String {
+    format!(r"{}\shell\open\command", class_key())
+}
+
+/// What the shell runs for a link, with the link as the only argument.
+pub fn open_command(exe: &Path) -> String {
+    format!("\"{}\" \"%1\"", exe.display())
+}
+
+/// Whether links open this executable, which isn't the case anymore once it was moved.
+pub fn is_registered(exe: &Path) -> bool {
+    read_default(&command_key()).is_some_and(|command| command == open_command(exe))
+}
+
+/// Points the links at this executable, replacing whatever handled them before.
+pub fn register(exe: &Path) -> Result<()> {
+    let class = class_key();
+    write(&class, None, DISPLAY_NAME)?;
+    // Marks the class as a scheme, rather than a file type.
+    write(&class, Some("URL Protocol"), "")?;
+    write(&format!(r"{}\DefaultIcon", class), None, &format!("\"{}\",0", exe.display()))?;
+    write(&command_key(), None, &open_command(exe))
+}
+
+/// Removes the registration, if there is one.
+pub fn unregister() -> Result<()> {
+    let result = unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, &HSTRING::from(class_key())) };
+    if result != ERROR_SUCCESS && result != ERROR_FILE_NOT_FOUND {
+        return Err(UrlProtocolError::RemoveFailed(result));
+    }
+    Ok(())
+}
+
+/// Registers or unregisters this executable, if it isn't already. Another executable handling the links is only
+/// replaced, never removed, so turning it off in one install leaves the others alone.
+pub fn sync(enabled: bool) -> Result<()> {
+    let exe = std::env::current_exe().map_err(UrlProtocolError::CurrentExe)?;
+    match (enabled, is_registered(&exe)) {
+        (true, false) => register(&exe),
+        (false, true) => unregister(),
+        _ => Ok(()),
+    }
+}
+
+// The default value of the key if the name is None.
+fn write(key: &str, name: Option<&str>, value: &str) -> Result<()> {
+    // REG_SZ values are the null-terminated UTF-16 string.
+    let data: Vec<u16> = value.encode_utf16().chain(std::iter::once(0)).collect();
+    let name = name.map(HSTRING::from);
+    let result = unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            &HSTRING::from(key),
+            name.as_ref().map_or(PCWSTR::null(), |name| PCWSTR(name.as_ptr())),
+            REG_SZ.0,
+            Some(data.as_ptr().cast()),
+            std::mem::size_of_val(data.as_slice()) as u32,
+        )
+    };
+    if result != ERROR_SUCCESS {
+        return Err(UrlProtocolError::WriteFailed(key.to_owned(), result));
+    }
+    Ok(())
+}
+
+fn read_default(key: &str) -> Option<String> {
+    let key = HSTRING::from(key);
+    let mut size = 0u32;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &key,
+            PCWSTR::null(),
+            RRF_RT_REG_SZ,
+            None,
+            None,
+            Some(&mut size),
+        )
+    };
+    if result != ERROR_SUCCESS {
+        return None;
+    }
+
+    let mut data = vec![0u16; (size as usize).div_ceil(2)];
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &key,
+            PCWSTR::null(),
+            RRF_RT_REG_SZ,
+            None,
+            Some(data.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+    };
+    if result != ERROR_SUCCESS {
+        return None;
+    }
+    let len = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+    Some(String::from_utf16_lossy(&data[..len]))
+}
@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tokio::sync::{Mutex, mpsc};
+
+use crate::{
+    config::Config,
+    media::ffmpeg::FFmpegTranscodeType,
+    networking::{
+        signaling::LOOPBACK_IDS,
+        webrtc::{
+            PacketRouter, PeerEvent, PeerId, TrackId, WebRTC, WebRTCError, WebRTCEvent,
+            WebRTCReceivers,
+        },
+    },
+};
+
+/// How calls are set up, which the app takes from its config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallOptions {
+    /// How long the received packets wait for the ones missing before them, in packets.
+    pub max_depacket_latency: u16,
+    /// Whether the wait is tuned to the packets dropped, up to the max.
+    pub auto_depacket_latency: bool,
+    /// How many received packets can wait to be decoded.
+    pub receive_buffer: usize,
+    /// The codec offered for the video, which the received video is decoded with as well.
+    pub transcoding_type: FFmpegTranscodeType,
+}
+
+impl CallOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_depacket_latency: config.max_depacket_latency,
+            auto_depacket_latency: config.auto_depacket_latency,
+            receive_buffer: config.receive_buffer,
+            transcoding_type: config.transcoding_type,
+        }
+    }
+}
+
+impl Default for CallOptions {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+/// The channels a connection delivers the received video packets and its events on.
+/// Each connection has its own, so whatever reads from them has to switch over when it is replaced, e.g. by one to another server.
+#[derive(Debug, Clone)]
+pub struct CallChannels {
+    packets: PacketRouter,
+    events: Arc<Mutex<mpsc::Receiver<PeerEvent>>>,
+}
+
+impl CallChannels {
+    /// The video packets received on the track, for a [`DecoderWorker`](crate::media::decoder_worker::DecoderWorker)
+    /// to decode.
+    pub fn packets(&self, track: TrackId) -> Arc<Mutex<mpsc::Receiver<Bytes>>> {
+        self.packets.track(track)
+    }
+
+    /// Drops the packets of a track that ended.
+    pub fn close_track(&self, track: TrackId) {
+        self.packets.remove(track);
+    }
+
+    pub fn events(&self) -> Arc<Mutex<mpsc::Receiver<PeerEvent>>> {
+        self.events.clone()
+    }
+}
+
+// Routes the packets from here on, so it has to be made in the runtime.
+impl From<WebRTCReceivers> for CallChannels {
+    fn from(receivers: WebRTCReceivers) -> Self {
+        Self {
+            packets: PacketRouter::spawn(receivers.packets),
+            events: Arc::new(Mutex::new(receivers.events)),
+        }
+    }
+}
+
+/// Sets up connections for calls, so they can be made without the UI.
+pub struct CallSession;
+
+impl CallSession {
+    /// Connects to the signaling server, ready to call or be called.
+    pub async fn listen(
+        signaling_url: String,
+        options: CallOptions,
+    ) -> Result<CallHandle, WebRTCError> {
+        let (webrtc, receivers) = WebRTC::init(
+            signaling_url,
+            options.max_depacket_latency,
+            options.auto_depacket_latency,
+            options.receive_buffer,
+            options.transcoding_type,
+        )
+        .await?;
+        Ok(CallHandle { webrtc, channels: receivers.into() })
+    }
+
+    /// Connects to the signaling server, and calls the peer, by its ID or short code.
+    pub async fn connect(
+        signaling_url: String,
+        peer_id: &str,
+        options: CallOptions,
+    ) -> Result<CallHandle, WebRTCError> {
+        let handle = Self::listen(signaling_url, options).await?;
+        handle.call(peer_id).await?;
+        Ok(handle)
+    }
+
+    /// Calls itself, over two connections in this process that only reach each other, to try the whole pipeline on one machine.
+    /// The handle shares over the calling end, and receives what the other end receives, so what is shared comes back as the remote video.
+    /// The control messages sent come back as well. The other end is shut down along with the handle.
+    pub async fn loopback(options: CallOptions) -> Result<CallHandle, WebRTCError> {
+        let [(caller, caller_receivers), (callee, callee_receivers)] = WebRTC::loopback(
+            options.max_depacket_latency,
+            options.auto_depacket_latency,
+            options.receive_buffer,
+            options.transcoding_type,
+        )
+        .await?;
+
+        let (event_tx, events) = mpsc::channel(LOOPBACK_EVENT_BUFFER);
+        tokio::spawn(forward_loopback_events(
+            caller.peer_id(),
+            caller_receivers.events,
+            callee,
+            callee_receivers.events,
+            event_tx,
+        ));
+        let receivers = WebRTCReceivers { packets: callee_receivers.packets, events };
+        let handle = CallHandle { webrtc: caller, channels: receivers.into() };
+        handle.call(LOOPBACK_IDS[1]).await?;
+        Ok(handle)
+    }
+}
+
+const LOOPBACK_EVENT_BUFFER: usize = 100;
+
+// Passes on the events of the calling end, along with the control messages the other end receives,
+// as if the peer of the calling end sent them. The other end accepts the call itself, as nobody is there to,
+// and is shut down once the calling end is shut down.
+async fn forward_loopback_events(
+    caller_id: PeerId,
+    mut caller_events: mpsc::Receiver<PeerEvent>,
+    callee: WebRTC,
+    mut callee_events: mpsc::Receiver<PeerEvent>,
+    event_tx: mpsc::Sender<PeerEvent>,
+) {
+    loop {
+        let event = tokio::select! {
+            event = caller_events.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+            Some(PeerEvent { event, .. }) = callee_events.recv() => match event {
+                WebRTCEvent::Control(message) => {
+                    PeerEvent { peer: caller_id, event: WebRTCEvent::Control(message) }
+                }
+                WebRTCEvent::IncomingCall(..) => {
+                    if let Err(e) = callee.accept_call().await {
+                        tracing::warn!("The other end of the loopback failed to accept the call: {}", e);
+                    }
+                    continue;
+                }
+                event => {
+                    tracing::debug!("Loopback callee: {:?}", event);
+                    continue;
+                }
+            },
+        };
+        if event_tx.send(event).await.is_err() {
+            break;
+        }
+    }
+    if let Err(e) = callee.shutdown().await {
+        tracing::warn!("Failed to shut down the other end of the loopback: {}", e);
+    }
+}
+
+/// A connection to the signaling server, and the call made over it, if any.
+#[derive(Debug, Clone)]
+pub struct CallHandle {
+    webrtc: WebRTC,
+    channels: CallChannels,
+}
+
+impl CallHandle {
+    /// The connection itself, which is also where a [`CaptureSession`](super::CaptureSession) sends the video.
+    pub fn webrtc(&self) -> &WebRTC {
+        &self.webrtc
+    }
+
+    pub fn channels(&self) -> &CallChannels {
+        &self.channels
+    }
+
+    /// Calls the peer, by its ID or short code.
+    pub async fn call(&self, peer_id: &str) -> Result<(), WebRTCError> {
+        self.webrtc.create_offer(peer_id.trim().to_owned()).await
+    }
+
+    /// Waits for the next event of the connection.
+    pub async fn next_event(&self) -> Option<WebRTCEvent> {
+        Some(self.channels.events.lock().await.recv().await?.event)
+    }
+
+    /// Waits for the peer to connect, skipping the other events until it does.
+    /// False if the call ended first.
+    pub async fn wait_connected(&self) -> bool {
+        loop {
+            match self.next_event().await {
+                Some(WebRTCEvent::Connected) => return true,
+                Some(WebRTCEvent::Disconnected) | None => return false,
+                Some(event) => tracing::debug!("Skipping {:?} while waiting to connect", event),
+            }
+        }
+    }
+
+    /// Hangs up, closes the connection to the signaling server, and waits for it to shut down.
+    pub async fn close(self) -> Result<(), WebRTCError> {
+        self.webrtc.shutdown().await
+    }
+}
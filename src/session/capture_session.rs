@@ -0,0 +1,133 @@
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{Stream, StreamExt};
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::{
+    media::{
+        encoder_worker::{
+            EncoderConfig, EncoderHandle, EncoderWorker, EncoderWorkerError, SampleSink,
+        },
+        ffmpeg::FFmpegEncoderError,
+    },
+    utils::{abort_on_drop::AbortOnDrop, frame::Frame, metrics::Metrics},
+};
+
+/// A sample as the encoder wrote it.
+#[derive(Debug, Clone)]
+pub struct EncodedPacket {
+    pub data: Vec<u8>,
+    pub duration: Duration,
+}
+
+/// The packets of a [`CaptureSession`] that isn't sent anywhere, in the order they were encoded.
+#[derive(Debug)]
+pub struct PacketStream {
+    packets: mpsc::Receiver<EncodedPacket>,
+}
+
+impl Stream for PacketStream {
+    type Item = EncodedPacket;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.packets.poll_recv(cx)
+    }
+}
+
+// Waits for room rather than dropping packets, as it's up to the reader what to drop.
+struct PacketSink(mpsc::Sender<EncodedPacket>);
+
+impl SampleSink for PacketSink {
+    type Error = mpsc::error::SendError<EncodedPacket>;
+
+    async fn write_sample(&self, data: Vec<u8>, duration: Duration) -> Result<(), Self::Error> {
+        self.0.send(EncodedPacket { data, duration }).await
+    }
+}
+
+/// Encodes captured frames, and sends the encoded samples to a sink, e.g. a call.
+pub struct CaptureSession;
+
+impl CaptureSession {
+    const PACKET_BUFFER: usize = 60;
+
+    /// Starts encoding the frames, e.g. a capture provider's stream, into the sink.
+    /// Runs until the frames end or the session is stopped.
+    pub fn start<F, S>(
+        frames: F,
+        config: EncoderConfig,
+        sink: S,
+    ) -> Result<SessionHandle, FFmpegEncoderError>
+    where
+        F: Stream<Item = Frame> + Send + 'static,
+        S: SampleSink,
+    {
+        let metrics = Metrics::default();
+        let encoder = EncoderWorker::spawn(config, sink, &metrics)?;
+        let pump = tokio::spawn(Self::pump(frames, encoder.clone()).in_current_span());
+        Ok(SessionHandle { encoder, metrics, pump: AbortOnDrop(pump.abort_handle()) })
+    }
+
+    /// Starts encoding the frames, handing the packets back instead of sending them anywhere.
+    pub fn start_packets<F>(
+        frames: F,
+        config: EncoderConfig,
+    ) -> Result<(PacketStream, SessionHandle), FFmpegEncoderError>
+    where
+        F: Stream<Item = Frame> + Send + 'static,
+    {
+        let (packet_tx, packets) = mpsc::channel(Self::PACKET_BUFFER);
+        let handle = Self::start(frames, config, PacketSink(packet_tx))?;
+        Ok((PacketStream { packets }, handle))
+    }
+
+    // Frames are dropped when the encoder can't keep up, same as in a call.
+    async fn pump(frames: impl Stream<Item = Frame> + Send, encoder: EncoderHandle) {
+        let mut frames = std::pin::pin!(frames);
+        while let Some(frame) = frames.next().await {
+            match encoder.send_frame(Arc::new(frame)) {
+                Ok(()) => (),
+                Err(EncoderWorkerError::QueueFull) => {
+                    tracing::debug!("Encoder queue full, dropping frame");
+                }
+                Err(e) => {
+                    tracing::error!("Stopping capture session: {}", e);
+                    break;
+                }
+            }
+        }
+        tracing::debug!("Capture session ran out of frames.");
+    }
+}
+
+/// A running [`CaptureSession`]. Dropping it stops the session without waiting for the frames already taken.
+#[derive(Debug)]
+pub struct SessionHandle {
+    encoder: EncoderHandle,
+    metrics: Metrics,
+    pump: AbortOnDrop,
+}
+
+impl SessionHandle {
+    /// The encoder, to change its settings.
+    pub fn encoder(&mut self) -> &mut EncoderHandle {
+        &mut self.encoder
+    }
+
+    /// The session's own metrics, which the encoder keeps its stats in.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Stops taking frames, and waits for the ones already taken to be encoded and sent.
+    pub async fn stop(self) -> Result<(), EncoderWorkerError> {
+        drop(self.pump);
+        self.encoder.shutdown().await
+    }
+}
@@ -0,0 +1,5 @@
+mod call_session;
+mod capture_session;
+
+pub use call_session::{CallChannels, CallHandle, CallOptions, CallSession};
+pub use capture_session::{CaptureSession, EncodedPacket, PacketStream, SessionHandle};
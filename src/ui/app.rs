@@ -1,19 +1,34 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::sync::Arc;
 
-use bytes::Bytes;
 use futures::stream::unfold;
 use iced::{Element, Program, Subscription, Task, executor, window};
 use tokio::sync::{Mutex, RwLock, mpsc};
 
-use super::screens::{self, Screen};
+use super::screens::{
+    self, Screen,
+    call::{CallMessage, CallScreen},
+};
 use crate::{
-    capture_providers::PlatformCaptureProvider,
+    capture_providers::{PlatformCaptureProviderError, create_platform_capture_provider},
     config::Config,
-    networking::webrtc::{WebRTC, WebRTCEvent},
+    networking::{
+        signaling::LOOPBACK_IDS,
+        webrtc::{PeerEvent, WebRTCEvent},
+    },
+    platform::{
+        single_instance::{InstanceMessage, InstanceMessages},
+        url_protocol,
+    },
+    session::{CallOptions, CallSession},
+    tr,
     ui::{
+        call_history::CallHistory,
+        confirm_dialog::confirm_dialog,
+        consent::ConsentStore,
+        i18n,
         message::{Message, Route},
-        notification_provider::NotificationProvider,
-        state::{AppContext, State},
+        native_notifications::{self, Activation, NativeNotifier},
+        state::{AppContext, CaptureProviderState, ConfigStore, State},
     },
 };
 
@@ -21,21 +36,156 @@ use crate::{
 pub enum ActiveScreen {
     Onboarding(screens::onboarding::OnboardingScreen),
     Home(screens::home::HomeScreen),
-    Call(screens::call::CallScreen),
+    // Boxed, as it holds far more state than the other screens.
+    Call(Box<screens::call::CallScreen>),
     Settings(screens::settings::SettingsScreen),
+    Diagnostics(screens::diagnostics::DiagnosticsScreen),
+}
+
+impl ActiveScreen {
+    /// The screen to show for the route. There is only one call at a time, so a call screen waiting in the back queue
+    /// (e.g. behind settings) is taken from there, rather than a second one being created with its own decoder.
+    pub fn from_route(ctx: &mut AppContext, route: Route) -> Self {
+        match route {
+            Route::Home => Self::Home(screens::home::HomeScreen::new(ctx)),
+            Route::Call => Self::Call(
+                take_queued_call_screen(ctx)
+                    .unwrap_or_else(|| Box::new(screens::call::CallScreen::new(ctx))),
+            ),
+            Route::Settings => {
+                Self::Settings(screens::settings::SettingsScreen::new(ctx.config.clone()))
+            }
+            Route::Diagnostics => Self::Diagnostics(screens::diagnostics::DiagnosticsScreen::new()),
+        }
+    }
+
+    /// The route that leads to the screen. None for onboarding, which is only shown on its own.
+    pub fn route(&self) -> Option<Route> {
+        match self {
+            Self::Onboarding(_) => None,
+            Self::Home(_) => Some(Route::Home),
+            Self::Call(_) => Some(Route::Call),
+            Self::Settings(_) => Some(Route::Settings),
+            Self::Diagnostics(_) => Some(Route::Diagnostics),
+        }
+    }
 }
 
+fn take_queued_call_screen(ctx: &mut AppContext) -> Option<Box<CallScreen>> {
+    let index = ctx.back_queue.iter().position(|screen| matches!(screen, ActiveScreen::Call(_)))?;
+    match ctx.back_queue.remove(index) {
+        Some(ActiveScreen::Call(screen)) => Some(screen),
+        _ => None,
+    }
+}
+
+// The call screen, whether it is shown or waits in the back queue behind another screen.
+fn call_screen(state: &State) -> Option<&CallScreen> {
+    match &state.active_screen {
+        ActiveScreen::Call(screen) => Some(screen),
+        _ => state.ctx.back_queue.iter().find_map(|screen| match screen {
+            ActiveScreen::Call(screen) => Some(&**screen),
+            _ => None,
+        }),
+    }
+}
+
+// Updates the call screen wherever it is, as the call carries on while another screen is shown.
+fn update_call_screen(
+    state: &mut State,
+    update: impl FnOnce(&mut CallScreen, &mut AppContext) -> Task<Message>,
+) -> Task<Message> {
+    if let ActiveScreen::Call(screen) = &mut state.active_screen {
+        return update(screen, &mut state.ctx);
+    }
+    let Some(index) =
+        state.ctx.back_queue.iter().position(|screen| matches!(screen, ActiveScreen::Call(_)))
+    else {
+        return Task::none();
+    };
+    // Taken out while it is updated, as the queue is part of the context it is updated with.
+    let Some(ActiveScreen::Call(mut screen)) = state.ctx.back_queue.remove(index) else {
+        return Task::none();
+    };
+    let task = update(&mut screen, &mut state.ctx);
+    state.ctx.back_queue.insert(index, ActiveScreen::Call(screen));
+    task
+}
+
+#[derive(Default)]
 pub struct App {
-    capture: Arc<RwLock<PlatformCaptureProvider>>,
+    // What the instances started after this one ask for, unless more than one is allowed.
+    instance: Option<InstanceMessages>,
+    // Asked to call, from the link this instance was started with.
+    call_target: Option<String>,
 }
 
 impl App {
     const APP_TITLE: &'static str = "Fjarsyn";
+    // Exiting is never held up longer than this, even if a step of the teardown hangs.
+    const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+    // Polled, as the hotkey has to work while other windows are focused.
+    const PANIC_HOTKEY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
 
     pub fn new(
-        capture: Arc<RwLock<PlatformCaptureProvider>>,
+        instance: Option<InstanceMessages>,
+        call_target: Option<String>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(Self { capture })
+        Ok(Self { instance, call_target })
+    }
+
+    /// Connects to the signaling server, unless already connecting, which finishes with [`Message::WebRTCInitialized`].
+    pub fn connect(ctx: &mut AppContext, server_url: String) -> Task<Message> {
+        if ctx.call.connecting {
+            tracing::debug!("Already connecting to the signaling server, not connecting again.");
+            return Task::none();
+        }
+        ctx.call.connecting = true;
+
+        let options = CallOptions::from_config(&ctx.config);
+        Task::future(CallSession::listen(server_url, options))
+            .map(|result| Message::WebRTCInitialized(result.map(Arc::new).map_err(Arc::new)))
+    }
+
+    /// Calls ourselves in place of the connection to the signaling server, which finishes with [`Message::LoopbackStarted`].
+    /// The server is connected to again once the call ends.
+    pub fn start_loopback(ctx: &mut AppContext) -> Task<Message> {
+        if ctx.call.loopback || ctx.call.connecting {
+            tracing::debug!(
+                "Already calling ourselves or connecting, not starting a loopback call."
+            );
+            return Task::none();
+        }
+        ctx.call.loopback = true;
+
+        let options = CallOptions::from_config(&ctx.config);
+        Task::future(CallSession::loopback(options))
+            .map(|result| Message::LoopbackStarted(result.map(Arc::new).map_err(Arc::new)))
+    }
+
+    /// Creates the capture provider in the background, unless it was already, which finishes with [`Message::CaptureProviderReady`].
+    /// Creating the graphics device is slow, so it only happens once something is to be captured.
+    pub fn start_capture_provider(ctx: &mut AppContext) -> Task<Message> {
+        if !matches!(ctx.capture, CaptureProviderState::NotStarted) {
+            return Task::none();
+        }
+        ctx.capture = CaptureProviderState::Pending;
+
+        let pixel_format = ctx.config.pixel_format;
+        let session_options = ctx.config.capture_session_options;
+        let metrics = ctx.metrics.clone();
+        Task::future(async move {
+            tokio::task::spawn_blocking(move || {
+                create_platform_capture_provider(pixel_format, session_options, metrics)
+            })
+            .await
+            .unwrap_or_else(|e| Err(PlatformCaptureProviderError::Panicked(e.to_string())))
+        })
+        .map(|result| {
+            Message::CaptureProviderReady(
+                result.map(|capture| Arc::new(RwLock::new(capture))).map_err(Arc::new),
+            )
+        })
     }
 
     pub fn run(self) -> crate::Result<()> {
@@ -46,7 +196,7 @@ impl App {
 
 // Wrapper to implement Hash which is needed by iced subscriptions.
 #[derive(Clone)]
-struct WebRTCEventReceiverRef(Arc<Mutex<mpsc::Receiver<WebRTCEvent>>>);
+struct WebRTCEventReceiverRef(Arc<Mutex<mpsc::Receiver<PeerEvent>>>);
 
 impl std::hash::Hash for WebRTCEventReceiverRef {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
@@ -67,11 +217,11 @@ fn webrtc_event_subscription_stream(
     let receiver = receiver_ref.0.clone();
     Box::new(Box::pin(unfold(
         receiver,
-        |receiver: Arc<Mutex<mpsc::Receiver<WebRTCEvent>>>| async move {
+        |receiver: Arc<Mutex<mpsc::Receiver<PeerEvent>>>| async move {
             let mut lock = receiver.lock().await;
-            if let Some(event) = lock.recv().await {
+            if let Some(PeerEvent { peer, event }) = lock.recv().await {
                 drop(lock);
-                Some((Message::WebRTCEvent(event), receiver))
+                Some((Message::WebRTCEvent(peer, event), receiver))
             } else {
                 drop(lock);
                 None
@@ -80,40 +230,66 @@ fn webrtc_event_subscription_stream(
     )))
 }
 
-// Wrapper to implement Hash which is needed by iced subscriptions.
-#[derive(Clone)]
-pub struct PacketReceiverRef(pub Arc<Mutex<mpsc::Receiver<Bytes>>>);
-
-impl std::hash::Hash for PacketReceiverRef {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        Arc::as_ptr(&self.0).hash(state);
-    }
+fn instance_message_stream(
+    instance: &InstanceMessages,
+) -> Box<dyn futures::Stream<Item = Message> + Send + Unpin> {
+    Box::new(Box::pin(unfold(instance.receiver(), |messages| async move {
+        let message = messages.lock().await.recv().await;
+        message.map(|message| (Message::InstanceMessage(message), messages))
+    })))
 }
 
-impl PartialEq for PacketReceiverRef {
-    fn eq(&self, other: &Self) -> bool {
-        Arc::ptr_eq(&self.0, &other.0)
+// Restores the main window first, as a minimized window can't take the focus.
+fn focus_main_window(ctx: &AppContext) -> Task<Message> {
+    match ctx.windows.main_id {
+        Some(id) => Task::batch([window::minimize(id, false), window::gain_focus(id)]),
+        None => Task::none(),
     }
 }
 
-impl Eq for PacketReceiverRef {}
+// The actions of the buttons on the OS notification of an incoming call.
+const ACCEPT_CALL_ACTION: &str = "accept-call";
+const DECLINE_CALL_ACTION: &str = "decline-call";
 
-fn frame_subscription_stream(
-    receiver_ref: &PacketReceiverRef,
+fn native_notification_activation_stream(
+    notifier: &NativeNotifier,
 ) -> Box<dyn futures::Stream<Item = Message> + Send + Unpin> {
-    let receiver = receiver_ref.0.clone();
-    Box::new(Box::pin(unfold(receiver, |receiver| async move {
-        let mut lock = receiver.lock().await;
-        if let Some(packet) = lock.recv().await {
-            drop(lock);
-            Some((Message::PacketReceived(packet), receiver))
-        } else {
-            drop(lock);
-            None
-        }
+    Box::new(Box::pin(unfold(notifier.activations(), |activations| async move {
+        let activation = activations.lock().await.recv().await?;
+        let message = match activation {
+            Activation::Action(action) if action == ACCEPT_CALL_ACTION => Message::AcceptCall,
+            Activation::Action(action) if action == DECLINE_CALL_ACTION => Message::DeclineCall,
+            Activation::Clicked | Activation::Action(_) => Message::NativeNotificationClicked,
+        };
+        Some((message, activations))
     })))
 }
 
+// Announces an incoming call through the OS as well, in case the window is minimized or buried.
+// It can be accepted or declined right there.
+fn notify_incoming_call(ctx: &AppContext, sender: &str) {
+    let Some(window) = ctx.windows.main_handle else {
+        return;
+    };
+    if !ctx.config.native_notifications || native_notifications::is_foreground(window) {
+        return;
+    }
+
+    if let Some(notifier) = &ctx.notifier.native
+        && let Err(e) = notifier.show_call(
+            "Incoming call",
+            &format!("{} is calling you", sender),
+            &[
+                (ACCEPT_CALL_ACTION, tr!("home.accept_call")),
+                (DECLINE_CALL_ACTION, tr!("home.decline_call")),
+            ],
+        )
+    {
+        tracing::warn!("Failed to show incoming call notification: {}", e);
+    }
+    native_notifications::flash_taskbar(window);
+}
+
 impl Program for App {
     type State = State;
     type Message = Message;
@@ -130,41 +306,47 @@ impl Program for App {
     }
 
     fn window(&self) -> Option<window::Settings> {
-        Some(window::Settings { visible: true, transparent: true, ..Default::default() })
+        // Closing is handled in update, so the call can be torn down before exiting.
+        Some(window::Settings {
+            visible: true,
+            transparent: true,
+            exit_on_close_request: false,
+            ..Default::default()
+        })
     }
 
     fn boot(&self) -> (Self::State, Task<Self::Message>) {
-        const REMOTE_FRAMES_BUFFER: usize = 100;
-        const WEBRTC_EVENT_BUFFER: usize = 100;
-        let (frame_tx, frame_rx) = mpsc::channel(REMOTE_FRAMES_BUFFER);
-        let (event_tx, event_rx) = mpsc::channel(WEBRTC_EVENT_BUFFER);
-
-        let config = Config::load();
+        let (config, config_recovered) = Config::load();
         let server_url = config.server_url.clone();
+        i18n::set_language(config.language);
 
         let onboarding_done = config.onboarding_done;
 
-        // Clone for potential init task
-        let init_frame_tx = frame_tx.clone();
-        let init_event_tx = event_tx.clone();
-
-        let mut ctx = AppContext {
-            config,
-            main_window_handle: None,
-
-            back_queue: VecDeque::new(),
-
-            packet_tx: Some(frame_tx),
-            packet_rx: PacketReceiverRef(Arc::new(Mutex::new(frame_rx))),
-
-            webrtc_event_tx: Some(event_tx),
-            webrtc_event_rx: Some(Arc::new(Mutex::new(event_rx))),
-
-            webrtc: None,
-            target_id: None,
-
-            notifications: NotificationProvider::new(),
-        };
+        let mut ctx = AppContext::new(config, ConfigStore::user());
+        if let Some(peer) = &self.call_target {
+            ctx.call.open_call_link(peer.clone());
+        }
+        // Every start, so the links follow the executable when it is moved.
+        if let Err(e) = url_protocol::sync(ctx.config.call_links) {
+            tracing::warn!("Failed to update the call link registration: {}", e);
+        }
+        ctx.notifier.native = NativeNotifier::new()
+            .inspect_err(|e| tracing::warn!("Failed to set up OS notifications: {}", e))
+            .ok();
+        let history_recovered;
+        let consents_recovered;
+        (ctx.call_history, history_recovered) = CallHistory::load();
+        (ctx.consents, consents_recovered) = ConsentStore::load();
+        for from in [config_recovered, history_recovered, consents_recovered].into_iter().flatten()
+        {
+            // The copy is named after the file, with a suffix for what it is.
+            let file = from.with_extension("");
+            ctx.notifier.in_app.error(tr!(
+                "common.recovered",
+                file = file.display(),
+                copy = from.display()
+            ));
+        }
 
         let active_screen = if onboarding_done {
             ActiveScreen::Home(screens::home::HomeScreen::new(&mut ctx))
@@ -172,21 +354,8 @@ impl Program for App {
             ActiveScreen::Onboarding(screens::onboarding::OnboardingScreen::new(server_url.clone()))
         };
 
-        let init_task = if onboarding_done {
-            Task::future(async move {
-                WebRTC::init(
-                    server_url,
-                    init_frame_tx,
-                    init_event_tx,
-                    ctx.config.max_depacket_latency,
-                )
-                .await
-            })
-            .map_err(Arc::new)
-            .map(Message::WebRTCInitialized)
-        } else {
-            Task::none()
-        };
+        let init_task =
+            if onboarding_done { Self::connect(&mut ctx, server_url) } else { Task::none() };
 
         (State { ctx, active_screen }, init_task)
     }
@@ -197,30 +366,56 @@ impl Program for App {
             ActiveScreen::Home(screen) => screen.subscription(&state.ctx),
             ActiveScreen::Call(screen) => screen.subscription(&state.ctx),
             ActiveScreen::Settings(screen) => screen.subscription(&state.ctx),
+            ActiveScreen::Diagnostics(screen) => screen.subscription(&state.ctx),
         };
 
-        let frame_subscription =
-            Subscription::run_with(state.ctx.packet_rx.clone(), frame_subscription_stream);
-
-        let event_subscription = if let Some(rx) = &state.ctx.webrtc_event_rx {
-            Subscription::run_with(
-                WebRTCEventReceiverRef(rx.clone()),
+        // A new connection brings new receivers, which replace the subscription to the old ones.
+        let event_subscription = match &state.ctx.call.channels {
+            Some(channels) => Subscription::run_with(
+                WebRTCEventReceiverRef(channels.events()),
                 webrtc_event_subscription_stream,
-            )
-        } else {
-            Subscription::none()
+            ),
+            None => Subscription::none(),
+        };
+
+        let native_notification_subscription = match &state.ctx.notifier.native {
+            Some(notifier) => {
+                Subscription::run_with(notifier.clone(), native_notification_activation_stream)
+            }
+            None => Subscription::none(),
+        };
+
+        let instance_subscription = match &self.instance {
+            Some(instance) => Subscription::run_with(instance.clone(), instance_message_stream),
+            None => Subscription::none(),
         };
 
         let window_open_subscription = iced::window::open_events().map(Message::WindowOpened);
+        let window_close_subscription = iced::window::close_events().map(Message::WindowClosed);
+        let window_close_request_subscription =
+            iced::window::close_requests().map(Message::WindowCloseRequested);
         let tick_subscription =
             iced::time::every(std::time::Duration::from_millis(500)).map(Message::Tick);
 
+        // The panic hotkey is the way out when the peer takes over, so it works on every screen.
+        let panic_hotkey_subscription = if call_screen(state)
+            .is_some_and(|call| call.remote_control_allowed)
+        {
+            iced::time::every(Self::PANIC_HOTKEY_POLL_INTERVAL).map(|_| Message::PollPanicHotkey)
+        } else {
+            Subscription::none()
+        };
+
         Subscription::batch(vec![
             screen_subscriptions,
-            frame_subscription,
             event_subscription,
+            native_notification_subscription,
+            instance_subscription,
             window_open_subscription,
+            window_close_subscription,
+            window_close_request_subscription,
             tick_subscription,
+            panic_hotkey_subscription,
         ])
     }
 
@@ -231,33 +426,59 @@ impl Program for App {
                 ActiveScreen::Home(screen) => screen.update(&mut state.ctx, msg),
                 ActiveScreen::Call(screen) => screen.update(&mut state.ctx, msg),
                 ActiveScreen::Settings(screen) => screen.update(&mut state.ctx, msg),
+                ActiveScreen::Diagnostics(screen) => screen.update(&mut state.ctx, msg),
             };
             task
         }
 
-        fn screen_from_route(
-            state: &mut State,
-            capture: Arc<RwLock<PlatformCaptureProvider>>,
-            route: Route,
-        ) -> ActiveScreen {
-            match route {
-                Route::Home => ActiveScreen::Home(screens::home::HomeScreen::new(&mut state.ctx)),
-                Route::Call => ActiveScreen::Call(screens::call::CallScreen::new(capture)),
-                Route::Settings => ActiveScreen::Settings(screens::settings::SettingsScreen::new(
-                    state.ctx.config.clone(),
-                )),
-            }
+        // Stops the call in order, so the peer and the signaling server are told instead of timing out.
+        fn shutdown(state: &mut State) -> Task<Message> {
+            let call_shutdown = match &mut state.active_screen {
+                ActiveScreen::Call(screen) => Some(screen.shutdown(&state.ctx)),
+                _ => take_queued_call_screen(&mut state.ctx)
+                    .map(|mut screen| screen.shutdown(&state.ctx)),
+            };
+            let webrtc = state.ctx.call.webrtc.clone();
+
+            let teardown = async move {
+                if let Some(call_shutdown) = call_shutdown {
+                    call_shutdown.await;
+                }
+
+                if let Some(webrtc) = webrtc {
+                    if let Err(e) = webrtc.disconnect().await {
+                        tracing::error!("Failed to disconnect WebRTC: {}", e);
+                    }
+                    webrtc.close_signaling();
+                }
+            };
+
+            Task::future(async move {
+                if tokio::time::timeout(App::SHUTDOWN_TIMEOUT, teardown).await.is_err() {
+                    tracing::warn!(
+                        "Shutdown didn't finish within {:?}, exiting anyway",
+                        App::SHUTDOWN_TIMEOUT
+                    );
+                }
+                Message::ShutdownFinished
+            })
         }
 
         // Every message should be delegated to the active screen in the case that the active screen also wants to listen to it.
         // The exception being messages like Navigate.
         match message {
+            // Navigating to the call screen while on it would throw away the running call.
+            Message::Navigate(Route::Call) | Message::NavigateWithBack(Route::Call)
+                if matches!(state.active_screen, ActiveScreen::Call(_)) =>
+            {
+                Task::none()
+            }
             Message::Navigate(route) => {
-                state.active_screen = screen_from_route(state, self.capture.clone(), route);
+                state.active_screen = ActiveScreen::from_route(&mut state.ctx, route);
                 Task::none()
             }
             Message::NavigateWithBack(route) => {
-                let mut screen = screen_from_route(state, self.capture.clone(), route);
+                let mut screen = ActiveScreen::from_route(&mut state.ctx, route);
                 std::mem::swap(&mut state.active_screen, &mut screen);
                 // screen is not set to the old screen
 
@@ -272,53 +493,265 @@ impl Program for App {
             }
 
             Message::Tick(now) => {
-                state.ctx.notifications.dismiss_expired(now);
-                delegate_to_screen(state, message)
+                state.ctx.notifier.in_app.dismiss_expired(now);
+                let retry = if state.ctx.config.onboarding_done
+                    && state.ctx.call.webrtc.is_none()
+                    && !state.ctx.call.loopback
+                    && state.ctx.call.reconnect.is_due(now)
+                {
+                    tracing::info!(
+                        "Retrying connection to signaling server (attempt {}).",
+                        state.ctx.call.reconnect.failures() + 1
+                    );
+                    let server_url = state.ctx.config.server_url.clone();
+                    Self::connect(&mut state.ctx, server_url)
+                } else {
+                    Task::none()
+                };
+                Task::batch([retry, delegate_to_screen(state, message)])
+            }
+            Message::PollPanicHotkey => {
+                update_call_screen(state, |call, ctx| call.check_panic_hotkey(ctx))
+            }
+            Message::RetryConnection => {
+                let server_url = state.ctx.config.server_url.clone();
+                Self::connect(&mut state.ctx, server_url)
             }
             Message::DismissNotification(id) => {
-                state.ctx.notifications.dismiss(id);
+                state.ctx.notifier.in_app.dismiss(id);
                 delegate_to_screen(state, message)
             }
-            Message::WindowOpened(id) => Task::batch([
-                iced::window::raw_id::<Message>(id).map(Message::WindowIdFetched),
-                delegate_to_screen(state, message),
-            ]),
+            Message::NotificationAction(id, action) => {
+                state.ctx.notifier.in_app.dismiss(id);
+                Task::done(*action)
+            }
+            Message::NativeNotificationClicked => focus_main_window(&state.ctx),
+            // Connecting switches to the call screen, so that is all there is to it here.
+            Message::AcceptCall => {
+                let (Some(peer), Some(webrtc)) =
+                    (state.ctx.call.incoming_call.take(), state.ctx.call.webrtc.clone())
+                else {
+                    return Task::none();
+                };
+                tracing::info!("Accepting the call from {}", peer);
+                let accept_task = Task::future(async move {
+                    if let Err(e) = webrtc.accept_call().await {
+                        tracing::error!("Failed to accept the call: {}", e);
+                    }
+                    Message::NoOp
+                });
+                Task::batch([accept_task, focus_main_window(&state.ctx)])
+            }
+            Message::DeclineCall => {
+                let (Some(peer), Some(webrtc)) =
+                    (state.ctx.call.incoming_call.take(), state.ctx.call.webrtc.clone())
+                else {
+                    return Task::none();
+                };
+                tracing::info!("Declining the call from {}", peer);
+                state.ctx.call.target_id = None;
+                state.ctx.call.peer_mode = None;
+                Task::future(async move {
+                    if let Err(e) = webrtc.decline_call().await {
+                        tracing::error!("Failed to decline the call: {}", e);
+                    }
+                    Message::NoOp
+                })
+            }
+            Message::InstanceMessage(message) => {
+                tracing::info!("Another instance was started: {:?}", message);
+                if let InstanceMessage::Call(peer) = message {
+                    state.ctx.call.open_call_link(peer);
+                }
+                focus_main_window(&state.ctx)
+            }
+            Message::WindowOpened(id) => {
+                // The main window is always the first one to open.
+                if state.ctx.windows.main_id.is_none() {
+                    state.ctx.windows.main_id = Some(id);
+                }
+
+                Task::batch([
+                    iced::window::raw_id::<Message>(id).map(Message::WindowIdFetched),
+                    delegate_to_screen(state, message),
+                ])
+            }
+
+            Message::WindowClosed(id) => {
+                if state.ctx.windows.sharing_indicator == Some(id) {
+                    state.ctx.windows.sharing_indicator = None;
+                    return delegate_to_screen(state, message);
+                }
+                if state.ctx.windows.popout_id == Some(id) {
+                    // Only the pop-out closed, so the video just returns to the main window.
+                    state.ctx.windows.popout_id = None;
+                    state.ctx.windows.popout_handle = None;
+                    return delegate_to_screen(state, message);
+                }
+
+                // The app only exits once every window is gone, so take the others down with the main window.
+                let close_tasks = if state.ctx.windows.main_id == Some(id) {
+                    [state.ctx.windows.popout_id.take(), state.ctx.windows.sharing_indicator.take()]
+                        .into_iter()
+                        .flatten()
+                        .map(iced::window::close)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                Task::batch(close_tasks.into_iter().chain([delegate_to_screen(state, message)]))
+            }
+
+            Message::WindowCloseRequested(id) => {
+                if state.ctx.windows.main_id != Some(id) {
+                    return iced::window::close(id);
+                }
+
+                if state.ctx.shutting_down {
+                    tracing::warn!("Close requested again while shutting down, exiting now");
+                    return iced::exit();
+                }
+
+                tracing::info!("Shutting down...");
+                state.ctx.shutting_down = true;
+                shutdown(state)
+            }
+
+            Message::ShutdownFinished => {
+                tracing::info!("Shutdown finished.");
+                iced::exit()
+            }
 
             Message::WindowIdFetched(id) => {
-                if state.ctx.main_window_handle.is_none() {
-                    state.ctx.main_window_handle = Some(id);
+                if state.ctx.windows.main_handle.is_none() {
+                    state.ctx.windows.main_handle = Some(id);
                 }
                 delegate_to_screen(state, message)
             }
 
-            Message::PacketReceived(packet) => {
-                delegate_to_screen(state, Message::PacketReceived(packet))
+            Message::CaptureProviderReady(ref result) => {
+                match result {
+                    Ok(capture) => {
+                        tracing::info!("Capture provider initialized.");
+                        state.ctx.capture = CaptureProviderState::Ready(capture.clone());
+                    }
+                    Err(err) => {
+                        // Degraded mode, where screen sharing is unavailable but everything else works.
+                        tracing::error!("Failed to initialize capture provider: {}", err);
+                        state.ctx.capture = CaptureProviderState::Unavailable;
+                        state.ctx.notifier.in_app.error(
+                            "No graphics device could be initialized. Screen sharing is unavailable, but you can still view others.",
+                        );
+                    }
+                }
+                delegate_to_screen(state, message)
             }
 
-            Message::WebRTCInitialized(ref result) => match result.clone() {
-                Ok(webrtc) => {
-                    tracing::info!("WebRTC state initialized.");
-                    state.ctx.notifications.success("Successfully connected to signalling server.");
-                    state.ctx.webrtc = Some(webrtc);
-                    delegate_to_screen(state, message.clone())
-                }
+            // The connection being replaced is shut down first, so its tasks are gone before the new receivers are read.
+            Message::WebRTCInitialized(Ok(handle)) if state.ctx.call.webrtc.is_some() => {
+                let old = state.ctx.call.webrtc.take();
+                state.ctx.call.channels = None;
+                Task::future(async move {
+                    if let Some(old) = old
+                        && let Err(e) = old.shutdown().await
+                    {
+                        tracing::warn!("Failed to shut down the previous connection: {}", e);
+                    }
+                    Message::WebRTCInitialized(Ok(handle))
+                })
+            }
+
+            Message::WebRTCInitialized(ref result) => {
+                match result {
+                    Ok(handle) => {
+                        tracing::info!("WebRTC state initialized.");
+                        state
+                            .ctx
+                            .notifier
+                            .in_app
+                            .success("Successfully connected to signalling server.");
+                        let webrtc = handle.webrtc().clone();
+                        // The passphrase, the key exchange and the mode may have been set before the connection was made.
+                        webrtc.set_signaling_passphrase(&state.ctx.call.signaling_passphrase);
+                        webrtc.set_signaling_key_exchange(state.ctx.call.signaling_key_exchange);
+                        webrtc.set_call_mode(state.ctx.call.mode);
+                        state.ctx.call.webrtc = Some(webrtc);
+                        state.ctx.call.channels = Some(handle.channels().clone());
+                        state.ctx.call.connecting = false;
+                        state.ctx.call.reconnect.succeeded();
+                    }
 
-                Err(err) => {
-                    let err_msg = format!("Failed to initialize WebRTC: {}", err);
-                    tracing::error!(err_msg);
-                    state.ctx.notifications.error(err_msg);
-                    delegate_to_screen(state, message.clone())
+                    Err(err) => {
+                        state.ctx.call.connecting = false;
+                        let err_msg = format!("Failed to initialize WebRTC: {}", err);
+                        tracing::error!(err_msg);
+                        // Only the first failure is worth a notification, the retries after it are shown on the home screen.
+                        if state
+                            .ctx
+                            .call
+                            .reconnect
+                            .failed(std::time::Instant::now(), err.to_string())
+                        {
+                            state.ctx.notifier.in_app.error(err_msg);
+                        }
+                    }
                 }
-            },
+                delegate_to_screen(state, message)
+            }
 
-            Message::WebRTCEvent(ref event) => match event {
-                WebRTCEvent::IncomingCall(sender) => {
-                    tracing::info!("Incoming call from {}", sender);
+            // Like a new connection, the loopback call replaces the connection to the server once that is shut down.
+            Message::LoopbackStarted(Ok(handle)) if state.ctx.call.webrtc.is_some() => {
+                let old = state.ctx.call.webrtc.take();
+                state.ctx.call.channels = None;
+                Task::future(async move {
+                    if let Some(old) = old
+                        && let Err(e) = old.shutdown().await
+                    {
+                        tracing::warn!("Failed to shut down the previous connection: {}", e);
+                    }
+                    Message::LoopbackStarted(Ok(handle))
+                })
+            }
 
-                    //TODO: be able to accept or reject call
+            Message::LoopbackStarted(Ok(handle)) => {
+                tracing::info!("Calling ourselves over a loopback connection.");
+                state.ctx.call.webrtc = Some(handle.webrtc().clone());
+                state.ctx.call.channels = Some(handle.channels().clone());
+                state.ctx.call.remote_tracks.clear();
+                state.ctx.call.peer_mode = None;
+                state.ctx.call.target_id = Some(LOOPBACK_IDS[1].to_owned());
+                Task::done(Message::Navigate(Route::Call))
+                    .chain(Task::done(Message::Call(CallMessage::OpenSourcePicker)))
+            }
 
-                    state.ctx.target_id = Some(sender.clone());
+            Message::LoopbackStarted(Err(err)) => {
+                state.ctx.call.loopback = false;
+                tracing::error!("Failed to start the loopback call: {}", err);
+                state
+                    .ctx
+                    .notifier
+                    .in_app
+                    .error(format!("Failed to start the loopback test: {}", err));
+                Task::none()
+            }
+
+            Message::WebRTCEvent(_, ref event) => match event {
+                WebRTCEvent::IncomingCall(sender, mode) => {
+                    tracing::info!("Incoming call from {} ({:?})", sender, mode);
+                    notify_incoming_call(&state.ctx, sender);
+
+                    state.ctx.call.incoming_call = Some(sender.clone());
+                    state.ctx.call.target_id = Some(sender.clone());
+                    state.ctx.call.peer_mode = Some(*mode);
+
+                    delegate_to_screen(state, message)
+                }
 
+                WebRTCEvent::CallsCrossed(peer, mode) => {
+                    // Already ringing on the call screen, so no notification of an incoming call.
+                    tracing::info!("Calls with {} crossed, answering theirs", peer);
+                    state.ctx.call.peer_mode = Some(*mode);
                     delegate_to_screen(state, message)
                 }
 
@@ -326,16 +759,74 @@ impl Program for App {
                     tracing::info!("WebRTC Connected!");
 
                     if let ActiveScreen::Home(_) = state.active_screen {
-                        let call_screen = screens::call::CallScreen::new(self.capture.clone());
-
-                        state.active_screen = ActiveScreen::Call(call_screen);
+                        state.active_screen = ActiveScreen::from_route(&mut state.ctx, Route::Call);
                     }
 
                     delegate_to_screen(state, message)
                 }
 
+                WebRTCEvent::Answered(mode) => {
+                    tracing::info!("Call answered ({:?}), connecting...", mode);
+                    state.ctx.call.peer_mode = Some(*mode);
+                    delegate_to_screen(state, message)
+                }
+
+                WebRTCEvent::Interrupted => {
+                    tracing::warn!("WebRTC connection interrupted, reconnecting...");
+                    delegate_to_screen(state, message)
+                }
+
                 WebRTCEvent::Disconnected => {
                     tracing::info!("WebRTC Disconnected");
+                    // Also when the peer gave up before the call was answered.
+                    state.ctx.call.incoming_call = None;
+                    state.ctx.call.remote_tracks.clear();
+                    state.ctx.call.peer_mode = None;
+                    let screen_task = delegate_to_screen(state, message);
+                    if !state.ctx.call.loopback {
+                        return screen_task;
+                    }
+
+                    // The call to ourselves is over, so back to the signaling server.
+                    state.ctx.call.loopback = false;
+                    let loopback = state.ctx.call.webrtc.take();
+                    state.ctx.call.channels = None;
+                    let reconnect_task = Task::future(async move {
+                        if let Some(loopback) = loopback
+                            && let Err(e) = loopback.shutdown().await
+                        {
+                            tracing::warn!("Failed to shut down the loopback connection: {}", e);
+                        }
+                        Message::RetryConnection
+                    });
+                    Task::batch([screen_task, reconnect_task])
+                }
+
+                WebRTCEvent::TrackStarted { track, mime_type } => {
+                    tracing::info!("Remote {} started with codec {}", track, mime_type);
+                    // Kept, as the call screen may only be opened after the track started.
+                    state.ctx.call.remote_tracks.insert(*track, mime_type.clone());
+                    delegate_to_screen(state, message)
+                }
+
+                WebRTCEvent::TrackEnded { track } => {
+                    tracing::info!("Remote {} ended", track);
+                    state.ctx.call.remote_tracks.remove(track);
+                    if let Some(channels) = &state.ctx.call.channels {
+                        channels.close_track(*track);
+                    }
+                    delegate_to_screen(state, message)
+                }
+
+                WebRTCEvent::Control(_) => delegate_to_screen(state, message),
+
+                WebRTCEvent::SignalingRejected(reason) => {
+                    tracing::warn!("Rejected signaling from peer: {}", reason);
+                    state
+                        .ctx
+                        .notifier
+                        .in_app
+                        .error(format!("Could not set up the call: {}", reason));
                     delegate_to_screen(state, message)
                 }
             },
@@ -344,19 +835,69 @@ impl Program for App {
         }
     }
 
+    fn title(&self, state: &Self::State, window: window::Id) -> String {
+        if state.ctx.windows.popout_id == Some(window) {
+            format!("{} - Remote screen", Self::APP_TITLE)
+        } else if state.ctx.windows.sharing_indicator == Some(window) {
+            format!("{} - Sharing", Self::APP_TITLE)
+        } else {
+            Self::APP_TITLE.to_owned()
+        }
+    }
+
     fn view<'a>(
         &self,
         state: &'a Self::State,
-        _window: window::Id,
+        window: window::Id,
     ) -> Element<'a, Self::Message, Self::Theme, Self::Renderer> {
+        if state.ctx.windows.popout_id == Some(window) {
+            return match &state.active_screen {
+                ActiveScreen::Call(screen) => screen.view_popout(&state.ctx),
+                // The call screen is in the back queue (e.g. while in settings).
+                _ => iced::widget::container(iced::widget::text("Video paused").size(30))
+                    .center(iced::Length::Fill)
+                    .into(),
+            };
+        }
+
+        if state.ctx.windows.sharing_indicator == Some(window) {
+            return match &state.active_screen {
+                ActiveScreen::Call(screen) => screen.view_sharing_indicator(&state.ctx),
+                // The call screen is in the back queue, where it can't take the stop.
+                _ => iced::widget::container(iced::widget::text("Sharing your screen"))
+                    .center(iced::Length::Fill)
+                    .into(),
+            };
+        }
+
         let screen_content = match &state.active_screen {
             ActiveScreen::Onboarding(screen) => screen.view(&state.ctx),
             ActiveScreen::Home(screen) => screen.view(&state.ctx),
             ActiveScreen::Call(screen) => screen.view(&state.ctx),
             ActiveScreen::Settings(screen) => screen.view(&state.ctx),
+            ActiveScreen::Diagnostics(screen) => screen.view(&state.ctx),
         };
 
-        // Render notifications on a layer above the screen content
-        iced::widget::stack![screen_content, state.ctx.notifications.view()].into()
+        // Render notifications on a layer above the screen content, and a call ringing above that, whichever screen is shown
+        iced::widget::stack![
+            screen_content,
+            state.ctx.notifier.in_app.view(),
+            state.ctx.call.incoming_call.as_deref().map(incoming_call_view)
+        ]
+        .into()
     }
 }
+
+fn incoming_call_view(peer: &str) -> Element<'static, Message> {
+    confirm_dialog(
+        tr!("home.incoming_call_title", peer = peer),
+        tr!("home.incoming_call_body"),
+        [
+            iced::widget::button(tr!("home.accept_call")).on_press(Message::AcceptCall).into(),
+            iced::widget::button(tr!("home.decline_call"))
+                .style(iced::widget::button::danger)
+                .on_press(Message::DeclineCall)
+                .into(),
+        ],
+    )
+}
@@ -0,0 +1,27 @@
+use iced::{
+    Element, Length,
+    widget::{column, progress_bar, text},
+};
+
+use crate::tr;
+
+/// How much of the cap `sent` bits per second take up, from zero to one. Zero without a cap.
+pub fn cap_usage(sent: f64, cap: Option<u32>) -> f32 {
+    cap.filter(|&cap| cap > 0).map_or(0.0, |cap| (sent / cap as f64).clamp(0.0, 1.0) as f32)
+}
+
+/// What is being sent, against the bandwidth cap if there is one.
+pub fn bandwidth_meter<'a, Message: 'a>(sent: f64, cap: Option<u32>) -> Element<'a, Message> {
+    let mbps = |bits: f64| format!("{:.2}", bits / 1_000_000.0);
+    match cap {
+        Some(cap) => column![
+            text(tr!("bandwidth.sent_of_cap", sent = mbps(sent), cap = mbps(cap as f64))),
+            progress_bar(0.0..=1.0, cap_usage(sent, Some(cap)))
+                .length(Length::Fixed(200.0))
+                .girth(6),
+        ]
+        .spacing(4)
+        .into(),
+        None => text(tr!("bandwidth.sent", sent = mbps(sent))).into(),
+    }
+}
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, ui::call_summary::CallSummary, utils::atomic_file};
+
+/// The calls that connected, oldest first, kept across runs next to the config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CallHistory {
+    entries: Vec<CallSummary>,
+}
+
+impl CallHistory {
+    /// Older calls are dropped beyond this.
+    pub const MAX_ENTRIES: usize = 50;
+
+    fn path() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join("call_history.json"))
+    }
+
+    /// The history from the last runs, or an empty one if there is none or it can't be read,
+    /// and the copy it was recovered from if the file was broken.
+    pub fn load() -> (Self, Option<PathBuf>) {
+        match Self::path() {
+            Some(path) => atomic_file::load_json(&path).into_parts("call history"),
+            None => (Self::default(), None),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        match Self::path() {
+            Some(path) => atomic_file::save_json(&path, self),
+            None => Ok(()),
+        }
+    }
+
+    pub fn add(&mut self, summary: CallSummary) {
+        self.entries.push(summary);
+        let excess = self.entries.len().saturating_sub(Self::MAX_ENTRIES);
+        self.entries.drain(..excess);
+    }
+
+    pub fn entries(&self) -> &[CallSummary] {
+        &self.entries
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use crate::networking::webrtc::WebRTCEvent;
+
+/// Why a call ended, as told to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    /// The peer never answered, or the connection to them couldn't be made.
+    Unreachable,
+    /// The peer hung up.
+    PeerLeft,
+    /// The connection dropped, and couldn't be made again.
+    ConnectionLost,
+}
+
+impl Display for EndReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreachable => write!(f, "Couldn't reach the peer"),
+            Self::PeerLeft => write!(f, "The peer hung up"),
+            Self::ConnectionLost => write!(f, "The connection was lost"),
+        }
+    }
+}
+
+/// Where a call is at, from calling the peer until it ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallPhase {
+    /// Waiting for the peer to answer.
+    Ringing {
+        since: Instant,
+    },
+    /// Answered, and waiting for the connection to the peer.
+    Connecting {
+        since: Instant,
+    },
+    Connected {
+        since: Instant,
+    },
+    /// The connection dropped, and is being made again. Remembers when the call connected, as it carries on after.
+    Reconnecting {
+        since: Instant,
+        connected_since: Instant,
+    },
+    Ended(EndReason),
+}
+
+impl CallPhase {
+    /// The phase after the event, which is the same phase if the event doesn't change it.
+    pub fn next(self, event: &WebRTCEvent, now: Instant) -> Self {
+        match (self, event) {
+            (Self::Ended(_), _) => self,
+
+            (Self::Ringing { .. }, WebRTCEvent::Answered(_) | WebRTCEvent::CallsCrossed(..)) => {
+                Self::Connecting { since: now }
+            }
+            (Self::Ringing { .. } | Self::Connecting { .. }, WebRTCEvent::Connected) => {
+                Self::Connected { since: now }
+            }
+            (Self::Reconnecting { connected_since, .. }, WebRTCEvent::Connected) => {
+                Self::Connected { since: connected_since }
+            }
+
+            (Self::Connected { since }, WebRTCEvent::Interrupted) => {
+                Self::Reconnecting { since: now, connected_since: since }
+            }
+
+            (Self::Ringing { .. } | Self::Connecting { .. }, WebRTCEvent::Disconnected) => {
+                Self::Ended(EndReason::Unreachable)
+            }
+            (Self::Connected { .. }, WebRTCEvent::Disconnected) => Self::Ended(EndReason::PeerLeft),
+            (Self::Reconnecting { .. }, WebRTCEvent::Disconnected) => {
+                Self::Ended(EndReason::ConnectionLost)
+            }
+
+            _ => self,
+        }
+    }
+
+    /// How long the call has been in this phase, or connected for once connected.
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        match *self {
+            Self::Ringing { since }
+            | Self::Connecting { since }
+            | Self::Connected { since }
+            | Self::Reconnecting { connected_since: since, .. } => {
+                now.saturating_duration_since(since)
+            }
+            Self::Ended(_) => Duration::ZERO,
+        }
+    }
+
+    /// Whether the call can still be called off before it connects.
+    pub fn is_cancellable(&self) -> bool {
+        matches!(self, Self::Ringing { .. } | Self::Connecting { .. })
+    }
+}
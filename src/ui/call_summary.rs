@@ -0,0 +1,189 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::vector2::Vector2;
+
+/// What a call came to, shown once it ended and kept in the call history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallSummary {
+    pub peer: Option<String>,
+    pub ended_at: SystemTime,
+    /// From when the call first connected until it ended, including the time spent reconnecting.
+    pub duration: Duration,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// The frames sent per second while connected, if any were sent.
+    pub average_fps_sent: Option<f32>,
+    pub average_fps_received: Option<f32>,
+    /// The largest video sent or received.
+    pub peak_resolution: Option<Vector2<i32>>,
+    pub reconnects: u32,
+    /// Whether either peer recorded any of the call, as far as the peer's recording was told.
+    #[serde(default)]
+    pub was_recorded: bool,
+}
+
+impl CallSummary {
+    /// Bits per second sent and received together, over the whole call.
+    pub fn average_bitrate(&self) -> f64 {
+        let seconds = self.duration.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        (self.bytes_sent + self.bytes_received) as f64 * 8.0 / seconds
+    }
+
+    /// The figures as labels and values, in the order they are shown.
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        let fps = |fps: Option<f32>| fps.map_or("-".to_owned(), |fps| format!("{:.0} fps", fps));
+        vec![
+            ("Duration", format_duration(self.duration)),
+            ("Sent", format_bytes(self.bytes_sent)),
+            ("Received", format_bytes(self.bytes_received)),
+            ("Average bitrate", format!("{:.2} Mbps", self.average_bitrate() / 1_000_000.0)),
+            ("Frames sent", fps(self.average_fps_sent)),
+            ("Frames received", fps(self.average_fps_received)),
+            (
+                "Peak resolution",
+                self.peak_resolution
+                    .map_or("-".to_owned(), |size| format!("{}x{}", size.x, size.y)),
+            ),
+            ("Reconnects", self.reconnects.to_string()),
+            ("Recorded", if self.was_recorded { "Yes" } else { "No" }.to_owned()),
+        ]
+    }
+}
+
+/// As h:mm:ss, or m:ss under an hour.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match hours {
+        0 => format!("{}:{:02}", minutes, seconds),
+        hours => format!("{}:{:02}:{:02}", hours, minutes, seconds),
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1_000_000 => format!("{:.1} kB", bytes as f64 / 1_000.0),
+        1_000_000..1_000_000_000 => format!("{:.1} MB", bytes as f64 / 1_000_000.0),
+        _ => format!("{:.2} GB", bytes as f64 / 1_000_000_000.0),
+    }
+}
+
+// Sums up a counter that starts over with a new connection, track or worker.
+// A reset is only noticed if the counter reads lower than before, so what was counted since then until it caught up is missed.
+#[derive(Debug, Clone, Copy, Default)]
+struct Counter {
+    last: u64,
+    total: u64,
+}
+
+impl Counter {
+    fn record(&mut self, value: u64) {
+        self.total += value.checked_sub(self.last).unwrap_or(value);
+        self.last = value;
+    }
+}
+
+/// Sums up the stats of a call as they come in, for its [`CallSummary`].
+/// Time spent reconnecting counts towards the duration, but not the averages.
+#[derive(Debug, Clone, Default)]
+pub struct CallSummaryRecorder {
+    first_connected_at: Option<Instant>,
+    // Since when the call has been connected without interruption. None while it isn't.
+    connected_since: Option<Instant>,
+    connected_time: Duration,
+    reconnects: u32,
+    bytes_sent: Counter,
+    bytes_received: Counter,
+    frames_sent: Counter,
+    frames_received: Counter,
+    peak_resolution: Option<Vector2<i32>>,
+    was_recorded: bool,
+}
+
+impl CallSummaryRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connected(&mut self, now: Instant) {
+        self.first_connected_at.get_or_insert(now);
+        self.connected_since.get_or_insert(now);
+    }
+
+    /// The connection dropped, and is being made again.
+    pub fn interrupted(&mut self, now: Instant) {
+        if self.pause(now) {
+            self.reconnects += 1;
+        }
+    }
+
+    // Returns whether it was connected.
+    fn pause(&mut self, now: Instant) -> bool {
+        let Some(since) = self.connected_since.take() else {
+            return false;
+        };
+        self.connected_time += now.saturating_duration_since(since);
+        true
+    }
+
+    /// The bytes sent and received over the connection so far.
+    pub fn record_bytes(&mut self, sent: u64, received: u64) {
+        self.bytes_sent.record(sent);
+        self.bytes_received.record(received);
+    }
+
+    /// The frames encoded so far.
+    pub fn record_frames_sent(&mut self, total: u64) {
+        self.frames_sent.record(total);
+    }
+
+    /// The frames decoded so far.
+    pub fn record_frames_received(&mut self, total: u64) {
+        self.frames_received.record(total);
+    }
+
+    pub fn record_resolution(&mut self, size: Vector2<i32>) {
+        let area = |size: Vector2<i32>| size.x as i64 * size.y as i64;
+        if self.peak_resolution.is_none_or(|peak| area(size) > area(peak)) {
+            self.peak_resolution = Some(size);
+        }
+    }
+
+    /// Either peer started recording the call.
+    pub fn recorded(&mut self) {
+        self.was_recorded = true;
+    }
+
+    /// The summary of the call, which ended at `now`. None if it never connected.
+    pub fn finish(
+        mut self,
+        peer: Option<String>,
+        now: Instant,
+        ended_at: SystemTime,
+    ) -> Option<CallSummary> {
+        let first_connected_at = self.first_connected_at?;
+        self.pause(now);
+
+        let seconds = self.connected_time.as_secs_f64();
+        let average_fps = |frames: Counter| {
+            (frames.total > 0 && seconds > 0.0).then(|| (frames.total as f64 / seconds) as f32)
+        };
+        Some(CallSummary {
+            peer,
+            ended_at,
+            duration: now.saturating_duration_since(first_connected_at),
+            bytes_sent: self.bytes_sent.total,
+            bytes_received: self.bytes_received.total,
+            average_fps_sent: average_fps(self.frames_sent),
+            average_fps_received: average_fps(self.frames_received),
+            peak_resolution: self.peak_resolution,
+            reconnects: self.reconnects,
+            was_recorded: self.was_recorded,
+        })
+    }
+}
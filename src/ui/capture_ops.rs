@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+
+/// Something to do with the capture provider, which waits for its lock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureOp<T> {
+    /// Starts capturing the item, or switches the running capture over to it.
+    Start(T),
+    Stop,
+    /// Gets an interrupted capture going again, on the same source.
+    Recover,
+}
+
+impl<T> CaptureOp<T> {
+    pub fn kind(&self) -> CaptureOpKind {
+        match self {
+            Self::Start(_) => CaptureOpKind::Start,
+            Self::Stop => CaptureOpKind::Stop,
+            Self::Recover => CaptureOpKind::Recover,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureOpKind {
+    Start,
+    Stop,
+    Recover,
+}
+
+/// Tells the result of the pending operation apart from one that was superseded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CaptureOpId(u64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureOpDecision<T> {
+    /// Run it now. The operation pending before, if any, is superseded and its result ignored.
+    Run(CaptureOpId, CaptureOp<T>),
+    /// Runs once the pending stop finished, unless something else is asked for first.
+    Queued,
+    /// The same is pending already.
+    Ignored,
+}
+
+/// Which capture operation is pending, so one at a time is run and conflicting requests are settled the same way every time:
+/// the latest start wins over an earlier one, a stop wins over a start, and a start waits for a stop.
+/// A recovery is like a start of the same source, but there is nothing to recover once stopping.
+#[derive(Debug, Clone)]
+pub struct CaptureOps<T> {
+    pending: Option<(CaptureOpId, CaptureOpKind)>,
+    queued: Option<T>,
+    next_id: u64,
+}
+
+impl<T> Default for CaptureOps<T> {
+    fn default() -> Self {
+        Self { pending: None, queued: None, next_id: 0 }
+    }
+}
+
+impl<T> CaptureOps<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pending(&self) -> Option<CaptureOpKind> {
+        self.pending.map(|(_, kind)| kind)
+    }
+
+    pub fn request(&mut self, op: CaptureOp<T>) -> CaptureOpDecision<T> {
+        match (self.pending(), op) {
+            // Stopping after it anyway is what the user asked for last.
+            (Some(CaptureOpKind::Stop), CaptureOp::Stop) => {
+                self.queued = None;
+                CaptureOpDecision::Ignored
+            }
+            (Some(CaptureOpKind::Stop), CaptureOp::Start(item)) => {
+                self.queued = Some(item);
+                CaptureOpDecision::Queued
+            }
+            (Some(CaptureOpKind::Stop), CaptureOp::Recover) => CaptureOpDecision::Ignored,
+            (_, op) => {
+                self.queued = None;
+                self.run(op)
+            }
+        }
+    }
+
+    /// Marks the operation as done. Returns its kind, or None if it was superseded, so its result doesn't matter anymore.
+    pub fn finish(&mut self, id: CaptureOpId) -> Option<CaptureOpKind> {
+        match self.pending {
+            Some((pending, kind)) if pending == id => {
+                self.pending = None;
+                Some(kind)
+            }
+            _ => None,
+        }
+    }
+
+    /// The queued start, to run now that nothing is pending anymore.
+    pub fn take_queued(&mut self) -> Option<(CaptureOpId, CaptureOp<T>)> {
+        if self.pending.is_some() {
+            return None;
+        }
+        let item = self.queued.take()?;
+        match self.run(CaptureOp::Start(item)) {
+            CaptureOpDecision::Run(id, op) => Some((id, op)),
+            _ => None,
+        }
+    }
+
+    fn run(&mut self, op: CaptureOp<T>) -> CaptureOpDecision<T> {
+        let id = CaptureOpId(self.next_id);
+        self.next_id += 1;
+        self.pending = Some((id, op.kind()));
+        CaptureOpDecision::Run(id, op)
+    }
+}
+
+/// Runs `op` once the lock is free. None if that took longer than `timeout`, e.g. as whatever holds it is stuck.
+pub async fn with_write_lock<T, R>(
+    lock: &RwLock<T>,
+    timeout: Duration,
+    op: impl FnOnce(&mut T) -> R,
+) -> Option<R> {
+    let mut guard = tokio::time::timeout(timeout, lock.write()).await.ok()?;
+    Some(op(&mut guard))
+}
@@ -0,0 +1,22 @@
+use iced::{
+    Background, Color, Element, Length,
+    widget::{column, container, opaque, row, text},
+};
+
+/// Asks the user to pick one of the buttons, over the rest of the screen which can't be used until they did.
+pub fn confirm_dialog<'a, Message: 'a>(
+    title: impl text::IntoFragment<'a>,
+    body: impl text::IntoFragment<'a>,
+    buttons: impl IntoIterator<Item = Element<'a, Message>>,
+) -> Element<'a, Message> {
+    let dialog =
+        container(column![text(title).size(20), text(body), row(buttons).spacing(10)].spacing(15))
+            .padding(20)
+            .max_width(480)
+            .style(container::rounded_box);
+
+    opaque(container(dialog).center(Length::Fill).style(|_| container::Style {
+        background: Some(Background::Color(Color { a: 0.6, ..Color::BLACK })),
+        ..container::Style::default()
+    }))
+}
@@ -0,0 +1,167 @@
+use std::{
+    fmt::Display,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, utils::atomic_file};
+
+/// What a peer can ask of us, which the user has to agree to first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ConsentKind {
+    RemoteControl,
+}
+
+impl ConsentKind {
+    /// What the peer can do once it is agreed to, for the user to know what they agree to.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::RemoteControl => {
+                "move your mouse, click and scroll on what you share, until you revoke it with Ctrl+Alt+End"
+            }
+        }
+    }
+}
+
+impl Display for ConsentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RemoteControl => write!(f, "remote control"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsentDecision {
+    Accepted,
+    Declined,
+    /// A standing grant was taken back.
+    Revoked,
+}
+
+/// A decision of the user, kept as a trail of who was let do what and when.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsentRecord {
+    pub peer: String,
+    pub kind: ConsentKind,
+    pub decision: ConsentDecision,
+    pub at: SystemTime,
+    /// Whether it was accepted by a standing grant, without asking.
+    pub standing: bool,
+}
+
+/// Lets a peer do something without asking each time, until revoked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StandingGrant {
+    pub peer: String,
+    pub kind: ConsentKind,
+    pub granted_at: SystemTime,
+}
+
+/// The standing grants and the decisions made, kept across runs next to the config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsentStore {
+    grants: Vec<StandingGrant>,
+    /// Oldest first.
+    records: Vec<ConsentRecord>,
+}
+
+impl ConsentStore {
+    /// Older records are dropped beyond this. Grants are kept until revoked.
+    pub const MAX_RECORDS: usize = 1000;
+
+    fn path() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join("consents.json"))
+    }
+
+    /// The consents from the last runs, or none if there are none or they can't be read,
+    /// and the copy they were recovered from if the file was broken.
+    pub fn load() -> (Self, Option<PathBuf>) {
+        match Self::path() {
+            Some(path) => atomic_file::load_json(&path).into_parts("consents"),
+            None => (Self::default(), None),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        match Self::path() {
+            Some(path) => atomic_file::save_json(&path, self),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_granted(&self, peer: &str, kind: ConsentKind) -> bool {
+        self.grants.iter().any(|grant| grant.peer == peer && grant.kind == kind)
+    }
+
+    /// Whether the peer has a standing grant for it, in which case it is recorded as accepted.
+    pub fn accept_standing(&mut self, peer: &str, kind: ConsentKind, at: SystemTime) -> bool {
+        if !self.is_granted(peer, kind) {
+            return false;
+        }
+        self.record(peer, kind, ConsentDecision::Accepted, at, true);
+        true
+    }
+
+    /// Records what the user decided when asked. Accepting with `always` lets the peer do it from then on.
+    pub fn decide(
+        &mut self,
+        peer: &str,
+        kind: ConsentKind,
+        accepted: bool,
+        always: bool,
+        at: SystemTime,
+    ) {
+        let decision = if accepted { ConsentDecision::Accepted } else { ConsentDecision::Declined };
+        self.record(peer, kind, decision, at, false);
+        if accepted && always && !self.is_granted(peer, kind) {
+            self.grants.push(StandingGrant { peer: peer.to_owned(), kind, granted_at: at });
+        }
+    }
+
+    /// Takes back the standing grant, if the peer has one.
+    pub fn revoke(&mut self, peer: &str, kind: ConsentKind, at: SystemTime) -> bool {
+        let before = self.grants.len();
+        self.grants.retain(|grant| grant.peer != peer || grant.kind != kind);
+        if self.grants.len() == before {
+            return false;
+        }
+        self.record(peer, kind, ConsentDecision::Revoked, at, false);
+        true
+    }
+
+    fn record(
+        &mut self,
+        peer: &str,
+        kind: ConsentKind,
+        decision: ConsentDecision,
+        at: SystemTime,
+        standing: bool,
+    ) {
+        self.records.push(ConsentRecord { peer: peer.to_owned(), kind, decision, at, standing });
+        let excess = self.records.len().saturating_sub(Self::MAX_RECORDS);
+        self.records.drain(..excess);
+    }
+
+    pub fn grants(&self) -> &[StandingGrant] {
+        &self.grants
+    }
+
+    pub fn records(&self) -> &[ConsentRecord] {
+        &self.records
+    }
+}
+
+/// How long ago something was, roughly, e.g. "3 days ago".
+pub fn format_age(age: Duration) -> String {
+    let (count, unit) = match age.as_secs() {
+        seconds if seconds < 60 => return "just now".to_owned(),
+        seconds if seconds < 3600 => (seconds / 60, "minute"),
+        seconds if seconds < 86_400 => (seconds / 3600, "hour"),
+        seconds => (seconds / 86_400, "day"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
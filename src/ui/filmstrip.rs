@@ -0,0 +1,250 @@
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use iced::widget::image;
+
+use crate::{
+    config::Config,
+    utils::{bitmap_utils::encode_jpeg, frame::Frame, pixel_format::PixelFormat, vector2::Vector2},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum FilmstripError {
+    #[error("Only RGBA8 frames in memory can be saved, not {0:?}")]
+    UnsupportedFrame(PixelFormat),
+    #[error("Failed to encode snapshot: {0}")]
+    Encode(#[from] ::image::ImageError),
+    #[error("Failed to write {0}: {1}")]
+    Write(PathBuf, io::Error),
+}
+
+/// Where the snapshots of every call are saved, in a folder for each.
+pub fn dir() -> Option<PathBuf> {
+    Config::dir().map(|dir| dir.join("filmstrip"))
+}
+
+/// The name of the folder the snapshots of a call go into, from who it is with and when it started, in UTC.
+/// Whatever in the peer's ID can't be in a file name is replaced.
+pub fn session_folder_name(peer: &str, started: SystemTime) -> String {
+    let peer: String = peer
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let secs = started.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (year, month, day) = civil_date(secs / 86_400);
+    format!("{} {:04}-{:02}-{:02} {}", peer, year, month, day, clock(secs % 86_400))
+}
+
+/// The name of a snapshot taken this far into the call, which sorts them in the order they were taken.
+pub fn snapshot_name(offset: Duration) -> String {
+    format!("{}.jpg", clock(offset.as_secs()))
+}
+
+fn clock(secs: u64) -> String {
+    format!("{:02}-{:02}-{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// The year, month and day of the day this many days after 1970-01-01, by Howard Hinnant's `civil_from_days`.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Counted from March, so the leap day is the last of the year.
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    (year_of_era + era * 400 + (month <= 2) as u64, month, day)
+}
+
+/// Keeps the snapshots of a call within a budget of disk space, by deleting the oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotBudget {
+    budget: u64,
+    used: u64,
+    // Oldest first, with their sizes.
+    snapshots: VecDeque<(PathBuf, u64)>,
+}
+
+impl SnapshotBudget {
+    pub fn new(budget: u64) -> Self {
+        Self { budget, ..Self::default() }
+    }
+
+    /// Counts a snapshot that was saved, and returns the ones to delete to make room for it, oldest first.
+    /// The newest is always kept, even if it alone is over the budget.
+    pub fn add(&mut self, path: PathBuf, bytes: u64) -> Vec<PathBuf> {
+        self.used += bytes;
+        self.snapshots.push_back((path, bytes));
+        self.evict()
+    }
+
+    /// Changes the budget, and returns the snapshots to delete if it shrank.
+    pub fn set_budget(&mut self, budget: u64) -> Vec<PathBuf> {
+        self.budget = budget;
+        self.evict()
+    }
+
+    fn evict(&mut self) -> Vec<PathBuf> {
+        let mut evicted = Vec::new();
+        while self.used > self.budget && self.snapshots.len() > 1 {
+            let (path, bytes) = self.snapshots.pop_front().expect("more than one snapshot");
+            self.used -= bytes;
+            evicted.push(path);
+        }
+        evicted
+    }
+
+    /// The bytes the kept snapshots take up.
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+/// A snapshot on disk, with a thumbnail to show it in the strip by.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub thumbnail: image::Handle,
+}
+
+impl Snapshot {
+    /// What snapshots are scaled down to fit, which is plenty to tell what was shown.
+    pub const MAX_SIZE: Vector2<i32> = Vector2 { x: 1280, y: 720 };
+    pub const THUMBNAIL_SIZE: Vector2<i32> = Vector2 { x: 128, y: 72 };
+    const QUALITY: u8 = 80;
+
+    /// Scales the frame down and saves it to `path` as a JPEG, creating its folder if need be.
+    /// Blocks while it encodes and writes.
+    pub fn save(frame: &Frame, path: PathBuf) -> Result<Self, FilmstripError> {
+        if frame.format != PixelFormat::RGBA8 || frame.gpu.is_some() {
+            return Err(FilmstripError::UnsupportedFrame(frame.format));
+        }
+
+        let scaled = frame.downscaled(Self::MAX_SIZE);
+        let frame = scaled.as_ref().unwrap_or(frame);
+        let jpeg = encode_jpeg(&frame.data, frame.size, Self::QUALITY)?;
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).map_err(|e| FilmstripError::Write(folder.to_owned(), e))?;
+        }
+        fs::write(&path, &jpeg).map_err(|e| FilmstripError::Write(path.clone(), e))?;
+
+        let thumbnail = frame.downscaled(Self::THUMBNAIL_SIZE);
+        let thumbnail = thumbnail.as_ref().unwrap_or(frame);
+        Ok(Self {
+            path,
+            bytes: jpeg.len() as u64,
+            thumbnail: image::Handle::from_rgba(
+                thumbnail.size.x as u32,
+                thumbnail.size.y as u32,
+                thumbnail.data.to_vec(),
+            ),
+        })
+    }
+}
+
+/// Snapshots of the remote video, saved every so often while viewing, to look back at what was shown.
+#[derive(Debug, Clone)]
+pub struct Filmstrip {
+    folder: PathBuf,
+    started: Instant,
+    // When the last snapshot was taken, and whether it is still being saved.
+    last: Option<Instant>,
+    saving: bool,
+    budget: SnapshotBudget,
+    // The latest, oldest first.
+    shown: VecDeque<Snapshot>,
+    // The one shown at full size.
+    selected: Option<PathBuf>,
+}
+
+impl Filmstrip {
+    /// How many thumbnails the strip holds. Older snapshots stay on disk, within the budget.
+    pub const SHOWN: usize = 8;
+
+    pub fn new(folder: PathBuf, started: Instant, budget: u64) -> Self {
+        Self {
+            folder,
+            started,
+            last: None,
+            saving: false,
+            budget: SnapshotBudget::new(budget),
+            shown: VecDeque::new(),
+            selected: None,
+        }
+    }
+
+    pub fn folder(&self) -> &Path {
+        &self.folder
+    }
+
+    /// Whether it is time for the next snapshot. Not until the last one is saved, so a slow disk doesn't pile them up.
+    pub fn due(&self, now: Instant, interval: Duration) -> bool {
+        !self.saving && self.last.is_none_or(|last| now.saturating_duration_since(last) >= interval)
+    }
+
+    /// Takes note of a snapshot being taken, and returns where to save it.
+    pub fn take(&mut self, now: Instant) -> PathBuf {
+        self.last = Some(now);
+        self.saving = true;
+        self.folder.join(snapshot_name(now.saturating_duration_since(self.started)))
+    }
+
+    /// Adds a snapshot that was saved to the strip, and returns the ones to delete to stay within the budget.
+    pub fn saved(&mut self, snapshot: Snapshot) -> Vec<PathBuf> {
+        self.saving = false;
+        let evicted = self.budget.add(snapshot.path.clone(), snapshot.bytes);
+        self.shown.push_back(snapshot);
+        if self.shown.len() > Self::SHOWN {
+            self.shown.pop_front();
+        }
+        self.forget(&evicted);
+        evicted
+    }
+
+    /// Tries again with the next one.
+    pub fn failed(&mut self) {
+        self.saving = false;
+    }
+
+    /// Changes the budget, and returns the snapshots to delete if it shrank.
+    pub fn set_budget(&mut self, budget: u64) -> Vec<PathBuf> {
+        let evicted = self.budget.set_budget(budget);
+        self.forget(&evicted);
+        evicted
+    }
+
+    fn forget(&mut self, evicted: &[PathBuf]) {
+        self.shown.retain(|snapshot| !evicted.contains(&snapshot.path));
+        if self.selected.as_ref().is_some_and(|selected| evicted.contains(selected)) {
+            self.selected = None;
+        }
+    }
+
+    pub fn snapshots(&self) -> impl Iterator<Item = &Snapshot> {
+        self.shown.iter()
+    }
+
+    pub fn selected(&self) -> Option<&Path> {
+        self.selected.as_deref()
+    }
+
+    pub fn select(&mut self, path: Option<PathBuf>) {
+        self.selected = path;
+    }
+}
@@ -1,28 +1,157 @@
 use std::sync::Arc;
 
+use fjarsyn_shared::{CursorPosition, InputEvent, MouseButton};
 use iced::{
-    Element, Length, Rectangle, Size, advanced,
+    Border, Color, Element, Event, Length, Point, Rectangle, Shadow, Size, advanced,
     advanced::{
-        Widget,
+        Clipboard, Shell, Widget,
         layout::{self, Layout},
         mouse, renderer,
         widget::Tree,
     },
 };
 
-use crate::utils::frame::Frame;
+use crate::utils::{frame::Frame, rect::Rect};
 
-pub struct FrameViewer {
+pub struct FrameViewer<'a, Message> {
     frame: Arc<Frame>,
+    cursor: Option<CursorPosition>,
+    regions: Vec<Rect<f32>>,
+    on_input: Option<Box<dyn Fn(InputEvent) -> Message + 'a>>,
 }
 
-impl FrameViewer {
+impl<'a, Message> FrameViewer<'a, Message> {
+    // Radius of the synthetic cursor at the frame's native size.
+    const CURSOR_RADIUS: f32 = 6.0;
+    const MIN_CURSOR_RADIUS: f32 = 3.0;
+    // Used to convert pixel based scrolling (e.g. touchpads) into lines.
+    const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+
     pub fn new(frame: Arc<Frame>) -> Self {
-        Self { frame }
+        Self { frame, cursor: None, regions: Vec::new(), on_input: None }
+    }
+
+    /// Reports mouse input over the frame, with positions normalized to the frame.
+    pub fn on_input(mut self, on_input: impl Fn(InputEvent) -> Message + 'a) -> Self {
+        self.on_input = Some(Box::new(on_input));
+        self
+    }
+
+    fn to_input_event(
+        event: &Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Option<InputEvent> {
+        fn to_remote_button(button: mouse::Button) -> Option<MouseButton> {
+            match button {
+                mouse::Button::Left => Some(MouseButton::Left),
+                mouse::Button::Right => Some(MouseButton::Right),
+                mouse::Button::Middle => Some(MouseButton::Middle),
+                _ => None,
+            }
+        }
+
+        let Event::Mouse(event) = event else {
+            return None;
+        };
+
+        // Releases are always forwarded, so buttons don't get stuck when released outside the frame.
+        if let mouse::Event::ButtonReleased(button) = event {
+            return Some(InputEvent::MouseButton {
+                button: to_remote_button(*button)?,
+                pressed: false,
+            });
+        }
+
+        let position = cursor.position_in(bounds)?;
+        match event {
+            mouse::Event::CursorMoved { .. } => Some(InputEvent::MouseMove(CursorPosition {
+                x: position.x / bounds.width,
+                y: position.y / bounds.height,
+            })),
+            mouse::Event::ButtonPressed(button) => {
+                Some(InputEvent::MouseButton { button: to_remote_button(*button)?, pressed: true })
+            }
+            mouse::Event::WheelScrolled { delta } => {
+                let (x, y) = match *delta {
+                    mouse::ScrollDelta::Lines { x, y } => (x, y),
+                    mouse::ScrollDelta::Pixels { x, y } => {
+                        (x / Self::PIXELS_PER_SCROLL_LINE, y / Self::PIXELS_PER_SCROLL_LINE)
+                    }
+                };
+                Some(InputEvent::MouseScroll { x, y })
+            }
+            _ => None,
+        }
+    }
+
+    /// Draws a synthetic cursor over the frame, at a position normalized to the frame.
+    pub fn with_cursor(mut self, cursor: Option<CursorPosition>) -> Self {
+        self.cursor = cursor;
+        self
+    }
+
+    /// Marks regions over the frame, normalized to the frame, e.g. the parts of it kept private.
+    pub fn with_regions(mut self, regions: Vec<Rect<f32>>) -> Self {
+        self.regions = regions;
+        self
+    }
+
+    fn draw_regions<Renderer: advanced::Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        bounds: Rectangle,
+    ) {
+        let fill = Color { a: 0.5, ..Color::BLACK };
+        let border =
+            Border { color: Color::from_rgb(1.0, 0.3, 0.3), width: 1.5, radius: 0.0.into() };
+        renderer.with_layer(bounds, |renderer| {
+            for region in &self.regions {
+                let quad = renderer::Quad {
+                    bounds: Rectangle::new(
+                        Point::new(
+                            bounds.x + region.position.x * bounds.width,
+                            bounds.y + region.position.y * bounds.height,
+                        ),
+                        Size::new(region.size.x * bounds.width, region.size.y * bounds.height),
+                    ),
+                    border,
+                    shadow: Shadow::default(),
+                    snap: true,
+                };
+                renderer.fill_quad(quad, fill);
+            }
+        });
+    }
+
+    fn draw_cursor<Renderer: advanced::Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        bounds: Rectangle,
+        cursor: CursorPosition,
+    ) {
+        // Follow the same fit transform as the frame itself.
+        let scale = bounds.width / self.frame.size.x as f32;
+        let radius = (Self::CURSOR_RADIUS * scale).max(Self::MIN_CURSOR_RADIUS);
+        let center =
+            Point::new(bounds.x + cursor.x * bounds.width, bounds.y + cursor.y * bounds.height);
+
+        let quad = renderer::Quad {
+            bounds: Rectangle::new(
+                Point::new(center.x - radius, center.y - radius),
+                Size::new(radius * 2.0, radius * 2.0),
+            ),
+            border: Border { color: Color::BLACK, width: 1.5, radius: radius.into() },
+            shadow: Shadow::default(),
+            snap: false,
+        };
+
+        // Draw on a new layer, as images are drawn above quads within the same layer.
+        renderer.with_layer(bounds, |renderer| renderer.fill_quad(quad, Color::WHITE));
     }
 }
 
-impl<Theme, Message, Renderer> Widget<Message, Theme, Renderer> for FrameViewer
+impl<Theme, Message, Renderer> Widget<Message, Theme, Renderer> for FrameViewer<'_, Message>
 where
     Renderer: iced::advanced::image::Renderer<Handle = iced::advanced::image::Handle>,
 {
@@ -83,15 +212,60 @@ where
         let img = iced_core::Image::new(alloc.handle());
         let bounds = layout.bounds();
         renderer.draw_image(img, bounds, bounds);
+
+        if !self.regions.is_empty() {
+            self.draw_regions(renderer, bounds);
+        }
+
+        if let Some(cursor) = self.cursor {
+            self.draw_cursor(renderer, bounds, cursor);
+        }
+    }
+
+    fn update(
+        &mut self,
+        _tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) {
+        let Some(on_input) = &self.on_input else {
+            return;
+        };
+
+        if let Some(input) = Self::to_input_event(event, layout.bounds(), cursor) {
+            shell.publish(on_input(input));
+            shell.capture_event();
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        _tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        if self.on_input.is_some() && cursor.is_over(layout.bounds()) {
+            mouse::Interaction::Crosshair
+        } else {
+            mouse::Interaction::None
+        }
     }
 }
 
-impl<'a, Message, Theme, Renderer> From<FrameViewer> for Element<'a, Message, Theme, Renderer>
+impl<'a, Message, Theme, Renderer> From<FrameViewer<'a, Message>>
+    for Element<'a, Message, Theme, Renderer>
 where
     Renderer: iced::advanced::image::Renderer<Handle = iced::advanced::image::Handle>,
     Message: 'a,
 {
-    fn from(widget: FrameViewer) -> Self {
+    fn from(widget: FrameViewer<'a, Message>) -> Self {
         Self::new(widget)
     }
 }
@@ -0,0 +1,201 @@
+//! The UI strings in each of the bundled locales, looked up by key with [`tr!`](crate::tr).
+//! A locale is a resource of `key = value` lines, with `{name}` where a parameter goes.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        OnceLock,
+        atomic::{AtomicU8, Ordering},
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The locale the strings are looked up in, as an index into [`Locale::ALL`].
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+/// A locale whose strings are bundled with the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Locale {
+    English,
+    Icelandic,
+}
+
+impl Locale {
+    /// English comes first, as it has every string the others fall back to.
+    pub const ALL: [Locale; 2] = [Locale::English, Locale::Icelandic];
+
+    /// The language code, e.g. "en".
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::Icelandic => "is",
+        }
+    }
+
+    /// The locale of a language tag such as "is-IS", if it is bundled.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_', '.']).next()?;
+        Self::ALL.into_iter().find(|locale| locale.code().eq_ignore_ascii_case(language))
+    }
+
+    fn resource(self) -> &'static str {
+        match self {
+            Self::English => include_str!("../../locales/en.txt"),
+            Self::Icelandic => include_str!("../../locales/is.txt"),
+        }
+    }
+
+    /// The strings of the locale by key, parsed the first time they are needed.
+    pub fn strings(self) -> &'static HashMap<&'static str, &'static str> {
+        static STRINGS: [OnceLock<HashMap<&str, &str>>; Locale::ALL.len()] =
+            [const { OnceLock::new() }; Locale::ALL.len()];
+        STRINGS[self as usize].get_or_init(|| parse(self.resource()))
+    }
+
+    pub fn get(self, key: &str) -> Option<&'static str> {
+        self.strings().get(key).copied()
+    }
+}
+
+/// The language the UI is shown in, as chosen in the settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Language {
+    /// The one of the OS, or English if it isn't bundled.
+    #[default]
+    System,
+    English,
+    Icelandic,
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::System, Language::English, Language::Icelandic];
+
+    pub fn locale(self) -> Locale {
+        match self {
+            Self::System => system_locale().unwrap_or(Locale::English),
+            Self::English => Locale::English,
+            Self::Icelandic => Locale::Icelandic,
+        }
+    }
+}
+
+impl Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Each language is named in itself, so it can be found by someone who can't read the current one.
+        match self {
+            Self::System => write!(f, "{}", tr("language.system")),
+            Self::English => write!(f, "English"),
+            Self::Icelandic => write!(f, "\u{cd}slenska"),
+        }
+    }
+}
+
+/// Shows the UI in the language from now on.
+pub fn set_language(language: Language) {
+    let locale = language.locale();
+    tracing::debug!("Showing the UI in {:?}", locale);
+    CURRENT.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn current_locale() -> Locale {
+    Locale::ALL[CURRENT.load(Ordering::Relaxed) as usize]
+}
+
+/// The string in the current locale, falling back to English, and to the key itself if even that misses it.
+pub fn tr(key: &'static str) -> &'static str {
+    current_locale().get(key).or_else(|| Locale::English.get(key)).unwrap_or_else(|| {
+        tracing::warn!("Missing UI string {}", key);
+        key
+    })
+}
+
+/// Like [`tr`], with the `{name}` parameters filled in.
+pub fn tr_args(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    fill(tr(key), args)
+}
+
+/// Replaces each `{name}` with its argument. Those without one are left as they are.
+pub fn fill(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let argument = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter().find(|(arg, _)| *arg == name).map(|(_, value)| (end, value))
+        });
+        match argument {
+            Some((end, value)) => {
+                filled.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = after;
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// The strings of a resource by key. Blank lines and those starting with `#` are skipped,
+/// and `\n` in a value stands for a line break.
+pub fn parse(resource: &'static str) -> HashMap<&'static str, &'static str> {
+    resource
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let parsed = line.split_once('=').map(|(key, value)| (key.trim(), value.trim()));
+            if parsed.is_none() {
+                tracing::warn!("Ignoring UI string line without a key: {}", line);
+            }
+            parsed
+        })
+        .map(|(key, value)| match value.contains("\\n") {
+            // Leaked once per string, as the resources are around for the whole run anyway.
+            true => (key, &*Box::leak(value.replace("\\n", "\n").into_boxed_str())),
+            false => (key, value),
+        })
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn system_locale() -> Option<Locale> {
+    use windows::Win32::Globalization::GetUserDefaultLocaleName;
+
+    // LOCALE_NAME_MAX_LENGTH
+    let mut name = [0u16; 85];
+    let len = unsafe { GetUserDefaultLocaleName(&mut name) };
+    // The length includes the terminating null, and is zero on failure.
+    let name = String::from_utf16_lossy(&name[..(len.max(1) as usize - 1)]);
+    Locale::from_tag(&name)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn system_locale() -> Option<Locale> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()))
+        .and_then(|tag| Locale::from_tag(&tag))
+}
+
+/// Looks up a UI string in the current locale, by its key in the locale resources.
+/// Parameters are given by name, as in `tr!("home.my_id", id = id)`, and fill in the `{id}` of the string.
+/// Gives a `&'static str` without parameters, and a `String` with them.
+#[macro_export]
+macro_rules! tr {
+    ($key:literal) => {
+        $crate::ui::i18n::tr($key)
+    };
+    ($key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::ui::i18n::tr_args(
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+],
+        )
+    };
+}
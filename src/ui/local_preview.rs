@@ -0,0 +1,87 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::utils::{frame::Frame, throttle::Throttle, vector2::Vector2};
+
+/// The preview of what is shared. Small, it only needs a glimpse, so it takes frames at a capped rate
+/// and shows them scaled down, rather than uploading the full frame on every paint.
+/// Enlarged, it takes every frame at full size.
+#[derive(Debug, Clone)]
+pub struct LocalPreview {
+    fps: u32,
+    throttle: Throttle,
+    enlarged: bool,
+    frame: Option<Arc<Frame>>,
+    // The current frame scaled down, made when it is first shown. Shared with the clones the view is built from.
+    small: Arc<Mutex<Option<Arc<Frame>>>>,
+}
+
+impl LocalPreview {
+    pub const SMALL_SIZE: Vector2<i32> = Vector2 { x: 320, y: 180 };
+    pub const LARGE_SIZE: Vector2<i32> = Vector2 { x: 960, y: 540 };
+
+    /// Takes up to `fps` frames per second while small.
+    pub fn new(fps: u32) -> Self {
+        Self {
+            fps,
+            throttle: Throttle::per_second(fps),
+            enlarged: false,
+            frame: None,
+            small: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Changes the cap, starting over so the next frame is taken straight away.
+    pub fn set_fps(&mut self, fps: u32) {
+        self.fps = fps;
+        self.throttle = Throttle::per_second(fps);
+    }
+
+    pub fn is_enlarged(&self) -> bool {
+        self.enlarged
+    }
+
+    pub fn set_enlarged(&mut self, enlarged: bool) {
+        self.enlarged = enlarged;
+        // The next frame comes in at the cap again, rather than after the time left from before.
+        self.throttle = Throttle::per_second(self.fps);
+    }
+
+    /// Whether a frame captured now should be shown. Always when enlarged.
+    pub fn is_due(&mut self, now: Instant) -> bool {
+        // Passed either way, so shrinking doesn't let the next one through early.
+        self.throttle.pass(now) || self.enlarged
+    }
+
+    /// Shows the frame, if it is due. Frames left on the GPU can't be shown, so the last one from memory stays.
+    pub fn offer(&mut self, frame: &Arc<Frame>, now: Instant) -> bool {
+        if frame.gpu.is_some() || !self.is_due(now) {
+            return false;
+        }
+        self.frame = Some(frame.clone());
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.frame = None;
+        *self.small.lock().unwrap() = None;
+    }
+
+    /// The frame to show at the current size.
+    pub fn frame(&self) -> Option<Arc<Frame>> {
+        let frame = self.frame.as_ref()?;
+        if self.enlarged {
+            return Some(frame.clone());
+        }
+
+        let mut small = self.small.lock().unwrap();
+        if let Some(small) = small.as_ref().filter(|small| small.sequence == frame.sequence) {
+            return Some(small.clone());
+        }
+        let scaled = frame.downscaled(Self::SMALL_SIZE).map_or_else(|| frame.clone(), Arc::new);
+        *small = Some(scaled.clone());
+        Some(scaled)
+    }
+}
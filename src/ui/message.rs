@@ -1,12 +1,15 @@
 use std::sync::Arc;
 
-use bytes::Bytes;
+use tokio::sync::RwLock;
 
 use crate::{
-    networking::webrtc::{WebRTC, WebRTCError, WebRTCEvent},
+    capture_providers::{PlatformCaptureProvider, PlatformCaptureProviderError},
+    networking::webrtc::{PeerId, WebRTCError, WebRTCEvent},
+    platform::single_instance::InstanceMessage,
+    session::CallHandle,
     ui::screens::{
-        call::CallMessage, home::HomeMessage, onboarding::OnboardingMessage,
-        settings::SettingsMessage,
+        call::CallMessage, diagnostics::DiagnosticsMessage, home::HomeMessage,
+        onboarding::OnboardingMessage, settings::SettingsMessage,
     },
 };
 
@@ -15,6 +18,7 @@ pub enum Route {
     Home,
     Call,
     Settings,
+    Diagnostics,
 }
 
 #[derive(Debug, Clone)]
@@ -27,18 +31,43 @@ pub enum Message {
     Home(HomeMessage),
     Call(CallMessage),
     Settings(SettingsMessage),
+    Diagnostics(DiagnosticsMessage),
     Onboarding(OnboardingMessage),
 
     // Global / Shared
-    WebRTCInitialized(Result<WebRTC, Arc<WebRTCError>>),
-    WebRTCEvent(WebRTCEvent),
-    PacketReceived(Bytes),
+    CaptureProviderReady(
+        Result<Arc<RwLock<PlatformCaptureProvider>>, Arc<PlatformCaptureProviderError>>,
+    ),
+    // Shared, as the handle is large and the message is cloned on its way to the screen.
+    WebRTCInitialized(Result<Arc<CallHandle>, Arc<WebRTCError>>),
+    // A call to ourselves, which replaces the connection to the signaling server until it ends.
+    LoopbackStarted(Result<Arc<CallHandle>, Arc<WebRTCError>>),
+    // Connects to the signaling server again right away, rather than when the next attempt is due.
+    RetryConnection,
+    // The event, and the connection it happened on.
+    WebRTCEvent(PeerId, WebRTCEvent),
+    // Checks the panic hotkey while remote control is allowed, whichever screen is shown.
+    PollPanicHotkey,
 
     WindowOpened(iced::window::Id),
+    WindowClosed(iced::window::Id),
+    WindowCloseRequested(iced::window::Id),
+    ShutdownFinished,
     WindowIdFetched(u64),
 
     Tick(std::time::Instant),
     DismissNotification(u64),
+    // Dismisses the notification, and sends the message of its action.
+    NotificationAction(u64, Box<Message>),
+    NativeNotificationClicked,
+    // Answers the call that is ringing, or turns it down, from the dialog or the OS notification.
+    AcceptCall,
+    DeclineCall,
+    // Another instance was started, and exited after passing this on.
+    InstanceMessage(InstanceMessage),
 
     NoOp,
 }
+
+// Every message is moved, and often cloned, through the update loop, so a large payload goes behind a Box or an Arc.
+const _: () = assert!(std::mem::size_of::<Message>() <= 64);
@@ -0,0 +1,44 @@
+use iced::Point;
+
+use crate::{capture_providers::shared::MonitorGeometry, utils::rect::Rect};
+
+// Windows reach a few pixels past their frames with their shadows, and a sliver on the edge of the monitor shows too
+// little of the video to be worth a warning.
+const MIN_OVERLAP: i32 = 16;
+
+/// What to do about sharing a monitor the app's own windows are on. They show the peer's screen, so sharing them sends
+/// it back to the peer, mirrored into itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorAnswer {
+    /// Moves the windows to another monitor first.
+    MoveWindows,
+    ShareAnyway,
+    Cancel,
+}
+
+/// Whether enough of the window is on the monitor to show what it shows. Both in physical pixels.
+pub fn shows_on(monitor: Rect<i32>, window: Rect<i32>) -> bool {
+    monitor
+        .intersection(&window)
+        .is_some_and(|overlap| overlap.size.x >= MIN_OVERLAP && overlap.size.y >= MIN_OVERLAP)
+}
+
+/// Where to move the window so it is off the shared monitor: centered on the largest of the others, or in its top left
+/// corner if it doesn't fit. In logical pixels, which are exact if the monitors share a scale factor and close otherwise.
+/// None if there is no other monitor.
+pub fn move_off(
+    monitors: &[MonitorGeometry],
+    shared: Rect<i32>,
+    window: Rect<i32>,
+) -> Option<Point> {
+    let target = monitors
+        .iter()
+        .filter(|monitor| monitor.bounds.intersection(&shared).is_none())
+        .max_by_key(|monitor| monitor.bounds.area())?;
+
+    let bounds = target.bounds;
+    let x = bounds.position.x + ((bounds.size.x - window.size.x) / 2).max(0);
+    let y = bounds.position.y + ((bounds.size.y - window.size.y) / 2).max(0);
+    let scale = target.scale_factor.max(f32::EPSILON);
+    Some(Point::new(x as f32 / scale, y as f32 / scale))
+}
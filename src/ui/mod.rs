@@ -1,7 +1,27 @@
 pub mod app;
+pub mod bandwidth_meter;
+pub mod call_history;
+pub mod call_phase;
+pub mod call_summary;
+pub mod capture_ops;
+pub mod confirm_dialog;
+pub mod consent;
+pub mod filmstrip;
 pub mod frame_viewer;
+pub mod i18n;
+pub mod local_preview;
 pub mod message;
+pub mod mirror_warning;
+#[cfg(target_os = "windows")]
+pub mod native_notifications;
 pub mod notification;
 pub mod notification_provider;
+pub mod privacy_regions;
+pub mod quality;
+pub mod reconnect;
+pub mod remote_idle;
 pub mod screens;
+pub mod sharing_indicator;
+pub mod source_picker;
 pub mod state;
+pub mod test_support;
@@ -0,0 +1,184 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use tokio::sync::{Mutex, mpsc};
+use windows::{
+    Data::Xml::Dom::XmlDocument,
+    Foundation::TypedEventHandler,
+    UI::Notifications::{
+        ToastActivatedEventArgs, ToastNotification, ToastNotificationManager, ToastNotifier,
+    },
+    Win32::{
+        Foundation::{ERROR_SUCCESS, HWND, WIN32_ERROR},
+        System::Registry::{HKEY_CURRENT_USER, REG_SZ, RegSetKeyValueW},
+        UI::WindowsAndMessaging::{
+            FLASHW_ALL, FLASHW_TIMERNOFG, FLASHWINFO, FlashWindowEx, GetForegroundWindow,
+        },
+    },
+    core::{HSTRING, IInspectable, Interface},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum NativeNotificationError {
+    #[error("Windows error: {0}")]
+    WindowsError(#[from] windows::core::Error),
+    #[error("Failed to register for notifications: {0:?}")]
+    RegistrationFailed(WIN32_ERROR),
+}
+
+type Result<T> = std::result::Result<T, NativeNotificationError>;
+
+/// What the user did with one of the notifications.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Activation {
+    /// Clicked the notification itself.
+    Clicked,
+    /// Clicked one of its buttons, by the action it was shown with.
+    Action(String),
+}
+
+/// Shows notifications through the OS, for when the app is minimized or behind other windows.
+/// Clicking one is reported through [`NativeNotifier::activations`], so the app can bring itself to the front,
+/// or do what the button clicked stands for.
+#[derive(Debug, Clone)]
+pub struct NativeNotifier {
+    notifier: ToastNotifier,
+    activations_tx: mpsc::UnboundedSender<Activation>,
+    activations: Arc<Mutex<mpsc::UnboundedReceiver<Activation>>>,
+}
+
+impl NativeNotifier {
+    // Toasts are attributed to an app id, which unpackaged apps have to register themselves.
+    const APP_ID: &str = "Fjarsyn";
+    const DISPLAY_NAME: &str = "Fjarsyn";
+
+    pub fn new() -> Result<Self> {
+        register_app_id(Self::APP_ID, Self::DISPLAY_NAME)?;
+        let notifier =
+            ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(Self::APP_ID))?;
+        let (activations_tx, activations) = mpsc::unbounded_channel();
+        Ok(Self { notifier, activations_tx, activations: Arc::new(Mutex::new(activations)) })
+    }
+
+    /// Receives what the user did every time one of the notifications is clicked.
+    pub fn activations(&self) -> Arc<Mutex<mpsc::UnboundedReceiver<Activation>>> {
+        self.activations.clone()
+    }
+
+    pub fn show(&self, title: &str, body: &str) -> Result<()> {
+        self.show_toast(
+            format!(
+                r#"<toast><visual><binding template="ToastGeneric"><text>{}</text><text>{}</text></binding></visual></toast>"#,
+                escape_xml(title),
+                escape_xml(body)
+            ),
+        )
+    }
+
+    /// Shows a call ringing, which stays on screen with a button for each of the actions, by their label.
+    pub fn show_call(&self, title: &str, body: &str, actions: &[(&str, &str)]) -> Result<()> {
+        let buttons: String = actions
+            .iter()
+            .map(|(action, label)| {
+                format!(
+                    r#"<action content="{}" arguments="{}" activationType="foreground"/>"#,
+                    escape_xml(label),
+                    escape_xml(action)
+                )
+            })
+            .collect();
+        self.show_toast(format!(
+            r#"<toast scenario="incomingCall"><visual><binding template="ToastGeneric"><text>{}</text><text>{}</text></binding></visual><actions>{}</actions></toast>"#,
+            escape_xml(title),
+            escape_xml(body),
+            buttons
+        ))
+    }
+
+    fn show_toast(&self, content: String) -> Result<()> {
+        let xml = XmlDocument::new()?;
+        xml.LoadXml(&HSTRING::from(content))?;
+
+        let toast = ToastNotification::CreateToastNotification(&xml)?;
+        let activations = self.activations_tx.clone();
+        toast.Activated(&TypedEventHandler::<ToastNotification, IInspectable>::new(
+            move |_, args| {
+                // A button passes its action as the arguments, the notification itself none.
+                let action = args
+                    .as_ref()
+                    .and_then(|args| args.cast::<ToastActivatedEventArgs>().ok())
+                    .and_then(|args| args.Arguments().ok())
+                    .map(|arguments| arguments.to_string_lossy())
+                    .unwrap_or_default();
+                let activation = if action.is_empty() {
+                    Activation::Clicked
+                } else {
+                    Activation::Action(action)
+                };
+                let _ = activations.send(activation);
+                Ok(())
+            },
+        ))?;
+
+        self.notifier.Show(&toast)?;
+        Ok(())
+    }
+}
+
+// Identity based, so the subscription to the activations stays the same across updates.
+impl Hash for NativeNotifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.activations).hash(state);
+    }
+}
+
+impl PartialEq for NativeNotifier {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.activations, &other.activations)
+    }
+}
+
+impl Eq for NativeNotifier {}
+
+/// Whether the window is the one the user is working in, so they don't need to be notified.
+pub fn is_foreground(window: u64) -> bool {
+    unsafe { GetForegroundWindow() }.0 as usize as u64 == window
+}
+
+/// Flashes the window's taskbar button until the user switches to it.
+pub fn flash_taskbar(window: u64) {
+    let info = FLASHWINFO {
+        cbSize: std::mem::size_of::<FLASHWINFO>() as u32,
+        hwnd: HWND(window as usize as *mut core::ffi::c_void),
+        dwFlags: FLASHW_ALL | FLASHW_TIMERNOFG,
+        uCount: 0,
+        dwTimeout: 0,
+    };
+    let _ = unsafe { FlashWindowEx(&info) };
+}
+
+fn register_app_id(app_id: &str, display_name: &str) -> Result<()> {
+    let key_path = HSTRING::from(format!(r"Software\Classes\AppUserModelId\{}", app_id));
+    // REG_SZ values are the null-terminated UTF-16 string.
+    let value: Vec<u16> = display_name.encode_utf16().chain(std::iter::once(0)).collect();
+    let result = unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            &key_path,
+            &HSTRING::from("DisplayName"),
+            REG_SZ.0,
+            Some(value.as_ptr().cast()),
+            std::mem::size_of_val(value.as_slice()) as u32,
+        )
+    };
+    if result != ERROR_SUCCESS {
+        return Err(NativeNotificationError::RegistrationFailed(result));
+    }
+    Ok(())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -1,5 +1,7 @@
 use std::time::{Duration, Instant};
 
+use crate::ui::message::Message;
+
 const INFO_DEFAULT_DURATION: Duration = Duration::from_secs(7);
 const ERROR_DEFAULT_DURATION: Duration = Duration::from_secs(10);
 const SUCCESS_DEFAULT_DURATION: Duration = Duration::from_secs(5);
@@ -11,6 +13,13 @@ pub enum NotificationKind {
     Success,
 }
 
+/// A button on the notification, which sends the message and dismisses the notification.
+#[derive(Debug, Clone)]
+pub struct NotificationAction {
+    pub label: String,
+    pub message: Message,
+}
+
 #[derive(Debug, Clone)]
 pub struct Notification {
     pub id: u64,
@@ -18,6 +27,10 @@ pub struct Notification {
     pub kind: NotificationKind,
     pub created_at: Instant,
     pub duration: Duration,
+    pub action: Option<NotificationAction>,
+    /// Set for notifications shown with [`super::notification_provider::NotificationProvider::notify_once`],
+    /// which replace the one with the same key.
+    pub key: Option<&'static str>,
 }
 
 impl Notification {
@@ -32,6 +45,8 @@ impl Notification {
                 NotificationKind::Error => ERROR_DEFAULT_DURATION,
                 NotificationKind::Success => SUCCESS_DEFAULT_DURATION,
             },
+            action: None,
+            key: None,
         }
     }
 
@@ -1,13 +1,16 @@
-use std::{collections::HashMap, sync::atomic::AtomicU64, time::Instant};
+use std::{sync::atomic::AtomicU64, time::Instant};
 
 use iced::{
     Element, Length,
     widget::{button, container, text},
 };
 
-use crate::ui::{
-    message::Message,
-    notification::{Notification, NotificationKind},
+use crate::{
+    tr,
+    ui::{
+        message::Message,
+        notification::{Notification, NotificationAction, NotificationKind},
+    },
 };
 
 const NOTIFICATION_INFO_COLOR: iced::Color = iced::Color::from_rgb8(0, 100, 200);
@@ -16,45 +19,77 @@ const NOTIFICATION_SUCCESS_COLOR: iced::Color = iced::Color::from_rgb8(0, 200, 0
 
 static NEXT_ID: AtomicU64 = AtomicU64::new(0);
 
+/// The notifications shown, oldest first, so the newest is always at the bottom.
 pub struct NotificationProvider {
-    notifications: HashMap<u64, Notification>,
+    // In the order they were shown, which is also the order of their ids.
+    notifications: Vec<Notification>,
 }
 
 impl NotificationProvider {
     pub fn new() -> Self {
-        Self { notifications: HashMap::new() }
+        Self { notifications: Vec::new() }
     }
 
-    pub fn error(&mut self, message: impl Into<String>) {
+    fn push(&mut self, message: impl Into<String>, kind: NotificationKind) -> &mut Notification {
         let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        self.notifications
-            .insert(id, Notification::new(id, message.into(), NotificationKind::Error));
+        self.notifications.push(Notification::new(id, message.into(), kind));
+        self.notifications.last_mut().expect("Just pushed")
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(message, NotificationKind::Error);
     }
 
     pub fn info(&mut self, message: impl Into<String>) {
-        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        self.notifications
-            .insert(id, Notification::new(id, message.into(), NotificationKind::Info));
+        self.push(message, NotificationKind::Info);
     }
 
     pub fn success(&mut self, message: impl Into<String>) {
-        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        self.notifications
-            .insert(id, Notification::new(id, message.into(), NotificationKind::Success));
+        self.push(message, NotificationKind::Success);
+    }
+
+    /// Shows a notification in place of the one with the same key, if there is one, instead of stacking them up.
+    /// It goes to the bottom as the newest, with a new id. Returns the id.
+    pub fn notify_once(
+        &mut self,
+        key: &'static str,
+        kind: NotificationKind,
+        message: impl Into<String>,
+    ) -> u64 {
+        self.notifications.retain(|n| n.key != Some(key));
+        let notification = self.push(message, kind);
+        notification.key = Some(key);
+        notification.id
+    }
+
+    /// Shows an info notification with a button that sends the action. Returns its id, for dismissing it early.
+    pub fn info_with_action(
+        &mut self,
+        message: impl Into<String>,
+        label: impl Into<String>,
+        action: Message,
+    ) -> u64 {
+        let notification = self.push(message, NotificationKind::Info);
+        notification.action = Some(NotificationAction { label: label.into(), message: action });
+        notification.id
     }
 
     pub fn dismiss(&mut self, id: u64) {
-        self.notifications.remove(&id);
+        self.notifications.retain(|n| n.id != id);
     }
 
     pub fn dismiss_expired(&mut self, now: Instant) {
-        self.notifications.retain(|_k, n| !n.expired(now));
+        self.notifications.retain(|n| !n.expired(now));
+    }
+
+    /// The notifications shown, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &Notification> {
+        self.notifications.iter()
     }
 
     pub fn view<'a>(&'a self) -> Element<'a, Message> {
         let content = iced::widget::column(
-            self.notifications
-                .values()
+            self.iter()
                 .map(|n| {
                     let color = match n.kind {
                         NotificationKind::Info => NOTIFICATION_INFO_COLOR,
@@ -62,12 +97,26 @@ impl NotificationProvider {
                         NotificationKind::Success => NOTIFICATION_SUCCESS_COLOR,
                     };
 
+                    let action = n.action.as_ref().map(|action| {
+                        button(text(&action.label).size(14))
+                            .on_press(Message::NotificationAction(
+                                n.id,
+                                Box::new(action.message.clone()),
+                            ))
+                            .padding(5)
+                    });
+
                     container(
                         iced::widget::column![
                             text(&n.message).color(iced::Color::WHITE).size(14).width(Length::Fill),
-                            button(text("Dismiss").size(14))
-                                .on_press(Message::DismissNotification(n.id))
-                                .padding(5)
+                            iced::widget::row![]
+                                .push(action)
+                                .push(
+                                    button(text(tr!("common.dismiss")).size(14))
+                                        .on_press(Message::DismissNotification(n.id))
+                                        .padding(5)
+                                )
+                                .spacing(10)
                         ]
                         .align_x(iced::Alignment::Center)
                         .padding(10)
@@ -0,0 +1,102 @@
+use fjarsyn_shared::{InputEvent, MouseButton};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capture_providers::shared::CaptureSourceId,
+    utils::{rect::Rect, vector2::Vector2},
+};
+
+/// The regions kept for a source, so they are hidden again whenever it is shared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedPrivacyRegions {
+    pub source: CaptureSourceId,
+    pub regions: Vec<Rect<f32>>,
+}
+
+/// The parts of what is shared that are hidden from the peer, drawn by dragging over the local preview.
+/// Everything is normalized to the frame, so the regions cover the same part of it at any resolution.
+#[derive(Debug, Clone, Default)]
+pub struct PrivacyRegions {
+    regions: Vec<Rect<f32>>,
+    editing: bool,
+    // Where the pointer last was over the preview, and where the region being drawn started.
+    pointer: Option<Vector2<f32>>,
+    drag_start: Option<Vector2<f32>>,
+}
+
+impl PrivacyRegions {
+    // Smaller drags are taken for clicks, which would otherwise leave regions too small to see.
+    const MIN_SIZE: f32 = 0.01;
+
+    pub fn regions(&self) -> &[Rect<f32>] {
+        &self.regions
+    }
+
+    /// Replaces the regions, e.g. with those saved for the source that is now shared.
+    pub fn set_regions(&mut self, regions: Vec<Rect<f32>>) {
+        self.regions = regions;
+    }
+
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.editing
+    }
+
+    pub fn set_editing(&mut self, editing: bool) {
+        self.editing = editing;
+        self.drag_start = None;
+    }
+
+    /// The region being drawn, from where the drag started to the pointer.
+    pub fn draft(&self) -> Option<Rect<f32>> {
+        Some(Rect::from_corners(self.drag_start?, self.pointer?))
+    }
+
+    /// Takes the pointer over the preview while editing. Dragging with the left button draws a region,
+    /// and clicking a region with the right one removes it. Returns whether the regions changed.
+    pub fn input(&mut self, event: InputEvent) -> bool {
+        if !self.editing {
+            return false;
+        }
+
+        match event {
+            InputEvent::MouseMove(position) => {
+                let position = Vector2::new(position.x.clamp(0.0, 1.0), position.y.clamp(0.0, 1.0));
+                self.pointer = Some(position);
+                false
+            }
+            InputEvent::MouseButton { button: MouseButton::Left, pressed: true } => {
+                self.drag_start = self.pointer;
+                false
+            }
+            InputEvent::MouseButton { button: MouseButton::Left, pressed: false } => {
+                let Some(region) = self.draft() else {
+                    return false;
+                };
+                self.drag_start = None;
+                if region.size.x < Self::MIN_SIZE || region.size.y < Self::MIN_SIZE {
+                    return false;
+                }
+                self.regions.push(region);
+                true
+            }
+            InputEvent::MouseButton { button: MouseButton::Right, pressed: true } => {
+                let Some(pointer) = self.pointer else {
+                    return false;
+                };
+                // The one on top, which is the last drawn.
+                match self.regions.iter().rposition(|region| region.contains_point(pointer)) {
+                    Some(index) => {
+                        self.regions.remove(index);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
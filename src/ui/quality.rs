@@ -0,0 +1,180 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::media::stats::ConnectionStats;
+
+/// How good the connection is, shown as one to four bars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum QualityLevel {
+    Poor,
+    Fair,
+    Good,
+    Excellent,
+}
+
+impl QualityLevel {
+    pub fn bars(self) -> u8 {
+        match self {
+            Self::Poor => 1,
+            Self::Fair => 2,
+            Self::Good => 3,
+            Self::Excellent => 4,
+        }
+    }
+}
+
+impl Display for QualityLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Poor => write!(f, "Poor"),
+            Self::Fair => write!(f, "Fair"),
+            Self::Good => write!(f, "Good"),
+            Self::Excellent => write!(f, "Excellent"),
+        }
+    }
+}
+
+/// What holds the quality back the most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityLimit {
+    Latency,
+    PacketLoss,
+    Bandwidth,
+    Freezes,
+}
+
+impl Display for QualityLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Latency => write!(f, "high latency"),
+            Self::PacketLoss => write!(f, "high packet loss"),
+            Self::Bandwidth => write!(f, "not enough bandwidth"),
+            Self::Freezes => write!(f, "the video keeps freezing"),
+        }
+    }
+}
+
+// The upper bounds of Excellent, Good and Fair. Anything above is Poor.
+const ROUND_TRIP_TIMES: [Duration; 3] =
+    [Duration::from_millis(100), Duration::from_millis(200), Duration::from_millis(400)];
+const PACKET_LOSS: [f32; 3] = [0.01, 0.03, 0.08];
+// The lower bounds of the available bitrate over the target.
+const BANDWIDTH_RATIOS: [f64; 3] = [1.0, 0.75, 0.5];
+
+/// Rates the stats of one interval by the worst of them, along with which one that was.
+/// No limit when it is excellent. What isn't known doesn't count.
+pub fn score(stats: &ConnectionStats) -> (QualityLevel, Option<QualityLimit>) {
+    let levels = [
+        stats.round_trip_time.map(|rtt| {
+            (level_below(ROUND_TRIP_TIMES.map(|bound| rtt < bound)), QualityLimit::Latency)
+        }),
+        stats.packet_loss.map(|loss| {
+            (level_below(PACKET_LOSS.map(|bound| loss < bound)), QualityLimit::PacketLoss)
+        }),
+        stats.available_bitrate.zip(stats.target_bitrate).filter(|(_, target)| *target > 0.0).map(
+            |(available, target)| {
+                let ratio = available / target;
+                (level_below(BANDWIDTH_RATIOS.map(|bound| ratio >= bound)), QualityLimit::Bandwidth)
+            },
+        ),
+        Some((
+            match stats.freezes {
+                0 => QualityLevel::Excellent,
+                1 => QualityLevel::Fair,
+                _ => QualityLevel::Poor,
+            },
+            QualityLimit::Freezes,
+        )),
+    ];
+
+    // The first of the worst, so the order above breaks ties.
+    let (level, limit) = levels
+        .into_iter()
+        .flatten()
+        .reduce(|worst, next| if next.0 < worst.0 { next } else { worst })
+        .unwrap_or((QualityLevel::Excellent, QualityLimit::Latency));
+    (level, (level < QualityLevel::Excellent).then_some(limit))
+}
+
+// The best level whose bound is met, given whether the bounds of Excellent, Good and Fair are.
+fn level_below(within: [bool; 3]) -> QualityLevel {
+    match within {
+        [true, _, _] => QualityLevel::Excellent,
+        [_, true, _] => QualityLevel::Good,
+        [_, _, true] => QualityLevel::Fair,
+        _ => QualityLevel::Poor,
+    }
+}
+
+/// The level to show, which only changes once the scores agree for a while, so it doesn't flicker.
+/// Drops quickly, as the user notices trouble right away, and recovers slowly.
+#[derive(Debug, Clone, Default)]
+pub struct QualityIndicator {
+    level: Option<QualityLevel>,
+    limit: Option<QualityLimit>,
+    // The level the scores point to, and how many in a row did.
+    // Held at the least change they all agree on.
+    pending: Option<(QualityLevel, u32)>,
+}
+
+impl QualityIndicator {
+    /// The scores in a row it takes to drop.
+    pub const DROP_AFTER: u32 = 2;
+    /// The scores in a row it takes to recover.
+    pub const RISE_AFTER: u32 = 5;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// None before the first stats.
+    pub fn level(&self) -> Option<QualityLevel> {
+        self.level
+    }
+
+    /// What holds the level back, if anything.
+    pub fn limit(&self) -> Option<QualityLimit> {
+        self.limit.filter(|_| self.level < Some(QualityLevel::Excellent))
+    }
+
+    /// Forgets the level, e.g. for the next call.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Feeds the stats of the next interval, returning the level to show.
+    pub fn update(&mut self, stats: &ConnectionStats) -> QualityLevel {
+        let (scored, limit) = score(stats);
+        let Some(level) = self.level else {
+            self.level = Some(scored);
+            self.limit = limit;
+            return scored;
+        };
+        // Only what backs up the level shown explains it.
+        if scored <= level {
+            self.limit = limit;
+        }
+        if scored == level {
+            self.pending = None;
+            return level;
+        }
+
+        let dropping = scored < level;
+        let (target, count) = match self.pending {
+            // Still the same way, so only as far as all of them went.
+            Some((target, count)) if (target < level) == dropping => {
+                let target = if dropping { target.max(scored) } else { target.min(scored) };
+                (target, count + 1)
+            }
+            _ => (scored, 1),
+        };
+
+        let needed = if dropping { Self::DROP_AFTER } else { Self::RISE_AFTER };
+        if count < needed {
+            self.pending = Some((target, count));
+            return level;
+        }
+        self.pending = None;
+        self.level = Some(target);
+        target
+    }
+}
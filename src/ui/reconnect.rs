@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+/// When to try connecting to the signaling server again, after failing to.
+/// Backs off the more attempts fail in a row, so a server that is down isn't hammered.
+#[derive(Debug, Clone, Default)]
+pub struct Reconnect {
+    failures: u32,
+    retry_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl Reconnect {
+    pub const INITIAL_DELAY: Duration = Duration::from_secs(2);
+    pub const MAX_DELAY: Duration = Duration::from_secs(60);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules the next attempt. True if this is the first failure in a row, which is the one worth telling the user about.
+    pub fn failed(&mut self, now: Instant, error: String) -> bool {
+        let delay = Self::INITIAL_DELAY
+            .saturating_mul(2u32.saturating_pow(self.failures.min(16)))
+            .min(Self::MAX_DELAY);
+        self.failures += 1;
+        self.retry_at = Some(now + delay);
+        self.last_error = Some(error);
+        self.failures == 1
+    }
+
+    pub fn succeeded(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Whether the scheduled attempt should be made by now.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.retry_at.is_some_and(|retry_at| now >= retry_at)
+    }
+
+    /// How long until the scheduled attempt, or None if there is none.
+    pub fn retry_in(&self, now: Instant) -> Option<Duration> {
+        self.retry_at.map(|retry_at| retry_at.saturating_duration_since(now))
+    }
+
+    /// Whether the last attempt failed, so the app is offline until the next one succeeds.
+    pub fn is_offline(&self) -> bool {
+        self.failures > 0
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+}
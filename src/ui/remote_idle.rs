@@ -0,0 +1,40 @@
+use std::time::{Duration, Instant};
+
+/// Tracks when the last remote frame arrived, to tell the user once the video has been still for a while.
+/// The sender skips frames when nothing changes, so a still picture doesn't mean the call froze.
+#[derive(Debug, Clone, Default)]
+pub struct RemoteIdle {
+    // Zero never counts as idle, and holds the last frame without saying so.
+    idle_after: Duration,
+    last_frame: Option<Instant>,
+}
+
+impl RemoteIdle {
+    pub fn new(idle_after: Duration) -> Self {
+        Self { idle_after, last_frame: None }
+    }
+
+    pub fn set_idle_after(&mut self, idle_after: Duration) {
+        self.idle_after = idle_after;
+    }
+
+    pub fn frame_arrived(&mut self, now: Instant) {
+        self.last_frame = Some(now);
+    }
+
+    /// Forgets the last frame, e.g. when the video stopped for good.
+    pub fn reset(&mut self) {
+        self.last_frame = None;
+    }
+
+    /// None before the first frame.
+    pub fn since_last_frame(&self, now: Instant) -> Option<Duration> {
+        self.last_frame.map(|last| now.saturating_duration_since(last))
+    }
+
+    /// Whether no frame arrived for longer than the threshold. Never before the first frame, as nothing is shown yet.
+    pub fn is_idle(&self, now: Instant) -> bool {
+        !self.idle_after.is_zero()
+            && self.since_last_frame(now).is_some_and(|since| since >= self.idle_after)
+    }
+}
@@ -1,171 +1,2189 @@
 use std::{
+    collections::BTreeMap,
     hash::{Hash, Hasher},
+    path::PathBuf,
     sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 
+use fjarsyn_shared::{
+    CallMode, ControlMessage, CursorPosition, InputEvent, QualityRequest, RecordingState,
+};
 use iced::{
     Element, Length, Subscription, Task,
-    widget::{button, container, stack, text},
+    alignment::Vertical,
+    task::Handle,
+    widget::{
+        button, checkbox, column, container, image, mouse_area, pick_list, row, stack, text,
+        tooltip,
+    },
+    window,
 };
-use tokio::sync::{Mutex, RwLock, mpsc};
+use tokio::sync::{RwLock, watch};
+use tracing::Span;
 
 use super::Screen;
 use crate::{
     capture_providers::{
-        CaptureProvider, PlatformCaptureProvider, PlatformCaptureStream, shared::CaptureFramerate,
+        CaptureProvider, PlatformCaptureItem, PlatformCaptureProvider, PlatformCursorTracker,
+        SourceDescriptor, SourceKind, exclude_platform_window_from_capture, find_platform_source,
+        platform_monitor_geometry, platform_monitor_layout, platform_window_bounds,
+        saved_platform_capture_item,
+        shared::{
+            CaptureFramerate, CaptureItemInfo, CaptureSourceId, CaptureState, CaptureStats,
+            CaptureStatsWindow, FrameMeta, RotationCorrection, SavedCaptureSource,
+        },
         user_pick_platform_capture_item,
     },
-    media::ffmpeg::{FFmpegDecoder, FFmpegEncoder},
-    networking::webrtc::WebRTCEvent,
+    config::{EncodingSettings, QualityPreset},
+    media::{
+        click_highlights::{Click, ClickHighlights},
+        decoder_worker::{CatchUp, DecoderHandle, DecoderWorker},
+        encode_size,
+        encoder_worker::{EncoderConfig, EncoderHandle, EncoderWorker, EncoderWorkerError},
+        ffmpeg::{DecodeAccel, FFmpegDecoder},
+        framerate_check::{FramerateCheck, FramerateCheckEvent},
+        quality_request::{ViewerQuality, clamp_settings, reduces},
+        recorder::{self, Recorder},
+        stats::{ConnectionStats, DecoderStats, DepacketStats, EncoderStats},
+    },
+    networking::{
+        impairment::ImpairmentStats,
+        webrtc::{CallFingerprints, TrackId, WebRTC, WebRTCEvent},
+    },
+    platform::{
+        input_hook::{InputHookError, MouseHook},
+        input_injection,
+    },
+    tr,
     ui::{
+        app::App,
+        bandwidth_meter::bandwidth_meter,
+        call_phase::CallPhase,
+        call_summary::CallSummaryRecorder,
+        capture_ops::{
+            CaptureOp, CaptureOpDecision, CaptureOpId, CaptureOpKind, CaptureOps, with_write_lock,
+        },
+        confirm_dialog::confirm_dialog,
+        consent::ConsentKind,
+        filmstrip::{self, Filmstrip, Snapshot, session_folder_name},
         frame_viewer::FrameViewer,
+        local_preview::LocalPreview,
         message::{Message, Route},
-        state::AppContext,
+        mirror_warning::{self, MirrorAnswer},
+        notification::NotificationKind,
+        privacy_regions::PrivacyRegions,
+        quality::QualityIndicator,
+        remote_idle::RemoteIdle,
+        sharing_indicator::SharingIndicator,
+        source_picker::{SourcePicker, SourcePickerMessage, icon_handle, icon_view},
+        state::{AppContext, CaptureProviderState},
+    },
+    utils::{
+        abort_on_drop::AbortOnDrop, frame::Frame, locked_stream::stream_when_unlocked,
+        metrics::MetricsSnapshot, rect::Rect, throttle::Throttle, vector2::Vector2,
     },
-    utils::frame::Frame,
 };
 
+// How often the connection quality is rated.
+const CONNECTION_STATS_INTERVAL: Duration = Duration::from_secs(1);
+// How long starting or stopping waits for the capture provider, in case whatever holds it is stuck.
+const CAPTURE_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+// The notifications that say how something is now, which replace the one before instead of piling up.
+const ENCODER_FPS_NOTIFICATION: &str = "encoder_fps";
+const REMOTE_CONTROL_NOTIFICATION: &str = "remote_control";
+const QUALITY_REQUEST_NOTIFICATION: &str = "quality_request";
+const RECORDING_NOTIFICATION: &str = "recording";
+
 #[derive(Debug, Clone)]
 struct FrameReceiverSubData {
     capture: Arc<RwLock<PlatformCaptureProvider>>,
     framerate: CaptureFramerate,
+    thread_priority: bool,
+    hdr: bool,
+    rotation: RotationCorrection,
     stream_name: &'static str,
 }
 
+// The framerate, thread priority, HDR capture and rotation are part of the identity, so changing them restarts the
+// stream.
 impl Hash for FrameReceiverSubData {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.stream_name.hash(state);
+        self.framerate.hash(state);
+        self.thread_priority.hash(state);
+        self.hdr.hash(state);
+        self.rotation.hash(state);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CaptureStateSubData(Arc<RwLock<PlatformCaptureProvider>>);
+
+impl Hash for CaptureStateSubData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CaptureOpOutcome {
+    Started {
+        item: PlatformCaptureItem,
+        info: Option<CaptureItemInfo>,
+        // Whether a running capture was switched over to the item, rather than started.
+        switched: bool,
+    },
+    Stopped,
+}
+
+// The mouse hook, and the task that turns what it sees and where the cursor is into what the encoder draws.
+// The hook is removed once this is dropped.
+#[derive(Debug)]
+struct ClickHighlighter {
+    highlights: watch::Receiver<ClickHighlights>,
+    _hook: MouseHook,
+    _task: AbortOnDrop,
+}
+
+impl ClickHighlighter {
+    // As often as the cursor is sent, which is smooth enough for the halo to follow it.
+    const UPDATE_INTERVAL: Duration = Duration::from_millis(33);
+
+    fn start(tracker: PlatformCursorTracker) -> Result<Self, InputHookError> {
+        let (clicks_tx, mut clicks) = tokio::sync::mpsc::unbounded_channel();
+        let hook = MouseHook::install(move |click| {
+            let _ = clicks_tx.send(click);
+        })?;
+
+        let (highlights_tx, highlights) = watch::channel(ClickHighlights::default());
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Self::UPDATE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                tokio::select! {
+                    Some(click) = clicks.recv() => {
+                        // Clicks outside of what is shared aren't shown.
+                        let Some(position) =
+                            tracker.transform().and_then(|t| t.to_capture(click.position))
+                        else {
+                            continue;
+                        };
+                        highlights_tx.send_modify(|highlights| {
+                            highlights.click(Click { position, button: click.button, at: click.at })
+                        });
+                    }
+                    _ = interval.tick() => {
+                        let cursor = tracker.poll().map(|position| Vector2::new(position.x, position.y));
+                        let now = Instant::now();
+                        highlights_tx.send_if_modified(|highlights| {
+                            highlights.move_cursor(cursor) | highlights.expire(now)
+                        });
+                    }
+                }
+            }
+        });
+
+        Ok(Self { highlights, _hook: hook, _task: AbortOnDrop(task.abort_handle()) })
     }
 }
 
+#[derive(Debug, Clone)]
+struct MirrorPrompt {
+    source: Box<SourceDescriptor>,
+    // Whether there is another monitor to move the windows to.
+    can_move: bool,
+}
+
 #[derive(Debug, Clone)]
 pub enum CallMessage {
-    StartCapture,
-    CaptureStarted,
+    OpenSourcePicker,
+    CloseSourcePicker,
+    SourcePicker(SourcePickerMessage),
+    SourceSelected(Box<SourceDescriptor>),
+    // The source was picked, and the user was warned about it if it would capture the call itself.
+    ShareSource(Box<SourceDescriptor>),
+    MirrorAnswered(MirrorAnswer),
+    // One of the app's windows moved, which may have put it on or off the shared monitor.
+    WindowMoved,
+    PopoutOpened(u64),
+    OpenSystemPicker,
+    ShareLastSource(Box<SavedCaptureSource>),
+    // None if the last source is gone, in which case the picker opens instead.
+    LastSourceResolved(Option<Box<SourceDescriptor>>),
+    CaptureStateChanged(CaptureState),
     StopCapture,
     CaptureStopped,
-    TryStartCapture(crate::capture_providers::PlatformCaptureItem),
-    TryStopCapture,
-    PlatformUserPickedCaptureItem(Result<crate::capture_providers::PlatformCaptureItem, String>),
+    CaptureOpFinished(CaptureOpId, Result<Box<CaptureOpOutcome>, String>),
+    PlatformUserPickedCaptureItem(Result<PlatformCaptureItem, String>),
     FrameCaptured(Arc<Frame>),
-    DecodedFrameReady(Arc<Frame>),
+    DecodedFrameReady(TrackId, Arc<Frame>),
     ToggleLocalPreview,
+    TogglePreviewSize,
+    TogglePrivacyEditor,
+    // The pointer over the local preview, while editing the privacy regions.
+    PrivacyInput(InputEvent),
+    ClearPrivacyRegions,
+    RememberPrivacyRegions(bool),
+    ToggleStats,
+    QualityPresetSelected(QualityPreset),
+    ViewerQualitySelected(ViewerQuality),
+    // None if the session descriptions don't have them.
+    FingerprintsReady(Option<Box<CallFingerprints>>),
+    ConnectionStatsReady(Box<ConnectionStats>),
+    SetPeerVerified(bool),
+    SetFramerate(CaptureFramerate),
+    PopOut,
+    PopIn,
+    ToggleFullscreen,
+    ToggleRemoteControl,
+    ToggleClickHighlights,
+    ToggleRecording,
+    RequestRemoteControl,
+    // The user's answer to what the peer asked for.
+    ConsentAnswered { accepted: bool, always: bool },
+    // The sharing indicator opened, with the raw ID of its window.
+    SharingIndicatorOpened(u64),
+    PulseSharingIndicator(Instant),
+    SendInput(InputEvent),
+    // Sent once the recorder started or stopped recording the call.
+    RecordingChanged(bool),
+    // The recording was written to the file, with its size, or failed.
+    RecordingSaved(PathBuf, Result<u64, String>),
+    SnapshotSaved(Result<Box<Snapshot>, String>),
+    // The snapshot of the filmstrip to show at full size, or None to close it.
+    ShowSnapshot(Option<PathBuf>),
     EndCall,
 }
 
 #[derive(Clone, Debug)]
 pub struct CallScreen {
-    capture: Arc<RwLock<PlatformCaptureProvider>>,
+    phase: CallPhase,
+    // Who the call is with, as the user entered it or as the offer came from.
+    peer: Option<String>,
+    // What our end does in the call. Only viewing leaves the capture provider alone for the whole call.
+    mode: CallMode,
 
     // Local Capture State
-    pub local_frame: Option<Arc<Frame>>,
-    pub frame_sender: Option<mpsc::Sender<Arc<Frame>>>,
+    local_preview: LocalPreview,
+    // Mirrors the provider, so the controls show what it is actually doing.
+    capture_state: CaptureState,
+    sharing_info: Option<CaptureItemInfo>,
+    // The icon of the shared window, made drawable once.
+    sharing_icon: Option<image::Handle>,
+    encoder: Option<EncoderHandle>,
+    // Whether an encoder sent video in this call already, so the next one has to take over from it.
+    sent_video: bool,
+    // Whether the capture was last told to leave the frames on the GPU.
+    #[cfg(feature = "gpu-frames")]
+    gpu_output: bool,
+    // When the current source switch started, to measure the gap until the first new frame.
+    switch_started: Option<Instant>,
+    capture_ops: CaptureOps<PlatformCaptureItem>,
+    // Aborts the pending capture operation once dropped, which is when another one supersedes it.
+    capture_op_task: Option<Handle>,
+    // What the user asked to share before the capture provider was ready, which is done once it is.
+    pending_share: Option<CallMessage>,
+    sharing_indicator: Option<SharingIndicator>,
     pub show_local_preview: bool,
+    privacy: PrivacyRegions,
+    // The source being shared, to keep its privacy regions for, if it can be found again.
+    shared_source: Option<CaptureSourceId>,
+    // Whether the privacy regions are kept for the source once edited.
+    remember_privacy: bool,
+    encoder_stats: Option<EncoderStats>,
+    capture_stats: Option<CaptureStats>,
+    capture_stats_window: CaptureStatsWindow,
+    frame_meta: Option<watch::Receiver<FrameMeta>>,
+    show_stats: bool,
+    framerate_check: FramerateCheck,
+    // The notification about the framerate falling short, so it can be taken back once it recovers.
+    framerate_warning: Option<u64>,
+    // Boxed, as it is large and only there while picking.
+    source_picker: Option<Box<SourcePicker>>,
+    cursor_sender: Option<Arc<AbortOnDrop>>,
+    cursor_tracker: Option<PlatformCursorTracker>,
+    // Whether our clicks are drawn into what we share. Always starts off, and turns off when sharing stops.
+    highlight_clicks: bool,
+    // Only there while sharing with the clicks highlighted, as it watches the mouse system-wide.
+    click_highlighter: Option<Arc<ClickHighlighter>>,
+    // Whether we let the peer control our mouse. Always starts off.
+    pub remote_control_allowed: bool,
+    // What the peer asked for, waiting for the user to agree to it.
+    consent_prompt: Option<ConsentKind>,
+    // A monitor picked to share while the app's windows are on it, waiting for the user to decide what to do about it.
+    mirror_prompt: Option<MirrorPrompt>,
+    // Whether the app's windows are on the shared monitor, so the peer sees their own screen in it.
+    mirrored: bool,
+    // What the peer asked us to keep our quality under, on top of our own settings.
+    quality_request: Option<QualityRequest>,
+    // Whether we record the call, which the peer is told about.
+    recording: bool,
+    // Writes the shown remote track to a file while recording.
+    recorder: Option<Recorder>,
 
     // Remote Capture State
     pub remote_frame: Option<Arc<Frame>>,
-    pub decoder: Option<Arc<Mutex<FFmpegDecoder>>>,
+    pub remote_cursor: Option<CursorPosition>,
+    // Whether the peer lets us control their mouse.
+    pub remote_control_granted: bool,
+    // Set while waiting for the peer to answer our request for remote control.
+    remote_control_requested: bool,
+    // Whether the peer says it records the call. A modified build could record without saying so.
+    pub remote_recording: bool,
+    // One for each remote track. The latest track to start is the one shown.
+    decoders: BTreeMap<TrackId, DecoderHandle>,
+    // Whether the user was told that hardware decoding didn't work out. Only once per call.
+    software_decode_notified: bool,
+    // What we asked the peer to send us.
+    viewer_quality: ViewerQuality,
+    // When the last remote frame arrived, to tell still content apart from a frozen call.
+    remote_idle: RemoteIdle,
+    // Started with the first snapshot, if they are taken.
+    filmstrip: Option<Filmstrip>,
+    // The certificate fingerprints of the connection, for the users to compare.
+    fingerprints: Option<CallFingerprints>,
+    // Whether the peer was verified with another certificate, which is shown until they are verified again.
+    fingerprint_changed: bool,
+    // How good the connection is, as shown in bars.
+    quality: QualityIndicator,
+    quality_throttle: Throttle,
+    // The packets received, dropped of those and decoder catch ups as of the last stats, to count the new ones.
+    receive_counts: Option<(u64, u64, u32)>,
+    // Taken for the call history once the call is over.
+    summary: CallSummaryRecorder,
 }
 
 impl CallScreen {
-    pub fn new(capture: Arc<RwLock<PlatformCaptureProvider>>) -> Self {
-        Self {
-            capture,
+    pub fn new(ctx: &mut AppContext) -> Self {
+        // The tracks may have started before the screen opened, otherwise TrackStarted replaces this decoder.
+        let decoders = if ctx.call.remote_tracks.is_empty() {
+            let transcoding_type = ctx.config.transcoding_type;
+            let track = TrackId::default();
+            let _call = Self::call_span(ctx).entered();
+            let packets = ctx.call.channels.as_ref().map(|channels| channels.packets(track));
+            // Nothing is skipped until the codec is known, which is when this decoder is replaced anyway.
+            packets
+                .and_then(|packets| {
+                    DecoderWorker::spawn(
+                        move |accel| FFmpegDecoder::new(transcoding_type, accel),
+                        ctx.config.decode_hw_accel,
+                        track,
+                        packets,
+                        ctx.call.webrtc.clone(),
+                        None,
+                    )
+                    .inspect_err(|e| tracing::error!("Failed to create decoder: {}", e))
+                    .ok()
+                })
+                .map(|decoder| (track, decoder))
+                .into_iter()
+                .collect()
+        } else {
+            ctx.call
+                .remote_tracks
+                .clone()
+                .into_iter()
+                .filter_map(|(track, mime_type)| {
+                    Some((track, Self::spawn_decoder(ctx, track, &mime_type)?))
+                })
+                .collect()
+        };
 
-            local_frame: None,
-            frame_sender: None,
+        let peer =
+            ctx.call.webrtc.as_ref().and_then(WebRTC::get_remote_id).or(ctx.call.target_id.clone());
+        // The connection's, as a loopback call always shares.
+        let mode = ctx.call.webrtc.as_ref().map_or(ctx.call.mode, WebRTC::call_mode);
+        // Incoming calls only open the screen once connected, which the Connected event that opened it moves it on to.
+        Self {
+            phase: CallPhase::Ringing { since: Instant::now() },
+            peer,
+            mode,
+            local_preview: LocalPreview::new(ctx.config.preview_fps),
+            capture_state: CaptureState::Idle,
+            sharing_info: None,
+            sharing_icon: None,
+            encoder: None,
+            sent_video: false,
+            #[cfg(feature = "gpu-frames")]
+            gpu_output: false,
+            switch_started: None,
+            capture_ops: CaptureOps::new(),
+            capture_op_task: None,
+            pending_share: None,
+            sharing_indicator: None,
             show_local_preview: false,
+            privacy: PrivacyRegions::default(),
+            shared_source: None,
+            remember_privacy: true,
+            encoder_stats: None,
+            capture_stats: None,
+            capture_stats_window: CaptureStatsWindow::default(),
+            frame_meta: None,
+            show_stats: false,
+            framerate_check: FramerateCheck::default(),
+            framerate_warning: None,
+            source_picker: None,
+            cursor_sender: None,
+            cursor_tracker: None,
+            highlight_clicks: false,
+            click_highlighter: None,
+            remote_control_allowed: false,
+            consent_prompt: None,
+            mirror_prompt: None,
+            mirrored: false,
+            quality_request: None,
+            recording: false,
+            recorder: None,
 
             remote_frame: None,
-            decoder: None,
+            remote_cursor: None,
+            remote_control_granted: false,
+            remote_control_requested: false,
+            remote_recording: false,
+            decoders,
+            software_decode_notified: false,
+            viewer_quality: ViewerQuality::default(),
+            remote_idle: RemoteIdle::new(ctx.config.remote_idle_after),
+            filmstrip: None,
+            fingerprints: None,
+            fingerprint_changed: false,
+            quality: QualityIndicator::new(),
+            quality_throttle: Throttle::new(CONNECTION_STATS_INTERVAL),
+            receive_counts: None,
+            summary: CallSummaryRecorder::new(),
+        }
+    }
+
+    // Our own settings, lowered to what the peer asked for, with the bitrate within the bandwidth cap.
+    fn encoding_settings(&self, ctx: &AppContext) -> EncodingSettings {
+        let settings = ctx.config.encoding_settings();
+        let settings = match &self.quality_request {
+            Some(request) => clamp_settings(settings, request),
+            None => settings,
+        };
+        EncodingSettings { bitrate: ctx.config.capped_bitrate(settings.bitrate), ..settings }
+    }
+
+    fn encoder_config(&self, ctx: &AppContext) -> EncoderConfig {
+        let settings = self.encoding_settings(ctx);
+        EncoderConfig {
+            bitrate: settings.bitrate,
+            target_fps_hz: settings.framerate.to_hz(),
+            max_dimension: settings.max_encode_dimension,
+            ..EncoderConfig::from_config(&ctx.config)
+        }
+    }
+
+    fn check_framerate(&mut self, ctx: &mut AppContext, now: Instant) {
+        let Some(stats) = self.encoder_stats else {
+            return;
+        };
+
+        match self.framerate_check.evaluate(now, stats.target_fps, stats.encoded_fps) {
+            Some(FramerateCheckEvent::Shortfall { target_fps, achieved_fps }) => {
+                tracing::warn!(
+                    "Encoding {:.0} of the {:.0} fps targeted",
+                    achieved_fps,
+                    target_fps
+                );
+                let (label, action) = Self::framerate_suggestion(ctx, achieved_fps);
+                self.framerate_warning = Some(ctx.notifier.in_app.info_with_action(
+                    tr!(
+                        "call.framerate_shortfall",
+                        achieved = achieved_fps.round(),
+                        target = target_fps.round()
+                    ),
+                    label,
+                    Message::Call(action),
+                ));
+            }
+            Some(FramerateCheckEvent::Recovered) => {
+                tracing::info!("Encoding caught up with the target framerate");
+                self.dismiss_framerate_warning(ctx);
+            }
+            None => {}
         }
     }
 
-    fn create_frame_receiver_subscription(data: &FrameReceiverSubData) -> PlatformCaptureStream {
+    // The Balanced preset if it is a step down, otherwise the highest framerate that is being achieved.
+    fn framerate_suggestion(ctx: &AppContext, achieved_fps: f32) -> (String, CallMessage) {
+        let balanced = QualityPreset::Balanced;
+        if balanced.settings().framerate < ctx.config.framerate {
+            return (
+                tr!("call.use_preset", preset = balanced),
+                CallMessage::QualityPresetSelected(balanced),
+            );
+        }
+
+        let framerate = CaptureFramerate::ALL
+            .iter()
+            .copied()
+            .rev()
+            .find(|framerate| framerate.to_hz() <= achieved_fps)
+            .unwrap_or(CaptureFramerate::FPS5);
+        (tr!("call.use_framerate", framerate = framerate), CallMessage::SetFramerate(framerate))
+    }
+
+    fn dismiss_framerate_warning(&mut self, ctx: &mut AppContext) {
+        if let Some(id) = self.framerate_warning.take() {
+            ctx.notifier.in_app.dismiss(id);
+        }
+    }
+
+    fn update_encoder_stats(&mut self, ctx: &mut AppContext, stats: EncoderStats) {
+        self.summary.record_frames_sent(stats.encoded);
+        ctx.call.send_bitrate = Some(stats.sent_bitrate_1s);
+        let previous = self.encoder_stats.replace(stats);
+        if stats.restarts > previous.map_or(0, |previous| previous.restarts) {
+            ctx.notifier.in_app.error(tr!("call.encoder_restarted"));
+        }
+
+        // The stats change every frame, but the user only needs to hear about the framerate.
+        if previous.is_none_or(|previous| previous.output_fps == stats.output_fps) {
+            return;
+        }
+
+        let message = if stats.output_fps < stats.target_fps {
+            tr!(
+                "call.encoder_behind",
+                target = stats.target_fps.round(),
+                output = stats.output_fps.round()
+            )
+        } else {
+            tr!("call.encoder_caught_up", output = stats.output_fps.round())
+        };
+        ctx.notifier.in_app.notify_once(ENCODER_FPS_NOTIFICATION, NotificationKind::Info, message);
+    }
+
+    // The workers run in the span they are spawned in, so their logs carry the call's.
+    fn call_span(ctx: &AppContext) -> Span {
+        ctx.call.webrtc.as_ref().map_or_else(Span::none, WebRTC::call_span)
+    }
+
+    // The decoder shown, of the latest track to start.
+    fn shown_decoder(&self) -> Option<&DecoderHandle> {
+        self.decoders.values().next_back()
+    }
+
+    // A decoder isn't replaced, as it would likely panic on the same packets again. The next track gets a new one.
+    fn check_decoders(&mut self, ctx: &mut AppContext) {
+        let shown = self.shown_decoder().map(DecoderHandle::track);
+        let panicked = self
+            .decoders
+            .iter()
+            .filter_map(|(&track, decoder)| Some((track, decoder.panic()?.to_owned())))
+            .collect::<Vec<_>>();
+        for (track, message) in panicked {
+            ctx.notifier.in_app.error(tr!("call.decoder_crashed", error = message));
+            self.decoders.remove(&track);
+            if Some(track) == shown {
+                self.remote_frame = None;
+                self.remote_idle.reset();
+            }
+        }
+
+        if !self.software_decode_notified
+            && ctx.config.decode_hw_accel != DecodeAccel::Software
+            && let Some(stats) = self.shown_decoder().map(DecoderHandle::stats)
+            && !stats.accel.is_hardware()
+        {
+            self.software_decode_notified = true;
+            ctx.notifier
+                .in_app
+                .info(tr!("call.software_decoding", accel = ctx.config.decode_hw_accel));
+        }
+    }
+
+    fn spawn_decoder(
+        ctx: &mut AppContext,
+        track: TrackId,
+        mime_type: &str,
+    ) -> Option<DecoderHandle> {
+        let _call = Self::call_span(ctx).entered();
+        let create_decoder = {
+            let mime_type = mime_type.to_owned();
+            move |accel| FFmpegDecoder::for_mime_type(&mime_type, accel)
+        };
+
+        let packets = ctx.call.channels.as_ref()?.packets(track);
+        let catch_up = CatchUp::for_mime_type(mime_type, ctx.config.max_receive_queue);
+        match DecoderWorker::spawn(
+            create_decoder,
+            ctx.config.decode_hw_accel,
+            track,
+            packets,
+            ctx.call.webrtc.clone(),
+            catch_up,
+        ) {
+            Ok(decoder) => Some(decoder),
+            Err(e) => {
+                tracing::error!("Failed to create decoder for {} of {}: {}", mime_type, track, e);
+                ctx.notifier.in_app.error(tr!("call.decoder_failed", error = e));
+                None
+            }
+        }
+    }
+
+    fn capture_state_stream(
+        data: &CaptureStateSubData,
+    ) -> Box<dyn futures::Stream<Item = CaptureState> + Send + Unpin> {
+        let capture = data.0.clone();
+        Box::new(Box::pin(futures::stream::unfold(
+            None,
+            move |states: Option<watch::Receiver<CaptureState>>| {
+                let capture = capture.clone();
+                async move {
+                    let mut states = match states {
+                        Some(mut states) => {
+                            states.changed().await.ok()?;
+                            states
+                        }
+                        // The current state comes first, as it may have changed before subscribing.
+                        None => capture.read().await.subscribe_state(),
+                    };
+                    let state = states.borrow_and_update().clone();
+                    Some((state, Some(states)))
+                }
+            },
+        )))
+    }
+
+    fn decoded_frame_stream(
+        decoder: &DecoderHandle,
+    ) -> Box<dyn futures::Stream<Item = (TrackId, Arc<Frame>)> + Send + Unpin> {
+        let track = decoder.track();
+        Box::new(Box::pin(futures::stream::unfold(decoder.frames(), move |frames| async move {
+            let frame = frames.lock().await.recv().await?;
+            Some(((track, frame), frames))
+        })))
+    }
+
+    // The provider may be locked for a while, e.g. by a capture being started, so the stream is made once it's free.
+    fn create_frame_receiver_subscription(
+        data: &FrameReceiverSubData,
+    ) -> Box<dyn futures::Stream<Item = Frame> + Send + Unpin> {
         tracing::info!("Creating frame receiver sub with framerate: {}", data.framerate);
 
-        data.capture
-            .blocking_write()
-            .create_stream(data.framerate)
-            .expect("Failed to create stream!")
+        let FrameReceiverSubData { framerate, thread_priority, hdr, rotation, .. } = *data;
+        Box::new(Box::pin(stream_when_unlocked(data.capture.clone(), move |capture| {
+            capture.set_elevated_thread_priority(thread_priority);
+            if let Err(e) = capture.set_hdr_capture(hdr) {
+                tracing::error!("Failed to switch the HDR capture: {}", e);
+            }
+            if let Err(e) = capture.set_rotation_correction(rotation) {
+                tracing::error!("Failed to switch the rotation correction: {}", e);
+            }
+            capture
+                // The subscription restarting means the previous stream is no longer listened to.
+                .create_stream(framerate, true)
+                .inspect_err(|e| tracing::error!("Failed to create frame stream: {}", e))
+                .ok()
+        })))
+    }
+
+    pub fn phase(&self) -> CallPhase {
+        self.phase
+    }
+
+    pub fn mode(&self) -> CallMode {
+        self.mode
+    }
+
+    // The capture provider, unless only viewing, in which case the call never touches it.
+    fn capture<'a>(&self, ctx: &'a AppContext) -> Option<&'a Arc<RwLock<PlatformCaptureProvider>>> {
+        ctx.capture.provider().filter(|_| self.mode.can_share())
+    }
+
+    /// Stops sharing and flushes the encoder, for when the app is about to exit.
+    pub fn shutdown(&mut self, ctx: &AppContext) -> impl Future<Output = ()> + Send + use<> {
+        let capture = self.capture(ctx).cloned().filter(|_| self.is_capturing());
+        let encoder = self.encoder.take();
+        self.cursor_tracker = None;
+        self.cursor_sender = None;
+        self.click_highlighter = None;
+
+        async move {
+            if let Some(capture) = capture
+                && let Err(e) = capture.write().await.stop_capture()
+            {
+                tracing::error!("Failed to stop capture: {}", e);
+            }
+
+            if let Some(encoder) = encoder
+                && let Err(e) = encoder.shutdown().await
+            {
+                tracing::debug!("Failed to shut down encoder: {}", e);
+            }
+        }
+    }
+
+    fn is_capturing(&self) -> bool {
+        matches!(
+            self.capture_state,
+            CaptureState::Starting | CaptureState::Capturing | CaptureState::Interrupted(_)
+        )
+    }
+
+    // Creates the capture provider on the first share, and shares once it is ready if there is what to share.
+    fn capture_unavailable(
+        &mut self,
+        ctx: &mut AppContext,
+        share: Option<CallMessage>,
+    ) -> Task<Message> {
+        if !self.mode.can_share() {
+            ctx.notifier.in_app.info(tr!("call.view_only_no_share"));
+            return Task::none();
+        }
+        let start = App::start_capture_provider(ctx);
+        match (&ctx.capture, share) {
+            (CaptureProviderState::Pending, Some(share)) => self.pending_share = Some(share),
+            (CaptureProviderState::Pending, None) => {
+                ctx.notifier.in_app.info(tr!("call.capture_pending"))
+            }
+            _ => {
+                tracing::error!("Tried to share screen, but no capture provider is available");
+                ctx.notifier.in_app.error(tr!("call.capture_unavailable"))
+            }
+        }
+        start
+    }
+
+    // Saves the source, so it is offered again next time. Sources that can't be found again aren't worth saving.
+    // Hides what was kept hidden of it before, or keeps hiding the same as before if nothing was.
+    fn remember_source(&mut self, ctx: &mut AppContext, saved: Option<SavedCaptureSource>) {
+        self.shared_source = saved.as_ref().map(|saved| saved.id.clone());
+        if let Some(regions) =
+            self.shared_source.as_ref().and_then(|source| ctx.config.privacy_regions(source))
+        {
+            self.privacy.set_regions(regions.to_vec());
+        }
+        let Some(saved) = saved else {
+            tracing::debug!("Selected capture source can't be saved");
+            return;
+        };
+        if ctx.config.last_capture_source.as_ref() == Some(&saved) {
+            return;
+        }
+
+        ctx.config.last_capture_source = Some(saved);
+        if let Err(e) = ctx.store.save_config(&ctx.config) {
+            tracing::error!("Failed to save last capture source: {}", e);
+        }
+    }
+
+    // The app's windows that show on the monitor, with where they are.
+    fn windows_on(ctx: &AppContext, monitor: Rect<i32>) -> Vec<(window::Id, Rect<i32>)> {
+        [
+            (ctx.windows.main_id, ctx.windows.main_handle),
+            (ctx.windows.popout_id, ctx.windows.popout_handle),
+        ]
+        .into_iter()
+        .filter_map(|(id, handle)| {
+            let bounds = platform_window_bounds(handle?)
+                .inspect_err(|e| tracing::debug!("Failed to find where a window is: {}", e))
+                .ok()??;
+            Some((id?, bounds))
+        })
+        .filter(|(_, bounds)| mirror_warning::shows_on(monitor, *bounds))
+        .collect()
+    }
+
+    fn move_windows_off(ctx: &AppContext, source: &SourceDescriptor) -> Task<Message> {
+        let Some(monitor) = source.monitor_bounds() else {
+            return Task::none();
+        };
+        let monitors = match platform_monitor_layout() {
+            Ok(monitors) => monitors,
+            Err(e) => {
+                tracing::warn!("Failed to list the monitors: {}", e);
+                return Task::none();
+            }
+        };
+        Task::batch(Self::windows_on(ctx, monitor).into_iter().filter_map(|(id, bounds)| {
+            let position = mirror_warning::move_off(&monitors, monitor, bounds)?;
+            Some(window::move_to(id, position))
+        }))
+    }
+
+    // Checks whether the app's windows are on the shared monitor, as they may have been moved there since sharing it.
+    fn check_mirrored(&mut self, ctx: &AppContext) {
+        let monitor = self
+            .sharing_info
+            .as_ref()
+            .filter(|info| info.kind == SourceKind::Monitor)
+            .and_then(|_| self.cursor_tracker.as_ref()?.capture_bounds());
+        let mirrored = monitor.is_some_and(|monitor| !Self::windows_on(ctx, monitor).is_empty());
+        if mirrored != self.mirrored {
+            tracing::info!(mirrored, "The call is shown on the shared monitor");
+            self.mirrored = mirrored;
+        }
+    }
+
+    // Keeps the privacy regions for the shared source, or forgets those kept for it if they aren't to be kept.
+    fn keep_privacy_regions(&self, ctx: &mut AppContext) {
+        let Some(source) = &self.shared_source else {
+            return;
+        };
+        let regions = if self.remember_privacy { self.privacy.regions() } else { &[] };
+        if ctx.config.privacy_regions(source).unwrap_or_default() == regions {
+            return;
+        }
+        ctx.config.set_privacy_regions(source, regions);
+        if let Err(e) = ctx.store.save_config(&ctx.config) {
+            tracing::error!("Failed to save privacy regions: {}", e);
+        }
+    }
+
+    // Starts a capture with the item, or switches the running one over to it.
+    fn use_capture_item(
+        &mut self,
+        ctx: &mut AppContext,
+        item: PlatformCaptureItem,
+    ) -> Task<Message> {
+        self.request_capture_op(ctx, CaptureOp::Start(item))
+    }
+
+    fn request_capture_op(
+        &mut self,
+        ctx: &mut AppContext,
+        op: CaptureOp<PlatformCaptureItem>,
+    ) -> Task<Message> {
+        let Some(capture) = self.capture(ctx).cloned() else {
+            return match op {
+                CaptureOp::Start(_) => self.capture_unavailable(ctx, None),
+                // Nothing can be capturing without a provider.
+                CaptureOp::Stop | CaptureOp::Recover => {
+                    Task::done(Message::Call(CallMessage::CaptureStopped))
+                }
+            };
+        };
+
+        match self.capture_ops.request(op) {
+            CaptureOpDecision::Run(id, op) => self.run_capture_op(capture, id, op),
+            CaptureOpDecision::Queued => {
+                tracing::debug!("Starting the capture once it has stopped");
+                Task::none()
+            }
+            CaptureOpDecision::Ignored => Task::none(),
+        }
+    }
+
+    fn run_capture_op(
+        &mut self,
+        capture: Arc<RwLock<PlatformCaptureProvider>>,
+        id: CaptureOpId,
+        op: CaptureOp<PlatformCaptureItem>,
+    ) -> Task<Message> {
+        if matches!(op, CaptureOp::Start(_)) && self.is_capturing() {
+            tracing::info!("Switching capture source");
+            self.switch_started = Some(Instant::now());
+        }
+
+        let (task, handle) = Task::future(async move {
+            let outcome = with_write_lock(&capture, CAPTURE_LOCK_TIMEOUT, |capture| {
+                apply_capture_op(capture, op)
+            })
+            .await
+            .unwrap_or_else(|| {
+                Err(tr!("call.capture_busy", seconds = CAPTURE_LOCK_TIMEOUT.as_secs()))
+            });
+            Message::Call(CallMessage::CaptureOpFinished(id, outcome.map(Box::new)))
+        })
+        .abortable();
+        // Replacing the handle aborts the superseded operation, if it is still waiting for the lock.
+        self.capture_op_task = Some(handle.abort_on_drop());
+        task
+    }
+
+    fn capture_op_finished(
+        &mut self,
+        ctx: &mut AppContext,
+        kind: CaptureOpKind,
+        outcome: Result<Box<CaptureOpOutcome>, String>,
+    ) -> Task<Message> {
+        match (kind, outcome.map(|outcome| *outcome)) {
+            (_, Ok(CaptureOpOutcome::Started { item, info, switched })) => {
+                // The peer can't decode the new content from the old references.
+                if switched
+                    && let Some(encoder) = &self.encoder
+                    && let Err(e) = encoder.request_keyframe()
+                {
+                    tracing::warn!("Failed to request keyframe: {}", e);
+                }
+                self.track_cursor(ctx, &item);
+                self.set_sharing_info(info);
+                self.show_sharing_indicator(ctx)
+            }
+            (_, Ok(CaptureOpOutcome::Stopped)) => {
+                Task::done(Message::Call(CallMessage::CaptureStopped))
+            }
+            (CaptureOpKind::Start, Err(err)) => {
+                tracing::error!("Failed to start capture: {}", err);
+                ctx.notifier.in_app.error(tr!("call.share_failed", error = err));
+                self.switch_started = None;
+                Task::none()
+            }
+            (CaptureOpKind::Recover, Err(err)) => {
+                tracing::error!("Failed to recover capture: {}", err);
+                ctx.notifier.in_app.error(tr!("call.capture_stopped", error = err));
+                Task::done(Message::Call(CallMessage::StopCapture))
+            }
+            // Cleaned up anyway, as whatever is left of the capture isn't wanted anymore.
+            (CaptureOpKind::Stop, Err(err)) => {
+                tracing::error!("Failed to stop capture: {}", err);
+                Task::done(Message::Call(CallMessage::CaptureStopped))
+            }
+        }
+    }
+
+    // Only makes the icon drawable again when it changed, as that uploads it anew.
+    fn set_sharing_info(&mut self, info: Option<CaptureItemInfo>) {
+        let icon = info.as_ref().and_then(|info| info.icon.as_ref());
+        if icon != self.sharing_info.as_ref().and_then(|info| info.icon.as_ref()) {
+            self.sharing_icon = icon.map(icon_handle);
+        }
+        self.sharing_info = info;
+    }
+
+    // Opens the indicator over the shared monitor, or moves it there if the source was switched.
+    fn show_sharing_indicator(&mut self, ctx: &mut AppContext) -> Task<Message> {
+        let monitor =
+            self.cursor_tracker.as_ref().and_then(|tracker| tracker.capture_bounds()).and_then(
+                |bounds| {
+                    platform_monitor_geometry(bounds)
+                        .inspect_err(|e| tracing::warn!("Failed to find the shared monitor: {}", e))
+                        .ok()
+                },
+            );
+
+        if let Some(id) = ctx.windows.sharing_indicator {
+            return match monitor {
+                Some(monitor) => window::move_to(id, SharingIndicator::position(&monitor)),
+                None => Task::none(),
+            };
+        }
+
+        self.sharing_indicator = Some(SharingIndicator::new(Instant::now()));
+        let (id, open_task) = window::open(SharingIndicator::window_settings(monitor.as_ref()));
+        ctx.windows.sharing_indicator = Some(id);
+        open_task
+            .then(window::raw_id::<Message>)
+            .map(|raw_id| Message::Call(CallMessage::SharingIndicatorOpened(raw_id)))
+    }
+
+    fn hide_sharing_indicator(&mut self, ctx: &mut AppContext) -> Task<Message> {
+        self.sharing_indicator = None;
+        match ctx.windows.sharing_indicator.take() {
+            Some(id) => window::close(id),
+            None => Task::none(),
+        }
+    }
+
+    fn track_cursor(&mut self, ctx: &mut AppContext, capture_item: &PlatformCaptureItem) {
+        // The cursor is an overlay on the viewer's side, so a missing tracker isn't fatal.
+        self.cursor_tracker = PlatformCursorTracker::new(capture_item)
+            .inspect_err(|err| tracing::warn!("Failed to track cursor: {}", err))
+            .ok();
+        self.cursor_sender = match (self.cursor_tracker.clone(), ctx.call.webrtc.clone()) {
+            (Some(tracker), Some(webrtc)) => {
+                Some(Arc::new(Self::spawn_cursor_sender(tracker, webrtc)))
+            }
+            _ => None,
+        };
+        self.update_click_highlighter(ctx);
+    }
+
+    // Starts watching the clicks on what is shared now, or stops if they aren't highlighted or nothing is shared.
+    fn update_click_highlighter(&mut self, ctx: &mut AppContext) {
+        // The old hook goes first, so there is never more than one.
+        self.click_highlighter = None;
+        let Some(tracker) = self.cursor_tracker.clone().filter(|_| self.highlight_clicks) else {
+            return;
+        };
+        match ClickHighlighter::start(tracker) {
+            Ok(highlighter) => self.click_highlighter = Some(Arc::new(highlighter)),
+            Err(e) => {
+                tracing::error!("Failed to highlight clicks: {}", e);
+                ctx.notifier.in_app.error(tr!("call.highlight_clicks_failed", error = e));
+                self.highlight_clicks = false;
+            }
+        }
+    }
+
+    // Sends the presenter's cursor to the peer whenever it moves.
+    fn spawn_cursor_sender(tracker: PlatformCursorTracker, webrtc: WebRTC) -> AbortOnDrop {
+        const CURSOR_UPDATE_INTERVAL: Duration = Duration::from_millis(33); // ~30 Hz
+
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CURSOR_UPDATE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+            let mut last_position = None;
+            loop {
+                interval.tick().await;
+
+                let position = tracker.poll();
+                if position == last_position {
+                    continue;
+                }
+                last_position = position;
+
+                // The control channel isn't open until the peer connects, so just try again on the next move.
+                if let Err(e) = webrtc.send_control(&ControlMessage::Cursor(position)).await {
+                    tracing::trace!("Failed to send cursor position: {}", e);
+                }
+            }
+        });
+
+        AbortOnDrop(task.abort_handle())
+    }
+
+    fn fetch_fingerprints(ctx: &AppContext) -> Task<Message> {
+        let Some(webrtc) = ctx.call.webrtc.clone() else {
+            return Task::none();
+        };
+
+        Task::perform(async move { webrtc.fingerprints().await }, |fingerprints| {
+            Message::Call(CallMessage::FingerprintsReady(fingerprints.map(Box::new)))
+        })
+    }
+
+    fn fetch_connection_stats(&mut self, ctx: &AppContext, now: Instant) -> Task<Message> {
+        if !matches!(self.phase, CallPhase::Connected { .. }) || !self.quality_throttle.pass(now) {
+            return Task::none();
+        }
+        let Some(webrtc) = ctx.call.webrtc.clone() else {
+            return Task::none();
+        };
+
+        Task::perform(async move { webrtc.connection_stats().await }, |stats| {
+            Message::Call(CallMessage::ConnectionStatsReady(Box::new(stats)))
+        })
+    }
+
+    // Saves a snapshot of the remote video for the filmstrip, once the interval since the last one has passed.
+    fn take_snapshot(&mut self, ctx: &AppContext, now: Instant) -> Task<Message> {
+        if !ctx.config.filmstrip || !matches!(self.phase, CallPhase::Connected { .. }) {
+            return Task::none();
+        }
+
+        let budget = ctx.config.filmstrip_budget_mb * 1024 * 1024;
+        let evicted = self.filmstrip.as_mut().map(|f| f.set_budget(budget)).unwrap_or_default();
+        let delete = Self::delete_snapshots(evicted);
+        // Nothing new to keep while the content is static.
+        let Some(frame) = self.remote_frame.clone() else {
+            return delete;
+        };
+        if self.remote_idle.is_idle(now) {
+            return delete;
+        }
+
+        let filmstrip = match &mut self.filmstrip {
+            Some(filmstrip) => filmstrip,
+            None => {
+                let Some(dir) = filmstrip::dir() else {
+                    return delete;
+                };
+                let peer = self.peer.as_deref().unwrap_or("unknown");
+                let folder = dir.join(session_folder_name(peer, SystemTime::now()));
+                tracing::info!("Saving snapshots of the call to {}", folder.display());
+                self.filmstrip.insert(Filmstrip::new(folder, now, budget))
+            }
+        };
+        if !filmstrip.due(now, ctx.config.filmstrip_interval) {
+            return delete;
+        }
+
+        let path = filmstrip.take(now);
+        let save = Task::future(async move {
+            let result = tokio::task::spawn_blocking(move || Snapshot::save(&frame, path))
+                .await
+                .expect("Saving a snapshot panicked");
+            Message::Call(CallMessage::SnapshotSaved(
+                result.map(Box::new).map_err(|e| e.to_string()),
+            ))
+        });
+        Task::batch([delete, save])
+    }
+
+    // Records the shown remote track to a file named after the call, like the folder of its snapshots.
+    fn start_recording(&mut self, ctx: &mut AppContext) -> Task<Message> {
+        let Some(decoder) = self.shown_decoder() else {
+            return Task::none();
+        };
+        let Some(dir) = recorder::dir() else {
+            ctx.notifier.in_app.error(tr!("call.recording_failed", error = "no folder to save to"));
+            return Task::none();
+        };
+        // Until the tracks are known, the decoder is the one for the codec we'd pick.
+        let mime_type = match ctx.call.remote_tracks.get(&decoder.track()) {
+            Some(mime_type) => mime_type.clone(),
+            None => ctx.config.transcoding_type.mime_type().to_owned(),
+        };
+        let peer = self.peer.as_deref().unwrap_or("unknown");
+        let name = session_folder_name(peer, SystemTime::now());
+        match Recorder::start(&dir, &name, &mime_type) {
+            Ok(recorder) => {
+                tracing::info!("Recording the call to {}", recorder.path().display());
+                decoder.record(Some(recorder.tap()));
+                self.recorder = Some(recorder);
+                Task::done(Message::Call(CallMessage::RecordingChanged(true)))
+            }
+            Err(e) => {
+                tracing::error!("Failed to start recording: {}", e);
+                ctx.notifier.in_app.error(tr!("call.recording_failed", error = e));
+                Task::none()
+            }
+        }
+    }
+
+    // Stops recording, if it does, and reports the recording once what was queued of it is written.
+    fn stop_recording(&mut self) -> Task<Message> {
+        let Some(recorder) = self.recorder.take() else {
+            return Task::none();
+        };
+        for decoder in self.decoders.values() {
+            decoder.record(None);
+        }
+        let path = recorder.path().to_owned();
+        Task::batch([
+            Task::done(Message::Call(CallMessage::RecordingChanged(false))),
+            Task::perform(recorder.stop(), move |result| {
+                Message::Call(CallMessage::RecordingSaved(path, result.map_err(|e| e.to_string())))
+            }),
+        ])
+    }
+
+    fn delete_snapshots(paths: Vec<PathBuf>) -> Task<Message> {
+        if paths.is_empty() {
+            return Task::none();
+        }
+
+        Task::future(tokio::task::spawn_blocking(move || {
+            for path in paths {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    tracing::warn!("Failed to delete snapshot {}: {}", path.display(), e);
+                }
+            }
+        }))
+        .discard()
+    }
+
+    // Adds what only this side knows to the stats, i.e. how the video received fared and the bitrate aimed for.
+    fn update_quality(&mut self, ctx: &AppContext, mut stats: ConnectionStats) {
+        if let (Some(sent), Some(received)) = (stats.bytes_sent, stats.bytes_received) {
+            self.summary.record_bytes(sent, received);
+        }
+        if self.is_capturing() {
+            stats.target_bitrate = Some(self.encoding_settings(ctx).bitrate as f64);
+        }
+
+        if !self.decoders.is_empty() {
+            self.summary.record_frames_received(self.frames_decoded());
+        }
+        let shown = self.shown_decoder().map(|decoder| (decoder.track(), decoder.stats()));
+        if let (Some((track, decoder_stats)), Some(webrtc)) = (shown, &ctx.call.webrtc) {
+            let depacket_stats = webrtc.depacket_stats(track);
+            let counts =
+                (depacket_stats.packets, depacket_stats.dropped_packets, decoder_stats.catch_ups);
+            // The counts start over with a new track or decoder, which the saturation keeps from counting as a lot.
+            if let Some((packets, dropped, catch_ups)) = self.receive_counts.replace(counts) {
+                let packets = counts.0.saturating_sub(packets);
+                let dropped = counts.1.saturating_sub(dropped);
+                if packets > 0 {
+                    let loss = (dropped as f32 / packets as f32).min(1.0);
+                    stats.packet_loss = Some(stats.packet_loss.map_or(loss, |sent| sent.max(loss)));
+                }
+                stats.freezes = counts.2.saturating_sub(catch_ups);
+            }
+        }
+
+        self.quality.update(&stats);
+    }
+
+    // Of all the tracks, which starts over lower when one ends, as its decoder is dropped.
+    fn frames_decoded(&self) -> u64 {
+        self.decoders.values().map(|decoder| decoder.stats().decoded).sum()
+    }
+
+    // Once the call ended or was left. Whatever is left of it afterwards was never connected, and isn't kept.
+    fn finish_summary(&mut self, ctx: &mut AppContext) {
+        if !self.decoders.is_empty() {
+            self.summary.record_frames_received(self.frames_decoded());
+        }
+        let summary = std::mem::take(&mut self.summary).finish(
+            self.peer.clone(),
+            Instant::now(),
+            SystemTime::now(),
+        );
+        let Some(summary) = summary else {
+            return;
+        };
+
+        tracing::info!("Call summary: {:?}", summary);
+        ctx.call_history.add(summary.clone());
+        if let Err(e) = ctx.store.save_call_history(&ctx.call_history) {
+            tracing::error!("Failed to save call history: {}", e);
+        }
+        ctx.last_call_summary = Some(summary);
+    }
+
+    // Bars for how good the connection is, with what holds it back on hover.
+    fn quality_view(&self) -> Option<Element<'_, Message>> {
+        if !matches!(self.phase, CallPhase::Connected { .. }) {
+            return None;
+        }
+        let level = self.quality.level()?;
+        let color = match level.bars() {
+            1 => iced::Color::from_rgb8(0xd9, 0x3b, 0x3b),
+            2 => iced::Color::from_rgb8(0xe0, 0x9b, 0x1a),
+            _ => iced::Color::from_rgb8(0x3c, 0xb3, 0x5a),
+        };
+        let bars = row((1..=4).map(|bar| {
+            let color = if bar <= level.bars() {
+                color
+            } else {
+                iced::Color::from_rgba8(128, 128, 128, 0.4)
+            };
+            container(iced::widget::Space::new())
+                .width(Length::Fixed(4.0))
+                .height(Length::Fixed(4.0 * bar as f32))
+                .style(move |_| container::Style {
+                    background: Some(iced::Background::Color(color)),
+                    border: iced::Border { radius: 1.0.into(), ..Default::default() },
+                    ..Default::default()
+                })
+                .into()
+        }))
+        .spacing(2)
+        .align_y(iced::alignment::Vertical::Bottom);
+
+        let description = match self.quality.limit() {
+            Some(limit) => tr!("call.connection_limited", level = level, limit = limit),
+            None => tr!("call.connection", level = level),
+        };
+        Some(
+            tooltip(
+                container(bars).padding(5),
+                container(text(description)).padding(5).style(container::rounded_box),
+                tooltip::Position::Bottom,
+            )
+            .into(),
+        )
+    }
+
+    // "Sharing: <icon> Title (process) — 1920×1080", with the icon and process for windows only.
+    fn sharing_label<'a>(&'a self, info: &'a CaptureItemInfo) -> Element<'a, Message> {
+        let details = match &info.process_name {
+            Some(process) => tr!(
+                "call.sharing_window",
+                name = info.name,
+                process = process,
+                width = info.size.x,
+                height = info.size.y
+            ),
+            None => tr!(
+                "call.sharing_source",
+                name = info.name,
+                width = info.size.x,
+                height = info.size.y
+            ),
+        };
+
+        let label = row![text(tr!("call.sharing")).size(12)].spacing(5).align_y(Vertical::Center);
+        let label = match info.kind {
+            SourceKind::Window => label.push(icon_view(self.sharing_icon.as_ref(), 12.0)),
+            _ => label,
+        };
+        label.push(text(details).size(12)).into()
+    }
+
+    // The security code to compare with the peer, and whether they were verified already.
+    fn security_view(&self, ctx: &AppContext) -> Option<Element<'_, Message>> {
+        let fingerprints = self.fingerprints.as_ref()?;
+        let verified = ctx.config.is_verified(&fingerprints.remote);
+        let code = if verified {
+            tr!("call.verified", code = fingerprints.short_auth_string())
+        } else {
+            tr!("call.verify", code = fingerprints.short_auth_string())
+        };
+
+        let sealed = ctx.call.webrtc.as_ref().is_some_and(|webrtc| webrtc.is_signaling_sealed());
+        let row = row![
+            sealed.then(|| text(tr!("call.signaling_encrypted")).size(12)),
+            text(code).size(12),
+            checkbox(verified)
+                .label(tr!("call.codes_match"))
+                .text_size(12)
+                .on_toggle(|verified| Message::Call(CallMessage::SetPeerVerified(verified))),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center);
+
+        if !self.fingerprint_changed {
+            return Some(container(row).center_x(Length::Fill).into());
+        }
+        Some(
+            column![
+                container(text(tr!("call.fingerprint_changed")))
+                    .padding(5)
+                    .center_x(Length::Fill)
+                    .style(container::danger),
+                container(row).center_x(Length::Fill),
+            ]
+            .into(),
+        )
+    }
+
+    fn send_control(ctx: &AppContext, message: ControlMessage) -> Task<Message> {
+        let Some(webrtc) = ctx.call.webrtc.clone() else {
+            return Task::none();
+        };
+
+        Task::future(async move {
+            if let Err(e) = webrtc.send_control(&message).await {
+                tracing::debug!("Failed to send control message {:?}: {}", message, e);
+            }
+            Message::NoOp
+        })
+    }
+
+    /// Revokes remote control if the panic hotkey is held down. The app polls this whichever screen is shown, as the
+    /// call carries on behind the others.
+    pub fn check_panic_hotkey(&mut self, ctx: &mut AppContext) -> Task<Message> {
+        if self.remote_control_allowed && input_injection::is_panic_hotkey_pressed() {
+            tracing::info!("Panic hotkey pressed");
+            return self.set_remote_control_allowed(ctx, false);
+        }
+        Task::none()
+    }
+
+    fn set_remote_control_allowed(&mut self, ctx: &mut AppContext, allowed: bool) -> Task<Message> {
+        if self.remote_control_allowed == allowed {
+            return Task::none();
+        }
+        self.remote_control_allowed = allowed;
+
+        if allowed {
+            tracing::info!("Remote control granted");
+            ctx.notifier.in_app.notify_once(
+                REMOTE_CONTROL_NOTIFICATION,
+                NotificationKind::Info,
+                tr!("call.remote_control_granted"),
+            );
+        } else {
+            tracing::info!("Remote control revoked");
+            ctx.notifier.in_app.notify_once(
+                REMOTE_CONTROL_NOTIFICATION,
+                NotificationKind::Info,
+                tr!("call.remote_control_revoked"),
+            );
+        }
+        Self::send_control(ctx, ControlMessage::RemoteControl(allowed))
+    }
+
+    // The peer asks to control what we share, which the user agrees to unless they did for good already.
+    fn remote_control_requested(&mut self, ctx: &mut AppContext) -> Task<Message> {
+        if !self.is_capturing() {
+            return Self::send_control(ctx, ControlMessage::RemoteControl(false));
+        }
+        if self.remote_control_allowed {
+            return Self::send_control(ctx, ControlMessage::RemoteControl(true));
+        }
+
+        let peer = self.peer.clone().unwrap_or_default();
+        if ctx.consents.accept_standing(&peer, ConsentKind::RemoteControl, SystemTime::now()) {
+            tracing::info!(%peer, "Remote control allowed by a standing grant");
+            Self::save_consents(ctx);
+            return self.set_remote_control_allowed(ctx, true);
+        }
+
+        tracing::info!(%peer, "Asking the user to allow remote control");
+        self.consent_prompt = Some(ConsentKind::RemoteControl);
+        Task::none()
+    }
+
+    fn save_consents(ctx: &mut AppContext) {
+        if let Err(e) = ctx.store.save_consents(&ctx.consents) {
+            tracing::error!("Failed to save consents: {}", e);
+            ctx.notifier.in_app.error(tr!("settings.consents_save_failed", error = e));
+        }
+    }
+
+    fn consent_prompt_view(&self, kind: ConsentKind) -> Element<'_, Message> {
+        let peer = self.peer.as_deref().unwrap_or(tr!("call.consent_unknown_peer"));
+        let answer =
+            |accepted, always| Message::Call(CallMessage::ConsentAnswered { accepted, always });
+        confirm_dialog(
+            tr!("call.consent_title", kind = kind),
+            tr!("call.consent_body", peer = peer, kind = kind, description = kind.description()),
+            [
+                button(tr!("call.consent_allow")).on_press(answer(true, false)).into(),
+                button(text(tr!("call.consent_always_allow", peer = peer)))
+                    .style(button::secondary)
+                    .on_press(answer(true, true))
+                    .into(),
+                button(tr!("call.consent_decline"))
+                    .style(button::danger)
+                    .on_press(answer(false, false))
+                    .into(),
+            ],
+        )
+    }
+
+    fn mirror_prompt_view(prompt: &MirrorPrompt) -> Element<'_, Message> {
+        let answer = |answer| Message::Call(CallMessage::MirrorAnswered(answer));
+        confirm_dialog(
+            tr!("call.mirror_title"),
+            tr!("call.mirror_body", name = prompt.source.name),
+            [
+                prompt.can_move.then(|| {
+                    button(tr!("call.mirror_move"))
+                        .on_press(answer(MirrorAnswer::MoveWindows))
+                        .into()
+                }),
+                Some(
+                    button(tr!("call.mirror_share_anyway"))
+                        .style(button::secondary)
+                        .on_press(answer(MirrorAnswer::ShareAnyway))
+                        .into(),
+                ),
+                Some(
+                    button(tr!("common.cancel"))
+                        .style(button::secondary)
+                        .on_press(answer(MirrorAnswer::Cancel))
+                        .into(),
+                ),
+            ]
+            .into_iter()
+            .flatten(),
+        )
+    }
+
+    // The latest snapshots along the bottom, to click on for a closer look.
+    fn filmstrip_view(filmstrip: &Filmstrip) -> Option<Element<'_, Message>> {
+        let mut snapshots = filmstrip.snapshots().peekable();
+        snapshots.peek()?;
+        let size = Snapshot::THUMBNAIL_SIZE;
+        let thumbnails = row(snapshots.map(|snapshot| {
+            mouse_area(
+                container(image(snapshot.thumbnail.clone()))
+                    .width(Length::Fixed(size.x as f32))
+                    .height(Length::Fixed(size.y as f32))
+                    .style(container::bordered_box),
+            )
+            .on_press(Message::Call(CallMessage::ShowSnapshot(Some(snapshot.path.clone()))))
+            .into()
+        }))
+        .spacing(6);
+        Some(container(thumbnails).padding(6).center_x(Length::Fill).into())
+    }
+
+    fn snapshot_view(path: &std::path::Path) -> Element<'_, Message> {
+        let close = Message::Call(CallMessage::ShowSnapshot(None));
+        let snapshot = mouse_area(image(image::Handle::from_path(path))).on_press(close.clone());
+        let content = column![
+            snapshot,
+            button(tr!("call.close_snapshot")).style(button::secondary).on_press(close),
+        ]
+        .spacing(10)
+        .align_x(iced::alignment::Horizontal::Center);
+        container(container(content).padding(10).style(container::rounded_box))
+            .padding(40)
+            .center(Length::Fill)
+            .into()
+    }
+
+    fn inject_remote_input(&self, event: InputEvent) {
+        if !self.remote_control_allowed {
+            tracing::warn!("Ignoring remote input, as remote control is not allowed");
+            return;
+        }
+
+        let Some(transform) = self.cursor_tracker.as_ref().and_then(|t| t.transform()) else {
+            tracing::debug!("Ignoring remote input, as the captured region is unknown");
+            return;
+        };
+
+        if let Err(e) = input_injection::inject(event, &transform) {
+            tracing::warn!("Failed to inject remote input: {}", e);
+        }
+    }
+
+    fn remote_view(&self) -> Element<'_, Message> {
+        if !matches!(self.phase, CallPhase::Connected { .. }) {
+            return self.phase_view();
+        }
+
+        match self.remote_frame.clone() {
+            Some(frame) => {
+                let mut viewer = FrameViewer::new(frame).with_cursor(self.remote_cursor);
+                if self.remote_control_granted {
+                    viewer = viewer.on_input(|input| Message::Call(CallMessage::SendInput(input)));
+                }
+                let viewer = container(viewer).center(Length::Fill);
+                if !self.remote_idle.is_idle(Instant::now()) {
+                    return viewer.into();
+                }
+
+                // Unlike reconnecting, the call is fine, there is just nothing new to show.
+                let badge = container(text(tr!("call.remote_idle")).size(12))
+                    .padding(6)
+                    .style(container::rounded_box);
+                stack![
+                    viewer,
+                    container(badge)
+                        .width(Length::Fill)
+                        .height(Length::Fill)
+                        .align_x(iced::alignment::Horizontal::Center)
+                        .align_y(iced::alignment::Vertical::Bottom)
+                        .padding(20)
+                ]
+                .into()
+            }
+            None => {
+                container(text(tr!("call.waiting_for_video")).size(30)).center(Length::Fill).into()
+            }
+        }
+    }
+
+    // Where the call is at, until it has connected.
+    fn phase_view(&self) -> Element<'_, Message> {
+        let now = Instant::now();
+        let peer = self.peer.as_deref().unwrap_or(tr!("call.unknown_peer"));
+        let elapsed = self.phase.elapsed(now).as_secs();
+        // Dots that count up, to show it is still working on it.
+        let dots = ".".repeat(elapsed as usize % 3 + 1);
+
+        let (status, detail, action) = match self.phase {
+            CallPhase::Ringing { .. } => (
+                tr!("call.ringing", peer = peer, dots = dots),
+                tr!("call.seconds", seconds = elapsed),
+                tr!("common.cancel"),
+            ),
+            CallPhase::Connecting { .. } => (
+                tr!("call.connecting", peer = peer, dots = dots),
+                tr!("call.seconds", seconds = elapsed),
+                tr!("common.cancel"),
+            ),
+            CallPhase::Reconnecting { .. } => (
+                tr!("call.reconnecting", peer = peer, dots = dots),
+                tr!("call.in_call_for", duration = format!("{}:{:02}", elapsed / 60, elapsed % 60)),
+                tr!("call.hang_up"),
+            ),
+            CallPhase::Connected { .. } => {
+                (tr!("call.connected", peer = peer), String::new(), tr!("call.hang_up"))
+            }
+            CallPhase::Ended(reason) => {
+                (tr!("home.call_ended").to_owned(), reason.to_string(), tr!("common.back"))
+            }
+        };
+
+        container(
+            column![
+                text(status).size(30),
+                text(detail).size(16),
+                button(action).on_press(Message::Call(CallMessage::EndCall)).padding(10)
+            ]
+            .spacing(15)
+            .align_x(iced::Alignment::Center),
+        )
+        .center(Length::Fill)
+        .into()
+    }
+
+    /// The view of the sharing indicator window.
+    pub fn view_sharing_indicator(&self, _ctx: &AppContext) -> Element<'_, Message> {
+        let name = self.sharing_info.as_ref().map(|info| info.name.as_str());
+        match &self.sharing_indicator {
+            Some(indicator) => indicator.view(name, self.mirrored),
+            None => container(text("")).into(),
+        }
+    }
+
+    /// The view of the pop-out window, which only shows the remote video.
+    pub fn view_popout(&self, _ctx: &AppContext) -> Element<'_, Message> {
+        let fullscreen_button = container(
+            button(tr!("call.fullscreen")).on_press(Message::Call(CallMessage::ToggleFullscreen)),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .align_x(iced::alignment::Horizontal::Right);
+
+        stack![self.remote_view(), fullscreen_button].into()
+    }
+
+    // Only worked out while the stats are shown. The provider is skipped while busy, so the UI never waits on it.
+    fn update_capture_stats(&mut self, ctx: &AppContext, metrics: &MetricsSnapshot) {
+        if !self.show_stats || !self.is_capturing() {
+            return;
+        }
+        self.capture_stats = Some(self.capture_stats_window.stats(metrics));
+        if self.frame_meta.is_none()
+            && let Some(capture) = self.capture(ctx)
+            && let Ok(capture) = capture.try_read()
+        {
+            self.frame_meta = Some(capture.subscribe_frame_meta());
+        }
+    }
+
+    // The encoder only keeps its stats in the metrics, so they are read on the tick rather than with every frame.
+    fn poll_encoder_stats(&mut self, ctx: &mut AppContext, metrics: &MetricsSnapshot) {
+        if self.encoder.is_none() {
+            return;
+        }
+        if let Some(stats) = EncoderStats::from_snapshot(metrics)
+            && self.encoder_stats != Some(stats)
+        {
+            self.update_encoder_stats(ctx, stats);
+        }
+    }
+
+    // Has the capture leave the frames on the GPU for as long as the encoder takes them from there.
+    #[cfg(feature = "gpu-frames")]
+    fn update_gpu_output(&mut self, ctx: &AppContext) {
+        let accepts = self.encoder.as_ref().is_some_and(EncoderHandle::accepts_gpu_frames);
+        if accepts == self.gpu_output {
+            return;
+        }
+        if let Some(capture) = self.capture(ctx)
+            && let Ok(capture) = capture.try_read()
+        {
+            // Not retried on failure, as the frames keep coming from memory then.
+            if let Err(e) = capture.set_gpu_output(accepts) {
+                tracing::warn!("Failed to switch capture to GPU frames: {}", e);
+            }
+            self.gpu_output = accepts;
+        }
+    }
+
+    // The encoder stats come with the bandwidth cap, which what is sent is shown against.
+    fn stats_overlay<'a>(
+        stats: Option<(&'a EncoderStats, Option<u32>)>,
+        capture_stats: Option<&'a CaptureStats>,
+        frame_meta: Option<FrameMeta>,
+        decoder_stats: Option<DecoderStats>,
+        depacket_stats: Option<DepacketStats>,
+        since_last_frame: Option<Duration>,
+        impairment_stats: [Option<ImpairmentStats>; 2],
+    ) -> Element<'a, Message> {
+        container(
+            column![]
+                .push(stats.map(|(stats, cap)| {
+                    column![
+                        text(tr!(
+                            "call.stats_encoding",
+                            output = stats.output_fps.round(),
+                            target = stats.target_fps.round()
+                        )),
+                        text(tr!(
+                            "call.stats_encode_time",
+                            ms = format!("{:.1}", stats.last_encode_time.as_secs_f64() * 1000.0)
+                        )),
+                        text(tr!("call.stats_queued", frames = stats.queue_len)),
+                        text(tr!(
+                            "call.stats_bitrate",
+                            short = format!("{:.2}", stats.bitrate_1s / 1_000_000.0),
+                            long = format!("{:.2}", stats.bitrate_10s / 1_000_000.0)
+                        )),
+                        bandwidth_meter(stats.sent_bitrate_1s, cap),
+                    ]
+                    .push((stats.capped_drops > 0).then(|| {
+                        text(tr!("call.stats_capped_drops", samples = stats.capped_drops))
+                    }))
+                    .push((stats.unchanged_skips > 0).then(|| {
+                        text(tr!("call.stats_unchanged_skips", frames = stats.unchanged_skips))
+                    }))
+                    .spacing(2)
+                }))
+                .push(capture_stats.map(|capture_stats| {
+                    column![
+                        text(tr!(
+                            "call.stats_capture",
+                            fps = capture_stats.arrival_fps.round(),
+                            jitter = format!("{:.1}", capture_stats.jitter.as_secs_f64() * 1000.0),
+                            gap =
+                                format!("{:.1}", capture_stats.max_interval.as_secs_f64() * 1000.0)
+                        )),
+                        text(tr!(
+                            "call.stats_readback",
+                            skipped = capture_stats.skipped_readbacks,
+                            buffers = capture_stats.readback_depth
+                        )),
+                    ]
+                    .spacing(2)
+                }))
+                .push(frame_meta.map(|meta| {
+                    text(tr!(
+                        "call.stats_frame",
+                        sequence = meta.sequence,
+                        width = meta.size.x,
+                        height = meta.size.y,
+                        rects = meta.dirty_rects,
+                        percent = (meta.dirty_fraction * 100.0).round()
+                    ))
+                }))
+                .push(decoder_stats.map(|stats| {
+                    column![
+                        text(tr!("call.stats_decoder", accel = stats.accel)),
+                        text(tr!(
+                            "call.stats_decoded",
+                            frames = stats.decoded,
+                            queued = stats.queue_len,
+                            skipped = stats.dropped_packets
+                        )),
+                    ]
+                    .push(since_last_frame.map(|since| {
+                        text(tr!(
+                            "call.stats_last_frame",
+                            seconds = format!("{:.1}", since.as_secs_f64())
+                        ))
+                    }))
+                    .spacing(2)
+                }))
+                .push(depacket_stats.map(|stats| {
+                    // How long a lost packet holds up its frame is what the setting trades for fewer broken frames.
+                    let added = stats.added_latency().map_or_else(
+                        || tr!("call.stats_latency_unknown").to_owned(),
+                        |added| {
+                            tr!(
+                                "call.stats_latency_up_to",
+                                ms = (added.as_secs_f64() * 1000.0).round()
+                            )
+                        },
+                    );
+                    let depacketizer = match stats.auto_tuned {
+                        true => tr!(
+                            "call.stats_depacketizer_tuned",
+                            packets = stats.max_latency,
+                            added = added
+                        ),
+                        false => {
+                            tr!(
+                                "call.stats_depacketizer",
+                                packets = stats.max_latency,
+                                added = added
+                            )
+                        }
+                    };
+                    column![
+                        text(depacketizer),
+                        text(tr!(
+                            "call.stats_reassembled",
+                            frames = stats.samples,
+                            packets = stats.packets,
+                            dropped = stats.dropped_packets
+                        )),
+                    ]
+                    .spacing(2)
+                }))
+                .extend(
+                    impairment_stats
+                        .into_iter()
+                        .zip([tr!("call.stats_sent"), tr!("call.stats_received")])
+                        .filter_map(|(stats, direction)| {
+                            let stats = stats?;
+                            Some(
+                                text(tr!(
+                                    "call.stats_impaired",
+                                    direction = direction,
+                                    config = stats.config,
+                                    passed = stats.passed,
+                                    lost = stats.lost,
+                                    overflowed = stats.overflowed,
+                                    ms = (stats.last_delay.as_secs_f64() * 1000.0).round()
+                                ))
+                                .into(),
+                            )
+                        }),
+                )
+                .spacing(2),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    // Shares the last source again if there is one, or picks one.
+    fn share_button(ctx: &AppContext) -> Element<'static, Message> {
+        let share_button = match &ctx.config.last_capture_source {
+            Some(saved) => button(text(tr!("call.share_again", name = saved.name))).on_press_maybe(
+                Self::share_unavailable_reason(ctx)
+                    .is_none()
+                    .then(|| Message::Call(CallMessage::ShareLastSource(Box::new(saved.clone())))),
+            ),
+            None => button(tr!("call.share_screen")),
+        };
+        match Self::share_unavailable_reason(ctx) {
+            // Disabled until the capture provider is ready.
+            Some(reason) => tooltip(
+                share_button,
+                container(text(reason)).padding(5).style(container::rounded_box),
+                tooltip::Position::Bottom,
+            )
+            .into(),
+            None if ctx.config.last_capture_source.is_some() => row![
+                share_button,
+                button(text(tr!("call.choose_different_source")).size(12))
+                    .style(button::text)
+                    .on_press(Message::Call(CallMessage::OpenSourcePicker)),
+            ]
+            .spacing(5)
+            .align_y(iced::alignment::Vertical::Center)
+            .into(),
+            None => share_button.on_press(Message::Call(CallMessage::OpenSourcePicker)).into(),
+        }
+    }
+
+    // Which end of the call only views, if either does.
+    fn mode_label(&self, ctx: &AppContext) -> Option<String> {
+        match (self.mode, ctx.call.peer_mode) {
+            (CallMode::ViewOnly, _) => Some(tr!("call.view_only").to_owned()),
+            (CallMode::ShareAndView, Some(CallMode::ViewOnly)) => Some(tr!(
+                "call.peer_view_only",
+                peer = self.peer.as_deref().unwrap_or(tr!("call.unknown_peer"))
+            )),
+            _ => None,
+        }
+    }
+
+    fn share_unavailable_reason(ctx: &AppContext) -> Option<&'static str> {
+        match ctx.capture {
+            CaptureProviderState::Pending => Some(tr!("call.capture_starting")),
+            // Created once the share is asked for.
+            CaptureProviderState::NotStarted | CaptureProviderState::Ready(_) => None,
+            CaptureProviderState::Unavailable => Some(tr!("call.no_graphics_device")),
+        }
+    }
+}
+
+// Runs on the provider once its lock is held, off the UI thread.
+fn apply_capture_op(
+    capture: &mut PlatformCaptureProvider,
+    op: CaptureOp<PlatformCaptureItem>,
+) -> Result<CaptureOpOutcome, String> {
+    match op {
+        CaptureOp::Start(item) => {
+            // A running capture takes the new item in place, without stopping the stream or the encoder.
+            let switched = capture.is_capturing();
+            capture.set_capture_item(item.clone()).map_err(|e| e.to_string())?;
+            if !switched {
+                capture.start_capture().map_err(|e| e.to_string())?;
+            }
+            Ok(CaptureOpOutcome::Started { item, info: capture.capture_item_info(), switched })
+        }
+        CaptureOp::Stop => {
+            capture.stop_capture().map_err(|e| e.to_string())?;
+            Ok(CaptureOpOutcome::Stopped)
+        }
+        // Taken like a switch, as the peer needs a keyframe and the cursor tracker the new item.
+        CaptureOp::Recover => {
+            let item = capture.recover_capture().map_err(|e| e.to_string())?;
+            Ok(CaptureOpOutcome::Started {
+                item,
+                info: capture.capture_item_info(),
+                switched: true,
+            })
+        }
+    }
+}
+
+impl Screen for CallScreen {
+    fn subscription(&self, ctx: &AppContext) -> Subscription<Message> {
+        let mut subscriptions = vec![];
+
+        if let Some(capture) = self.capture(ctx)
+            && self.is_capturing()
+        {
+            subscriptions.push(
+                Subscription::<Frame>::run_with(
+                    FrameReceiverSubData {
+                        capture: capture.clone(),
+                        framerate: self.encoding_settings(ctx).framerate,
+                        thread_priority: ctx.config.capture_thread_priority,
+                        hdr: ctx.config.hdr_capture,
+                        rotation: ctx.config.rotation_correction,
+                        stream_name: "frame-receiver",
+                    },
+                    Self::create_frame_receiver_subscription,
+                )
+                .map(|f| Message::Call(CallMessage::FrameCaptured(Arc::new(f)))),
+            );
+        }
+
+        // Only a monitor can be shared with the app's windows on it.
+        if self.sharing_info.as_ref().is_some_and(|info| info.kind == SourceKind::Monitor) {
+            subscriptions.push(window::events().filter_map(|(_, event)| {
+                matches!(event, window::Event::Moved(_))
+                    .then_some(Message::Call(CallMessage::WindowMoved))
+            }));
+        }
+
+        if let Some(capture) = self.capture(ctx) {
+            subscriptions.push(
+                Subscription::run_with(
+                    CaptureStateSubData(capture.clone()),
+                    Self::capture_state_stream,
+                )
+                .map(|state| Message::Call(CallMessage::CaptureStateChanged(state))),
+            );
+        }
+
+        for decoder in self.decoders.values() {
+            subscriptions.push(
+                Subscription::run_with(decoder.clone(), Self::decoded_frame_stream).map(
+                    |(track, frame)| Message::Call(CallMessage::DecodedFrameReady(track, frame)),
+                ),
+            );
+        }
+
+        if self.sharing_indicator.is_some() {
+            subscriptions.push(
+                iced::time::every(SharingIndicator::PULSE_INTERVAL)
+                    .map(|now| Message::Call(CallMessage::PulseSharingIndicator(now))),
+            );
+        }
+
+        Subscription::batch(subscriptions)
     }
 
-    fn is_capturing(&self) -> bool {
-        self.capture.try_read().map(|c| c.is_capturing()).unwrap_or(false)
-    }
-}
+    fn update(&mut self, ctx: &mut AppContext, message: Message) -> Task<Message> {
+        if let Message::WebRTCEvent(_, event) = &message {
+            let phase = self.phase.next(event, Instant::now());
+            if phase != self.phase {
+                tracing::debug!("Call phase changed from {:?} to {:?}", self.phase, phase);
+                self.phase = phase;
+                match phase {
+                    CallPhase::Connected { .. } => self.summary.connected(Instant::now()),
+                    CallPhase::Reconnecting { .. } => self.summary.interrupted(Instant::now()),
+                    _ => (),
+                }
+            }
+        }
+
+        match message {
+            Message::Call(msg) => match msg {
+                // The other tracks are decoded, but not shown yet.
+                CallMessage::DecodedFrameReady(track, _)
+                    if self.shown_decoder().is_none_or(|shown| shown.track() != track) =>
+                {
+                    Task::none()
+                }
+                CallMessage::DecodedFrameReady(_, frame) => {
+                    self.summary.record_resolution(frame.size);
+                    self.remote_frame = Some(frame);
+                    self.remote_idle.frame_arrived(Instant::now());
+                    Task::none()
+                }
+
+                CallMessage::ToggleLocalPreview => {
+                    self.show_local_preview = !self.show_local_preview;
+                    if self.show_local_preview {
+                        // Picks up changes to the rate, and shows the next frame straight away.
+                        self.local_preview.set_fps(ctx.config.preview_fps);
+                    } else {
+                        self.local_preview.clear();
+                    }
+                    Task::none()
+                }
+
+                CallMessage::TogglePreviewSize => {
+                    self.local_preview.set_enlarged(!self.local_preview.is_enlarged());
+                    Task::none()
+                }
+
+                CallMessage::TogglePrivacyEditor => {
+                    let editing = !self.privacy.is_editing();
+                    self.privacy.set_editing(editing);
+                    if editing {
+                        // Drawn over the preview, which is hard to aim at while small.
+                        if !self.show_local_preview {
+                            self.show_local_preview = true;
+                            self.local_preview.set_fps(ctx.config.preview_fps);
+                        }
+                        self.local_preview.set_enlarged(true);
+                    } else {
+                        self.keep_privacy_regions(ctx);
+                    }
+                    Task::none()
+                }
+
+                CallMessage::PrivacyInput(input) => {
+                    self.privacy.input(input);
+                    Task::none()
+                }
+
+                CallMessage::ClearPrivacyRegions => {
+                    self.privacy.clear();
+                    Task::none()
+                }
+
+                CallMessage::RememberPrivacyRegions(remember) => {
+                    self.remember_privacy = remember;
+                    Task::none()
+                }
+
+                CallMessage::ToggleStats => {
+                    self.show_stats = !self.show_stats;
+                    Task::none()
+                }
+
+                CallMessage::SetFramerate(framerate) => {
+                    tracing::info!("Switching to {} fps", framerate);
+                    ctx.config.framerate = framerate;
+                    if let Err(e) = ctx.store.save_config(&ctx.config) {
+                        tracing::error!("Failed to save framerate: {}", e);
+                    }
+                    Task::none()
+                }
+
+                // Applied to the encoder and the stream along with the next frame.
+                CallMessage::QualityPresetSelected(preset) => {
+                    tracing::info!("Switching to the {} quality preset", preset);
+                    ctx.config.apply_preset(preset);
+                    if let Err(e) = ctx.store.save_config(&ctx.config) {
+                        tracing::error!("Failed to save quality preset: {}", e);
+                    }
+                    Task::none()
+                }
+
+                CallMessage::FingerprintsReady(fingerprints) => {
+                    self.fingerprint_changed = match (&fingerprints, &self.peer) {
+                        (Some(fingerprints), Some(peer)) => {
+                            !ctx.config.is_verified(&fingerprints.remote)
+                                && ctx.config.fingerprint_changed(peer, &fingerprints.remote)
+                        }
+                        _ => false,
+                    };
+                    if self.fingerprint_changed {
+                        ctx.notifier.in_app.error(tr!(
+                            "call.fingerprint_changed_notice",
+                            peer = self.peer.as_deref().unwrap_or(tr!("call.unknown_peer"))
+                        ));
+                    }
+                    self.fingerprints = fingerprints.map(|fingerprints| *fingerprints);
+                    Task::none()
+                }
+
+                CallMessage::SetPeerVerified(verified) => {
+                    let (Some(fingerprints), Some(peer)) = (&self.fingerprints, &self.peer) else {
+                        return Task::none();
+                    };
+                    ctx.config.set_verified(peer, &fingerprints.remote, verified);
+                    self.fingerprint_changed &= !verified;
+                    if let Err(e) = ctx.store.save_config(&ctx.config) {
+                        tracing::error!("Failed to save verified peer: {}", e);
+                    }
+                    Task::none()
+                }
+
+                CallMessage::ViewerQualitySelected(quality) => {
+                    self.viewer_quality = quality;
+                    Self::send_control(ctx, ControlMessage::QualityRequest(quality.request()))
+                }
+
+                CallMessage::PopOut => match ctx.windows.popout_id {
+                    Some(id) => window::gain_focus(id),
+                    None => {
+                        let (id, open_task) = window::open(window::Settings {
+                            size: iced::Size::new(1280.0, 720.0),
+                            ..Default::default()
+                        });
+                        ctx.windows.popout_id = Some(id);
+                        open_task
+                            .then(window::raw_id::<Message>)
+                            .map(|raw_id| Message::Call(CallMessage::PopoutOpened(raw_id)))
+                    }
+                },
 
-impl Screen for CallScreen {
-    fn subscription(&self, ctx: &AppContext) -> Subscription<Message> {
-        let mut subscriptions = vec![];
+                CallMessage::PopIn => match ctx.windows.popout_id.take() {
+                    Some(id) => window::close(id),
+                    None => Task::none(),
+                },
 
-        if self.is_capturing() {
-            subscriptions.push(
-                Subscription::<Frame>::run_with(
-                    FrameReceiverSubData {
-                        capture: self.capture.clone(),
-                        framerate: ctx.config.framerate,
-                        stream_name: "frame-receiver",
-                    },
-                    Self::create_frame_receiver_subscription,
-                )
-                .map(|f| Message::Call(CallMessage::FrameCaptured(Arc::new(f)))),
-            );
-        }
+                CallMessage::ToggleFullscreen => {
+                    // Fullscreen applies to whichever window is showing the video.
+                    let Some(id) = ctx.windows.popout_id.or(ctx.windows.main_id) else {
+                        return Task::none();
+                    };
 
-        Subscription::batch(subscriptions)
-    }
+                    window::mode(id).then(move |mode| {
+                        let new_mode = match mode {
+                            window::Mode::Fullscreen => window::Mode::Windowed,
+                            _ => window::Mode::Fullscreen,
+                        };
+                        window::set_mode(id, new_mode)
+                    })
+                }
 
-    fn update(&mut self, ctx: &mut AppContext, message: Message) -> Task<Message> {
-        match message {
-            Message::PacketReceived(packet) => {
-                if self.decoder.is_none() {
-                    match FFmpegDecoder::new(ctx.config.transcoding_type) {
-                        Ok(decoder) => self.decoder = Some(Arc::new(Mutex::new(decoder))),
-                        Err(e) => {
-                            tracing::error!("Failed to create H264 Decoder: {}", e);
-                            return Task::none();
-                        }
+                CallMessage::RecordingChanged(recording) => {
+                    if self.recording == recording {
+                        return Task::none();
+                    }
+                    tracing::info!(
+                        "{} recording the call",
+                        if recording { "Started" } else { "Stopped" }
+                    );
+                    self.recording = recording;
+                    if recording {
+                        self.summary.recorded();
                     }
+                    Self::send_control(
+                        ctx,
+                        ControlMessage::RecordingState(RecordingState { active: recording }),
+                    )
                 }
 
-                if let Some(decoder) = &self.decoder {
-                    let decoder = decoder.clone();
-                    Task::future(async move {
-                        let mut lock = decoder.lock().await;
-                        match lock.decode(&packet) {
-                            Ok(Some(frame)) => Message::Call(CallMessage::DecodedFrameReady(frame)),
-                            Ok(None) => Message::NoOp,
-                            Err(e) => {
-                                tracing::error!("Failed to decode frame: {}", e);
-                                Message::NoOp
-                            }
-                        }
-                    })
-                } else {
+                CallMessage::RecordingSaved(path, Ok(bytes)) => {
+                    tracing::info!("Saved {} bytes of recording to {}", bytes, path.display());
+                    ctx.notifier
+                        .in_app
+                        .info(tr!("call.recording_saved", path = path.display().to_string()));
                     Task::none()
                 }
-            }
 
-            Message::Call(msg) => match msg {
-                CallMessage::DecodedFrameReady(frame) => {
-                    self.remote_frame = Some(frame);
+                CallMessage::RecordingSaved(path, Err(e)) => {
+                    tracing::error!("Failed to record the call to {}: {}", path.display(), e);
+                    ctx.notifier.in_app.error(tr!("call.recording_failed", error = e));
                     Task::none()
                 }
 
-                CallMessage::ToggleLocalPreview => {
-                    self.show_local_preview = !self.show_local_preview;
+                CallMessage::SnapshotSaved(Ok(snapshot)) => {
+                    let evicted =
+                        self.filmstrip.as_mut().map(|f| f.saved(*snapshot)).unwrap_or_default();
+                    Self::delete_snapshots(evicted)
+                }
+
+                CallMessage::SnapshotSaved(Err(e)) => {
+                    tracing::warn!("Failed to save snapshot: {}", e);
+                    if let Some(filmstrip) = &mut self.filmstrip {
+                        filmstrip.failed();
+                    }
+                    Task::none()
+                }
+
+                CallMessage::ShowSnapshot(path) => {
+                    if let Some(filmstrip) = &mut self.filmstrip {
+                        filmstrip.select(path);
+                    }
                     Task::none()
                 }
 
                 CallMessage::EndCall => {
+                    self.finish_summary(ctx);
+                    let close_popout_task = match ctx.windows.popout_id.take() {
+                        Some(id) => window::close(id),
+                        None => Task::none(),
+                    };
+
                     let stop_capture_task = if self.is_capturing() {
                         Task::done(Message::Call(CallMessage::StopCapture))
                     } else {
                         Task::none()
                     };
 
-                    let disconnect_task = if let Some(webrtc) = &ctx.webrtc {
+                    let disconnect_task = if let Some(webrtc) = &ctx.call.webrtc {
                         let webrtc_clone = webrtc.clone();
                         Task::future(async move {
                             if let Err(e) = webrtc_clone.disconnect().await {
@@ -178,13 +2196,105 @@ impl Screen for CallScreen {
                     };
 
                     Task::batch(vec![
+                        close_popout_task,
                         stop_capture_task,
+                        self.stop_recording(),
                         disconnect_task,
                         Task::done(Message::Navigate(Route::Home)),
                     ])
                 }
-                CallMessage::StartCapture => {
-                    let window_handle = match ctx.main_window_handle {
+                CallMessage::OpenSourcePicker => {
+                    if self.capture(ctx).is_none() {
+                        return self.capture_unavailable(ctx, Some(CallMessage::OpenSourcePicker));
+                    }
+
+                    let (picker, task) = SourcePicker::open();
+                    self.source_picker = Some(Box::new(picker));
+                    task
+                }
+
+                // Dropping the picker cancels any thumbnails still loading.
+                CallMessage::CloseSourcePicker => {
+                    self.source_picker = None;
+                    Task::none()
+                }
+
+                CallMessage::SourcePicker(msg) => match &mut self.source_picker {
+                    Some(picker) => picker.update(msg),
+                    // Leftovers from a picker that was already closed.
+                    None => Task::none(),
+                },
+
+                CallMessage::SourceSelected(source) => {
+                    self.source_picker = None;
+
+                    let Some(monitor) = source.monitor_bounds() else {
+                        return Task::done(Message::Call(CallMessage::ShareSource(source)));
+                    };
+                    let windows = Self::windows_on(ctx, monitor);
+                    if windows.is_empty() {
+                        return Task::done(Message::Call(CallMessage::ShareSource(source)));
+                    }
+                    tracing::info!("Warning about sharing the monitor the call is shown on");
+                    let monitors = platform_monitor_layout()
+                        .inspect_err(|e| tracing::warn!("Failed to list the monitors: {}", e))
+                        .unwrap_or_default();
+                    let can_move = windows.iter().all(|(_, window)| {
+                        mirror_warning::move_off(&monitors, monitor, *window).is_some()
+                    });
+                    self.mirror_prompt = Some(MirrorPrompt { source, can_move });
+                    Task::none()
+                }
+
+                CallMessage::MirrorAnswered(answer) => {
+                    let Some(MirrorPrompt { source, .. }) = self.mirror_prompt.take() else {
+                        return Task::none();
+                    };
+                    tracing::info!("Answered the warning about sharing the call with {:?}", answer);
+                    let share = Task::done(Message::Call(CallMessage::ShareSource(source.clone())));
+                    match answer {
+                        MirrorAnswer::MoveWindows => {
+                            Task::batch([Self::move_windows_off(ctx, &source), share])
+                        }
+                        MirrorAnswer::ShareAnyway => share,
+                        MirrorAnswer::Cancel => Task::none(),
+                    }
+                }
+
+                CallMessage::WindowMoved => {
+                    self.check_mirrored(ctx);
+                    Task::none()
+                }
+
+                CallMessage::PopoutOpened(raw_id) => {
+                    ctx.windows.popout_handle = Some(raw_id);
+                    Task::none()
+                }
+
+                CallMessage::ShareSource(source) => match source.create_capture_item() {
+                    Ok(item) => {
+                        self.remember_source(ctx, source.to_saved());
+                        self.use_capture_item(ctx, item)
+                    }
+                    Err(err) => {
+                        tracing::error!("Failed to create capture item for {:?}: {}", source, err);
+                        ctx.notifier.in_app.error(tr!(
+                            "call.source_failed",
+                            name = source.name,
+                            error = err
+                        ));
+                        Task::none()
+                    }
+                },
+
+                CallMessage::OpenSystemPicker => {
+                    self.source_picker = None;
+
+                    if self.capture(ctx).is_none() {
+                        return self.capture_unavailable(ctx, Some(CallMessage::OpenSystemPicker));
+                    }
+
+                    let window_handle = match ctx.windows.main_handle {
                         Some(handle) => handle,
                         None => {
                             tracing::error!("No active window handle");
@@ -196,7 +2306,7 @@ impl Screen for CallScreen {
                         Ok(future) => Task::future(async move {
                             match future.await {
                                 Ok(item) => Message::Call(
-                                    CallMessage::PlatformUserPickedCaptureItem(Ok(item)),
+                                    CallMessage::PlatformUserPickedCaptureItem(Ok(item.into())),
                                 ),
                                 Err(e) => Message::Call(
                                     CallMessage::PlatformUserPickedCaptureItem(Err(e.to_string())),
@@ -218,178 +2328,588 @@ impl Screen for CallScreen {
                             return Task::none();
                         }
                     };
-                    Task::done(Message::Call(CallMessage::TryStartCapture(capture_item)))
+                    self.remember_source(ctx, saved_platform_capture_item(&capture_item));
+                    self.use_capture_item(ctx, capture_item)
                 }
 
-                CallMessage::TryStartCapture(capture_item) => match self.capture.try_write() {
-                    Ok(mut capture) => {
-                        if let Err(err) = capture.set_capture_item(capture_item.clone()) {
-                            tracing::error!("Failed to set capture item: {}", err);
-                            return Task::none();
-                        }
+                CallMessage::ShareLastSource(saved) => {
+                    if self.capture(ctx).is_none() {
+                        return self
+                            .capture_unavailable(ctx, Some(CallMessage::ShareLastSource(saved)));
+                    }
 
-                        if let Err(err) = capture.start_capture() {
-                            tracing::error!("Failed to start capture: {}", err);
-                            return Task::none();
-                        }
+                    Task::future(async move {
+                        let result =
+                            tokio::task::spawn_blocking(move || find_platform_source(&saved.id))
+                                .await
+                                .expect("Capture source enumeration panicked");
+                        let source = result
+                            .inspect_err(|e| tracing::warn!("Failed to find last source: {}", e))
+                            .ok()
+                            .flatten()
+                            .map(Box::new);
+                        Message::Call(CallMessage::LastSourceResolved(source))
+                    })
+                }
+
+                CallMessage::LastSourceResolved(Some(source)) => {
+                    Task::done(Message::Call(CallMessage::SourceSelected(source)))
+                }
 
-                        Task::done(Message::Call(CallMessage::CaptureStarted))
+                CallMessage::LastSourceResolved(None) => {
+                    tracing::info!("Last capture source is gone, opening the picker instead");
+                    Task::done(Message::Call(CallMessage::OpenSourcePicker))
+                }
+
+                CallMessage::CaptureOpFinished(id, outcome) => {
+                    let Some(kind) = self.capture_ops.finish(id) else {
+                        tracing::debug!("Ignoring the result of a superseded capture operation");
+                        return Task::none();
+                    };
+                    self.capture_op_task = None;
+
+                    let task = self.capture_op_finished(ctx, kind, outcome);
+                    match self.capture_ops.take_queued() {
+                        Some((id, op)) => match self.capture(ctx).cloned() {
+                            Some(capture) => {
+                                Task::batch([task, self.run_capture_op(capture, id, op)])
+                            }
+                            None => task,
+                        },
+                        None => task,
                     }
-                    Err(_) => {
-                        let capture_arc = self.capture.clone();
-                        Task::future(async move {
-                            let _lock = capture_arc.write().await;
-                        })
-                        .map(move |_| {
-                            Message::Call(CallMessage::TryStartCapture(capture_item.clone()))
-                        })
+                }
+
+                CallMessage::CaptureStateChanged(state) => {
+                    tracing::debug!("Capture state changed to {:?}", state);
+                    let task = match &state {
+                        // Clean up after the capture, as it won't produce anything anymore.
+                        CaptureState::Error(e) => {
+                            ctx.notifier.in_app.error(tr!("call.capture_stopped", error = e));
+                            Task::done(Message::Call(CallMessage::StopCapture))
+                        }
+                        CaptureState::Interrupted(reason) => {
+                            tracing::warn!("Capture interrupted, recovering: {}", reason);
+                            self.request_capture_op(ctx, CaptureOp::Recover)
+                        }
+                        _ => Task::none(),
+                    };
+                    let info = match state {
+                        CaptureState::Idle => None,
+                        // Keep what we have if the provider is busy, it was set when the item was.
+                        _ => ctx
+                            .capture
+                            .provider()
+                            .and_then(|capture| capture.try_read().ok())
+                            .map_or(self.sharing_info.clone(), |capture| {
+                                capture.capture_item_info()
+                            }),
+                    };
+                    self.set_sharing_info(info);
+                    self.capture_state = state;
+                    task
+                }
+
+                CallMessage::StopCapture => self.request_capture_op(ctx, CaptureOp::Stop),
+
+                CallMessage::CaptureStopped => {
+                    self.local_preview.clear();
+                    self.mirrored = false;
+                    if self.privacy.is_editing() {
+                        self.privacy.set_editing(false);
+                        self.keep_privacy_regions(ctx);
                     }
+                    // What was encoded since the last tick still counts towards the summary.
+                    let metrics = ctx.metrics.snapshot();
+                    self.poll_encoder_stats(ctx, &metrics);
+                    self.encoder_stats = None;
+                    ctx.call.send_bitrate = None;
+                    self.capture_stats = None;
+                    self.capture_stats_window.reset();
+                    self.frame_meta = None;
+                    #[cfg(feature = "gpu-frames")]
+                    self.update_gpu_output(ctx);
+                    self.framerate_check.stop();
+                    self.dismiss_framerate_warning(ctx);
+
+                    // Flush what's left of the stream, so the peer sees the last frame.
+                    let encoder_shutdown_task = match self.encoder.take() {
+                        Some(encoder) => Task::future(async move {
+                            if let Err(e) = encoder.shutdown().await {
+                                tracing::debug!("Failed to shut down encoder: {}", e);
+                            }
+                            Message::NoOp
+                        }),
+                        None => Task::none(),
+                    };
+
+                    self.cursor_tracker = None;
+                    // Removes the hook right away, rather than once the encoder is done.
+                    self.click_highlighter = None;
+                    self.highlight_clicks = false;
+
+                    // Nothing left to control, or to ask for.
+                    let revoke_task = if self.consent_prompt.take().is_some() {
+                        Self::send_control(ctx, ControlMessage::RemoteControl(false))
+                    } else {
+                        self.set_remote_control_allowed(ctx, false)
+                    };
+
+                    // Hide the cursor on the peer's side, as there is nothing for it to point at anymore.
+                    let hide_cursor_task = if self.cursor_sender.take().is_some() {
+                        Self::send_control(ctx, ControlMessage::Cursor(None))
+                    } else {
+                        Task::none()
+                    };
+
+                    let hide_indicator_task = self.hide_sharing_indicator(ctx);
+
+                    Task::batch([
+                        encoder_shutdown_task,
+                        revoke_task,
+                        hide_cursor_task,
+                        hide_indicator_task,
+                    ])
+                }
+
+                CallMessage::ToggleRemoteControl => {
+                    self.set_remote_control_allowed(ctx, !self.remote_control_allowed)
+                }
+
+                CallMessage::ToggleClickHighlights => {
+                    self.highlight_clicks = !self.highlight_clicks;
+                    self.update_click_highlighter(ctx);
+                    Task::none()
+                }
+
+                CallMessage::ToggleRecording => match self.recorder {
+                    Some(_) => self.stop_recording(),
+                    None => self.start_recording(ctx),
                 },
 
-                CallMessage::CaptureStarted => Task::none(),
+                CallMessage::RequestRemoteControl => {
+                    if self.remote_control_granted || self.remote_control_requested {
+                        return Task::none();
+                    }
+                    self.remote_control_requested = true;
+                    Self::send_control(ctx, ControlMessage::RemoteControlRequest)
+                }
 
-                CallMessage::StopCapture => Task::done(Message::Call(CallMessage::TryStopCapture)),
+                CallMessage::ConsentAnswered { accepted, always } => {
+                    let Some(kind) = self.consent_prompt.take() else {
+                        return Task::none();
+                    };
+                    let peer = self.peer.clone().unwrap_or_default();
+                    tracing::info!(%peer, accepted, always, "Answered the request for {}", kind);
+                    ctx.consents.decide(&peer, kind, accepted, always, SystemTime::now());
+                    Self::save_consents(ctx);
 
-                CallMessage::TryStopCapture => match self.capture.try_write() {
-                    Ok(mut capture) => {
-                        if let Err(err) = capture.stop_capture() {
-                            tracing::error!("Failed to stop capture: {}", err);
+                    match kind {
+                        ConsentKind::RemoteControl if accepted => {
+                            self.set_remote_control_allowed(ctx, true)
+                        }
+                        ConsentKind::RemoteControl => {
+                            Self::send_control(ctx, ControlMessage::RemoteControl(false))
                         }
-                        Task::done(Message::Call(CallMessage::CaptureStopped))
                     }
-                    Err(_) => {
-                        tracing::debug!(
-                            "Failed to acquire capture lock. Trying again with waiter..."
+                }
+
+                CallMessage::SharingIndicatorOpened(raw_id) => {
+                    // Otherwise the peer would see it on top of the shared monitor.
+                    if let Err(e) = exclude_platform_window_from_capture(raw_id) {
+                        tracing::warn!(
+                            "Failed to keep the sharing indicator out of the capture: {}",
+                            e
                         );
-                        let capture_arc = self.capture.clone();
-                        Task::future(async move {
-                            let _lock = capture_arc.write().await;
-                            Message::Call(CallMessage::TryStopCapture)
-                        })
                     }
-                },
+                    Task::none()
+                }
 
-                CallMessage::CaptureStopped => {
-                    self.frame_sender = None;
-                    self.local_frame = None;
+                CallMessage::PulseSharingIndicator(now) => {
+                    if let Some(indicator) = &mut self.sharing_indicator {
+                        indicator.tick(now);
+                    }
                     Task::none()
                 }
 
+                CallMessage::SendInput(input) => {
+                    if !self.remote_control_granted {
+                        return Task::none();
+                    }
+                    Self::send_control(ctx, ControlMessage::Input(input))
+                }
+
                 CallMessage::FrameCaptured(frame) => {
-                    self.local_frame = Some(frame.clone());
+                    tracing::trace!(frame = frame.sequence, "Frame reached the call screen");
+                    if self.show_local_preview {
+                        self.local_preview.offer(&frame, Instant::now());
+                    }
+
+                    if let Some(switch_started) = self.switch_started.take() {
+                        tracing::info!(
+                            "First frame of the new source arrived {:?} after switching",
+                            switch_started.elapsed()
+                        );
+                    }
+
+                    // The settings may have been changed during the call.
+                    let config = self.encoder_config(ctx);
+                    self.summary.record_resolution(encode_size::capped_size(
+                        frame.size,
+                        config.max_dimension,
+                    ));
+                    if self
+                        .encoder
+                        .as_ref()
+                        .is_some_and(|encoder| !encoder.can_reconfigure(&config))
+                    {
+                        tracing::info!("Encoder settings changed, restarting encoder");
+                        self.encoder = None;
+                    }
 
-                    if self.frame_sender.is_none() {
-                        let Some(webrtc) = &ctx.webrtc else {
+                    if self.encoder.is_none() {
+                        let Some(webrtc) = &ctx.call.webrtc else {
                             tracing::error!("WebRTC is not initialized yet");
                             return Task::none();
                         };
 
-                        let (tx, mut rx) = mpsc::channel::<Arc<Frame>>(10);
-                        self.frame_sender = Some(tx.clone());
-
-                        let webrtc = webrtc.clone();
-                        let target_fps_hz = ctx.config.framerate.to_hz();
-                        let bitrate = ctx.config.bitrate;
-                        let transcoding_type = ctx.config.transcoding_type;
-                        let input_format = ctx.config.pixel_format;
-
-                        tracing::debug!(
-                            "Starting encoder thread. target_fps_hz: {}, bitrate: {}",
-                            target_fps_hz,
-                            bitrate
-                        );
-                        tokio::spawn(async move {
-                            let mut encoder =
-                                match FFmpegEncoder::new(bitrate, target_fps_hz, input_format) {
-                                    Ok(e) => e,
-                                    Err(e) => {
-                                        tracing::error!("Failed to create encoder: {}", e);
-                                        return;
-                                    }
-                                };
-
-                            while let Some(frame) = rx.recv().await {
-                                match encoder.encode(
-                                    &frame.data,
-                                    transcoding_type,
-                                    frame.size.x,
-                                    frame.size.y,
-                                ) {
-                                    Ok(nal_units) => {
-                                        let frame_duration = match frame.duration {
-                                            Some(duration) => duration,
-                                            None => {
-                                                tracing::error!("Frame duration is None!");
-                                                continue;
-                                            }
-                                        };
-                                        for nal in nal_units {
-                                            if let Err(e) =
-                                                webrtc.write_sample(nal, frame_duration).await
-                                            {
-                                                tracing::error!("WebRTC write failed: {}", e);
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::error!("Encoding failed: {}", e);
-                                    }
-                                }
+                        let _call = webrtc.call_span().entered();
+                        // The peer is still decoding what the previous encoder sent.
+                        if std::mem::replace(&mut self.sent_video, true) {
+                            let replacement = webrtc.replace_video_source(config.transcoding_type);
+                            tracing::info!("Restarted the video: {:?}", replacement);
+                        }
+                        match EncoderWorker::spawn(config, webrtc.clone(), &ctx.metrics) {
+                            Ok(encoder) => {
+                                self.encoder = Some(encoder);
+                                self.framerate_check.restart(Instant::now());
                             }
-                            tracing::info!("Encoder thread finished.");
-                        });
+                            Err(e) => {
+                                tracing::error!("Failed to create encoder: {}", e);
+                                return Task::none();
+                            }
+                        }
                     }
 
-                    if let Some(tx) = &self.frame_sender {
-                        match tx.try_send(frame) {
+                    let mut encoder_panic = None;
+                    if let Some(encoder) = &mut self.encoder {
+                        if let Err(e) = encoder.reconfigure(config) {
+                            tracing::warn!("Failed to reconfigure encoder: {}", e);
+                        }
+                        let style = ctx.config.redaction_style;
+                        if let Err(e) = encoder.set_redaction(self.privacy.regions(), style) {
+                            tracing::warn!("Failed to set the privacy regions: {}", e);
+                        }
+                        let highlights = self
+                            .click_highlighter
+                            .as_ref()
+                            .map(|highlighter| highlighter.highlights.clone());
+                        if let Err(e) = encoder.set_click_highlights(highlights) {
+                            tracing::warn!("Failed to set the click highlights: {}", e);
+                        }
+
+                        let frame_sequence = frame.sequence;
+                        match encoder.send_frame(frame) {
                             Ok(_) => {}
-                            Err(mpsc::error::TrySendError::Full(_)) => {
-                                tracing::debug!("Encoder queue full, dropping frame");
+                            Err(EncoderWorkerError::QueueFull) => {
+                                tracing::debug!(
+                                    frame = frame_sequence,
+                                    "Encoder queue full, dropping frame"
+                                );
+                            }
+                            Err(EncoderWorkerError::Panicked(message)) => {
+                                encoder_panic = Some(message);
                             }
                             Err(e) => {
                                 tracing::warn!("Failed to send frame to encoder: {}", e);
                             }
                         }
                     }
+                    #[cfg(feature = "gpu-frames")]
+                    self.update_gpu_output(ctx);
+
+                    // The call carries on, but there is nothing to send the frames with anymore.
+                    if let Some(message) = encoder_panic {
+                        self.encoder = None;
+                        ctx.notifier.in_app.error(tr!("call.encoder_crashed", error = message));
+                        return Task::done(Message::Call(CallMessage::StopCapture));
+                    }
+
+                    Task::none()
+                }
 
+                CallMessage::ConnectionStatsReady(stats) => {
+                    self.update_quality(ctx, *stats);
                     Task::none()
                 }
             },
 
-            // End the call if the peer disconnects
-            Message::WebRTCEvent(WebRTCEvent::Disconnected) => {
-                Task::done(Message::Call(CallMessage::EndCall))
+            // The user closed the indicator, e.g. with Alt+F4.
+            Message::WindowClosed(_) if ctx.windows.sharing_indicator.is_none() => {
+                self.sharing_indicator = None;
+                Task::none()
+            }
+
+            // Failing, the app says so, and there is nothing to share with.
+            Message::CaptureProviderReady(_) => match self.pending_share.take() {
+                Some(share) if self.capture(ctx).is_some() => Task::done(Message::Call(share)),
+                _ => Task::none(),
+            },
+
+            Message::Tick(now) => {
+                // Also redraws the view, which shows the idle badge once the threshold passes.
+                self.remote_idle.set_idle_after(ctx.config.remote_idle_after);
+                let metrics = ctx.metrics.snapshot();
+                if self.is_capturing() {
+                    self.poll_encoder_stats(ctx, &metrics);
+                }
+                self.check_framerate(ctx, now);
+                self.update_capture_stats(ctx, &metrics);
+                self.check_decoders(ctx);
+                self.check_mirrored(ctx);
+                // The writer only stops by itself when it failed, which stopping reports.
+                let recording = match &self.recorder {
+                    Some(recorder) if recorder.is_finished() => self.stop_recording(),
+                    _ => Task::none(),
+                };
+                let snapshot = self.take_snapshot(ctx, now);
+                Task::batch([recording, snapshot, self.fetch_connection_stats(ctx, now)])
+            }
+
+            // Replace the decoder with one for the codec the peers actually negotiated.
+            // Both session descriptions are in place by now.
+            Message::WebRTCEvent(_, WebRTCEvent::Connected) => Self::fetch_fingerprints(ctx),
+
+            // A new track comes with another codec, which the raw stream of the recording can't switch to.
+            Message::WebRTCEvent(_, WebRTCEvent::TrackStarted { track, mime_type }) => {
+                // Stop the old worker first, so the new one gets the packets.
+                self.decoders.remove(&track);
+                if let Some(decoder) = Self::spawn_decoder(ctx, track, &mime_type) {
+                    self.decoders.insert(track, decoder);
+                }
+                self.stop_recording()
+            }
+
+            // If it was shown, the track that started before it is shown instead, if any.
+            Message::WebRTCEvent(_, WebRTCEvent::TrackEnded { track }) => {
+                let shown = self.shown_decoder().map(DecoderHandle::track);
+                if self.decoders.remove(&track).is_some() && shown == Some(track) {
+                    self.remote_frame = None;
+                    self.remote_idle.reset();
+                    return self.stop_recording();
+                }
+                Task::none()
+            }
+
+            Message::WebRTCEvent(_, WebRTCEvent::Control(control)) => {
+                match control {
+                    ControlMessage::Cursor(position) => self.remote_cursor = position,
+                    ControlMessage::Input(input) => self.inject_remote_input(input),
+                    ControlMessage::RemoteControl(granted) => {
+                        self.remote_control_granted = granted;
+                        if granted {
+                            ctx.notifier.in_app.info(tr!("call.remote_control_accepted"));
+                        } else if self.remote_control_requested {
+                            ctx.notifier.in_app.info(tr!("call.remote_control_declined"));
+                        }
+                        self.remote_control_requested = false;
+                    }
+                    ControlMessage::RemoteControlRequest => {
+                        return self.remote_control_requested(ctx);
+                    }
+                    ControlMessage::KeyframeRequest => {
+                        if let Some(encoder) = &self.encoder
+                            && let Err(e) = encoder.request_keyframe()
+                        {
+                            tracing::warn!("Failed to request keyframe: {}", e);
+                        }
+                    }
+                    // Applied to the encoder and the stream along with the next frame.
+                    ControlMessage::QualityRequest(request) => {
+                        tracing::info!("Peer requested quality {:?}", request);
+                        match &request {
+                            Some(request) if reduces(ctx.config.encoding_settings(), request) => {
+                                ctx.notifier.in_app.notify_once(
+                                    QUALITY_REQUEST_NOTIFICATION,
+                                    NotificationKind::Info,
+                                    tr!("call.quality_reduced"),
+                                );
+                            }
+                            None if self.quality_request.is_some() => {
+                                ctx.notifier.in_app.notify_once(
+                                    QUALITY_REQUEST_NOTIFICATION,
+                                    NotificationKind::Info,
+                                    tr!("call.quality_restored"),
+                                );
+                            }
+                            _ => {}
+                        }
+                        self.quality_request = request;
+                    }
+                    ControlMessage::RecordingState(RecordingState { active }) => {
+                        if active != self.remote_recording {
+                            tracing::info!(
+                                "Peer {} recording the call",
+                                if active { "started" } else { "stopped" }
+                            );
+                            self.remote_recording = active;
+                            if active {
+                                self.summary.recorded();
+                            }
+                            ctx.notifier.in_app.notify_once(
+                                RECORDING_NOTIFICATION,
+                                NotificationKind::Info,
+                                if active {
+                                    tr!("call.remote_recording_started")
+                                } else {
+                                    tr!("call.remote_recording_stopped")
+                                },
+                            );
+                        }
+                    }
+                }
+                Task::none()
+            }
+
+            // The call is over, which is left on screen until the user goes back.
+            Message::WebRTCEvent(_, WebRTCEvent::Disconnected) => {
+                self.finish_summary(ctx);
+                self.quality_request = None;
+                self.remote_recording = false;
+                self.quality.reset();
+                self.receive_counts = None;
+                let close_popout_task = match ctx.windows.popout_id.take() {
+                    Some(id) => window::close(id),
+                    None => Task::none(),
+                };
+                let stop_capture_task = if self.is_capturing() {
+                    Task::done(Message::Call(CallMessage::StopCapture))
+                } else {
+                    Task::none()
+                };
+                Task::batch([close_popout_task, stop_capture_task, self.stop_recording()])
             }
 
             _ => Task::none(),
         }
     }
 
-    fn view(&self, _ctx: &AppContext) -> Element<'_, Message> {
+    fn view(&self, ctx: &AppContext) -> Element<'_, Message> {
         let mut controls_row: iced::widget::Row<'_, Message, iced::Theme, iced::Renderer> =
             iced::widget::Row::new()
-                .push(button("Settings").on_press(Message::NavigateWithBack(Route::Settings)))
+                .push(self.quality_view())
+                .push(
+                    button(tr!("common.settings"))
+                        .on_press(Message::NavigateWithBack(Route::Settings)),
+                )
+                // Shows "Custom" once the settings no longer match a preset. Nothing is sent when only viewing.
+                .push(self.mode.can_share().then(|| {
+                    pick_list(QualityPreset::ALL, ctx.config.quality_preset(), |preset| {
+                        Message::Call(CallMessage::QualityPresetSelected(preset))
+                    })
+                    .placeholder(tr!("call.custom_quality"))
+                }))
                 .spacing(10);
 
         controls_row = if self.is_capturing() {
             controls_row.extend([
-                button("Change Screen").on_press(Message::Call(CallMessage::StartCapture)).into(),
-                button(if self.show_local_preview { "Hide Preview" } else { "Show Preview" })
-                    .on_press(Message::Call(CallMessage::ToggleLocalPreview))
+                button(tr!("call.change_screen"))
+                    .on_press(Message::Call(CallMessage::OpenSourcePicker))
                     .into(),
-                button("Stop Sharing")
+                button(if self.show_local_preview {
+                    tr!("call.hide_preview")
+                } else {
+                    tr!("call.show_preview")
+                })
+                .on_press(Message::Call(CallMessage::ToggleLocalPreview))
+                .into(),
+                button(if self.privacy.is_editing() {
+                    tr!("call.done_privacy")
+                } else {
+                    tr!("call.edit_privacy")
+                })
+                .on_press(Message::Call(CallMessage::TogglePrivacyEditor))
+                .into(),
+                button(if self.show_stats {
+                    tr!("call.hide_stats")
+                } else {
+                    tr!("call.show_stats")
+                })
+                .on_press(Message::Call(CallMessage::ToggleStats))
+                .into(),
+                button(if self.remote_control_allowed {
+                    tr!("call.revoke_remote_control")
+                } else {
+                    tr!("call.allow_remote_control")
+                })
+                .on_press(Message::Call(CallMessage::ToggleRemoteControl))
+                .into(),
+                button(if self.highlight_clicks {
+                    tr!("call.stop_highlighting_clicks")
+                } else {
+                    tr!("call.highlight_clicks")
+                })
+                .on_press(Message::Call(CallMessage::ToggleClickHighlights))
+                .into(),
+                button(tr!("call.stop_sharing"))
                     .style(iced::widget::button::danger)
                     .on_press(Message::Call(CallMessage::StopCapture))
                     .into(),
             ])
         } else {
-            controls_row.extend([button("Share Screen")
-                .on_press(Message::Call(CallMessage::StartCapture))
-                .into()])
+            let share_button = self.mode.can_share().then(|| Self::share_button(ctx));
+            // The decoder's stats, while only watching.
+            controls_row
+                .push(share_button)
+                .push((!self.decoders.is_empty()).then(|| {
+                    button(if self.show_stats {
+                        tr!("call.hide_stats")
+                    } else {
+                        tr!("call.show_stats")
+                    })
+                    .on_press(Message::Call(CallMessage::ToggleStats))
+                }))
+                .push((!self.decoders.is_empty() && !self.remote_control_granted).then(|| {
+                    button(tr!("call.request_remote_control"))
+                        .style(button::secondary)
+                        .on_press_maybe(
+                            (!self.remote_control_requested)
+                                .then_some(Message::Call(CallMessage::RequestRemoteControl)),
+                        )
+                }))
+        };
+
+        // Records what the peer shares, whether or not we share too.
+        controls_row = controls_row.push((!self.decoders.is_empty()).then(|| {
+            button(if self.recorder.is_some() {
+                tr!("call.stop_recording")
+            } else {
+                tr!("call.record")
+            })
+            .on_press(Message::Call(CallMessage::ToggleRecording))
+        }));
+
+        let popout_button = if ctx.windows.popout_id.is_some() {
+            button(tr!("call.pop_in")).on_press(Message::Call(CallMessage::PopIn))
+        } else {
+            button(tr!("call.pop_out")).on_press(Message::Call(CallMessage::PopOut))
         };
+        // Only worth asking for once there is a peer to ask.
+        if matches!(self.phase, CallPhase::Connected { .. }) {
+            controls_row = controls_row.push(pick_list(
+                ViewerQuality::ALL,
+                Some(self.viewer_quality),
+                |quality| Message::Call(CallMessage::ViewerQualitySelected(quality)),
+            ));
+        }
+        controls_row = controls_row.extend([
+            popout_button.into(),
+            button(tr!("call.fullscreen"))
+                .on_press(Message::Call(CallMessage::ToggleFullscreen))
+                .into(),
+        ]);
 
-        controls_row = controls_row.extend([button("End Call")
+        controls_row = controls_row.extend([button(tr!("call.end_call"))
             .style(iced::widget::button::danger)
             .on_press(Message::Call(CallMessage::EndCall))
             .into()]);
@@ -397,18 +2917,130 @@ impl Screen for CallScreen {
         let controls_row: Element<'_, Message> =
             container(controls_row).padding(10).center_x(Length::Fill).into();
 
-        let remote_view: Element<Message> = match self.remote_frame.clone() {
-            Some(frame) => container(FrameViewer::new(frame)).center(Length::Fill).into(),
-            None => container(text("Waiting for video...").size(30)).center(Length::Fill).into(),
+        let controls_row: Element<'_, Message> = match &self.sharing_info {
+            Some(info) if self.is_capturing() => {
+                column![controls_row, container(self.sharing_label(info)).center_x(Length::Fill)]
+                    .into()
+            }
+            _ => controls_row,
+        };
+
+        let controls_row: Element<'_, Message> = match self.mode_label(ctx) {
+            Some(label) => {
+                column![controls_row, container(text(label).size(12)).center_x(Length::Fill)].into()
+            }
+            None => controls_row,
+        };
+
+        let controls_row: Element<'_, Message> = match self.security_view(ctx) {
+            Some(security) => column![controls_row, security].into(),
+            None => controls_row,
+        };
+
+        // Always visible while the mouse is watched outside of the app.
+        let controls_row: Element<'_, Message> = if self.click_highlighter.is_some() {
+            column![
+                container(text(tr!("call.highlighting_clicks")))
+                    .padding(5)
+                    .center_x(Length::Fill)
+                    .style(container::warning),
+                controls_row
+            ]
+            .into()
+        } else {
+            controls_row
+        };
+
+        // Always visible while someone else can control our mouse.
+        let controls_row: Element<'_, Message> = if self.remote_control_allowed {
+            column![
+                container(text(tr!("call.remote_control_active")))
+                    .padding(5)
+                    .center_x(Length::Fill)
+                    .style(container::danger),
+                controls_row
+            ]
+            .into()
+        } else {
+            controls_row
+        };
+
+        // Always visible while either side records the call.
+        let controls_row: Element<'_, Message> = match (self.recording, self.remote_recording) {
+            (false, false) => controls_row,
+            (recording, remote_recording) => column![
+                recording.then(|| {
+                    container(text(tr!("call.recording")))
+                        .padding(5)
+                        .center_x(Length::Fill)
+                        .style(container::danger)
+                }),
+                remote_recording.then(|| {
+                    container(text(tr!("call.remote_recording")))
+                        .padding(5)
+                        .center_x(Length::Fill)
+                        .style(container::danger)
+                }),
+                controls_row
+            ]
+            .into(),
+        };
+
+        let remote_view: Element<Message> = if ctx.windows.popout_id.is_some() {
+            container(text(tr!("call.popped_out")).size(30)).center(Length::Fill).into()
+        } else {
+            self.remote_view()
+        };
+        let remote_view = match self.filmstrip.as_ref().and_then(Self::filmstrip_view) {
+            Some(strip) => column![remote_view, strip].into(),
+            None => remote_view,
         };
 
-        let content = if let Some(local_frame) = self.local_frame.clone()
+        let content = if let Some(local_frame) = self.local_preview.frame()
             && self.show_local_preview
         {
-            let local_view = container(FrameViewer::new(local_frame))
-                .width(Length::Fixed(320.0))
-                .height(Length::Fixed(180.0))
-                .style(container::bordered_box);
+            let size = if self.local_preview.is_enlarged() {
+                LocalPreview::LARGE_SIZE
+            } else {
+                LocalPreview::SMALL_SIZE
+            };
+            let mut regions = self.privacy.regions().to_vec();
+            regions.extend(self.privacy.draft());
+            let viewer = FrameViewer::new(local_frame).with_regions(regions);
+            // Clicking it switches between the sizes, unless the privacy regions are being drawn on it.
+            let local_view: Element<Message> = if self.privacy.is_editing() {
+                let viewer =
+                    viewer.on_input(|input| Message::Call(CallMessage::PrivacyInput(input)));
+                let editor = row![
+                    text(tr!("call.privacy_hint")).size(12),
+                    checkbox(self.remember_privacy).label(tr!("call.remember_privacy")).on_toggle(
+                        |remember| Message::Call(CallMessage::RememberPrivacyRegions(remember))
+                    ),
+                    button(tr!("call.clear_privacy"))
+                        .on_press(Message::Call(CallMessage::ClearPrivacyRegions)),
+                ]
+                .spacing(10)
+                .align_y(iced::alignment::Vertical::Center);
+                column![
+                    container(editor).padding(6).style(container::rounded_box),
+                    container(viewer)
+                        .width(Length::Fixed(size.x as f32))
+                        .height(Length::Fixed(size.y as f32))
+                        .style(container::bordered_box),
+                ]
+                .spacing(6)
+                .align_x(iced::alignment::Horizontal::Right)
+                .into()
+            } else {
+                mouse_area(
+                    container(viewer)
+                        .width(Length::Fixed(size.x as f32))
+                        .height(Length::Fixed(size.y as f32))
+                        .style(container::bordered_box),
+                )
+                .on_press(Message::Call(CallMessage::TogglePreviewSize))
+                .into()
+            };
 
             stack![
                 remote_view,
@@ -427,6 +3059,57 @@ impl Screen for CallScreen {
             ]
         };
 
-        content.into()
+        let shown = self.shown_decoder();
+        let decoder_stats = shown.map(DecoderHandle::stats);
+        // Only the receiving side reassembles samples.
+        let depacket_stats = ctx
+            .call
+            .webrtc
+            .as_ref()
+            .zip(shown)
+            .map(|(webrtc, decoder)| webrtc.depacket_stats(decoder.track()));
+        let content =
+            if self.show_stats && (self.encoder_stats.is_some() || decoder_stats.is_some()) {
+                stack![
+                    content,
+                    container(Self::stats_overlay(
+                        self.encoder_stats.as_ref().map(|stats| (stats, ctx.config.bandwidth_cap)),
+                        self.capture_stats.as_ref(),
+                        self.frame_meta.as_ref().map(|meta| *meta.borrow()),
+                        decoder_stats,
+                        depacket_stats,
+                        self.remote_idle.since_last_frame(Instant::now()),
+                        ctx.call.webrtc.as_ref().map_or([None, None], WebRTC::impairment_stats),
+                    ))
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .align_y(iced::alignment::Vertical::Bottom)
+                    .padding(20)
+                ]
+            } else {
+                content
+            };
+
+        let content = match &self.source_picker {
+            Some(picker) => {
+                stack![content, container(picker.view()).padding(40).center(Length::Fill)]
+            }
+            None => content,
+        };
+
+        let content = match self.filmstrip.as_ref().and_then(Filmstrip::selected) {
+            Some(path) => stack![content, Self::snapshot_view(path)],
+            None => content,
+        };
+
+        let content = match &self.mirror_prompt {
+            Some(prompt) => stack![content, Self::mirror_prompt_view(prompt)],
+            None => content,
+        };
+
+        match self.consent_prompt {
+            Some(kind) => stack![content, self.consent_prompt_view(kind)].into(),
+            None => content.into(),
+        }
     }
 }
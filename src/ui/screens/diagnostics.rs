@@ -0,0 +1,209 @@
+use std::time::SystemTime;
+
+use iced::{
+    Element, Length, Subscription, Task,
+    widget::{button, column, container, row, scrollable, text},
+};
+
+use super::Screen;
+use crate::{
+    capabilities,
+    capture_providers::CaptureProvider,
+    diagnostics::{self, Check, CheckResult, CheckStatus, DiagnosticsOptions},
+    ui::{app::App, message::Message, state::AppContext},
+};
+
+#[derive(Debug, Clone)]
+pub enum DiagnosticsMessage {
+    Run,
+    CheckFinished(CheckResult),
+    // The capabilities report, as cached at startup.
+    CapabilitiesLoaded(String),
+    CopyReport,
+    // Calls ourselves, to try sharing and viewing end to end on this machine.
+    StartLoopback,
+}
+
+/// Runs a self-test of the whole pipeline, from the graphics device to the network,
+/// so a bug report says what doesn't work.
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticsScreen {
+    // The results so far, in the order of the checks.
+    results: Vec<CheckResult>,
+    running: bool,
+    capabilities: Option<String>,
+}
+
+impl DiagnosticsScreen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // One at a time, so the checks don't compete for the GPU or the network and skew each other.
+    fn run_next(&mut self, ctx: &AppContext) -> Task<Message> {
+        let Some(&check) = Check::ALL.get(self.results.len()) else {
+            self.running = false;
+            tracing::info!("Diagnostics finished:\n{}", self.report());
+            return Task::none();
+        };
+
+        self.running = true;
+        let mut options = DiagnosticsOptions::from_config(&ctx.config);
+        // Skipped if the provider is busy, as the checks shouldn't wait for a capture to start.
+        options.applied_session_options = ctx
+            .capture
+            .provider()
+            .and_then(|capture| capture.try_read().ok())
+            .and_then(|capture| capture.capture_item_info())
+            .map(|info| info.applied_options);
+        Task::perform(async move { diagnostics::run(check, &options).await }, |result| {
+            Message::Diagnostics(DiagnosticsMessage::CheckFinished(result))
+        })
+    }
+
+    fn load_capabilities() -> Task<Message> {
+        Task::perform(
+            tokio::task::spawn_blocking(|| {
+                capabilities::report(&capabilities::load(false), SystemTime::now())
+            }),
+            |report| match report {
+                Ok(report) => Message::Diagnostics(DiagnosticsMessage::CapabilitiesLoaded(report)),
+                Err(e) => {
+                    tracing::error!("Loading the capabilities panicked: {}", e);
+                    Message::NoOp
+                }
+            },
+        )
+    }
+
+    // Followed by the capabilities, which say what else there is to use besides what the checks ran with.
+    fn report(&self) -> String {
+        let mut report = diagnostics::report(&self.results);
+        if let Some(capabilities) = &self.capabilities {
+            report.push('\n');
+            report.push_str(capabilities);
+        }
+        report
+    }
+
+    fn check_view(&self, index: usize, check: Check) -> Element<'_, Message> {
+        let (status, detail) = match self.results.get(index) {
+            Some(result) => (
+                result.status.to_string(),
+                format!("{} ({} ms)", result.detail, result.duration.as_millis()),
+            ),
+            None if self.running && index == self.results.len() => {
+                ("...".to_owned(), "Running".to_owned())
+            }
+            None => (String::new(), String::new()),
+        };
+        let style = match self.results.get(index).map(|result| result.status) {
+            Some(CheckStatus::Passed) => text::success,
+            Some(CheckStatus::Warning) => text::warning,
+            Some(CheckStatus::Failed) => text::danger,
+            _ => text::default,
+        };
+
+        row![
+            text(status).style(style).width(Length::Fixed(50.0)),
+            column![text(check.to_string()), text(detail).size(12)].spacing(2),
+        ]
+        .spacing(10)
+        .into()
+    }
+}
+
+impl Screen for DiagnosticsScreen {
+    fn update(&mut self, ctx: &mut AppContext, message: Message) -> Task<Message> {
+        let Message::Diagnostics(message) = message else {
+            return Task::none();
+        };
+
+        match message {
+            DiagnosticsMessage::Run => {
+                if self.running {
+                    return Task::none();
+                }
+                self.results.clear();
+                let checks = self.run_next(ctx);
+                match self.capabilities {
+                    Some(_) => checks,
+                    None => Task::batch([checks, Self::load_capabilities()]),
+                }
+            }
+            DiagnosticsMessage::CheckFinished(result) => {
+                self.results.push(result);
+                self.run_next(ctx)
+            }
+            DiagnosticsMessage::CapabilitiesLoaded(report) => {
+                self.capabilities = Some(report);
+                Task::none()
+            }
+            DiagnosticsMessage::CopyReport => {
+                ctx.notifier.in_app.info("Diagnostics report copied to the clipboard.");
+                iced::clipboard::write(self.report())
+            }
+            DiagnosticsMessage::StartLoopback => App::start_loopback(ctx),
+        }
+    }
+
+    fn view(&self, ctx: &AppContext) -> Element<'_, Message> {
+        let title = text("Diagnostics").size(30);
+        let progress = if self.running {
+            text(format!("Running check {} of {}...", self.results.len() + 1, Check::ALL.len()))
+        } else if self.results.is_empty() {
+            text("Tests capturing, encoding, decoding and connecting, which takes a few seconds.")
+        } else {
+            text("Done. Copy the report to add it to a bug report.")
+        };
+
+        let checks = column(
+            Check::ALL.iter().enumerate().map(|(index, &check)| self.check_view(index, check)),
+        )
+        .push(self.capabilities.as_deref().map(|report| text(report).size(12)))
+        .spacing(10);
+
+        let buttons = row![
+            button(if self.results.is_empty() { "Run" } else { "Run again" })
+                .on_press_maybe(
+                    (!self.running).then_some(Message::Diagnostics(DiagnosticsMessage::Run))
+                )
+                .padding(10),
+            button("Copy report")
+                .style(button::secondary)
+                .on_press_maybe(
+                    (!self.running && !self.results.is_empty())
+                        .then_some(Message::Diagnostics(DiagnosticsMessage::CopyReport)),
+                )
+                .padding(10),
+            button("Back").on_press(Message::Back).padding(10),
+        ]
+        .spacing(20);
+
+        let loopback = row![
+            button("Loopback test")
+                .style(button::secondary)
+                .on_press_maybe(
+                    (!self.running && !ctx.call.loopback && !ctx.call.connecting)
+                        .then_some(Message::Diagnostics(DiagnosticsMessage::StartLoopback)),
+                )
+                .padding(10),
+            text("Calls yourself and shows what you share as the remote screen, through the same encoding, network and decoding as a real call. Disconnects from the server until the call ends.")
+                .size(12),
+        ]
+        .spacing(20)
+        .align_y(iced::Alignment::Center);
+
+        let content =
+            column![title, progress, scrollable(checks).height(Length::Fill), buttons, loopback]
+                .spacing(20)
+                .padding(20)
+                .max_width(700);
+
+        container(content).center_x(Length::Fill).height(Length::Fill).into()
+    }
+
+    fn subscription(&self, _ctx: &AppContext) -> Subscription<Message> {
+        Subscription::none()
+    }
+}
@@ -1,27 +1,160 @@
+use std::time::Instant;
+
+use fjarsyn_shared::CallMode;
 use iced::{
     Element, Length, Subscription, Task,
-    widget::{button, column, container, row, text, text_input},
+    widget::{button, checkbox, column, container, row, scrollable, stack, text, text_input},
 };
 
 use super::Screen;
-use crate::ui::{
-    message::{Message, Route},
-    state::AppContext,
+use crate::{
+    tr,
+    ui::{
+        call_summary::{CallSummary, format_duration},
+        confirm_dialog::confirm_dialog,
+        message::{Message, Route},
+        state::AppContext,
+    },
 };
 
+// The calls listed on the home screen, out of the whole history.
+const RECENT_CALLS: usize = 5;
+
 #[derive(Debug, Clone)]
 pub enum HomeMessage {
     TargetIdChanged(String),
     StartCall(String),
     CopyId(String),
+    PassphraseChanged(String),
+    // Whether the signaling is sealed with a key agreed with the peer, when no passphrase is set.
+    KeyExchangeToggled(bool),
+    DismissCallSummary,
+    // The index of the entry in the call history.
+    ToggleCallDetails(usize),
+    // Calls the peer of the link, or leaves it filled in without calling.
+    ConfirmCallLink,
+    DismissCallLink,
+    // Whether the calls from now on only view the peer's screen.
+    ViewOnlyToggled(bool),
 }
 
 #[derive(Debug, Clone)]
-pub struct HomeScreen {}
+pub struct HomeScreen {
+    // The call in the history whose summary is shown.
+    expanded_call: Option<usize>,
+}
 
 impl HomeScreen {
     pub fn new(_ctx: &mut AppContext) -> Self {
-        Self {}
+        Self { expanded_call: None }
+    }
+
+    /// Who the call button calls, which is disabled until there is someone to call.
+    pub fn call_target(ctx: &AppContext) -> Option<&str> {
+        // Codes are often pasted with the spaces around them.
+        ctx.call.target_id.as_deref().map(str::trim).filter(|id| !id.is_empty())
+    }
+
+    fn summary_view(summary: &CallSummary) -> Element<'static, Message> {
+        column(summary.describe().into_iter().map(|(label, value)| {
+            row![text(label).size(12).width(Length::Fixed(120.0)), text(value).size(12)].into()
+        }))
+        .spacing(2)
+        .into()
+    }
+
+    // The summary of the call just left, until dismissed.
+    fn last_call_view(ctx: &AppContext) -> Option<Element<'static, Message>> {
+        let summary = ctx.last_call_summary.as_ref()?;
+        let title = match &summary.peer {
+            Some(peer) => tr!("home.call_with_ended", peer = peer),
+            None => tr!("home.call_ended").to_owned(),
+        };
+        Some(
+            container(
+                column![
+                    row![
+                        text(title).size(16).width(Length::Fill),
+                        button(text(tr!("common.dismiss")).size(12))
+                            .on_press(Message::Home(HomeMessage::DismissCallSummary))
+                            .padding([2, 8])
+                    ]
+                    .align_y(iced::Alignment::Center),
+                    Self::summary_view(summary)
+                ]
+                .spacing(10),
+            )
+            .padding(10)
+            .width(Length::Fixed(400.0))
+            .style(container::rounded_box)
+            .into(),
+        )
+    }
+
+    // The latest calls first, each showing its summary when clicked.
+    fn recent_calls_view(&self, ctx: &AppContext) -> Option<Element<'_, Message>> {
+        let entries = ctx.call_history.entries();
+        if entries.is_empty() {
+            return None;
+        }
+
+        let calls = entries.iter().enumerate().rev().take(RECENT_CALLS).map(|(index, summary)| {
+            let label = tr!(
+                "home.recent_call",
+                peer = summary.peer.as_deref().unwrap_or(tr!("home.unknown_peer")),
+                duration = format_duration(summary.duration)
+            );
+            let entry = button(text(label).size(14))
+                .on_press(Message::Home(HomeMessage::ToggleCallDetails(index)))
+                .style(button::text)
+                .width(Length::Fill);
+            match self.expanded_call == Some(index) {
+                true => column![entry, Self::summary_view(summary)].spacing(5).into(),
+                false => entry.into(),
+            }
+        });
+        Some(
+            column![text(tr!("home.recent_calls")).size(16), column(calls).spacing(2)]
+                .spacing(5)
+                .width(Length::Fixed(400.0))
+                .into(),
+        )
+    }
+
+    fn call_link_view(peer: &str) -> Element<'static, Message> {
+        confirm_dialog(
+            tr!("home.call_link_title", peer = peer),
+            tr!("home.call_link_body"),
+            [
+                button(tr!("home.call_peer"))
+                    .on_press(Message::Home(HomeMessage::ConfirmCallLink))
+                    .into(),
+                button(tr!("common.cancel"))
+                    .style(button::secondary)
+                    .on_press(Message::Home(HomeMessage::DismissCallLink))
+                    .into(),
+            ],
+        )
+    }
+
+    // Shown until connected to the signaling server, along with when it will be tried again.
+    fn offline_view(ctx: &AppContext) -> Element<'static, Message> {
+        if ctx.call.connecting || !ctx.call.reconnect.is_offline() {
+            return text(tr!("home.connecting")).size(20).into();
+        }
+
+        let status = match ctx.call.reconnect.retry_in(Instant::now()) {
+            Some(retry_in) => tr!("home.offline_retrying", seconds = retry_in.as_secs_f32().ceil()),
+            None => tr!("home.offline").to_owned(),
+        };
+        column![
+            text(status).size(20),
+            ctx.call.reconnect.last_error().map(|error| text(error.to_owned()).size(14)),
+            button(tr!("home.retry_connection")).on_press(Message::RetryConnection)
+        ]
+        .spacing(10)
+        .align_x(iced::Alignment::Center)
+        .into()
     }
 }
 
@@ -34,11 +167,12 @@ impl Screen for HomeScreen {
         match message {
             Message::Home(msg) => match msg {
                 HomeMessage::TargetIdChanged(id) => {
-                    ctx.target_id = Some(id);
+                    ctx.call.target_id = Some(id);
                     Task::none()
                 }
                 HomeMessage::StartCall(target_id) => {
-                    if let Some(webrtc) = &ctx.webrtc {
+                    ctx.call.peer_mode = None;
+                    if let Some(webrtc) = &ctx.call.webrtc {
                         let webrtc_clone = webrtc.clone();
                         Task::future(async move {
                             match webrtc_clone.create_offer(target_id).await {
@@ -51,60 +185,153 @@ impl Screen for HomeScreen {
                         })
                     } else {
                         tracing::warn!("Could not start call. WebRTC not initialized...");
+                        ctx.notifier.in_app.error(tr!("home.not_connected"));
                         Task::none()
                     }
                 }
                 HomeMessage::CopyId(id) => iced::clipboard::write(id),
+                HomeMessage::PassphraseChanged(passphrase) => {
+                    if let Some(webrtc) = &ctx.call.webrtc {
+                        webrtc.set_signaling_passphrase(&passphrase);
+                    }
+                    ctx.call.signaling_passphrase = passphrase;
+                    Task::none()
+                }
+                HomeMessage::KeyExchangeToggled(enabled) => {
+                    if let Some(webrtc) = &ctx.call.webrtc {
+                        webrtc.set_signaling_key_exchange(enabled);
+                    }
+                    ctx.call.signaling_key_exchange = enabled;
+                    Task::none()
+                }
+                HomeMessage::DismissCallSummary => {
+                    ctx.last_call_summary = None;
+                    Task::none()
+                }
+                HomeMessage::ToggleCallDetails(index) => {
+                    self.expanded_call = (self.expanded_call != Some(index)).then_some(index);
+                    Task::none()
+                }
+                HomeMessage::ConfirmCallLink => match ctx.call.call_link.take() {
+                    Some(peer) => Task::done(Message::Home(HomeMessage::StartCall(peer))),
+                    None => Task::none(),
+                },
+                HomeMessage::DismissCallLink => {
+                    ctx.call.call_link = None;
+                    Task::none()
+                }
+                HomeMessage::ViewOnlyToggled(view_only) => {
+                    let mode = if view_only { CallMode::ViewOnly } else { CallMode::ShareAndView };
+                    // The offer of a call is taken as soon as it rings, so the connection has to know before one comes in.
+                    if let Some(webrtc) = &ctx.call.webrtc {
+                        webrtc.set_call_mode(mode);
+                    }
+                    ctx.call.mode = mode;
+                    Task::none()
+                }
             },
             _ => Task::none(),
         }
     }
 
     fn view(&self, ctx: &AppContext) -> Element<'_, Message> {
-        let title = text("Welcome to Fjarsyn").size(30);
+        let title = text(tr!("home.title")).size(30);
 
-        let id_display = match &ctx.webrtc {
+        let id_display: Element<'_, Message> = match &ctx.call.webrtc {
             Some(webrtc) => match webrtc.get_local_id() {
-                Some(id) => row![
-                    text(format!("My ID: {}", id)).size(20),
-                    button("Copy").on_press(Message::Home(HomeMessage::CopyId(id)))
-                ]
-                .spacing(10),
-                None => row![text("Connecting to signaling server...").size(20)],
+                Some(id) => {
+                    let id_row = row![
+                        text(tr!("home.my_id", id = id)).size(14),
+                        button(tr!("common.copy")).on_press(Message::Home(HomeMessage::CopyId(id)))
+                    ]
+                    .spacing(10)
+                    .align_y(iced::Alignment::Center);
+
+                    // The code is what gets read out to the other person, so it comes first.
+                    match webrtc.get_local_short_code() {
+                        Some(code) => column![
+                            row![
+                                text(tr!("home.my_code", code = code)).size(28),
+                                button(tr!("common.copy"))
+                                    .on_press(Message::Home(HomeMessage::CopyId(code)))
+                            ]
+                            .spacing(10)
+                            .align_y(iced::Alignment::Center),
+                            id_row
+                        ]
+                        .spacing(10)
+                        .align_x(iced::Alignment::Center)
+                        .into(),
+                        None => id_row.into(),
+                    }
+                }
+                None => text(tr!("home.connecting")).size(20).into(),
             },
-            None => row![text("Connecting to signaling server...").size(20)],
+            None => Self::offline_view(ctx),
         };
 
         let remote_input =
-            text_input("Enter Peer ID to call", ctx.target_id.as_deref().unwrap_or(""))
+            text_input(tr!("home.target_placeholder"), ctx.call.target_id.as_deref().unwrap_or(""))
                 .on_input(|id| Message::Home(HomeMessage::TargetIdChanged(id)))
                 .padding(10)
                 .width(Length::Fixed(400.0));
 
-        let call_button = button("Call Peer")
+        // The server relays the call setup, so with a passphrase it can't read or tamper with it.
+        let passphrase_input =
+            text_input(tr!("home.passphrase_placeholder"), &ctx.call.signaling_passphrase)
+                .on_input(|passphrase| Message::Home(HomeMessage::PassphraseChanged(passphrase)))
+                .secure(true)
+                .padding(10)
+                .width(Length::Fixed(400.0));
+        // Without a passphrase, the server could take part in the exchange, which the security code of the call shows.
+        let key_exchange_check = ctx.call.signaling_passphrase.is_empty().then(|| {
+            checkbox(ctx.call.signaling_key_exchange)
+                .label(tr!("home.key_exchange"))
+                .on_toggle(|enabled| Message::Home(HomeMessage::KeyExchangeToggled(enabled)))
+        });
+        let passphrase_status = if !ctx.call.signaling_passphrase.is_empty() {
+            text(tr!("home.signaling_encrypted")).size(12)
+        } else if ctx.call.signaling_key_exchange {
+            text(tr!("home.signaling_key_exchange")).size(12)
+        } else {
+            text(tr!("home.signaling_unencrypted")).size(12)
+        };
+
+        let call_button = button(tr!("home.call_peer"))
             .on_press_maybe(
-                if let Some(id) = ctx.target_id.as_deref()
-                    && !id.is_empty()
-                {
-                    Some(Message::Home(HomeMessage::StartCall(id.to_owned())))
-                } else {
-                    None
-                },
+                Self::call_target(ctx)
+                    .map(|id| Message::Home(HomeMessage::StartCall(id.to_owned()))),
             )
             .padding(10);
 
-        let settings_button =
-            button("Settings").on_press(Message::NavigateWithBack(Route::Settings)).padding(10);
+        let view_only_check = checkbox(ctx.call.mode == CallMode::ViewOnly)
+            .label(tr!("home.view_only"))
+            .on_toggle(|view_only| Message::Home(HomeMessage::ViewOnlyToggled(view_only)));
+
+        let settings_button = button(tr!("common.settings"))
+            .on_press(Message::NavigateWithBack(Route::Settings))
+            .padding(10);
 
         let content = column![
             title,
             id_display,
             remote_input,
-            row![call_button, settings_button].spacing(20)
+            column![passphrase_input, key_exchange_check, passphrase_status]
+                .spacing(5)
+                .align_x(iced::Alignment::Center),
+            row![call_button, settings_button].spacing(20),
+            view_only_check,
+            Self::last_call_view(ctx),
+            self.recent_calls_view(ctx)
         ]
         .spacing(20)
         .align_x(iced::Alignment::Center);
 
-        container(content).center(Length::Fill).into()
+        // Scrolls once a summary and the recent calls no longer fit.
+        let content = container(scrollable(content)).center(Length::Fill);
+        match &ctx.call.call_link {
+            Some(peer) => stack![content, Self::call_link_view(peer)].into(),
+            None => content.into(),
+        }
     }
 }
@@ -1,4 +1,5 @@
 pub mod call;
+pub mod diagnostics;
 pub mod home;
 pub mod onboarding;
 pub mod settings;
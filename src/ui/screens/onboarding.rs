@@ -1,31 +1,230 @@
+use std::sync::Arc;
+
 use iced::{
     Element, Length, Subscription, Task,
-    widget::{button, column, container, text, text_input},
+    widget::{button, column, container, radio, row, text, text_input},
 };
 
 use super::Screen;
 use crate::{
-    networking::webrtc::WebRTC,
+    capture_providers::{PlatformScreenshotter, create_platform_capture_item_for_primary_monitor},
+    config::QualityPreset,
+    tr,
     ui::{
+        app::App,
+        frame_viewer::FrameViewer,
         message::{Message, Route},
-        state::AppContext,
+        state::{AppContext, CaptureProviderState},
     },
+    utils::{frame::Frame, vector2::Vector2},
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnboardingStep {
+    Server,
+    Capture,
+    Quality,
+}
+
+#[derive(Debug, Clone)]
+enum CaptureTest {
+    Running,
+    Succeeded(Arc<Frame>),
+    // Screen sharing won't work, but the rest of the app does, so this doesn't stop the onboarding.
+    Failed(String),
+}
+
 #[derive(Debug, Clone)]
 pub enum OnboardingMessage {
     ServerUrlChanged(String),
-    SaveClicked,
+    Next,
+    Back,
+    CaptureTestFinished(Result<Arc<Frame>, String>),
+    PresetSelected(QualityPreset),
+    Finish,
 }
 
+/// Walks a new user through connecting to the server, checking that their screen can be captured,
+/// and picking the quality to share with.
 #[derive(Debug, Clone)]
 pub struct OnboardingScreen {
+    step: OnboardingStep,
     server_url: String,
+    // The URL the connection was made with, so going back and forth doesn't connect again.
+    connected_url: Option<String>,
+    connecting: bool,
+    connection_error: Option<String>,
+    capture_test: Option<CaptureTest>,
+    preset: QualityPreset,
 }
 
 impl OnboardingScreen {
+    const PREVIEW_SIZE: Vector2<i32> = Vector2 { x: 480, y: 270 };
+
     pub fn new(server_url: String) -> Self {
-        Self { server_url }
+        Self {
+            step: OnboardingStep::Server,
+            server_url,
+            connected_url: None,
+            connecting: false,
+            connection_error: None,
+            capture_test: None,
+            preset: QualityPreset::Balanced,
+        }
+    }
+
+    fn connect(&mut self, ctx: &mut AppContext) -> Task<Message> {
+        if self.connected_url.as_ref() == Some(&self.server_url) {
+            return self.enter_capture_step(ctx);
+        }
+        if ctx.call.connecting {
+            return Task::none();
+        }
+
+        self.connecting = true;
+        self.connection_error = None;
+        App::connect(ctx, self.server_url.clone())
+    }
+
+    fn enter_capture_step(&mut self, ctx: &mut AppContext) -> Task<Message> {
+        self.step = OnboardingStep::Capture;
+        if matches!(self.capture_test, Some(CaptureTest::Succeeded(_))) {
+            return Task::none();
+        }
+        self.start_capture_test(ctx)
+    }
+
+    // Waits for the capture provider, creating it if it wasn't yet, as that is what sharing will use.
+    fn start_capture_test(&mut self, ctx: &mut AppContext) -> Task<Message> {
+        match ctx.capture {
+            CaptureProviderState::NotStarted | CaptureProviderState::Pending => {
+                self.capture_test = Some(CaptureTest::Running);
+                App::start_capture_provider(ctx)
+            }
+            CaptureProviderState::Unavailable => {
+                self.capture_test =
+                    Some(CaptureTest::Failed(tr!("onboarding.no_graphics_device").to_owned()));
+                Task::none()
+            }
+            CaptureProviderState::Ready(_) => {
+                self.capture_test = Some(CaptureTest::Running);
+                Task::future(async {
+                    tokio::task::spawn_blocking(|| {
+                        let screenshotter = PlatformScreenshotter::new()?;
+                        let item = create_platform_capture_item_for_primary_monitor()?;
+                        screenshotter.capture(&item.into(), Self::PREVIEW_SIZE)
+                    })
+                    .await
+                    .expect("Capture self-test panicked")
+                })
+                .map(|result| {
+                    Message::Onboarding(OnboardingMessage::CaptureTestFinished(
+                        result.map(Arc::new).map_err(|e| e.to_string()),
+                    ))
+                })
+            }
+        }
+    }
+
+    fn finish(&self, ctx: &mut AppContext) -> Task<Message> {
+        ctx.config.onboarding_done = true;
+        ctx.config.server_url = self.server_url.clone();
+        ctx.config.apply_preset(self.preset);
+        if let Err(err) = ctx.store.save_config(&ctx.config) {
+            tracing::error!("Failed to save config: {}", err);
+        }
+        Task::done(Message::Navigate(Route::Home))
+    }
+
+    fn server_step(&self) -> Element<'_, Message> {
+        let next_button = button(if self.connecting {
+            tr!("onboarding.connecting")
+        } else {
+            tr!("onboarding.next")
+        })
+        .on_press_maybe((!self.connecting).then_some(Message::Onboarding(OnboardingMessage::Next)));
+
+        column![
+            text(tr!("onboarding.server_intro")).size(14),
+            text_input(tr!("onboarding.server_placeholder"), &self.server_url)
+                .on_input(|val| Message::Onboarding(OnboardingMessage::ServerUrlChanged(val)))
+                .padding(10),
+        ]
+        .push(
+            self.connection_error
+                .as_ref()
+                .map(|error| text(tr!("onboarding.connect_failed", error = error)).size(14)),
+        )
+        .push(next_button)
+        .spacing(20)
+        .align_x(iced::Alignment::Center)
+        .into()
+    }
+
+    fn capture_step(&self) -> Element<'_, Message> {
+        let result: Element<'_, Message> = match &self.capture_test {
+            None | Some(CaptureTest::Running) => text(tr!("onboarding.capture_checking")).into(),
+            Some(CaptureTest::Succeeded(frame)) => column![
+                text(tr!("onboarding.capture_preview")).size(14),
+                container(FrameViewer::new(frame.clone()))
+                    .width(Length::Fixed(Self::PREVIEW_SIZE.x as f32))
+                    .height(Length::Fixed(Self::PREVIEW_SIZE.y as f32))
+                    .style(container::bordered_box),
+            ]
+            .spacing(10)
+            .align_x(iced::Alignment::Center)
+            .into(),
+            Some(CaptureTest::Failed(error)) => column![
+                text(tr!("onboarding.capture_failed", error = error)).size(14),
+                text(tr!("onboarding.capture_unavailable")).size(14),
+            ]
+            .spacing(10)
+            .align_x(iced::Alignment::Center)
+            .into(),
+        };
+
+        let running = matches!(self.capture_test, None | Some(CaptureTest::Running));
+        column![
+            result,
+            row![
+                button(tr!("common.back")).on_press(Message::Onboarding(OnboardingMessage::Back)),
+                button(tr!("onboarding.next")).on_press_maybe(
+                    (!running).then_some(Message::Onboarding(OnboardingMessage::Next))
+                ),
+            ]
+            .spacing(20),
+        ]
+        .spacing(20)
+        .align_x(iced::Alignment::Center)
+        .into()
+    }
+
+    fn quality_step(&self) -> Element<'_, Message> {
+        let presets = QualityPreset::ALL.iter().fold(column![].spacing(10), |presets, preset| {
+            presets.push(
+                column![
+                    radio(preset.to_string(), *preset, Some(self.preset), |preset| {
+                        Message::Onboarding(OnboardingMessage::PresetSelected(preset))
+                    }),
+                    text(preset.description()).size(12),
+                ]
+                .spacing(5),
+            )
+        });
+
+        column![
+            text(tr!("onboarding.quality_intro")).size(14),
+            presets,
+            row![
+                button(tr!("common.back")).on_press(Message::Onboarding(OnboardingMessage::Back)),
+                button(tr!("onboarding.finish"))
+                    .on_press(Message::Onboarding(OnboardingMessage::Finish)),
+            ]
+            .spacing(20),
+        ]
+        .spacing(20)
+        .align_x(iced::Alignment::Center)
+        .into()
     }
 }
 
@@ -40,32 +239,62 @@ impl Screen for OnboardingScreen {
                 self.server_url = url;
                 Task::none()
             }
-            Message::Onboarding(OnboardingMessage::SaveClicked) => {
-                let Some(frame_tx) = ctx.packet_tx.clone() else {
-                    tracing::error!("Frame channel not available.");
-                    return Task::none();
-                };
-                let Some(webrtc_event_tx) = ctx.webrtc_event_tx.clone() else {
-                    tracing::error!("WebRTC event channel not available.");
-                    return Task::none();
+
+            Message::Onboarding(OnboardingMessage::Next) => match self.step {
+                OnboardingStep::Server => self.connect(ctx),
+                OnboardingStep::Capture => {
+                    self.step = OnboardingStep::Quality;
+                    Task::none()
+                }
+                OnboardingStep::Quality => Task::none(),
+            },
+
+            Message::Onboarding(OnboardingMessage::Back) => {
+                self.step = match self.step {
+                    OnboardingStep::Server | OnboardingStep::Capture => OnboardingStep::Server,
+                    OnboardingStep::Quality => OnboardingStep::Capture,
                 };
-                let server_url = ctx.config.server_url.clone();
-                let max_latency = ctx.config.max_depacket_latency;
+                Task::none()
+            }
 
-                Task::future(async move {
-                    WebRTC::init(server_url, frame_tx, webrtc_event_tx, max_latency).await
-                })
-                .map_err(std::sync::Arc::new)
-                .map(Message::WebRTCInitialized)
+            Message::Onboarding(OnboardingMessage::CaptureTestFinished(result)) => {
+                self.capture_test = Some(match result {
+                    Ok(frame) => CaptureTest::Succeeded(frame),
+                    Err(e) => {
+                        tracing::error!("Capture self-test failed: {}", e);
+                        CaptureTest::Failed(e)
+                    }
+                });
+                Task::none()
             }
 
-            Message::WebRTCInitialized(Ok(_webrtc)) => {
-                ctx.config.onboarding_done = true;
-                ctx.config.server_url = self.server_url.clone();
-                if let Err(err) = ctx.config.save() {
-                    tracing::error!("Failed to save config: {}", err);
+            Message::Onboarding(OnboardingMessage::PresetSelected(preset)) => {
+                self.preset = preset;
+                Task::none()
+            }
+
+            Message::Onboarding(OnboardingMessage::Finish) => self.finish(ctx),
+
+            Message::WebRTCInitialized(result) => {
+                self.connecting = false;
+                match result {
+                    Ok(_handle) => {
+                        self.connected_url = Some(self.server_url.clone());
+                        self.enter_capture_step(ctx)
+                    }
+                    Err(e) => {
+                        self.connection_error = Some(e.to_string());
+                        Task::none()
+                    }
                 }
-                Task::done(Message::Navigate(Route::Home))
+            }
+
+            // The test waits for the provider if it wasn't ready yet.
+            Message::CaptureProviderReady(_)
+                if self.step == OnboardingStep::Capture
+                    && matches!(self.capture_test, Some(CaptureTest::Running)) =>
+            {
+                self.start_capture_test(ctx)
             }
 
             _ => Task::none(),
@@ -73,17 +302,16 @@ impl Screen for OnboardingScreen {
     }
 
     fn view(&self, _ctx: &AppContext) -> Element<'_, Message> {
-        let content = column![
-            text("Welcome to Fjarsyn").size(30),
-            text("Before we get started, enter the URL of your signaling server").size(14),
-            text_input("Signaling Server URL", &self.server_url)
-                .on_input(|val| Message::Onboarding(OnboardingMessage::ServerUrlChanged(val)))
-                .padding(10),
-            button("Save").on_press(Message::Onboarding(OnboardingMessage::SaveClicked))
-        ]
-        .spacing(20)
-        .align_x(iced::Alignment::Center)
-        .max_width(500);
+        let (title, step) = match self.step {
+            OnboardingStep::Server => (tr!("onboarding.step_server"), self.server_step()),
+            OnboardingStep::Capture => (tr!("onboarding.step_capture"), self.capture_step()),
+            OnboardingStep::Quality => (tr!("onboarding.step_quality"), self.quality_step()),
+        };
+
+        let content = column![text(tr!("home.title")).size(30), text(title).size(20), step]
+            .spacing(20)
+            .align_x(iced::Alignment::Center)
+            .max_width(500);
 
         container(content).center(Length::Fill).into()
     }
@@ -1,23 +1,60 @@
+use std::time::{Duration, SystemTime};
+
 use iced::{
     Element, Length, Subscription, Task,
-    widget::{button, column, container, pick_list, row, text, text_input},
+    widget::{button, checkbox, column, container, pick_list, row, text, text_input},
 };
 
 use super::Screen;
 use crate::{
-    capture_providers::shared::CaptureFramerate,
-    config::Config,
-    media::ffmpeg::FFmpegTranscodeType,
-    ui::{message::Message, state::AppContext},
+    capture_providers::shared::{CaptureFramerate, DirtyRegionMode, RotationCorrection},
+    config::{Config, ConfigImport},
+    media::ffmpeg::{ContentHint, DecodeAccel, FFmpegTranscodeType, RateControl},
+    platform::url_protocol,
+    tr,
+    ui::{
+        bandwidth_meter::bandwidth_meter,
+        consent::{ConsentKind, StandingGrant, format_age},
+        i18n::{self, Language},
+        message::{Message, Route},
+        state::AppContext,
+    },
+    utils::bitmap_utils::RedactionStyle,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConfigField {
     Bitrate,
+    BandwidthCap,
     Framerate,
     ServerUrl,
     MaxDepacketLatency,
+    AutoDepacketLatency,
+    ReceiveBuffer,
+    MaxReceiveQueue,
     TranscodingType,
+    DecodeAccel,
+    MaxEncodeDimension,
+    NativeNotifications,
+    CallLinks,
+    CaptureThreadPriority,
+    HdrCapture,
+    RotationCorrection,
+    RedactionStyle,
+    CaptureBorder,
+    CaptureSecondaryWindows,
+    DirtyRegionMode,
+    Gop,
+    RateControl,
+    ContentHint,
+    IntraRefresh,
+    SkipUnchangedFrames,
+    PreviewFps,
+    RemoteIdleAfter,
+    Filmstrip,
+    FilmstripInterval,
+    FilmstripBudget,
+    Language,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,22 +62,161 @@ pub enum ConfigValue {
     String(String),
     Framerate(CaptureFramerate),
     TranscodingType(FFmpegTranscodeType),
+    DecodeAccel(DecodeAccel),
+    RateControl(RateControl),
+    ContentHint(ContentHint),
+    DirtyRegionMode(DirtyRegionMode),
+    RotationCorrection(RotationCorrection),
+    RedactionStyle(RedactionStyle),
+    Language(Language),
+    Bool(bool),
 }
 
 #[derive(Debug, Clone)]
 pub enum SettingsMessage {
     ConfigUpdate(ConfigField, ConfigValue),
     SaveConfig,
+    ExportSettings,
+    // Whether the settings were written, which they aren't if the dialog was cancelled.
+    SettingsExported(Result<bool, String>),
+    ImportSettings,
+    // The content of the picked file, or None if the dialog was cancelled.
+    ImportFileRead(Result<Option<String>, String>),
+    ApplyImport,
+    DiscardImport,
+    // Takes effect and is saved right away, without saving the settings.
+    RevokeConsent(String, ConsentKind),
 }
 
 #[derive(Debug, Clone)]
 pub struct SettingsScreen {
     pub pending_config: Option<Config>,
+    // Imported settings waiting for the user to look over the changes. Boxed, as it holds a whole config.
+    pending_import: Option<Box<ConfigImport>>,
 }
 
 impl SettingsScreen {
+    const FILE_EXTENSIONS: &[&str] = &["json"];
+
     pub fn new(current_config: Config) -> Self {
-        Self { pending_config: Some(current_config) }
+        Self { pending_config: Some(current_config), pending_import: None }
+    }
+
+    // Takes effect right away, as the strings are looked up on every view.
+    fn apply_language(ctx: &AppContext) {
+        i18n::set_language(ctx.config.language);
+    }
+
+    // Registers or unregisters the app as what opens the links.
+    fn apply_call_links(ctx: &mut AppContext) {
+        if let Err(e) = url_protocol::sync(ctx.config.call_links) {
+            tracing::error!("Failed to update the call link registration: {}", e);
+            ctx.notifier.in_app.error(tr!("settings.call_links_failed", error = e));
+        }
+    }
+
+    // Passes on what the running connection can take without being set up again.
+    fn apply_to_connection(ctx: &AppContext) {
+        if let Some(webrtc) = &ctx.call.webrtc {
+            webrtc.set_depacket_latency(
+                ctx.config.max_depacket_latency,
+                ctx.config.auto_depacket_latency,
+            );
+        }
+    }
+
+    // Applied to a running capture too, which may wait for the provider, so it happens in the background.
+    fn apply_to_capture(ctx: &AppContext) -> Task<Message> {
+        let Some(capture) = ctx.capture.provider().cloned() else {
+            return Task::none();
+        };
+        let options = ctx.config.capture_session_options;
+        Task::future(async move {
+            capture.write().await.set_session_options(options);
+            Message::NoOp
+        })
+    }
+
+    async fn export_to_file(content: String) -> Result<bool, String> {
+        let Some(file) = rfd::AsyncFileDialog::new()
+            .set_title(tr!("settings.export_title"))
+            .add_filter(tr!("settings.file_filter"), Self::FILE_EXTENSIONS)
+            .set_file_name("fjarsyn-settings.json")
+            .save_file()
+            .await
+        else {
+            return Ok(false);
+        };
+        file.write(content.as_bytes()).await.map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    async fn read_import_file() -> Result<Option<String>, String> {
+        let Some(file) = rfd::AsyncFileDialog::new()
+            .set_title(tr!("settings.import_title"))
+            .add_filter(tr!("settings.file_filter"), Self::FILE_EXTENSIONS)
+            .pick_file()
+            .await
+        else {
+            return Ok(None);
+        };
+        String::from_utf8(file.read().await).map(Some).map_err(|e| e.to_string())
+    }
+
+    fn import_preview(import: &ConfigImport) -> Element<'_, Message> {
+        let unknown = (!import.unknown.is_empty())
+            .then(|| text(tr!("settings.import_unknown", fields = import.unknown.join(", "))));
+        container(
+            column![text(tr!(
+                "settings.import_changes",
+                count = import.changed.len(),
+                fields = import.changed.join(", ")
+            )),]
+            .push(unknown)
+            .push(
+                row![
+                    button(tr!("settings.apply"))
+                        .on_press(Message::Settings(SettingsMessage::ApplyImport)),
+                    button(tr!("common.cancel"))
+                        .style(button::secondary)
+                        .on_press(Message::Settings(SettingsMessage::DiscardImport)),
+                ]
+                .spacing(10),
+            )
+            .spacing(10),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    // The peers let do things without asking, for the user to take back.
+    fn standing_grants(grants: &[StandingGrant]) -> Element<'static, Message> {
+        if grants.is_empty() {
+            return text(tr!("settings.no_grants")).into();
+        }
+
+        let now = SystemTime::now();
+        column(grants.iter().map(|grant| {
+            let age = now.duration_since(grant.granted_at).unwrap_or_default();
+            row![
+                text(tr!(
+                    "settings.grant",
+                    peer = grant.peer,
+                    kind = grant.kind,
+                    age = format_age(age)
+                ))
+                .width(Length::Fill),
+                button(tr!("settings.revoke")).style(button::danger).on_press(Message::Settings(
+                    SettingsMessage::RevokeConsent(grant.peer.clone(), grant.kind)
+                )),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center)
+            .into()
+        }))
+        .spacing(5)
+        .into()
     }
 }
 
@@ -68,6 +244,125 @@ impl Screen for SettingsScreen {
                             config.transcoding_type = t;
                         }
 
+                        (ConfigField::DecodeAccel, ConfigValue::DecodeAccel(accel)) => {
+                            config.decode_hw_accel = accel;
+                        }
+
+                        (ConfigField::AutoDepacketLatency, ConfigValue::Bool(enabled)) => {
+                            config.auto_depacket_latency = enabled;
+                        }
+
+                        (ConfigField::NativeNotifications, ConfigValue::Bool(enabled)) => {
+                            config.native_notifications = enabled;
+                        }
+
+                        (ConfigField::CallLinks, ConfigValue::Bool(enabled)) => {
+                            config.call_links = enabled;
+                        }
+
+                        (ConfigField::CaptureThreadPriority, ConfigValue::Bool(enabled)) => {
+                            config.capture_thread_priority = enabled;
+                        }
+
+                        (ConfigField::HdrCapture, ConfigValue::Bool(enabled)) => {
+                            config.hdr_capture = enabled;
+                        }
+
+                        (
+                            ConfigField::RotationCorrection,
+                            ConfigValue::RotationCorrection(correction),
+                        ) => {
+                            config.rotation_correction = correction;
+                        }
+
+                        (ConfigField::RedactionStyle, ConfigValue::RedactionStyle(style)) => {
+                            config.redaction_style = style;
+                        }
+
+                        (ConfigField::CaptureBorder, ConfigValue::Bool(enabled)) => {
+                            config.capture_session_options.border_required = enabled;
+                        }
+
+                        (ConfigField::CaptureSecondaryWindows, ConfigValue::Bool(enabled)) => {
+                            config.capture_session_options.include_secondary_windows = enabled;
+                        }
+
+                        (ConfigField::DirtyRegionMode, ConfigValue::DirtyRegionMode(mode)) => {
+                            config.capture_session_options.dirty_region_mode = mode;
+                        }
+
+                        (ConfigField::RateControl, ConfigValue::RateControl(rate_control)) => {
+                            config.rate_control = rate_control;
+                        }
+
+                        (ConfigField::ContentHint, ConfigValue::ContentHint(hint)) => {
+                            config.content_hint = hint;
+                        }
+
+                        (ConfigField::IntraRefresh, ConfigValue::Bool(enabled)) => {
+                            config.intra_refresh = enabled;
+                        }
+
+                        (ConfigField::SkipUnchangedFrames, ConfigValue::Bool(enabled)) => {
+                            config.skip_unchanged_frames = enabled;
+                        }
+
+                        (ConfigField::Language, ConfigValue::Language(language)) => {
+                            config.language = language;
+                        }
+
+                        (ConfigField::Gop, ConfigValue::String(s)) => {
+                            if let Ok(num) = s.parse() {
+                                config.gop = num;
+                            } else {
+                                tracing::error!("Unable to parse GOP: {}", s);
+                                //TODO: show field as invalid
+                            }
+                        }
+
+                        (ConfigField::PreviewFps, ConfigValue::String(s)) => {
+                            if let Ok(num) = s.parse() {
+                                config.preview_fps = num;
+                            } else {
+                                tracing::error!("Unable to parse preview framerate: {}", s);
+                                //TODO: show field as invalid
+                            }
+                        }
+
+                        (ConfigField::RemoteIdleAfter, ConfigValue::String(s)) => {
+                            if let Ok(secs) = s.trim().parse() {
+                                config.remote_idle_after = Duration::from_secs(secs);
+                            } else {
+                                tracing::error!("Unable to parse remote idle time: {}", s);
+                                //TODO: show field as invalid
+                            }
+                        }
+
+                        (ConfigField::Filmstrip, ConfigValue::Bool(enabled)) => {
+                            config.filmstrip = enabled;
+                        }
+
+                        (ConfigField::FilmstripInterval, ConfigValue::String(s)) => {
+                            match s.trim().parse() {
+                                Ok(secs) if secs > 0 => {
+                                    config.filmstrip_interval = Duration::from_secs(secs);
+                                }
+                                _ => {
+                                    tracing::error!("Unable to parse filmstrip interval: {}", s);
+                                    //TODO: show field as invalid
+                                }
+                            }
+                        }
+
+                        (ConfigField::FilmstripBudget, ConfigValue::String(s)) => {
+                            if let Ok(num) = s.trim().parse() {
+                                config.filmstrip_budget_mb = num;
+                            } else {
+                                tracing::error!("Unable to parse filmstrip budget: {}", s);
+                                //TODO: show field as invalid
+                            }
+                        }
+
                         (ConfigField::Bitrate, ConfigValue::String(s)) => {
                             if let Ok(num) = s.parse() {
                                 config.bitrate = num;
@@ -77,6 +372,30 @@ impl Screen for SettingsScreen {
                             }
                         }
 
+                        (ConfigField::BandwidthCap, ConfigValue::String(s)) => {
+                            // Empty means sending whatever the encoder makes.
+                            if s.trim().is_empty() {
+                                config.bandwidth_cap = None;
+                            } else if let Ok(num) = s.trim().parse() {
+                                config.bandwidth_cap = Some(num);
+                            } else {
+                                tracing::error!("Unable to parse bandwidth cap: {}", s);
+                                //TODO: show field as invalid
+                            }
+                        }
+
+                        (ConfigField::MaxEncodeDimension, ConfigValue::String(s)) => {
+                            // Empty means encoding at the captured resolution.
+                            if s.trim().is_empty() {
+                                config.max_encode_dimension = None;
+                            } else if let Ok(num) = s.trim().parse() {
+                                config.max_encode_dimension = Some(num);
+                            } else {
+                                tracing::error!("Unable to parse max encode dimension: {}", s);
+                                //TODO: show field as invalid
+                            }
+                        }
+
                         (ConfigField::MaxDepacketLatency, ConfigValue::String(s)) => {
                             if let Ok(num) = s.parse() {
                                 config.max_depacket_latency = num;
@@ -86,6 +405,24 @@ impl Screen for SettingsScreen {
                             }
                         }
 
+                        (ConfigField::ReceiveBuffer, ConfigValue::String(s)) => {
+                            if let Ok(num) = s.parse() {
+                                config.receive_buffer = num;
+                            } else {
+                                tracing::error!("Unable to parse receive buffer: {}", s);
+                                //TODO: show field as invalid
+                            }
+                        }
+
+                        (ConfigField::MaxReceiveQueue, ConfigValue::String(s)) => {
+                            if let Ok(num) = s.parse() {
+                                config.max_receive_queue = num;
+                            } else {
+                                tracing::error!("Unable to parse max receive queue: {}", s);
+                                //TODO: show field as invalid
+                            }
+                        }
+
                         _ => {}
                     }
 
@@ -94,13 +431,103 @@ impl Screen for SettingsScreen {
 
                 SettingsMessage::SaveConfig => {
                     if let Some(pending) = self.pending_config.take() {
+                        let call_links_changed = pending.call_links != ctx.config.call_links;
                         ctx.config = pending;
-                        if let Err(e) = ctx.config.save() {
-                            let msg = format!("Failed to save config: {}", e);
-                            tracing::error!(msg);
-                            ctx.notifications.error(msg);
+                        Self::apply_language(ctx);
+                        if call_links_changed {
+                            Self::apply_call_links(ctx);
+                        }
+                        Self::apply_to_connection(ctx);
+                        if let Err(e) = ctx.store.save_config(&ctx.config) {
+                            tracing::error!("Failed to save config: {}", e);
+                            ctx.notifier.in_app.error(tr!("settings.save_failed", error = e));
+                        } else {
+                            ctx.notifier.in_app.success(tr!("settings.saved"));
+                        }
+                        return Self::apply_to_capture(ctx);
+                    }
+                    Task::none()
+                }
+
+                // Exports what is on screen, including changes that aren't saved yet.
+                SettingsMessage::ExportSettings => match config.export() {
+                    Ok(content) => Task::future(Self::export_to_file(content))
+                        .map(|result| Message::Settings(SettingsMessage::SettingsExported(result))),
+                    Err(e) => {
+                        ctx.notifier.in_app.error(tr!("settings.export_failed", error = e));
+                        Task::none()
+                    }
+                },
+
+                SettingsMessage::SettingsExported(result) => {
+                    match result {
+                        Ok(true) => ctx.notifier.in_app.success(tr!("settings.exported")),
+                        Ok(false) => {}
+                        Err(e) => {
+                            tracing::error!("Failed to export settings: {}", e);
+                            ctx.notifier.in_app.error(tr!("settings.export_failed", error = e));
+                        }
+                    }
+                    Task::none()
+                }
+
+                SettingsMessage::ImportSettings => Task::future(Self::read_import_file())
+                    .map(|result| Message::Settings(SettingsMessage::ImportFileRead(result))),
+
+                SettingsMessage::ImportFileRead(Ok(None)) => Task::none(),
+
+                SettingsMessage::ImportFileRead(Ok(Some(content))) => {
+                    match config.import(&content) {
+                        Ok(import) if import.changed.is_empty() => {
+                            ctx.notifier.in_app.info(tr!("settings.import_unchanged"));
+                        }
+                        Ok(import) => self.pending_import = Some(Box::new(import)),
+                        Err(e) => {
+                            tracing::error!("Failed to import settings: {}", e);
+                            ctx.notifier.in_app.error(tr!("settings.import_failed", error = e));
+                        }
+                    }
+                    Task::none()
+                }
+
+                SettingsMessage::ImportFileRead(Err(e)) => {
+                    tracing::error!("Failed to read settings file: {}", e);
+                    ctx.notifier.in_app.error(tr!("settings.read_failed", error = e));
+                    Task::none()
+                }
+
+                // Saved right away, as the changes were looked over already.
+                SettingsMessage::ApplyImport => {
+                    if let Some(import) = self.pending_import.take() {
+                        *config = import.config;
+                        ctx.config = config.clone();
+                        Self::apply_language(ctx);
+                        Self::apply_to_connection(ctx);
+                        if let Err(e) = ctx.store.save_config(&ctx.config) {
+                            tracing::error!("Failed to save config: {}", e);
+                            ctx.notifier.in_app.error(tr!("settings.save_failed", error = e));
                         } else {
-                            ctx.notifications.success("Config saved!");
+                            ctx.notifier
+                                .in_app
+                                .success(tr!("settings.imported", count = import.changed.len()));
+                        }
+                    }
+                    Task::none()
+                }
+
+                SettingsMessage::DiscardImport => {
+                    self.pending_import = None;
+                    Task::none()
+                }
+
+                SettingsMessage::RevokeConsent(peer, kind) => {
+                    if ctx.consents.revoke(&peer, kind, SystemTime::now()) {
+                        tracing::info!(%peer, "Revoked the standing grant for {}", kind);
+                        if let Err(e) = ctx.store.save_consents(&ctx.consents) {
+                            tracing::error!("Failed to save consents: {}", e);
+                            ctx.notifier
+                                .in_app
+                                .error(tr!("settings.consents_save_failed", error = e));
                         }
                     }
                     Task::none()
@@ -114,9 +541,17 @@ impl Screen for SettingsScreen {
     fn view(&self, ctx: &AppContext) -> Element<'_, Message> {
         let config = self.pending_config.as_ref().unwrap_or(&ctx.config);
 
-        let title = text("Settings").size(30);
+        let title = text(tr!("common.settings")).size(30);
+
+        let language_pick = pick_list(Language::ALL, Some(config.language), |language| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::Language,
+                ConfigValue::Language(language),
+            ))
+        })
+        .padding(10);
 
-        let url_input = text_input("Signaling Server URL", &config.server_url)
+        let url_input = text_input(tr!("onboarding.server_placeholder"), &config.server_url)
             .on_input(|val| {
                 Message::Settings(SettingsMessage::ConfigUpdate(
                     ConfigField::ServerUrl,
@@ -142,47 +577,366 @@ impl Screen for SettingsScreen {
             })
             .padding(10);
 
-        let bitrate_input = text_input("Bitrate (bps)", &config.bitrate.to_string())
+        let bitrate_input =
+            text_input(tr!("settings.bitrate_placeholder"), &config.bitrate.to_string())
+                .on_input(|val| {
+                    Message::Settings(SettingsMessage::ConfigUpdate(
+                        ConfigField::Bitrate,
+                        ConfigValue::String(val),
+                    ))
+                })
+                .padding(10);
+
+        let bandwidth_cap = config.bandwidth_cap.map(|cap| cap.to_string());
+        let bandwidth_cap_input = text_input(
+            tr!("settings.bandwidth_cap_placeholder"),
+            bandwidth_cap.as_deref().unwrap_or_default(),
+        )
+        .on_input(|val| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::BandwidthCap,
+                ConfigValue::String(val),
+            ))
+        })
+        .padding(10);
+        // The usage is only shown while sharing, as nothing is sent otherwise.
+        let bandwidth_cap_input = column![bandwidth_cap_input]
+            .push(ctx.call.send_bitrate.map(|sent| bandwidth_meter(sent, config.bandwidth_cap)))
+            .spacing(8);
+
+        let gop_input = text_input(tr!("settings.gop_placeholder"), &config.gop.to_string())
             .on_input(|val| {
                 Message::Settings(SettingsMessage::ConfigUpdate(
-                    ConfigField::Bitrate,
+                    ConfigField::Gop,
                     ConfigValue::String(val),
                 ))
             })
             .padding(10);
 
-        let max_depacket_input =
-            text_input("Max Depacket Latency (ms)", &config.max_depacket_latency.to_string())
+        let rate_control_pick =
+            pick_list(RateControl::ALL, Some(config.rate_control), |rate_control| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::RateControl,
+                    ConfigValue::RateControl(rate_control),
+                ))
+            })
+            .padding(10);
+
+        let content_hint_pick = pick_list(ContentHint::ALL, Some(config.content_hint), |hint| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::ContentHint,
+                ConfigValue::ContentHint(hint),
+            ))
+        })
+        .padding(10);
+
+        let intra_refresh_check = checkbox(config.intra_refresh)
+            .label(tr!("settings.intra_refresh"))
+            .on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::IntraRefresh,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let skip_unchanged_check = checkbox(config.skip_unchanged_frames)
+            .label(tr!("settings.skip_unchanged_frames"))
+            .on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::SkipUnchangedFrames,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let decode_accel_pick =
+            pick_list(DecodeAccel::ALL, Some(config.decode_hw_accel), |accel| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::DecodeAccel,
+                    ConfigValue::DecodeAccel(accel),
+                ))
+            })
+            .padding(10);
+
+        let max_encode_dimension = config.max_encode_dimension.map(|d| d.to_string());
+        let max_encode_dimension_input = text_input(
+            tr!("settings.max_encode_dimension_placeholder"),
+            max_encode_dimension.as_deref().unwrap_or_default(),
+        )
+        .on_input(|val| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::MaxEncodeDimension,
+                ConfigValue::String(val),
+            ))
+        })
+        .padding(10);
+
+        let preview_fps_input =
+            text_input(tr!("settings.preview_fps_placeholder"), &config.preview_fps.to_string())
                 .on_input(|val| {
                     Message::Settings(SettingsMessage::ConfigUpdate(
-                        ConfigField::MaxDepacketLatency,
+                        ConfigField::PreviewFps,
                         ConfigValue::String(val),
                     ))
                 })
                 .padding(10);
 
-        let save_button =
-            button("Save").on_press(Message::Settings(SettingsMessage::SaveConfig)).padding(10);
-
-        let back_button = button("Back").on_press(Message::Back).padding(10);
-
-        let content = column![
-            title,
-            text("Server URL:"),
-            url_input,
-            text("Framerate:"),
-            framerate_pick,
-            text("Transcoding Type:"),
-            transcode_pick,
-            text("Bitrate:"),
-            bitrate_input,
-            text("Max Depacket Latency:"),
-            max_depacket_input,
-            row![save_button, back_button].spacing(20)
-        ]
-        .spacing(20)
-        .padding(20)
-        .max_width(600);
+        let remote_idle_input = text_input(
+            tr!("settings.remote_idle_placeholder"),
+            &config.remote_idle_after.as_secs().to_string(),
+        )
+        .on_input(|val| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::RemoteIdleAfter,
+                ConfigValue::String(val),
+            ))
+        })
+        .padding(10);
+
+        let filmstrip_check =
+            checkbox(config.filmstrip).label(tr!("settings.filmstrip")).on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::Filmstrip,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let filmstrip_interval_input = text_input(
+            tr!("settings.filmstrip_interval_placeholder"),
+            &config.filmstrip_interval.as_secs().to_string(),
+        )
+        .on_input(|val| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::FilmstripInterval,
+                ConfigValue::String(val),
+            ))
+        })
+        .padding(10);
+
+        let filmstrip_budget_input = text_input(
+            tr!("settings.filmstrip_budget_placeholder"),
+            &config.filmstrip_budget_mb.to_string(),
+        )
+        .on_input(|val| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::FilmstripBudget,
+                ConfigValue::String(val),
+            ))
+        })
+        .padding(10);
+
+        let max_depacket_input = text_input(
+            tr!("settings.max_depacket_placeholder"),
+            &config.max_depacket_latency.to_string(),
+        )
+        .on_input(|val| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::MaxDepacketLatency,
+                ConfigValue::String(val),
+            ))
+        })
+        .padding(10);
+
+        let receive_buffer_input = text_input(
+            tr!("settings.receive_buffer_placeholder"),
+            &config.receive_buffer.to_string(),
+        )
+        .on_input(|val| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::ReceiveBuffer,
+                ConfigValue::String(val),
+            ))
+        })
+        .padding(10);
+
+        let max_receive_queue_input = text_input(
+            tr!("settings.max_receive_queue_placeholder"),
+            &config.max_receive_queue.to_string(),
+        )
+        .on_input(|val| {
+            Message::Settings(SettingsMessage::ConfigUpdate(
+                ConfigField::MaxReceiveQueue,
+                ConfigValue::String(val),
+            ))
+        })
+        .padding(10);
+
+        let auto_depacket_check = checkbox(config.auto_depacket_latency)
+            .label(tr!("settings.auto_depacket"))
+            .on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::AutoDepacketLatency,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let native_notifications_check = checkbox(config.native_notifications)
+            .label(tr!("settings.native_notifications"))
+            .on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::NativeNotifications,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let call_links_check =
+            checkbox(config.call_links).label(tr!("settings.call_links")).on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::CallLinks,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let capture_thread_priority_check = checkbox(config.capture_thread_priority)
+            .label(tr!("settings.capture_thread_priority"))
+            .on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::CaptureThreadPriority,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let hdr_capture_check =
+            checkbox(config.hdr_capture).label(tr!("settings.hdr_capture")).on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::HdrCapture,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let rotation_correction_pick =
+            pick_list(RotationCorrection::ALL, Some(config.rotation_correction), |correction| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::RotationCorrection,
+                    ConfigValue::RotationCorrection(correction),
+                ))
+            })
+            .padding(10);
+
+        let redaction_style_pick =
+            pick_list(RedactionStyle::ALL, Some(config.redaction_style), |style| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::RedactionStyle,
+                    ConfigValue::RedactionStyle(style),
+                ))
+            })
+            .padding(10);
+
+        let session_options = config.capture_session_options;
+        let capture_border_check = checkbox(session_options.border_required)
+            .label(tr!("settings.capture_border"))
+            .on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::CaptureBorder,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let capture_secondary_windows_check = checkbox(session_options.include_secondary_windows)
+            .label(tr!("settings.capture_secondary_windows"))
+            .on_toggle(|enabled| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::CaptureSecondaryWindows,
+                    ConfigValue::Bool(enabled),
+                ))
+            });
+
+        let dirty_region_mode_pick =
+            pick_list(DirtyRegionMode::ALL, Some(session_options.dirty_region_mode), |mode| {
+                Message::Settings(SettingsMessage::ConfigUpdate(
+                    ConfigField::DirtyRegionMode,
+                    ConfigValue::DirtyRegionMode(mode),
+                ))
+            })
+            .padding(10);
+
+        let save_button = button(tr!("settings.save"))
+            .on_press(Message::Settings(SettingsMessage::SaveConfig))
+            .padding(10);
+
+        let back_button = button(tr!("common.back")).on_press(Message::Back).padding(10);
+
+        let export_button = button(tr!("settings.export"))
+            .style(button::secondary)
+            .on_press(Message::Settings(SettingsMessage::ExportSettings))
+            .padding(10);
+
+        let import_button = button(tr!("settings.import"))
+            .style(button::secondary)
+            .on_press(Message::Settings(SettingsMessage::ImportSettings))
+            .padding(10);
+
+        let diagnostics_button = button(tr!("settings.diagnostics"))
+            .style(button::secondary)
+            .on_press(Message::NavigateWithBack(Route::Diagnostics))
+            .padding(10);
+
+        let content = column![title]
+            .push(self.pending_import.as_deref().map(Self::import_preview))
+            .push(
+                column![
+                    text(tr!("settings.language")),
+                    language_pick,
+                    text(tr!("settings.server_url")),
+                    url_input,
+                    text(tr!("settings.framerate")),
+                    framerate_pick,
+                    text(tr!("settings.transcoding_type")),
+                    transcode_pick,
+                    text(tr!("settings.bitrate")),
+                    bitrate_input,
+                    text(tr!("settings.bandwidth_cap")),
+                    bandwidth_cap_input,
+                    text(tr!("settings.gop")),
+                    gop_input,
+                    text(tr!("settings.rate_control")),
+                    rate_control_pick,
+                    text(tr!("settings.content_hint")),
+                    content_hint_pick,
+                    intra_refresh_check,
+                    skip_unchanged_check,
+                    text(tr!("settings.max_encode_dimension")),
+                    max_encode_dimension_input,
+                    text(tr!("settings.preview_fps")),
+                    preview_fps_input,
+                    text(tr!("settings.remote_idle")),
+                    remote_idle_input,
+                    filmstrip_check,
+                    text(tr!("settings.filmstrip_interval")),
+                    filmstrip_interval_input,
+                    text(tr!("settings.filmstrip_budget")),
+                    filmstrip_budget_input,
+                    text(tr!("settings.max_depacket")),
+                    max_depacket_input,
+                    auto_depacket_check,
+                    text(tr!("settings.decode_accel")),
+                    decode_accel_pick,
+                    text(tr!("settings.receive_buffer")),
+                    receive_buffer_input,
+                    text(tr!("settings.max_receive_queue")),
+                    max_receive_queue_input,
+                    native_notifications_check,
+                    call_links_check,
+                    capture_thread_priority_check,
+                    hdr_capture_check,
+                    text(tr!("settings.rotation_correction")),
+                    rotation_correction_pick,
+                    text(tr!("settings.redaction_style")),
+                    redaction_style_pick,
+                    text(tr!("settings.capture_options")),
+                    capture_border_check,
+                    capture_secondary_windows_check,
+                    text(tr!("settings.dirty_region_mode")),
+                    dirty_region_mode_pick,
+                    text(tr!("settings.grants")),
+                    Self::standing_grants(ctx.consents.grants()),
+                    row![save_button, back_button].spacing(20),
+                    row![export_button, import_button, diagnostics_button].spacing(20),
+                ]
+                .spacing(20),
+            )
+            .spacing(20)
+            .padding(20)
+            .max_width(600);
 
         container(content)
             .width(Length::Fill)
@@ -0,0 +1,124 @@
+use std::time::{Duration, Instant};
+
+use iced::{
+    Alignment, Element, Length, Point, Size,
+    widget::{button, container, row, text},
+    window,
+};
+
+use crate::{
+    capture_providers::shared::MonitorGeometry,
+    ui::{message::Message, screens::call::CallMessage},
+};
+
+/// The pill shown on top of everything while sharing, as people forget they are.
+/// It is kept out of the capture, so the peer doesn't see it.
+#[derive(Debug, Clone)]
+pub struct SharingIndicator {
+    shown_at: Instant,
+    // The opacity of the dot, which pulses.
+    pulse: f32,
+}
+
+impl SharingIndicator {
+    pub const SIZE: Size = Size::new(320.0, 44.0);
+    /// How often the dot is redrawn.
+    pub const PULSE_INTERVAL: Duration = Duration::from_millis(100);
+    const PULSE_PERIOD: Duration = Duration::from_millis(1600);
+    // Below the top edge, so it doesn't cover the title bar of a maximized window entirely.
+    const TOP_MARGIN: f32 = 8.0;
+
+    pub fn new(now: Instant) -> Self {
+        Self { shown_at: now, pulse: 1.0 }
+    }
+
+    /// The window, at the top center of the monitor, or wherever the system puts it if that is unknown.
+    pub fn window_settings(monitor: Option<&MonitorGeometry>) -> window::Settings {
+        window::Settings {
+            size: Self::SIZE,
+            position: monitor.map_or(window::Position::Default, |monitor| {
+                window::Position::Specific(Self::position(monitor))
+            }),
+            resizable: false,
+            decorations: false,
+            transparent: true,
+            level: window::Level::AlwaysOnTop,
+            exit_on_close_request: false,
+            platform_specific: window::settings::PlatformSpecific {
+                skip_taskbar: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Where the window goes on the monitor, in logical pixels.
+    /// Exact if the monitors share a scale factor, close otherwise.
+    pub fn position(monitor: &MonitorGeometry) -> Point {
+        let scale = monitor.scale_factor.max(f32::EPSILON);
+        let left = monitor.bounds.position.x as f32 / scale;
+        let width = monitor.bounds.size.x as f32 / scale;
+        Point::new(
+            left + (width - Self::SIZE.width) / 2.0,
+            monitor.bounds.position.y as f32 / scale + Self::TOP_MARGIN,
+        )
+    }
+
+    /// The opacity of the dot, `elapsed` after it was shown. Fades between full and a third.
+    pub fn pulse_at(elapsed: Duration) -> f32 {
+        let phase = elapsed.as_secs_f32() / Self::PULSE_PERIOD.as_secs_f32();
+        let wave = (phase * std::f32::consts::TAU).cos() * 0.5 + 0.5;
+        1.0 / 3.0 + wave * 2.0 / 3.0
+    }
+
+    pub fn tick(&mut self, now: Instant) {
+        self.pulse = Self::pulse_at(now.saturating_duration_since(self.shown_at));
+    }
+
+    /// `mirrored` shows the pill in a warning color, for while the call itself is on the shared monitor.
+    pub fn view<'a>(&self, source_name: Option<&'a str>, mirrored: bool) -> Element<'a, Message> {
+        let pulse = self.pulse;
+        let dot = container(iced::widget::Space::new())
+            .width(Length::Fixed(10.0))
+            .height(Length::Fixed(10.0))
+            .style(move |_| container::Style {
+                background: Some(iced::Background::Color(iced::Color::from_rgba8(
+                    0xd9, 0x3b, 0x3b, pulse,
+                ))),
+                border: iced::Border { radius: 5.0.into(), ..Default::default() },
+                ..Default::default()
+            });
+        let label = match source_name {
+            Some(name) => format!("Sharing {}", name),
+            None => "Sharing your screen".to_owned(),
+        };
+
+        let content = row![
+            dot,
+            text(label).width(Length::Fill).wrapping(text::Wrapping::None),
+            button(text("Stop").size(13))
+                .style(button::danger)
+                .padding([4, 12])
+                .on_press(Message::Call(CallMessage::StopCapture)),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        container(content)
+            .padding([6, 12])
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .align_y(Alignment::Center)
+            .style(move |theme: &iced::Theme| container::Style {
+                background: Some(iced::Background::Color(if mirrored {
+                    theme.extended_palette().warning.base.color
+                } else {
+                    theme.extended_palette().background.strong.color
+                })),
+                text_color: mirrored.then(|| theme.extended_palette().warning.base.text),
+                border: iced::Border { radius: 22.0.into(), ..Default::default() },
+                ..Default::default()
+            })
+            .into()
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use iced::{
+    Element, Length, Task,
+    alignment::Vertical,
+    task::Handle,
+    widget::{button, column, container, image, row, scrollable, text},
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    capture_providers::{
+        PlatformScreenshotter, SourceDescriptor, SourceKind, enumerate_platform_sources,
+        shared::WindowIcon,
+    },
+    ui::{frame_viewer::FrameViewer, message::Message, screens::call::CallMessage},
+    utils::{frame::Frame, vector2::Vector2},
+};
+
+#[derive(Debug, Clone)]
+pub enum SourcePickerMessage {
+    Refresh,
+    SourcesLoaded(Result<Vec<SourceDescriptor>, String>),
+    ThumbnailLoaded(Box<SourceDescriptor>, Arc<Frame>),
+    /// The icon of the window with the handle, or None if it has none.
+    IconLoaded(u64, Option<WindowIcon>),
+}
+
+/// Makes an icon something iced can draw, once, rather than copying its pixels on every frame.
+pub fn icon_handle(icon: &WindowIcon) -> image::Handle {
+    image::Handle::from_rgba(icon.size.x as u32, icon.size.y as u32, icon.rgba.to_vec())
+}
+
+/// A window's icon, or a generic glyph for windows without one.
+pub fn icon_view<'a, M: 'a>(icon: Option<&image::Handle>, size: f32) -> Element<'a, M> {
+    match icon {
+        Some(handle) => image(handle.clone()).width(size).height(size).into(),
+        None => container(text("\u{25a1}").size(size * 0.8)).center(size).into(),
+    }
+}
+
+/// Lets the user pick a monitor, all of them, or a window to share, with a preview of each.
+#[derive(Debug, Clone, Default)]
+pub struct SourcePicker {
+    // None while the sources are being enumerated.
+    sources: Option<Vec<SourceDescriptor>>,
+    thumbnails: HashMap<SourceDescriptor, Arc<Frame>>,
+    // By window handle. Kept while the picker is open, as windows don't change their icon often.
+    icons: HashMap<u64, Option<image::Handle>>,
+    error: Option<String>,
+    // Aborts the loading in flight once the picker is closed or refreshed.
+    loading: Option<Handle>,
+}
+
+impl SourcePicker {
+    const THUMBNAIL_SIZE: Vector2<i32> = Vector2 { x: 240, y: 135 };
+    const TILE_WIDTH: f32 = 256.0;
+    const ICON_SIZE: f32 = 16.0;
+
+    /// Creates the picker, and starts loading the sources.
+    pub fn open() -> (Self, Task<Message>) {
+        let mut picker = Self::default();
+        let task = picker.refresh();
+        (picker, task)
+    }
+
+    fn refresh(&mut self) -> Task<Message> {
+        self.sources = None;
+        self.thumbnails.clear();
+        self.error = None;
+
+        let task = Task::future(async {
+            tokio::task::spawn_blocking(enumerate_platform_sources)
+                .await
+                .expect("Capture source enumeration panicked")
+        })
+        .map(|result| {
+            Message::Call(CallMessage::SourcePicker(SourcePickerMessage::SourcesLoaded(
+                result.map_err(|e| e.to_string()),
+            )))
+        });
+
+        self.track_loading(task)
+    }
+
+    fn track_loading(&mut self, task: Task<Message>) -> Task<Message> {
+        let (task, handle) = task.abortable();
+        // Replacing the handle aborts whatever was loading before.
+        self.loading = Some(handle.abort_on_drop());
+        task
+    }
+
+    // The icons missing come first as they are quick, then the thumbnails, captured one by one on a single device.
+    // Both stop as soon as nobody is listening anymore.
+    fn load_previews(sources: Vec<SourceDescriptor>, cached_icons: HashSet<u64>) -> Task<Message> {
+        let (tx, rx) = mpsc::channel::<SourcePickerMessage>(1);
+
+        tokio::task::spawn_blocking(move || {
+            for source in &sources {
+                let Some(window) =
+                    source.window_handle().filter(|window| !cached_icons.contains(window))
+                else {
+                    continue;
+                };
+                if tx.blocking_send(SourcePickerMessage::IconLoaded(window, source.icon())).is_err()
+                {
+                    tracing::debug!("Source picker closed, stopping icon loading");
+                    return;
+                }
+            }
+
+            let screenshotter = match PlatformScreenshotter::new() {
+                Ok(screenshotter) => screenshotter,
+                Err(e) => {
+                    tracing::warn!("Failed to create screenshotter for thumbnails: {}", e);
+                    return;
+                }
+            };
+
+            for source in sources {
+                let thumbnail = source
+                    .create_capture_item()
+                    .and_then(|item| screenshotter.capture(&item, Self::THUMBNAIL_SIZE));
+
+                match thumbnail {
+                    Ok(frame) => {
+                        let loaded =
+                            SourcePickerMessage::ThumbnailLoaded(Box::new(source), Arc::new(frame));
+                        if tx.blocking_send(loaded).is_err() {
+                            tracing::debug!("Source picker closed, stopping thumbnail loading");
+                            return;
+                        }
+                    }
+                    // Minimized windows and the like just don't get a preview.
+                    Err(e) => tracing::debug!("Failed to capture thumbnail of {:?}: {}", source, e),
+                }
+            }
+        });
+
+        Task::run(
+            futures::stream::unfold(rx, |mut rx| async move {
+                rx.recv().await.map(|message| (message, rx))
+            }),
+            |message| Message::Call(CallMessage::SourcePicker(message)),
+        )
+    }
+
+    pub fn update(&mut self, message: SourcePickerMessage) -> Task<Message> {
+        match message {
+            SourcePickerMessage::Refresh => self.refresh(),
+
+            SourcePickerMessage::SourcesLoaded(Ok(sources)) => {
+                self.sources = Some(sources.clone());
+                let cached_icons = self.icons.keys().copied().collect();
+                self.track_loading(Self::load_previews(sources, cached_icons))
+            }
+
+            SourcePickerMessage::SourcesLoaded(Err(e)) => {
+                tracing::error!("Failed to enumerate capture sources: {}", e);
+                self.sources = Some(Vec::new());
+                self.error = Some(e);
+                Task::none()
+            }
+
+            SourcePickerMessage::ThumbnailLoaded(source, frame) => {
+                self.thumbnails.insert(*source, frame);
+                Task::none()
+            }
+
+            SourcePickerMessage::IconLoaded(window, icon) => {
+                self.icons.insert(window, icon.as_ref().map(icon_handle));
+                Task::none()
+            }
+        }
+    }
+
+    fn source_tile<'a>(&'a self, source: &'a SourceDescriptor) -> Element<'a, Message> {
+        let thumbnail: Element<'a, Message> = match self.thumbnails.get(source) {
+            Some(frame) => FrameViewer::new(frame.clone()).into(),
+            None => container(text("No preview").size(12)).center(Length::Fill).into(),
+        };
+
+        button(
+            column![
+                container(thumbnail)
+                    .width(Length::Fixed(Self::THUMBNAIL_SIZE.x as f32))
+                    .height(Length::Fixed(Self::THUMBNAIL_SIZE.y as f32)),
+                self.source_label(source),
+            ]
+            .spacing(5),
+        )
+        .width(Length::Fixed(Self::TILE_WIDTH))
+        .style(button::secondary)
+        .on_press(Message::Call(CallMessage::SourceSelected(Box::new(source.clone()))))
+        .into()
+    }
+
+    // Windows get their icon and process, so that those with the same title can be told apart.
+    fn source_label<'a>(&'a self, source: &'a SourceDescriptor) -> Element<'a, Message> {
+        let Some(window) = source.window_handle() else {
+            return text(&source.name).size(12).into();
+        };
+
+        let icon = self.icons.get(&window).and_then(Option::as_ref);
+        let name = match &source.process_name {
+            Some(process) => column![text(&source.name).size(12), text(process).size(10)],
+            None => column![text(&source.name).size(12)],
+        };
+        row![icon_view(icon, Self::ICON_SIZE), name].spacing(5).align_y(Vertical::Center).into()
+    }
+
+    fn section<'a>(&'a self, title: &'a str, kinds: &[SourceKind]) -> Option<Element<'a, Message>> {
+        let sources = self.sources.as_ref()?;
+        let tiles: Vec<_> = sources
+            .iter()
+            .filter(|source| kinds.contains(&source.kind))
+            .map(|source| self.source_tile(source))
+            .collect();
+        if tiles.is_empty() {
+            return None;
+        }
+
+        Some(column![text(title).size(20), row(tiles).spacing(10).wrap()].spacing(10).into())
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let header = row![
+            text("Choose what to share").size(24).width(Length::Fill),
+            button("Refresh")
+                .on_press(Message::Call(CallMessage::SourcePicker(SourcePickerMessage::Refresh))),
+            // For anything the enumeration misses.
+            button("System Picker").on_press(Message::Call(CallMessage::OpenSystemPicker)),
+            button("Cancel")
+                .style(button::danger)
+                .on_press(Message::Call(CallMessage::CloseSourcePicker)),
+        ]
+        .spacing(10);
+
+        let body: Element<'_, Message> = match (&self.sources, &self.error) {
+            (None, _) => container(text("Looking for screens and windows...")).into(),
+            (Some(_), Some(error)) => {
+                container(text(format!("Failed to list screens and windows: {}", error))).into()
+            }
+            (Some(_), None) => scrollable(
+                column![]
+                    .push(self.section("Screens", &[SourceKind::Monitor, SourceKind::Region]))
+                    .push(self.section("Windows", &[SourceKind::Window]))
+                    .spacing(20),
+            )
+            .into(),
+        };
+
+        container(column![header, body].spacing(20))
+            .padding(20)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(container::bordered_box)
+            .into()
+    }
+}
@@ -1,34 +1,187 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
-use bytes::Bytes;
-use tokio::sync::{Mutex, mpsc};
+use fjarsyn_shared::CallMode;
+use tokio::sync::RwLock;
 
 use crate::{
+    capture_providers::PlatformCaptureProvider,
     config::Config,
-    networking::webrtc::{WebRTC, WebRTCEvent},
+    networking::webrtc::{TrackId, WebRTC},
+    session::CallChannels,
     ui::{
-        app::{ActiveScreen, PacketReceiverRef},
-        notification_provider::NotificationProvider,
+        app::ActiveScreen, call_history::CallHistory, call_summary::CallSummary,
+        consent::ConsentStore, native_notifications::NativeNotifier,
+        notification_provider::NotificationProvider, reconnect::Reconnect,
     },
+    utils::metrics::Metrics,
 };
 
+#[derive(Debug, Clone)]
+pub enum CaptureProviderState {
+    /// Not needed yet. It is created on the first share or capture test, as viewing never needs it.
+    NotStarted,
+    /// Still being created in the background.
+    Pending,
+    Ready(Arc<RwLock<PlatformCaptureProvider>>),
+    /// Creation failed, so screen sharing is unavailable (degraded mode).
+    Unavailable,
+}
+
+impl CaptureProviderState {
+    pub fn provider(&self) -> Option<&Arc<RwLock<PlatformCaptureProvider>>> {
+        match self {
+            CaptureProviderState::Ready(capture) => Some(capture),
+            _ => None,
+        }
+    }
+}
+
+/// Where the config, the call history and the consents are saved.
+/// In memory, nothing is written, so tests don't touch the user's files.
+#[derive(Debug)]
+pub struct ConfigStore {
+    in_memory: bool,
+    // How often something was saved, for tests to check.
+    saves: AtomicUsize,
+}
+
+impl ConfigStore {
+    /// Saves next to the user's config.
+    pub fn user() -> Self {
+        Self { in_memory: false, saves: AtomicUsize::new(0) }
+    }
+
+    pub fn in_memory() -> Self {
+        Self { in_memory: true, saves: AtomicUsize::new(0) }
+    }
+
+    pub fn save_config(&self, config: &Config) -> std::io::Result<()> {
+        self.save(|| config.save())
+    }
+
+    pub fn save_call_history(&self, call_history: &CallHistory) -> std::io::Result<()> {
+        self.save(|| call_history.save())
+    }
+
+    pub fn save_consents(&self, consents: &ConsentStore) -> std::io::Result<()> {
+        self.save(|| consents.save())
+    }
+
+    fn save(&self, save: impl FnOnce() -> std::io::Result<()>) -> std::io::Result<()> {
+        self.saves.fetch_add(1, Ordering::Relaxed);
+        if self.in_memory { Ok(()) } else { save() }
+    }
+
+    pub fn saves(&self) -> usize {
+        self.saves.load(Ordering::Relaxed)
+    }
+}
+
+/// The connection to the signaling server and the peer, and what goes with it.
+#[derive(Debug, Default)]
+pub struct CallServices {
+    pub webrtc: Option<WebRTC>,
+    // The receivers of the current connection, which are replaced along with it.
+    pub channels: Option<CallChannels>,
+    // The codecs of the remote video tracks that started and haven't ended yet.
+    pub remote_tracks: BTreeMap<TrackId, String>,
+    // Set while connecting to the signaling server, so overlapping attempts don't create two connections.
+    pub connecting: bool,
+    // Set while calling ourselves instead of being connected to the signaling server, which is connected to again after.
+    pub loopback: bool,
+    pub reconnect: Reconnect,
+    pub target_id: Option<String>,
+    // Seals the signaling of the calls if not empty, which the peer has to enter as well.
+    pub signaling_passphrase: String,
+    // Seals the signaling of the calls with a key agreed with the peer instead, while there is no passphrase.
+    pub signaling_key_exchange: bool,
+    // What our end does in the calls made or answered from now on.
+    pub mode: CallMode,
+    // What the peer does in the current call, once its offer or answer said.
+    pub peer_mode: Option<CallMode>,
+    // The bits per second sent while sharing, for the settings to show against the bandwidth cap.
+    pub send_bitrate: Option<f64>,
+    // Who a `fjarsyn://` link asked to call, which waits on the home screen for the user to confirm it.
+    pub call_link: Option<String>,
+    // Who is calling us, until the user accepts or declines it.
+    pub incoming_call: Option<String>,
+}
+
+impl CallServices {
+    /// Fills in the peer of the link to call, and asks before calling it, as anyone can send a link.
+    pub fn open_call_link(&mut self, peer: String) {
+        self.target_id = Some(peer.clone());
+        self.call_link = Some(peer);
+    }
+}
+
+/// The windows of the app besides the screens, by their iced ids.
+#[derive(Debug, Default)]
+pub struct WindowHandles {
+    // The raw handle of the main window, for what has to go through the OS.
+    pub main_handle: Option<u64>,
+    pub main_id: Option<iced::window::Id>,
+    // The window the remote video is popped out into, if any.
+    pub popout_id: Option<iced::window::Id>,
+    pub popout_handle: Option<u64>,
+    // The pill shown on top of everything while sharing, if it is open.
+    pub sharing_indicator: Option<iced::window::Id>,
+}
+
+/// Tells the user what happened, in the app and through the OS.
+pub struct Notifier {
+    pub in_app: NotificationProvider,
+    // None if the OS notifications couldn't be set up, in which case only the in-app ones are shown.
+    pub native: Option<NativeNotifier>,
+}
+
 pub struct AppContext {
     pub config: Config,
+    pub store: ConfigStore,
 
     pub back_queue: VecDeque<ActiveScreen>,
 
-    pub packet_tx: Option<mpsc::Sender<Bytes>>,
-    pub packet_rx: PacketReceiverRef,
+    pub call: CallServices,
+    pub windows: WindowHandles,
+    pub capture: CaptureProviderState,
+    pub notifier: Notifier,
 
-    pub webrtc_event_tx: Option<mpsc::Sender<WebRTCEvent>>,
-    pub webrtc_event_rx: Option<Arc<Mutex<mpsc::Receiver<WebRTCEvent>>>>,
+    pub call_history: CallHistory,
+    pub consents: ConsentStore,
+    // Shown on the home screen after a call, until dismissed.
+    pub last_call_summary: Option<CallSummary>,
 
-    pub main_window_handle: Option<u64>,
+    // Set once the main window was asked to close, and the call is being torn down.
+    pub shutting_down: bool,
 
-    pub webrtc: Option<WebRTC>,
-    pub target_id: Option<String>,
+    // What the capture and the encoder keep their stats in, for the stats overlay.
+    pub metrics: Metrics,
+}
 
-    pub notifications: NotificationProvider,
+impl AppContext {
+    /// Before connecting, capturing or opening any window, with no calls in the history and nothing consented to.
+    pub fn new(config: Config, store: ConfigStore) -> Self {
+        Self {
+            config,
+            store,
+            back_queue: VecDeque::new(),
+            call: CallServices::default(),
+            windows: WindowHandles::default(),
+            capture: CaptureProviderState::NotStarted,
+            notifier: Notifier { in_app: NotificationProvider::new(), native: None },
+            call_history: CallHistory::default(),
+            consents: ConsentStore::default(),
+            last_call_summary: None,
+            shutting_down: false,
+            metrics: Metrics::default(),
+        }
+    }
 }
 
 pub struct State {
@@ -0,0 +1,28 @@
+//! Drives the screens in tests, with a context that has none of the platform behind it.
+
+use crate::{
+    config::Config,
+    ui::{
+        app::ActiveScreen,
+        notification::NotificationKind,
+        screens::home::HomeScreen,
+        state::{AppContext, ConfigStore, State},
+    },
+};
+
+/// Not connected, capturing nothing, without windows or OS notifications, and saving nothing.
+pub fn mock_context(config: Config) -> AppContext {
+    AppContext::new(config, ConfigStore::in_memory())
+}
+
+/// The state the app boots into once onboarding is done, before it has connected.
+pub fn home_state() -> State {
+    let mut ctx = mock_context(Config { onboarding_done: true, ..Config::default() });
+    let active_screen = ActiveScreen::Home(HomeScreen::new(&mut ctx));
+    State { ctx, active_screen }
+}
+
+/// The in-app notifications shown so far, oldest first.
+pub fn notifications(ctx: &AppContext) -> Vec<(NotificationKind, String)> {
+    ctx.notifier.in_app.iter().map(|n| (n.kind, n.message.clone())).collect()
+}
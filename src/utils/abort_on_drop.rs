@@ -0,0 +1,12 @@
+use tokio::task::AbortHandle;
+
+/// Aborts the task once dropped. Wrap it in an `Arc` to abort once the last clone is dropped,
+/// so a task can't outlive whatever owns it.
+#[derive(Debug)]
+pub struct AbortOnDrop(pub AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
@@ -0,0 +1,28 @@
+use windows_core::{AgileReference, Interface, Result};
+
+/// A thread-safe handle to a COM interface.
+///
+/// Unlike wrapping the interface itself and asserting `Send`/`Sync`, this stores an agile reference
+/// (`RoGetAgileReference`) and resolves it into a proxy that is valid on the calling thread's apartment.
+/// This way non-agile interfaces are marshalled correctly instead of being used from the wrong apartment.
+#[derive(Clone)]
+pub struct AgileRef<T: Interface> {
+    reference: AgileReference<T>,
+}
+
+impl<T: Interface> AgileRef<T> {
+    pub fn new(object: &T) -> Result<Self> {
+        Ok(Self { reference: AgileReference::new(object)? })
+    }
+
+    /// Resolves the reference into an interface that can be used on the current thread.
+    pub fn resolve(&self) -> Result<T> {
+        self.reference.resolve()
+    }
+}
+
+impl<T: Interface> std::fmt::Debug for AgileRef<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AgileRef").field("interface", &std::any::type_name::<T>()).finish()
+    }
+}
@@ -0,0 +1,154 @@
+//! JSON files that a crash or power loss mid-write can't leave broken. The new content is written to a
+//! temporary file next to the original and renamed over it once it is on disk, and the previous content is kept
+//! as a backup. Reading falls back to either copy if the file itself is broken.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AtomicFileError {
+    #[error("Failed to read {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("Failed to parse {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+}
+
+/// What was read from a file saved with [`save_json`].
+#[derive(Debug)]
+pub enum Loaded<T> {
+    /// Neither the file nor any copy of it exist, as on the first run.
+    Missing,
+    Intact(T),
+    /// The file was broken or missing, so it was read from a copy, and restored from it.
+    Recovered {
+        value: T,
+        from: PathBuf,
+    },
+    /// Neither the file nor any copy of it could be read. The broken file is moved aside, to its `.corrupt` path.
+    Broken(AtomicFileError),
+}
+
+impl<T> Loaded<T> {
+    /// The value, the default if there is none, and the copy it was recovered from if it was.
+    pub fn into_parts(self, name: &str) -> (T, Option<PathBuf>)
+    where
+        T: Default,
+    {
+        match self {
+            Self::Missing => (T::default(), None),
+            Self::Intact(value) => (value, None),
+            Self::Recovered { value, from } => (value, Some(from)),
+            Self::Broken(e) => {
+                tracing::error!("Failed to load the {}, starting over: {}", name, e);
+                (T::default(), None)
+            }
+        }
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Where the new content is written before it replaces the file.
+pub fn temp_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".tmp")
+}
+
+/// Where the content before the last save is kept.
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// Where a file that couldn't be read, nor recovered, is moved to.
+pub fn corrupt_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".corrupt")
+}
+
+// Whether the content is whole, as a truncated file isn't valid JSON.
+fn is_whole(content: &[u8]) -> bool {
+    serde_json::from_slice::<serde::de::IgnoredAny>(content).is_ok()
+}
+
+/// Writes the content in place of the file, so that it has either all of the old or all of the new content
+/// whenever the write is cut short.
+pub fn write(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp = temp_path(path);
+    let mut file = File::create(&temp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+
+    // Only a whole file is worth keeping, or a broken one could replace the good backup.
+    if fs::read(path).is_ok_and(|previous| is_whole(&previous)) {
+        fs::copy(path, backup_path(path))?;
+    }
+    fs::rename(&temp, path)?;
+
+    // The rename only lasts once the directory is on disk too. Windows can't open a directory to sync it.
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Saves the value as pretty JSON, through [`write`].
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    write(path, serde_json::to_string_pretty(value)?.as_bytes())
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, AtomicFileError> {
+    let content = fs::read(path).map_err(|e| AtomicFileError::Read(path.to_owned(), e))?;
+    serde_json::from_slice(&content).map_err(|e| AtomicFileError::Parse(path.to_owned(), e))
+}
+
+/// Reads a file saved with [`save_json`]. If it is broken, it is recovered from the file a save was cut short
+/// writing, which is the newer, or else from the backup.
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Loaded<T> {
+    let error = match read_json(path) {
+        Ok(value) => return Loaded::Intact(value),
+        Err(AtomicFileError::Read(_, e)) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => Some(e),
+    };
+    if let Some(e) = &error {
+        tracing::warn!("{}, trying to recover it", e);
+    }
+
+    for copy in [temp_path(path), backup_path(path)] {
+        if !copy.exists() {
+            continue;
+        }
+        match read_json(&copy) {
+            Ok(value) => {
+                tracing::warn!("Recovered {} from {}", path.display(), copy.display());
+                if let Err(e) = fs::read(&copy).and_then(|content| write(path, &content)) {
+                    tracing::error!("Failed to restore {}: {}", path.display(), e);
+                }
+                return Loaded::Recovered { value, from: copy };
+            }
+            Err(e) => tracing::warn!("Can't recover from {}", e),
+        }
+    }
+
+    let Some(error) = error else {
+        return Loaded::Missing;
+    };
+    // Kept for someone to look at, rather than overwritten by the next save.
+    if let Err(e) = fs::rename(path, corrupt_path(path)) {
+        tracing::warn!("Failed to move aside {}: {}", path.display(), e);
+    }
+    Loaded::Broken(error)
+}
@@ -1,15 +1,105 @@
-use crate::utils::pixel_format::PixelFormat;
+use std::{fmt::Display, sync::OnceLock};
 
+use bytes::BytesMut;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    orientation::Orientation, pixel_format::PixelFormat, rect::Rect, vector2::Vector2,
+};
+
+/// Converts the bitmap to RGBA8 in place. RGBA16 is tone-mapped, and the bitmap shrinks to half its length.
 #[inline]
-pub fn ensure_rgba(bitmap: &mut [u8], src_format: &mut PixelFormat) {
+pub fn ensure_rgba(bitmap: &mut BytesMut, src_format: &mut PixelFormat) {
     match src_format {
-        PixelFormat::RGBA16 => (),
+        PixelFormat::RGBA16 => {
+            let len = rgba16f_to_rgba8(bitmap).len();
+            bitmap.truncate(len);
+        }
         PixelFormat::RGBA8 => (),
         PixelFormat::BGRA8 => bgra8_to_rgba8(bitmap),
     };
     *src_format = PixelFormat::RGBA8;
 }
 
+// Where the tone curve starts to bend. Anything darker is only encoded as sRGB, so SDR content keeps its shadows
+// and midtones.
+const TONE_MAP_KNEE: f32 = 0.75;
+// The brightest value that is told apart, 1000 nits in scRGB. Anything brighter is white.
+const TONE_MAP_PEAK: f32 = 12.5;
+
+/// Tone-maps half float RGBA in linear scRGB, which displays in HDR mode are captured in, to RGBA8 in sRGB.
+/// Works in place, with the converted pixels taking the first half of the bitmap, which is returned.
+/// Negative values, which scRGB uses for colors outside of sRGB, are clamped.
+pub fn rgba16f_to_rgba8(bitmap: &mut [u8]) -> &mut [u8] {
+    let (color, alpha) = (color_lut(), alpha_lut());
+    let pixels = bitmap.len() / 8;
+    for i in 0..pixels {
+        // A pixel is read whole before it is written, and is never written past the start of the next one.
+        let channel =
+            |c: usize| u16::from_le_bytes([bitmap[i * 8 + c * 2], bitmap[i * 8 + c * 2 + 1]]);
+        let pixel = [
+            color[channel(0) as usize],
+            color[channel(1) as usize],
+            color[channel(2) as usize],
+            alpha[channel(3) as usize],
+        ];
+        bitmap[i * 4..i * 4 + 4].copy_from_slice(&pixel);
+    }
+    &mut bitmap[..pixels * 4]
+}
+
+// Every half float there is, tone-mapped and encoded as sRGB.
+fn color_lut() -> &'static [u8] {
+    static LUT: OnceLock<Box<[u8]>> = OnceLock::new();
+    LUT.get_or_init(|| {
+        (0..=u16::MAX).map(|bits| to_u8(linear_to_srgb(tone_map(f16_to_f32(bits))))).collect()
+    })
+}
+
+// Alpha is neither tone-mapped nor encoded, only clamped.
+fn alpha_lut() -> &'static [u8] {
+    static LUT: OnceLock<Box<[u8]>> = OnceLock::new();
+    LUT.get_or_init(|| (0..=u16::MAX).map(|bits| to_u8(f16_to_f32(bits))).collect())
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+// Leaves everything up to the knee alone, and rolls off above it so the peak reaches 1.
+// An extended Reinhard curve over what is above the knee, whose slope is 1 where it starts.
+fn tone_map(linear: f32) -> f32 {
+    if linear.is_nan() || linear <= 0.0 {
+        return 0.0;
+    }
+    if linear <= TONE_MAP_KNEE {
+        return linear;
+    }
+    if linear >= TONE_MAP_PEAK {
+        return 1.0;
+    }
+    let range = 1.0 - TONE_MAP_KNEE;
+    let over = (linear - TONE_MAP_KNEE) / range;
+    let peak = (TONE_MAP_PEAK - TONE_MAP_KNEE) / range;
+    TONE_MAP_KNEE + range * over * (1.0 + over / (peak * peak)) / (1.0 + over)
+}
+
+fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 { linear * 12.92 } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn to_u8(value: f32) -> u8 {
+    if value.is_nan() { 0 } else { (value.clamp(0.0, 1.0) * 255.0).round() as u8 }
+}
+
 //TODO: SIMD
 #[inline]
 fn swap_first_channel(bitmap: &mut [u8]) {
@@ -43,3 +133,239 @@ fn swap_first_channel(bitmap: &mut [u8]) {
 pub fn bgra8_to_rgba8(bgra8: &mut [u8]) {
     swap_first_channel(bgra8);
 }
+
+/// The largest size with the aspect ratio of `size` that fits within `max_size`. Never scales up.
+pub fn fit_size(size: Vector2<i32>, max_size: Vector2<i32>) -> Vector2<i32> {
+    if size.x <= max_size.x && size.y <= max_size.y {
+        return size;
+    }
+
+    let scale = (max_size.x as f32 / size.x as f32).min(max_size.y as f32 / size.y as f32);
+    Vector2::new(((size.x as f32 * scale) as i32).max(1), ((size.y as f32 * scale) as i32).max(1))
+}
+
+/// Resizes a tightly packed bitmap with nearest neighbour sampling.
+/// Cheap, and good enough for previews.
+pub fn resize_nearest(
+    src: &[u8],
+    src_size: Vector2<i32>,
+    dst: &mut [u8],
+    dst_size: Vector2<i32>,
+    bytes_per_pixel: usize,
+) {
+    let src_row_bytes = src_size.x as usize * bytes_per_pixel;
+    let dst_row_bytes = dst_size.x as usize * bytes_per_pixel;
+
+    for y in 0..dst_size.y as usize {
+        let src_y = y * src_size.y as usize / dst_size.y as usize;
+        let src_row = &src[src_y * src_row_bytes..][..src_row_bytes];
+        let dst_row = &mut dst[y * dst_row_bytes..][..dst_row_bytes];
+
+        for x in 0..dst_size.x as usize {
+            let src_x = x * src_size.x as usize / dst_size.x as usize;
+            dst_row[x * bytes_per_pixel..][..bytes_per_pixel]
+                .copy_from_slice(&src_row[src_x * bytes_per_pixel..][..bytes_per_pixel]);
+        }
+    }
+}
+
+/// Copies a tightly packed bitmap into a larger one, with its top left corner at `position`.
+/// Whatever falls outside of `dst` is left out.
+pub fn blit(
+    src: &[u8],
+    src_size: Vector2<i32>,
+    dst: &mut [u8],
+    dst_size: Vector2<i32>,
+    position: Vector2<i32>,
+    bytes_per_pixel: usize,
+) {
+    let Some(visible) = Rect::new(position.x, position.y, src_size.x, src_size.y)
+        .intersection(&Rect::new(0, 0, dst_size.x, dst_size.y))
+    else {
+        return;
+    };
+
+    let src_row_bytes = src_size.x as usize * bytes_per_pixel;
+    let dst_row_bytes = dst_size.x as usize * bytes_per_pixel;
+    let row_bytes = visible.size.x as usize * bytes_per_pixel;
+    let src_x = (visible.position.x - position.x) as usize * bytes_per_pixel;
+    let dst_x = visible.position.x as usize * bytes_per_pixel;
+
+    for y in visible.position.y..visible.bottom() {
+        let src_row = &src[(y - position.y) as usize * src_row_bytes + src_x..][..row_bytes];
+        dst[y as usize * dst_row_bytes + dst_x..][..row_bytes].copy_from_slice(src_row);
+    }
+}
+
+/// Copies a bitmap of `size` whose rows are `src_pitch` bytes apart into a tightly packed one, rotated by
+/// `orientation`. The rows of the source are read in order, so it can be mapped memory.
+pub fn rotate(
+    src: &[u8],
+    src_pitch: usize,
+    size: Vector2<i32>,
+    dst: &mut [u8],
+    orientation: Orientation,
+    bytes_per_pixel: usize,
+) {
+    let row_bytes = size.x as usize * bytes_per_pixel;
+    let dst_row_bytes = orientation.rotated_size(size).x as usize * bytes_per_pixel;
+    for y in 0..size.y {
+        let src_row = &src[y as usize * src_pitch..][..row_bytes];
+        if orientation == Orientation::Upright {
+            dst[y as usize * row_bytes..][..row_bytes].copy_from_slice(src_row);
+            continue;
+        }
+        for (x, pixel) in src_row.chunks_exact(bytes_per_pixel).enumerate() {
+            let to = orientation.rotate_point(Vector2::new(x as i32, y), size);
+            let offset = to.y as usize * dst_row_bytes + to.x as usize * bytes_per_pixel;
+            dst[offset..][..bytes_per_pixel].copy_from_slice(pixel);
+        }
+    }
+}
+
+/// Encodes a tightly packed RGBA8 bitmap of `size` as a JPEG of the quality, from 1 to 100. The alpha is left out,
+/// as JPEGs have none.
+pub fn encode_jpeg(rgba: &[u8], size: Vector2<i32>, quality: u8) -> image::ImageResult<Vec<u8>> {
+    let rgb: Vec<u8> =
+        rgba.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality).encode(
+        &rgb,
+        size.x as u32,
+        size.y as u32,
+        image::ExtendedColorType::Rgb8,
+    )?;
+    Ok(jpeg)
+}
+
+/// Blends a filled circle of the color into an RGBA8 bitmap of `size`, with its edge antialiased. The center and radius
+/// are in pixels, the opacity is from 0 to 1, and whatever of the circle is outside of the bitmap is left out.
+/// The alpha channel is left as it is.
+pub fn blend_circle(
+    bitmap: &mut [u8],
+    size: Vector2<i32>,
+    center: Vector2<f32>,
+    radius: f32,
+    color: [u8; 3],
+    opacity: f32,
+) {
+    let opacity = opacity.min(1.0);
+    if radius <= 0.0 || opacity <= 0.0 {
+        return;
+    }
+
+    // The pixels the circle and its antialiased edge reach.
+    let reach = radius + 0.5;
+    let (left, right) = ((center.x - reach).floor().max(0.0), (center.x + reach).ceil());
+    let (top, bottom) = ((center.y - reach).floor().max(0.0), (center.y + reach).ceil());
+    let (right, bottom) = (right.min(size.x as f32), bottom.min(size.y as f32));
+    let row_bytes = size.x as usize * 4;
+    for y in top as i32..bottom as i32 {
+        for x in left as i32..right as i32 {
+            // From the center of the pixel, which is covered in full a pixel in from the edge and not at all outside of it.
+            let (dx, dy) = (x as f32 + 0.5 - center.x, y as f32 + 0.5 - center.y);
+            let coverage = (reach - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0);
+            if coverage == 0.0 {
+                continue;
+            }
+            let alpha = opacity * coverage;
+            let pixel = &mut bitmap[y as usize * row_bytes + x as usize * 4..][..3];
+            for (channel, &target) in pixel.iter_mut().zip(&color) {
+                let blended = *channel as f32 + (target as f32 - *channel as f32) * alpha;
+                *channel = blended.round() as u8;
+            }
+        }
+    }
+}
+
+/// How the redacted regions of a frame are hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum RedactionStyle {
+    /// Filled with black, which leaves nothing of what was there.
+    #[default]
+    Fill,
+    /// Blurred until text can't be read, which still shows that something is there.
+    Blur,
+}
+
+impl RedactionStyle {
+    pub const ALL: &[RedactionStyle] = &[RedactionStyle::Fill, RedactionStyle::Blur];
+}
+
+impl Display for RedactionStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fill => f.write_str("Fill"),
+            Self::Blur => f.write_str("Blur"),
+        }
+    }
+}
+
+// Three passes of a box blur come close to a gaussian one.
+const BLUR_RADIUS: i32 = 12;
+const BLUR_PASSES: usize = 3;
+
+/// Hides the regions of an RGBA8 or BGRA8 bitmap of `size`. The regions are normalized to the bitmap, so they cover the same
+/// part of it at any resolution, and are rounded outwards to whole pixels. Nothing outside of them is read or written.
+pub fn redact(bitmap: &mut [u8], size: Vector2<i32>, regions: &[Rect<f32>], style: RedactionStyle) {
+    let row_bytes = size.x as usize * 4;
+    for region in regions.iter().filter_map(|region| region.to_pixels(size)) {
+        match style {
+            RedactionStyle::Fill => {
+                for y in region.position.y..region.bottom() {
+                    let row = &mut bitmap[y as usize * row_bytes..][..row_bytes];
+                    for pixel in row[region.position.x as usize * 4..region.right() as usize * 4]
+                        .chunks_exact_mut(4)
+                    {
+                        pixel.copy_from_slice(&[0, 0, 0, 255]);
+                    }
+                }
+            }
+            RedactionStyle::Blur => {
+                for _ in 0..BLUR_PASSES {
+                    box_blur(bitmap, row_bytes, region, true);
+                    box_blur(bitmap, row_bytes, region, false);
+                }
+            }
+        }
+    }
+}
+
+// One pass of a box blur along the rows or the columns of the region. Only the region itself is sampled, with the
+// pixels on its edges repeated past it.
+fn box_blur(bitmap: &mut [u8], row_bytes: usize, region: Rect<i32>, along_rows: bool) {
+    let (lines, len) =
+        if along_rows { (region.size.y, region.size.x) } else { (region.size.x, region.size.y) };
+    let offset = |line: i32, i: i32| {
+        let (x, y) = if along_rows { (i, line) } else { (line, i) };
+        (region.position.y + y) as usize * row_bytes + (region.position.x + x) as usize * 4
+    };
+    let window = (2 * BLUR_RADIUS + 1) as u32;
+
+    let mut pixels = Vec::with_capacity(len as usize);
+    for line in 0..lines {
+        pixels.clear();
+        pixels.extend((0..len).map(|i| {
+            let offset = offset(line, i);
+            [bitmap[offset], bitmap[offset + 1], bitmap[offset + 2], bitmap[offset + 3]]
+        }));
+        let at = |i: i32| pixels[i.clamp(0, len - 1) as usize];
+
+        let mut sum = [0u32; 4];
+        for i in -BLUR_RADIUS..=BLUR_RADIUS {
+            for (sum, channel) in sum.iter_mut().zip(at(i)) {
+                *sum += channel as u32;
+            }
+        }
+        for i in 0..len {
+            let offset = offset(line, i);
+            for (c, sum) in sum.iter().enumerate() {
+                bitmap[offset + c] = ((sum + window / 2) / window) as u8;
+            }
+            let (leaving, entering) = (at(i - BLUR_RADIUS), at(i + BLUR_RADIUS + 1));
+            for c in 0..4 {
+                sum[c] = sum[c] + entering[c] as u32 - leaving[c] as u32;
+            }
+        }
+    }
+}
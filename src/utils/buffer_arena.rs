@@ -60,6 +60,11 @@ impl BufferRef {
         BufferRef { data, data_taken: false, parent_buffer }
     }
 
+    /// Creates a buffer that doesn't belong to an arena, so it is simply freed when dropped.
+    pub fn detached(data: BytesMut) -> Self {
+        BufferRef::new(data, std::sync::Weak::new())
+    }
+
     /// Freezes the underlying buffer into a `Bytes` object.
     /// This is zero-copy and makes the memory immutable.
     pub fn freeze(mut self) -> bytes::Bytes {
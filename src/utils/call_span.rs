@@ -0,0 +1,25 @@
+use std::fmt::Display;
+
+use tracing::Span;
+
+/// Which side of the call started it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallDirection {
+    Incoming,
+    Outgoing,
+}
+
+impl Display for CallDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Incoming => write!(f, "incoming"),
+            Self::Outgoing => write!(f, "outgoing"),
+        }
+    }
+}
+
+/// The span everything done for a call runs in, so the logs of concurrent calls can be told apart.
+/// Tasks spawned for the call have to be instrumented with it, as spans don't carry over into new tasks by themselves.
+pub fn call_span(peer_id: &str, direction: CallDirection) -> Span {
+    tracing::info_span!("call", peer = %peer_id, direction = %direction)
+}
@@ -0,0 +1,71 @@
+use std::{
+    hash::{BuildHasher, Hasher},
+    time::{Duration, Instant},
+};
+
+use foldhash::fast::FixedState;
+
+use crate::utils::vector2::Vector2;
+
+/// The most bytes of a frame that are hashed. Hashing runs at several GB/s, so this takes well under a millisecond.
+pub const SAMPLE_BUDGET: usize = 2 * 1024 * 1024;
+
+/// Every how many rows of a bitmap are hashed, so no more than the budget is, whatever its resolution.
+pub fn row_stride(size: Vector2<i32>, bytes_per_pixel: usize) -> usize {
+    let bytes = size.x.max(0) as usize * size.y.max(0) as usize * bytes_per_pixel;
+    bytes.div_ceil(SAMPLE_BUDGET).max(1)
+}
+
+/// A hash of every [`row_stride`]th row of a tightly packed bitmap, starting with the first. It is the same for
+/// bitmaps that look the same, and almost surely differs for ones that differ in a sampled row.
+pub fn sampled_hash(data: &[u8], size: Vector2<i32>, bytes_per_pixel: usize) -> u64 {
+    let mut hasher = FixedState::default().build_hasher();
+    hasher.write_i32(size.x);
+    hasher.write_i32(size.y);
+    let row_bytes = size.x.max(0) as usize * bytes_per_pixel;
+    if row_bytes == 0 {
+        return hasher.finish();
+    }
+    for row in data.chunks_exact(row_bytes).step_by(row_stride(size, bytes_per_pixel)) {
+        hasher.write(row);
+    }
+    hasher.finish()
+}
+
+/// Tells frames that look the same as the last one encoded apart, as encoding them again only sends the same picture.
+/// Some apps redraw constantly, and the capture reports the redrawn regions as dirty even when nothing changed.
+#[derive(Debug, Default)]
+pub struct UnchangedFrames {
+    // The hash of the last frame encoded, and when it was.
+    last: Option<(u64, Instant)>,
+    skipped: u64,
+}
+
+impl UnchangedFrames {
+    /// A frame is encoded at least this often anyway, in case two different ones hash the same, or differ only in
+    /// rows that aren't sampled.
+    pub const MAX_SKIP: Duration = Duration::from_secs(1);
+
+    /// Whether the frame with this hash can be skipped. Frames that aren't are taken to be encoded.
+    pub fn skip(&mut self, hash: u64, now: Instant) -> bool {
+        if let Some((last, encoded_at)) = self.last
+            && last == hash
+            && now.saturating_duration_since(encoded_at) < Self::MAX_SKIP
+        {
+            self.skipped += 1;
+            return true;
+        }
+        self.last = Some((hash, now));
+        false
+    }
+
+    /// Makes sure the next frame is encoded, e.g. as it has to be a keyframe.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// The frames skipped so far.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
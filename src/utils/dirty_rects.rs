@@ -0,0 +1,36 @@
+use crate::utils::{rect::Rect, vector2::Vector2};
+
+/// The dirty rects a frame carries at most. Busier frames carry one rect around all of them instead.
+pub const MAX_DIRTY_RECTS: usize = 16;
+
+/// Merges the rects that overlap or touch, and drops the empty ones.
+/// The result covers every rect given, and none of its rects overlap.
+/// If that is still more than `max` rects, returns the one rect around them all.
+pub fn coalesce(rects: impl IntoIterator<Item = Rect<i32>>, max: usize) -> Vec<Rect<i32>> {
+    let mut merged: Vec<Rect<i32>> = Vec::new();
+    for rect in rects.into_iter().filter(|rect| !rect.is_empty()) {
+        // Growing the rect can make it touch ones it didn't before, so it is merged until it touches none.
+        let mut rect = rect;
+        while let Some(i) = merged.iter().position(|other| other.touches(&rect)) {
+            rect = rect.union(&merged.swap_remove(i));
+        }
+        merged.push(rect);
+    }
+
+    if merged.len() > max {
+        let bounds = merged.iter().skip(1).fold(merged[0], |bounds, rect| bounds.union(rect));
+        merged.clear();
+        merged.push(bounds);
+    }
+    merged
+}
+
+/// The part of the frame the rects cover, from 0 to 1. Only exact for rects that don't overlap, like the coalesced ones.
+pub fn area_fraction(rects: &[Rect<i32>], size: Vector2<i32>) -> f32 {
+    let frame_area = size.x as f64 * size.y as f64;
+    if frame_area <= 0.0 {
+        return 0.0;
+    }
+    let dirty_area: i64 = rects.iter().map(Rect::area).sum();
+    (dirty_area as f64 / frame_area).min(1.0) as f32
+}
@@ -0,0 +1,220 @@
+use windows::Win32::{
+    Foundation::HWND,
+    Graphics::Gdi::HMONITOR,
+    UI::HiDpi::{
+        DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+        DPI_AWARENESS_PER_MONITOR_AWARE, DPI_AWARENESS_SYSTEM_AWARE,
+        GetAwarenessFromDpiAwarenessContext, GetDpiForMonitor, GetDpiForSystem, GetDpiForWindow,
+        GetThreadDpiAwarenessContext, MDT_EFFECTIVE_DPI, SetThreadDpiAwarenessContext,
+    },
+};
+
+use crate::{
+    capture_providers::shared::MonitorGeometry,
+    utils::{rect::Rect, vector2::Vector2},
+};
+
+/// What Windows considers unscaled.
+pub const UNSCALED_DPI: u32 = 96;
+
+pub fn scale_factor(dpi: u32) -> f32 {
+    dpi as f32 / UNSCALED_DPI as f32
+}
+
+/// The coordinates the system hands out, e.g. for the cursor or a window, which depend on how DPI aware the thread is.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScreenSpace {
+    /// Per monitor aware, so in physical pixels like the captured content.
+    #[default]
+    Physical,
+    /// Scaled for the scale factor given, the system's if system aware or 1 if not aware at all.
+    /// Each monitor keeps its top left corner, and its content is scaled by its own scale factor over this one.
+    Scaled(f32),
+}
+
+/// The monitors, and the coordinates the system hands out for them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DisplayLayout {
+    /// In physical pixels.
+    pub monitors: Vec<MonitorGeometry>,
+    pub space: ScreenSpace,
+}
+
+impl DisplayLayout {
+    pub fn new(monitors: Vec<MonitorGeometry>, space: ScreenSpace) -> Self {
+        Self { monitors, space }
+    }
+
+    // How many physical pixels a screen coordinate is on the monitor.
+    fn pixels_per_point(&self, monitor: &MonitorGeometry) -> f32 {
+        match self.space {
+            ScreenSpace::Physical => 1.0,
+            ScreenSpace::Scaled(scale) => monitor.scale_factor / scale.max(f32::EPSILON),
+        }
+    }
+
+    fn screen_bounds(&self, monitor: &MonitorGeometry) -> Rect<i32> {
+        let pixels_per_point = self.pixels_per_point(monitor);
+        let size = monitor.bounds.size;
+        Rect {
+            position: monitor.bounds.position,
+            size: Vector2::new(
+                (size.x as f32 / pixels_per_point).round() as i32,
+                (size.y as f32 / pixels_per_point).round() as i32,
+            ),
+        }
+    }
+
+    fn monitor_at(
+        &self,
+        point: Vector2<i32>,
+        bounds: impl Fn(&MonitorGeometry) -> Rect<i32>,
+    ) -> Option<&MonitorGeometry> {
+        self.monitors.iter().find(|monitor| {
+            bounds(monitor).contains(&Rect { position: point, size: Vector2::new(1, 1) })
+        })
+    }
+
+    /// The point in screen coordinates, in physical pixels. Points on no monitor are left as they are.
+    pub fn to_physical(&self, point: Vector2<i32>) -> Vector2<i32> {
+        match self.monitor_at(point, |monitor| self.screen_bounds(monitor)) {
+            Some(monitor) => {
+                scale_from(point, monitor.bounds.position, self.pixels_per_point(monitor))
+            }
+            None => point,
+        }
+    }
+
+    /// The point in physical pixels, in screen coordinates. Points on no monitor are left as they are.
+    pub fn to_screen(&self, point: Vector2<i32>) -> Vector2<i32> {
+        match self.monitor_at(point, |monitor| monitor.bounds) {
+            Some(monitor) => {
+                scale_from(point, monitor.bounds.position, 1.0 / self.pixels_per_point(monitor))
+            }
+            None => point,
+        }
+    }
+
+    /// The rect in screen coordinates, in physical pixels, scaled for the monitor most of it is on.
+    /// A rect on no monitor is left as it is.
+    pub fn rect_to_physical(&self, rect: Rect<i32>) -> Rect<i32> {
+        let Some(monitor) = self.monitors.iter().max_by_key(|monitor| {
+            self.screen_bounds(monitor).intersection(&rect).map_or(0, |overlap| overlap.area())
+        }) else {
+            return rect;
+        };
+        if self.screen_bounds(monitor).intersection(&rect).is_none() {
+            return rect;
+        }
+
+        let pixels_per_point = self.pixels_per_point(monitor);
+        Rect {
+            position: scale_from(rect.position, monitor.bounds.position, pixels_per_point),
+            size: Vector2::new(
+                (rect.size.x as f32 * pixels_per_point).round() as i32,
+                (rect.size.y as f32 * pixels_per_point).round() as i32,
+            ),
+        }
+    }
+}
+
+// Scales the point's distance from the origin, which stays put.
+fn scale_from(point: Vector2<i32>, origin: Vector2<i32>, scale: f32) -> Vector2<i32> {
+    let scale_axis =
+        |value: i32, origin: i32| origin + ((value - origin) as f32 * scale).round() as i32;
+    Vector2::new(scale_axis(point.x, origin.x), scale_axis(point.y, origin.y))
+}
+
+/// Converts between positions in the captured content, normalized to 0..1, and screen coordinates.
+/// The cursor shared with the peer, the input it sends and the regions captured all go through this,
+/// so they line up with the content whatever the scale of each monitor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureTransform {
+    // In physical pixels, like the captured content.
+    bounds: Rect<i32>,
+    layout: DisplayLayout,
+}
+
+impl CaptureTransform {
+    /// `bounds` is what is captured in screen coordinates, e.g. the rect of a window or a monitor.
+    pub fn new(bounds: Rect<i32>, layout: DisplayLayout) -> Self {
+        Self { bounds: layout.rect_to_physical(bounds), layout }
+    }
+
+    /// What is captured, in physical pixels.
+    pub fn bounds(&self) -> Rect<i32> {
+        self.bounds
+    }
+
+    /// Where the point in screen coordinates is in the captured content, or None if it is outside of it.
+    pub fn to_capture(&self, point: Vector2<i32>) -> Option<Vector2<f32>> {
+        let (width, height) = (self.bounds.size.x as f32, self.bounds.size.y as f32);
+        if width <= 0.0 || height <= 0.0 {
+            return None;
+        }
+
+        let point = self.layout.to_physical(point);
+        let x = (point.x - self.bounds.position.x) as f32 / width;
+        let y = (point.y - self.bounds.position.y) as f32 / height;
+        if !(0.0..1.0).contains(&x) || !(0.0..1.0).contains(&y) {
+            return None;
+        }
+        Some(Vector2::new(x, y))
+    }
+
+    /// Where the position in the captured content is in screen coordinates, clamped to the content.
+    pub fn to_screen(&self, position: Vector2<f32>) -> Vector2<i32> {
+        // Kept off the far edges, which belong to whatever is next to the content and may be scaled differently.
+        let axis = |position: f32, start: i32, size: i32| {
+            (start + (position.clamp(0.0, 1.0) * size as f32) as i32)
+                .min(start + size - 1)
+                .max(start)
+        };
+        let x = axis(position.x, self.bounds.position.x, self.bounds.size.x);
+        let y = axis(position.y, self.bounds.position.y, self.bounds.size.y);
+        self.layout.to_screen(Vector2::new(x, y))
+    }
+}
+
+pub fn monitor_scale(monitor: HMONITOR) -> windows_core::Result<f32> {
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) }?;
+    Ok(scale_factor(dpi_x))
+}
+
+/// The scale of the monitor the window is on, or of the system if the window isn't DPI aware. None if it is gone.
+pub fn window_scale(window: HWND) -> Option<f32> {
+    let dpi = unsafe { GetDpiForWindow(window) };
+    (dpi != 0).then(|| scale_factor(dpi))
+}
+
+/// What the coordinates the system hands this thread are in.
+pub fn screen_space() -> ScreenSpace {
+    let awareness = unsafe { GetAwarenessFromDpiAwarenessContext(GetThreadDpiAwarenessContext()) };
+    match awareness {
+        DPI_AWARENESS_PER_MONITOR_AWARE => ScreenSpace::Physical,
+        DPI_AWARENESS_SYSTEM_AWARE => {
+            ScreenSpace::Scaled(scale_factor(unsafe { GetDpiForSystem() }))
+        }
+        _ => ScreenSpace::Scaled(1.0),
+    }
+}
+
+/// Has the thread see physical pixels for as long as it is kept, whatever the process is aware of.
+#[must_use]
+pub struct PhysicalCoordinates(DPI_AWARENESS_CONTEXT);
+
+impl PhysicalCoordinates {
+    pub fn enter() -> Self {
+        Self(unsafe { SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) })
+    }
+}
+
+impl Drop for PhysicalCoordinates {
+    fn drop(&mut self) {
+        // Null if it couldn't be changed, in which case there is nothing to go back to.
+        if !self.0.is_invalid() {
+            unsafe { SetThreadDpiAwarenessContext(self.0) };
+        }
+    }
+}
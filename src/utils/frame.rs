@@ -1,7 +1,15 @@
 use std::time::Duration;
 
+use bytes::BytesMut;
+
 use crate::utils::{
-    bitmap_utils::ensure_rgba, buffer_arena::BufferRef, pixel_format::PixelFormat, rect::Rect,
+    bitmap_utils::{ensure_rgba, fit_size, resize_nearest},
+    buffer_arena::BufferRef,
+    dirty_rects,
+    gpu_frame::GpuFrame,
+    orientation::Orientation,
+    pixel_format::PixelFormat,
+    rect::Rect,
     vector2::Vector2,
 };
 
@@ -13,6 +21,18 @@ pub struct Frame {
     pub size: Vector2<i32>,
     pub duration: Option<Duration>,
     pub dirty_rects: Option<Vec<Rect<i32>>>,
+    /// The part of the frame the dirty rects cover, from 0 to 1. 1 if they aren't known, as anything may have changed.
+    pub dirty_area_fraction: f32,
+    /// Set when the frame was left on the GPU, in which case `data` is empty.
+    pub gpu: Option<GpuFrame>,
+    /// The capture's count of the frame, which the logs of every stage it passes through carry.
+    /// Zero for frames that weren't captured.
+    pub sequence: u64,
+    /// Set when the frame was captured from a display in HDR mode, and tone-mapped to fit RGBA8. What it was
+    /// tone-mapped from isn't kept, but whatever passes HDR on as it is can capture without it.
+    pub hdr: bool,
+    /// How the capture rotated the frame to turn it upright, e.g. when it arrived in the shape of a portrait display's panel.
+    pub orientation: Orientation,
 }
 
 impl Frame {
@@ -23,8 +43,9 @@ impl Frame {
         duration: Option<Duration>,
         dirty_rects: Option<Vec<Rect<i32>>>,
     ) -> Self {
+        let hdr = format == PixelFormat::RGBA16;
         ensure_rgba(&mut data, &mut format);
-        Self::new_raw(data, format, size, duration, dirty_rects)
+        Self { hdr, ..Self::new_raw(data, format, size, duration, dirty_rects) }
     }
 
     pub fn new_raw(
@@ -34,6 +55,66 @@ impl Frame {
         duration: Option<Duration>,
         dirty_rects: Option<Vec<Rect<i32>>>,
     ) -> Self {
-        Frame { data, format, size, duration, dirty_rects }
+        let dirty_area_fraction = Self::dirty_area_fraction(dirty_rects.as_deref(), size);
+        Frame {
+            data,
+            format,
+            size,
+            duration,
+            dirty_rects,
+            dirty_area_fraction,
+            gpu: None,
+            sequence: 0,
+            hdr: false,
+            orientation: Orientation::Upright,
+        }
+    }
+
+    pub fn new_gpu(
+        gpu: GpuFrame,
+        duration: Option<Duration>,
+        dirty_rects: Option<Vec<Rect<i32>>>,
+    ) -> Self {
+        Frame {
+            data: BufferRef::detached(BytesMut::new()),
+            format: gpu.format,
+            size: gpu.size,
+            duration,
+            dirty_area_fraction: Self::dirty_area_fraction(dirty_rects.as_deref(), gpu.size),
+            dirty_rects,
+            gpu: Some(gpu),
+            sequence: 0,
+            hdr: false,
+            orientation: Orientation::Upright,
+        }
+    }
+
+    fn dirty_area_fraction(dirty_rects: Option<&[Rect<i32>]>, size: Vector2<i32>) -> f32 {
+        dirty_rects.map_or(1.0, |rects| dirty_rects::area_fraction(rects, size))
+    }
+
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// A copy scaled down to fit within `max_size`, e.g. for a thumbnail that shouldn't upload the full frame.
+    /// None if it already fits, or is on the GPU.
+    pub fn downscaled(&self, max_size: Vector2<i32>) -> Option<Frame> {
+        let size = fit_size(self.size, max_size);
+        if size == self.size || self.gpu.is_some() {
+            return None;
+        }
+
+        let bytes_per_pixel = self.format.bytes_per_pixel() as usize;
+        let mut data = BytesMut::zeroed(size.x as usize * size.y as usize * bytes_per_pixel);
+        resize_nearest(&self.data, self.size, &mut data, size, bytes_per_pixel);
+        let frame =
+            Frame::new_raw(BufferRef::detached(data), self.format, size, self.duration, None);
+        Some(Frame {
+            hdr: self.hdr,
+            orientation: self.orientation,
+            ..frame.with_sequence(self.sequence)
+        })
     }
 }
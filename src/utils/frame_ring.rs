@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use bytes::BytesMut;
+
+use crate::utils::{
+    buffer_arena::BufferRef, frame::Frame, pixel_format::PixelFormat, vector2::Vector2,
+};
+
+/// A few frames of one size that are written into in turn, for a producer whose frames are held for a while,
+/// e.g. the last one shown, which would keep an arena from ever getting its memory back.
+/// A frame that is still held when its turn comes around is left alone, and a one-off frame is made instead.
+#[derive(Debug)]
+pub struct FrameRing {
+    slots: Vec<Arc<Frame>>,
+    len: usize,
+    next: usize,
+    size: Vector2<i32>,
+    format: PixelFormat,
+    overflows: u64,
+}
+
+impl FrameRing {
+    /// Allocates the frames once the first one is filled, as the size isn't known before.
+    pub fn new(len: usize) -> Self {
+        Self {
+            slots: Vec::new(),
+            len: len.max(1),
+            next: 0,
+            size: Vector2::new(0, 0),
+            format: PixelFormat::RGBA8,
+            overflows: 0,
+        }
+    }
+
+    /// Has `write` fill in the next frame, whose data may hold an older frame. A new size or format reallocates the
+    /// ring, leaving the old frames to whoever still holds them.
+    pub fn fill(
+        &mut self,
+        size: Vector2<i32>,
+        format: PixelFormat,
+        write: impl FnOnce(&mut [u8]),
+    ) -> Arc<Frame> {
+        if self.slots.is_empty() || size != self.size || format != self.format {
+            tracing::debug!("Allocating {} frames of {}x{}", self.len, size.x, size.y);
+            self.size = size;
+            self.format = format;
+            self.next = 0;
+            self.slots = (0..self.len).map(|_| Arc::new(self.frame())).collect();
+        }
+
+        let slot = &mut self.slots[self.next];
+        self.next = (self.next + 1) % self.len;
+        match Arc::get_mut(slot) {
+            Some(frame) => {
+                write(&mut frame.data);
+                slot.clone()
+            }
+            None => {
+                self.overflows += 1;
+                crate::log_throttled!(
+                    tracing::Level::DEBUG,
+                    "Every frame of the ring is still held, allocating another"
+                );
+                let mut frame = self.frame();
+                write(&mut frame.data);
+                Arc::new(frame)
+            }
+        }
+    }
+
+    /// The frames that had to be allocated because the one whose turn it was was still held.
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    fn frame(&self) -> Frame {
+        let len =
+            self.size.x as usize * self.size.y as usize * self.format.bytes_per_pixel() as usize;
+        let data = BufferRef::detached(BytesMut::zeroed(len));
+        Frame::new_raw(data, self.format, self.size, None, None)
+    }
+}
@@ -0,0 +1,113 @@
+//! Framerates as exact fractions, and the conversions to what they are handed on as: frame times, the 100ns ticks of
+//! a WinRT `TimeSpan`, and the encoder's time base. Each conversion rounds one way on purpose, so e.g. 144 fps or
+//! 23.976 fps don't drift depending on which float they went through.
+
+use std::time::Duration;
+
+/// The ticks of a WinRT `TimeSpan` in a second, which counts in 100ns.
+pub const TICKS_PER_SECOND: u64 = 10_000_000;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+// How close a rate has to be to a whole or an NTSC one to be taken for it.
+const SNAP_TOLERANCE: f64 = 0.0005;
+
+/// A framerate of `num / den` frames a second, e.g. 24000/1001 for what is called 23.976 fps. Always in lowest terms,
+/// and within what the encoder's 32 bit fractions hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Framerate {
+    num: u32,
+    den: u32,
+}
+
+impl Framerate {
+    // A millionth of a frame a second finer than that is never asked for, and keeps both terms within 32 bits.
+    const MAX_DEN: u32 = 1_000_000;
+    const MAX_HZ: f64 = 1000.0;
+    const MIN_HZ: f64 = 1.0 / 3600.0;
+
+    /// A whole number of frames a second. Zero is taken for one.
+    pub const fn whole(hz: u32) -> Self {
+        Self { num: if hz == 0 { 1 } else { hz }, den: 1 }
+    }
+
+    /// `num / den` frames a second, reduced to lowest terms. None if either is zero or too large.
+    pub fn new(num: u32, den: u32) -> Option<Self> {
+        if num == 0 || den == 0 || num > i32::MAX as u32 || den > i32::MAX as u32 {
+            return None;
+        }
+        let divisor = gcd(num, den);
+        Some(Self { num: num / divisor, den: den / divisor })
+    }
+
+    /// The rate closest to `hz`, taken for a whole or an NTSC one (N * 1000/1001) if it is within half a millihertz
+    /// of it, and to the nearest millionth of a frame a second otherwise. Clamped to between one frame an hour and
+    /// 1000 fps, and rates that aren't a number are taken for one frame a second.
+    pub fn from_hz(hz: f64) -> Self {
+        if hz.is_nan() {
+            return Self::whole(1);
+        }
+        let hz = hz.clamp(Self::MIN_HZ, Self::MAX_HZ);
+
+        let whole = hz.round();
+        if whole >= 1.0 && (hz - whole).abs() < SNAP_TOLERANCE {
+            return Self::whole(whole as u32);
+        }
+        let ntsc = (hz * 1.001).round();
+        if ntsc >= 1.0 && (hz - ntsc * 1000.0 / 1001.0).abs() < SNAP_TOLERANCE {
+            return Self::new(ntsc as u32 * 1000, 1001).expect("within the bounds");
+        }
+        let num = (hz * Self::MAX_DEN as f64).round() as u32;
+        Self::new(num.max(1), Self::MAX_DEN).expect("within the bounds")
+    }
+
+    /// The rate with frames this far apart, as [`Self::from_hz`] rounds it.
+    pub fn from_frametime(frametime: Duration) -> Self {
+        Self::from_hz(NANOS_PER_SECOND as f64 / frametime.as_nanos().max(1) as f64)
+    }
+
+    /// The rate with frames this many `TimeSpan` ticks apart, as [`Self::from_hz`] rounds it.
+    pub fn from_ticks(ticks: i64) -> Self {
+        Self::from_hz(TICKS_PER_SECOND as f64 / ticks.max(1) as f64)
+    }
+
+    pub fn num(self) -> u32 {
+        self.num
+    }
+
+    pub fn den(self) -> u32 {
+        self.den
+    }
+
+    /// The frames a second, for what only needs it roughly, e.g. stats.
+    pub fn hz(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// The time from one frame to the next, rounded to the nearest nanosecond.
+    pub fn frametime(self) -> Duration {
+        let nanos = (self.den as u64 * NANOS_PER_SECOND + self.num as u64 / 2) / self.num as u64;
+        Duration::from_nanos(nanos)
+    }
+
+    /// The time from one frame to the next in `TimeSpan` ticks, rounded down so a minimum interval between frames never
+    /// holds them back below the rate. At least one tick, as zero means no minimum.
+    pub fn frametime_ticks(self) -> i64 {
+        (self.den as u64 * TICKS_PER_SECOND / self.num as u64).max(1) as i64
+    }
+
+    /// The encoder's time base, `(num, den)` of a second, in which every frame is one tick long. Exact.
+    pub fn time_base(self) -> (i32, i32) {
+        (self.den as i32, self.num as i32)
+    }
+
+    /// The rate as the encoder takes it, `(num, den)` frames a second. Exact.
+    pub fn frame_rate(self) -> (i32, i32) {
+        (self.num as i32, self.den as i32)
+    }
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
@@ -0,0 +1,87 @@
+use std::sync::{Arc, Mutex};
+
+use windows::Win32::Graphics::Direct3D11::{D3D11_TEXTURE2D_DESC, ID3D11Device, ID3D11Texture2D};
+
+use crate::utils::{pixel_format::PixelFormat, vector2::Vector2};
+
+/// A captured frame that stays on the GPU, for encoders that can take it from there instead of from memory.
+/// The texture belongs to the same device as the capture, so commands on it are ordered after the copy that filled it.
+pub struct GpuFrame {
+    // Only None while being returned to the pool.
+    texture: Option<ID3D11Texture2D>,
+    pool: GpuTexturePool,
+    pub format: PixelFormat,
+    pub size: Vector2<i32>,
+}
+
+impl GpuFrame {
+    pub fn new(
+        texture: ID3D11Texture2D,
+        pool: GpuTexturePool,
+        format: PixelFormat,
+        size: Vector2<i32>,
+    ) -> Self {
+        Self { texture: Some(texture), pool, format, size }
+    }
+
+    pub fn texture(&self) -> &ID3D11Texture2D {
+        self.texture.as_ref().expect("GpuFrame texture taken before drop")
+    }
+}
+
+impl Drop for GpuFrame {
+    fn drop(&mut self) {
+        if let Some(texture) = self.texture.take() {
+            self.pool.give_back(texture);
+        }
+    }
+}
+
+impl std::fmt::Debug for GpuFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuFrame").field("format", &self.format).field("size", &self.size).finish()
+    }
+}
+
+/// Reuses the textures of GPU frames that have been dropped, as creating one for every frame is slow.
+#[derive(Debug, Clone, Default)]
+pub struct GpuTexturePool {
+    textures: Arc<Mutex<Vec<ID3D11Texture2D>>>,
+}
+
+impl GpuTexturePool {
+    // The frames in flight rarely exceed the encoder queue, so more than this are freed instead of kept.
+    const MAX_POOLED: usize = 4;
+
+    /// Takes a texture matching the description, creating one if none is free.
+    /// Textures of another size are dropped, as the source only changes size when it is resized.
+    pub fn take(
+        &self,
+        device: &ID3D11Device,
+        desc: &D3D11_TEXTURE2D_DESC,
+    ) -> windows::core::Result<ID3D11Texture2D> {
+        {
+            let mut textures = self.textures.lock().unwrap();
+            while let Some(texture) = textures.pop() {
+                let mut existing = D3D11_TEXTURE2D_DESC::default();
+                unsafe { texture.GetDesc(&mut existing) };
+                if (existing.Width, existing.Height, existing.Format)
+                    == (desc.Width, desc.Height, desc.Format)
+                {
+                    return Ok(texture);
+                }
+            }
+        }
+
+        let mut texture = None;
+        unsafe { device.CreateTexture2D(desc, None, Some(&mut texture))? };
+        Ok(texture.expect("CreateTexture2D succeeded without a texture"))
+    }
+
+    fn give_back(&self, texture: ID3D11Texture2D) {
+        let mut textures = self.textures.lock().unwrap();
+        if textures.len() < Self::MAX_POOLED {
+            textures.push(texture);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+use tokio::sync::RwLock;
+
+/// A stream made by `create` once the lock is free. Whoever asks for the stream doesn't wait for the lock,
+/// which matters for subscriptions, as they are made on the UI thread.
+/// Ends without an item if `create` returns None.
+pub fn stream_when_unlocked<T, S>(
+    lock: Arc<RwLock<T>>,
+    create: impl FnOnce(&mut T) -> Option<S> + Send + 'static,
+) -> impl Stream<Item = S::Item> + Send + 'static
+where
+    T: Send + Sync + 'static,
+    S: Stream + Send + 'static,
+{
+    futures::stream::once(async move { create(&mut *lock.write().await) })
+        .filter_map(futures::future::ready)
+        .flatten()
+}
@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+/// How often a [`log_throttled!`](crate::log_throttled) line is logged at most.
+pub const INTERVAL: Duration = Duration::from_secs(5);
+
+/// Lets a log line through at most once per interval, counting the ones held back in between,
+/// so a line logged for every frame neither slows the frames down nor floods the log.
+#[derive(Debug, Clone)]
+pub struct LogThrottle {
+    interval: Duration,
+    last_logged: Option<Instant>,
+    suppressed: u64,
+}
+
+impl LogThrottle {
+    pub const fn new(interval: Duration) -> Self {
+        Self { interval, last_logged: None, suppressed: 0 }
+    }
+
+    /// Whether a line happening now should be logged, in which case it returns how many were held back since the last one.
+    pub fn pass(&mut self, now: Instant) -> Option<u64> {
+        if self.last_logged.is_some_and(|last| now.saturating_duration_since(last) < self.interval)
+        {
+            self.suppressed += 1;
+            return None;
+        }
+        self.last_logged = Some(now);
+        Some(std::mem::take(&mut self.suppressed))
+    }
+}
+
+/// Logs like [`tracing::event!`] at the level given, but at most once per [`INTERVAL`] for each place it is used.
+/// The lines held back in between are counted in the `suppressed` field of the next one.
+/// Nothing is counted while the level is disabled.
+#[macro_export]
+macro_rules! log_throttled {
+    ($level:expr, $($arg:tt)+) => {{
+        if ::tracing::enabled!($level) {
+            static THROTTLE: ::std::sync::Mutex<$crate::utils::log_throttle::LogThrottle> =
+                ::std::sync::Mutex::new($crate::utils::log_throttle::LogThrottle::new(
+                    $crate::utils::log_throttle::INTERVAL,
+                ));
+            let suppressed = THROTTLE
+                .lock()
+                .unwrap_or_else(::std::sync::PoisonError::into_inner)
+                .pass(::std::time::Instant::now());
+            if let Some(suppressed) = suppressed {
+                ::tracing::event!($level, suppressed, $($arg)+);
+            }
+        }
+    }};
+}
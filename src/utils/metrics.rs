@@ -0,0 +1,261 @@
+//! Stats kept by the workers and shown by the UI, by name. Updating a metric is an atomic operation that never waits on
+//! a lock, so reading them can't hold a worker up, and the UI takes a [`MetricsSnapshot`] of all of them on its tick.
+//! A snapshot reads each metric on its own, so metrics that change together may be a frame apart in it, which is close
+//! enough for stats.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// A count that only goes up, e.g. of the frames encoded.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that is set as it changes, e.g. the bitrate over the last second.
+// The bits of an f64, which are all zero for 0.0.
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Sets it to the value if that is higher, e.g. for the deepest of several queues. Retries if another thread set
+    /// it in between, so it is lock-free rather than wait-free.
+    pub fn set_max(&self, value: f64) {
+        let _ = self.0.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            (value > f64::from_bits(bits)).then_some(value.to_bits())
+        });
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Counts values into fixed buckets, e.g. the intervals between frames in microseconds. Keeps their sum and the sum of
+/// their squares too, for their mean and standard deviation.
+#[derive(Debug)]
+pub struct Histogram {
+    // The upper bounds of the buckets, inclusive and ascending. The values above the last go into one more bucket.
+    bounds: Box<[u64]>,
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    sum_of_squares: AtomicU64,
+}
+
+impl Histogram {
+    /// With buckets up to each of the bounds, in any order.
+    pub fn new(bounds: &[u64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_unstable();
+        bounds.dedup();
+        Self {
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            bounds: bounds.into(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            sum_of_squares: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, value: u64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < value);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.sum_of_squares.fetch_add(value.saturating_mul(value), Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Starts over, e.g. when what is measured changes. Values recorded meanwhile may be partly lost.
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+        self.sum_of_squares.store(0, Ordering::Relaxed);
+    }
+
+    pub fn bounds(&self) -> &[u64] {
+        &self.bounds
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            bounds: self.bounds.to_vec(),
+            buckets: self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum: self.sum.load(Ordering::Relaxed),
+            sum_of_squares: self.sum_of_squares.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A [`Histogram`] as it was when the snapshot was taken.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HistogramSnapshot {
+    pub bounds: Vec<u64>,
+    /// One for each bound, and one more for the values above the last.
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: u64,
+    pub sum_of_squares: u64,
+}
+
+impl HistogramSnapshot {
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    /// The population standard deviation.
+    pub fn std_dev(&self) -> Option<f64> {
+        let mean = self.mean()?;
+        let variance = self.sum_of_squares as f64 / self.count as f64 - mean * mean;
+        // Rounding can take it just below zero when the values are all the same.
+        Some(variance.max(0.0).sqrt())
+    }
+
+    /// The upper bound of the bucket the quantile, from 0 to 1, falls into, or `u64::MAX` if it is above the last
+    /// bound. None if nothing was recorded.
+    pub fn quantile(&self, quantile: f64) -> Option<u64> {
+        // The number of values at or below it, which is at least one.
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bucket = self.buckets.iter().position(|&count| {
+            seen += count;
+            seen >= rank
+        })?;
+        Some(self.upper_bound(bucket))
+    }
+
+    /// The upper bound of the highest bucket with anything in it, or `u64::MAX` if it is above the last bound. None if
+    /// nothing was recorded.
+    pub fn max(&self) -> Option<u64> {
+        let bucket = self.buckets.iter().rposition(|&count| count > 0)?;
+        Some(self.upper_bound(bucket))
+    }
+
+    fn upper_bound(&self, bucket: usize) -> u64 {
+        self.bounds.get(bucket).copied().unwrap_or(u64::MAX)
+    }
+
+    /// What was recorded since an earlier snapshot of the same histogram. Everything in this one if it was reset in
+    /// between.
+    pub fn since(&self, earlier: &Self) -> Self {
+        if earlier.count > self.count || earlier.bounds != self.bounds {
+            return self.clone();
+        }
+        Self {
+            bounds: self.bounds.clone(),
+            buckets: self
+                .buckets
+                .iter()
+                .zip(&earlier.buckets)
+                .map(|(now, before)| now.saturating_sub(*before))
+                .collect(),
+            count: self.count - earlier.count,
+            sum: self.sum.saturating_sub(earlier.sum),
+            sum_of_squares: self.sum_of_squares.saturating_sub(earlier.sum_of_squares),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    counters: BTreeMap<&'static str, Arc<Counter>>,
+    gauges: BTreeMap<&'static str, Arc<Gauge>>,
+    histograms: BTreeMap<&'static str, Arc<Histogram>>,
+}
+
+/// The metrics of the app by name, e.g. "encoder.encoded". Cheap to clone, and every clone has the same metrics.
+/// Registering one takes a lock, so the workers do it once, up front, and keep the metrics they get.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics(Arc<Mutex<Registry>>);
+
+impl Metrics {
+    /// The counter registered under the name, which is registered if there is none.
+    pub fn counter(&self, name: &'static str) -> Arc<Counter> {
+        self.0.lock().unwrap().counters.entry(name).or_default().clone()
+    }
+
+    /// The gauge registered under the name, which is registered if there is none.
+    pub fn gauge(&self, name: &'static str) -> Arc<Gauge> {
+        self.0.lock().unwrap().gauges.entry(name).or_default().clone()
+    }
+
+    /// The histogram registered under the name, which is registered with the bounds if there is none.
+    pub fn histogram(&self, name: &'static str, bounds: &[u64]) -> Arc<Histogram> {
+        let mut registry = self.0.lock().unwrap();
+        registry.histograms.entry(name).or_insert_with(|| Arc::new(Histogram::new(bounds))).clone()
+    }
+
+    /// Unregisters the metrics with names that start with the prefix, e.g. those of a worker that is replaced, so the
+    /// ones registered under them next start from zero. Whoever still has them can keep updating them, unseen.
+    pub fn remove(&self, prefix: &str) {
+        let mut registry = self.0.lock().unwrap();
+        registry.counters.retain(|name, _| !name.starts_with(prefix));
+        registry.gauges.retain(|name, _| !name.starts_with(prefix));
+        registry.histograms.retain(|name, _| !name.starts_with(prefix));
+    }
+
+    /// Reads every metric. Only waits on the workers registering theirs, never on them updating them.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let registry = self.0.lock().unwrap();
+        MetricsSnapshot {
+            counters: registry
+                .counters
+                .iter()
+                .map(|(&name, counter)| (name, counter.get()))
+                .collect(),
+            gauges: registry.gauges.iter().map(|(&name, gauge)| (name, gauge.get())).collect(),
+            histograms: registry
+                .histograms
+                .iter()
+                .map(|(&name, histogram)| (name, histogram.snapshot()))
+                .collect(),
+        }
+    }
+}
+
+/// Every metric as it was when the snapshot was taken, by name.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MetricsSnapshot {
+    pub counters: BTreeMap<&'static str, u64>,
+    pub gauges: BTreeMap<&'static str, f64>,
+    pub histograms: BTreeMap<&'static str, HistogramSnapshot>,
+}
+
+impl MetricsSnapshot {
+    pub fn counter(&self, name: &str) -> Option<u64> {
+        self.counters.get(name).copied()
+    }
+
+    pub fn gauge(&self, name: &str) -> Option<f64> {
+        self.gauges.get(name).copied()
+    }
+
+    pub fn histogram(&self, name: &str) -> Option<&HistogramSnapshot> {
+        self.histograms.get(name)
+    }
+}
@@ -1,10 +1,25 @@
+pub mod abort_on_drop;
+pub mod agile_ref;
+pub mod atomic_file;
 pub mod bitmap_utils;
-pub(crate) mod buffer_arena;
+pub mod buffer_arena;
+pub mod call_span;
+pub mod content_hash;
+pub mod dirty_rects;
+pub mod dpi;
 pub(crate) mod errable_option;
 pub mod frame;
+pub mod frame_ring;
+pub mod framerate;
+pub mod gpu_frame;
+pub mod locked_stream;
+pub mod log_throttle;
+pub mod metrics;
+pub mod orientation;
+pub mod panic_guard;
 pub mod pixel_format;
+pub mod rate_limiter;
 pub mod rect;
+pub mod test_support;
+pub mod throttle;
 pub mod vector2;
-
-#[allow(dead_code)]
-mod unsafe_send_wrapper;
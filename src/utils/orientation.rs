@@ -0,0 +1,52 @@
+use crate::utils::{rect::Rect, vector2::Vector2};
+
+/// The clockwise rotation that turns a frame upright, e.g. one captured from a display in portrait
+/// that arrives in the shape of its panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Orientation {
+    #[default]
+    Upright,
+    Rotated90,
+    Rotated180,
+    Rotated270,
+}
+
+impl Orientation {
+    pub const ALL: [Self; 4] = [Self::Upright, Self::Rotated90, Self::Rotated180, Self::Rotated270];
+
+    pub fn is_upright(self) -> bool {
+        self == Self::Upright
+    }
+
+    /// Whether the width and height trade places.
+    pub fn is_transposed(self) -> bool {
+        matches!(self, Self::Rotated90 | Self::Rotated270)
+    }
+
+    /// The size of a frame of `size` once it is rotated.
+    pub fn rotated_size(self, size: Vector2<i32>) -> Vector2<i32> {
+        if self.is_transposed() { Vector2::new(size.y, size.x) } else { size }
+    }
+
+    /// Where a pixel of a frame of `size` ends up once the frame is rotated.
+    pub fn rotate_point(self, point: Vector2<i32>, size: Vector2<i32>) -> Vector2<i32> {
+        match self {
+            Self::Upright => point,
+            Self::Rotated90 => Vector2::new(size.y - 1 - point.y, point.x),
+            Self::Rotated180 => Vector2::new(size.x - 1 - point.x, size.y - 1 - point.y),
+            Self::Rotated270 => Vector2::new(point.y, size.x - 1 - point.x),
+        }
+    }
+
+    /// Where a rect of a frame of `size` ends up once the frame is rotated, e.g. a dirty rect.
+    pub fn rotate_rect(self, rect: Rect<i32>, size: Vector2<i32>) -> Rect<i32> {
+        if rect.is_empty() {
+            return Rect { position: self.rotate_point(rect.position, size), ..rect };
+        }
+        // The corners of its first and last pixel, which end up at opposite corners.
+        let first = self.rotate_point(rect.position, size);
+        let last = self.rotate_point(Vector2::new(rect.right() - 1, rect.bottom() - 1), size);
+        let (x, y) = (first.x.min(last.x), first.y.min(last.y));
+        Rect::new(x, y, first.x.max(last.x) - x + 1, first.y.max(last.y) - y + 1)
+    }
+}
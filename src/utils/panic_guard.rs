@@ -0,0 +1,78 @@
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, OnceLock},
+};
+
+use futures::FutureExt;
+
+/// Where a background worker leaves the message of the panic that stopped it,
+/// for whoever holds its handle to notice, as the worker can't tell anyone itself anymore.
+#[derive(Debug, Clone, Default)]
+pub struct PanicSlot(Arc<OnceLock<String>>);
+
+impl PanicSlot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The message of the panic, if the worker panicked.
+    pub fn get(&self) -> Option<&str> {
+        self.0.get().map(String::as_str)
+    }
+
+    // Only the first panic is kept, as the worker is gone after it.
+    fn record(&self, message: String) {
+        let _ = self.0.set(message);
+    }
+}
+
+/// The message a panic was raised with, which is a `&str` or `String` unless the panic was raised with something else.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
+
+/// Runs the closure, returning the message of the panic instead of unwinding if it panics.
+/// Needed wherever the OS calls into us, as unwinding into it aborts the whole app.
+pub fn catch_panic<T>(name: &str, f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        let message = panic_message(payload.as_ref());
+        tracing::error!("{} panicked: {}", name, message);
+        message
+    })
+}
+
+/// Runs the worker until it finishes, leaving the message of the panic in the slot if it panics.
+/// Without this, a panicking task just ends, and nothing notices until the frames stop.
+pub async fn catch_panics(name: &str, slot: PanicSlot, worker: impl Future<Output = ()>) {
+    if let Err(payload) = AssertUnwindSafe(worker).catch_unwind().await {
+        let message = panic_message(payload.as_ref());
+        tracing::error!("{} panicked: {}", name, message);
+        slot.record(message);
+    }
+}
+
+/// Logs panics along with their backtrace before the default hook prints them,
+/// so they end up wherever the logs go rather than only in the console.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let location = info.location().map_or_else(|| "<unknown>".to_owned(), ToString::to_string);
+        let thread = std::thread::current();
+        tracing::error!(
+            "Thread '{}' panicked at {}: {}\n{}",
+            thread.name().unwrap_or("<unnamed>"),
+            location,
+            panic_message(info.payload()),
+            Backtrace::force_capture()
+        );
+        default_hook(info);
+    }));
+}
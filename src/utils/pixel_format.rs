@@ -11,7 +11,7 @@ macro_rules! define_pixel_formats {
             }
         ),* $(,)?
     ) => {
-        #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
         pub enum PixelFormat {
             $(
                 $variant,
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+/// What happens to a packet offered to a [`RateLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Send,
+    /// Sent once the delay is over. It is counted as sent already.
+    Delay(Duration),
+    Drop,
+}
+
+/// Holds packets to a rate with a token bucket that fills up to a window's worth of bytes. Bursts up to that go
+/// straight through, and no more than a window's worth goes out over any window, other than for keyframes.
+/// A packet that would wait longer than the max delay is dropped, and so are the ones after it up to the next keyframe,
+/// as they can't be decoded without it. Keyframes are never dropped: they wait until a window's worth is there,
+/// or as much as they need, and the bytes they take beyond that hold back the packets after them.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bytes_per_second: f64,
+    window: Duration,
+    max_delay: Duration,
+    // Below zero while a keyframe that took more than there was is paid off.
+    tokens: f64,
+    last_refill: Option<Instant>,
+    awaiting_keyframe: bool,
+    dropped: u64,
+}
+
+impl RateLimiter {
+    /// Starts out with a full window's worth, as nothing was sent before.
+    pub fn new(bytes_per_second: u64, window: Duration, max_delay: Duration) -> Self {
+        let bytes_per_second = bytes_per_second as f64;
+        Self {
+            bytes_per_second,
+            window,
+            max_delay,
+            tokens: bytes_per_second * window.as_secs_f64(),
+            last_refill: None,
+            awaiting_keyframe: false,
+            dropped: 0,
+        }
+    }
+
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second as u64
+    }
+
+    /// Changes the rate from now on. What is left over is kept, up to the window's worth at the new rate.
+    pub fn set_rate(&mut self, bytes_per_second: u64, now: Instant) {
+        self.refill(now);
+        self.bytes_per_second = bytes_per_second as f64;
+        self.tokens = self.tokens.min(self.capacity());
+    }
+
+    pub fn admit(&mut self, bytes: usize, keyframe: bool, now: Instant) -> Admission {
+        self.refill(now);
+        let bytes = bytes as f64;
+
+        if keyframe {
+            self.awaiting_keyframe = false;
+            let needed = bytes.min(self.capacity());
+            let wait = self.wait_for(needed);
+            self.tokens -= bytes;
+            return if wait.is_zero() { Admission::Send } else { Admission::Delay(wait) };
+        }
+
+        if self.awaiting_keyframe {
+            self.dropped += 1;
+            return Admission::Drop;
+        }
+        let wait = self.wait_for(bytes);
+        if wait > self.max_delay {
+            self.awaiting_keyframe = true;
+            self.dropped += 1;
+            return Admission::Drop;
+        }
+        self.tokens -= bytes;
+        if wait.is_zero() { Admission::Send } else { Admission::Delay(wait) }
+    }
+
+    /// Whether packets are dropped until the next keyframe, which is then worth asking the encoder for.
+    pub fn is_awaiting_keyframe(&self) -> bool {
+        self.awaiting_keyframe
+    }
+
+    /// The packets dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn capacity(&self) -> f64 {
+        self.bytes_per_second * self.window.as_secs_f64()
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(last) = self.last_refill.replace(now) {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.bytes_per_second).min(self.capacity());
+        }
+    }
+
+    // How long until there are as many tokens as needed.
+    fn wait_for(&self, needed: f64) -> Duration {
+        let missing = needed - self.tokens;
+        if missing <= 0.0 || self.bytes_per_second <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(missing / self.bytes_per_second)
+    }
+}
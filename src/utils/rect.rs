@@ -1,11 +1,103 @@
+use serde::{Deserialize, Serialize};
+
 use crate::utils::vector2::Vector2;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub struct Rect<N = f32> {
     pub position: Vector2<N>,
     pub size: Vector2<N>,
 }
 
+impl Rect<i32> {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self { position: Vector2 { x, y }, size: Vector2 { x: width, y: height } }
+    }
+
+    pub fn right(&self) -> i32 {
+        self.position.x + self.size.x
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.position.y + self.size.y
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.x <= 0 || self.size.y <= 0
+    }
+
+    /// In pixels. Wide enough that no frame overflows it.
+    pub fn area(&self) -> i64 {
+        if self.is_empty() { 0 } else { self.size.x as i64 * self.size.y as i64 }
+    }
+
+    /// The smallest rect around both.
+    pub fn union(&self, other: &Self) -> Self {
+        let x = self.position.x.min(other.position.x);
+        let y = self.position.y.min(other.position.y);
+        Self::new(x, y, self.right().max(other.right()) - x, self.bottom().max(other.bottom()) - y)
+    }
+
+    /// Whether the rects overlap or share an edge, so their union covers little that neither does.
+    pub fn touches(&self, other: &Self) -> bool {
+        self.position.x <= other.right()
+            && other.position.x <= self.right()
+            && self.position.y <= other.bottom()
+            && other.position.y <= self.bottom()
+    }
+
+    /// The part both cover, or None if they don't overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let x = self.position.x.max(other.position.x);
+        let y = self.position.y.max(other.position.y);
+        let rect = Self::new(
+            x,
+            y,
+            self.right().min(other.right()) - x,
+            self.bottom().min(other.bottom()) - y,
+        );
+        (!rect.is_empty()).then_some(rect)
+    }
+
+    /// The same rect, moved by the offset.
+    pub fn translated(&self, offset: Vector2<i32>) -> Self {
+        Self::new(self.position.x + offset.x, self.position.y + offset.y, self.size.x, self.size.y)
+    }
+
+    pub fn contains(&self, other: &Self) -> bool {
+        self.position.x <= other.position.x
+            && self.position.y <= other.position.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+}
+
+impl Rect<f32> {
+    /// The rect between two opposite corners, in whichever order.
+    pub fn from_corners(a: Vector2<f32>, b: Vector2<f32>) -> Self {
+        let position = Vector2::new(a.x.min(b.x), a.y.min(b.y));
+        Self { position, size: Vector2::new(a.x.max(b.x) - position.x, a.y.max(b.y) - position.y) }
+    }
+
+    pub fn contains_point(&self, point: Vector2<f32>) -> bool {
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x <= self.position.x + self.size.x
+            && point.y <= self.position.y + self.size.y
+    }
+
+    /// The pixels of a frame of `size` that the rect covers, normalized to the frame, rounded outwards.
+    /// None if it covers none of the frame.
+    pub fn to_pixels(&self, size: Vector2<i32>) -> Option<Rect<i32>> {
+        let (width, height) = (size.x as f32, size.y as f32);
+        let left = (self.position.x * width).floor() as i32;
+        let top = (self.position.y * height).floor() as i32;
+        let right = ((self.position.x + self.size.x) * width).ceil() as i32;
+        let bottom = ((self.position.y + self.size.y) * height).ceil() as i32;
+        Rect::new(left, top, right - left, bottom - top)
+            .intersection(&Rect::new(0, 0, size.x, size.y))
+    }
+}
+
 impl From<windows::Foundation::Rect> for Rect<f32> {
     fn from(rect: windows::Foundation::Rect) -> Self {
         Rect {
@@ -26,6 +118,12 @@ impl From<Rect<f32>> for windows::Foundation::Rect {
     }
 }
 
+impl From<windows::Win32::Foundation::RECT> for Rect<i32> {
+    fn from(rect: windows::Win32::Foundation::RECT) -> Self {
+        Rect::new(rect.left, rect.top, rect.right - rect.left, rect.bottom - rect.top)
+    }
+}
+
 impl From<windows::Graphics::RectInt32> for Rect<i32> {
     fn from(rect: windows::Graphics::RectInt32) -> Self {
         Rect {
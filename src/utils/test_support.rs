@@ -0,0 +1,319 @@
+use std::time::Duration;
+
+use bytes::BytesMut;
+use futures::{Stream, StreamExt, stream::BoxStream};
+use tokio::sync::watch;
+
+use crate::{
+    capture_providers::{
+        CaptureProvider, SourceKind,
+        shared::{CaptureFramerate, CaptureItemInfo, CaptureState, FrameMeta},
+    },
+    utils::{
+        buffer_arena::BufferRef, frame::Frame, orientation::Orientation, pixel_format::PixelFormat,
+        vector2::Vector2,
+    },
+};
+
+/// The resolutions sources are commonly captured at.
+pub const RESOLUTIONS: &[(&str, Vector2<i32>)] = &[
+    ("1080p", Vector2 { x: 1920, y: 1080 }),
+    ("1440p", Vector2 { x: 2560, y: 1440 }),
+    ("4k", Vector2 { x: 3840, y: 2160 }),
+];
+
+/// What a synthetic frame looks like, which decides how hard it is to encode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePattern {
+    /// A single color, like an idle desktop. The cheapest case.
+    Flat,
+    /// Smooth gradients that move between frames, like scrolling or video.
+    Gradient,
+    /// Random pixels, which nothing can compress. The worst case.
+    Noise,
+}
+
+impl FramePattern {
+    pub const ALL: &[FramePattern] =
+        &[FramePattern::Flat, FramePattern::Gradient, FramePattern::Noise];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Flat => "flat",
+            Self::Gradient => "gradient",
+            Self::Noise => "noise",
+        }
+    }
+}
+
+/// Generates frames in place of a capture, for benchmarks and for running the pipeline without a source.
+/// The frames are deterministic, so runs can be compared.
+#[derive(Debug, Clone)]
+pub struct SyntheticFrames {
+    size: Vector2<i32>,
+    format: PixelFormat,
+    pattern: FramePattern,
+    frame_duration: Duration,
+    index: u32,
+    rng_state: u64,
+}
+
+impl SyntheticFrames {
+    const SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    pub fn new(size: Vector2<i32>, format: PixelFormat, pattern: FramePattern) -> Self {
+        Self {
+            size,
+            format,
+            pattern,
+            frame_duration: Duration::from_secs(1) / 60,
+            index: 0,
+            rng_state: Self::SEED,
+        }
+    }
+
+    pub fn size(&self) -> Vector2<i32> {
+        self.size
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// The length of the bitmap of a frame.
+    pub fn frame_len(&self) -> usize {
+        self.size.x as usize * self.size.y as usize * self.format.bytes_per_pixel() as usize
+    }
+
+    /// Writes the next frame into the bitmap, which has to be `frame_len` long.
+    pub fn fill(&mut self, bitmap: &mut [u8]) {
+        debug_assert_eq!(bitmap.len(), self.frame_len());
+        let bytes_per_pixel = self.format.bytes_per_pixel() as usize;
+
+        match self.pattern {
+            FramePattern::Flat => {
+                for pixel in bitmap.chunks_exact_mut(bytes_per_pixel) {
+                    pixel.fill(0x40);
+                    pixel[bytes_per_pixel - 1] = 0xFF;
+                }
+            }
+            FramePattern::Gradient => {
+                let width = self.size.x as usize;
+                let shift = self.index as usize * 4;
+                for (i, pixel) in bitmap.chunks_exact_mut(bytes_per_pixel).enumerate() {
+                    let (x, y) = (i % width + shift, i / width);
+                    pixel[0] = x as u8;
+                    pixel[1] = y as u8;
+                    pixel[2] = ((x + y) / 2) as u8;
+                    pixel[bytes_per_pixel - 1] = 0xFF;
+                }
+            }
+            FramePattern::Noise => {
+                for pixel in bitmap.chunks_exact_mut(bytes_per_pixel) {
+                    let bits = self.next_random().to_le_bytes();
+                    let len = pixel.len().min(bits.len());
+                    pixel[..len].copy_from_slice(&bits[..len]);
+                    pixel[bytes_per_pixel - 1] = 0xFF;
+                }
+            }
+        }
+
+        self.index = self.index.wrapping_add(1);
+    }
+
+    /// The bitmap of the next frame.
+    pub fn next_bitmap(&mut self) -> Vec<u8> {
+        let mut bitmap = vec![0; self.frame_len()];
+        self.fill(&mut bitmap);
+        bitmap
+    }
+
+    /// The next frame, as the capture would send it.
+    pub fn next_frame(&mut self) -> Frame {
+        let mut data = BytesMut::zeroed(self.frame_len());
+        self.fill(&mut data);
+        Frame::new_raw(
+            BufferRef::detached(data),
+            self.format,
+            self.size,
+            Some(self.frame_duration),
+            None,
+        )
+    }
+
+    /// The frames as a stream, paced like a capture at the framerate.
+    pub fn into_stream(mut self, framerate_hz: f32) -> impl Stream<Item = Frame> + Send + 'static {
+        let period = Duration::from_secs_f32(1.0 / framerate_hz);
+        self.frame_duration = period;
+
+        // The interval is created on the first poll, as it needs to be on the runtime.
+        futures::stream::unfold((self, None), move |(mut frames, interval)| async move {
+            let mut interval = interval.unwrap_or_else(|| {
+                let mut interval = tokio::time::interval(period);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                interval
+            });
+            interval.tick().await;
+            Some((frames.next_frame(), (frames, Some(interval))))
+        })
+    }
+
+    // xorshift64, as the frames only need to look random to an encoder.
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        x
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyntheticCaptureError {
+    #[error("Another stream is active")]
+    StreamAlreadyActive,
+    #[error("Already capturing")]
+    AlreadyCapturing,
+    #[error("Not capturing")]
+    NotCapturing,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Streams {
+    // Bumped for every new stream, which ends the one before.
+    generation: u64,
+    capturing: bool,
+}
+
+/// A capture provider that captures [`SyntheticFrames`] instead of a source, for running the capture without one.
+/// Its capture item is the pattern of the frames. The stream sends frames while it captures.
+#[derive(Debug)]
+pub struct SyntheticCaptureProvider {
+    size: Vector2<i32>,
+    format: PixelFormat,
+    pattern: FramePattern,
+    streams: watch::Sender<Streams>,
+    stream_active: bool,
+    state: watch::Sender<CaptureState>,
+    frame_meta: watch::Sender<FrameMeta>,
+}
+
+impl SyntheticCaptureProvider {
+    pub fn new(size: Vector2<i32>, format: PixelFormat, pattern: FramePattern) -> Self {
+        Self {
+            size,
+            format,
+            pattern,
+            streams: watch::Sender::default(),
+            stream_active: false,
+            state: watch::Sender::default(),
+            frame_meta: watch::Sender::default(),
+        }
+    }
+}
+
+impl CaptureProvider for SyntheticCaptureProvider {
+    type Error = SyntheticCaptureError;
+    type Stream = BoxStream<'static, Frame>;
+    type CaptureItem = FramePattern;
+
+    fn create_stream(
+        &mut self,
+        framerate: CaptureFramerate,
+        replace: bool,
+    ) -> Result<Self::Stream, Self::Error> {
+        if self.stream_active && !replace {
+            return Err(SyntheticCaptureError::StreamAlreadyActive);
+        }
+        self.stream_active = true;
+        self.streams.send_modify(|streams| streams.generation += 1);
+
+        let generation = self.streams.borrow().generation;
+        let frames = SyntheticFrames::new(self.size, self.format, self.pattern)
+            .into_stream(framerate.to_hz())
+            .boxed();
+        let frame_meta = self.frame_meta.clone();
+        Ok(futures::stream::unfold(
+            (frames, self.streams.subscribe()),
+            move |(mut frames, mut streams)| {
+                let frame_meta = frame_meta.clone();
+                async move {
+                    // Waits out the time the capture is stopped, and ends once replaced.
+                    loop {
+                        let current = *streams.borrow_and_update();
+                        if current.generation != generation {
+                            return None;
+                        }
+                        if current.capturing {
+                            break;
+                        }
+                        streams.changed().await.ok()?;
+                    }
+                    let frame = frames.next().await?;
+                    frame_meta.send_modify(|meta| *meta = meta.next(&frame));
+                    Some((frame, (frames, streams)))
+                }
+            },
+        )
+        .boxed())
+    }
+
+    fn set_capture_item(&mut self, capture_item: Self::CaptureItem) -> Result<(), Self::Error> {
+        // Takes effect with the next stream.
+        self.pattern = capture_item;
+        Ok(())
+    }
+
+    fn start_capture(&mut self) -> Result<(), Self::Error> {
+        if self.is_capturing() {
+            return Err(SyntheticCaptureError::AlreadyCapturing);
+        }
+        self.streams.send_modify(|streams| streams.capturing = true);
+        self.state.send_replace(CaptureState::Capturing);
+        Ok(())
+    }
+
+    fn stop_capture(&mut self) -> Result<(), Self::Error> {
+        // Ends the stream, as with a real source.
+        self.streams.send_modify(|streams| {
+            streams.generation += 1;
+            streams.capturing = false;
+        });
+        self.stream_active = false;
+        self.state.send_replace(CaptureState::Idle);
+        Ok(())
+    }
+
+    fn recover_capture(&mut self) -> Result<Self::CaptureItem, Self::Error> {
+        if !self.is_capturing() {
+            return Err(SyntheticCaptureError::NotCapturing);
+        }
+        self.state.send_replace(CaptureState::Capturing);
+        Ok(self.pattern)
+    }
+
+    fn is_capturing(&self) -> bool {
+        self.streams.borrow().capturing
+    }
+
+    fn capture_item_info(&self) -> Option<CaptureItemInfo> {
+        Some(CaptureItemInfo {
+            name: self.pattern.name().to_owned(),
+            size: self.size,
+            orientation: Orientation::Upright,
+            kind: SourceKind::Monitor,
+            applied_options: Vec::new(),
+            process_name: None,
+            icon: None,
+        })
+    }
+
+    fn subscribe_state(&self) -> watch::Receiver<CaptureState> {
+        self.state.subscribe()
+    }
+
+    fn subscribe_frame_meta(&self) -> watch::Receiver<FrameMeta> {
+        self.frame_meta.subscribe()
+    }
+}
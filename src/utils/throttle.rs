@@ -0,0 +1,35 @@
+use std::time::{Duration, Instant};
+
+/// Lets through at most one event per interval, e.g. to sample a 60 fps stream at 10 fps.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    interval: Duration,
+    last_passed: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, last_passed: None }
+    }
+
+    /// Lets through up to `rate` events per second. A rate of 0 lets everything through.
+    pub fn per_second(rate: u32) -> Self {
+        Self::new(if rate == 0 { Duration::ZERO } else { Duration::from_secs(1) / rate })
+    }
+
+    /// Whether an event happening now should pass, which it does if the interval has passed since the last one that did.
+    pub fn pass(&mut self, now: Instant) -> bool {
+        if self.last_passed.is_some_and(|last| now.saturating_duration_since(last) < self.interval)
+        {
+            return false;
+        }
+        self.last_passed = Some(now);
+        true
+    }
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new(Duration::ZERO)
+    }
+}
@@ -0,0 +1,65 @@
+//! COM objects made on one thread and used from another.
+#![cfg(target_os = "windows")]
+
+use std::{thread, time::Duration};
+
+use fjarsyn::{
+    capture_providers::{
+        CaptureProvider, create_platform_capture_provider,
+        shared::{CaptureFramerate, WgcSessionOptions},
+    },
+    utils::{agile_ref::AgileRef, metrics::Metrics, pixel_format::PixelFormat},
+};
+use futures::StreamExt;
+use windows::{Foundation::Uri, core::h};
+
+const FRAMES: usize = 3;
+const DEADLINE: Duration = Duration::from_secs(5);
+
+#[test]
+fn resolves_on_other_threads() {
+    let uri = Uri::CreateUri(h!("https://example.com/path")).unwrap();
+    let reference = AgileRef::new(&uri).unwrap();
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let reference = reference.clone();
+            thread::spawn(move || reference.resolve().unwrap().Host().unwrap().to_string())
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), "example.com");
+    }
+    // And still on the thread it was made on.
+    assert_eq!(reference.resolve().unwrap().Path().unwrap(), "/path");
+}
+
+#[test]
+#[ignore = "needs a monitor to capture"]
+fn the_provider_is_made_on_one_thread_and_streamed_from_another() {
+    let capture = thread::spawn(|| {
+        create_platform_capture_provider(
+            PixelFormat::BGRA8,
+            WgcSessionOptions::default(),
+            Metrics::default(),
+        )
+        .unwrap()
+    })
+    .join()
+    .unwrap();
+
+    let frames = thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async move {
+            let mut capture = capture;
+            capture.start_capture().unwrap();
+            let frames = capture.create_stream(CaptureFramerate::FPS30, false).unwrap();
+            let frames =
+                tokio::time::timeout(DEADLINE, frames.take(FRAMES).collect::<Vec<_>>()).await;
+            capture.stop_capture().unwrap();
+            frames.unwrap()
+        })
+    })
+    .join()
+    .unwrap();
+    assert_eq!(frames.len(), FRAMES);
+}
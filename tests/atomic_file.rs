@@ -0,0 +1,121 @@
+mod common;
+
+use std::fs;
+
+use common::test_dir::TestDir;
+use fjarsyn::utils::atomic_file::{self, Loaded, backup_path, corrupt_path, temp_path};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Settings {
+    name: String,
+    bitrate: u32,
+}
+
+fn settings(name: &str) -> Settings {
+    Settings { name: name.to_owned(), bitrate: 5_000_000 }
+}
+
+// What a save that was cut short leaves: the start of the content.
+fn truncated(settings: &Settings) -> String {
+    let content = serde_json::to_string_pretty(settings).unwrap();
+    content[..content.len() / 2].to_owned()
+}
+
+#[test]
+fn saves_and_loads() {
+    let dir = TestDir::new("saves");
+    let path = dir.join("settings.json");
+    assert!(matches!(atomic_file::load_json::<Settings>(&path), Loaded::Missing));
+
+    atomic_file::save_json(&path, &settings("first")).unwrap();
+    assert!(!temp_path(&path).exists());
+    // There was nothing to back up.
+    assert!(!backup_path(&path).exists());
+    match atomic_file::load_json::<Settings>(&path) {
+        Loaded::Intact(loaded) => assert_eq!(loaded, settings("first")),
+        other => panic!("Loaded {:?}", other),
+    }
+}
+
+#[test]
+fn keeps_the_previous_content_as_a_backup() {
+    let dir = TestDir::new("backup");
+    let path = dir.join("settings.json");
+    atomic_file::save_json(&path, &settings("first")).unwrap();
+    atomic_file::save_json(&path, &settings("second")).unwrap();
+
+    let backup: Settings = serde_json::from_slice(&fs::read(backup_path(&path)).unwrap()).unwrap();
+    assert_eq!(backup, settings("first"));
+    let current: Settings = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    assert_eq!(current, settings("second"));
+}
+
+#[test]
+fn recovers_a_truncated_file_from_the_backup() {
+    let dir = TestDir::new("truncated");
+    let path = dir.join("settings.json");
+    atomic_file::save_json(&path, &settings("first")).unwrap();
+    atomic_file::save_json(&path, &settings("second")).unwrap();
+    fs::write(&path, truncated(&settings("second"))).unwrap();
+
+    match atomic_file::load_json::<Settings>(&path) {
+        Loaded::Recovered { value, from } => {
+            assert_eq!(value, settings("first"));
+            assert_eq!(from, backup_path(&path));
+        }
+        other => panic!("Loaded {:?}", other),
+    }
+    // The file is whole again.
+    assert!(matches!(atomic_file::load_json::<Settings>(&path), Loaded::Intact(_)));
+}
+
+#[test]
+fn recovers_from_a_save_cut_short_before_the_rename() {
+    let dir = TestDir::new("temp");
+    let path = dir.join("settings.json");
+    atomic_file::save_json(&path, &settings("first")).unwrap();
+    atomic_file::save_json(&path, &settings("second")).unwrap();
+    // The new content made it to disk, but the file didn't.
+    fs::write(temp_path(&path), serde_json::to_string(&settings("third")).unwrap()).unwrap();
+    fs::write(&path, "").unwrap();
+
+    match atomic_file::load_json::<Settings>(&path) {
+        Loaded::Recovered { value, from } => {
+            // The newer of the copies.
+            assert_eq!(value, settings("third"));
+            assert_eq!(from, temp_path(&path));
+        }
+        other => panic!("Loaded {:?}", other),
+    }
+}
+
+#[test]
+fn a_broken_file_does_not_replace_the_backup() {
+    let dir = TestDir::new("broken-backup");
+    let path = dir.join("settings.json");
+    atomic_file::save_json(&path, &settings("first")).unwrap();
+    atomic_file::save_json(&path, &settings("second")).unwrap();
+    fs::write(&path, truncated(&settings("second"))).unwrap();
+
+    // Saving over the broken file keeps the backup from before it.
+    atomic_file::save_json(&path, &settings("third")).unwrap();
+    let backup: Settings = serde_json::from_slice(&fs::read(backup_path(&path)).unwrap()).unwrap();
+    assert_eq!(backup, settings("first"));
+}
+
+#[test]
+fn moves_aside_a_file_that_cannot_be_recovered() {
+    let dir = TestDir::new("unrecoverable");
+    let path = dir.join("settings.json");
+    atomic_file::save_json(&path, &settings("first")).unwrap();
+    fs::write(&path, truncated(&settings("first"))).unwrap();
+
+    assert!(matches!(atomic_file::load_json::<Settings>(&path), Loaded::Broken(_)));
+    assert!(!path.exists());
+    assert_eq!(fs::read_to_string(corrupt_path(&path)).unwrap(), truncated(&settings("first")));
+
+    let (loaded, recovered) = atomic_file::load_json::<Settings>(&path).into_parts("settings");
+    assert_eq!(loaded, Settings::default());
+    assert_eq!(recovered, None);
+}
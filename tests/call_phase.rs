@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::{
+    networking::webrtc::{TrackId, WebRTCEvent},
+    ui::call_phase::{CallPhase, EndReason},
+};
+use fjarsyn_shared::CallMode;
+
+fn ringing() -> (CallPhase, Instant) {
+    let start = Instant::now();
+    (CallPhase::Ringing { since: start }, start)
+}
+
+#[test]
+fn outgoing_call_rings_then_connects() {
+    let (phase, start) = ringing();
+    let answered = start + Duration::from_secs(3);
+    let phase = phase.next(&WebRTCEvent::Answered(CallMode::ShareAndView), answered);
+    assert_eq!(phase, CallPhase::Connecting { since: answered });
+    assert!(phase.is_cancellable());
+
+    let connected = answered + Duration::from_secs(1);
+    let phase = phase.next(&WebRTCEvent::Connected, connected);
+    assert_eq!(phase, CallPhase::Connected { since: connected });
+    assert!(!phase.is_cancellable());
+}
+
+#[test]
+fn crossed_calls_connect_without_ringing_again() {
+    let (phase, start) = ringing();
+    let crossed = start + Duration::from_secs(1);
+    let phase = phase
+        .next(&WebRTCEvent::CallsCrossed("someone".to_owned(), CallMode::ShareAndView), crossed);
+    assert_eq!(phase, CallPhase::Connecting { since: crossed });
+}
+
+#[test]
+fn incoming_call_connects_straight_away() {
+    let (phase, start) = ringing();
+    assert_eq!(phase.next(&WebRTCEvent::Connected, start), CallPhase::Connected { since: start });
+}
+
+#[test]
+fn unrelated_events_keep_the_phase() {
+    let (phase, start) = ringing();
+    let later = start + Duration::from_secs(1);
+    for event in [
+        WebRTCEvent::IncomingCall("someone".to_owned(), CallMode::ViewOnly),
+        WebRTCEvent::TrackStarted { track: TrackId::default(), mime_type: "video/H264".to_owned() },
+        WebRTCEvent::TrackEnded { track: TrackId::default() },
+        WebRTCEvent::Interrupted,
+    ] {
+        assert_eq!(phase.next(&event, later), phase);
+    }
+
+    let connected = CallPhase::Connected { since: start };
+    assert_eq!(connected.next(&WebRTCEvent::Answered(CallMode::ShareAndView), later), connected);
+    assert_eq!(connected.next(&WebRTCEvent::Connected, later), connected);
+}
+
+#[test]
+fn interruption_reconnects_without_losing_the_call_time() {
+    let start = Instant::now();
+    let connected = CallPhase::Connected { since: start };
+    let dropped = start + Duration::from_secs(60);
+
+    let reconnecting = connected.next(&WebRTCEvent::Interrupted, dropped);
+    assert_eq!(reconnecting, CallPhase::Reconnecting { since: dropped, connected_since: start });
+    assert_eq!(reconnecting.elapsed(dropped), Duration::from_secs(60));
+
+    let back = reconnecting.next(&WebRTCEvent::Connected, dropped + Duration::from_secs(2));
+    assert_eq!(back, connected);
+}
+
+#[test]
+fn disconnecting_ends_with_a_reason_for_the_phase() {
+    let (phase, start) = ringing();
+    assert_eq!(
+        phase.next(&WebRTCEvent::Disconnected, start),
+        CallPhase::Ended(EndReason::Unreachable)
+    );
+    assert_eq!(
+        CallPhase::Connecting { since: start }.next(&WebRTCEvent::Disconnected, start),
+        CallPhase::Ended(EndReason::Unreachable)
+    );
+    assert_eq!(
+        CallPhase::Connected { since: start }.next(&WebRTCEvent::Disconnected, start),
+        CallPhase::Ended(EndReason::PeerLeft)
+    );
+    assert_eq!(
+        CallPhase::Reconnecting { since: start, connected_since: start }
+            .next(&WebRTCEvent::Disconnected, start),
+        CallPhase::Ended(EndReason::ConnectionLost)
+    );
+}
+
+#[test]
+fn ended_is_final() {
+    let ended = CallPhase::Ended(EndReason::PeerLeft);
+    let now = Instant::now();
+    for event in [
+        WebRTCEvent::Answered(CallMode::ShareAndView),
+        WebRTCEvent::Connected,
+        WebRTCEvent::Interrupted,
+    ] {
+        assert_eq!(ended.next(&event, now), ended);
+    }
+    assert_eq!(ended.elapsed(now), Duration::ZERO);
+    assert!(!ended.is_cancellable());
+}
@@ -0,0 +1,157 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use fjarsyn::utils::call_span::{CallDirection, call_span};
+use tracing::{
+    Event, Instrument, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id},
+};
+use tracing_subscriber::{
+    Layer,
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+};
+
+#[derive(Debug, Default)]
+struct Fields(Vec<(String, String)>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push((field.name().to_owned(), format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name().to_owned(), value.to_owned()));
+    }
+}
+
+impl Fields {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+struct CapturedEvent {
+    fields: Fields,
+    // The fields of the spans the event happened in, innermost first.
+    spans: Vec<(String, Fields)>,
+}
+
+impl CapturedEvent {
+    fn call(&self) -> Option<&Fields> {
+        self.spans.iter().find(|(name, _)| name == "call").map(|(_, fields)| fields)
+    }
+}
+
+// Records every event along with the spans it happened in.
+#[derive(Clone, Default)]
+struct CapturingLayer {
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CapturingLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let spans = ctx
+            .event_scope(event)
+            .into_iter()
+            .flatten()
+            .map(|span| {
+                let extensions = span.extensions();
+                let fields = extensions.get::<Fields>().map(|fields| fields.0.clone());
+                (span.name().to_owned(), Fields(fields.unwrap_or_default()))
+            })
+            .collect();
+
+        self.events.lock().unwrap().push(CapturedEvent { fields, spans });
+    }
+}
+
+fn capture() -> (impl Subscriber + Send + Sync, Arc<Mutex<Vec<CapturedEvent>>>) {
+    let layer = CapturingLayer::default();
+    let events = layer.events.clone();
+    (tracing_subscriber::registry().with(layer), events)
+}
+
+#[test]
+fn call_span_carries_peer_and_direction() {
+    let (subscriber, events) = capture();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _call = call_span("peer-a", CallDirection::Outgoing).entered();
+        tracing::trace!(frame = 7u64, "Encoded frame");
+    });
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.fields.get("frame"), Some("7"));
+    let call = event.call().expect("event outside the call span");
+    assert_eq!(call.get("peer"), Some("peer-a"));
+    assert_eq!(call.get("direction"), Some("outgoing"));
+}
+
+#[test]
+fn call_span_follows_tasks_spawned_in_it() {
+    let (subscriber, events) = capture();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    tracing::subscriber::with_default(subscriber, || {
+        runtime.block_on(async {
+            // The way the workers are spawned: from within the entered span, instrumented with the current one.
+            let task = {
+                let _call = call_span("peer-b", CallDirection::Incoming).entered();
+                tokio::spawn(
+                    async {
+                        tracing::trace!(frame = 1u64, "Decoded frame");
+                    }
+                    .in_current_span(),
+                )
+            };
+            // Spawned outside of any call, so it shouldn't pick one up.
+            let unrelated = tokio::spawn(async {
+                tracing::trace!("Unrelated");
+            });
+            task.await.unwrap();
+            unrelated.await.unwrap();
+        });
+    });
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    let decoded = events.iter().find(|event| event.fields.get("frame") == Some("1")).unwrap();
+    let call = decoded.call().expect("spawned task lost the call span");
+    assert_eq!(call.get("peer"), Some("peer-b"));
+    assert_eq!(call.get("direction"), Some("incoming"));
+
+    let unrelated = events.iter().find(|event| event.fields.get("frame").is_none()).unwrap();
+    assert!(unrelated.call().is_none());
+}
+
+#[test]
+fn spans_between_calls_are_disabled() {
+    let (subscriber, events) = capture();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _none = tracing::Span::none().entered();
+        tracing::trace!("Between calls");
+    });
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].spans.is_empty());
+}
@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use fjarsyn::{
+    ui::{
+        call_history::CallHistory,
+        call_summary::{CallSummary, CallSummaryRecorder, format_bytes, format_duration},
+    },
+    utils::vector2::Vector2,
+};
+
+const SECOND: Duration = Duration::from_secs(1);
+
+fn finish(recorder: CallSummaryRecorder, now: Instant) -> CallSummary {
+    recorder.finish(Some("peer".to_owned()), now, SystemTime::UNIX_EPOCH).expect("Never connected")
+}
+
+// Feeds a sample a second for `seconds`, with the counters going up by the given amounts each time.
+fn feed(
+    recorder: &mut CallSummaryRecorder,
+    start: Instant,
+    seconds: u32,
+    counters: &mut (u64, u64, u64),
+    per_second: (u64, u64, u64),
+) -> Instant {
+    let mut now = start;
+    for _ in 0..seconds {
+        now += SECOND;
+        counters.0 += per_second.0;
+        counters.1 += per_second.1;
+        counters.2 += per_second.2;
+        recorder.record_bytes(counters.0, counters.1);
+        recorder.record_frames_sent(counters.2);
+    }
+    now
+}
+
+#[test]
+fn a_call_that_never_connected_has_no_summary() {
+    let mut recorder = CallSummaryRecorder::new();
+    recorder.record_bytes(100, 100);
+    assert_eq!(recorder.finish(None, Instant::now(), SystemTime::UNIX_EPOCH), None);
+}
+
+#[test]
+fn sums_up_a_steady_call() {
+    let start = Instant::now();
+    let mut recorder = CallSummaryRecorder::new();
+    recorder.connected(start);
+    let mut counters = (0, 0, 0);
+    let now = feed(&mut recorder, start, 10, &mut counters, (1_000_000, 10_000, 30));
+
+    let summary = finish(recorder, now);
+    assert_eq!(summary.duration, 10 * SECOND);
+    assert_eq!(summary.bytes_sent, 10_000_000);
+    assert_eq!(summary.bytes_received, 100_000);
+    assert_eq!(summary.average_fps_sent, Some(30.0));
+    assert_eq!(summary.average_fps_received, None);
+    assert_eq!(summary.reconnects, 0);
+    assert!((summary.average_bitrate() - 8_080_000.0).abs() < 1.0);
+}
+
+#[test]
+fn counters_that_start_over_after_a_reconnect_are_added_up() {
+    let start = Instant::now();
+    let mut recorder = CallSummaryRecorder::new();
+    recorder.connected(start);
+    let mut counters = (0, 0, 0);
+    let now = feed(&mut recorder, start, 5, &mut counters, (1_000, 1_000, 30));
+
+    // Nothing comes in while reconnecting, and the new connection counts from zero.
+    recorder.interrupted(now);
+    let now = now + 5 * SECOND;
+    recorder.connected(now);
+    let mut counters = (0, 0, 0);
+    let now = feed(&mut recorder, now, 5, &mut counters, (1_000, 1_000, 30));
+
+    let summary = finish(recorder, now);
+    assert_eq!(summary.reconnects, 1);
+    assert_eq!(summary.bytes_sent, 10_000);
+    assert_eq!(summary.bytes_received, 10_000);
+    // The gap counts towards the duration, but not the framerate.
+    assert_eq!(summary.duration, 15 * SECOND);
+    assert_eq!(summary.average_fps_sent, Some(30.0));
+}
+
+#[test]
+fn only_the_first_drop_of_a_reconnect_counts() {
+    let start = Instant::now();
+    let mut recorder = CallSummaryRecorder::new();
+    recorder.connected(start);
+    recorder.interrupted(start + SECOND);
+    recorder.interrupted(start + 2 * SECOND);
+    recorder.connected(start + 3 * SECOND);
+    recorder.connected(start + 4 * SECOND);
+    recorder.interrupted(start + 5 * SECOND);
+    recorder.connected(start + 6 * SECOND);
+
+    assert_eq!(finish(recorder, start + 7 * SECOND).reconnects, 2);
+}
+
+#[test]
+fn ends_mid_reconnect() {
+    let start = Instant::now();
+    let mut recorder = CallSummaryRecorder::new();
+    recorder.connected(start);
+    recorder.record_frames_received(20);
+    recorder.interrupted(start + 2 * SECOND);
+
+    let summary = finish(recorder, start + 10 * SECOND);
+    assert_eq!(summary.duration, 10 * SECOND);
+    assert_eq!(summary.average_fps_received, Some(10.0));
+}
+
+#[test]
+fn keeps_the_largest_resolution() {
+    let start = Instant::now();
+    let mut recorder = CallSummaryRecorder::new();
+    recorder.connected(start);
+    recorder.record_resolution(Vector2::new(1280, 720));
+    recorder.record_resolution(Vector2::new(2560, 1440));
+    recorder.record_resolution(Vector2::new(1920, 1080));
+
+    assert_eq!(finish(recorder, start + SECOND).peak_resolution, Some(Vector2::new(2560, 1440)));
+}
+
+#[test]
+fn history_drops_the_oldest_calls() {
+    let start = Instant::now();
+    let mut history = CallHistory::default();
+    for seconds in 1..=CallHistory::MAX_ENTRIES as u64 + 2 {
+        let mut recorder = CallSummaryRecorder::new();
+        recorder.connected(start);
+        history.add(finish(recorder, start + Duration::from_secs(seconds)));
+    }
+
+    let entries = history.entries();
+    assert_eq!(entries.len(), CallHistory::MAX_ENTRIES);
+    assert_eq!(entries[0].duration, Duration::from_secs(3));
+}
+
+#[test]
+fn formats_for_display() {
+    assert_eq!(format_duration(Duration::from_secs(65)), "1:05");
+    assert_eq!(format_duration(Duration::from_secs(3 * 3600 + 5)), "3:00:05");
+    assert_eq!(format_bytes(1_500), "1.5 kB");
+    assert_eq!(format_bytes(12_340_000), "12.3 MB");
+    assert_eq!(format_bytes(2_000_000_000), "2.00 GB");
+}
+
+#[test]
+fn summaries_survive_the_history_file() {
+    let start = Instant::now();
+    let mut recorder = CallSummaryRecorder::new();
+    recorder.connected(start);
+    recorder.record_resolution(Vector2::new(1920, 1080));
+    let summary = finish(recorder, start + SECOND);
+
+    let json = serde_json::to_string(&summary).expect("Failed to serialize");
+    assert_eq!(serde_json::from_str::<CallSummary>(&json).expect("Failed to parse"), summary);
+}
+
+#[test]
+fn history_from_before_recordings_were_noted_still_loads() {
+    let start = Instant::now();
+    let mut recorder = CallSummaryRecorder::new();
+    recorder.connected(start);
+    let summary = finish(recorder, start + SECOND);
+
+    let mut json = serde_json::to_value(&summary).unwrap();
+    json.as_object_mut().unwrap().remove("was_recorded");
+    let loaded = serde_json::from_value::<CallSummary>(json).expect("Failed to parse");
+    assert!(!loaded.was_recorded);
+    assert_eq!(loaded, summary);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use std::{
+    fs,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use common::test_dir::TestDir;
+use fjarsyn::{
+    capabilities::{
+        CachedCapabilities, Capabilities, CapabilityKey, ProbeOutcome, ffmpeg_build_hash, lookup,
+        report, store,
+    },
+    capture_providers::shared::GraphicsAdapter,
+};
+
+const FFMPEG_BUILD: &str = "avutil 3866468 avcodec 3999844 --enable-gpl --enable-libx264";
+
+// An NVIDIA card with driver 32.0.15.6094, made up but shaped like a real one.
+fn adapter() -> GraphicsAdapter {
+    GraphicsAdapter {
+        vendor_id: 0x10de,
+        device_id: 0x2684,
+        driver_version: Some((32 << 48) | (15 << 16) | 6094),
+    }
+}
+
+fn key() -> CapabilityKey {
+    CapabilityKey::new(Some(&adapter()), FFMPEG_BUILD)
+}
+
+fn capabilities() -> Capabilities {
+    Capabilities {
+        graphics_device: Some("NVIDIA GeForce RTX 4090, feature level 11_1".to_owned()),
+        capture_supported: true,
+        session_options: vec!["IsBorderRequired".to_owned()],
+        encoders: vec![
+            ProbeOutcome { name: "H264Software".to_owned(), error: None },
+            ProbeOutcome { name: "H264Amf".to_owned(), error: Some("No AMD GPU".to_owned()) },
+        ],
+        decoders: vec![ProbeOutcome { name: "H264 Software".to_owned(), error: None }],
+    }
+}
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+const PROBED_AT: u64 = 1_800_000_000;
+
+fn cached() -> CachedCapabilities {
+    CachedCapabilities::new(key(), capabilities(), at(PROBED_AT))
+}
+
+#[test]
+fn key_identifies_the_adapter_and_driver() {
+    let key = key();
+    assert_eq!(key.gpu, "10de:2684");
+    assert_eq!(key.driver, "32.0.15.6094");
+    assert_eq!(key.ffmpeg, ffmpeg_build_hash(FFMPEG_BUILD));
+}
+
+#[test]
+fn key_without_an_adapter_is_unknown() {
+    let key = CapabilityKey::new(None, FFMPEG_BUILD);
+    assert_eq!(key.gpu, "unknown");
+    assert_eq!(key.driver, "unknown");
+
+    let without_driver = GraphicsAdapter { driver_version: None, ..adapter() };
+    let key = CapabilityKey::new(Some(&without_driver), FFMPEG_BUILD);
+    assert_eq!(key.gpu, "10de:2684");
+    assert_eq!(key.driver, "unknown");
+}
+
+#[test]
+fn ffmpeg_build_hash_is_stable() {
+    // FNV-1a of nothing is its offset basis, whatever the platform or Rust version.
+    assert_eq!(ffmpeg_build_hash(""), "cbf29ce484222325");
+    assert_eq!(ffmpeg_build_hash(FFMPEG_BUILD), ffmpeg_build_hash(FFMPEG_BUILD));
+    assert_ne!(ffmpeg_build_hash(FFMPEG_BUILD), ffmpeg_build_hash("--enable-gpl"));
+}
+
+#[test]
+fn cache_round_trips_through_json() {
+    let cached = cached();
+    let json = serde_json::to_string(&cached).unwrap();
+    assert_eq!(serde_json::from_str::<CachedCapabilities>(&json).unwrap(), cached);
+    assert_eq!(cached.probed_at, PROBED_AT);
+}
+
+#[test]
+fn valid_until_it_expires() {
+    let cached = cached();
+    assert!(cached.is_valid_for(&key(), at(PROBED_AT)));
+    let ttl = CachedCapabilities::TTL.as_secs();
+    assert!(cached.is_valid_for(&key(), at(PROBED_AT + ttl - 1)));
+    assert!(!cached.is_valid_for(&key(), at(PROBED_AT + ttl)));
+}
+
+#[test]
+fn invalid_if_probed_in_the_future() {
+    // As when the clock was turned back since, so its age can't be trusted.
+    let cached = cached();
+    assert_eq!(cached.age(at(PROBED_AT - 1)), None);
+    assert!(!cached.is_valid_for(&key(), at(PROBED_AT - 1)));
+}
+
+#[test]
+fn invalid_for_another_machine_or_build() {
+    let cached = cached();
+    let now = at(PROBED_AT + 60);
+
+    let other_gpu = GraphicsAdapter { device_id: 0x2704, ..adapter() };
+    assert!(!cached.is_valid_for(&CapabilityKey::new(Some(&other_gpu), FFMPEG_BUILD), now));
+
+    let updated_driver =
+        GraphicsAdapter { driver_version: Some((32 << 48) | (15 << 16) | 6590), ..adapter() };
+    assert!(!cached.is_valid_for(&CapabilityKey::new(Some(&updated_driver), FFMPEG_BUILD), now));
+
+    let other_build = CapabilityKey::new(Some(&adapter()), "avutil 3866468 --enable-gpl");
+    assert!(!cached.is_valid_for(&other_build, now));
+}
+
+#[test]
+fn stored_cache_is_looked_up() {
+    let dir = TestDir::new("lookup");
+    let path = dir.join("capabilities.json");
+    let now = at(PROBED_AT + 60);
+    assert_eq!(lookup(&path, &key(), now), None);
+
+    store(&path, &cached()).unwrap();
+    assert_eq!(lookup(&path, &key(), now), Some(cached()));
+    assert_eq!(lookup(&path, &CapabilityKey::new(None, FFMPEG_BUILD), now), None);
+    assert_eq!(lookup(&path, &key(), at(PROBED_AT) + CachedCapabilities::TTL), None);
+}
+
+#[test]
+fn broken_cache_is_ignored() {
+    let dir = TestDir::new("broken");
+    let path = dir.join("capabilities.json");
+    fs::create_dir_all(dir.path()).unwrap();
+    fs::write(&path, "{\"key\": {\"gpu\": \"10de").unwrap();
+    assert_eq!(lookup(&path, &key(), at(PROBED_AT)), None);
+}
+
+#[test]
+fn report_lists_what_works_and_what_doesnt() {
+    let report = report(&cached(), at(PROBED_AT + 3 * 60 * 60));
+    assert!(report.contains("probed 3 h ago"), "{}", report);
+    assert!(report.contains("GPU 10de:2684, driver 32.0.15.6094"), "{}", report);
+    assert!(report.contains("Capture: supported, with IsBorderRequired"), "{}", report);
+    assert!(report.contains("Encoder H264Software: works"), "{}", report);
+    assert!(report.contains("Encoder H264Amf: fails, No AMD GPU"), "{}", report);
+    assert!(report.contains("Decoder H264 Software: works"), "{}", report);
+}
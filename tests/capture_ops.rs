@@ -0,0 +1,125 @@
+use std::{sync::Arc, time::Duration};
+
+use fjarsyn::ui::capture_ops::{
+    CaptureOp, CaptureOpDecision, CaptureOpId, CaptureOpKind, CaptureOps, with_write_lock,
+};
+use tokio::sync::RwLock;
+
+fn run(ops: &mut CaptureOps<&'static str>, op: CaptureOp<&'static str>) -> CaptureOpId {
+    match ops.request(op.clone()) {
+        CaptureOpDecision::Run(id, run) => {
+            assert_eq!(run, op);
+            id
+        }
+        decision => panic!("Expected {:?} to run, got {:?}", op, decision),
+    }
+}
+
+#[test]
+fn runs_one_op_at_a_time() {
+    let mut ops = CaptureOps::new();
+    assert_eq!(ops.pending(), None);
+
+    let start = run(&mut ops, CaptureOp::Start("monitor"));
+    assert_eq!(ops.pending(), Some(CaptureOpKind::Start));
+    assert_eq!(ops.finish(start), Some(CaptureOpKind::Start));
+    assert_eq!(ops.pending(), None);
+
+    let stop = run(&mut ops, CaptureOp::Stop);
+    assert_eq!(ops.finish(stop), Some(CaptureOpKind::Stop));
+    assert_eq!(ops.take_queued(), None);
+}
+
+#[test]
+fn latest_start_supersedes_an_earlier_one() {
+    let mut ops = CaptureOps::new();
+    let first = run(&mut ops, CaptureOp::Start("monitor"));
+    let second = run(&mut ops, CaptureOp::Start("window"));
+    assert_ne!(first, second);
+
+    // The first may still report back, e.g. if it had the lock before it was aborted.
+    assert_eq!(ops.finish(first), None);
+    assert_eq!(ops.pending(), Some(CaptureOpKind::Start));
+    assert_eq!(ops.finish(second), Some(CaptureOpKind::Start));
+}
+
+#[test]
+fn stop_supersedes_a_pending_start() {
+    let mut ops = CaptureOps::new();
+    let start = run(&mut ops, CaptureOp::Start("monitor"));
+    let stop = run(&mut ops, CaptureOp::Stop);
+
+    assert_eq!(ops.finish(start), None);
+    assert_eq!(ops.finish(stop), Some(CaptureOpKind::Stop));
+    assert_eq!(ops.take_queued(), None);
+}
+
+#[test]
+fn start_waits_for_a_pending_stop() {
+    let mut ops = CaptureOps::new();
+    let stop = run(&mut ops, CaptureOp::Stop);
+    assert_eq!(ops.request(CaptureOp::Start("monitor")), CaptureOpDecision::Queued);
+    // Only the latest queued start is kept.
+    assert_eq!(ops.request(CaptureOp::Start("window")), CaptureOpDecision::Queued);
+    assert_eq!(ops.take_queued(), None);
+
+    assert_eq!(ops.finish(stop), Some(CaptureOpKind::Stop));
+    let (start, op) = ops.take_queued().unwrap();
+    assert_eq!(op, CaptureOp::Start("window"));
+    assert_eq!(ops.pending(), Some(CaptureOpKind::Start));
+    assert_eq!(ops.take_queued(), None);
+    assert_eq!(ops.finish(start), Some(CaptureOpKind::Start));
+}
+
+#[test]
+fn stopping_again_drops_the_queued_start() {
+    let mut ops = CaptureOps::new();
+    let stop = run(&mut ops, CaptureOp::Stop);
+    assert_eq!(ops.request(CaptureOp::Start("monitor")), CaptureOpDecision::Queued);
+    assert_eq!(ops.request(CaptureOp::Stop), CaptureOpDecision::Ignored);
+
+    assert_eq!(ops.finish(stop), Some(CaptureOpKind::Stop));
+    assert_eq!(ops.take_queued(), None);
+}
+
+#[test]
+fn recovering_is_dropped_while_stopping() {
+    let mut ops = CaptureOps::new();
+    let stop = run(&mut ops, CaptureOp::Stop);
+    assert_eq!(ops.request(CaptureOp::Start("monitor")), CaptureOpDecision::Queued);
+    assert_eq!(ops.request(CaptureOp::Recover), CaptureOpDecision::Ignored);
+
+    // The start asked for before is still wanted.
+    assert_eq!(ops.finish(stop), Some(CaptureOpKind::Stop));
+    assert_eq!(ops.take_queued().map(|(_, op)| op), Some(CaptureOp::Start("monitor")));
+}
+
+#[test]
+fn recovering_supersedes_a_pending_start_and_a_stop_it() {
+    let mut ops = CaptureOps::new();
+    let start = run(&mut ops, CaptureOp::Start("monitor"));
+    let recover = run(&mut ops, CaptureOp::Recover);
+    assert_eq!(ops.finish(start), None);
+    assert_eq!(ops.pending(), Some(CaptureOpKind::Recover));
+
+    let stop = run(&mut ops, CaptureOp::Stop);
+    assert_eq!(ops.finish(recover), None);
+    assert_eq!(ops.finish(stop), Some(CaptureOpKind::Stop));
+}
+
+#[tokio::test]
+async fn gives_up_on_a_lock_held_too_long() {
+    let lock = Arc::new(RwLock::new(0));
+    let guard = lock.clone().write_owned().await;
+
+    let result = with_write_lock(&lock, Duration::from_millis(50), |value| *value += 1).await;
+    assert_eq!(result, None);
+
+    drop(guard);
+    let result = with_write_lock(&lock, Duration::from_millis(50), |value| {
+        *value += 1;
+        *value
+    })
+    .await;
+    assert_eq!(result, Some(1));
+}
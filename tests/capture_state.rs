@@ -0,0 +1,81 @@
+//! The states a capture provider goes through, as the UI receives them.
+
+mod common;
+
+use common::fake_capture::FakeCapture;
+use fjarsyn::capture_providers::{CaptureProvider, shared::CaptureState};
+use tokio::sync::watch;
+
+// The states sent since the last call, as the UI would receive them.
+fn received(states: &mut watch::Receiver<CaptureState>, seen: &mut Vec<CaptureState>) {
+    if states.has_changed().unwrap() {
+        seen.push(states.borrow_and_update().clone());
+    }
+}
+
+fn is_error(state: &CaptureState) -> bool {
+    matches!(state, CaptureState::Error(_))
+}
+
+#[test]
+fn goes_from_idle_to_capturing_to_an_error_when_the_item_closes() {
+    let mut capture = FakeCapture::default();
+    let mut states = capture.subscribe_state();
+    let mut seen = vec![states.borrow_and_update().clone()];
+
+    capture.start_capture().unwrap();
+    received(&mut states, &mut seen);
+    assert!(capture.is_capturing());
+    capture.source_started();
+    received(&mut states, &mut seen);
+    capture.close_item();
+    received(&mut states, &mut seen);
+
+    assert_eq!(seen[..3], [CaptureState::Idle, CaptureState::Starting, CaptureState::Capturing]);
+    assert!(is_error(&seen[3]), "{seen:?}");
+    assert_eq!(seen.len(), 4);
+
+    // Stopping is how the UI cleans up after the error.
+    capture.stop_capture().unwrap();
+    received(&mut states, &mut seen);
+    assert_eq!(seen.last(), Some(&CaptureState::Idle));
+    assert!(!capture.is_capturing());
+}
+
+#[test]
+fn only_a_running_capture_fails() {
+    let mut capture = FakeCapture::default();
+    let mut states = capture.subscribe_state();
+
+    // Before it started, and while it is starting.
+    capture.close_item();
+    assert!(!states.has_changed().unwrap());
+    capture.start_capture().unwrap();
+    states.mark_unchanged();
+    capture.close_item();
+    assert!(!states.has_changed().unwrap());
+
+    // After it stopped.
+    capture.source_started();
+    capture.stop_capture().unwrap();
+    states.mark_unchanged();
+    capture.close_item();
+    assert!(!states.has_changed().unwrap());
+    assert_eq!(*states.borrow(), CaptureState::Idle);
+}
+
+#[test]
+fn stopping_when_stopped_sends_nothing() {
+    let mut capture = FakeCapture::default();
+    let mut states = capture.subscribe_state();
+    capture.stop_capture().unwrap();
+    assert!(!states.has_changed().unwrap());
+
+    capture.start_capture().unwrap();
+    capture.source_started();
+    capture.stop_capture().unwrap();
+    assert!(states.has_changed().unwrap());
+    assert_eq!(*states.borrow_and_update(), CaptureState::Idle);
+    capture.stop_capture().unwrap();
+    assert!(!states.has_changed().unwrap());
+}
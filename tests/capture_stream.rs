@@ -0,0 +1,157 @@
+//! The capture driven from plain tokio tasks, without the UI.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use common::fake_capture::{FakeCapture, FakeCaptureError};
+use fjarsyn::{
+    capture_providers::{CaptureProvider, CaptureProviderExt, shared::CaptureFramerate},
+    utils::{
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticCaptureError, SyntheticCaptureProvider},
+        vector2::Vector2,
+    },
+};
+use futures::StreamExt;
+use tokio::sync::RwLock;
+
+const SIZE: Vector2<i32> = Vector2 { x: 64, y: 48 };
+const FRAMES: usize = 5;
+const DEADLINE: Duration = Duration::from_secs(5);
+
+fn provider() -> Arc<RwLock<SyntheticCaptureProvider>> {
+    Arc::new(RwLock::new(SyntheticCaptureProvider::new(
+        SIZE,
+        PixelFormat::BGRA8,
+        FramePattern::Gradient,
+    )))
+}
+
+#[tokio::test]
+async fn captures_frames_from_a_spawned_task() {
+    let capture = provider();
+    capture.write().await.start_capture().unwrap();
+
+    let frames = SyntheticCaptureProvider::stream(&capture, CaptureFramerate::FPS60);
+    let frames = tokio::spawn(frames.take(FRAMES).collect::<Vec<_>>());
+    let frames = tokio::time::timeout(DEADLINE, frames).await.unwrap().unwrap();
+
+    assert_eq!(frames.len(), FRAMES);
+    assert!(frames.iter().all(|frame| frame.size == SIZE));
+    let meta = *capture.read().await.subscribe_frame_meta().borrow();
+    assert_eq!(meta.sequence, FRAMES as u64);
+}
+
+#[tokio::test]
+async fn asking_for_the_stream_doesnt_wait_for_the_lock() {
+    let capture = provider();
+    capture.write().await.start_capture().unwrap();
+    let guard = capture.clone().write_owned().await;
+
+    let mut frames = SyntheticCaptureProvider::stream(&capture, CaptureFramerate::FPS60).boxed();
+    assert!(tokio::time::timeout(Duration::from_millis(50), frames.next()).await.is_err());
+
+    drop(guard);
+    let frame = tokio::time::timeout(DEADLINE, frames.next()).await.unwrap();
+    assert!(frame.is_some());
+}
+
+#[tokio::test]
+async fn stream_ends_when_replaced_or_stopped() {
+    let capture = provider();
+    capture.write().await.start_capture().unwrap();
+
+    let mut first = SyntheticCaptureProvider::stream(&capture, CaptureFramerate::FPS60).boxed();
+    assert!(tokio::time::timeout(DEADLINE, first.next()).await.unwrap().is_some());
+    let mut second = SyntheticCaptureProvider::stream(&capture, CaptureFramerate::FPS60).boxed();
+    assert!(tokio::time::timeout(DEADLINE, second.next()).await.unwrap().is_some());
+    assert!(tokio::time::timeout(DEADLINE, first.next()).await.unwrap().is_none());
+
+    capture.write().await.stop_capture().unwrap();
+    assert!(tokio::time::timeout(DEADLINE, second.next()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn only_one_stream_without_replacing() {
+    let mut capture = SyntheticCaptureProvider::new(SIZE, PixelFormat::BGRA8, FramePattern::Flat);
+    let _stream = capture.create_stream(CaptureFramerate::FPS30, false).unwrap();
+    assert!(matches!(
+        capture.create_stream(CaptureFramerate::FPS30, false),
+        Err(SyntheticCaptureError::StreamAlreadyActive)
+    ));
+    assert!(capture.create_stream(CaptureFramerate::FPS30, true).is_ok());
+}
+
+#[tokio::test]
+async fn replacing_the_stream_ends_the_one_before() {
+    let mut capture = FakeCapture::default();
+    let mut first = capture.create_stream(CaptureFramerate::FPS30, false).unwrap();
+    assert!(matches!(
+        capture.create_stream(CaptureFramerate::FPS30, false),
+        Err(FakeCaptureError::StreamAlreadyActive)
+    ));
+    // Refusing the second one left the first as it was.
+    assert!(capture.send_frame());
+    assert!(first.next().await.is_some());
+
+    let mut second = capture.create_stream(CaptureFramerate::FPS30, true).unwrap();
+    assert!(first.next().await.is_none());
+    assert!(capture.send_frame());
+    assert!(second.next().await.is_some());
+
+    // Nothing is active once the stream is dropped, so there is nothing to replace.
+    drop(second);
+    assert!(!capture.send_frame());
+    assert!(capture.create_stream(CaptureFramerate::FPS30, false).is_ok());
+}
+
+#[tokio::test]
+async fn shared_stream_replaces_the_one_before() {
+    let capture = Arc::new(RwLock::new(FakeCapture::default()));
+    // Made once it is first polled.
+    let poll = Duration::from_millis(50);
+
+    let mut first = FakeCapture::stream(&capture, CaptureFramerate::FPS30).boxed();
+    assert!(tokio::time::timeout(poll, first.next()).await.is_err());
+    assert!(capture.write().await.send_frame());
+    assert!(tokio::time::timeout(DEADLINE, first.next()).await.unwrap().is_some());
+
+    let mut second = FakeCapture::stream(&capture, CaptureFramerate::FPS30).boxed();
+    assert!(tokio::time::timeout(poll, second.next()).await.is_err());
+    assert!(tokio::time::timeout(DEADLINE, first.next()).await.unwrap().is_none());
+    assert!(capture.write().await.send_frame());
+    assert!(tokio::time::timeout(DEADLINE, second.next()).await.unwrap().is_some());
+}
+
+#[cfg(target_os = "windows")]
+#[tokio::test]
+#[ignore = "needs a monitor to capture"]
+async fn captures_frames_from_the_screen() {
+    use fjarsyn::{
+        capture_providers::{
+            PlatformCaptureProvider, create_platform_capture_provider, shared::WgcSessionOptions,
+        },
+        utils::metrics::Metrics,
+    };
+
+    let capture = tokio::task::spawn_blocking(|| {
+        create_platform_capture_provider(
+            PixelFormat::BGRA8,
+            WgcSessionOptions::default(),
+            Metrics::default(),
+        )
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    let capture = Arc::new(RwLock::new(capture));
+    capture.write().await.start_capture().unwrap();
+
+    let frames = PlatformCaptureProvider::stream(&capture, CaptureFramerate::FPS30);
+    let frames = tokio::spawn(frames.take(FRAMES).collect::<Vec<_>>());
+    let frames = tokio::time::timeout(DEADLINE, frames).await.unwrap().unwrap();
+    assert_eq!(frames.len(), FRAMES);
+
+    capture.write().await.stop_capture().unwrap();
+}
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::{
+    media::click_highlights::{Click, ClickHighlights},
+    utils::{bitmap_utils::blend_circle, vector2::Vector2},
+};
+use fjarsyn_shared::MouseButton;
+
+const GRAY: [u8; 4] = [100, 100, 100, 255];
+
+fn bitmap(size: Vector2<i32>) -> Vec<u8> {
+    (0..size.x * size.y).flat_map(|_| GRAY).collect()
+}
+
+fn pixel(bitmap: &[u8], size: Vector2<i32>, x: i32, y: i32) -> [u8; 4] {
+    let offset = (y * size.x + x) as usize * 4;
+    bitmap[offset..offset + 4].try_into().unwrap()
+}
+
+fn click(x: f32, y: f32, button: MouseButton, at: Instant) -> Click {
+    Click { position: Vector2::new(x, y), button, at }
+}
+
+#[test]
+fn an_opaque_circle_fills_its_middle() {
+    let size = Vector2::new(20, 20);
+    let mut bitmap = bitmap(size);
+    blend_circle(&mut bitmap, size, Vector2::new(10.0, 10.0), 5.0, [255, 0, 0], 1.0);
+    assert_eq!(pixel(&bitmap, size, 10, 10), [255, 0, 0, 255]);
+    assert_eq!(pixel(&bitmap, size, 7, 10), [255, 0, 0, 255]);
+    // Outside of it, even along the diagonal of its bounding box.
+    assert_eq!(pixel(&bitmap, size, 0, 0), GRAY);
+    assert_eq!(pixel(&bitmap, size, 14, 14), GRAY);
+    assert_eq!(pixel(&bitmap, size, 16, 10), GRAY);
+}
+
+#[test]
+fn the_edge_is_antialiased() {
+    let size = Vector2::new(20, 20);
+    let mut bitmap = bitmap(size);
+    blend_circle(&mut bitmap, size, Vector2::new(10.5, 10.5), 5.0, [200, 200, 200], 1.0);
+    // The pixel at 14 is covered in full, and the one at 15 has its center on the edge, so it is half covered.
+    assert_eq!(pixel(&bitmap, size, 14, 10), [200, 200, 200, 255]);
+    assert_eq!(pixel(&bitmap, size, 15, 10), [150, 150, 150, 255]);
+    assert_eq!(pixel(&bitmap, size, 16, 10), GRAY);
+}
+
+#[test]
+fn opacity_blends_with_what_is_there() {
+    let size = Vector2::new(10, 10);
+    let mut bitmap = bitmap(size);
+    blend_circle(&mut bitmap, size, Vector2::new(5.0, 5.0), 3.0, [200, 0, 100], 0.5);
+    assert_eq!(pixel(&bitmap, size, 5, 5), [150, 50, 100, 255]);
+
+    // Nothing is drawn at no opacity or size, and more than full is taken for full.
+    let before = bitmap.clone();
+    blend_circle(&mut bitmap, size, Vector2::new(5.0, 5.0), 3.0, [0, 0, 0], 0.0);
+    blend_circle(&mut bitmap, size, Vector2::new(5.0, 5.0), 0.0, [0, 0, 0], 1.0);
+    assert_eq!(bitmap, before);
+    blend_circle(&mut bitmap, size, Vector2::new(5.0, 5.0), 3.0, [0, 0, 0], 2.0);
+    assert_eq!(pixel(&bitmap, size, 5, 5), [0, 0, 0, 255]);
+}
+
+#[test]
+fn alpha_is_left_alone() {
+    let size = Vector2::new(8, 8);
+    let mut bitmap: Vec<u8> = (0..size.x * size.y).flat_map(|_| [0, 0, 0, 77]).collect();
+    blend_circle(&mut bitmap, size, Vector2::new(4.0, 4.0), 10.0, [255, 255, 255], 1.0);
+    assert!(bitmap.chunks_exact(4).all(|pixel| pixel == [255, 255, 255, 77]));
+}
+
+#[test]
+fn circles_are_clipped_to_the_bitmap() {
+    let size = Vector2::new(10, 6);
+    for center in [(0.0, 0.0), (10.0, 6.0), (-3.0, 3.0), (12.0, -2.0), (-100.0, -100.0)] {
+        let mut bitmap = bitmap(size);
+        blend_circle(&mut bitmap, size, Vector2::new(center.0, center.1), 4.0, [0, 0, 0], 1.0);
+        assert_eq!(bitmap.len(), (size.x * size.y * 4) as usize);
+    }
+    let mut bitmap = bitmap(size);
+    blend_circle(&mut bitmap, size, Vector2::new(0.0, 0.0), 3.0, [0, 0, 0], 1.0);
+    assert_eq!(pixel(&bitmap, size, 0, 0), [0, 0, 0, 255]);
+    assert_eq!(pixel(&bitmap, size, 9, 5), GRAY);
+}
+
+#[test]
+fn clicks_fade_out() {
+    let start = Instant::now();
+    let mut highlights = ClickHighlights::default();
+    assert!(!highlights.is_animating(start));
+    highlights.click(click(0.5, 0.5, MouseButton::Left, start));
+    assert!(highlights.is_animating(start));
+    assert!(highlights.is_animating(start + ClickHighlights::FADE / 2));
+    assert!(!highlights.is_animating(start + ClickHighlights::FADE));
+
+    assert!(!highlights.expire(start + Duration::from_millis(100)));
+    assert!(highlights.expire(start + ClickHighlights::FADE));
+    assert_eq!(highlights.clicks(start).count(), 0);
+}
+
+#[test]
+fn clicks_outside_are_left_out() {
+    let now = Instant::now();
+    let mut highlights = ClickHighlights::default();
+    highlights.click(click(-0.1, 0.5, MouseButton::Left, now));
+    highlights.click(click(0.5, 1.5, MouseButton::Left, now));
+    assert!(!highlights.is_animating(now));
+    highlights.click(click(1.0, 0.0, MouseButton::Right, now));
+    assert_eq!(highlights.clicks(now).count(), 1);
+}
+
+#[test]
+fn the_cursor_only_moves_when_it_changes() {
+    let mut highlights = ClickHighlights::default();
+    assert!(!highlights.move_cursor(None));
+    assert!(highlights.move_cursor(Some(Vector2::new(0.25, 0.75))));
+    assert!(!highlights.move_cursor(Some(Vector2::new(0.25, 0.75))));
+    assert_eq!(highlights.cursor(), Some(Vector2::new(0.25, 0.75)));
+    assert!(highlights.move_cursor(None));
+}
+
+#[test]
+fn clicks_are_drawn_in_their_buttons_color() {
+    let size = Vector2::new(1920, 1080);
+    let now = Instant::now();
+    let mut highlights = ClickHighlights::default();
+    highlights.click(click(0.25, 0.5, MouseButton::Left, now));
+    highlights.click(click(0.75, 0.5, MouseButton::Right, now));
+    let mut bitmap = bitmap(size);
+    highlights.draw(&mut bitmap, size, now);
+
+    let left = pixel(&bitmap, size, 480, 540);
+    let right = pixel(&bitmap, size, 1440, 540);
+    assert!(left[0] > left[2], "{:?}", left);
+    assert!(right[2] > right[0], "{:?}", right);
+    // Only where they were.
+    assert_eq!(pixel(&bitmap, size, 960, 540), GRAY);
+}
+
+#[test]
+fn clicks_grow_and_fade() {
+    let size = Vector2::new(1920, 1080);
+    let start = Instant::now();
+    let mut highlights = ClickHighlights::default();
+    highlights.click(click(0.5, 0.5, MouseButton::Left, start));
+
+    let draw = |at: Instant| {
+        let mut bitmap = bitmap(size);
+        highlights.draw(&mut bitmap, size, at);
+        bitmap
+    };
+    let fresh = draw(start);
+    let later = draw(start + ClickHighlights::FADE / 2);
+    let gone = draw(start + ClickHighlights::FADE);
+    // Fainter in the middle, but reaching further out.
+    assert!(pixel(&later, size, 960, 540)[0] < pixel(&fresh, size, 960, 540)[0]);
+    assert_eq!(pixel(&fresh, size, 960 + 20, 540), GRAY);
+    assert_ne!(pixel(&later, size, 960 + 20, 540), GRAY);
+    assert!(gone.chunks_exact(4).all(|pixel| pixel == GRAY));
+}
+
+#[test]
+fn the_halo_scales_with_the_frame() {
+    let now = Instant::now();
+    let mut highlights = ClickHighlights::default();
+    highlights.move_cursor(Some(Vector2::new(0.5, 0.5)));
+
+    let reach = |size: Vector2<i32>| {
+        let mut bitmap = bitmap(size);
+        highlights.draw(&mut bitmap, size, now);
+        let y = size.y / 2;
+        (size.x / 2..size.x).take_while(|&x| pixel(&bitmap, size, x, y) != GRAY).count()
+    };
+    // 28 pixels at 1080p, and twice that at 2160p.
+    let (hd, uhd) = (reach(Vector2::new(1920, 1080)), reach(Vector2::new(3840, 2160)));
+    assert!((28..=29).contains(&hd), "{}", hd);
+    assert!((56..=57).contains(&uhd), "{}", uhd);
+}
@@ -0,0 +1,102 @@
+//! The video codecs the media engine registers, and what ends up in the offer.
+
+use std::sync::Arc;
+
+use fjarsyn::{media::ffmpeg::FFmpegTranscodeType, networking::webrtc::codecs};
+use webrtc::{
+    api::{APIBuilder, media_engine::MediaEngine},
+    peer_connection::configuration::RTCConfiguration,
+    track::track_local::{TrackLocal, track_local_static_sample::TrackLocalStaticSample},
+};
+
+async fn offer_sdp(transcode_type: FFmpegTranscodeType) -> String {
+    let mut media_engine = MediaEngine::default();
+    codecs::register_video_codecs(&mut media_engine, transcode_type).unwrap();
+    let api = APIBuilder::new().with_media_engine(media_engine).build();
+    let peer_connection = api.new_peer_connection(RTCConfiguration::default()).await.unwrap();
+    let track = Arc::new(TrackLocalStaticSample::new(
+        codecs::local_video_capability(transcode_type),
+        "video".to_owned(),
+        "stream".to_owned(),
+    ));
+    peer_connection.add_track(track as Arc<dyn TrackLocal + Send + Sync>).await.unwrap();
+    let offer = peer_connection.create_offer(None).await.unwrap();
+    peer_connection.close().await.unwrap();
+    offer.sdp
+}
+
+// The codec names of the `a=rtpmap:<pt> <name>/<clock rate>` lines.
+fn rtpmap_codecs(sdp: &str) -> Vec<String> {
+    sdp.lines()
+        .filter_map(|line| line.strip_prefix("a=rtpmap:"))
+        .filter_map(|map| map.split_once(' '))
+        .filter_map(|(_, codec)| codec.split_once('/'))
+        .map(|(name, _)| name.to_owned())
+        .collect()
+}
+
+// The `a=fmtp:<pt> <parameters>` lines, in the order the payload types are listed on the `m=video` line.
+fn offered_fmtp_lines(sdp: &str) -> Vec<String> {
+    let payload_types =
+        sdp.lines().find_map(|line| line.strip_prefix("m=video ")).unwrap().split(' ').skip(2);
+    payload_types
+        .filter_map(|payload_type| {
+            let prefix = format!("a=fmtp:{payload_type} ");
+            sdp.lines().find_map(|line| line.strip_prefix(&prefix)).map(str::to_owned)
+        })
+        .collect()
+}
+
+#[test]
+fn registers_only_the_codec_of_the_transcode_type() {
+    for &transcode_type in FFmpegTranscodeType::ALL {
+        let registered = codecs::video_codecs(transcode_type);
+        assert!(!registered.is_empty(), "{transcode_type}");
+        for codec in registered {
+            assert_eq!(codec.capability.mime_type, transcode_type.mime_type(), "{transcode_type}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn offers_only_the_codec_of_the_transcode_type() {
+    for &transcode_type in FFmpegTranscodeType::ALL {
+        let sdp = offer_sdp(transcode_type).await;
+        let offered = rtpmap_codecs(&sdp);
+        assert!(!offered.is_empty(), "{transcode_type}: {sdp}");
+        let expected = transcode_type.mime_type().trim_start_matches("video/");
+        for codec in offered {
+            assert!(codec.eq_ignore_ascii_case(expected), "{transcode_type} offered {codec}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn offers_every_decodable_profile_with_the_sent_one_first() {
+    for &transcode_type in FFmpegTranscodeType::ALL {
+        let sdp = offer_sdp(transcode_type).await;
+        let fmtp_lines = offered_fmtp_lines(&sdp);
+        let sent = codecs::local_video_capability(transcode_type).sdp_fmtp_line;
+        assert_eq!(fmtp_lines.first(), Some(&sent), "{transcode_type}: {sdp}");
+
+        let expected: &[&str] = match transcode_type {
+            FFmpegTranscodeType::H265Vulkan => &["profile-id=1"],
+            _ => &["profile-level-id=42e01f", "profile-level-id=42001f", "profile-level-id=640032"],
+        };
+        for parameter in expected {
+            assert!(
+                fmtp_lines.iter().any(|line| line.split(';').any(|p| p == *parameter)),
+                "{transcode_type} didn't offer {parameter}: {sdp}"
+            );
+        }
+    }
+}
+
+#[test]
+fn sends_the_profile_of_the_encoder() {
+    let profile = |transcode_type| codecs::local_video_capability(transcode_type).sdp_fmtp_line;
+    assert!(profile(FFmpegTranscodeType::H264Software).ends_with("profile-level-id=42e01f"));
+    assert!(profile(FFmpegTranscodeType::H264Vulkan).ends_with("profile-level-id=640032"));
+    assert!(profile(FFmpegTranscodeType::H264Nvenc).ends_with("profile-level-id=640032"));
+    assert_eq!(profile(FFmpegTranscodeType::H265Vulkan), "profile-id=1");
+}
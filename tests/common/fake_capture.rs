@@ -0,0 +1,167 @@
+//! A capture provider with a source the test drives, following the rules of the platform one: a stream at a time,
+//! and states that only a running capture leaves.
+
+use fjarsyn::{
+    capture_providers::{
+        CaptureProvider, SourceKind,
+        shared::{CaptureFramerate, CaptureItemInfo, CaptureState, FrameMeta},
+    },
+    utils::{
+        frame::Frame,
+        orientation::Orientation,
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+use futures::channel::mpsc;
+use tokio::sync::watch;
+
+const SIZE: Vector2<i32> = Vector2 { x: 32, y: 24 };
+
+#[derive(Debug, thiserror::Error)]
+pub enum FakeCaptureError {
+    #[error("Another stream is active")]
+    StreamAlreadyActive,
+    #[error("Already capturing")]
+    AlreadyCapturing,
+    #[error("Not capturing")]
+    NotCapturing,
+}
+
+#[derive(Debug)]
+pub struct FakeCapture {
+    item: String,
+    // The sender of the active stream. Dropping it ends the stream.
+    stream: Option<mpsc::UnboundedSender<Frame>>,
+    capturing: bool,
+    frames: SyntheticFrames,
+    state: watch::Sender<CaptureState>,
+    frame_meta: watch::Sender<FrameMeta>,
+}
+
+impl Default for FakeCapture {
+    fn default() -> Self {
+        Self {
+            item: "fake".to_owned(),
+            stream: None,
+            capturing: false,
+            frames: SyntheticFrames::new(SIZE, PixelFormat::BGRA8, FramePattern::Flat),
+            state: watch::Sender::default(),
+            frame_meta: watch::Sender::default(),
+        }
+    }
+}
+
+impl FakeCapture {
+    /// The source sends its first frame, which is when the capture is running.
+    pub fn source_started(&mut self) {
+        self.state.send_if_modified(|state| {
+            let starting = *state == CaptureState::Starting;
+            if starting {
+                *state = CaptureState::Capturing;
+            }
+            starting
+        });
+        self.send_frame();
+    }
+
+    /// The source sends a frame to the active stream. False if there is none to take it.
+    pub fn send_frame(&mut self) -> bool {
+        let frame = self.frames.next_frame();
+        self.frame_meta.send_modify(|meta| *meta = meta.next(&frame));
+        self.stream.as_ref().is_some_and(|stream| stream.unbounded_send(frame).is_ok())
+    }
+
+    /// The source goes away, e.g. as the shared window was closed. Only a running capture can fail.
+    pub fn close_item(&mut self) {
+        self.state.send_if_modified(|state| {
+            if *state != CaptureState::Capturing {
+                return false;
+            }
+            *state = CaptureState::Error("The capture item was closed".to_owned());
+            true
+        });
+    }
+
+    fn stream_active(&self) -> bool {
+        self.stream.as_ref().is_some_and(|stream| !stream.is_closed())
+    }
+}
+
+impl CaptureProvider for FakeCapture {
+    type Error = FakeCaptureError;
+    type Stream = mpsc::UnboundedReceiver<Frame>;
+    type CaptureItem = String;
+
+    fn create_stream(
+        &mut self,
+        _framerate: CaptureFramerate,
+        replace: bool,
+    ) -> Result<Self::Stream, Self::Error> {
+        if self.stream_active() && !replace {
+            return Err(FakeCaptureError::StreamAlreadyActive);
+        }
+        let (sender, receiver) = mpsc::unbounded();
+        self.stream = Some(sender);
+        Ok(receiver)
+    }
+
+    fn set_capture_item(&mut self, capture_item: Self::CaptureItem) -> Result<(), Self::Error> {
+        self.item = capture_item;
+        Ok(())
+    }
+
+    fn start_capture(&mut self) -> Result<(), Self::Error> {
+        if self.capturing {
+            return Err(FakeCaptureError::AlreadyCapturing);
+        }
+        self.capturing = true;
+        self.state.send_replace(CaptureState::Starting);
+        Ok(())
+    }
+
+    fn stop_capture(&mut self) -> Result<(), Self::Error> {
+        self.capturing = false;
+        self.stream = None;
+        // Also clears an error.
+        self.state.send_if_modified(|state| {
+            let changed = *state != CaptureState::Idle;
+            *state = CaptureState::Idle;
+            changed
+        });
+        Ok(())
+    }
+
+    fn recover_capture(&mut self) -> Result<Self::CaptureItem, Self::Error> {
+        if !self.capturing {
+            return Err(FakeCaptureError::NotCapturing);
+        }
+        self.state.send_replace(CaptureState::Capturing);
+        Ok(self.item.clone())
+    }
+
+    fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+
+    fn capture_item_info(&self) -> Option<CaptureItemInfo> {
+        Some(CaptureItemInfo {
+            name: self.item.clone(),
+            size: SIZE,
+            orientation: Orientation::Upright,
+            kind: SourceKind::Window,
+            applied_options: Vec::new(),
+            process_name: None,
+            icon: None,
+        })
+    }
+
+    fn subscribe_state(&self) -> watch::Receiver<CaptureState> {
+        self.state.subscribe()
+    }
+
+    fn subscribe_frame_meta(&self) -> watch::Receiver<FrameMeta> {
+        self.frame_meta.subscribe()
+    }
+}
@@ -0,0 +1,5 @@
+//! What the tests share. Each test file only uses some of it.
+#![allow(dead_code)]
+
+pub mod fake_capture;
+pub mod test_dir;
@@ -0,0 +1,32 @@
+//! Directories for the tests that write files, each test with its own, removed once the test is done with it.
+
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+/// A directory of its own, which the test starts without. Removed along with everything in it when dropped, even if
+/// the test fails.
+#[derive(Debug)]
+pub struct TestDir {
+    // Removes the directory when dropped.
+    root: TempDir,
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new(test: &str) -> Self {
+        let root = tempfile::Builder::new().prefix(&format!("fjarsyn-{test}-")).tempdir().unwrap();
+        // Inside the one that is created, so the code under test gets to create it.
+        let path = root.path().join(test);
+        Self { root, path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A file in the directory.
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
@@ -0,0 +1,127 @@
+use fjarsyn::{
+    capture_providers::shared::{CaptureFramerate, DirtyRegionMode, WgcSessionOptions},
+    config::{Config, ConfigImportError},
+};
+
+fn exported_fields(config: &Config) -> serde_json::Map<String, serde_json::Value> {
+    serde_json::from_str(&config.export().expect("Failed to export")).expect("Export isn't JSON")
+}
+
+#[test]
+fn export_leaves_out_machine_fields() {
+    let config = Config { onboarding_done: true, ..Config::default() };
+    let fields = exported_fields(&config);
+    for field in Config::MACHINE_FIELDS {
+        assert!(!fields.contains_key(*field), "{} was exported", field);
+    }
+}
+
+#[test]
+fn export_includes_every_other_field() {
+    let all = serde_json::to_value(Config::default()).expect("Failed to serialize");
+    let fields = exported_fields(&Config::default());
+    for field in all.as_object().expect("Config isn't an object").keys() {
+        assert_eq!(
+            fields.contains_key(field),
+            !Config::MACHINE_FIELDS.contains(&field.as_str()),
+            "{}",
+            field
+        );
+    }
+}
+
+#[test]
+fn round_trip_carries_the_settings_over() {
+    let exported = Config {
+        server_url: "wss://signal.example.com/ws".to_owned(),
+        bitrate: 4_000_000,
+        framerate: CaptureFramerate::FPS60,
+        gop: 60,
+        ..Config::default()
+    };
+    let other_machine = Config { onboarding_done: true, ..Config::default() };
+
+    let import = other_machine
+        .import(&exported.export().expect("Failed to export"))
+        .expect("Failed to import");
+    assert_eq!(import.changed, ["bitrate", "framerate", "gop", "server_url"]);
+    assert!(import.unknown.is_empty());
+    assert_eq!(import.config.server_url, exported.server_url);
+    assert_eq!(import.config.framerate, CaptureFramerate::FPS60);
+    // Kept from the machine it is imported on.
+    assert!(import.config.onboarding_done);
+}
+
+#[test]
+fn importing_the_same_settings_changes_nothing() {
+    let config = Config::default();
+    let import =
+        config.import(&config.export().expect("Failed to export")).expect("Failed to import");
+    assert!(import.changed.is_empty());
+}
+
+#[test]
+fn machine_fields_in_the_file_are_ignored() {
+    let config = Config::default();
+    let import = config
+        .import(r#"{ "onboarding_done": true, "bitrate": 1000000 }"#)
+        .expect("Failed to import");
+    assert!(!import.config.onboarding_done);
+    assert_eq!(import.changed, ["bitrate"]);
+}
+
+#[test]
+fn unknown_fields_are_left_out_with_a_warning() {
+    let config = Config::default();
+    let import = config
+        .import(r#"{ "bitrate": 2000000, "from_a_newer_version": 1 }"#)
+        .expect("Failed to import");
+    assert_eq!(import.unknown, ["from_a_newer_version"]);
+    assert_eq!(import.config.bitrate, 2_000_000);
+}
+
+#[test]
+fn invalid_values_are_rejected() {
+    let config = Config::default();
+    assert!(matches!(
+        config.import(r#"{ "bitrate": 0 }"#),
+        Err(ConfigImportError::InvalidField { field: "bitrate", .. })
+    ));
+    assert!(matches!(
+        config.import(r#"{ "bandwidth_cap": 0 }"#),
+        Err(ConfigImportError::InvalidField { field: "bandwidth_cap", .. })
+    ));
+    assert!(matches!(
+        config.import(r#"{ "server_url": "http://example.com" }"#),
+        Err(ConfigImportError::InvalidField { field: "server_url", .. })
+    ));
+    assert!(matches!(
+        config.import(r#"{ "framerate": "fast" }"#),
+        Err(ConfigImportError::ParseError(_))
+    ));
+    assert!(matches!(config.import("[1, 2, 3]"), Err(ConfigImportError::ParseError(_))));
+}
+
+#[test]
+fn missing_capture_session_options_keep_their_defaults() {
+    let import = Config::default()
+        .import(r#"{ "capture_session_options": { "dirty_region_mode": "ReportAndRender" } }"#)
+        .expect("Failed to import");
+    assert_eq!(
+        import.config.capture_session_options,
+        WgcSessionOptions {
+            dirty_region_mode: DirtyRegionMode::ReportAndRender,
+            ..WgcSessionOptions::default()
+        }
+    );
+}
+
+#[test]
+fn bandwidth_cap_bounds_the_bitrate() {
+    let config = Config { bitrate: 4_000_000, ..Config::default() };
+    assert_eq!(config.capped_bitrate(config.bitrate), 4_000_000);
+
+    let capped = Config { bandwidth_cap: Some(1_500_000), ..config };
+    assert_eq!(capped.capped_bitrate(capped.bitrate), 1_500_000);
+    assert_eq!(capped.capped_bitrate(1_000_000), 1_000_000);
+}
@@ -0,0 +1,130 @@
+use std::time::{Duration, SystemTime};
+
+use fjarsyn::ui::consent::{ConsentDecision, ConsentKind, ConsentRecord, ConsentStore, format_age};
+
+const PEER: &str = "peer-a";
+const KIND: ConsentKind = ConsentKind::RemoteControl;
+
+fn at(seconds: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+fn decisions(store: &ConsentStore) -> Vec<(ConsentDecision, bool)> {
+    store.records().iter().map(|record| (record.decision, record.standing)).collect()
+}
+
+#[test]
+fn nothing_is_granted_at_first() {
+    let mut store = ConsentStore::default();
+    assert!(!store.is_granted(PEER, KIND));
+    assert!(!store.accept_standing(PEER, KIND, at(1)));
+    assert!(store.records().is_empty());
+}
+
+#[test]
+fn records_each_decision_with_its_time() {
+    let mut store = ConsentStore::default();
+    store.decide(PEER, KIND, true, false, at(1));
+    store.decide("peer-b", KIND, false, false, at(2));
+
+    assert_eq!(
+        store.records(),
+        [
+            ConsentRecord {
+                peer: PEER.to_owned(),
+                kind: KIND,
+                decision: ConsentDecision::Accepted,
+                at: at(1),
+                standing: false,
+            },
+            ConsentRecord {
+                peer: "peer-b".to_owned(),
+                kind: KIND,
+                decision: ConsentDecision::Declined,
+                at: at(2),
+                standing: false,
+            },
+        ]
+    );
+    // Accepting once doesn't let the peer do it again without asking.
+    assert!(!store.is_granted(PEER, KIND));
+}
+
+#[test]
+fn always_allowing_grants_it_to_that_peer_only() {
+    let mut store = ConsentStore::default();
+    store.decide(PEER, KIND, true, true, at(1));
+    assert!(store.is_granted(PEER, KIND));
+    assert!(!store.is_granted("peer-b", KIND));
+
+    assert!(store.accept_standing(PEER, KIND, at(2)));
+    assert_eq!(
+        decisions(&store),
+        [(ConsentDecision::Accepted, false), (ConsentDecision::Accepted, true)]
+    );
+}
+
+#[test]
+fn declining_never_grants() {
+    let mut store = ConsentStore::default();
+    store.decide(PEER, KIND, false, true, at(1));
+    assert!(!store.is_granted(PEER, KIND));
+    assert!(store.grants().is_empty());
+}
+
+#[test]
+fn granting_twice_keeps_one_grant() {
+    let mut store = ConsentStore::default();
+    store.decide(PEER, KIND, true, true, at(1));
+    store.decide(PEER, KIND, true, true, at(2));
+    assert_eq!(store.grants().len(), 1);
+    assert_eq!(store.grants()[0].granted_at, at(1));
+}
+
+#[test]
+fn revoking_takes_the_grant_back() {
+    let mut store = ConsentStore::default();
+    store.decide(PEER, KIND, true, true, at(1));
+    assert!(store.revoke(PEER, KIND, at(2)));
+    assert!(!store.is_granted(PEER, KIND));
+    assert!(!store.accept_standing(PEER, KIND, at(3)));
+    assert_eq!(decisions(&store).last(), Some(&(ConsentDecision::Revoked, false)));
+
+    // Nothing left to revoke, so nothing is recorded.
+    assert!(!store.revoke(PEER, KIND, at(4)));
+    assert_eq!(store.records().len(), 2);
+}
+
+#[test]
+fn drops_the_oldest_records() {
+    let mut store = ConsentStore::default();
+    for seconds in 0..ConsentStore::MAX_RECORDS as u64 + 5 {
+        store.decide(PEER, KIND, false, false, at(seconds));
+    }
+    assert_eq!(store.records().len(), ConsentStore::MAX_RECORDS);
+    assert_eq!(store.records()[0].at, at(5));
+}
+
+#[test]
+fn survives_the_consents_file() {
+    let mut store = ConsentStore::default();
+    store.decide(PEER, KIND, true, true, at(1));
+    store.decide("peer-b", KIND, false, false, at(2));
+
+    let json = serde_json::to_string(&store).expect("Failed to serialize");
+    let loaded: ConsentStore = serde_json::from_str(&json).expect("Failed to parse");
+    assert_eq!(loaded.grants(), store.grants());
+    assert_eq!(loaded.records(), store.records());
+
+    // Missing fields are left empty.
+    let empty: ConsentStore = serde_json::from_str("{}").expect("Failed to parse");
+    assert!(empty.grants().is_empty() && empty.records().is_empty());
+}
+
+#[test]
+fn formats_ages_roughly() {
+    assert_eq!(format_age(Duration::from_secs(30)), "just now");
+    assert_eq!(format_age(Duration::from_secs(60)), "1 minute ago");
+    assert_eq!(format_age(Duration::from_secs(3 * 3600 + 100)), "3 hours ago");
+    assert_eq!(format_age(Duration::from_secs(2 * 86_400)), "2 days ago");
+}
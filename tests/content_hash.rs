@@ -0,0 +1,122 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use fjarsyn::utils::{
+    content_hash::{SAMPLE_BUDGET, UnchangedFrames, row_stride, sampled_hash},
+    test_support::RESOLUTIONS,
+    vector2::Vector2,
+};
+
+fn bitmap(size: Vector2<i32>) -> Vec<u8> {
+    (0..size.x * size.y * 4).map(|i| (i % 251) as u8).collect()
+}
+
+fn set_pixel(bitmap: &mut [u8], size: Vector2<i32>, x: i32, y: i32, value: u8) {
+    bitmap[((y * size.x + x) * 4) as usize] = value;
+}
+
+#[test]
+fn the_same_picture_hashes_the_same() {
+    let size = Vector2::new(640, 360);
+    let frame = bitmap(size);
+    assert_eq!(sampled_hash(&frame, size, 4), sampled_hash(&frame.clone(), size, 4));
+}
+
+#[test]
+fn changes_in_sampled_rows_change_the_hash() {
+    let size = Vector2::new(3840, 2160);
+    let stride = row_stride(size, 4) as i32;
+    let frame = bitmap(size);
+    let hash = sampled_hash(&frame, size, 4);
+    for y in (0..size.y).step_by(stride as usize) {
+        let mut changed = frame.clone();
+        set_pixel(&mut changed, size, (y * 7) % size.x, y, 255);
+        assert_ne!(sampled_hash(&changed, size, 4), hash, "row {}", y);
+    }
+
+    // Rows between the samples aren't looked at, which is what sending a frame every so often anyway makes up for.
+    assert!(stride > 1);
+    let mut changed = frame.clone();
+    set_pixel(&mut changed, size, 0, 1, 255);
+    assert_eq!(sampled_hash(&changed, size, 4), hash);
+}
+
+#[test]
+fn small_frames_are_hashed_whole() {
+    let size = Vector2::new(320, 240);
+    assert_eq!(row_stride(size, 4), 1);
+    let frame = bitmap(size);
+    let mut changed = frame.clone();
+    set_pixel(&mut changed, size, 319, 239, 255);
+    assert_ne!(sampled_hash(&changed, size, 4), sampled_hash(&frame, size, 4));
+}
+
+#[test]
+fn the_stride_keeps_within_the_budget_at_any_resolution() {
+    for &(name, size) in RESOLUTIONS.iter().chain(&[("8k", Vector2::new(7680, 4320))]) {
+        let stride = row_stride(size, 4);
+        let rows = (size.y as usize).div_ceil(stride);
+        let hashed = rows * size.x as usize * 4;
+        // Rounding up to a whole row can go over by at most one.
+        assert!(hashed <= SAMPLE_BUDGET + size.x as usize * 4, "{}: {} bytes", name, hashed);
+        // And it doesn't sample far fewer rows than it could.
+        assert!(hashed * 2 > SAMPLE_BUDGET.min((size.x * size.y * 4) as usize), "{}", name);
+    }
+}
+
+#[test]
+fn the_shape_is_part_of_the_hash() {
+    let frame = vec![7; 64 * 16 * 4];
+    assert_ne!(
+        sampled_hash(&frame, Vector2::new(64, 16), 4),
+        sampled_hash(&frame, Vector2::new(16, 64), 4)
+    );
+}
+
+#[test]
+fn frames_that_differ_in_one_pixel_dont_collide() {
+    let size = Vector2::new(64, 64);
+    let mut frame = vec![0; 64 * 64 * 4];
+    let mut hashes = HashSet::new();
+    for i in 0..100_000u32 {
+        let pixel = (i as usize % (64 * 64)) * 4;
+        frame[pixel..pixel + 4].copy_from_slice(&i.to_le_bytes());
+        assert!(hashes.insert(sampled_hash(&frame, size, 4)), "collision at {}", i);
+    }
+}
+
+#[test]
+fn unchanged_frames_are_skipped_for_up_to_a_second() {
+    let start = Instant::now();
+    let mut unchanged = UnchangedFrames::default();
+    assert!(!unchanged.skip(1, start));
+    assert!(unchanged.skip(1, start + Duration::from_millis(16)));
+    assert!(unchanged.skip(1, start + Duration::from_millis(900)));
+    // Sent anyway, in case the hash missed a change.
+    assert!(!unchanged.skip(1, start + UnchangedFrames::MAX_SKIP));
+    assert!(unchanged.skip(1, start + UnchangedFrames::MAX_SKIP + Duration::from_millis(16)));
+    assert_eq!(unchanged.skipped(), 3);
+}
+
+#[test]
+fn changed_frames_are_encoded_and_become_the_last() {
+    let start = Instant::now();
+    let mut unchanged = UnchangedFrames::default();
+    assert!(!unchanged.skip(1, start));
+    assert!(!unchanged.skip(2, start));
+    assert!(unchanged.skip(2, start));
+    // Back to the first picture, which is a change from the last one.
+    assert!(!unchanged.skip(1, start));
+}
+
+#[test]
+fn reset_makes_the_next_frame_encode() {
+    let start = Instant::now();
+    let mut unchanged = UnchangedFrames::default();
+    unchanged.skip(1, start);
+    unchanged.reset();
+    assert!(!unchanged.skip(1, start));
+    assert!(unchanged.skip(1, start));
+}
@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::media::{
+    content_switcher::{ContentSwitcher, HOLD},
+    ffmpeg::{ContentHint, ContentType, FFmpegTranscodeType},
+};
+
+const FRAME: Duration = Duration::from_millis(33);
+
+// Feeds frames with the same dirty fraction for `duration`, returning the switches.
+fn feed(
+    switcher: &mut ContentSwitcher,
+    start: Instant,
+    duration: Duration,
+    dirty_fraction: f32,
+) -> (Instant, Vec<ContentType>) {
+    let mut now = start;
+    let mut switches = Vec::new();
+    while now < start + duration {
+        now += FRAME;
+        switches.extend(switcher.observe(now, dirty_fraction));
+    }
+    (now, switches)
+}
+
+#[test]
+fn auto_starts_out_as_text() {
+    assert_eq!(ContentSwitcher::new(ContentHint::Auto).content(), ContentType::Text);
+    assert_eq!(ContentSwitcher::new(ContentHint::Motion).content(), ContentType::Motion);
+}
+
+#[test]
+fn sustained_motion_switches_once() {
+    let mut switcher = ContentSwitcher::new(ContentHint::Auto);
+    let (_, switches) = feed(&mut switcher, Instant::now(), HOLD * 3, 0.8);
+    assert_eq!(switches, [ContentType::Motion]);
+}
+
+#[test]
+fn a_burst_shorter_than_the_hold_doesnt_switch() {
+    let mut switcher = ContentSwitcher::new(ContentHint::Auto);
+    let (now, switches) = feed(&mut switcher, Instant::now(), HOLD / 3, 1.0);
+    assert!(switches.is_empty());
+    let (_, switches) = feed(&mut switcher, now, HOLD * 2, 0.0);
+    assert!(switches.is_empty());
+    assert_eq!(switcher.content(), ContentType::Text);
+}
+
+#[test]
+fn stays_put_between_the_thresholds() {
+    let mut switcher = ContentSwitcher::new(ContentHint::Auto);
+    let (now, _) = feed(&mut switcher, Instant::now(), HOLD * 2, 0.8);
+    assert_eq!(switcher.content(), ContentType::Motion);
+
+    let (_, switches) = feed(&mut switcher, now, HOLD * 3, 0.15);
+    assert!(switches.is_empty());
+}
+
+#[test]
+fn switches_back_to_text_once_still() {
+    let mut switcher = ContentSwitcher::new(ContentHint::Auto);
+    let (now, _) = feed(&mut switcher, Instant::now(), HOLD * 2, 0.8);
+    let (_, switches) = feed(&mut switcher, now, HOLD * 3, 0.01);
+    assert_eq!(switches, [ContentType::Text]);
+}
+
+#[test]
+fn a_long_gap_counts_for_the_frame_after_it() {
+    let mut switcher = ContentSwitcher::new(ContentHint::Auto);
+    let (now, _) = feed(&mut switcher, Instant::now(), HOLD * 2, 0.8);
+    // Nothing changed on screen for a while, so no frames came.
+    let now = now + HOLD * 2;
+    assert_eq!(switcher.observe(now, 0.01), None);
+    assert_eq!(switcher.observe(now + HOLD, 0.01), Some(ContentType::Text));
+}
+
+#[test]
+fn fixed_hints_ignore_the_frames() {
+    let mut switcher = ContentSwitcher::new(ContentHint::Text);
+    let (_, switches) = feed(&mut switcher, Instant::now(), HOLD * 3, 1.0);
+    assert!(switches.is_empty());
+    assert_eq!(switcher.content(), ContentType::Text);
+}
+
+#[test]
+fn setting_the_hint_switches_right_away() {
+    let mut switcher = ContentSwitcher::new(ContentHint::Auto);
+    assert_eq!(switcher.set_hint(ContentHint::Motion), Some(ContentType::Motion));
+    assert_eq!(switcher.set_hint(ContentHint::Motion), None);
+    // Auto carries on from where the fixed hint left it.
+    assert_eq!(switcher.set_hint(ContentHint::Auto), None);
+    assert_eq!(switcher.content(), ContentType::Motion);
+}
+
+#[test]
+fn every_transcode_type_has_options_for_both_contents() {
+    for transcoding_type in FFmpegTranscodeType::ALL {
+        let text = transcoding_type.content_options(ContentType::Text);
+        let motion = transcoding_type.content_options(ContentType::Motion);
+        assert!(!text.is_empty(), "{}", transcoding_type);
+        assert_ne!(text, motion, "{}", transcoding_type);
+    }
+}
@@ -0,0 +1,40 @@
+use fjarsyn::media::ffmpeg::{DecodeAccel, FFmpegDecoder, FFmpegDecoderError};
+
+#[test]
+fn every_choice_falls_back_to_software() {
+    for &accel in DecodeAccel::ALL {
+        assert_eq!(accel.probe_order().last(), Some(&DecodeAccel::Software));
+        assert!(!accel.probe_order().contains(&DecodeAccel::Auto));
+    }
+}
+
+#[test]
+fn auto_prefers_d3d11va() {
+    assert_eq!(
+        DecodeAccel::Auto.probe_order(),
+        &[DecodeAccel::D3D11VA, DecodeAccel::Vulkan, DecodeAccel::Software]
+    );
+}
+
+#[test]
+fn software_decodes_without_a_device() {
+    let decoder = FFmpegDecoder::for_mime_type("video/H264", DecodeAccel::Software)
+        .expect("Failed to create decoder");
+    assert_eq!(decoder.accel(), DecodeAccel::Software);
+    assert!(!decoder.accel().is_hardware());
+}
+
+#[test]
+fn auto_settles_on_one_that_works() {
+    let decoder = FFmpegDecoder::for_mime_type("video/H264", DecodeAccel::Auto)
+        .expect("Failed to create decoder");
+    assert_ne!(decoder.accel(), DecodeAccel::Auto);
+}
+
+#[test]
+fn unknown_codecs_are_rejected() {
+    assert!(matches!(
+        FFmpegDecoder::for_mime_type("video/AV1", DecodeAccel::Auto),
+        Err(FFmpegDecoderError::UnsupportedCodec(_))
+    ));
+}
@@ -0,0 +1,206 @@
+//! The decoder worker decoding the packets of a track as they arrive, and getting past the ones it can't.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use fjarsyn::{
+    media::{
+        decoder_worker::{DecoderHandle, DecoderWorker},
+        ffmpeg::{DecodeAccel, FFmpegDecoder, FFmpegEncoder, FFmpegTranscodeType, RateControl},
+    },
+    networking::webrtc::TrackId,
+    utils::{frame::Frame, pixel_format::PixelFormat, vector2::Vector2},
+};
+use tokio::sync::{Mutex, mpsc};
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+const SIZE: Vector2<i32> = Vector2 { x: 64, y: 48 };
+// Far enough apart that the gray of a frame is still told apart from its neighbours' after encoding.
+const GRAY_STEP: u8 = 16;
+const FRAMES: u8 = 15;
+// Where the stream has a keyframe besides the first.
+const KEYFRAME: u8 = 8;
+
+// Frames of ever lighter gray, one packet each, so the gray of a decoded frame says which packet it was.
+fn numbered_packets() -> Vec<Bytes> {
+    let mut encoder =
+        FFmpegEncoder::new(1_000_000, 30.0, PixelFormat::RGBA8, None, 30, RateControl::Variable)
+            .unwrap();
+    let mut packets = Vec::new();
+    for number in 0..FRAMES {
+        if number == KEYFRAME {
+            encoder.request_keyframe();
+        }
+        let bitmap = vec![gray(number); (SIZE.x * SIZE.y * 4) as usize];
+        packets.extend(
+            encoder.encode(&bitmap, FFmpegTranscodeType::H264Software, SIZE.x, SIZE.y).unwrap(),
+        );
+    }
+    packets.extend(encoder.flush().unwrap());
+    packets.into_iter().map(Bytes::from).collect()
+}
+
+fn gray(number: u8) -> u8 {
+    number * GRAY_STEP + GRAY_STEP / 2
+}
+
+// Which of the numbered packets the frame was decoded from.
+fn number(frame: &Frame) -> u8 {
+    let center = ((SIZE.y / 2 * SIZE.x + SIZE.x / 2) * 4) as usize;
+    frame.data[center] / GRAY_STEP
+}
+
+fn spawn(packets: mpsc::Receiver<Bytes>) -> DecoderHandle {
+    spawn_counting(packets, Arc::default())
+}
+
+// Counts the decoders the worker creates, including those replacing one that got stuck.
+fn spawn_counting(packets: mpsc::Receiver<Bytes>, created: Arc<AtomicUsize>) -> DecoderHandle {
+    DecoderWorker::spawn(
+        move |accel| {
+            created.fetch_add(1, Ordering::Relaxed);
+            FFmpegDecoder::new(FFmpegTranscodeType::H264Software, accel)
+        },
+        DecodeAccel::Software,
+        TrackId::default(),
+        Arc::new(Mutex::new(packets)),
+        None,
+        None,
+    )
+    .unwrap()
+}
+
+// The numbers of the frames decoded until the packets end. Frames are dropped rather than queued when they aren't
+// taken in time, so some may be missing.
+async fn decoded_numbers(decoder: &DecoderHandle) -> Vec<u8> {
+    let frames = decoder.frames();
+    let mut frames = frames.lock().await;
+    let mut numbers = Vec::new();
+    while let Ok(Some(frame)) = tokio::time::timeout(RECV_TIMEOUT, frames.recv()).await {
+        numbers.push(number(&frame));
+        if numbers.last() == Some(&(FRAMES - 1)) {
+            break;
+        }
+    }
+    numbers
+}
+
+#[tokio::test]
+async fn decodes_the_packets_in_the_order_they_arrive() {
+    let packets = numbered_packets();
+    assert_eq!(packets.len(), FRAMES as usize);
+    let (packet_tx, packet_rx) = mpsc::channel(packets.len());
+    // All at once, so they are queued up by the time the worker gets to them.
+    for packet in packets {
+        packet_tx.send(packet).await.unwrap();
+    }
+    let decoder = spawn(packet_rx);
+
+    let numbers = decoded_numbers(&decoder).await;
+    assert!(numbers.len() > 1, "only decoded {numbers:?}");
+    assert!(numbers.is_sorted_by(|a, b| a < b), "decoded out of order: {numbers:?}");
+}
+
+#[tokio::test]
+async fn decodes_each_packet_as_it_arrives() {
+    let (packet_tx, packet_rx) = mpsc::channel(1);
+    let decoder = spawn(packet_rx);
+    let frames = decoder.frames();
+
+    // One at a time, so every frame is taken before the next is decoded.
+    for (expected, packet) in (0..FRAMES).zip(numbered_packets()) {
+        packet_tx.send(packet).await.unwrap();
+        let frame = tokio::time::timeout(RECV_TIMEOUT, frames.lock().await.recv())
+            .await
+            .unwrap()
+            .expect("nothing was decoded");
+        assert_eq!(number(&frame), expected);
+    }
+}
+
+// What packet loss and corruption that slipped past the depacketizer leave of a packet.
+fn garbled(packet: &[u8]) -> Vec<Bytes> {
+    let truncated = packet[..packet.len() / 2].to_vec();
+    let mut flipped = packet.to_vec();
+    for byte in flipped.iter_mut().skip(8).step_by(3) {
+        *byte ^= 0x5A;
+    }
+    // A start code and a slice header, followed by nothing that makes sense.
+    let junk =
+        [0, 0, 0, 1, 0x41].into_iter().chain((0..200u8).map(|i| i.wrapping_mul(37))).collect();
+    // Not even a start code.
+    let noise = (0..64u8).map(|i| i.wrapping_mul(91) ^ 0xC3).collect();
+    [truncated, flipped, junk, noise].map(Bytes::from).into()
+}
+
+// Waits for the frame decoded from the numbered packet, skipping what was made of the garbled ones.
+async fn wait_for(decoder: &DecoderHandle, expected: u8) {
+    let frames = decoder.frames();
+    let mut frames = frames.lock().await;
+    tokio::time::timeout(RECV_TIMEOUT, async {
+        loop {
+            let frame = frames.recv().await.expect("the worker stopped");
+            if number(&frame) == expected {
+                return;
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("frame {expected} wasn't decoded"));
+}
+
+#[tokio::test]
+async fn recovers_from_garbled_packets_at_the_next_keyframe() {
+    let packets = numbered_packets();
+    let (packet_tx, packet_rx) = mpsc::channel(1);
+    let decoder = spawn(packet_rx);
+
+    for (number, packet) in (0..KEYFRAME / 2).zip(&packets) {
+        packet_tx.send(packet.clone()).await.unwrap();
+        wait_for(&decoder, number).await;
+    }
+    // The packets up to the keyframe are lost, and what arrives in their place is garbage.
+    for packet in &packets[(KEYFRAME / 2) as usize..KEYFRAME as usize] {
+        for garbled in garbled(packet) {
+            packet_tx.send(garbled).await.unwrap();
+        }
+    }
+    for (number, packet) in (KEYFRAME..FRAMES).zip(&packets[KEYFRAME as usize..]) {
+        packet_tx.send(packet.clone()).await.unwrap();
+        wait_for(&decoder, number).await;
+    }
+}
+
+#[tokio::test]
+async fn recovers_from_more_garbage_than_the_decoder_gets_stuck_on() {
+    let packets = numbered_packets();
+    let (packet_tx, packet_rx) = mpsc::channel(1);
+    let created = Arc::new(AtomicUsize::new(0));
+    let decoder = spawn_counting(packet_rx, created.clone());
+    assert_eq!(created.load(Ordering::Relaxed), 1);
+
+    packet_tx.send(packets[0].clone()).await.unwrap();
+    wait_for(&decoder, 0).await;
+    // Well past the failures in a row after which the decoder is replaced.
+    for packet in &packets[1..KEYFRAME as usize] {
+        for _ in 0..10 {
+            for garbled in garbled(packet) {
+                packet_tx.send(garbled).await.unwrap();
+            }
+        }
+    }
+    for (number, packet) in (KEYFRAME..FRAMES).zip(&packets[KEYFRAME as usize..]) {
+        packet_tx.send(packet.clone()).await.unwrap();
+        wait_for(&decoder, number).await;
+    }
+
+    // However many were needed, they all came from the factory, and none fell back from anything.
+    assert!(created.load(Ordering::Relaxed) >= 1);
+    assert_eq!(decoder.stats().accel, DecodeAccel::Software);
+}
@@ -0,0 +1,101 @@
+use fjarsyn::networking::webrtc::DepacketTuner;
+
+const PACKETS: u64 = 1000;
+
+fn clean(tuner: &mut DepacketTuner, intervals: u32) {
+    for _ in 0..intervals {
+        tuner.update(PACKETS, 0);
+    }
+}
+
+#[test]
+fn starts_at_the_least() {
+    let tuner = DepacketTuner::new(64, 1000);
+    assert_eq!(tuner.latency(), 64);
+}
+
+#[test]
+fn grows_when_too_many_are_dropped() {
+    let mut tuner = DepacketTuner::new(64, 1000);
+    assert_eq!(tuner.update(PACKETS, 50), Some(128));
+    assert_eq!(tuner.latency(), 128);
+}
+
+#[test]
+fn ignores_drops_below_the_threshold() {
+    let mut tuner = DepacketTuner::new(64, 1000);
+    assert_eq!(tuner.update(PACKETS, 2), None);
+    assert_eq!(tuner.latency(), 64);
+}
+
+#[test]
+fn waits_for_a_change_to_take_effect_before_growing_again() {
+    let mut tuner = DepacketTuner::new(64, 1000);
+    tuner.update(PACKETS, 50);
+
+    // The drops keep coming until the next keyframe picks up the change.
+    for _ in 0..DepacketTuner::COOLDOWN_INTERVALS {
+        assert_eq!(tuner.update(PACKETS, 50), None);
+    }
+    assert_eq!(tuner.update(PACKETS, 50), Some(256));
+}
+
+#[test]
+fn never_grows_past_the_most() {
+    let mut tuner = DepacketTuner::new(64, 200);
+    for _ in 0..20 {
+        tuner.update(PACKETS, 50);
+    }
+    assert_eq!(tuner.latency(), 200);
+}
+
+#[test]
+fn shrinks_once_the_link_is_clean_for_a_while() {
+    let mut tuner = DepacketTuner::new(64, 1000);
+    tuner.update(PACKETS, 50);
+    clean(&mut tuner, DepacketTuner::COOLDOWN_INTERVALS);
+    tuner.update(PACKETS, 50);
+    assert_eq!(tuner.latency(), 256);
+
+    clean(&mut tuner, DepacketTuner::CLEAN_INTERVALS - 1);
+    assert_eq!(tuner.latency(), 256);
+    assert_eq!(tuner.update(PACKETS, 0), Some(128));
+
+    // Never below where it started.
+    clean(&mut tuner, DepacketTuner::CLEAN_INTERVALS * 4);
+    assert_eq!(tuner.latency(), 64);
+}
+
+#[test]
+fn occasional_drops_keep_it_from_shrinking() {
+    let mut tuner = DepacketTuner::new(64, 1000);
+    tuner.update(PACKETS, 50);
+
+    for _ in 0..4 {
+        clean(&mut tuner, DepacketTuner::CLEAN_INTERVALS - 1);
+        tuner.update(PACKETS, 1);
+    }
+    assert_eq!(tuner.latency(), 128);
+}
+
+#[test]
+fn idle_intervals_count_for_nothing() {
+    let mut tuner = DepacketTuner::new(64, 1000);
+    tuner.update(PACKETS, 50);
+
+    for _ in 0..DepacketTuner::CLEAN_INTERVALS * 2 {
+        assert_eq!(tuner.update(0, 0), None);
+    }
+    assert_eq!(tuner.latency(), 128);
+}
+
+#[test]
+fn lowering_the_most_applies_right_away() {
+    let mut tuner = DepacketTuner::new(64, 1000);
+    tuner.update(PACKETS, 50);
+    tuner.set_max(100);
+    assert_eq!(tuner.latency(), 100);
+
+    tuner.set_max(32);
+    assert_eq!(tuner.latency(), 32);
+}
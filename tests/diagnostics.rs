@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use bifrost::SignalingServer;
+use fjarsyn::{
+    diagnostics::{
+        Check, CheckResult, CheckStatus, DiagnosticsOptions, report, run, timer_resolution,
+    },
+    media::ffmpeg::{DecodeAccel, FFmpegTranscodeType},
+};
+use tokio::net::TcpListener;
+
+async fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { SignalingServer::new().serve(listener).await });
+    format!("ws://{}", addr)
+}
+
+// A port nothing listens on, as it was just freed.
+async fn closed_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("ws://{}", listener.local_addr().unwrap())
+}
+
+fn options(server_url: String) -> DiagnosticsOptions {
+    DiagnosticsOptions {
+        server_url,
+        transcoding_type: FFmpegTranscodeType::H264Software,
+        decode_accel: DecodeAccel::Software,
+        applied_session_options: None,
+    }
+}
+
+#[tokio::test]
+async fn reaches_the_signaling_server() {
+    let result = run(Check::SignalingServer, &options(start_server().await)).await;
+    assert_eq!(result.status, CheckStatus::Passed, "{}", result.detail);
+    assert!(result.detail.contains("short code"), "{}", result.detail);
+}
+
+#[tokio::test]
+async fn reports_an_unreachable_signaling_server() {
+    let result = run(Check::SignalingServer, &options(closed_server().await)).await;
+    assert_eq!(result.status, CheckStatus::Failed);
+    assert!(!result.detail.is_empty());
+}
+
+#[tokio::test]
+async fn skips_the_hardware_encoder_when_software_is_selected() {
+    let result = run(Check::HardwareEncoder, &options(String::new())).await;
+    assert_eq!(result.status, CheckStatus::Skipped);
+}
+
+#[tokio::test]
+async fn encodes_and_decodes_a_test_frame() {
+    for check in [Check::SoftwareEncoder, Check::DecoderRoundTrip] {
+        let result = run(check, &options(String::new())).await;
+        assert_eq!(result.status, CheckStatus::Passed, "{}: {}", check, result.detail);
+    }
+}
+
+#[test]
+fn rates_the_timer_by_the_mean_sleep() {
+    let millis =
+        |millis: &[u64]| millis.iter().map(|&m| Duration::from_millis(m)).collect::<Vec<_>>();
+    let cases = [
+        (millis(&[1, 1, 2, 1]), CheckStatus::Passed),
+        (millis(&[1, 1, 1, 15]), CheckStatus::Warning),
+        (millis(&[15, 16, 15, 16]), CheckStatus::Warning),
+        (Vec::new(), CheckStatus::Skipped),
+    ];
+    for (samples, status) in cases {
+        assert_eq!(timer_resolution(&samples).0, status, "{:?}", samples);
+    }
+}
+
+#[test]
+fn report_has_a_line_per_check() {
+    let results = [
+        CheckResult {
+            check: Check::GraphicsDevice,
+            status: CheckStatus::Passed,
+            detail: "Test Adapter, feature level 11_1".to_owned(),
+            duration: Duration::from_millis(42),
+        },
+        CheckResult {
+            check: Check::Stun,
+            status: CheckStatus::Failed,
+            detail: "No answer within 5s".to_owned(),
+            duration: Duration::from_secs(5),
+        },
+    ];
+    let report = report(&results);
+    let lines: Vec<_> = report.lines().collect();
+
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("Fjarsyn "), "{}", lines[0]);
+    assert_eq!(lines[1], "[PASS] Graphics device: Test Adapter, feature level 11_1 (42 ms)");
+    assert_eq!(lines[2], "[FAIL] Public address (STUN): No answer within 5s (5000 ms)");
+}
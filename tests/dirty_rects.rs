@@ -0,0 +1,95 @@
+use fjarsyn::utils::{
+    dirty_rects::{self, MAX_DIRTY_RECTS},
+    rect::Rect,
+    vector2::Vector2,
+};
+
+const FRAME: Vector2<i32> = Vector2 { x: 1920, y: 1080 };
+
+// Deterministic xorshift, so a failing case can be found again.
+fn random_rects(seed: u64, count: usize) -> Vec<Rect<i32>> {
+    let mut state = seed;
+    let mut next = move |max: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % max) as i32
+    };
+    (0..count)
+        .map(|_| {
+            // Mostly tiny rects like the ones WGC reports, with the odd empty one.
+            let x = next(FRAME.x as u64);
+            let y = next(FRAME.y as u64);
+            Rect::new(x, y, next(64), next(64))
+        })
+        .collect()
+}
+
+#[test]
+fn output_covers_the_input_within_the_cap() {
+    for seed in 1..200u64 {
+        let rects = random_rects(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15), (seed % 300) as usize);
+        for max in [1, 4, MAX_DIRTY_RECTS] {
+            let coalesced = dirty_rects::coalesce(rects.iter().copied(), max);
+            assert!(coalesced.len() <= max, "{} rects over the cap of {}", coalesced.len(), max);
+            for rect in rects.iter().filter(|rect| !rect.is_empty()) {
+                assert!(
+                    coalesced.iter().any(|covering| covering.contains(rect)),
+                    "{:?} isn't covered by {:?}",
+                    rect,
+                    coalesced
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn output_rects_do_not_overlap() {
+    for seed in 1..200u64 {
+        let rects = random_rects(seed.wrapping_mul(0x2545_F491_4F6C_DD1D), (seed % 100) as usize);
+        let coalesced = dirty_rects::coalesce(rects, usize::MAX);
+        for (i, a) in coalesced.iter().enumerate() {
+            for b in &coalesced[i + 1..] {
+                assert!(!a.touches(b), "{:?} and {:?} should have been merged", a, b);
+            }
+        }
+        let fraction = dirty_rects::area_fraction(&coalesced, FRAME);
+        assert!((0.0..=1.0).contains(&fraction));
+    }
+}
+
+#[test]
+fn merges_touching_rects() {
+    let rects = [Rect::new(0, 0, 10, 10), Rect::new(10, 0, 10, 10), Rect::new(100, 100, 5, 5)];
+    assert_eq!(
+        dirty_rects::coalesce(rects, MAX_DIRTY_RECTS),
+        vec![Rect::new(0, 0, 20, 10), Rect::new(100, 100, 5, 5)]
+    );
+}
+
+#[test]
+fn merges_rects_that_only_touch_once_grown() {
+    // The third rect bridges the first two, which then have to be merged too.
+    let rects = [Rect::new(0, 0, 10, 10), Rect::new(30, 0, 10, 10), Rect::new(10, 0, 20, 10)];
+    assert_eq!(dirty_rects::coalesce(rects, MAX_DIRTY_RECTS), vec![Rect::new(0, 0, 40, 10)]);
+}
+
+#[test]
+fn falls_back_to_the_bounding_rect() {
+    let rects = [Rect::new(0, 0, 1, 1), Rect::new(50, 50, 1, 1), Rect::new(100, 10, 1, 1)];
+    assert_eq!(dirty_rects::coalesce(rects, 2), vec![Rect::new(0, 0, 101, 51)]);
+}
+
+#[test]
+fn drops_empty_rects() {
+    let rects = [Rect::new(5, 5, 0, 10), Rect::new(5, 5, 10, -1)];
+    assert!(dirty_rects::coalesce(rects, MAX_DIRTY_RECTS).is_empty());
+    assert_eq!(dirty_rects::area_fraction(&[], FRAME), 0.0);
+}
+
+#[test]
+fn fraction_of_the_whole_frame_is_one() {
+    assert_eq!(dirty_rects::area_fraction(&[Rect::new(0, 0, FRAME.x, FRAME.y)], FRAME), 1.0);
+    assert_eq!(dirty_rects::area_fraction(&[Rect::new(0, 0, 960, 1080)], FRAME), 0.5);
+}
@@ -0,0 +1,158 @@
+use fjarsyn::{
+    capture_providers::shared::MonitorGeometry,
+    utils::{
+        dpi::{CaptureTransform, DisplayLayout, ScreenSpace, scale_factor},
+        rect::Rect,
+        vector2::Vector2,
+    },
+};
+
+// A 1920x1080 primary monitor, with a 2560x1440 secondary one to its right. Both in physical pixels.
+fn layout(primary_scale: f32, secondary_scale: f32, space: ScreenSpace) -> DisplayLayout {
+    DisplayLayout::new(
+        vec![
+            MonitorGeometry { bounds: Rect::new(0, 0, 1920, 1080), scale_factor: primary_scale },
+            MonitorGeometry {
+                bounds: Rect::new(1920, 0, 2560, 1440),
+                scale_factor: secondary_scale,
+            },
+        ],
+        space,
+    )
+}
+
+fn point(x: i32, y: i32) -> Vector2<i32> {
+    Vector2::new(x, y)
+}
+
+#[test]
+fn scale_factor_of_common_dpis() {
+    assert_eq!(scale_factor(96), 1.0);
+    assert_eq!(scale_factor(144), 1.5);
+    assert_eq!(scale_factor(192), 2.0);
+}
+
+#[test]
+fn physical_coordinates_are_left_as_they_are() {
+    for scale in [1.0_f32, 1.5, 2.0] {
+        let layout = layout(scale, scale, ScreenSpace::Physical);
+        for p in [point(100, 100), point(1920 + 500, 700), point(-50, -50)] {
+            assert_eq!(layout.to_physical(p), p);
+            assert_eq!(layout.to_screen(p), p);
+        }
+    }
+}
+
+#[test]
+fn unaware_coordinates_scale_with_each_monitor() {
+    // 100% on the primary, 200% on the secondary.
+    let layout = layout(1.0, 2.0, ScreenSpace::Scaled(1.0));
+    assert_eq!(layout.to_physical(point(100, 100)), point(100, 100));
+    // The secondary keeps its corner, and shows as 1280x720.
+    assert_eq!(layout.to_physical(point(1920 + 100, 50)), point(1920 + 200, 100));
+    assert_eq!(layout.to_physical(point(1920 + 1279, 719)), point(1920 + 2558, 1438));
+
+    // 150% on the primary.
+    let layout = self::layout(1.5, 1.0, ScreenSpace::Scaled(1.0));
+    assert_eq!(layout.to_physical(point(100, 100)), point(150, 150));
+    assert_eq!(layout.to_physical(point(1920 + 100, 50)), point(1920 + 100, 50));
+}
+
+#[test]
+fn system_aware_coordinates_scale_relative_to_the_system() {
+    // The system is at 150%, like the primary, and the secondary is at 200%.
+    let layout = layout(1.5, 2.0, ScreenSpace::Scaled(1.5));
+    assert_eq!(layout.to_physical(point(100, 100)), point(100, 100));
+    assert_eq!(layout.to_physical(point(1920 + 300, 150)), point(1920 + 400, 200));
+}
+
+#[test]
+fn to_screen_undoes_to_physical() {
+    for (primary, secondary, system) in [(1.0, 1.5, 1.0), (1.5, 2.0, 1.5), (2.0, 1.0, 1.0)] {
+        let layout = layout(primary, secondary, ScreenSpace::Scaled(system));
+        for p in [point(0, 0), point(300, 400), point(1920, 0), point(1920 + 600, 300)] {
+            assert_eq!(layout.to_screen(layout.to_physical(p)), p, "{:?} at {:?}", p, layout);
+        }
+    }
+}
+
+#[test]
+fn monitors_left_of_the_primary_scale_from_their_own_corner() {
+    let layout = DisplayLayout::new(
+        vec![
+            MonitorGeometry { bounds: Rect::new(0, 0, 1920, 1080), scale_factor: 1.0 },
+            MonitorGeometry { bounds: Rect::new(-2560, 0, 2560, 1440), scale_factor: 2.0 },
+        ],
+        ScreenSpace::Scaled(1.0),
+    );
+    assert_eq!(layout.to_physical(point(-2560 + 100, 100)), point(-2560 + 200, 200));
+    assert_eq!(layout.to_screen(point(-2560 + 200, 200)), point(-2560 + 100, 100));
+}
+
+#[test]
+fn points_on_no_monitor_are_left_as_they_are() {
+    let layout = layout(1.5, 2.0, ScreenSpace::Scaled(1.0));
+    assert_eq!(layout.to_physical(point(-100, -100)), point(-100, -100));
+    assert_eq!(layout.to_screen(point(10_000, 0)), point(10_000, 0));
+}
+
+#[test]
+fn rects_scale_with_the_monitor_most_of_them_is_on() {
+    let layout = layout(1.0, 2.0, ScreenSpace::Scaled(1.0));
+    // Mostly on the secondary, which shows as 1280x720 from x 1920.
+    let rect = Rect::new(1900, 100, 400, 300);
+    assert_eq!(layout.rect_to_physical(rect), Rect::new(1880, 200, 800, 600));
+    // Mostly on the primary.
+    let rect = Rect::new(1700, 100, 400, 300);
+    assert_eq!(layout.rect_to_physical(rect), rect);
+    // On neither.
+    let rect = Rect::new(-500, -500, 100, 100);
+    assert_eq!(layout.rect_to_physical(rect), rect);
+}
+
+#[test]
+fn captured_window_on_a_scaled_secondary_monitor() {
+    for scale in [1.0_f32, 1.5, 2.0] {
+        let layout = layout(1.0, scale, ScreenSpace::Scaled(1.0));
+        // 400x300 in screen coordinates, 100 in from the corner of the secondary.
+        let transform = CaptureTransform::new(Rect::new(1920 + 100, 100, 400, 300), layout);
+        let size = (400.0 * scale).round() as i32;
+        assert_eq!(transform.bounds().size.x, size);
+
+        let center = transform.to_capture(point(1920 + 300, 250)).unwrap();
+        assert!((center.x - 0.5).abs() < 0.01 && (center.y - 0.5).abs() < 0.01, "{:?}", center);
+        assert_eq!(transform.to_screen(Vector2::new(0.5, 0.5)), point(1920 + 300, 250));
+        assert_eq!(transform.to_screen(Vector2::new(0.0, 0.0)), point(1920 + 100, 100));
+
+        // Outside of the window.
+        assert_eq!(transform.to_capture(point(1920 + 50, 250)), None);
+        assert_eq!(transform.to_capture(point(1920 + 300, 450)), None);
+    }
+}
+
+#[test]
+fn captured_primary_monitor_at_each_scale() {
+    for scale in [1.0_f32, 1.5, 2.0] {
+        let width = (1920.0 / scale).round() as i32;
+        let height = (1080.0 / scale).round() as i32;
+        let transform = CaptureTransform::new(
+            Rect::new(0, 0, width, height),
+            layout(scale, 1.0, ScreenSpace::Scaled(1.0)),
+        );
+        assert_eq!(transform.bounds(), Rect::new(0, 0, 1920, 1080));
+
+        let center = transform.to_capture(point(width / 2, height / 2)).unwrap();
+        assert!((center.x - 0.5).abs() < 0.01 && (center.y - 0.5).abs() < 0.01, "{:?}", center);
+        // Positions past the content are clamped to its edge.
+        let edge = transform.to_screen(Vector2::new(2.0, -1.0));
+        assert_eq!(edge.y, 0);
+        assert!((edge.x - width).abs() <= 1, "{:?}", edge);
+    }
+}
+
+#[test]
+fn empty_captures_have_no_cursor() {
+    let transform =
+        CaptureTransform::new(Rect::new(0, 0, 0, 0), layout(1.0, 1.0, ScreenSpace::Physical));
+    assert_eq!(transform.to_capture(point(0, 0)), None);
+}
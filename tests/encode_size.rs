@@ -0,0 +1,61 @@
+//! The size frames are encoded at under the resolution cap.
+
+use fjarsyn::{media::encode_size::capped_size, utils::vector2::Vector2};
+
+fn capped(width: i32, height: i32, max_dimension: Option<u32>) -> (i32, i32) {
+    let size = capped_size(Vector2::new(width, height), max_dimension);
+    (size.x, size.y)
+}
+
+#[test]
+fn scales_4k_down_to_1080p() {
+    assert_eq!(capped(3840, 2160, Some(1920)), (1920, 1080));
+}
+
+#[test]
+fn leaves_what_fits_alone() {
+    assert_eq!(capped(1920, 1080, Some(1920)), (1920, 1080));
+    assert_eq!(capped(1280, 720, Some(1920)), (1280, 720));
+    assert_eq!(capped(3840, 2160, None), (3840, 2160));
+}
+
+#[test]
+fn caps_the_longer_side() {
+    // Portrait, as with a rotated monitor.
+    assert_eq!(capped(2160, 3840, Some(1920)), (1080, 1920));
+    // Ultrawide.
+    assert_eq!(capped(3440, 1440, Some(1720)), (1720, 720));
+}
+
+#[test]
+fn keeps_the_aspect_ratio() {
+    for (width, height) in [(3840, 2160), (2560, 1600), (3440, 1440), (1366, 768), (2160, 3840)] {
+        for max_dimension in [640, 1000, 1280, 1920] {
+            let (capped_width, capped_height) = capped(width, height, Some(max_dimension));
+            assert!(capped_width.max(capped_height) <= max_dimension as i32);
+            let ratio = width as f64 / height as f64;
+            let capped_ratio = capped_width as f64 / capped_height as f64;
+            // Within what rounding to even numbers can change.
+            let tolerance = 2.0 / capped_width.min(capped_height) as f64 * ratio.max(1.0);
+            assert!(
+                (ratio - capped_ratio).abs() <= tolerance,
+                "{width}x{height} at {max_dimension} became {capped_width}x{capped_height}"
+            );
+        }
+    }
+}
+
+#[test]
+fn rounds_down_to_even() {
+    // 769 * 1000/1366 rounds to 563.
+    assert_eq!(capped(1366, 769, Some(1000)), (1000, 562));
+    // Without a cap too, as the encoder only takes even sizes.
+    assert_eq!(capped(1921, 1081, None), (1920, 1080));
+    assert_eq!(capped(801, 601, Some(1920)), (800, 600));
+}
+
+#[test]
+fn never_scales_to_nothing() {
+    assert_eq!(capped(4000, 2, Some(100)), (100, 2));
+    assert_eq!(capped(2, 4000, Some(100)), (2, 100));
+}
@@ -0,0 +1,184 @@
+//! The encoder worker writing what it encodes to a sink, in order and all of it, across restarts and fallbacks.
+
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use fjarsyn::{
+    config::Config,
+    media::{
+        encoder_worker::{EncoderConfig, EncoderWorker, EncoderWorkerError, SampleSink},
+        ffmpeg::FFmpegTranscodeType,
+        nal::{self, NalCodec},
+    },
+    utils::{
+        frame::Frame,
+        metrics::Metrics,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+
+const SIZE: Vector2<i32> = Vector2 { x: 64, y: 48 };
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The data and the duration of each sample.
+type Samples = Vec<(Vec<u8>, Duration)>;
+
+/// Keeps the samples written to it, in the order they were written.
+#[derive(Debug, Clone, Default)]
+struct RecordingSink {
+    samples: Arc<Mutex<Samples>>,
+}
+
+impl RecordingSink {
+    fn durations(&self) -> Vec<Duration> {
+        self.samples.lock().unwrap().iter().map(|(_, duration)| *duration).collect()
+    }
+
+    fn first_sample(&self) -> Vec<u8> {
+        self.samples.lock().unwrap()[0].0.clone()
+    }
+}
+
+impl SampleSink for RecordingSink {
+    type Error = Infallible;
+
+    async fn write_sample(&self, data: Vec<u8>, duration: Duration) -> Result<(), Self::Error> {
+        self.samples.lock().unwrap().push((data, duration));
+        Ok(())
+    }
+}
+
+// One frame a second, which no encoder is too slow for, so no frame is skipped to keep up.
+fn config(transcoding_type: FFmpegTranscodeType) -> EncoderConfig {
+    EncoderConfig {
+        target_fps_hz: 1.0,
+        transcoding_type,
+        skip_unchanged: false,
+        bandwidth_cap: None,
+        ..EncoderConfig::from_config(&Config::default())
+    }
+}
+
+// Frames that each last a millisecond longer than the one before, so the samples tell which frame they came from.
+fn numbered_frames(config: &EncoderConfig, count: u32) -> Vec<Arc<Frame>> {
+    let mut frames = SyntheticFrames::new(SIZE, config.input_format, FramePattern::Gradient);
+    (1..=count)
+        .map(|number| {
+            let mut frame = frames.next_frame();
+            frame.duration = Some(Duration::from_millis(number as u64));
+            Arc::new(frame)
+        })
+        .collect()
+}
+
+fn millis(count: u32) -> Vec<Duration> {
+    (1..=count).map(|number| Duration::from_millis(number as u64)).collect()
+}
+
+#[tokio::test]
+async fn writes_the_samples_in_the_order_of_the_frames() {
+    const FRAMES: u32 = 40;
+    let config = config(FFmpegTranscodeType::H264Software);
+    let sink = RecordingSink::default();
+    let encoder = EncoderWorker::spawn(config, sink.clone(), &Metrics::default()).unwrap();
+
+    // More than the queue takes, so some have to wait for room.
+    for frame in numbered_frames(&config, FRAMES) {
+        tokio::time::timeout(SEND_TIMEOUT, async {
+            while let Err(EncoderWorkerError::QueueFull) = encoder.send_frame(frame.clone()) {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+    encoder.shutdown().await.unwrap();
+
+    // The encoder doesn't hold frames back, so there is a sample for each, right as it was encoded.
+    assert_eq!(sink.durations(), millis(FRAMES));
+}
+
+#[tokio::test]
+async fn flushes_the_queued_frames_on_shutdown() {
+    const FRAMES: u32 = 8;
+    let config = config(FFmpegTranscodeType::H264Software);
+    let sink = RecordingSink::default();
+    let encoder = EncoderWorker::spawn(config, sink.clone(), &Metrics::default()).unwrap();
+
+    for frame in numbered_frames(&config, FRAMES) {
+        encoder.send_frame(frame).unwrap();
+    }
+    // Straight away, with the frames still queued.
+    encoder.shutdown().await.unwrap();
+
+    assert_eq!(sink.durations(), millis(FRAMES));
+    let frame = numbered_frames(&config, 1).remove(0);
+    assert!(matches!(encoder.send_frame(frame), Err(EncoderWorkerError::Closed)));
+}
+
+#[tokio::test]
+async fn a_respawned_worker_starts_over_with_a_keyframe() {
+    const FRAMES: u32 = 4;
+    let config = config(FFmpegTranscodeType::H264Software);
+    let codec = NalCodec::from_mime_type(config.transcoding_type.mime_type()).unwrap();
+    let metrics = Metrics::default();
+
+    let first = RecordingSink::default();
+    let encoder = EncoderWorker::spawn(config, first.clone(), &metrics).unwrap();
+    for frame in numbered_frames(&config, FRAMES) {
+        encoder.send_frame(frame).unwrap();
+    }
+    encoder.shutdown().await.unwrap();
+    assert!(encoder.shutdown().await.is_err());
+
+    // What the app does when the capture restarts: a new worker in place of the old, with the same metrics.
+    let second = RecordingSink::default();
+    let encoder = EncoderWorker::spawn(config, second.clone(), &metrics).unwrap();
+    for frame in numbered_frames(&config, FRAMES) {
+        encoder.send_frame(frame).unwrap();
+    }
+    encoder.shutdown().await.unwrap();
+
+    assert_eq!(first.durations(), millis(FRAMES));
+    assert_eq!(second.durations(), millis(FRAMES));
+    assert!(nal::is_keyframe(&second.first_sample(), codec));
+}
+
+#[tokio::test]
+async fn falls_back_to_software_when_the_hardware_encoder_fails() {
+    const FRAMES: u32 = 4;
+    let config = config(FFmpegTranscodeType::H264Vulkan);
+    let created = Arc::new(Mutex::new(Vec::new()));
+    let create_encoder = {
+        let created = created.clone();
+        move |config: &EncoderConfig| {
+            created.lock().unwrap().push(config.transcoding_type);
+            EncoderWorker::<RecordingSink>::new_encoder(config)
+        }
+    };
+    let sink = RecordingSink::default();
+    let encoder =
+        EncoderWorker::spawn_with(create_encoder, config, sink.clone(), &Metrics::default())
+            .unwrap();
+
+    // Rounded down to even, this is nothing to encode, so the first encoder fails whether there is hardware or not.
+    let mut unencodable =
+        SyntheticFrames::new(Vector2::new(1, 1), config.input_format, FramePattern::Flat)
+            .next_frame();
+    unencodable.duration = Some(Duration::ZERO);
+    encoder.send_frame(Arc::new(unencodable)).unwrap();
+    for frame in numbered_frames(&config, FRAMES) {
+        encoder.send_frame(frame).unwrap();
+    }
+    encoder.shutdown().await.unwrap();
+
+    assert_eq!(
+        *created.lock().unwrap(),
+        [FFmpegTranscodeType::H264Vulkan, FFmpegTranscodeType::H264Software]
+    );
+    assert_eq!(sink.durations(), millis(FRAMES));
+}
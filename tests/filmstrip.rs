@@ -0,0 +1,155 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use fjarsyn::{
+    ui::filmstrip::{Filmstrip, Snapshot, SnapshotBudget, session_folder_name, snapshot_name},
+    utils::{
+        bitmap_utils::encode_jpeg,
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+use iced::widget::image;
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(name)
+}
+
+// A snapshot that was never written, for the bookkeeping alone.
+fn snapshot(name: &str, bytes: u64) -> Snapshot {
+    Snapshot { path: path(name), bytes, thumbnail: image::Handle::from_rgba(1, 1, vec![0; 4]) }
+}
+
+#[test]
+fn the_oldest_are_evicted_first() {
+    let mut budget = SnapshotBudget::new(100);
+    assert!(budget.add(path("a"), 40).is_empty());
+    assert!(budget.add(path("b"), 40).is_empty());
+    assert_eq!(budget.add(path("c"), 40), [path("a")]);
+    assert_eq!(budget.used(), 80);
+    // Makes room for one that takes up more than one did.
+    assert_eq!(budget.add(path("d"), 90), [path("b"), path("c")]);
+    assert_eq!(budget.used(), 90);
+    assert_eq!(budget.len(), 1);
+}
+
+#[test]
+fn the_newest_is_kept_even_over_budget() {
+    let mut budget = SnapshotBudget::new(10);
+    assert!(budget.add(path("a"), 50).is_empty());
+    assert_eq!(budget.add(path("b"), 50), [path("a")]);
+    assert_eq!(budget.used(), 50);
+    assert!(!budget.is_empty());
+}
+
+#[test]
+fn shrinking_the_budget_evicts() {
+    let mut budget = SnapshotBudget::new(100);
+    for name in ["a", "b", "c"] {
+        budget.add(path(name), 30);
+    }
+    assert!(budget.set_budget(90).is_empty());
+    assert_eq!(budget.set_budget(30), [path("a"), path("b")]);
+    assert_eq!(budget.used(), 30);
+}
+
+#[test]
+fn folders_are_named_after_the_peer_and_start() {
+    let started = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+    assert_eq!(session_folder_name("alice", started), "alice 2024-02-29 12-34-56");
+    let started = UNIX_EPOCH + Duration::from_secs(946_684_799);
+    assert_eq!(session_folder_name("bob", started), "bob 1999-12-31 23-59-59");
+    assert_eq!(session_folder_name("a/b:c d", UNIX_EPOCH), "a_b_c_d 1970-01-01 00-00-00");
+    // Whatever the clock says, it makes a name.
+    assert!(session_folder_name("carol", SystemTime::now()).starts_with("carol 20"));
+}
+
+#[test]
+fn snapshots_sort_in_the_order_they_were_taken() {
+    assert_eq!(snapshot_name(Duration::from_millis(3_725_900)), "01-02-05.jpg");
+    let names: Vec<_> =
+        [5, 59, 60, 3599, 3600, 36_000].map(|secs| snapshot_name(Duration::from_secs(secs))).into();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+}
+
+#[test]
+fn snapshots_are_taken_once_per_interval() {
+    let start = Instant::now();
+    let interval = Duration::from_secs(10);
+    let mut filmstrip = Filmstrip::new(path("call"), start, 1000);
+    assert!(filmstrip.due(start, interval));
+    let taken = filmstrip.take(start + Duration::from_secs(65));
+    assert_eq!(taken, path("call").join("00-01-05.jpg"));
+
+    // Not while it is still being saved, even once it is time for the next one.
+    let now = start + Duration::from_secs(80);
+    assert!(!filmstrip.due(now, interval));
+    filmstrip.saved(snapshot("00-01-05.jpg", 10));
+    assert!(filmstrip.due(now, interval));
+    assert!(!filmstrip.due(start + Duration::from_secs(74), interval));
+
+    filmstrip.take(now);
+    filmstrip.failed();
+    assert!(filmstrip.due(now + interval, interval));
+}
+
+#[test]
+fn evicted_snapshots_leave_the_strip() {
+    let start = Instant::now();
+    let mut filmstrip = Filmstrip::new(path("call"), start, 25);
+    filmstrip.saved(snapshot("a", 10));
+    filmstrip.saved(snapshot("b", 10));
+    filmstrip.select(Some(path("a")));
+    assert_eq!(filmstrip.saved(snapshot("c", 10)), [path("a")]);
+    assert_eq!(filmstrip.selected(), None);
+    let shown: Vec<_> = filmstrip.snapshots().map(|snapshot| snapshot.path.clone()).collect();
+    assert_eq!(shown, [path("b"), path("c")]);
+}
+
+#[test]
+fn the_strip_only_shows_the_latest() {
+    let mut filmstrip = Filmstrip::new(path("call"), Instant::now(), u64::MAX);
+    for i in 0..Filmstrip::SHOWN + 3 {
+        assert!(filmstrip.saved(snapshot(&i.to_string(), 10)).is_empty());
+    }
+    let shown: Vec<_> = filmstrip.snapshots().map(|snapshot| snapshot.path.clone()).collect();
+    assert_eq!(shown.len(), Filmstrip::SHOWN);
+    assert_eq!(shown[0], path("3"));
+}
+
+#[test]
+fn snapshots_are_scaled_down_jpegs() {
+    let dir = std::env::temp_dir().join(format!("fjarsyn-filmstrip-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let size = Vector2::new(2560, 1440);
+    let frame = SyntheticFrames::new(size, PixelFormat::RGBA8, FramePattern::Gradient).next_frame();
+
+    // The folder is made along with the first.
+    let snapshot = Snapshot::save(&frame, dir.join("00-00-10.jpg")).unwrap();
+    let jpeg = fs::read(&snapshot.path).unwrap();
+    assert_eq!(jpeg.len() as u64, snapshot.bytes);
+    let decoded = ::image::load_from_memory(&jpeg).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (1280, 720));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn jpegs_keep_the_colors() {
+    let size = Vector2::new(16, 8);
+    let rgba: Vec<u8> = (0..size.x * size.y).flat_map(|_| [200, 100, 50, 255]).collect();
+    let jpeg = encode_jpeg(&rgba, size, 90).unwrap();
+    assert_eq!(jpeg[..2], [0xFF, 0xD8]);
+    let decoded = ::image::load_from_memory(&jpeg).unwrap().to_rgb8();
+    assert_eq!(decoded.dimensions(), (16, 8));
+    for pixel in decoded.pixels() {
+        for (channel, expected) in pixel.0.iter().zip([200u8, 100, 50]) {
+            assert!(channel.abs_diff(expected) <= 4, "{:?}", pixel);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use fjarsyn::networking::webrtc::{CallFingerprints, Fingerprint};
+
+const SDP: &str = "v=0\r\n\
+o=- 4215775240449105457 2 IN IP4 127.0.0.1\r\n\
+s=-\r\n\
+t=0 0\r\n\
+a=group:BUNDLE 0\r\n\
+m=video 9 UDP/TLS/RTP/SAVPF 96\r\n\
+c=IN IP4 0.0.0.0\r\n\
+a=setup:actpass\r\n\
+a=mid:0\r\n\
+a=fingerprint:sha-256 1A:2B:3C:4D:5E:6F:70:81:92:A3:B4:C5:D6:E7:F8:09:1A:2B:3C:4D:5E:6F:70:81:92:A3:B4:C5:D6:E7:F8:09\r\n\
+a=sendrecv\r\n";
+
+fn fingerprint(fill: u8) -> Fingerprint {
+    Fingerprint { algorithm: "sha-256".to_owned(), bytes: vec![fill; 32] }
+}
+
+#[test]
+fn parses_the_fingerprint_of_a_session_description() {
+    let fingerprint = Fingerprint::from_sdp(SDP).unwrap();
+    assert_eq!(fingerprint.algorithm, "sha-256");
+    assert_eq!(fingerprint.bytes.len(), 32);
+    assert_eq!(&fingerprint.bytes[..3], &[0x1A, 0x2B, 0x3C]);
+}
+
+#[test]
+fn is_none_without_a_fingerprint() {
+    assert_eq!(Fingerprint::from_sdp("v=0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\n"), None);
+}
+
+#[test]
+fn rejects_malformed_fingerprints() {
+    assert_eq!(Fingerprint::parse("sha-256"), None);
+    assert_eq!(Fingerprint::parse("sha-256 "), None);
+    assert_eq!(Fingerprint::parse("sha-256 1A:2"), None);
+    assert_eq!(Fingerprint::parse("sha-256 1A:ZZ"), None);
+    assert_eq!(Fingerprint::parse("sha-256 1A2B"), None);
+}
+
+#[test]
+fn reads_back_what_it_writes() {
+    let fingerprint = Fingerprint::parse("SHA-256 0a:1B:ff").unwrap();
+    assert_eq!(fingerprint.to_string(), "sha-256 0A:1B:FF");
+    assert_eq!(Fingerprint::parse(&fingerprint.to_string()), Some(fingerprint));
+}
+
+#[test]
+fn both_ends_get_the_same_code() {
+    let ours = CallFingerprints { local: fingerprint(0x12), remote: fingerprint(0xA7) };
+    let theirs = CallFingerprints { local: fingerprint(0xA7), remote: fingerprint(0x12) };
+    assert_eq!(ours.short_auth_string(), theirs.short_auth_string());
+}
+
+#[test]
+fn the_code_is_short_and_easy_to_read() {
+    let code = CallFingerprints { local: fingerprint(0x12), remote: fingerprint(0xA7) }
+        .short_auth_string();
+    assert_eq!(code.len(), 9);
+    assert_eq!(code.chars().nth(4), Some('-'));
+    assert!(
+        code.chars()
+            .filter(|&c| c != '-')
+            .all(|c| c.is_ascii_digit() || (c.is_ascii_uppercase() && !"ILOU".contains(c)))
+    );
+}
+
+#[test]
+fn someone_in_between_changes_the_code() {
+    let direct = CallFingerprints { local: fingerprint(0x12), remote: fingerprint(0xA7) };
+    let intercepted = CallFingerprints { local: fingerprint(0x12), remote: fingerprint(0x5C) };
+    assert_ne!(direct.short_auth_string(), intercepted.short_auth_string());
+}
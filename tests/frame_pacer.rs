@@ -0,0 +1,123 @@
+//! Which frames the pacer keeps when the encoder falls behind, and what the samples cover.
+
+use std::time::Duration;
+
+use fjarsyn::media::frame_pacer::{FramePacer, decimation_factor, should_encode};
+
+const INTERVAL: Duration = Duration::from_millis(10);
+// Enough encodes for the pacer to go by.
+const HISTORY: usize = 10;
+
+fn encodes(millis: u64) -> Vec<Duration> {
+    vec![Duration::from_millis(millis); HISTORY]
+}
+
+// A pacer at 100 fps, which has settled on the decimation of encodes taking that long.
+fn pacer_with_encodes(millis: u64) -> FramePacer {
+    let mut pacer = FramePacer::new(100.0);
+    for duration in encodes(millis) {
+        pacer.record_encode(duration);
+    }
+    pacer
+}
+
+// Which of the frames the pacer keeps.
+fn kept(pacer: &mut FramePacer, dirty: &[bool]) -> Vec<bool> {
+    dirty.iter().map(|&dirty| pacer.admit(INTERVAL, dirty).is_some()).collect()
+}
+
+#[test]
+fn decimation_follows_the_encode_cost() {
+    for (millis, factor) in [(1, 1), (9, 1), (11, 2), (19, 2), (25, 3), (35, 4), (100, 4)] {
+        assert_eq!(decimation_factor(INTERVAL, &encodes(millis), 1), factor, "{millis} ms");
+    }
+}
+
+#[test]
+fn decimation_goes_by_the_average_encode() {
+    let durations = [5, 5, 5, 20].map(Duration::from_millis);
+    assert_eq!(decimation_factor(INTERVAL, &durations, 1), 1);
+    let durations = [5, 5, 25, 25].map(Duration::from_millis);
+    assert_eq!(decimation_factor(INTERVAL, &durations, 1), 2);
+}
+
+#[test]
+fn decimation_keeps_the_current_one_without_anything_to_go_by() {
+    assert_eq!(decimation_factor(INTERVAL, &[], 3), 3);
+    assert_eq!(decimation_factor(Duration::ZERO, &encodes(50), 2), 2);
+    // Still within bounds.
+    assert_eq!(decimation_factor(INTERVAL, &[], 0), 1);
+    assert_eq!(decimation_factor(INTERVAL, &[], 9), 4);
+}
+
+#[test]
+fn decimation_is_only_lowered_with_headroom() {
+    // Fits the lower decimation, but only just.
+    assert_eq!(decimation_factor(INTERVAL, &encodes(19), 3), 3);
+    assert_eq!(decimation_factor(INTERVAL, &encodes(10), 2), 2);
+    // Comfortably.
+    assert_eq!(decimation_factor(INTERVAL, &encodes(17), 3), 2);
+    assert_eq!(decimation_factor(INTERVAL, &encodes(8), 2), 1);
+    // Raising it doesn't wait.
+    assert_eq!(decimation_factor(INTERVAL, &encodes(21), 2), 3);
+}
+
+#[test]
+fn encodes_one_frame_per_window() {
+    // Every frame without decimation.
+    assert!(should_encode(1, 0, false, false));
+    // The first dirty one, otherwise the last.
+    assert!(should_encode(3, 0, false, true));
+    assert!(!should_encode(3, 0, false, false));
+    assert!(!should_encode(3, 1, false, false));
+    assert!(should_encode(3, 2, false, false));
+    // Nothing more once one was.
+    assert!(!should_encode(3, 1, true, true));
+    assert!(!should_encode(3, 2, true, false));
+}
+
+#[test]
+fn waits_for_enough_encodes_before_decimating() {
+    let mut pacer = FramePacer::new(100.0);
+    for _ in 1..HISTORY {
+        assert_eq!(pacer.record_encode(Duration::from_millis(25)), None);
+    }
+    assert_eq!(pacer.record_encode(Duration::from_millis(25)), Some(3));
+    assert_eq!(pacer.decimation(), 3);
+    // Only reported when it changes.
+    assert_eq!(pacer.record_encode(Duration::from_millis(25)), None);
+}
+
+#[test]
+fn keeps_the_same_frames_every_time() {
+    let dirty = [false, false, false, true, false, false, false, true, true, false, false, false];
+    let expected = [false, false, true, true, false, false, false, true, false, false, false, true];
+
+    let mut pacer = pacer_with_encodes(25);
+    assert_eq!(pacer.decimation(), 3);
+    assert_eq!(kept(&mut pacer, &dirty), expected);
+    // Again, and with another pacer.
+    assert_eq!(kept(&mut pacer, &dirty), expected);
+    assert_eq!(kept(&mut pacer_with_encodes(25), &dirty), expected);
+}
+
+#[test]
+fn keeps_the_last_of_clean_frames() {
+    let mut pacer = pacer_with_encodes(35);
+    assert_eq!(pacer.decimation(), 4);
+    let kept = kept(&mut pacer, &[false; 12]);
+    assert_eq!(kept, [false, false, false, true].repeat(3));
+}
+
+#[test]
+fn samples_cover_the_skipped_frames() {
+    let mut pacer = pacer_with_encodes(25);
+    assert_eq!(pacer.decimation(), 3);
+    let durations: Vec<_> = (0..6).filter_map(|_| pacer.admit(INTERVAL, false)).collect();
+    assert_eq!(durations, [INTERVAL * 3, INTERVAL * 3]);
+
+    // What isn't encoded after all goes with the next sample.
+    pacer.defer(INTERVAL * 3);
+    let durations: Vec<_> = (0..3).filter_map(|_| pacer.admit(INTERVAL, false)).collect();
+    assert_eq!(durations, [INTERVAL * 6]);
+}
@@ -0,0 +1,143 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::Arc,
+};
+
+use fjarsyn::{
+    media::ffmpeg::{DecodeAccel, FFmpegDecoder, FFmpegEncoder, FFmpegTranscodeType, RateControl},
+    utils::{
+        frame::Frame,
+        frame_ring::FrameRing,
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+
+const RING_LEN: usize = 4;
+const FRAMES: usize = 100;
+
+// Counts the allocations of at least a frame's size on this thread, as the other tests allocate on theirs.
+struct CountingAllocator;
+
+thread_local! {
+    static LARGE_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static LARGE_SIZE: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LARGE_SIZE.try_with(|large| {
+            if layout.size() >= large.get() {
+                let _ = LARGE_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            }
+        });
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// The allocations of at least `size` bytes that `f` makes.
+fn large_allocations<T>(size: usize, f: impl FnOnce() -> T) -> (T, usize) {
+    LARGE_SIZE.with(|large| large.set(size));
+    let before = LARGE_ALLOCATIONS.with(Cell::get);
+    let result = f();
+    let count = LARGE_ALLOCATIONS.with(Cell::get) - before;
+    LARGE_SIZE.with(|large| large.set(usize::MAX));
+    (result, count)
+}
+
+fn fill(ring: &mut FrameRing, size: Vector2<i32>, value: u8) -> Arc<Frame> {
+    ring.fill(size, PixelFormat::RGBA8, |data| data.fill(value))
+}
+
+#[test]
+fn released_frames_are_reused() {
+    let size = Vector2::new(16, 8);
+    let mut ring = FrameRing::new(RING_LEN);
+    let first = fill(&mut ring, size, 1);
+    let first_data = first.data.as_ptr();
+    drop(first);
+
+    for value in 2..=RING_LEN as u8 {
+        fill(&mut ring, size, value);
+    }
+    // Around again, to the first one.
+    let again = fill(&mut ring, size, 9);
+    assert_eq!(again.data.as_ptr(), first_data);
+    assert!(again.data.iter().all(|&b| b == 9));
+    assert_eq!(ring.overflows(), 0);
+}
+
+#[test]
+fn held_frames_are_left_alone() {
+    let size = Vector2::new(16, 8);
+    let mut ring = FrameRing::new(RING_LEN);
+    let held: Vec<_> = (0..RING_LEN as u8).map(|value| fill(&mut ring, size, value)).collect();
+
+    let extra = fill(&mut ring, size, 0xff);
+    assert_eq!(ring.overflows(), 1);
+    assert!(held.iter().all(|frame| frame.data.as_ptr() != extra.data.as_ptr()));
+    for (value, frame) in held.iter().enumerate() {
+        assert!(frame.data.iter().all(|&b| b == value as u8), "Frame {} was overwritten", value);
+    }
+}
+
+#[test]
+fn new_size_reallocates() {
+    let mut ring = FrameRing::new(RING_LEN);
+    let small = fill(&mut ring, Vector2::new(16, 8), 1);
+    let large = fill(&mut ring, Vector2::new(32, 16), 2);
+    assert_eq!(large.size, Vector2::new(32, 16));
+    assert_eq!(large.data.len(), 32 * 16 * 4);
+    // The old frame is still whole for whoever holds it.
+    assert_eq!(small.data.len(), 16 * 8 * 4);
+    assert_eq!(ring.overflows(), 0);
+}
+
+#[test]
+fn steady_playback_allocates_no_frames() {
+    let size = Vector2::new(320, 240);
+    let frame_bytes = (size.x * size.y * 4) as usize;
+    let mut encoder =
+        FFmpegEncoder::new(1_000_000, 30.0, PixelFormat::RGBA8, None, 30, RateControl::Variable)
+            .unwrap();
+    let mut frames = SyntheticFrames::new(size, PixelFormat::RGBA8, FramePattern::Gradient);
+    let mut packets = Vec::new();
+    for _ in 0..FRAMES {
+        packets.extend(
+            encoder
+                .encode(&frames.next_bitmap(), FFmpegTranscodeType::H264Software, size.x, size.y)
+                .unwrap(),
+        );
+    }
+    packets.extend(encoder.flush().unwrap());
+
+    let mut decoder =
+        FFmpegDecoder::new(FFmpegTranscodeType::H264Software, DecodeAccel::Software).unwrap();
+    let (decoded, allocations) = large_allocations(frame_bytes, || {
+        // Held until the next one arrives, as the UI holds the frame it shows.
+        let mut shown = None;
+        let mut decoded = 0;
+        for packet in &packets {
+            if let Some(frame) = decoder.decode(packet).unwrap() {
+                shown = Some(frame);
+                decoded += 1;
+            }
+        }
+        drop(shown);
+        decoded
+    });
+
+    assert!(decoded >= FRAMES / 2, "Only decoded {} frames", decoded);
+    // Only the ring itself, when the first frame arrived.
+    assert!(allocations <= RING_LEN, "{} frame sized allocations", allocations);
+    assert_eq!(decoder.frame_overflows(), 0);
+}
@@ -0,0 +1,122 @@
+//! Capture callbacks sending frames while the stream they send to is swapped and ended under them.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use fjarsyn::{
+    capture_providers::shared::FrameSenderSlot,
+    utils::{
+        frame::Frame,
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+use tokio::sync::mpsc::{self, error::TryRecvError};
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+// As many as the sessions of a region capture, each with its own callback.
+const CALLBACKS: usize = 4;
+const STREAMS: usize = 200;
+
+fn frames() -> SyntheticFrames {
+    SyntheticFrames::new(Vector2::new(2, 2), PixelFormat::BGRA8, FramePattern::Flat)
+}
+
+// What the frame handler does with each frame: send it to the active stream, if there is one.
+fn callback(slot: FrameSenderSlot, stop: Arc<AtomicBool>, sent: Arc<AtomicUsize>) {
+    let mut frames = frames();
+    while !stop.load(Ordering::Relaxed) {
+        let tx = slot.load();
+        if let Some(tx) = tx.as_deref()
+            && tx.try_send(frames.next_frame()).is_ok()
+        {
+            sent.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn recv(rx: &mut mpsc::Receiver<Frame>) -> Option<Frame> {
+    tokio::time::timeout(RECV_TIMEOUT, rx.recv()).await.expect("the stream neither sent nor ended")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streams_swapped_and_ended_under_busy_callbacks_all_end() {
+    let slot = FrameSenderSlot::default();
+    let stop = Arc::new(AtomicBool::new(false));
+    let sent = Arc::new(AtomicUsize::new(0));
+    let callbacks: Vec<_> = (0..CALLBACKS)
+        .map(|_| {
+            let (slot, stop, sent) = (slot.clone(), stop.clone(), sent.clone());
+            thread::spawn(move || callback(slot, stop, sent))
+        })
+        .collect();
+
+    let (tx, mut previous) = mpsc::channel(2);
+    slot.replace(tx);
+    for stream in 0..STREAMS {
+        let (tx, mut rx) = mpsc::channel(2);
+        slot.replace(tx);
+        // The replaced stream ends once the callbacks are done with its sender, having sent whatever it sent.
+        while recv(&mut previous).await.is_some() {}
+        // And the new one is fed.
+        assert!(recv(&mut rx).await.is_some(), "stream {stream} got nothing");
+        previous = rx;
+
+        // Now and then, the capture stops and starts again.
+        if stream % 10 == 0 {
+            slot.end();
+            while recv(&mut previous).await.is_some() {}
+            let (tx, rx) = mpsc::channel(2);
+            slot.replace(tx);
+            previous = rx;
+        }
+    }
+
+    slot.end();
+    while recv(&mut previous).await.is_some() {}
+    assert!(slot.load().is_none());
+    stop.store(true, Ordering::Relaxed);
+    for callback in callbacks {
+        callback.join().unwrap();
+    }
+    assert!(sent.load(Ordering::Relaxed) >= STREAMS);
+}
+
+#[test]
+fn an_ended_stream_ends_once_the_callback_lets_go_of_it() {
+    let slot = FrameSenderSlot::default();
+    assert!(slot.load().is_none());
+    let (tx, mut rx) = mpsc::channel(2);
+    slot.replace(tx);
+
+    // A callback that loaded the sender right before the capture stopped still gets its frame through.
+    let held = slot.load();
+    slot.end();
+    assert!(slot.load().is_none());
+    held.as_deref().unwrap().try_send(frames().next_frame()).unwrap();
+    assert!(rx.try_recv().is_ok());
+    assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Empty);
+
+    drop(held);
+    assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Disconnected);
+}
+
+#[test]
+fn a_replaced_stream_ends_and_the_next_one_gets_the_frames() {
+    let slot = FrameSenderSlot::default();
+    let (tx, mut first) = mpsc::channel(2);
+    slot.replace(tx);
+    let (tx, mut second) = mpsc::channel(2);
+    slot.replace(tx);
+
+    assert_eq!(first.try_recv().unwrap_err(), TryRecvError::Disconnected);
+    slot.load().as_deref().unwrap().try_send(frames().next_frame()).unwrap();
+    assert!(second.try_recv().is_ok());
+}
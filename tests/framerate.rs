@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use fjarsyn::{
+    capture_providers::shared::CaptureFramerate,
+    utils::framerate::{Framerate, TICKS_PER_SECOND},
+};
+
+// Representative of what a custom rate could be, on top of the presets.
+const CUSTOM_HZ: &[f64] =
+    &[1.0, 7.5, 12.5, 23.976, 25.0, 29.97, 47.952, 50.0, 59.94, 75.0, 90.0, 119.88, 165.0, 240.0];
+
+fn all() -> impl Iterator<Item = Framerate> {
+    let presets = CaptureFramerate::ALL.iter().map(CaptureFramerate::to_framerate);
+    presets.chain(CUSTOM_HZ.iter().map(|&hz| Framerate::from_hz(hz)))
+}
+
+fn exact_nanos(framerate: Framerate) -> f64 {
+    framerate.den() as f64 * 1e9 / framerate.num() as f64
+}
+
+#[test]
+fn presets_are_whole_rates() {
+    for framerate in CaptureFramerate::ALL {
+        let exact = framerate.to_framerate();
+        assert_eq!(exact.den(), 1, "{}", framerate);
+        assert_eq!(exact.hz(), framerate.to_hz() as f64);
+        assert_eq!(framerate.to_frametime(), exact.frametime());
+    }
+}
+
+#[test]
+fn ntsc_rates_are_exact() {
+    for (hz, num) in [(23.976, 24_000), (29.97, 30_000), (59.94, 60_000), (119.88, 120_000)] {
+        assert_eq!(Framerate::from_hz(hz), Framerate::new(num, 1001).unwrap(), "{}", hz);
+    }
+    // As it comes out of an f32.
+    assert_eq!(Framerate::from_hz(23.976f32 as f64), Framerate::new(24_000, 1001).unwrap());
+}
+
+#[test]
+fn other_rates_keep_their_fraction() {
+    assert_eq!(Framerate::from_hz(7.5), Framerate::new(15, 2).unwrap());
+    assert_eq!(Framerate::from_hz(12.5), Framerate::new(25, 2).unwrap());
+    assert_eq!(Framerate::from_hz(144.0), Framerate::whole(144));
+    // Close enough to whole that a float can't tell.
+    assert_eq!(Framerate::from_hz(59.99999), Framerate::whole(60));
+    let odd = Framerate::from_hz(33.3333);
+    assert!((odd.hz() - 33.3333).abs() < 1e-6, "{:?}", odd);
+}
+
+#[test]
+fn fractions_are_in_lowest_terms() {
+    assert_eq!(Framerate::new(60, 2), Some(Framerate::whole(30)));
+    assert_eq!(Framerate::new(48_000, 2002), Framerate::new(24_000, 1001));
+    assert_eq!(Framerate::new(0, 1), None);
+    assert_eq!(Framerate::new(1, 0), None);
+    assert_eq!(Framerate::new(u32::MAX, 1), None);
+    for framerate in all() {
+        let (num, den) = (framerate.num(), framerate.den());
+        assert_eq!(Framerate::new(num, den), Some(framerate));
+    }
+}
+
+#[test]
+fn nonsense_rates_are_clamped() {
+    assert_eq!(Framerate::from_hz(f64::NAN), Framerate::whole(1));
+    assert_eq!(Framerate::from_hz(f64::INFINITY), Framerate::whole(1000));
+    assert_eq!(Framerate::from_hz(1e9), Framerate::whole(1000));
+    assert_eq!(Framerate::whole(0), Framerate::whole(1));
+    for hz in [0.0, -30.0, f64::NEG_INFINITY] {
+        let framerate = Framerate::from_hz(hz);
+        assert!(framerate.hz() > 0.0 && framerate.hz() < 0.001, "{} -> {:?}", hz, framerate);
+        // Still gives a frame time and a tick count that fit.
+        assert!(framerate.frametime() > Duration::from_secs(1000));
+        assert!(framerate.frametime_ticks() > 0);
+    }
+}
+
+#[test]
+fn frametimes_round_to_the_nearest_nanosecond() {
+    assert_eq!(Framerate::whole(144).frametime(), Duration::from_nanos(6_944_444));
+    assert_eq!(Framerate::whole(30).frametime(), Duration::from_nanos(33_333_333));
+    // 142857142.86 rounds up.
+    assert_eq!(Framerate::whole(7).frametime(), Duration::from_nanos(142_857_143));
+    assert_eq!(Framerate::new(24_000, 1001).unwrap().frametime(), Duration::from_nanos(41_708_333));
+    for framerate in all() {
+        let error = framerate.frametime().as_nanos() as f64 - exact_nanos(framerate);
+        assert!(error.abs() <= 0.5, "{:?} is off by {} ns", framerate, error);
+    }
+}
+
+#[test]
+fn ticks_round_down() {
+    assert_eq!(Framerate::whole(144).frametime_ticks(), 69_444);
+    assert_eq!(Framerate::whole(200).frametime_ticks(), 50_000);
+    assert_eq!(Framerate::whole(60).frametime_ticks(), 166_666);
+    assert_eq!(Framerate::new(24_000, 1001).unwrap().frametime_ticks(), 417_083);
+    for framerate in all() {
+        // Never longer than a frame, so the capture isn't held back below the rate, and short by under a tick.
+        let ticks = framerate.frametime_ticks() as f64 * 100.0;
+        let exact = exact_nanos(framerate);
+        assert!(
+            ticks <= exact && exact - ticks < 100.0,
+            "{:?}: {} for {}",
+            framerate,
+            ticks,
+            exact
+        );
+    }
+}
+
+#[test]
+fn time_bases_are_exact() {
+    assert_eq!(Framerate::whole(24).time_base(), (1, 24));
+    assert_eq!(Framerate::new(24_000, 1001).unwrap().time_base(), (1001, 24_000));
+    assert_eq!(Framerate::from_hz(7.5).time_base(), (2, 15));
+    for framerate in all() {
+        let (tb_num, tb_den) = framerate.time_base();
+        let (fr_num, fr_den) = framerate.frame_rate();
+        // A frame is one tick, so a tick times the rate is exactly one.
+        assert_eq!(tb_num as i64 * fr_num as i64, tb_den as i64 * fr_den as i64, "{:?}", framerate);
+        assert!(tb_num > 0 && tb_den > 0);
+    }
+}
+
+#[test]
+fn an_hour_of_frames_takes_an_hour() {
+    // The timestamps are the frame count in the time base, which doesn't drift, however odd the rate.
+    for framerate in all() {
+        let (tb_num, tb_den) = framerate.time_base();
+        let frames = 3600 * framerate.num() as i64 / framerate.den() as i64;
+        let seconds = frames as f64 * tb_num as f64 / tb_den as f64;
+        assert!((3600.0 - seconds).abs() < framerate.frametime().as_secs_f64(), "{:?}", framerate);
+    }
+}
+
+#[test]
+fn round_trips_stay_close() {
+    for framerate in all() {
+        // Through the frame time it comes back as it was, as a nanosecond is much finer than any rate tells apart.
+        assert_eq!(Framerate::from_frametime(framerate.frametime()), framerate);
+
+        // The ticks are coarser, but still within 10 mHz at 240 fps, and never slower.
+        let back = Framerate::from_ticks(framerate.frametime_ticks());
+        let error = (back.hz() - framerate.hz()).abs();
+        assert!(error < 0.01, "{:?} came back as {:?}", framerate, back);
+        assert!(back.hz() >= framerate.hz(), "{:?} came back slower, as {:?}", framerate, back);
+    }
+}
+
+#[test]
+fn zero_intervals_are_taken_for_the_fastest_rate() {
+    assert_eq!(Framerate::from_frametime(Duration::ZERO), Framerate::whole(1000));
+    assert_eq!(Framerate::from_ticks(0), Framerate::whole(1000));
+    assert_eq!(Framerate::from_ticks(-5), Framerate::whole(1000));
+    assert_eq!(Framerate::from_ticks(TICKS_PER_SECOND as i64), Framerate::whole(1));
+}
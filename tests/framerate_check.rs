@@ -0,0 +1,113 @@
+//! When the user is told the pipeline can't keep up with the framerate, and when that is taken back.
+
+use std::time::{Duration, Instant};
+
+use fjarsyn::media::framerate_check::{FramerateCheck, FramerateCheckEvent, framerate_shortfall};
+
+const WARMUP: Duration = Duration::from_secs(10);
+
+fn shortfall(target_fps: f32, achieved_fps: f32) -> FramerateCheckEvent {
+    FramerateCheckEvent::Shortfall { target_fps, achieved_fps }
+}
+
+// A check started at the returned instant, and past its warm-up at the other.
+fn warmed_up() -> (FramerateCheck, Instant) {
+    let start = Instant::now();
+    let mut check = FramerateCheck::default();
+    check.restart(start);
+    (check, start + WARMUP)
+}
+
+#[test]
+fn nothing_falls_short_while_warming_up() {
+    assert_eq!(framerate_shortfall(60.0, 10.0, Duration::ZERO), None);
+    assert_eq!(framerate_shortfall(60.0, 10.0, WARMUP - Duration::from_millis(1)), None);
+    assert!(framerate_shortfall(60.0, 10.0, WARMUP).is_some());
+}
+
+#[test]
+fn falls_short_past_30_percent_below_the_target() {
+    let after_warmup = WARMUP * 2;
+    assert_eq!(framerate_shortfall(60.0, 60.0, after_warmup), None);
+    assert_eq!(framerate_shortfall(60.0, 43.0, after_warmup), None);
+    // Exactly 30 % is still fine.
+    assert_eq!(framerate_shortfall(100.0, 70.0, after_warmup), None);
+    let shortfall = framerate_shortfall(100.0, 69.0, after_warmup).unwrap();
+    assert!((shortfall - 0.31).abs() < 1e-4, "{shortfall}");
+    assert_eq!(framerate_shortfall(30.0, 0.0, after_warmup), Some(1.0));
+    // Above the target isn't short of it.
+    assert_eq!(framerate_shortfall(30.0, 45.0, after_warmup), None);
+    // Nor is there anything to fall short of without one.
+    assert_eq!(framerate_shortfall(0.0, 0.0, after_warmup), None);
+}
+
+#[test]
+fn says_nothing_before_it_started_or_while_warming_up() {
+    let start = Instant::now();
+    let mut check = FramerateCheck::default();
+    assert_eq!(check.evaluate(start + WARMUP, 60.0, 10.0), None);
+
+    check.restart(start);
+    assert_eq!(check.evaluate(start, 60.0, 10.0), None);
+    assert_eq!(check.evaluate(start + WARMUP / 2, 60.0, 10.0), None);
+    assert_eq!(check.evaluate(start + WARMUP, 60.0, 10.0), Some(shortfall(60.0, 10.0)));
+
+    // Stopped, and started again, which warms up again.
+    check.stop();
+    assert_eq!(check.evaluate(start + WARMUP * 2, 60.0, 10.0), None);
+    check.restart(start + WARMUP * 2);
+    assert_eq!(check.evaluate(start + WARMUP * 2 + WARMUP / 2, 60.0, 10.0), None);
+}
+
+#[test]
+fn warns_once_per_target() {
+    let (mut check, now) = warmed_up();
+    assert_eq!(check.evaluate(now, 60.0, 30.0), Some(shortfall(60.0, 30.0)));
+    // Not again while it stays short.
+    assert_eq!(check.evaluate(now + Duration::from_secs(1), 60.0, 25.0), None);
+
+    // Nor after it recovered and fell short again at the same target.
+    assert_eq!(
+        check.evaluate(now + Duration::from_secs(2), 60.0, 58.0),
+        Some(FramerateCheckEvent::Recovered)
+    );
+    assert_eq!(check.evaluate(now + Duration::from_secs(3), 60.0, 30.0), None);
+    // Nor after the encoder restarted.
+    check.restart(now + Duration::from_secs(4));
+    assert_eq!(check.evaluate(now + Duration::from_secs(4) + WARMUP, 60.0, 30.0), None);
+
+    // Another target is warned about again.
+    check.restart(now + WARMUP * 2);
+    assert_eq!(check.evaluate(now + WARMUP * 3, 30.0, 15.0), Some(shortfall(30.0, 15.0)));
+    // As is the one before, once the user went back to it.
+    check.restart(now + WARMUP * 3);
+    assert_eq!(check.evaluate(now + WARMUP * 4, 60.0, 30.0), Some(shortfall(60.0, 30.0)));
+}
+
+#[test]
+fn takes_the_warning_back_once_it_catches_up() {
+    let (mut check, now) = warmed_up();
+    assert_eq!(check.evaluate(now, 60.0, 20.0), Some(shortfall(60.0, 20.0)));
+    assert_eq!(
+        check.evaluate(now + Duration::from_secs(1), 60.0, 50.0),
+        Some(FramerateCheckEvent::Recovered)
+    );
+    // Only once.
+    assert_eq!(check.evaluate(now + Duration::from_secs(2), 60.0, 60.0), None);
+
+    // Recovering is also told when the warning wasn't repeated, so the UI doesn't keep one that no longer holds.
+    assert_eq!(check.evaluate(now + Duration::from_secs(3), 60.0, 20.0), None);
+    assert_eq!(
+        check.evaluate(now + Duration::from_secs(4), 60.0, 50.0),
+        Some(FramerateCheckEvent::Recovered)
+    );
+}
+
+#[test]
+fn a_restart_forgets_that_it_was_falling_short() {
+    let (mut check, now) = warmed_up();
+    assert_eq!(check.evaluate(now, 60.0, 20.0), Some(shortfall(60.0, 20.0)));
+    check.restart(now);
+    // Caught up after the restart, which there is nothing to take back from.
+    assert_eq!(check.evaluate(now + WARMUP, 60.0, 60.0), None);
+}
@@ -0,0 +1,134 @@
+//! Both peers calling each other at once.
+
+use std::time::Duration;
+
+use bifrost::SignalingServer;
+use fjarsyn::{
+    networking::webrtc::{GlareRole, WebRTCEvent, glare_role},
+    session::{CallHandle, CallOptions, CallSession},
+};
+use tokio::net::TcpListener;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+const LOWER_ID: &str = "3f2b8c1e-0000-4000-8000-000000000000";
+const HIGHER_ID: &str = "a91d44f0-0000-4000-8000-000000000000";
+
+#[test]
+fn the_greater_id_keeps_its_offer() {
+    assert_eq!(glare_role(HIGHER_ID, LOWER_ID), GlareRole::Keep);
+    assert_eq!(glare_role(LOWER_ID, HIGHER_ID), GlareRole::Yield);
+}
+
+#[test]
+fn both_ends_pick_opposite_roles() {
+    let ids = ["a", "b", "ab", "B", "0", "ffffffff", "00000000-0000"];
+    for local in ids {
+        for remote in ids.iter().filter(|&&remote| remote != local) {
+            assert_ne!(
+                glare_role(local, remote),
+                glare_role(remote, local),
+                "{} and {} picked the same role",
+                local,
+                remote
+            );
+        }
+    }
+}
+
+async fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { SignalingServer::new().serve(listener).await });
+    format!("ws://{}/ws", addr)
+}
+
+// The events until the call connects, which must not announce it as incoming.
+async fn events_until_connected(session: &CallHandle) -> Vec<WebRTCEvent> {
+    let mut events = Vec::new();
+    loop {
+        let event = session.next_event().await.expect("the events ended before connecting");
+        let connected = matches!(event, WebRTCEvent::Connected);
+        assert!(!matches!(event, WebRTCEvent::Disconnected), "the call ended: {:?}", events);
+        events.push(event);
+        if connected {
+            return events;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn crossed_calls_connect_once() {
+    let url = start_server().await;
+    let first = CallSession::listen(url.clone(), CallOptions::default()).await.unwrap();
+    let second = CallSession::listen(url, CallOptions::default()).await.unwrap();
+    let first_id = first.webrtc().get_local_id().unwrap();
+    let second_id = second.webrtc().get_local_id().unwrap();
+
+    let (called_first, called_second) =
+        tokio::join!(first.call(&second_id), second.call(&first_id));
+    called_first.unwrap();
+    called_second.unwrap();
+
+    let (first_events, second_events) = tokio::time::timeout(CONNECT_TIMEOUT, async {
+        tokio::join!(events_until_connected(&first), events_until_connected(&second))
+    })
+    .await
+    .expect("the crossed calls didn't connect");
+
+    let (keeping, yielding) = match glare_role(&first_id, &second_id) {
+        GlareRole::Keep => (first_events, second_events),
+        GlareRole::Yield => (second_events, first_events),
+    };
+    // The end that kept its offer got an answer, the other answered, and neither rang again.
+    assert!(keeping.iter().any(|event| matches!(event, WebRTCEvent::Answered(_))));
+    assert!(yielding.iter().any(|event| matches!(event, WebRTCEvent::CallsCrossed(..))));
+    for event in keeping.iter().chain(&yielding) {
+        assert!(!matches!(event, WebRTCEvent::IncomingCall(..)), "rang again: {:?}", event);
+    }
+
+    first.close().await.unwrap();
+    second.close().await.unwrap();
+}
+
+// The next event of the kind, skipping the others.
+async fn wait_for(session: &CallHandle, matches: impl Fn(&WebRTCEvent) -> bool) -> WebRTCEvent {
+    tokio::time::timeout(CONNECT_TIMEOUT, async {
+        loop {
+            let event = session.next_event().await.expect("the events ended");
+            if matches(&event) {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("the event didn't come")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_call_from_another_peer_is_turned_down_as_busy() {
+    let url = start_server().await;
+    let caller = CallSession::listen(url.clone(), CallOptions::default()).await.unwrap();
+    let callee = CallSession::listen(url.clone(), CallOptions::default()).await.unwrap();
+    let other = CallSession::listen(url, CallOptions::default()).await.unwrap();
+    let caller_id = caller.webrtc().get_local_id().unwrap();
+    let callee_id = callee.webrtc().get_local_id().unwrap();
+
+    caller.call(&callee_id).await.unwrap();
+    wait_for(&callee, |event| matches!(event, WebRTCEvent::IncomingCall(..))).await;
+
+    // The caller's offer is still pending, but this call isn't the one it crossed with.
+    other.call(&caller_id).await.unwrap();
+    wait_for(&other, |event| matches!(event, WebRTCEvent::Disconnected)).await;
+    assert_eq!(caller.webrtc().get_remote_id(), Some(callee_id));
+
+    callee.webrtc().accept_call().await.unwrap();
+    let events = tokio::time::timeout(CONNECT_TIMEOUT, events_until_connected(&caller))
+        .await
+        .expect("the call didn't connect");
+    assert!(!events.iter().any(|event| matches!(event, WebRTCEvent::CallsCrossed(..))));
+
+    caller.close().await.unwrap();
+    callee.close().await.unwrap();
+    other.close().await.unwrap();
+}
@@ -0,0 +1,120 @@
+use bytes::BytesMut;
+use fjarsyn::utils::{
+    bitmap_utils::rgba16f_to_rgba8, buffer_arena::BufferRef, frame::Frame,
+    pixel_format::PixelFormat, vector2::Vector2,
+};
+
+const F16_INFINITY: u16 = 0x7c00;
+const F16_NAN: u16 = 0x7e00;
+
+// The half float of a value that has an exact one, as every value here does.
+fn f16(value: f32) -> u16 {
+    if value == 0.0 {
+        return 0;
+    }
+    let bits = value.to_bits();
+    let sign = (bits >> 16) & 0x8000;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    assert!((1..31).contains(&exponent), "{} is out of the half float range", value);
+    (sign | ((exponent as u32) << 10) | ((bits >> 13) & 0x3ff)) as u16
+}
+
+fn bitmap(pixels: &[[u16; 4]]) -> Vec<u8> {
+    pixels.iter().flatten().flat_map(|channel| channel.to_le_bytes()).collect()
+}
+
+fn pixel(r: f32, g: f32, b: f32, a: f32) -> [u16; 4] {
+    [f16(r), f16(g), f16(b), f16(a)]
+}
+
+fn convert(pixels: &[[u16; 4]]) -> Vec<u8> {
+    let mut bitmap = bitmap(pixels);
+    rgba16f_to_rgba8(&mut bitmap).to_vec()
+}
+
+// What the value is in sRGB, without any tone mapping.
+fn srgb8(linear: f32) -> u8 {
+    let encoded =
+        if linear <= 0.0031308 { linear * 12.92 } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 };
+    (encoded.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn grey(linear: f32) -> u8 {
+    convert(&[pixel(linear, linear, linear, 1.0)])[0]
+}
+
+#[test]
+fn sdr_tones_are_only_encoded() {
+    for linear in [0.0, 0.001, 0.0625, 0.125, 0.25, 0.5, 0.625] {
+        let converted = grey(linear);
+        assert!(converted.abs_diff(srgb8(linear)) <= 1, "{} became {}", linear, converted);
+    }
+}
+
+#[test]
+fn highlights_above_one_stay_apart() {
+    let highlights: Vec<u8> = [0.75, 1.0, 1.5, 2.0, 4.0, 8.0].into_iter().map(grey).collect();
+    assert!(highlights.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", highlights);
+    assert!(highlights.iter().all(|&value| value < 255), "{:?}", highlights);
+    // Past the peak there is nothing brighter to tell apart from.
+    assert_eq!(grey(12.5), 255);
+    assert_eq!(grey(64.0), 255);
+}
+
+#[test]
+fn values_out_of_range_are_clamped() {
+    assert_eq!(grey(-0.5), 0);
+    let special = [[F16_INFINITY, F16_NAN, f16(-2.0), f16(1.0)]];
+    assert_eq!(convert(&special)[..3], [255, 0, 0]);
+
+    // Alpha is only clamped, neither tone-mapped nor encoded.
+    let alphas = [2.0, 1.0, 0.5, 0.0, -1.0].map(|alpha| pixel(0.0, 0.0, 0.0, alpha));
+    let converted: Vec<u8> = convert(&alphas).chunks(4).map(|pixel| pixel[3]).collect();
+    assert_eq!(converted, [255, 255, 128, 0, 0]);
+}
+
+#[test]
+fn channels_are_converted_on_their_own() {
+    let converted = convert(&[pixel(4.0, 0.25, 0.0, 1.0)]);
+    assert_eq!(converted, [grey(4.0), grey(0.25), 0, 255]);
+}
+
+#[test]
+fn conversion_in_place_keeps_every_pixel() {
+    let pixels: Vec<[u16; 4]> = (0..64)
+        .map(|i| {
+            let value = i as f32 / 8.0;
+            pixel(value, 8.0 - value, value / 2.0, 1.0)
+        })
+        .collect();
+    let converted = convert(&pixels);
+    assert_eq!(converted.len(), pixels.len() * 4);
+    for (pixel, expected) in converted.chunks(4).zip(&pixels) {
+        assert_eq!(pixel, convert(&[*expected]));
+    }
+}
+
+#[test]
+fn hdr_frames_are_tone_mapped_to_rgba8() {
+    let size = Vector2::new(4, 2);
+    let pixels = vec![pixel(3.0, 0.5, 0.0, 1.0); 8];
+    let data = BufferRef::detached(BytesMut::from(&bitmap(&pixels)[..]));
+
+    let frame = Frame::new_ensure_rgba(data, PixelFormat::RGBA16, size, None, None);
+    assert_eq!(frame.format, PixelFormat::RGBA8);
+    assert!(frame.hdr);
+    assert_eq!(frame.data.len(), 4 * 2 * 4);
+    assert!(frame.data.chunks(4).all(|pixel| pixel == convert(&pixels[..1])));
+}
+
+#[test]
+fn sdr_frames_are_left_as_they_were() {
+    let size = Vector2::new(2, 1);
+    let bgra = [10, 20, 30, 255, 40, 50, 60, 255];
+    let data = BufferRef::detached(BytesMut::from(&bgra[..]));
+
+    let frame = Frame::new_ensure_rgba(data, PixelFormat::BGRA8, size, None, None);
+    assert_eq!(frame.format, PixelFormat::RGBA8);
+    assert!(!frame.hdr);
+    assert_eq!(&frame.data[..], [30, 20, 10, 255, 60, 50, 40, 255]);
+}
@@ -0,0 +1,128 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use fjarsyn::ui::i18n::{self, Language, Locale, fill, parse};
+
+// The keys looked up with `tr!("key", ...)` or `tr("key")` in a source file.
+fn referenced_keys(source: &str, keys: &mut BTreeSet<String>) {
+    for pattern in ["tr!(", "tr("] {
+        for (start, _) in source.match_indices(pattern) {
+            // Not the end of another name, like `attr(`.
+            if source[..start].ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                continue;
+            }
+            let rest = source[start + pattern.len()..].trim_start();
+            if let Some(literal) = rest.strip_prefix('"')
+                && let Some(end) = literal.find('"')
+            {
+                keys.insert(literal[..end].to_owned());
+            }
+        }
+    }
+}
+
+fn collect_keys(dir: &Path, keys: &mut BTreeSet<String>) {
+    for entry in fs::read_dir(dir).expect("Failed to read the sources") {
+        let path = entry.expect("Failed to read the sources").path();
+        if path.is_dir() {
+            collect_keys(&path, keys);
+        } else if path.extension().is_some_and(|extension| extension == "rs") {
+            referenced_keys(&fs::read_to_string(&path).expect("Failed to read a source"), keys);
+        }
+    }
+}
+
+// The names of the `{name}` parameters in a string.
+fn placeholders(string: &str) -> BTreeSet<&str> {
+    string
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+        .collect()
+}
+
+#[test]
+fn every_referenced_key_is_in_every_locale() {
+    let mut keys = BTreeSet::new();
+    collect_keys(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut keys);
+    assert!(keys.len() > 100, "Only found {} keys", keys.len());
+
+    for locale in Locale::ALL {
+        let missing: Vec<_> = keys.iter().filter(|key| locale.get(key).is_none()).collect();
+        assert!(missing.is_empty(), "{:?} is missing {:?}", locale, missing);
+    }
+}
+
+#[test]
+fn locales_have_the_same_keys_and_parameters() {
+    let english = Locale::English.strings();
+    for locale in Locale::ALL {
+        let strings = locale.strings();
+        let extra: Vec<_> = strings.keys().filter(|key| !english.contains_key(*key)).collect();
+        assert!(extra.is_empty(), "{:?} has keys English doesn't: {:?}", locale, extra);
+
+        for (key, string) in strings {
+            assert_eq!(
+                placeholders(string),
+                placeholders(english[key]),
+                "{:?} has other parameters for {}",
+                locale,
+                key
+            );
+        }
+    }
+}
+
+#[test]
+fn fills_in_parameters() {
+    assert_eq!(
+        fill("Calling {peer}{dots}", &[("peer", &"peer-a"), ("dots", &"..")]),
+        "Calling peer-a.."
+    );
+    assert_eq!(fill("{count} of {count}", &[("count", &3)]), "3 of 3");
+    // Those without an argument are left for someone to notice.
+    assert_eq!(fill("Hello {name}", &[]), "Hello {name}");
+    assert_eq!(fill("Unclosed {brace", &[("brace", &1)]), "Unclosed {brace");
+}
+
+#[test]
+fn parses_resources() {
+    let strings =
+        parse("# A comment\n\n  a.key = Some value  \nbroken line\nb = One\\nTwo\nc = x = y\n");
+    assert_eq!(strings.len(), 3);
+    assert_eq!(strings["a.key"], "Some value");
+    assert_eq!(strings["b"], "One\nTwo");
+    assert_eq!(strings["c"], "x = y");
+}
+
+#[test]
+fn finds_the_locale_of_a_language_tag() {
+    assert_eq!(Locale::from_tag("is-IS"), Some(Locale::Icelandic));
+    assert_eq!(Locale::from_tag("en_GB.UTF-8"), Some(Locale::English));
+    assert_eq!(Locale::from_tag("IS"), Some(Locale::Icelandic));
+    assert_eq!(Locale::from_tag("de-DE"), None);
+    assert_eq!(Locale::from_tag(""), None);
+}
+
+#[test]
+fn language_is_stored_by_name() {
+    for language in Language::ALL {
+        let json = serde_json::to_string(&language).unwrap();
+        assert_eq!(serde_json::from_str::<Language>(&json).unwrap(), language);
+    }
+    assert_eq!(serde_json::to_string(&Language::Icelandic).unwrap(), r#""Icelandic""#);
+    assert_eq!(Language::default(), Language::System);
+}
+
+// The only test that changes the language, as it is shared by the whole process.
+#[test]
+fn switches_language() {
+    assert_eq!(i18n::current_locale(), Locale::English);
+    assert_eq!(fjarsyn::tr!("common.copy"), "Copy");
+
+    i18n::set_language(Language::Icelandic);
+    assert_eq!(fjarsyn::tr!("common.copy"), "Afrita");
+    assert_eq!(fjarsyn::tr!("home.my_id", id = 42), "Auðkennið mitt: 42");
+
+    i18n::set_language(Language::English);
+    assert_eq!(fjarsyn::tr!("home.my_id", id = 42), "My ID: 42");
+}
@@ -0,0 +1,67 @@
+use std::{fs, path::PathBuf};
+
+use fjarsyn::networking::identity::IdentityKey;
+use fjarsyn_shared::peer_id_from_public_key;
+
+const NONCE: &str = "5f0e2c7a9b1d4e3f8a6c0b2d4e6f8a1c";
+
+// A file of its own per test, as they run in parallel.
+fn key_path(test: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("fjarsyn-identity-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir.join("identity_key")
+}
+
+#[test]
+fn signs_for_the_id_of_its_key() {
+    let key = IdentityKey::generate();
+    assert_eq!(key.authenticate(NONCE).verify(NONCE), Some(key.peer_id()));
+    assert_eq!(key.peer_id(), peer_id_from_public_key(&key.public_key()));
+}
+
+#[test]
+fn signature_is_only_good_for_its_nonce() {
+    let key = IdentityKey::generate();
+    assert_eq!(key.authenticate(NONCE).verify("another nonce"), None);
+}
+
+#[test]
+fn same_secret_gives_the_same_id() {
+    let first = IdentityKey::from_bytes(&[3; 32]);
+    let second = IdentityKey::from_bytes(&[3; 32]);
+    assert_eq!(first.peer_id(), second.peer_id());
+    assert_ne!(first.peer_id(), IdentityKey::from_bytes(&[4; 32]).peer_id());
+}
+
+#[test]
+fn keeps_the_key_across_runs() {
+    let path = key_path("keeps");
+    let created = IdentityKey::load_or_create(&path).unwrap();
+    let loaded = IdentityKey::load_or_create(&path).unwrap();
+    assert_eq!(created.peer_id(), loaded.peer_id());
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn replaces_a_malformed_key() {
+    let path = key_path("malformed");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, b"too short").unwrap();
+
+    let key = IdentityKey::load_or_create(&path).unwrap();
+    assert_eq!(fs::read(&path).unwrap().len(), 32);
+    assert_eq!(IdentityKey::load_or_create(&path).unwrap().peer_id(), key.peer_id());
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn stores_the_key_for_the_user_only() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = key_path("permissions");
+    IdentityKey::load_or_create(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
@@ -0,0 +1,223 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::networking::impairment::{
+    ImpairedLink, Impairment, ImpairmentConfig, LossPattern, MAX_QUEUE_DELAY, SeededRng,
+    TokenBucket,
+};
+use tokio::sync::mpsc;
+
+const PACKETS: usize = 100_000;
+
+fn config(loss: f32, latency_ms: u64, jitter_ms: u64, bandwidth: Option<u32>) -> ImpairmentConfig {
+    ImpairmentConfig {
+        loss,
+        burst_length: 1.0,
+        latency: Duration::from_millis(latency_ms),
+        jitter: Duration::from_millis(jitter_ms),
+        bandwidth,
+        seed: 7,
+        send: false,
+        receive: true,
+    }
+}
+
+// Whether each of the packets is dropped.
+fn drops(loss: f32, burst_length: f32, seed: u64) -> Vec<bool> {
+    let mut rng = SeededRng::new(seed);
+    let mut pattern = LossPattern::new(loss, burst_length);
+    (0..PACKETS).map(|_| pattern.drops(&mut rng)).collect()
+}
+
+// Rounded, as the delays are computed in floating point.
+fn millis(delay: Duration) -> u64 {
+    (delay.as_secs_f64() * 1000.0).round() as u64
+}
+
+fn mean_burst(drops: &[bool]) -> f64 {
+    let bursts = drops.windows(2).filter(|pair| !pair[0] && pair[1]).count();
+    drops.iter().filter(|dropped| **dropped).count() as f64 / bursts as f64
+}
+
+#[test]
+fn same_seed_same_numbers() {
+    let mut a = SeededRng::new(42);
+    let mut b = SeededRng::new(42);
+    let mut c = SeededRng::new(43);
+    let a: Vec<_> = (0..100).map(|_| a.next_u64()).collect();
+    let b: Vec<_> = (0..100).map(|_| b.next_u64()).collect();
+    let c: Vec<_> = (0..100).map(|_| c.next_u64()).collect();
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
+
+#[test]
+fn fractions_stay_below_one() {
+    let mut rng = SeededRng::new(1);
+    let values: Vec<_> = (0..PACKETS).map(|_| rng.next_f64()).collect();
+    assert!(values.iter().all(|value| (0.0..1.0).contains(value)));
+    let mean = values.iter().sum::<f64>() / PACKETS as f64;
+    assert!((mean - 0.5).abs() < 0.01, "mean {}", mean);
+}
+
+#[test]
+fn drops_the_share_asked_for() {
+    for loss in [0.01, 0.05, 0.2] {
+        let dropped = drops(loss, 1.0, 3).iter().filter(|dropped| **dropped).count();
+        let share = dropped as f32 / PACKETS as f32;
+        assert!((share - loss).abs() < loss * 0.1, "{} dropped instead of {}", share, loss);
+    }
+}
+
+#[test]
+fn drops_in_bursts() {
+    let independent = drops(0.05, 1.0, 3);
+    let bursty = drops(0.05, 4.0, 3);
+    assert!((mean_burst(&independent) - 1.05).abs() < 0.1);
+    assert!((mean_burst(&bursty) - 4.0).abs() < 0.4, "bursts of {}", mean_burst(&bursty));
+
+    let share = bursty.iter().filter(|dropped| **dropped).count() as f32 / PACKETS as f32;
+    assert!((share - 0.05).abs() < 0.01, "{} dropped", share);
+}
+
+#[test]
+fn drops_nothing_or_everything_at_the_ends() {
+    assert!(drops(0.0, 3.0, 5).iter().all(|dropped| !dropped));
+    assert!(drops(1.0, 3.0, 5).iter().all(|dropped| *dropped));
+}
+
+#[test]
+fn bucket_lets_a_burst_through_then_holds_back() {
+    let start = Instant::now();
+    // 80 kbit/s is 10 kB/s, and the bucket holds 50 ms of that.
+    let mut bucket = TokenBucket::new(80_000, start);
+    assert_eq!(bucket.delay(500, start), Duration::ZERO);
+    assert_eq!(millis(bucket.delay(500, start)), 50);
+    assert_eq!(millis(bucket.delay(1_000, start)), 150);
+
+    // Dropping the last one gives its share back.
+    bucket.refund(1_000);
+    assert_eq!(millis(bucket.delay(1_000, start)), 150);
+}
+
+#[test]
+fn bucket_fills_up_while_idle_but_no_more_than_it_holds() {
+    let start = Instant::now();
+    let mut bucket = TokenBucket::new(80_000, start);
+    assert_eq!(millis(bucket.delay(1_500, start)), 100);
+
+    // 100 ms pays off the debt, the second after that only fills it to 500 bytes.
+    let later = start + Duration::from_millis(1_100);
+    assert_eq!(bucket.delay(500, later), Duration::ZERO);
+    assert_eq!(millis(bucket.delay(100, later)), 10);
+}
+
+#[test]
+fn adds_latency_and_jitter_in_order() {
+    let start = Instant::now();
+    let mut impairment = Impairment::new(config(0.0, 100, 40, None), start);
+    let mut last = start;
+    for packet in 0..1_000u64 {
+        let now = start + Duration::from_millis(packet);
+        let due = impairment.admit(1_000, now).expect("Nothing should be dropped");
+        // Never sooner than the latency less the jitter, and never before the packet before it.
+        assert!(due >= now + Duration::from_millis(60));
+        assert!(due <= now + Duration::from_millis(140) || due == last);
+        assert!(due >= last);
+        last = due;
+    }
+    assert_eq!(impairment.stats().passed, 1_000);
+}
+
+#[test]
+fn same_seed_same_impairment() {
+    let start = Instant::now();
+    let run = || {
+        let mut impairment = Impairment::new(config(0.1, 20, 10, Some(1_000_000)), start);
+        (0..1_000u64)
+            .map(|packet| impairment.admit(1_200, start + Duration::from_millis(packet)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn overflows_when_held_back_too_long() {
+    let start = Instant::now();
+    let mut impairment = Impairment::new(config(0.0, 0, 0, Some(80_000)), start);
+    // 10 kB/s takes a second for 10 kB, so the queue overflows just past that.
+    let passed = (0..20).filter(|_| impairment.admit(1_000, start).is_some()).count();
+    assert_eq!(passed, 10);
+
+    let stats = impairment.stats();
+    assert_eq!((stats.passed, stats.overflowed, stats.lost), (10, 10, 0));
+    assert!(stats.last_delay <= MAX_QUEUE_DELAY);
+}
+
+#[test]
+fn reads_the_environment_variables() {
+    let vars = |pairs: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            pairs.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        }
+    };
+
+    assert_eq!(ImpairmentConfig::from_vars(vars(&[])), None);
+    // Only making the packets come in bursts doesn't drop any of them.
+    assert_eq!(ImpairmentConfig::from_vars(vars(&[("BURST", "3"), ("SEED", "9")])), None);
+
+    let config = ImpairmentConfig::from_vars(vars(&[
+        ("LOSS", "0.05"),
+        ("BURST", "3"),
+        ("LATENCY", "80"),
+        ("JITTER", "not a number"),
+        ("BANDWIDTH", "2000000"),
+        ("SEED", "9"),
+        ("DIRECTION", "both"),
+    ]))
+    .expect("Should impair");
+    assert_eq!(
+        config,
+        ImpairmentConfig {
+            loss: 0.05,
+            burst_length: 3.0,
+            latency: Duration::from_millis(80),
+            jitter: Duration::ZERO,
+            bandwidth: Some(2_000_000),
+            seed: 9,
+            send: true,
+            receive: true,
+        }
+    );
+    assert_eq!(config.to_string(), "5.0% loss in bursts of 3.0, 80 ms, 2.00 Mbps cap");
+
+    let config = ImpairmentConfig::from_vars(vars(&[("LOSS", "2"), ("DIRECTION", "send")]))
+        .expect("Should impair");
+    assert_eq!((config.loss, config.send, config.receive), (1.0, true, false));
+}
+
+#[tokio::test]
+async fn link_delivers_in_order_once_due() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let link = ImpairedLink::spawn(
+        Impairment::new(config(0.0, 30, 0, None), Instant::now()),
+        move |item: u32| {
+            let tx = tx.clone();
+            async move { tx.send((item, Instant::now())).is_ok() }
+        },
+    );
+
+    let sent = Instant::now();
+    for item in 0..5 {
+        assert!(link.send(item, 100));
+    }
+    for expected in 0..5 {
+        let (item, at) = rx.recv().await.expect("Should be delivered");
+        assert_eq!(item, expected);
+        assert!(at >= sent + Duration::from_millis(30));
+    }
+    assert_eq!(link.stats().passed, 5);
+
+    link.close();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(!link.send(5, 100));
+}
@@ -0,0 +1,69 @@
+//! Accepting and declining a call, over connections that only reach each other.
+
+use std::time::Duration;
+
+use fjarsyn::{
+    networking::{
+        signaling::LOOPBACK_IDS,
+        webrtc::{WebRTC, WebRTCEvent},
+    },
+    session::{CallChannels, CallOptions},
+};
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn next_event(channels: &CallChannels) -> WebRTCEvent {
+    let events = channels.events();
+    let event = tokio::time::timeout(RECV_TIMEOUT, events.lock().await.recv()).await.unwrap();
+    event.expect("the events ended").event
+}
+
+async fn wait_for(channels: &CallChannels, expected: fn(&WebRTCEvent) -> bool) -> WebRTCEvent {
+    loop {
+        let event = next_event(channels).await;
+        if expected(&event) {
+            return event;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_declined_call_ends_and_the_next_one_can_be_accepted() {
+    let options = CallOptions::default();
+    let [(caller, caller_receivers), (callee, callee_receivers)] = WebRTC::loopback(
+        options.max_depacket_latency,
+        options.auto_depacket_latency,
+        options.receive_buffer,
+        options.transcoding_type,
+    )
+    .await
+    .unwrap();
+    let caller_channels = CallChannels::from(caller_receivers);
+    let callee_channels = CallChannels::from(callee_receivers);
+
+    caller.create_offer(LOOPBACK_IDS[1].to_owned()).await.unwrap();
+    let event = next_event(&callee_channels).await;
+    assert!(
+        matches!(&event, WebRTCEvent::IncomingCall(from, _) if from == LOOPBACK_IDS[0]),
+        "unexpected event: {:?}",
+        event
+    );
+    // Not answered until accepted.
+    assert!(callee.peer_connection().local_description().await.is_none());
+
+    callee.decline_call().await.unwrap();
+    wait_for(&caller_channels, |event| matches!(event, WebRTCEvent::Disconnected)).await;
+    assert_eq!(callee.get_remote_id(), None);
+    assert!(callee.peer_connection().remote_description().await.is_none());
+
+    // Calling again rings again, as the declined offer was taken back.
+    caller.create_offer(LOOPBACK_IDS[1].to_owned()).await.unwrap();
+    wait_for(&callee_channels, |event| matches!(event, WebRTCEvent::IncomingCall(..))).await;
+    callee.accept_call().await.unwrap();
+    wait_for(&caller_channels, |event| matches!(event, WebRTCEvent::Answered(_))).await;
+    wait_for(&caller_channels, |event| matches!(event, WebRTCEvent::Connected)).await;
+    wait_for(&callee_channels, |event| matches!(event, WebRTCEvent::Connected)).await;
+
+    caller.shutdown().await.unwrap();
+    callee.shutdown().await.unwrap();
+}
@@ -0,0 +1,68 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
+
+use fjarsyn::platform::input_hook::{self, MouseHook};
+
+// The count of installed hooks is process-wide, so the tests take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn serial() -> MutexGuard<'static, ()> {
+    SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[test]
+fn the_hook_is_removed_when_dropped() {
+    let _serial = serial();
+    assert_eq!(input_hook::installed(), 0);
+    let hook = MouseHook::install(|_| {}).unwrap();
+    assert_eq!(input_hook::installed(), 1);
+    drop(hook);
+    assert_eq!(input_hook::installed(), 0);
+}
+
+#[test]
+fn every_hook_is_removed() {
+    let _serial = serial();
+    let hooks: Vec<_> = (0..3).map(|_| MouseHook::install(|_| {}).unwrap()).collect();
+    assert_eq!(input_hook::installed(), 3);
+    let mut hooks = hooks.into_iter();
+    drop(hooks.next());
+    assert_eq!(input_hook::installed(), 2);
+    drop(hooks);
+    assert_eq!(input_hook::installed(), 0);
+}
+
+#[test]
+fn the_callback_goes_with_the_hook() {
+    let _serial = serial();
+    // Whatever the callback holds on to is let go of along with the hook.
+    let held = Arc::new(());
+    let in_callback = held.clone();
+    let hook = MouseHook::install(move |_| {
+        let _ = &in_callback;
+    })
+    .unwrap();
+    assert_eq!(Arc::strong_count(&held), 2);
+    drop(hook);
+    assert_eq!(Arc::strong_count(&held), 1);
+}
+
+#[test]
+fn it_can_be_dropped_on_another_thread() {
+    let _serial = serial();
+    let hook = MouseHook::install(|_| {}).unwrap();
+    thread::spawn(move || drop(hook)).join().unwrap();
+    assert_eq!(input_hook::installed(), 0);
+}
+
+#[test]
+fn dropping_right_away_doesnt_hang() {
+    let _serial = serial();
+    // The quit message can come before the hook's thread is waiting for it.
+    for _ in 0..20 {
+        drop(MouseHook::install(|_| {}).unwrap());
+    }
+    assert_eq!(input_hook::installed(), 0);
+}
@@ -0,0 +1,85 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fjarsyn::{
+    ui::local_preview::LocalPreview,
+    utils::{
+        frame::Frame,
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+
+const SIZE: Vector2<i32> = Vector2 { x: 2560, y: 1440 };
+
+fn frames() -> impl Iterator<Item = Arc<Frame>> {
+    let mut frames = SyntheticFrames::new(SIZE, PixelFormat::RGBA8, FramePattern::Gradient);
+    (1..).map(move |sequence| Arc::new(frames.next_frame().with_sequence(sequence)))
+}
+
+// Offers a frame every 1/60 s for a second, returning how many were taken.
+fn taken_in_a_second(preview: &mut LocalPreview, start: Instant) -> usize {
+    frames()
+        .take(60)
+        .enumerate()
+        .filter(|(i, frame)| preview.offer(frame, start + Duration::from_secs(1) * *i as u32 / 60))
+        .count()
+}
+
+#[test]
+fn caps_the_rate_while_small() {
+    let mut preview = LocalPreview::new(10);
+    assert_eq!(taken_in_a_second(&mut preview, Instant::now()), 10);
+}
+
+#[test]
+fn takes_every_frame_when_enlarged() {
+    let mut preview = LocalPreview::new(10);
+    preview.set_enlarged(true);
+    assert_eq!(taken_in_a_second(&mut preview, Instant::now()), 60);
+}
+
+#[test]
+fn takes_the_first_frame_straight_away() {
+    let start = Instant::now();
+    let mut preview = LocalPreview::new(10);
+    let mut frames = frames();
+    assert!(preview.offer(&frames.next().unwrap(), start));
+    assert!(!preview.offer(&frames.next().unwrap(), start + Duration::from_millis(50)));
+    assert!(preview.offer(&frames.next().unwrap(), start + Duration::from_millis(100)));
+
+    // A new rate starts over.
+    preview.set_fps(2);
+    assert!(preview.offer(&frames.next().unwrap(), start + Duration::from_millis(110)));
+    assert!(!preview.offer(&frames.next().unwrap(), start + Duration::from_millis(400)));
+}
+
+#[test]
+fn shows_a_scaled_down_copy_while_small() {
+    let mut preview = LocalPreview::new(10);
+    assert!(preview.frame().is_none());
+
+    let frame = frames().next().unwrap();
+    preview.offer(&frame, Instant::now());
+    let small = preview.frame().unwrap();
+    assert_eq!(small.size, LocalPreview::SMALL_SIZE);
+    assert_eq!(small.sequence, frame.sequence);
+    assert_eq!(small.data.len(), 320 * 180 * 4);
+
+    // Scaled once per frame, not on every paint.
+    assert!(Arc::ptr_eq(&small, &preview.frame().unwrap()));
+
+    preview.set_enlarged(true);
+    assert!(Arc::ptr_eq(&preview.frame().unwrap(), &frame));
+}
+
+#[test]
+fn clear_forgets_the_frame() {
+    let mut preview = LocalPreview::new(10);
+    preview.offer(&frames().next().unwrap(), Instant::now());
+    preview.clear();
+    assert!(preview.frame().is_none());
+}
@@ -0,0 +1,39 @@
+use std::{sync::Arc, time::Duration};
+
+use fjarsyn::utils::locked_stream::stream_when_unlocked;
+use futures::{StreamExt, stream};
+use tokio::sync::{RwLock, mpsc};
+
+const DEADLINE: Duration = Duration::from_millis(500);
+
+#[tokio::test]
+async fn keeps_the_update_loop_responsive_while_the_lock_is_held() {
+    let lock = Arc::new(RwLock::new(0));
+    // E.g. a capture being started while the subscription is made.
+    let guard = lock.clone().write_owned().await;
+
+    let frames = stream_when_unlocked(lock.clone(), |streams| {
+        *streams += 1;
+        Some(stream::iter([1, 2, 3]))
+    });
+    let frames = tokio::spawn(frames.collect::<Vec<_>>());
+
+    // Stands in for the update loop, which has to keep handling messages.
+    let (messages, mut update_loop) = mpsc::unbounded_channel();
+    messages.send("Tick").unwrap();
+    let message = tokio::time::timeout(DEADLINE, update_loop.recv()).await;
+    assert_eq!(message, Ok(Some("Tick")));
+    assert!(!frames.is_finished());
+
+    drop(guard);
+    let frames = tokio::time::timeout(DEADLINE, frames).await.unwrap().unwrap();
+    assert_eq!(frames, [1, 2, 3]);
+    assert_eq!(*lock.read().await, 1);
+}
+
+#[tokio::test]
+async fn ends_without_items_when_creation_fails() {
+    let lock = Arc::new(RwLock::new(()));
+    let frames = stream_when_unlocked(lock, |_| None::<stream::Iter<std::vec::IntoIter<u32>>>);
+    assert_eq!(frames.collect::<Vec<_>>().await, Vec::<u32>::new());
+}
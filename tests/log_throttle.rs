@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::utils::log_throttle::LogThrottle;
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+#[test]
+fn the_first_line_passes() {
+    let mut throttle = LogThrottle::new(INTERVAL);
+    assert_eq!(throttle.pass(Instant::now()), Some(0));
+}
+
+#[test]
+fn holds_back_lines_within_the_interval() {
+    let start = Instant::now();
+    let mut throttle = LogThrottle::new(INTERVAL);
+    throttle.pass(start);
+    assert_eq!(throttle.pass(start), None);
+    assert_eq!(throttle.pass(start + INTERVAL / 2), None);
+    assert_eq!(throttle.pass(start + INTERVAL - Duration::from_millis(1)), None);
+}
+
+#[test]
+fn counts_the_lines_held_back_once_the_interval_passed() {
+    let start = Instant::now();
+    let mut throttle = LogThrottle::new(INTERVAL);
+    throttle.pass(start);
+    for frame in 1..=60 {
+        throttle.pass(start + Duration::from_millis(frame * 16));
+    }
+    assert_eq!(throttle.pass(start + INTERVAL), Some(60));
+
+    // The count starts over with each line logged.
+    assert_eq!(throttle.pass(start + INTERVAL + Duration::from_secs(1)), None);
+    assert_eq!(throttle.pass(start + INTERVAL * 2), Some(1));
+}
+
+#[test]
+fn the_interval_counts_from_the_last_line_logged() {
+    let start = Instant::now();
+    let mut throttle = LogThrottle::new(INTERVAL);
+    throttle.pass(start);
+    throttle.pass(start + INTERVAL - Duration::from_secs(1));
+    // Held back lines don't push the next one out.
+    assert_eq!(throttle.pass(start + INTERVAL), Some(1));
+}
+
+#[test]
+fn a_zero_interval_lets_everything_through() {
+    let now = Instant::now();
+    let mut throttle = LogThrottle::new(Duration::ZERO);
+    assert_eq!(throttle.pass(now), Some(0));
+    assert_eq!(throttle.pass(now), Some(0));
+}
+
+#[test]
+fn the_macro_takes_fields_and_format_arguments() {
+    // Without a subscriber nothing is enabled, so this only checks it can be used like the tracing macros.
+    for frame in 0..10 {
+        fjarsyn::log_throttled!(tracing::Level::TRACE, frame, "Frame");
+        fjarsyn::log_throttled!(tracing::Level::DEBUG, "Another frame {}", frame);
+    }
+}
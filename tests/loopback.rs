@@ -0,0 +1,152 @@
+//! Calls to ourselves, over connections that only reach each other.
+
+use std::{sync::Arc, time::Duration};
+
+use fjarsyn::{
+    config::Config,
+    media::{
+        encoder_worker::EncoderConfig,
+        ffmpeg::{DecodeAccel, FFmpegDecoder},
+        nal::{self, NalCodec},
+    },
+    networking::{
+        signaling::{self, LOOPBACK_IDS},
+        webrtc::{SourceReplacement, TrackId, WebRTCEvent},
+    },
+    session::{CallOptions, CallSession, CaptureSession, SessionHandle},
+    utils::{
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+use fjarsyn_shared::{ControlMessage, PROTOCOL_VERSION, SignalingMessage, SignalingType};
+use tokio::sync::{Notify, mpsc};
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+// How long nothing has to arrive for, for what was sent to be all in.
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+
+fn message(to: &str) -> SignalingMessage {
+    SignalingMessage {
+        to: to.to_owned(),
+        from: String::new(),
+        sig_type: SignalingType::Bye,
+        data: String::new(),
+        protocol_version: PROTOCOL_VERSION,
+        sealed: false,
+        mode: None,
+    }
+}
+
+#[tokio::test]
+async fn loopback_signaling_passes_messages_to_the_other_end() {
+    let (caller_tx, mut caller_rx) = mpsc::channel(10);
+    let (callee_tx, mut callee_rx) = mpsc::channel(10);
+    let close = [Arc::new(Notify::new()), Arc::new(Notify::new())];
+    let [caller, callee] = signaling::loopback([caller_tx, callee_tx], close.clone());
+    assert_eq!(caller.identity.uuid, LOOPBACK_IDS[0]);
+    assert_eq!(callee.identity.uuid, LOOPBACK_IDS[1]);
+
+    // Only the other end can be reached.
+    caller.messages.send(message("someone-else")).await.unwrap();
+    caller.messages.send(message(LOOPBACK_IDS[1])).await.unwrap();
+    let received = tokio::time::timeout(RECV_TIMEOUT, callee_rx.recv()).await.unwrap().unwrap();
+    assert_eq!(received.from, LOOPBACK_IDS[0]);
+
+    callee.messages.send(message(LOOPBACK_IDS[0])).await.unwrap();
+    let received = tokio::time::timeout(RECV_TIMEOUT, caller_rx.recv()).await.unwrap().unwrap();
+    assert_eq!(received.from, LOOPBACK_IDS[1]);
+
+    // Closing one end leaves nothing for the other to receive either.
+    close[0].notify_one();
+    assert!(tokio::time::timeout(RECV_TIMEOUT, callee_rx.recv()).await.unwrap().is_none());
+    assert!(tokio::time::timeout(RECV_TIMEOUT, caller_rx.recv()).await.unwrap().is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn loopback_call_connects_and_returns_what_is_sent() {
+    let handle = CallSession::loopback(CallOptions::default()).await.unwrap();
+    assert_eq!(handle.webrtc().get_remote_id().as_deref(), Some(LOOPBACK_IDS[1]));
+    assert!(tokio::time::timeout(RECV_TIMEOUT, handle.wait_connected()).await.unwrap());
+
+    // The control channel only opens once connected.
+    tokio::time::timeout(RECV_TIMEOUT, async {
+        while handle.webrtc().control_channel().ready_state() != RTCDataChannelState::Open {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the control channel didn't open");
+
+    // The control messages reach the other end, and come back from it.
+    handle.webrtc().send_control(&ControlMessage::KeyframeRequest).await.unwrap();
+    let event = tokio::time::timeout(RECV_TIMEOUT, handle.next_event()).await.unwrap();
+    assert!(
+        matches!(event, Some(WebRTCEvent::Control(ControlMessage::KeyframeRequest))),
+        "unexpected event: {:?}",
+        event
+    );
+
+    let channels = handle.channels().clone();
+    handle.close().await.unwrap();
+    // Once the calling end is shut down, so is the other, and nothing is received anymore.
+    tokio::time::timeout(RECV_TIMEOUT, async {
+        while channels.events().lock().await.recv().await.is_some() {}
+    })
+    .await
+    .expect("the events were still open after shutdown");
+    assert!(channels.packets(TrackId::default()).lock().await.recv().await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restarted_share_decodes_on_the_same_connection() {
+    let handle = CallSession::loopback(CallOptions::default()).await.unwrap();
+    assert!(tokio::time::timeout(RECV_TIMEOUT, handle.wait_connected()).await.unwrap());
+    let peer_connection = handle.webrtc().peer_connection();
+    let config = EncoderConfig::from_config(&Config::default());
+    let codec = NalCodec::from_mime_type(config.transcoding_type.mime_type()).unwrap();
+    let packets = handle.channels().packets(TrackId::default());
+    let share = || -> SessionHandle {
+        let frames = SyntheticFrames::new(
+            Vector2::new(320, 240),
+            config.input_format,
+            FramePattern::Gradient,
+        );
+        CaptureSession::start(frames.into_stream(30.0), config, handle.webrtc().clone()).unwrap()
+    };
+
+    let first = share();
+    let received = tokio::time::timeout(RECV_TIMEOUT, packets.lock().await.recv()).await.unwrap();
+    assert!(received.is_some(), "nothing of the first share arrived");
+    first.stop().await.unwrap();
+    // Whatever is left of the first share, so only the second one's is read below.
+    while let Ok(Some(_)) = tokio::time::timeout(QUIET_PERIOD, packets.lock().await.recv()).await {}
+
+    assert_eq!(
+        handle.webrtc().replace_video_source(config.transcoding_type),
+        SourceReplacement::Reset
+    );
+    let second = share();
+
+    // A decoder that never saw the first share decodes the second from its first packet on.
+    let mut decoder = FFmpegDecoder::new(config.transcoding_type, DecodeAccel::Software).unwrap();
+    let first_packet = tokio::time::timeout(RECV_TIMEOUT, packets.lock().await.recv())
+        .await
+        .unwrap()
+        .expect("nothing of the second share arrived");
+    assert!(
+        nal::is_keyframe(&first_packet, codec),
+        "the second share didn't start with a keyframe"
+    );
+    let mut decoded = decoder.decode(&first_packet).unwrap();
+    while decoded.is_none() {
+        let packet =
+            tokio::time::timeout(RECV_TIMEOUT, packets.lock().await.recv()).await.unwrap().unwrap();
+        decoded = decoder.decode(&packet).unwrap();
+    }
+    assert!(Arc::ptr_eq(&peer_connection, &handle.webrtc().peer_connection()));
+
+    second.stop().await.unwrap();
+    handle.close().await.unwrap();
+}
@@ -0,0 +1,453 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use fjarsyn::{
+    capture_providers::shared::{CaptureMetrics, CaptureStatsWindow, FrameIntervals},
+    media::stats::{EncoderMetrics, EncoderStats},
+    utils::metrics::{Counter, Gauge, Histogram, HistogramSnapshot, Metrics},
+};
+
+const THREADS: u64 = 8;
+const PER_THREAD: u64 = 10_000;
+
+#[test]
+fn counters_add_up_across_threads() {
+    let counter = Counter::default();
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..PER_THREAD {
+                    counter.increment();
+                }
+            });
+        }
+    });
+    assert_eq!(counter.get(), THREADS * PER_THREAD);
+    counter.add(5);
+    assert_eq!(counter.get(), THREADS * PER_THREAD + 5);
+}
+
+#[test]
+fn the_highest_gauge_wins_across_threads() {
+    let gauge = Gauge::default();
+    assert_eq!(gauge.get(), 0.0);
+    thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let gauge = &gauge;
+            scope.spawn(move || {
+                for value in 0..PER_THREAD {
+                    gauge.set_max((value * THREADS + thread) as f64);
+                }
+            });
+        }
+    });
+    assert_eq!(gauge.get(), (PER_THREAD * THREADS - 1) as f64);
+
+    // Unlike setting it, which takes it down too.
+    gauge.set_max(1.0);
+    assert_eq!(gauge.get(), (PER_THREAD * THREADS - 1) as f64);
+    gauge.set(-2.5);
+    assert_eq!(gauge.get(), -2.5);
+}
+
+#[test]
+fn histograms_count_every_value_across_threads() {
+    let histogram = Histogram::new(&[10, 100, 1_000]);
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for value in 0..PER_THREAD {
+                    histogram.record(value % 2_000);
+                }
+            });
+        }
+    });
+    let snapshot = histogram.snapshot();
+    let total = THREADS * PER_THREAD;
+    assert_eq!(snapshot.count, total);
+    assert_eq!(snapshot.buckets.iter().sum::<u64>(), total);
+    assert_eq!(snapshot.sum, THREADS * (0..PER_THREAD).map(|value| value % 2_000).sum::<u64>());
+    // 0 to 10, 11 to 100, 101 to 1000 and the rest, out of every 2000.
+    assert_eq!(snapshot.buckets, [11, 90, 900, 999].map(|per| per * total / 2_000));
+}
+
+#[test]
+fn values_go_into_the_bucket_of_their_upper_bound() {
+    // In any order.
+    let histogram = Histogram::new(&[100, 10, 100]);
+    assert_eq!(histogram.bounds(), [10, 100]);
+    for value in [0, 10, 11, 100, 101] {
+        histogram.record(value);
+    }
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.buckets, [2, 2, 1]);
+    assert_eq!(snapshot.quantile(0.0), Some(10));
+    assert_eq!(snapshot.quantile(0.4), Some(10));
+    assert_eq!(snapshot.quantile(0.5), Some(100));
+    assert_eq!(snapshot.quantile(1.0), Some(u64::MAX));
+    assert_eq!(snapshot.max(), Some(u64::MAX));
+
+    histogram.reset();
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count, 0);
+    assert_eq!(snapshot.quantile(0.5), None);
+    assert_eq!(snapshot.max(), None);
+    assert_eq!(snapshot.mean(), None);
+}
+
+#[test]
+fn mean_and_standard_deviation() {
+    let histogram = Histogram::new(&[10]);
+    for value in [2, 4, 4, 4, 5, 5, 7, 9] {
+        histogram.record(value);
+    }
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.mean(), Some(5.0));
+    assert_eq!(snapshot.std_dev(), Some(2.0));
+    assert_eq!(snapshot.max(), Some(10));
+
+    // No deviation at all when they are all the same.
+    let histogram = Histogram::new(&[10]);
+    for _ in 0..3 {
+        histogram.record(7);
+    }
+    assert_eq!(histogram.snapshot().std_dev(), Some(0.0));
+}
+
+#[test]
+fn since_leaves_out_what_was_there_before() {
+    let histogram = Histogram::new(&[10, 100]);
+    histogram.record(5);
+    histogram.record(50);
+    let earlier = histogram.snapshot();
+    histogram.record(500);
+    histogram.record(6);
+
+    let since = histogram.snapshot().since(&earlier);
+    assert_eq!(since.buckets, [1, 0, 1]);
+    assert_eq!(since.count, 2);
+    assert_eq!(since.sum, 506);
+    assert_eq!(since.sum_of_squares, 500 * 500 + 6 * 6);
+    assert_eq!(histogram.snapshot().since(&HistogramSnapshot::default()), histogram.snapshot());
+
+    // Everything there is, once it was reset in between.
+    histogram.reset();
+    histogram.record(7);
+    let since = histogram.snapshot().since(&earlier);
+    assert_eq!(since.buckets, [1, 0, 0]);
+    assert_eq!(since.count, 1);
+}
+
+#[test]
+fn metrics_are_shared_by_name() {
+    let metrics = Metrics::default();
+    let clone = metrics.clone();
+    metrics.counter("test.counter").add(3);
+    clone.counter("test.counter").add(4);
+    clone.gauge("test.gauge").set(1.5);
+    // The bounds of the histogram registered first are kept.
+    metrics.histogram("test.histogram", &[10]).record(5);
+    clone.histogram("test.histogram", &[20, 30]).record(25);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.counter("test.counter"), Some(7));
+    assert_eq!(snapshot.gauge("test.gauge"), Some(1.5));
+    let histogram = snapshot.histogram("test.histogram").unwrap();
+    assert_eq!(histogram.bounds, [10]);
+    assert_eq!(histogram.buckets, [1, 1]);
+    assert_eq!(snapshot.counter("test.missing"), None);
+    assert_eq!(snapshot.gauge("test.counter"), None);
+}
+
+#[test]
+fn removed_metrics_start_over() {
+    let metrics = Metrics::default();
+    let old = metrics.counter("worker.frames");
+    old.add(10);
+    metrics.gauge("worker.fps").set(30.0);
+    metrics.counter("other.frames").add(1);
+
+    metrics.remove("worker.");
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.counter("worker.frames"), None);
+    assert_eq!(snapshot.gauge("worker.fps"), None);
+    assert_eq!(snapshot.counter("other.frames"), Some(1));
+
+    // The old one can still be updated, but isn't seen anymore.
+    let new = metrics.counter("worker.frames");
+    old.increment();
+    new.increment();
+    assert_eq!(metrics.snapshot().counter("worker.frames"), Some(1));
+}
+
+#[test]
+fn snapshots_dont_hold_up_the_threads_updating() {
+    let metrics = Metrics::default();
+    let counter = metrics.counter("test.counter");
+    let histogram = metrics.histogram("test.histogram", &[10, 100]);
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let updaters: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let (counter, histogram) = (&counter, &histogram);
+                scope.spawn(move || {
+                    for value in 0..PER_THREAD {
+                        counter.increment();
+                        histogram.record(value % 200);
+                    }
+                })
+            })
+            .collect();
+        // Every snapshot sees at least what the one before did.
+        scope.spawn(|| {
+            let mut last = 0;
+            while !done.load(Ordering::Relaxed) {
+                let count = metrics.snapshot().counter("test.counter").unwrap();
+                assert!(count >= last, "{} < {}", count, last);
+                last = count;
+            }
+        });
+        for updater in updaters {
+            updater.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.counter("test.counter"), Some(THREADS * PER_THREAD));
+    assert_eq!(snapshot.histogram("test.histogram").unwrap().count, THREADS * PER_THREAD);
+}
+
+#[test]
+fn frame_intervals_leave_out_idle_gaps() {
+    let metrics = Metrics::default();
+    let capture = CaptureMetrics::register(&metrics, Duration::from_millis(10));
+    let start = Instant::now();
+    for at in [0, 10, 20, 30, 200, 210] {
+        capture.intervals.record(start + Duration::from_millis(at));
+    }
+    let mut window = CaptureStatsWindow::default();
+    let stats = window.stats(&metrics.snapshot());
+    // The 170ms gap is the screen standing still.
+    assert_eq!(stats.mean_interval, Duration::from_millis(10));
+    assert_eq!(stats.jitter, Duration::ZERO);
+    assert_eq!(stats.max_interval, Duration::from_millis(10));
+    assert!((stats.arrival_fps - 100.0).abs() < 0.01, "{}", stats.arrival_fps);
+
+    // A new stream starts over, without an interval to the last frame of the one before.
+    capture.intervals.reset(Duration::from_millis(20));
+    capture.intervals.record(start + Duration::from_millis(220));
+    capture.intervals.record(start + Duration::from_millis(240));
+    let stats = window.stats(&metrics.snapshot());
+    assert_eq!(stats.mean_interval, Duration::from_millis(20));
+}
+
+// Intervals recorded straight into a histogram of their own, as the capture callback records them.
+fn frame_intervals(target_interval: Duration) -> (FrameIntervals, Arc<Histogram>) {
+    let histogram = Arc::new(Histogram::new(&FrameIntervals::bounds()));
+    (FrameIntervals::new(histogram.clone(), target_interval), histogram)
+}
+
+// Records arrivals at these milliseconds after the returned instant.
+fn arrivals(intervals: &FrameIntervals, millis: &[u64]) -> Instant {
+    let start = Instant::now();
+    for &at in millis {
+        intervals.record(start + Duration::from_millis(at));
+    }
+    start
+}
+
+#[test]
+fn frame_intervals_give_the_rate() {
+    let (intervals, histogram) = frame_intervals(Duration::from_millis(20));
+    let at: Vec<_> = (0..=50).map(|frame| frame * 20).collect();
+    arrivals(&intervals, &at);
+    let snapshot = histogram.snapshot();
+    // The first arrival has nothing to be an interval from.
+    assert_eq!(snapshot.count, 50);
+    assert_eq!(snapshot.mean(), Some(20_000.0));
+    assert_eq!(snapshot.std_dev(), Some(0.0));
+    assert_eq!(snapshot.max(), Some(20_000));
+}
+
+#[test]
+fn frame_intervals_give_the_jitter() {
+    let (intervals, histogram) = frame_intervals(Duration::from_millis(20));
+    // Alternately early and late, averaging out to the target.
+    let at: Vec<_> = (0..=40).map(|frame| frame * 20 + frame % 2 * 10).collect();
+    arrivals(&intervals, &at);
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count, 40);
+    assert_eq!(snapshot.mean(), Some(20_000.0));
+    assert_eq!(snapshot.std_dev(), Some(10_000.0));
+    assert_eq!(snapshot.max(), Some(30_000));
+}
+
+#[test]
+fn frame_intervals_give_the_worst_gap() {
+    let (intervals, histogram) = frame_intervals(Duration::from_millis(20));
+    // A late frame, into the bucket of the 46 ms bound.
+    arrivals(&intervals, &[0, 20, 40, 85, 105, 125]);
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count, 5);
+    assert_eq!(snapshot.max(), Some(46_000));
+    assert_eq!(snapshot.quantile(0.8), Some(20_000));
+
+    // Up to four intervals is judder. Past that, the screen stood still.
+    let (intervals, histogram) = frame_intervals(Duration::from_millis(20));
+    arrivals(&intervals, &[0, 80, 161, 181]);
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count, 2);
+    assert_eq!(snapshot.max(), Some(80_000));
+}
+
+#[test]
+fn frame_intervals_leave_out_arrivals_out_of_order() {
+    let (intervals, histogram) = frame_intervals(Duration::from_millis(20));
+    let start = arrivals(&intervals, &[0, 20, 40]);
+    // Came in on another thread after a later one.
+    intervals.record(start + Duration::from_millis(30));
+    assert_eq!(histogram.snapshot().count, 2);
+    // And the next one goes from the one that came in last.
+    intervals.record(start + Duration::from_millis(50));
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count, 3);
+    assert_eq!(snapshot.max(), Some(20_000));
+}
+
+#[test]
+fn frame_intervals_start_over_with_a_new_target() {
+    let (intervals, histogram) = frame_intervals(Duration::from_millis(10));
+    let start = arrivals(&intervals, &[0, 10, 20]);
+    intervals.reset(Duration::from_millis(100));
+    assert_eq!(histogram.snapshot().count, 0);
+
+    // Nothing from the last arrival before, and the longer gaps of the new target count.
+    for at in [300, 400, 700] {
+        intervals.record(start + Duration::from_millis(at));
+    }
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count, 2);
+    assert_eq!(snapshot.mean(), Some(200_000.0));
+}
+
+#[test]
+fn frame_intervals_are_recorded_from_several_threads() {
+    let metrics = Metrics::default();
+    let capture = CaptureMetrics::register(&metrics, Duration::from_secs(1));
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..1_000 {
+                    capture.intervals.record(Instant::now());
+                }
+            });
+        }
+    });
+    let snapshot = metrics.snapshot();
+    let histogram = snapshot.histogram("capture.frame_interval_us").unwrap();
+    // Every arrival but the first makes an interval, unless it came in before the one it follows.
+    assert!((1..THREADS * 1_000).contains(&histogram.count), "{}", histogram.count);
+    assert_eq!(histogram.buckets.iter().sum::<u64>(), histogram.count);
+}
+
+#[test]
+fn the_window_covers_the_last_intervals() {
+    let metrics = Metrics::default();
+    let capture = CaptureMetrics::register(&metrics, Duration::from_millis(50));
+    let mut window = CaptureStatsWindow::default();
+    let start = Instant::now();
+    let mut at = Duration::ZERO;
+    let mut record = |interval: Duration, frames: usize| {
+        for _ in 0..frames {
+            at += interval;
+            capture.intervals.record(start + at);
+        }
+    };
+
+    // A hiccup early on.
+    record(Duration::from_millis(10), 10);
+    record(Duration::from_millis(100), 1);
+    record(Duration::from_millis(10), 200);
+    assert_eq!(window.stats(&metrics.snapshot()).max_interval, Duration::from_millis(100));
+    // It is still there with fewer than a window's worth of frames since.
+    record(Duration::from_millis(10), 100);
+    assert_eq!(window.stats(&metrics.snapshot()).max_interval, Duration::from_millis(100));
+    // And drops out after.
+    record(Duration::from_millis(10), 100);
+    let stats = window.stats(&metrics.snapshot());
+    assert_eq!(stats.max_interval, Duration::from_millis(10));
+    assert_eq!(stats.jitter, Duration::ZERO);
+}
+
+#[test]
+fn the_window_turns_the_intervals_into_stats() {
+    let metrics = Metrics::default();
+    let capture = CaptureMetrics::register(&metrics, Duration::from_millis(20));
+    let at: Vec<_> = (0..=40).map(|frame| frame * 20 + frame % 2 * 10).collect();
+    arrivals(&capture.intervals, &at);
+
+    let stats = CaptureStatsWindow::default().stats(&metrics.snapshot());
+    assert!((stats.arrival_fps - 50.0).abs() < 0.01, "{}", stats.arrival_fps);
+    assert_eq!(stats.mean_interval, Duration::from_millis(20));
+    assert_eq!(stats.jitter, Duration::from_millis(10));
+    assert_eq!(stats.max_interval, Duration::from_millis(30));
+}
+
+#[test]
+fn the_window_shows_the_readbacks() {
+    let metrics = Metrics::default();
+    let capture = CaptureMetrics::register(&metrics, Duration::from_millis(10));
+    let mut window = CaptureStatsWindow::default();
+    assert_eq!(window.stats(&metrics.snapshot()).readback_depth, 0);
+
+    capture.skipped_readbacks.add(3);
+    capture.readback_depth.set_max(2.0);
+    capture.readback_depth.set_max(4.0);
+    let stats = window.stats(&metrics.snapshot());
+    assert_eq!(stats.skipped_readbacks, 3);
+    assert_eq!(stats.readback_depth, 4);
+    // No intervals yet.
+    assert_eq!(stats.mean_interval, Duration::ZERO);
+}
+
+#[test]
+fn encoder_stats_come_from_the_current_worker() {
+    let metrics = Metrics::default();
+    assert_eq!(EncoderStats::from_snapshot(&metrics.snapshot()), None);
+
+    let encoder = EncoderMetrics::register(&metrics);
+    encoder.target_fps.set(60.0);
+    encoder.output_fps.set(30.0);
+    encoder.last_encode_time.set(0.004);
+    encoder.queue_len.set(2.0);
+    encoder.sent_bitrate_1s.set(1_000_000.0);
+    encoder.restarts.increment();
+    encoder.encoded.add(100);
+    encoder.unchanged_skips.add(7);
+    encoder.capped_drops.add(2);
+    let stats = EncoderStats::from_snapshot(&metrics.snapshot()).unwrap();
+    assert_eq!(stats.target_fps, 60.0);
+    assert_eq!(stats.output_fps, 30.0);
+    assert_eq!(stats.last_encode_time, Duration::from_millis(4));
+    assert_eq!(stats.queue_len, 2);
+    assert_eq!(stats.sent_bitrate_1s, 1_000_000.0);
+    assert_eq!(stats.restarts, 1);
+    assert_eq!(stats.encoded, 100);
+    assert_eq!(stats.unchanged_skips, 7);
+    assert_eq!(stats.capped_drops, 2);
+
+    // The next worker starts from zero.
+    let next = EncoderMetrics::register(&metrics);
+    next.target_fps.set(30.0);
+    encoder.encoded.increment();
+    let stats = EncoderStats::from_snapshot(&metrics.snapshot()).unwrap();
+    assert_eq!(stats.encoded, 0);
+    assert_eq!(stats.target_fps, 30.0);
+}
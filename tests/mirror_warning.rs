@@ -0,0 +1,74 @@
+use fjarsyn::{
+    capture_providers::shared::MonitorGeometry,
+    ui::mirror_warning::{move_off, shows_on},
+    utils::rect::Rect,
+};
+use iced::Point;
+
+fn primary() -> Rect<i32> {
+    Rect::new(0, 0, 1920, 1080)
+}
+
+fn monitor(bounds: Rect<i32>, scale_factor: f32) -> MonitorGeometry {
+    MonitorGeometry { bounds, scale_factor }
+}
+
+#[test]
+fn windows_on_the_monitor_show_on_it() {
+    assert!(shows_on(primary(), Rect::new(100, 100, 1280, 720)));
+    // Straddling the edge, with most of it on the other monitor.
+    assert!(shows_on(primary(), Rect::new(1800, 100, 1280, 720)));
+    assert!(shows_on(primary(), Rect::new(-1200, -300, 1280, 720)));
+    // Covering it entirely, e.g. fullscreen.
+    assert!(shows_on(primary(), Rect::new(-8, -8, 1936, 1096)));
+}
+
+#[test]
+fn windows_off_the_monitor_or_barely_on_it_dont() {
+    assert!(!shows_on(primary(), Rect::new(1920, 0, 1280, 720)));
+    assert!(!shows_on(primary(), Rect::new(-1280, 200, 1280, 720)));
+    // Only a shadow's width over the edge.
+    assert!(!shows_on(primary(), Rect::new(1912, 0, 1280, 720)));
+    assert!(!shows_on(primary(), Rect::new(200, 1075, 1280, 720)));
+    assert!(!shows_on(primary(), Rect::new(100, 100, 0, 0)));
+}
+
+#[test]
+fn moves_to_the_center_of_the_largest_other_monitor() {
+    let monitors = [
+        monitor(primary(), 1.0),
+        monitor(Rect::new(1920, 0, 1280, 1024), 1.0),
+        monitor(Rect::new(-2560, 0, 2560, 1440), 1.0),
+    ];
+    let window = Rect::new(100, 100, 1280, 720);
+    assert_eq!(move_off(&monitors, primary(), window), Some(Point::new(-1920.0, 360.0)));
+    let position = move_off(&monitors, monitors[2].bounds, window).unwrap();
+    assert_eq!(position, Point::new(320.0, 180.0));
+}
+
+#[test]
+fn moved_windows_land_off_the_shared_monitor() {
+    let monitors = [monitor(primary(), 1.0), monitor(Rect::new(1920, -200, 1280, 1024), 1.0)];
+    for window in
+        [Rect::new(0, 0, 800, 600), Rect::new(0, 0, 1920, 1080), Rect::new(0, 0, 3000, 2000)]
+    {
+        let position = move_off(&monitors, primary(), window).unwrap();
+        let moved = Rect::new(position.x as i32, position.y as i32, window.size.x, window.size.y);
+        assert!(!shows_on(primary(), moved), "{:?}", moved);
+        // Too large ones are put in the corner, so their title bar can still be reached.
+        assert!(moved.position.x >= 1920 && moved.position.y >= -200, "{:?}", moved);
+    }
+}
+
+#[test]
+fn positions_are_in_the_logical_pixels_of_the_target() {
+    let monitors = [monitor(primary(), 1.0), monitor(Rect::new(1920, 0, 3840, 2160), 1.5)];
+    let position = move_off(&monitors, primary(), Rect::new(0, 0, 1840, 1080)).unwrap();
+    assert_eq!(position, Point::new((1920.0 + 1000.0) / 1.5, 540.0 / 1.5));
+}
+
+#[test]
+fn nowhere_to_move_with_a_single_monitor() {
+    assert_eq!(move_off(&[monitor(primary(), 1.0)], primary(), Rect::new(0, 0, 800, 600)), None);
+    assert_eq!(move_off(&[], primary(), Rect::new(0, 0, 800, 600)), None);
+}
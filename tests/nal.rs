@@ -0,0 +1,176 @@
+use fjarsyn::{
+    media::{
+        ffmpeg::{FFmpegEncoder, FFmpegTranscodeType, RateControl},
+        nal::{self, NalCodec, NalKind},
+    },
+    utils::{
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+
+// SPS, PPS and an IDR slice, the way the encoders start a keyframe.
+const H264_KEYFRAME: &[u8] = &[
+    0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1F, //
+    0, 0, 0, 1, 0x68, 0xCE, 0x3C, 0x80, //
+    0, 0, 1, 0x65, 0x88, 0x84, 0x00,
+];
+const H264_DELTA: &[u8] = &[0, 0, 0, 1, 0x41, 0x9A, 0x02, 0x03];
+// VPS, SPS, PPS and an IDR_W_RADL slice.
+const HEVC_KEYFRAME: &[u8] = &[
+    0, 0, 0, 1, 0x40, 0x01, 0x0C, //
+    0, 0, 0, 1, 0x42, 0x01, 0x01, //
+    0, 0, 0, 1, 0x44, 0x01, 0xC1, //
+    0, 0, 0, 1, 0x26, 0x01, 0xAF,
+];
+const HEVC_DELTA: &[u8] = &[0, 0, 0, 1, 0x02, 0x01, 0xD0];
+
+#[test]
+fn splits_on_three_and_four_byte_start_codes() {
+    let units: Vec<&[u8]> = nal::units(H264_KEYFRAME).collect();
+    assert_eq!(
+        units,
+        [&[0x67, 0x42, 0x00, 0x1F][..], &[0x68, 0xCE, 0x3C, 0x80], &[0x65, 0x88, 0x84]]
+    );
+}
+
+#[test]
+fn reads_unit_types() {
+    assert_eq!(NalCodec::H264.unit_type(&[0x65]), Some(5));
+    assert_eq!(NalCodec::H264.unit_type(&[0x41]), Some(1));
+    assert_eq!(NalCodec::Hevc.unit_type(&[0x26, 0x01]), Some(19));
+    assert_eq!(NalCodec::Hevc.unit_type(&[0x40, 0x01]), Some(32));
+    assert_eq!(NalCodec::H264.unit_type(&[]), None);
+}
+
+#[test]
+fn tells_keyframes_apart() {
+    assert!(nal::is_keyframe(H264_KEYFRAME, NalCodec::H264));
+    assert!(!nal::is_keyframe(H264_DELTA, NalCodec::H264));
+    assert!(nal::is_keyframe(HEVC_KEYFRAME, NalCodec::Hevc));
+    assert!(!nal::is_keyframe(HEVC_DELTA, NalCodec::Hevc));
+}
+
+#[test]
+fn parameter_sets_alone_are_not_keyframes() {
+    let parameter_sets = &H264_KEYFRAME[..16];
+    assert!(!nal::is_keyframe(parameter_sets, NalCodec::H264));
+}
+
+#[test]
+fn ignores_streams_without_start_codes() {
+    // What a depacketizer hands over when it doesn't produce annex B.
+    assert_eq!(nal::units(&[0x65, 0x88, 0x84]).count(), 0);
+    assert!(!nal::is_keyframe(&[0x65, 0x88, 0x84], NalCodec::H264));
+    assert_eq!(nal::units(&[]).count(), 0);
+}
+
+#[test]
+fn codecs_come_from_mime_types() {
+    assert_eq!(NalCodec::from_mime_type("video/H264"), Some(NalCodec::H264));
+    assert_eq!(NalCodec::from_mime_type("video/H265"), Some(NalCodec::Hevc));
+    assert_eq!(NalCodec::from_mime_type("video/VP8"), None);
+}
+
+#[test]
+fn classifies_h264_units() {
+    let kinds: Vec<_> = nal::units(H264_KEYFRAME).map(|unit| NalCodec::H264.kind(unit)).collect();
+    assert_eq!(kinds, [Some(NalKind::Sps), Some(NalKind::Pps), Some(NalKind::Idr)]);
+    assert_eq!(NalCodec::H264.kind(&H264_DELTA[4..]), Some(NalKind::NonIdr));
+    assert_eq!(NalCodec::H264.kind(&[0x06, 0x05]), Some(NalKind::Sei));
+    assert_eq!(NalCodec::H264.kind(&[0x09, 0x10]), Some(NalKind::Other(9)));
+    assert_eq!(NalCodec::H264.kind(&[]), None);
+}
+
+#[test]
+fn classifies_hevc_units() {
+    let kinds: Vec<_> = nal::units(HEVC_KEYFRAME).map(|unit| NalCodec::Hevc.kind(unit)).collect();
+    assert_eq!(
+        kinds,
+        [Some(NalKind::Vps), Some(NalKind::Sps), Some(NalKind::Pps), Some(NalKind::Idr)]
+    );
+    assert_eq!(NalCodec::Hevc.kind(&HEVC_DELTA[4..]), Some(NalKind::NonIdr));
+    // CRA pictures are random access points too.
+    assert_eq!(NalCodec::Hevc.kind(&[0x2A, 0x01]), Some(NalKind::Idr));
+    assert_eq!(NalCodec::Hevc.kind(&[0x4E, 0x01]), Some(NalKind::Sei));
+}
+
+// Every unit has to be a non-empty part of the input, whatever the input.
+fn assert_units_within(stream: &[u8]) {
+    let range = stream.as_ptr_range();
+    for unit in nal::units(stream) {
+        assert!(!unit.is_empty());
+        let unit_range = unit.as_ptr_range();
+        assert!(range.start <= unit_range.start && unit_range.end <= range.end);
+    }
+}
+
+#[test]
+fn truncated_streams_are_parsed_up_to_where_they_end() {
+    for fixture in [H264_KEYFRAME, H264_DELTA, HEVC_KEYFRAME, HEVC_DELTA] {
+        for end in 0..=fixture.len() {
+            let truncated = &fixture[..end];
+            assert_units_within(truncated);
+            let _ = nal::is_keyframe(truncated, NalCodec::H264);
+            let _ = nal::is_keyframe(truncated, NalCodec::Hevc);
+        }
+    }
+    // A keyframe cut off before its slice isn't one anymore.
+    assert!(!nal::is_keyframe(&H264_KEYFRAME[..19], NalCodec::H264));
+    assert!(nal::is_keyframe(&H264_KEYFRAME[..20], NalCodec::H264));
+}
+
+#[test]
+fn random_bytes_never_panic() {
+    // Deterministic xorshift, biased towards zeros so start codes turn up often.
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..2000 {
+        let len = (next() % 64) as usize;
+        let stream: Vec<u8> = (0..len)
+            .map(|_| match next() % 4 {
+                0 | 1 => 0,
+                2 => 1,
+                _ => next() as u8,
+            })
+            .collect();
+        assert_units_within(&stream);
+        let _ = nal::is_keyframe(&stream, NalCodec::H264);
+        let _ = nal::is_keyframe(&stream, NalCodec::Hevc);
+    }
+}
+
+// Fixtures straight from the software encoder, which is what most calls send.
+#[test]
+fn software_encoder_output_is_recognized() {
+    let size = Vector2 { x: 320, y: 240 };
+    let transcoding_type = FFmpegTranscodeType::H264Software;
+    let codec = NalCodec::from_mime_type(transcoding_type.mime_type()).unwrap();
+    let mut encoder =
+        FFmpegEncoder::new(1_000_000, 30.0, PixelFormat::RGBA8, None, 120, RateControl::Variable)
+            .expect("Failed to create encoder");
+    let mut frames = SyntheticFrames::new(size, PixelFormat::RGBA8, FramePattern::Gradient);
+
+    let packets: Vec<Vec<u8>> = (0..10)
+        .flat_map(|_| {
+            encoder.encode(&frames.next_bitmap(), transcoding_type, size.x, size.y).unwrap()
+        })
+        .collect();
+
+    let first = packets.first().expect("The encoder produced nothing");
+    assert!(nal::is_keyframe(first, codec));
+    let kinds: Vec<_> = nal::units(first).filter_map(|unit| codec.kind(unit)).collect();
+    assert!(kinds.contains(&NalKind::Sps) && kinds.contains(&NalKind::Pps));
+
+    // Well within the keyframe interval, so everything after the first refers back to it.
+    for packet in &packets[1..] {
+        assert!(!nal::is_keyframe(packet, codec));
+        assert!(nal::units(packet).any(|unit| codec.kind(unit) == Some(NalKind::NonIdr)));
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::ui::{
+    message::Message, notification::NotificationKind, notification_provider::NotificationProvider,
+};
+
+fn messages(provider: &NotificationProvider) -> Vec<&str> {
+    provider.iter().map(|n| n.message.as_str()).collect()
+}
+
+#[test]
+fn newest_is_last() {
+    let mut provider = NotificationProvider::new();
+    for message in ["first", "second", "third"] {
+        provider.info(message);
+    }
+    provider.error("fourth");
+    assert_eq!(messages(&provider), ["first", "second", "third", "fourth"]);
+}
+
+#[test]
+fn dismissing_keeps_the_order() {
+    let mut provider = NotificationProvider::new();
+    provider.info("first");
+    let second = provider.info_with_action("second", "Open", Message::NoOp);
+    provider.success("third");
+    provider.dismiss(second);
+    assert_eq!(messages(&provider), ["first", "third"]);
+}
+
+#[test]
+fn expired_ones_go_and_the_rest_keep_the_order() {
+    let mut provider = NotificationProvider::new();
+    provider.error("first");
+    provider.success("second");
+    provider.error("third");
+    // Past the success duration, but within the error one.
+    provider.dismiss_expired(Instant::now() + Duration::from_secs(8));
+    assert_eq!(messages(&provider), ["first", "third"]);
+}
+
+#[test]
+fn notify_once_replaces_the_one_with_the_same_key() {
+    let mut provider = NotificationProvider::new();
+    let first = provider.notify_once("fps", NotificationKind::Info, "slow");
+    provider.info("other");
+    let second = provider.notify_once("fps", NotificationKind::Info, "back");
+    assert_ne!(first, second);
+    assert_eq!(messages(&provider), ["other", "back"]);
+}
+
+#[test]
+fn notify_once_stacks_different_keys() {
+    let mut provider = NotificationProvider::new();
+    provider.notify_once("fps", NotificationKind::Info, "slow");
+    provider.notify_once("quality", NotificationKind::Info, "reduced");
+    provider.info("plain");
+    provider.info("plain");
+    assert_eq!(messages(&provider), ["slow", "reduced", "plain", "plain"]);
+}
@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use fjarsyn::{
+    media::{
+        decoder_worker::DecoderWorker,
+        ffmpeg::{DecodeAccel, FFmpegDecoder, FFmpegEncoder, FFmpegTranscodeType, RateControl},
+    },
+    networking::webrtc::{PacketRouter, TrackId},
+    utils::{
+        pixel_format::PixelFormat,
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+use tokio::sync::mpsc;
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+const FRAMES: usize = 10;
+
+fn packet(track: u32, index: u8) -> (TrackId, Bytes) {
+    (TrackId(track), Bytes::from(vec![track as u8, index]))
+}
+
+// What the software encoder makes of a few frames of the size.
+fn encoded(size: Vector2<i32>) -> Vec<Vec<u8>> {
+    let mut encoder =
+        FFmpegEncoder::new(1_000_000, 30.0, PixelFormat::RGBA8, None, 30, RateControl::Variable)
+            .unwrap();
+    let mut frames = SyntheticFrames::new(size, PixelFormat::RGBA8, FramePattern::Gradient);
+    let mut packets = Vec::new();
+    for _ in 0..FRAMES {
+        packets.extend(
+            encoder
+                .encode(&frames.next_bitmap(), FFmpegTranscodeType::H264Software, size.x, size.y)
+                .unwrap(),
+        );
+    }
+    packets.extend(encoder.flush().unwrap());
+    packets
+}
+
+#[tokio::test]
+async fn packets_go_to_their_track() {
+    let (packet_tx, packets) = mpsc::channel(10);
+    let router = PacketRouter::spawn(packets);
+    for packet in [packet(0, 0), packet(1, 0), packet(0, 1), packet(1, 1)] {
+        packet_tx.send(packet).await.unwrap();
+    }
+
+    for track in [0, 1] {
+        let receiver = router.track(TrackId(track));
+        let mut receiver = receiver.lock().await;
+        for index in 0..2 {
+            let received = tokio::time::timeout(RECV_TIMEOUT, receiver.recv()).await.unwrap();
+            assert_eq!(received, Some(packet(track, index).1));
+        }
+    }
+}
+
+#[tokio::test]
+async fn tracks_close_with_the_packets() {
+    let (packet_tx, packets) = mpsc::channel(10);
+    let router = PacketRouter::spawn(packets);
+    packet_tx.send(packet(0, 0)).await.unwrap();
+    let first = router.track(TrackId(0));
+    drop(packet_tx);
+
+    // What arrived before is still delivered.
+    let mut first = first.lock().await;
+    assert_eq!(first.recv().await, Some(packet(0, 0).1));
+    assert_eq!(tokio::time::timeout(RECV_TIMEOUT, first.recv()).await.unwrap(), None);
+    // A track asked for afterwards has nothing coming either.
+    let later = router.track(TrackId(1));
+    assert_eq!(tokio::time::timeout(RECV_TIMEOUT, later.lock().await.recv()).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn removed_track_closes() {
+    let (packet_tx, packets) = mpsc::channel(10);
+    let router = PacketRouter::spawn(packets);
+    let ended = router.track(TrackId(0));
+    router.remove(TrackId(0));
+    assert_eq!(tokio::time::timeout(RECV_TIMEOUT, ended.lock().await.recv()).await.unwrap(), None);
+
+    // The other tracks carry on.
+    packet_tx.send(packet(1, 0)).await.unwrap();
+    let other = router.track(TrackId(1));
+    assert_eq!(other.lock().await.recv().await, Some(packet(1, 0).1));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn two_tracks_decode_on_their_own_decoders() {
+    // Sized apart, to tell which track a decoded frame came from.
+    let sizes = [Vector2::new(320, 240), Vector2::new(160, 120)];
+    let [first, second] = sizes.map(encoded);
+    let (packet_tx, packets) = mpsc::channel(first.len() + second.len());
+    let router = PacketRouter::spawn(packets);
+
+    // Interleaved, as a connection receives them.
+    for index in 0..first.len().max(second.len()) {
+        for (track, packets) in [&first, &second].into_iter().enumerate() {
+            if let Some(packet) = packets.get(index) {
+                packet_tx.send((TrackId(track as u32), Bytes::from(packet.clone()))).await.unwrap();
+            }
+        }
+    }
+
+    for (track, size) in sizes.into_iter().enumerate() {
+        let track = TrackId(track as u32);
+        let decoder = DecoderWorker::spawn(
+            |accel| FFmpegDecoder::new(FFmpegTranscodeType::H264Software, accel),
+            DecodeAccel::Software,
+            track,
+            router.track(track),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(decoder.track(), track);
+
+        let frames = decoder.frames();
+        let frame = tokio::time::timeout(RECV_TIMEOUT, frames.lock().await.recv())
+            .await
+            .unwrap()
+            .expect("nothing was decoded");
+        assert_eq!(frame.size, size, "{} decoded another track's frame", track);
+    }
+}
@@ -0,0 +1,51 @@
+use fjarsyn::utils::panic_guard::{self, PanicSlot};
+
+#[test]
+fn catch_panic_returns_the_message() {
+    assert_eq!(panic_guard::catch_panic("test", || 42), Ok(42));
+    assert_eq!(
+        panic_guard::catch_panic("test", || -> () { panic!("static message") }),
+        Err("static message".to_owned())
+    );
+    assert_eq!(
+        panic_guard::catch_panic("test", || -> () { panic!("formatted {}", 1) }),
+        Err("formatted 1".to_owned())
+    );
+}
+
+#[test]
+fn panic_message_of_other_payloads() {
+    let payload: Box<dyn std::any::Any + Send> = Box::new(7u32);
+    assert_eq!(panic_guard::panic_message(payload.as_ref()), "unknown panic");
+}
+
+#[tokio::test]
+async fn worker_panic_is_left_in_the_slot() {
+    let slot = PanicSlot::new();
+    let (frames_tx, mut frames_rx) = tokio::sync::mpsc::channel::<u32>(4);
+
+    // Stands in for a stream whose frames make the worker panic partway through.
+    let worker = async move {
+        for frame in 0.. {
+            if frame == 3 {
+                panic!("bad frame {}", frame);
+            }
+            frames_tx.send(frame).await.unwrap();
+        }
+    };
+    tokio::spawn(panic_guard::catch_panics("Test worker", slot.clone(), worker)).await.unwrap();
+
+    assert_eq!(slot.get(), Some("bad frame 3"));
+    let mut received = vec![];
+    while let Some(frame) = frames_rx.recv().await {
+        received.push(frame);
+    }
+    assert_eq!(received, [0, 1, 2]);
+}
+
+#[tokio::test]
+async fn finished_worker_leaves_the_slot_empty() {
+    let slot = PanicSlot::new();
+    tokio::spawn(panic_guard::catch_panics("Test worker", slot.clone(), async {})).await.unwrap();
+    assert_eq!(slot.get(), None);
+}
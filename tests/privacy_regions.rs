@@ -0,0 +1,117 @@
+use fjarsyn::{
+    capture_providers::shared::CaptureSourceId,
+    config::Config,
+    ui::privacy_regions::PrivacyRegions,
+    utils::{rect::Rect, vector2::Vector2},
+};
+use fjarsyn_shared::{CursorPosition, InputEvent, MouseButton};
+
+fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect<f32> {
+    Rect { position: Vector2::new(x, y), size: Vector2::new(width, height) }
+}
+
+fn move_to(regions: &mut PrivacyRegions, x: f32, y: f32) -> bool {
+    regions.input(InputEvent::MouseMove(CursorPosition { x, y }))
+}
+
+fn button(regions: &mut PrivacyRegions, button: MouseButton, pressed: bool) -> bool {
+    regions.input(InputEvent::MouseButton { button, pressed })
+}
+
+fn drag(regions: &mut PrivacyRegions, from: (f32, f32), to: (f32, f32)) -> bool {
+    move_to(regions, from.0, from.1);
+    button(regions, MouseButton::Left, true);
+    move_to(regions, to.0, to.1);
+    button(regions, MouseButton::Left, false)
+}
+
+fn editing() -> PrivacyRegions {
+    let mut regions = PrivacyRegions::default();
+    regions.set_editing(true);
+    regions
+}
+
+#[test]
+fn dragging_draws_a_region() {
+    let mut regions = editing();
+    move_to(&mut regions, 0.75, 0.5);
+    button(&mut regions, MouseButton::Left, true);
+    move_to(&mut regions, 0.25, 0.25);
+    // Shown while it is being drawn, from either corner.
+    assert_eq!(regions.draft(), Some(rect(0.25, 0.25, 0.5, 0.25)));
+    assert!(regions.regions().is_empty());
+
+    assert!(button(&mut regions, MouseButton::Left, false));
+    assert_eq!(regions.regions(), [rect(0.25, 0.25, 0.5, 0.25)]);
+    assert_eq!(regions.draft(), None);
+}
+
+#[test]
+fn drags_past_the_preview_stop_at_its_edge() {
+    let mut regions = editing();
+    assert!(drag(&mut regions, (0.5, 0.5), (1.5, -0.25)));
+    assert_eq!(regions.regions(), [rect(0.5, 0.0, 0.5, 0.5)]);
+}
+
+#[test]
+fn clicks_draw_nothing() {
+    let mut regions = editing();
+    assert!(!drag(&mut regions, (0.5, 0.5), (0.5, 0.5)));
+    assert!(!drag(&mut regions, (0.5, 0.5), (0.8, 0.501)));
+    assert!(regions.regions().is_empty());
+}
+
+#[test]
+fn right_click_removes_the_region_on_top() {
+    let mut regions = editing();
+    drag(&mut regions, (0.125, 0.125), (0.625, 0.625));
+    drag(&mut regions, (0.375, 0.375), (0.875, 0.875));
+    move_to(&mut regions, 0.5, 0.5);
+    assert!(button(&mut regions, MouseButton::Right, true));
+    assert_eq!(regions.regions(), [rect(0.125, 0.125, 0.5, 0.5)]);
+
+    // Outside of every region.
+    move_to(&mut regions, 0.95, 0.05);
+    assert!(!button(&mut regions, MouseButton::Right, true));
+    assert_eq!(regions.regions().len(), 1);
+}
+
+#[test]
+fn nothing_is_drawn_unless_editing() {
+    let mut regions = PrivacyRegions::default();
+    assert!(!drag(&mut regions, (0.125, 0.125), (0.625, 0.625)));
+    assert!(regions.regions().is_empty());
+
+    // Nor is a drag that was under way kept once editing is done.
+    let mut regions = editing();
+    move_to(&mut regions, 0.1, 0.1);
+    button(&mut regions, MouseButton::Left, true);
+    regions.set_editing(false);
+    assert_eq!(regions.draft(), None);
+}
+
+#[test]
+fn regions_are_kept_per_source() {
+    let mut config = Config::default();
+    let monitor = CaptureSourceId::monitor(r"\\.\DISPLAY1");
+    let window = CaptureSourceId::window("editor.exe", "notes.txt - Editor");
+    let region = rect(0.1, 0.2, 0.3, 0.4);
+    config.set_privacy_regions(&monitor, &[region]);
+    assert_eq!(
+        config.privacy_regions(&CaptureSourceId::monitor(r"\\.\display1")),
+        Some(&[region][..])
+    );
+    assert_eq!(config.privacy_regions(&window), None);
+
+    // Replaced, rather than added to.
+    config.set_privacy_regions(&monitor, &[region, region]);
+    assert_eq!(config.privacy_regions.len(), 1);
+    assert_eq!(config.privacy_regions(&monitor).unwrap().len(), 2);
+
+    // Survives being saved.
+    let saved: Config = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+    assert_eq!(saved.privacy_regions(&monitor), config.privacy_regions(&monitor));
+
+    config.set_privacy_regions(&monitor, &[]);
+    assert!(config.privacy_regions.is_empty());
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use fjarsyn::{
+    media::stats::ConnectionStats,
+    ui::quality::{
+        QualityIndicator,
+        QualityLevel::{self, Excellent, Fair, Good, Poor},
+        QualityLimit, score,
+    },
+};
+
+fn rtt(millis: u64) -> ConnectionStats {
+    ConnectionStats { round_trip_time: Some(Duration::from_millis(millis)), ..Default::default() }
+}
+
+fn loss(fraction: f32) -> ConnectionStats {
+    ConnectionStats { packet_loss: Some(fraction), ..Default::default() }
+}
+
+fn bandwidth(available: f64, target: f64) -> ConnectionStats {
+    ConnectionStats {
+        available_bitrate: Some(available),
+        target_bitrate: Some(target),
+        ..Default::default()
+    }
+}
+
+fn freezes(freezes: u32) -> ConnectionStats {
+    ConnectionStats { freezes, ..Default::default() }
+}
+
+// Feeds the stats in order, returning the level shown after each.
+fn shown(stats: &[ConnectionStats]) -> Vec<QualityLevel> {
+    let mut indicator = QualityIndicator::new();
+    stats.iter().map(|stats| indicator.update(stats)).collect()
+}
+
+#[test]
+fn scores_each_metric() {
+    let cases = [
+        (rtt(40), Excellent, None),
+        (rtt(150), Good, Some(QualityLimit::Latency)),
+        (rtt(300), Fair, Some(QualityLimit::Latency)),
+        (rtt(600), Poor, Some(QualityLimit::Latency)),
+        (loss(0.0), Excellent, None),
+        (loss(0.02), Good, Some(QualityLimit::PacketLoss)),
+        (loss(0.05), Fair, Some(QualityLimit::PacketLoss)),
+        (loss(0.2), Poor, Some(QualityLimit::PacketLoss)),
+        (bandwidth(10e6, 8e6), Excellent, None),
+        (bandwidth(7e6, 8e6), Good, Some(QualityLimit::Bandwidth)),
+        (bandwidth(5e6, 8e6), Fair, Some(QualityLimit::Bandwidth)),
+        (bandwidth(2e6, 8e6), Poor, Some(QualityLimit::Bandwidth)),
+        (freezes(1), Fair, Some(QualityLimit::Freezes)),
+        (freezes(3), Poor, Some(QualityLimit::Freezes)),
+    ];
+    for (stats, level, limit) in cases {
+        assert_eq!(score(&stats), (level, limit), "{:?}", stats);
+    }
+}
+
+#[test]
+fn the_worst_metric_sets_the_score() {
+    let stats = ConnectionStats {
+        round_trip_time: Some(Duration::from_millis(150)),
+        packet_loss: Some(0.1),
+        ..bandwidth(7e6, 8e6)
+    };
+    assert_eq!(score(&stats), (Poor, Some(QualityLimit::PacketLoss)));
+}
+
+#[test]
+fn unknown_metrics_dont_count() {
+    assert_eq!(score(&ConnectionStats::default()), (Excellent, None));
+    // Without a target, e.g. while only watching, the bandwidth says nothing.
+    let stats = ConnectionStats { available_bitrate: Some(1.0), ..Default::default() };
+    assert_eq!(score(&stats), (Excellent, None));
+}
+
+#[test]
+fn follows_the_scores_with_hysteresis() {
+    let cases: [(&str, Vec<ConnectionStats>, Vec<QualityLevel>); 6] = [
+        ("the first score shows right away", vec![rtt(300)], vec![Fair]),
+        (
+            "a single bad sample doesn't drop it",
+            vec![rtt(40), loss(0.2), rtt(40), rtt(40)],
+            vec![Excellent, Excellent, Excellent, Excellent],
+        ),
+        (
+            "two bad samples do",
+            vec![rtt(40), loss(0.2), loss(0.2)],
+            vec![Excellent, Excellent, Poor],
+        ),
+        (
+            "drops only as far as both samples went",
+            vec![rtt(40), loss(0.2), rtt(150)],
+            vec![Excellent, Excellent, Good],
+        ),
+        (
+            "recovers after five good samples",
+            vec![rtt(600), rtt(40), rtt(40), rtt(40), rtt(40), rtt(40)],
+            vec![Poor, Poor, Poor, Poor, Poor, Excellent],
+        ),
+        (
+            "a bad sample starts the recovery over",
+            vec![rtt(600), rtt(40), rtt(40), rtt(40), rtt(40), rtt(600), rtt(40), rtt(40)],
+            vec![Poor; 8],
+        ),
+    ];
+    for (name, stats, expected) in cases {
+        assert_eq!(shown(&stats), expected, "{}", name);
+    }
+}
+
+#[test]
+fn recovers_only_as_far_as_all_samples_went() {
+    let stats = [rtt(600), rtt(40), rtt(150), rtt(40), rtt(300), rtt(40)];
+    assert_eq!(shown(&stats), [Poor, Poor, Poor, Poor, Poor, Fair]);
+}
+
+#[test]
+fn does_not_flicker_between_neighbouring_levels() {
+    // Hovering around the bound of Good and Fair.
+    let stats: Vec<_> = (0..20).map(|i| rtt(if i % 3 == 0 { 210 } else { 190 })).collect();
+    let levels = shown(&stats);
+    let changes = levels.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    assert_eq!(changes, 0, "{:?}", levels);
+}
+
+#[test]
+fn names_what_holds_the_shown_level_back() {
+    let mut indicator = QualityIndicator::new();
+    indicator.update(&loss(0.05));
+    assert_eq!(indicator.limit(), Some(QualityLimit::PacketLoss));
+
+    // Better samples don't explain a level they haven't lifted yet.
+    indicator.update(&rtt(40));
+    assert_eq!(indicator.level(), Some(Fair));
+    assert_eq!(indicator.limit(), Some(QualityLimit::PacketLoss));
+
+    indicator.update(&rtt(300));
+    assert_eq!(indicator.limit(), Some(QualityLimit::Latency));
+    assert_eq!(QualityLimit::PacketLoss.to_string(), "high packet loss");
+}
+
+#[test]
+fn reset_forgets_the_level() {
+    let mut indicator = QualityIndicator::new();
+    assert_eq!(indicator.level(), None);
+    indicator.update(&rtt(600));
+    indicator.reset();
+    assert_eq!(indicator.level(), None);
+    assert_eq!(indicator.update(&rtt(40)), Excellent);
+}
@@ -0,0 +1,96 @@
+//! What each quality preset writes into the config, and telling from the config which one it was set to.
+
+use fjarsyn::{
+    capture_providers::shared::CaptureFramerate,
+    config::{Config, QualityPreset},
+    media::ffmpeg::{FFmpegTranscodeType, RateControl},
+};
+
+#[test]
+fn each_preset_sets_its_bitrate_and_framerate() {
+    let table = [
+        (QualityPreset::Text, 6_000_000, CaptureFramerate::FPS24),
+        (QualityPreset::Balanced, 8_000_000, CaptureFramerate::FPS30),
+        (QualityPreset::Motion, 12_000_000, CaptureFramerate::FPS60),
+    ];
+    assert_eq!(table.len(), QualityPreset::ALL.len());
+
+    for (preset, bitrate, framerate) in table {
+        // Encoding on hardware, as chosen in the settings or found to work during onboarding.
+        let mut config =
+            Config { transcoding_type: FFmpegTranscodeType::H264Vulkan, ..Config::default() };
+        config.apply_preset(preset);
+        assert_eq!(config.bitrate, bitrate, "{preset}");
+        assert_eq!(config.framerate, framerate, "{preset}");
+        // Which encoders work depends on the machine rather than on what is shared, so the preset leaves it be.
+        assert_eq!(config.transcoding_type, FFmpegTranscodeType::H264Vulkan, "{preset}");
+    }
+}
+
+#[test]
+fn each_preset_expands_to_its_settings() {
+    for &preset in QualityPreset::ALL {
+        let mut config = Config::default();
+        config.apply_preset(preset);
+        assert_eq!(config.encoding_settings(), preset.settings(), "{preset}");
+        assert_eq!(config.gop, preset.settings().gop, "{preset}");
+        assert_eq!(config.rate_control, preset.settings().rate_control, "{preset}");
+        assert_eq!(config.max_encode_dimension, preset.settings().max_encode_dimension);
+    }
+    // No two are the same, or the one applied couldn't be told from the other.
+    for (i, a) in QualityPreset::ALL.iter().enumerate() {
+        for b in &QualityPreset::ALL[i + 1..] {
+            assert_ne!(a.settings(), b.settings(), "{a} and {b}");
+        }
+    }
+}
+
+#[test]
+fn applied_presets_are_recognized_again() {
+    let mut config = Config::default();
+    for &preset in QualityPreset::ALL {
+        config.apply_preset(preset);
+        assert_eq!(config.quality_preset(), Some(preset));
+    }
+    // Switching between them in any order.
+    for &preset in QualityPreset::ALL.iter().rev() {
+        config.apply_preset(preset);
+        assert_eq!(config.quality_preset(), Some(preset));
+    }
+}
+
+#[test]
+fn changing_any_setting_by_hand_makes_it_custom() {
+    let changes: [fn(&mut Config); 5] = [
+        |config| config.bitrate += 1,
+        |config| config.framerate = CaptureFramerate::FPS120,
+        |config| config.gop += 1,
+        |config| {
+            config.rate_control = match config.rate_control {
+                RateControl::Variable => RateControl::Constant,
+                RateControl::Constant => RateControl::Variable,
+            }
+        },
+        |config| config.max_encode_dimension = Some(1280),
+    ];
+    for &preset in QualityPreset::ALL {
+        for change in changes {
+            let mut config = Config::default();
+            config.apply_preset(preset);
+            change(&mut config);
+            assert_eq!(config.quality_preset(), None, "{preset}: {:?}", config.encoding_settings());
+            // Back from custom.
+            config.apply_preset(preset);
+            assert_eq!(config.quality_preset(), Some(preset));
+        }
+    }
+}
+
+#[test]
+fn settings_outside_the_presets_dont_make_it_custom() {
+    let mut config = Config::default();
+    config.apply_preset(QualityPreset::Text);
+    config.bandwidth_cap = Some(1_000_000);
+    config.transcoding_type = FFmpegTranscodeType::H264Software;
+    assert_eq!(config.quality_preset(), Some(QualityPreset::Text));
+}
@@ -0,0 +1,81 @@
+use fjarsyn::{
+    capture_providers::shared::CaptureFramerate,
+    config::{EncodingSettings, QualityPreset},
+    media::quality_request::{ViewerQuality, clamp_settings, reduces},
+};
+use fjarsyn_shared::QualityRequest;
+
+fn local() -> EncodingSettings {
+    EncodingSettings { max_encode_dimension: Some(1920), ..QualityPreset::Motion.settings() }
+}
+
+#[test]
+fn an_empty_request_changes_nothing() {
+    let settings = local();
+    assert_eq!(clamp_settings(settings, &QualityRequest::default()), settings);
+    assert!(!reduces(settings, &QualityRequest::default()));
+}
+
+#[test]
+fn takes_the_lower_of_each_setting() {
+    let settings =
+        EncodingSettings { bitrate: 8_000_000, framerate: CaptureFramerate::FPS60, ..local() };
+    let request = QualityRequest {
+        max_bitrate: Some(2_000_000),
+        max_fps: Some(30),
+        max_dimension: Some(1280),
+    };
+
+    let clamped = clamp_settings(settings, &request);
+    assert_eq!(clamped.bitrate, 2_000_000);
+    assert_eq!(clamped.framerate, CaptureFramerate::FPS30);
+    assert_eq!(clamped.max_encode_dimension, Some(1280));
+    // Not part of the request, so left as they are.
+    assert_eq!(clamped.gop, settings.gop);
+    assert_eq!(clamped.rate_control, settings.rate_control);
+    assert!(reduces(settings, &request));
+}
+
+#[test]
+fn never_raises_the_local_settings() {
+    let settings = EncodingSettings {
+        bitrate: 1_000_000,
+        framerate: CaptureFramerate::FPS24,
+        max_encode_dimension: Some(1280),
+        ..local()
+    };
+    let request = QualityRequest {
+        max_bitrate: Some(6_000_000),
+        max_fps: Some(60),
+        max_dimension: Some(1920),
+    };
+
+    assert_eq!(clamp_settings(settings, &request), settings);
+    assert!(!reduces(settings, &request));
+}
+
+#[test]
+fn limits_an_unlimited_dimension() {
+    let settings = EncodingSettings { max_encode_dimension: None, ..local() };
+    let request = QualityRequest { max_dimension: Some(1280), ..QualityRequest::default() };
+    assert_eq!(clamp_settings(settings, &request).max_encode_dimension, Some(1280));
+}
+
+#[test]
+fn rounds_the_framerate_down_to_one_that_can_be_captured() {
+    let settings = EncodingSettings { framerate: CaptureFramerate::FPS60, ..local() };
+
+    let request = QualityRequest { max_fps: Some(29), ..QualityRequest::default() };
+    assert!(clamp_settings(settings, &request).framerate.to_hz() <= 29.0);
+
+    // Below the lowest one, which is as low as it goes.
+    let request = QualityRequest { max_fps: Some(1), ..QualityRequest::default() };
+    assert_eq!(clamp_settings(settings, &request).framerate, CaptureFramerate::ALL[0]);
+}
+
+#[test]
+fn only_the_presenters_quality_clears_the_request() {
+    for quality in ViewerQuality::ALL {
+        assert_eq!(quality.request().is_none(), *quality == ViewerQuality::Presenter);
+    }
+}
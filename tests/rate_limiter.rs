@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::utils::rate_limiter::{Admission, RateLimiter};
+
+const RATE: u64 = 250_000;
+const WINDOW: Duration = Duration::from_secs(1);
+// Less than a frame, so the frames can't fall behind.
+const MAX_DELAY: Duration = Duration::from_millis(20);
+const FRAME: Duration = Duration::from_millis(33);
+
+fn limiter() -> RateLimiter {
+    RateLimiter::new(RATE, WINDOW, MAX_DELAY)
+}
+
+// Offers a packet a frame, and returns the bytes that went out, the packets dropped and how long it took.
+fn run(
+    limiter: &mut RateLimiter,
+    start: Instant,
+    packets: impl IntoIterator<Item = (usize, bool)>,
+) -> (u64, u64, Duration) {
+    let (mut sent, mut dropped) = (0, 0);
+    // The write path waits out a delay before it takes the next packet.
+    let mut busy_until = start;
+    let mut arrival = start;
+    for (bytes, keyframe) in packets {
+        let now = arrival.max(busy_until);
+        match limiter.admit(bytes, keyframe, now) {
+            Admission::Send => sent += bytes as u64,
+            Admission::Delay(delay) => {
+                sent += bytes as u64;
+                busy_until = now + delay;
+            }
+            Admission::Drop => dropped += 1,
+        }
+        arrival += FRAME;
+    }
+    (sent, dropped, arrival.max(busy_until) - start)
+}
+
+#[test]
+fn bursts_up_to_the_window_go_straight_through() {
+    let mut limiter = limiter();
+    let now = Instant::now();
+    for _ in 0..10 {
+        assert_eq!(limiter.admit(RATE as usize / 10, false, now), Admission::Send);
+    }
+    // The bucket is empty now, so the next one waits for what it needs.
+    assert_eq!(
+        limiter.admit(RATE as usize / 100, false, now),
+        Admission::Delay(Duration::from_millis(10))
+    );
+    assert_eq!(limiter.dropped(), 0);
+}
+
+#[test]
+fn refills_at_the_rate() {
+    let mut limiter = limiter();
+    let now = Instant::now();
+    assert_eq!(limiter.admit(RATE as usize, false, now), Admission::Send);
+    assert_eq!(limiter.admit(RATE as usize / 2, false, now + WINDOW / 2), Admission::Send);
+    // Never more than a window's worth, however long it was idle.
+    let later = now + 10 * WINDOW;
+    assert_eq!(limiter.admit(RATE as usize, false, later), Admission::Send);
+    assert!(matches!(limiter.admit(1_000, false, later), Admission::Delay(_)));
+}
+
+#[test]
+fn sustained_overload_stays_within_the_rate() {
+    let mut limiter = limiter();
+    let start = Instant::now();
+    // Twice the rate at 30 fps for ten seconds, with a keyframe every second.
+    let per_frame = 2 * RATE as usize * FRAME.as_millis() as usize / 1000;
+    let packets = (0..300).map(|frame| (per_frame, frame % 30 == 0));
+    let (sent, dropped, elapsed) = run(&mut limiter, start, packets);
+
+    // A window's worth from the start, then no more than the rate.
+    let seconds = elapsed.as_secs_f64();
+    assert!(sent as f64 <= RATE as f64 * (seconds + WINDOW.as_secs_f64()), "sent {}", sent);
+    assert!(sent as f64 >= RATE as f64 * seconds / 2.0, "sent {}", sent);
+    assert!(dropped > 0);
+    assert_eq!(dropped, limiter.dropped());
+}
+
+#[test]
+fn keyframes_are_never_dropped() {
+    let mut limiter = limiter();
+    let now = Instant::now();
+    assert_eq!(limiter.admit(RATE as usize, false, now), Admission::Send);
+
+    // Larger than the bucket holds, and with the bucket empty.
+    let Admission::Delay(delay) = limiter.admit(2 * RATE as usize, true, now) else {
+        panic!("Keyframe should wait for a full bucket");
+    };
+    assert_eq!(delay, WINDOW);
+    assert!(!limiter.is_awaiting_keyframe());
+}
+
+#[test]
+fn drops_until_the_next_keyframe() {
+    let mut limiter = limiter();
+    let now = Instant::now();
+    assert_eq!(limiter.admit(RATE as usize, false, now), Admission::Send);
+
+    // Would have to wait longer than it may.
+    assert_eq!(limiter.admit(RATE as usize / 2, false, now), Admission::Drop);
+    assert!(limiter.is_awaiting_keyframe());
+    // What comes after can't be decoded without it, even once there is room again.
+    let later = now + WINDOW;
+    assert_eq!(limiter.admit(1_000, false, later), Admission::Drop);
+    assert_eq!(limiter.admit(1_000, true, later), Admission::Send);
+    assert_eq!(limiter.admit(1_000, false, later), Admission::Send);
+    assert_eq!(limiter.dropped(), 2);
+}
+
+#[test]
+fn lowering_the_rate_keeps_at_most_its_window() {
+    let mut limiter = limiter();
+    let now = Instant::now();
+    limiter.set_rate(RATE / 10, now);
+    assert_eq!(limiter.bytes_per_second(), RATE / 10);
+    assert_eq!(limiter.admit(RATE as usize / 10, false, now), Admission::Send);
+    assert_eq!(limiter.admit(RATE as usize / 10, false, now), Admission::Drop);
+}
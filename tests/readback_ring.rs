@@ -0,0 +1,152 @@
+//! Which staging slot a frame is copied into and which one is read back, and when the ring grows.
+
+use fjarsyn::capture_providers::shared::ReadbackRing;
+
+// The depths the WGC provider starts with and may grow to.
+const DEPTH: usize = 2;
+const MAX_DEPTH: usize = 4;
+
+// A ring that delivered its first frame, whose readback doesn't wait for anything, so readiness counts from here.
+fn started() -> ReadbackRing {
+    let mut ring = ReadbackRing::new(DEPTH, MAX_DEPTH);
+    assert_eq!(ring.write_slot(), 0);
+    assert_eq!(ring.read_slot(|_| false), Some(0));
+    ring
+}
+
+fn none_ready(_: usize) -> bool {
+    false
+}
+
+#[test]
+fn writes_go_round_the_ring() {
+    let mut ring = ReadbackRing::new(DEPTH, MAX_DEPTH);
+    let slots: Vec<_> = (0..5).map(|_| ring.write_slot()).collect();
+    assert_eq!(slots, [0, 1, 0, 1, 0]);
+}
+
+#[test]
+fn the_first_readback_takes_the_frame_just_copied() {
+    let mut ring = ReadbackRing::new(DEPTH, MAX_DEPTH);
+    assert_eq!(ring.delivered(), 0);
+    assert_eq!(ring.write_slot(), 0);
+    assert_eq!(ring.read_slot(none_ready), Some(0));
+    assert_eq!(ring.delivered(), 1);
+    assert_eq!(ring.skipped(), 0);
+
+    // From then on, the copy has to be done.
+    assert_eq!(ring.write_slot(), 1);
+    assert_eq!(ring.read_slot(none_ready), None);
+    assert_eq!(ring.read_slot(|slot| slot == 1), Some(1));
+}
+
+#[test]
+fn reads_the_newest_copy_that_is_done() {
+    let mut ring = started();
+    ring.write_slot();
+    assert_eq!(ring.read_slot(none_ready), None);
+    ring.write_slot();
+    // Both are done by now, and the older frame is stale.
+    assert_eq!(ring.read_slot(|_| true), Some(0));
+    assert_eq!(ring.delivered(), 2);
+    // It was dropped along with the one read.
+    assert_eq!(ring.read_slot(|_| true), None);
+
+    // Only the older one is done.
+    ring.write_slot();
+    ring.write_slot();
+    assert_eq!(ring.read_slot(|slot| slot == 1), Some(1));
+    assert_eq!(ring.read_slot(|_| true), Some(0));
+}
+
+#[test]
+fn a_copy_over_an_unread_slot_loses_its_frame() {
+    let mut ring = started();
+    assert_eq!(ring.write_slot(), 1);
+    assert_eq!(ring.write_slot(), 0);
+    assert_eq!(ring.write_slot(), 1);
+    // The first frame in slot 1 is gone, and the one copied over it is now newer than slot 0.
+    assert_eq!(ring.read_slot(|slot| slot == 0), Some(0));
+    assert_eq!(ring.read_slot(|_| true), Some(1));
+    assert_eq!(ring.read_slot(|_| true), None);
+}
+
+#[test]
+fn counts_the_skipped_readbacks() {
+    let mut ring = started();
+    for skipped in 1..=5 {
+        ring.write_slot();
+        assert_eq!(ring.read_slot(none_ready), None);
+        assert_eq!(ring.skipped(), skipped);
+    }
+    assert_eq!(ring.delivered(), 1);
+}
+
+#[test]
+fn grows_after_skipping_several_in_a_row() {
+    let mut ring = started();
+    for _ in 0..2 {
+        ring.write_slot();
+        ring.read_slot(none_ready);
+        assert!(!ring.should_grow());
+    }
+    ring.write_slot();
+    ring.read_slot(none_ready);
+    assert!(ring.should_grow());
+
+    assert_eq!(ring.grow(), DEPTH);
+    assert_eq!(ring.depth(), DEPTH + 1);
+    // It starts counting again.
+    assert!(!ring.should_grow());
+    // And the new slot is written in turn.
+    let slots: Vec<_> = (0..6).map(|_| ring.write_slot()).collect();
+    assert!(slots.contains(&DEPTH), "{slots:?}");
+    assert!(slots.iter().all(|&slot| slot <= DEPTH), "{slots:?}");
+}
+
+#[test]
+fn a_readback_in_between_starts_the_skips_over() {
+    let mut ring = started();
+    for _ in 0..2 {
+        ring.write_slot();
+        ring.read_slot(none_ready);
+    }
+    let slot = ring.write_slot();
+    assert_eq!(ring.read_slot(|ready| ready == slot), Some(slot));
+    ring.write_slot();
+    ring.read_slot(none_ready);
+    assert!(!ring.should_grow());
+}
+
+#[test]
+fn grows_no_further_than_the_max_depth() {
+    let mut ring = started();
+    let mut grown = Vec::new();
+    for _ in 0..20 {
+        ring.write_slot();
+        ring.read_slot(none_ready);
+        if ring.should_grow() {
+            grown.push(ring.grow());
+        }
+    }
+    assert_eq!(grown, [2, 3]);
+    assert_eq!(ring.depth(), MAX_DEPTH);
+    let slots: Vec<_> = (0..MAX_DEPTH * 2).map(|_| ring.write_slot()).collect();
+    let mut seen = slots.clone();
+    seen.sort_unstable();
+    seen.dedup();
+    assert_eq!(seen, [0, 1, 2, 3]);
+}
+
+#[test]
+fn keeps_at_least_one_slot() {
+    let mut ring = ReadbackRing::new(0, 0);
+    assert_eq!(ring.depth(), 1);
+    assert_eq!([ring.write_slot(), ring.write_slot()], [0, 0]);
+    // Nor may it grow past the depth it starts with.
+    for _ in 0..5 {
+        ring.write_slot();
+        ring.read_slot(none_ready);
+    }
+    assert!(!ring.should_grow());
+}
@@ -0,0 +1,108 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fjarsyn::{
+    networking::webrtc::WebRTCError,
+    ui::{app::App, message::Message, reconnect::Reconnect, test_support::home_state},
+};
+use iced::Program;
+
+fn connection_failed() -> Message {
+    Message::WebRTCInitialized(Err(Arc::new(WebRTCError::PeerConnectionError(
+        webrtc::Error::ErrConnectionClosed,
+    ))))
+}
+
+#[test]
+fn backs_off_exponentially_up_to_the_max() {
+    let now = Instant::now();
+    let mut reconnect = Reconnect::new();
+    assert!(!reconnect.is_offline());
+    assert_eq!(reconnect.retry_in(now), None);
+
+    assert!(reconnect.failed(now, "refused".to_owned()));
+    assert_eq!(reconnect.retry_in(now), Some(Reconnect::INITIAL_DELAY));
+    assert!(!reconnect.failed(now, "refused".to_owned()));
+    assert_eq!(reconnect.retry_in(now), Some(Reconnect::INITIAL_DELAY * 2));
+
+    for _ in 0..20 {
+        reconnect.failed(now, "refused".to_owned());
+    }
+    assert_eq!(reconnect.retry_in(now), Some(Reconnect::MAX_DELAY));
+    assert_eq!(reconnect.last_error(), Some("refused"));
+}
+
+#[test]
+fn is_due_once_the_delay_has_passed() {
+    let now = Instant::now();
+    let mut reconnect = Reconnect::new();
+    assert!(!reconnect.is_due(now));
+
+    reconnect.failed(now, "refused".to_owned());
+    assert!(!reconnect.is_due(now + Reconnect::INITIAL_DELAY - Duration::from_millis(1)));
+    assert!(reconnect.is_due(now + Reconnect::INITIAL_DELAY));
+}
+
+#[test]
+fn success_resets_the_backoff() {
+    let now = Instant::now();
+    let mut reconnect = Reconnect::new();
+    reconnect.failed(now, "refused".to_owned());
+    reconnect.failed(now, "refused".to_owned());
+
+    reconnect.succeeded();
+    assert!(!reconnect.is_offline());
+    assert!(!reconnect.is_due(now + Reconnect::MAX_DELAY));
+    // The next outage starts over from the shortest delay, and is notified about again.
+    assert!(reconnect.failed(now, "refused".to_owned()));
+    assert_eq!(reconnect.retry_in(now), Some(Reconnect::INITIAL_DELAY));
+}
+
+#[test]
+fn retry_does_not_overlap_an_attempt_in_flight() {
+    let mut state = home_state();
+    let _ = App::default().update(&mut state, Message::RetryConnection);
+    assert!(state.ctx.call.connecting);
+
+    // A second retry while the first is in flight must not start another connection.
+    let _ = App::default().update(&mut state, Message::RetryConnection);
+    assert!(state.ctx.call.connecting);
+    assert_eq!(state.ctx.call.reconnect.failures(), 0);
+
+    let _ = App::default().update(&mut state, connection_failed());
+    assert!(!state.ctx.call.connecting);
+    assert!(state.ctx.call.reconnect.is_offline());
+    assert!(state.ctx.call.webrtc.is_none());
+}
+
+#[test]
+fn failure_goes_offline_and_tick_retries_when_due() {
+    let mut state = home_state();
+    let _ = App::default().update(&mut state, Message::RetryConnection);
+    let _ = App::default().update(&mut state, connection_failed());
+    assert_eq!(state.ctx.call.reconnect.failures(), 1);
+
+    // Not due yet, so still offline and waiting.
+    let _ = App::default().update(&mut state, Message::Tick(Instant::now()));
+    assert!(!state.ctx.call.connecting);
+
+    let _ =
+        App::default().update(&mut state, Message::Tick(Instant::now() + Reconnect::INITIAL_DELAY));
+    assert!(state.ctx.call.connecting);
+
+    let _ = App::default().update(&mut state, connection_failed());
+    assert!(!state.ctx.call.connecting);
+    assert_eq!(state.ctx.call.reconnect.failures(), 2);
+}
+
+#[test]
+fn tick_does_not_connect_before_onboarding_is_done() {
+    let mut state = home_state();
+    state.ctx.config.onboarding_done = false;
+    state.ctx.call.reconnect.failed(Instant::now(), "refused".to_owned());
+
+    let _ = App::default().update(&mut state, Message::Tick(Instant::now() + Reconnect::MAX_DELAY));
+    assert!(!state.ctx.call.connecting);
+}
@@ -0,0 +1,70 @@
+//! Recording the received video to a file, from its first keyframe on.
+
+mod common;
+
+use std::fs;
+
+use bytes::Bytes;
+use common::test_dir::TestDir;
+use fjarsyn::media::{
+    nal::NalCodec,
+    recorder::{Recorder, RecorderError, RecordingWriter},
+};
+
+// SPS, PPS and an IDR slice, the way the encoders start a keyframe.
+const H264_KEYFRAME: &[u8] = &[
+    0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1F, //
+    0, 0, 0, 1, 0x68, 0xCE, 0x3C, 0x80, //
+    0, 0, 1, 0x65, 0x88, 0x84, 0x00,
+];
+const H264_DELTA: &[u8] = &[0, 0, 0, 1, 0x41, 0x9A, 0x02, 0x03];
+
+#[test]
+fn starts_at_the_first_keyframe() {
+    let mut writer = RecordingWriter::new(Vec::new(), NalCodec::H264);
+    assert!(!writer.write(H264_DELTA).unwrap());
+    assert!(writer.write(H264_KEYFRAME).unwrap());
+    assert!(writer.write(H264_DELTA).unwrap());
+
+    assert_eq!(writer.bytes(), (H264_KEYFRAME.len() + H264_DELTA.len()) as u64);
+    assert_eq!(writer.into_inner(), [H264_KEYFRAME, H264_DELTA].concat());
+}
+
+#[test]
+fn skips_to_the_next_keyframe_after_a_gap() {
+    let mut writer = RecordingWriter::new(Vec::new(), NalCodec::H264);
+    writer.write(H264_KEYFRAME).unwrap();
+    writer.resync();
+    assert!(!writer.write(H264_DELTA).unwrap());
+    assert!(writer.write(H264_KEYFRAME).unwrap());
+
+    assert_eq!(writer.into_inner(), [H264_KEYFRAME, H264_KEYFRAME].concat());
+}
+
+#[tokio::test]
+async fn writes_what_the_tap_gets_to_the_file() {
+    let dir = TestDir::new("recording");
+    let recorder = Recorder::start(dir.path(), "call", "video/H264").unwrap();
+    let path = recorder.path().to_owned();
+    assert_eq!(path, dir.join("call.h264"));
+
+    let tap = recorder.tap();
+    for packet in [H264_DELTA, H264_KEYFRAME, H264_DELTA] {
+        tap.push(&Bytes::from_static(packet));
+    }
+    let bytes = recorder.stop().await.unwrap();
+
+    // Once stopped, the tap goes nowhere.
+    tap.push(&Bytes::from_static(H264_KEYFRAME));
+    assert_eq!(fs::read(&path).unwrap(), [H264_KEYFRAME, H264_DELTA].concat());
+    assert_eq!(bytes, fs::metadata(&path).unwrap().len());
+}
+
+#[test]
+fn only_records_nal_unit_streams() {
+    let dir = TestDir::new("unsupported");
+    assert!(matches!(
+        Recorder::start(dir.path(), "call", "video/VP8"),
+        Err(RecorderError::UnsupportedCodec(_))
+    ));
+}
@@ -0,0 +1,120 @@
+use fjarsyn::utils::{
+    bitmap_utils::{RedactionStyle, redact},
+    rect::Rect,
+    vector2::Vector2,
+};
+
+fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect<f32> {
+    Rect { position: Vector2::new(x, y), size: Vector2::new(width, height) }
+}
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+// Every pixel different, so anything that moves or blends them shows.
+fn pattern(size: Vector2<i32>) -> Vec<u8> {
+    (0..size.x * size.y)
+        .flat_map(|i| [(i % 251 + 1) as u8, (i * 7 % 253) as u8, (i * 13 % 255) as u8, 255])
+        .collect()
+}
+
+fn pixel(bitmap: &[u8], size: Vector2<i32>, x: i32, y: i32) -> [u8; 4] {
+    let offset = (y * size.x + x) as usize * 4;
+    bitmap[offset..offset + 4].try_into().unwrap()
+}
+
+fn inside(rect: Rect<i32>, x: i32, y: i32) -> bool {
+    x >= rect.position.x && x < rect.right() && y >= rect.position.y && y < rect.bottom()
+}
+
+#[test]
+fn fill_covers_the_pixels_rounded_outwards() {
+    let size = Vector2::new(10, 8);
+    let original = pattern(size);
+    let mut bitmap = original.clone();
+    // From 2.5 to 5.5 across, and 1 to 3 down.
+    let region = rect(0.25, 0.125, 0.3, 0.25);
+    redact(&mut bitmap, size, &[region], RedactionStyle::Fill);
+
+    let covered = Rect::new(2, 1, 4, 2);
+    assert_eq!(region.to_pixels(size), Some(covered));
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let expected = if inside(covered, x, y) { BLACK } else { pixel(&original, size, x, y) };
+            assert_eq!(pixel(&bitmap, size, x, y), expected, "at {},{}", x, y);
+        }
+    }
+}
+
+#[test]
+fn regions_past_the_frame_are_clamped() {
+    let size = Vector2::new(8, 4);
+    let mut bitmap = pattern(size);
+    redact(&mut bitmap, size, &[rect(0.75, -0.5, 1.0, 1.0)], RedactionStyle::Fill);
+    let covered = Rect::new(6, 0, 2, 2);
+    for y in 0..size.y {
+        for x in 0..size.x {
+            assert_eq!(pixel(&bitmap, size, x, y) == BLACK, inside(covered, x, y), "{},{}", x, y);
+        }
+    }
+
+    // Entirely outside, so nothing is touched.
+    let original = pattern(size);
+    let mut bitmap = original.clone();
+    redact(&mut bitmap, size, &[rect(1.5, 0.0, 0.5, 0.5)], RedactionStyle::Blur);
+    assert_eq!(bitmap, original);
+}
+
+#[test]
+fn regions_cover_the_same_part_at_any_resolution() {
+    let region = rect(0.5, 0.25, 0.25, 0.5);
+    for size in [Vector2::new(16, 8), Vector2::new(1920, 1080), Vector2::new(640, 360)] {
+        let mut bitmap = pattern(size);
+        redact(&mut bitmap, size, &[region], RedactionStyle::Fill);
+        let covered = region.to_pixels(size).unwrap();
+        assert_eq!(covered.position.x, size.x / 2);
+        assert_eq!(covered.right(), size.x * 3 / 4);
+        assert_eq!(pixel(&bitmap, size, covered.position.x, covered.position.y), BLACK);
+        assert_ne!(pixel(&bitmap, size, covered.position.x - 1, covered.position.y), BLACK);
+    }
+}
+
+#[test]
+fn blur_leaves_a_uniform_region_as_it_was() {
+    let size = Vector2::new(40, 30);
+    let mut bitmap: Vec<u8> = [90, 120, 200, 255].repeat((size.x * size.y) as usize);
+    let original = bitmap.clone();
+    redact(&mut bitmap, size, &[rect(0.1, 0.1, 0.8, 0.8)], RedactionStyle::Blur);
+    assert_eq!(bitmap, original);
+}
+
+#[test]
+fn blur_spreads_what_is_inside_and_nothing_else() {
+    let size = Vector2::new(200, 100);
+    let mut bitmap: Vec<u8> = BLACK.repeat((size.x * size.y) as usize);
+    let covered = Rect::new(50, 25, 100, 50);
+    // A bright pixel just outside the region, and a bright block in it, far enough from the edge not to reach it.
+    let mut brighten = |x: i32, y: i32| {
+        let offset = (y * size.x + x) as usize * 4;
+        bitmap[offset..offset + 4].copy_from_slice(&[255, 255, 255, 255]);
+    };
+    brighten(49, 50);
+    for y in 48..53 {
+        for x in 118..123 {
+            brighten(x, y);
+        }
+    }
+    let original = bitmap.clone();
+    redact(&mut bitmap, size, &[rect(0.25, 0.25, 0.5, 0.5)], RedactionStyle::Blur);
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            if !inside(covered, x, y) {
+                assert_eq!(pixel(&bitmap, size, x, y), pixel(&original, size, x, y), "{},{}", x, y);
+            }
+        }
+    }
+    // The block is smeared over its neighbours, and the pixel outside isn't pulled in.
+    assert!(pixel(&bitmap, size, 120, 50)[0] < 255);
+    assert!(pixel(&bitmap, size, 126, 50)[0] > 0);
+    assert_eq!(pixel(&bitmap, size, 50, 50), BLACK);
+}
@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::{
+    capture_providers::shared::RegionComposite,
+    utils::{bitmap_utils::blit, rect::Rect, vector2::Vector2},
+};
+
+const WAIT: Duration = Duration::from_millis(25);
+
+// One byte per pixel, so the pixels can be checked by their value.
+fn solid(size: Vector2<i32>, value: u8) -> Vec<u8> {
+    vec![value; size.x as usize * size.y as usize]
+}
+
+fn pixel(data: &[u8], size: Vector2<i32>, x: i32, y: i32) -> u8 {
+    data[(y * size.x + x) as usize]
+}
+
+// Two 4x2 monitors side by side, the left one starting left of the origin, and a region over both.
+fn side_by_side() -> RegionComposite {
+    let monitors = [Rect::new(-4, 0, 4, 2), Rect::new(0, 0, 4, 2)];
+    RegionComposite::new(Rect::new(-4, 0, 8, 2), monitors, 1, WAIT)
+}
+
+#[test]
+fn blit_clips_to_the_destination() {
+    let size = Vector2::new(4, 3);
+    let mut dst = solid(size, 0);
+    blit(&solid(Vector2::new(3, 3), 7), Vector2::new(3, 3), &mut dst, size, Vector2::new(2, -1), 1);
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let expected = if x >= 2 && y <= 1 { 7 } else { 0 };
+            assert_eq!(pixel(&dst, size, x, y), expected, "at {}, {}", x, y);
+        }
+    }
+}
+
+#[test]
+fn blit_outside_the_destination_does_nothing() {
+    let size = Vector2::new(4, 4);
+    let mut dst = solid(size, 0);
+    blit(&solid(Vector2::new(2, 2), 7), Vector2::new(2, 2), &mut dst, size, Vector2::new(4, 0), 1);
+    assert!(dst.iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn places_each_monitor_at_its_offset() {
+    let mut composite = side_by_side();
+    let now = Instant::now();
+    let frame = Vector2::new(4, 2);
+
+    assert!(!composite.submit(0, &solid(frame, 1), frame, None, now));
+    assert!(composite.submit(1, &solid(frame, 2), frame, None, now));
+
+    let mut out = vec![0; composite.len()];
+    composite.finish(&mut out);
+    let size = composite.size();
+    assert_eq!(size, Vector2::new(8, 2));
+    assert_eq!(pixel(&out, size, 0, 0), 1);
+    assert_eq!(pixel(&out, size, 3, 1), 1);
+    assert_eq!(pixel(&out, size, 4, 0), 2);
+    assert_eq!(pixel(&out, size, 7, 1), 2);
+}
+
+#[test]
+fn translates_dirty_rects_into_the_region() {
+    let mut composite = side_by_side();
+    let now = Instant::now();
+    let frame = Vector2::new(4, 2);
+
+    composite.submit(0, &solid(frame, 1), frame, Some(&[Rect::new(1, 0, 1, 1)]), now);
+    composite.submit(1, &solid(frame, 2), frame, Some(&[Rect::new(2, 1, 1, 1)]), now);
+
+    let mut out = vec![0; composite.len()];
+    let mut dirty = composite.finish(&mut out);
+    dirty.sort_by_key(|rect| rect.position.x);
+    assert_eq!(dirty, vec![Rect::new(1, 0, 1, 1), Rect::new(6, 1, 1, 1)]);
+}
+
+#[test]
+fn clips_monitors_to_the_region() {
+    // Only the right half of the left monitor, and the left half of the right one.
+    let monitors = [Rect::new(0, 0, 4, 2), Rect::new(4, 0, 4, 2)];
+    let mut composite = RegionComposite::new(Rect::new(2, 0, 4, 2), monitors, 1, WAIT);
+    let now = Instant::now();
+    let frame = Vector2::new(4, 2);
+
+    composite.submit(0, &solid(frame, 1), frame, None, now);
+    assert!(composite.submit(1, &solid(frame, 2), frame, None, now));
+
+    let mut out = vec![0; composite.len()];
+    let dirty = composite.finish(&mut out);
+    assert_eq!(out, vec![1, 1, 2, 2, 1, 1, 2, 2]);
+    assert_eq!(dirty, vec![Rect::new(0, 0, 4, 2)]);
+}
+
+#[test]
+fn waits_for_the_slower_monitor() {
+    let mut composite = side_by_side();
+    let start = Instant::now();
+    let frame = Vector2::new(4, 2);
+    let mut out = vec![0; composite.len()];
+
+    composite.submit(0, &solid(frame, 1), frame, None, start);
+    composite.submit(1, &solid(frame, 2), frame, None, start);
+    composite.finish(&mut out);
+
+    // The faster monitor delivering again doesn't finish the composite on its own.
+    assert!(!composite.submit(0, &solid(frame, 3), frame, None, start));
+    assert!(!composite.submit(0, &solid(frame, 4), frame, None, start + WAIT / 2));
+    assert!(composite.submit(1, &solid(frame, 5), frame, None, start + WAIT / 2));
+
+    composite.finish(&mut out);
+    let size = composite.size();
+    assert_eq!(pixel(&out, size, 0, 0), 4);
+    assert_eq!(pixel(&out, size, 4, 0), 5);
+}
+
+#[test]
+fn does_not_wait_forever_for_an_idle_monitor() {
+    let mut composite = side_by_side();
+    let start = Instant::now();
+    let frame = Vector2::new(4, 2);
+    let mut out = vec![0; composite.len()];
+
+    composite.submit(0, &solid(frame, 1), frame, None, start);
+    composite.submit(1, &solid(frame, 2), frame, None, start);
+    composite.finish(&mut out);
+
+    assert!(!composite.submit(0, &solid(frame, 3), frame, Some(&[]), start));
+    assert!(composite.submit(0, &solid(frame, 3), frame, Some(&[]), start + WAIT));
+
+    // The idle monitor keeps what it showed last.
+    composite.finish(&mut out);
+    assert_eq!(pixel(&out, composite.size(), 4, 0), 2);
+}
+
+#[test]
+fn is_not_done_before_every_monitor_delivered_once() {
+    let mut composite = side_by_side();
+    let start = Instant::now();
+    let frame = Vector2::new(4, 2);
+
+    assert!(!composite.submit(0, &solid(frame, 1), frame, None, start));
+    assert!(!composite.submit(0, &solid(frame, 1), frame, None, start + WAIT * 10));
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::ui::remote_idle::RemoteIdle;
+
+const IDLE_AFTER: Duration = Duration::from_secs(5);
+
+#[test]
+fn is_not_idle_before_the_first_frame() {
+    let idle = RemoteIdle::new(IDLE_AFTER);
+    let now = Instant::now();
+    assert!(!idle.is_idle(now + IDLE_AFTER * 10));
+    assert_eq!(idle.since_last_frame(now), None);
+}
+
+#[test]
+fn turns_idle_past_the_threshold() {
+    let mut idle = RemoteIdle::new(IDLE_AFTER);
+    let start = Instant::now();
+    idle.frame_arrived(start);
+
+    assert!(!idle.is_idle(start + IDLE_AFTER - Duration::from_millis(1)));
+    assert!(idle.is_idle(start + IDLE_AFTER));
+    assert_eq!(idle.since_last_frame(start + Duration::from_secs(2)), Some(Duration::from_secs(2)));
+}
+
+#[test]
+fn the_next_frame_clears_it_right_away() {
+    let mut idle = RemoteIdle::new(IDLE_AFTER);
+    let start = Instant::now();
+    idle.frame_arrived(start);
+
+    let later = start + IDLE_AFTER * 2;
+    assert!(idle.is_idle(later));
+    idle.frame_arrived(later);
+    assert!(!idle.is_idle(later));
+}
+
+#[test]
+fn zero_holds_the_last_frame_without_saying_so() {
+    let mut idle = RemoteIdle::new(Duration::ZERO);
+    let start = Instant::now();
+    idle.frame_arrived(start);
+    assert!(!idle.is_idle(start + Duration::from_secs(3600)));
+}
+
+#[test]
+fn reset_forgets_the_last_frame() {
+    let mut idle = RemoteIdle::new(IDLE_AFTER);
+    let start = Instant::now();
+    idle.frame_arrived(start);
+    idle.reset();
+    assert!(!idle.is_idle(start + IDLE_AFTER * 2));
+}
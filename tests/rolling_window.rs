@@ -0,0 +1,81 @@
+//! The rolling windows the encoder's bitrate and framerate are worked out over.
+
+use std::time::{Duration, Instant};
+
+use fjarsyn::media::stats::RollingWindow;
+
+const SECOND: Duration = Duration::from_secs(1);
+const TEN_SECONDS: Duration = Duration::from_secs(10);
+
+#[test]
+fn keeps_samples_up_to_the_edge_of_the_window() {
+    for window in [SECOND, TEN_SECONDS] {
+        let start = Instant::now();
+        let mut rolling = RollingWindow::new(window);
+        rolling.record(start, 100);
+
+        // Exactly a window later, the first sample is still in.
+        rolling.record(start + window, 10);
+        assert_eq!(rolling.total(), 110, "{window:?}");
+        // Just past it, it isn't.
+        rolling.record(start + window + Duration::from_nanos(1), 1);
+        assert_eq!(rolling.total(), 11, "{window:?}");
+    }
+}
+
+#[test]
+fn evicts_everything_after_a_gap() {
+    let start = Instant::now();
+    let mut rolling = RollingWindow::new(SECOND);
+    for millis in (0..1000).step_by(100) {
+        rolling.record(start + Duration::from_millis(millis), 5);
+    }
+    assert_eq!(rolling.total(), 50);
+
+    rolling.record(start + Duration::from_secs(5), 7);
+    assert_eq!(rolling.total(), 7);
+}
+
+#[test]
+fn slides_one_sample_at_a_time() {
+    let start = Instant::now();
+    let mut rolling = RollingWindow::new(SECOND);
+    // A sample every 250 ms, so a window holds five once it is full.
+    let totals: Vec<_> = (0..8)
+        .map(|index| {
+            rolling.record(start + Duration::from_millis(index * 250), 1);
+            rolling.total()
+        })
+        .collect();
+    assert_eq!(totals, [1, 2, 3, 4, 5, 5, 5, 5]);
+}
+
+#[test]
+fn works_out_the_bitrate_over_the_whole_window() {
+    let start = Instant::now();
+    let mut one_second = RollingWindow::new(SECOND);
+    let mut ten_seconds = RollingWindow::new(TEN_SECONDS);
+    // 30 frames a second of 5 kB each, for ten seconds.
+    for frame in 0..300u64 {
+        let now = start + Duration::from_micros(frame * 1_000_000 / 30);
+        one_second.record(now, 5_000);
+        ten_seconds.record(now, 5_000);
+    }
+    // The encoder reports bits.
+    assert_eq!(one_second.per_second() * 8.0, 31.0 * 5_000.0 * 8.0);
+    assert_eq!(ten_seconds.per_second() * 8.0, 300.0 * 5_000.0 * 8.0 / 10.0);
+
+    // Only the one second window is empty after a second without anything new.
+    let later = start + Duration::from_secs(11);
+    one_second.record(later, 0);
+    ten_seconds.record(later, 0);
+    assert_eq!(one_second.per_second(), 0.0);
+    assert!(ten_seconds.per_second() > 0.0);
+}
+
+#[test]
+fn is_empty_to_begin_with() {
+    let rolling = RollingWindow::new(TEN_SECONDS);
+    assert_eq!(rolling.total(), 0);
+    assert_eq!(rolling.per_second(), 0.0);
+}
@@ -0,0 +1,122 @@
+use fjarsyn::{
+    capture_providers::shared::RotationCorrection,
+    utils::{bitmap_utils::rotate, orientation::Orientation, rect::Rect, vector2::Vector2},
+};
+
+// One byte a pixel, which is all the rotation looks at.
+fn rotated(src: &[u8], size: Vector2<i32>, orientation: Orientation) -> Vec<u8> {
+    let mut dst = vec![0; src.len()];
+    rotate(src, size.x as usize, size, &mut dst, orientation, 1);
+    dst
+}
+
+#[test]
+fn quarter_turns_move_every_pixel() {
+    // a b c
+    // d e f
+    let size = Vector2::new(3, 2);
+    let src = *b"abcdef";
+    assert_eq!(rotated(&src, size, Orientation::Upright), *b"abcdef");
+    assert_eq!(rotated(&src, size, Orientation::Rotated90), *b"daebfc");
+    assert_eq!(rotated(&src, size, Orientation::Rotated180), *b"fedcba");
+    assert_eq!(rotated(&src, size, Orientation::Rotated270), *b"cfbead");
+}
+
+#[test]
+fn whole_pixels_are_moved() {
+    let size = Vector2::new(2, 1);
+    let src = [1, 2, 3, 4, 5, 6, 7, 8];
+    let mut dst = [0; 8];
+    rotate(&src, 8, size, &mut dst, Orientation::Rotated90, 4);
+    // One pixel wide, with the first on top.
+    assert_eq!(dst, [1, 2, 3, 4, 5, 6, 7, 8]);
+    rotate(&src, 8, size, &mut dst, Orientation::Rotated180, 4);
+    assert_eq!(dst, [5, 6, 7, 8, 1, 2, 3, 4]);
+}
+
+#[test]
+fn row_padding_is_left_out() {
+    let size = Vector2::new(3, 2);
+    // Each row padded to 5 bytes, as mapped textures are.
+    let src = *b"abc..def..";
+    for orientation in Orientation::ALL {
+        let mut dst = vec![0; 6];
+        rotate(&src, 5, size, &mut dst, orientation, 1);
+        assert_eq!(dst, rotated(b"abcdef", size, orientation), "{:?}", orientation);
+    }
+}
+
+#[test]
+fn turns_add_up() {
+    let size = Vector2::new(5, 3);
+    let src: Vec<u8> = (0..15).collect();
+    let mut frame = src.clone();
+    let mut frame_size = size;
+    for _ in 0..4 {
+        frame = rotated(&frame, frame_size, Orientation::Rotated90);
+        frame_size = Orientation::Rotated90.rotated_size(frame_size);
+    }
+    assert_eq!(frame, src);
+
+    let turned = rotated(&src, size, Orientation::Rotated90);
+    assert_eq!(rotated(&turned, Vector2::new(3, 5), Orientation::Rotated270), src);
+    let half = rotated(&turned, Vector2::new(3, 5), Orientation::Rotated90);
+    assert_eq!(half, rotated(&src, size, Orientation::Rotated180));
+}
+
+#[test]
+fn rects_follow_their_pixels() {
+    let size = Vector2::new(6, 4);
+    let rect = Rect::new(1, 0, 3, 2);
+    let mask: Vec<u8> = (0..size.y)
+        .flat_map(|y| (0..size.x).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            (x >= rect.position.x && x < rect.right() && y >= rect.position.y && y < rect.bottom())
+                as u8
+        })
+        .collect();
+
+    for orientation in Orientation::ALL {
+        let turned = rotated(&mask, size, orientation);
+        let turned_size = orientation.rotated_size(size);
+        let moved = orientation.rotate_rect(rect, size);
+        for y in 0..turned_size.y {
+            for x in 0..turned_size.x {
+                let inside = x >= moved.position.x
+                    && x < moved.right()
+                    && y >= moved.position.y
+                    && y < moved.bottom();
+                let pixel = turned[(y * turned_size.x + x) as usize];
+                assert_eq!(pixel == 1, inside, "{:?} at {},{}", orientation, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn auto_only_turns_frames_in_the_shape_of_the_panel() {
+    let desktop = Vector2::new(1080, 1920);
+    let panel = Vector2::new(1920, 1080);
+    let auto = RotationCorrection::Auto;
+    assert_eq!(auto.orientation(Orientation::Rotated90, desktop, panel), Orientation::Rotated90);
+    assert_eq!(auto.orientation(Orientation::Rotated270, desktop, panel), Orientation::Rotated270);
+    // The driver already turned it.
+    assert_eq!(auto.orientation(Orientation::Rotated90, desktop, desktop), Orientation::Upright);
+    // Nothing to tell an upside down frame by.
+    assert_eq!(auto.orientation(Orientation::Rotated180, panel, panel), Orientation::Upright);
+    // A size of neither, e.g. while the display is changing.
+    let other = Vector2::new(800, 600);
+    assert_eq!(auto.orientation(Orientation::Rotated90, desktop, other), Orientation::Upright);
+    assert_eq!(auto.orientation(Orientation::Upright, panel, panel), Orientation::Upright);
+}
+
+#[test]
+fn always_and_off_ignore_the_size() {
+    let desktop = Vector2::new(1080, 1920);
+    for rotation in Orientation::ALL {
+        let always = RotationCorrection::Always.orientation(rotation, desktop, desktop);
+        assert_eq!(always, rotation);
+        let off = RotationCorrection::Off.orientation(rotation, desktop, Vector2::new(1920, 1080));
+        assert_eq!(off, Orientation::Upright);
+    }
+}
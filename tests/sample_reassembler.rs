@@ -0,0 +1,124 @@
+use fjarsyn::{media::nal::NalCodec, networking::webrtc::SampleReassembler};
+use webrtc::rtp::{codecs::h264::H264Packet, header::Header, packet::Packet};
+
+const CLOCK_RATE: u32 = 90_000;
+const FRAME_TICKS: u32 = CLOCK_RATE / 30;
+
+// A frame in a single packet, holding one NAL unit that tells which frame it is.
+fn frame(index: u16, keyframe: bool) -> Packet {
+    let header_byte = if keyframe { 0x65 } else { 0x41 };
+    Packet {
+        header: Header {
+            version: 2,
+            marker: true,
+            sequence_number: index,
+            timestamp: u32::from(index) * FRAME_TICKS,
+            ..Default::default()
+        },
+        // The depacketizer takes anything shorter for a broken packet.
+        payload: vec![header_byte, 0, 0, index as u8].into(),
+    }
+}
+
+fn pop_all(reassembler: &mut SampleReassembler<H264Packet>, out: &mut Vec<u8>) {
+    while let Some(sample) = reassembler.pop() {
+        // The depacketizer puts a start code in front of the NAL unit.
+        out.push(*sample.data.last().unwrap());
+    }
+}
+
+#[test]
+fn reassembles_frames_in_order() {
+    let mut reassembler =
+        SampleReassembler::<H264Packet>::new(10, CLOCK_RATE, Some(NalCodec::H264));
+    let mut popped = Vec::new();
+    for index in 0..5 {
+        reassembler.push(frame(index, index == 0));
+        pop_all(&mut reassembler, &mut popped);
+    }
+
+    // The last frame isn't known to be complete until the next one starts.
+    assert_eq!(popped, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn switches_at_the_next_keyframe() {
+    let mut reassembler =
+        SampleReassembler::<H264Packet>::new(10, CLOCK_RATE, Some(NalCodec::H264));
+    let mut popped = Vec::new();
+    for index in 0..3 {
+        reassembler.push(frame(index, index == 0));
+        pop_all(&mut reassembler, &mut popped);
+    }
+
+    reassembler.set_max_late(50);
+    for index in 3..6 {
+        reassembler.push(frame(index, false));
+        pop_all(&mut reassembler, &mut popped);
+    }
+    assert_eq!(reassembler.max_late(), 10);
+
+    for index in 6..12 {
+        reassembler.push(frame(index, index == 6));
+        pop_all(&mut reassembler, &mut popped);
+    }
+    assert_eq!(reassembler.max_late(), 50);
+    // Nothing lost or repeated on the way.
+    assert_eq!(popped, (0..11).collect::<Vec<_>>());
+}
+
+#[test]
+fn switches_right_away_without_keyframes_to_wait_for() {
+    let mut reassembler = SampleReassembler::<H264Packet>::new(10, CLOCK_RATE, None);
+    let mut popped = Vec::new();
+    for index in 0..3 {
+        reassembler.push(frame(index, false));
+        pop_all(&mut reassembler, &mut popped);
+    }
+
+    reassembler.set_max_late(50);
+    for index in 3..6 {
+        reassembler.push(frame(index, false));
+        pop_all(&mut reassembler, &mut popped);
+    }
+    assert_eq!(reassembler.max_late(), 50);
+    assert_eq!(popped, (0..5).collect::<Vec<_>>());
+}
+
+#[test]
+fn setting_it_back_cancels_the_switch() {
+    let mut reassembler =
+        SampleReassembler::<H264Packet>::new(10, CLOCK_RATE, Some(NalCodec::H264));
+    let mut popped = Vec::new();
+    reassembler.push(frame(0, true));
+
+    reassembler.set_max_late(50);
+    reassembler.set_max_late(10);
+    for index in 1..4 {
+        reassembler.push(frame(index, index == 2));
+        pop_all(&mut reassembler, &mut popped);
+    }
+    assert_eq!(reassembler.max_late(), 10);
+    assert_eq!(popped, vec![0, 1, 2]);
+}
+
+#[test]
+fn counts_what_it_reassembles() {
+    let mut reassembler = SampleReassembler::<H264Packet>::new(4, CLOCK_RATE, Some(NalCodec::H264));
+    let mut popped = Vec::new();
+    for index in 0..10 {
+        let mut packet = frame(index, index == 0);
+        // Only the last fragment of frame 3 comes, so it can't be put together.
+        if index == 3 {
+            packet.payload = vec![0x7C, 0x41, 0, 3].into();
+        }
+        reassembler.push(packet);
+        pop_all(&mut reassembler, &mut popped);
+    }
+
+    let counts = reassembler.counts();
+    assert_eq!(counts.packets, 10);
+    assert_eq!(counts.samples, popped.len() as u64);
+    assert_eq!(counts.dropped_packets, 1);
+    assert!(!popped.contains(&3));
+}
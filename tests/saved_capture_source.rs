@@ -0,0 +1,92 @@
+//! Finding the source shared last among those available now, by the id it was saved with.
+
+use fjarsyn::{
+    capture_providers::shared::{CaptureSourceId, SavedCaptureSource, find_saved_source},
+    config::Config,
+};
+
+// The sources available now, as the picker lists them.
+fn available() -> Vec<(CaptureSourceId, &'static str)> {
+    vec![
+        (CaptureSourceId::monitor(r"\\.\DISPLAY1"), "Display 1"),
+        (CaptureSourceId::monitor(r"\\.\DISPLAY2"), "Display 2"),
+        (CaptureSourceId::window("notepad.exe", "notes.txt - Notepad"), "Notepad"),
+        (
+            CaptureSourceId::window(
+                "firefox.exe",
+                "Release notes for the next version — Mozilla Firefox",
+            ),
+            "Firefox",
+        ),
+    ]
+}
+
+fn saved(name: &str, id: CaptureSourceId) -> Config {
+    Config {
+        last_capture_source: Some(SavedCaptureSource { name: name.to_owned(), id }),
+        ..Config::default()
+    }
+}
+
+fn find(config: &Config) -> Option<&'static str> {
+    find_saved_source(&config.last_capture_source.as_ref()?.id, available())
+}
+
+#[test]
+fn finds_a_monitor_by_its_device_name() {
+    let config = saved("Display 2", CaptureSourceId::monitor(r"\\.\DISPLAY2"));
+    assert_eq!(find(&config), Some("Display 2"));
+    // Windows doesn't keep to one case.
+    let config = saved("Display 2", CaptureSourceId::monitor(r"\\.\display2"));
+    assert_eq!(find(&config), Some("Display 2"));
+}
+
+#[test]
+fn finds_a_window_by_its_process_and_the_start_of_its_title() {
+    // The title has moved on to another page since, but it starts the same.
+    let id = CaptureSourceId::window(
+        "Firefox.exe",
+        "Release notes for the next version — Mozilla Firefox",
+    );
+    assert_eq!(
+        id,
+        CaptureSourceId::Window {
+            process_name: "Firefox.exe".to_owned(),
+            title_prefix: "Release notes for the ne".to_owned(),
+        }
+    );
+    assert!(id.matches(&CaptureSourceId::window(
+        "firefox.exe",
+        "Release notes for the new UI — Mozilla Firefox"
+    )));
+    assert_eq!(find(&saved("Firefox", id)), Some("Firefox"));
+
+    // The same title in another process isn't the same window.
+    let id = CaptureSourceId::window("code.exe", "notes.txt - Notepad");
+    assert_eq!(find(&saved("Notepad", id)), None);
+    // Nor is a monitor a window.
+    assert!(
+        !CaptureSourceId::monitor("notepad.exe")
+            .matches(&CaptureSourceId::window("notepad.exe", ""))
+    );
+}
+
+#[test]
+fn finds_nothing_when_the_source_is_gone() {
+    // Unplugged, and closed.
+    assert_eq!(find(&saved("Display 3", CaptureSourceId::monitor(r"\\.\DISPLAY3"))), None);
+    let id = CaptureSourceId::window("notepad.exe", "todo.txt - Notepad");
+    assert_eq!(find(&saved("Notepad", id)), None);
+    // Nothing was shared yet.
+    assert_eq!(find(&Config::default()), None);
+}
+
+#[test]
+fn finds_the_first_of_several_matches() {
+    let id = CaptureSourceId::window("notepad.exe", "notes");
+    let candidates = [
+        (CaptureSourceId::window("notepad.exe", "notes.txt - Notepad"), 1),
+        (CaptureSourceId::window("notepad.exe", "notes.md - Notepad"), 2),
+    ];
+    assert_eq!(find_saved_source(&id, candidates), Some(1));
+}
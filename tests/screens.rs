@@ -0,0 +1,350 @@
+//! The screens, driven through their messages with a context that has none of the platform behind it.
+
+use fjarsyn::{
+    config::Config,
+    networking::webrtc::{PeerId, WebRTCEvent},
+    ui::{
+        app::ActiveScreen,
+        call_phase::{CallPhase, EndReason},
+        message::{Message, Route},
+        notification::NotificationKind,
+        screens::{
+            Screen,
+            call::{CallMessage, CallScreen},
+            home::{HomeMessage, HomeScreen},
+            settings::{ConfigField, ConfigValue, SettingsMessage, SettingsScreen},
+        },
+        state::{AppContext, CaptureProviderState},
+        test_support::{mock_context, notifications},
+    },
+};
+use fjarsyn_shared::{CallMode, ControlMessage, RecordingState};
+
+fn home() -> (HomeScreen, AppContext) {
+    let mut ctx = mock_context(Config { onboarding_done: true, ..Config::default() });
+    (HomeScreen::new(&mut ctx), ctx)
+}
+
+fn settings() -> (SettingsScreen, AppContext) {
+    let ctx = mock_context(Config::default());
+    (SettingsScreen::new(ctx.config.clone()), ctx)
+}
+
+fn call_with(peer: &str) -> (CallScreen, AppContext) {
+    let mut ctx = mock_context(Config::default());
+    ctx.call.target_id = Some(peer.to_owned());
+    (CallScreen::new(&mut ctx), ctx)
+}
+
+fn update_setting(
+    screen: &mut SettingsScreen,
+    ctx: &mut AppContext,
+    field: ConfigField,
+    value: &str,
+) {
+    let _ = screen.update(
+        ctx,
+        Message::Settings(SettingsMessage::ConfigUpdate(
+            field,
+            ConfigValue::String(value.to_owned()),
+        )),
+    );
+}
+
+fn event(screen: &mut CallScreen, ctx: &mut AppContext, event: WebRTCEvent) -> CallPhase {
+    let _ = screen.update(ctx, Message::WebRTCEvent(PeerId::default(), event));
+    screen.phase()
+}
+
+#[test]
+fn call_button_waits_for_someone_to_call() {
+    let (mut screen, mut ctx) = home();
+    assert_eq!(HomeScreen::call_target(&ctx), None);
+
+    for (entered, target) in [("", None), ("   ", None), (" 123-456 ", Some("123-456"))] {
+        let _ = screen
+            .update(&mut ctx, Message::Home(HomeMessage::TargetIdChanged(entered.to_owned())));
+        assert_eq!(HomeScreen::call_target(&ctx), target, "for {:?}", entered);
+    }
+}
+
+#[test]
+fn calling_before_connecting_tells_the_user() {
+    let (mut screen, mut ctx) = home();
+    let _ = screen.update(&mut ctx, Message::Home(HomeMessage::StartCall("peer".to_owned())));
+    assert_eq!(
+        notifications(&ctx),
+        [(NotificationKind::Error, "Not connected to the signaling server yet.".to_owned())]
+    );
+}
+
+#[test]
+fn call_link_waits_for_confirmation() {
+    let (mut screen, mut ctx) = home();
+    ctx.call.open_call_link("7FQ2-K9PL".to_owned());
+    assert_eq!(HomeScreen::call_target(&ctx), Some("7FQ2-K9PL"));
+    assert_eq!(ctx.call.call_link.as_deref(), Some("7FQ2-K9PL"));
+
+    // Cancelling leaves the peer filled in, for calling later.
+    let _ = screen.update(&mut ctx, Message::Home(HomeMessage::DismissCallLink));
+    assert_eq!(ctx.call.call_link, None);
+    assert_eq!(HomeScreen::call_target(&ctx), Some("7FQ2-K9PL"));
+
+    ctx.call.open_call_link("7FQ2-K9PL".to_owned());
+    let _ = screen.update(&mut ctx, Message::Home(HomeMessage::ConfirmCallLink));
+    assert_eq!(ctx.call.call_link, None);
+}
+
+#[test]
+fn passphrase_is_kept_for_the_next_connection() {
+    let (mut screen, mut ctx) = home();
+    let _ =
+        screen.update(&mut ctx, Message::Home(HomeMessage::PassphraseChanged("s3cret".to_owned())));
+    assert_eq!(ctx.call.signaling_passphrase, "s3cret");
+}
+
+#[test]
+fn settings_ignore_what_doesnt_parse() {
+    let (mut screen, mut ctx) = settings();
+    let bitrate = ctx.config.bitrate;
+
+    update_setting(&mut screen, &mut ctx, ConfigField::Bitrate, "fast");
+    update_setting(&mut screen, &mut ctx, ConfigField::Bitrate, "-5");
+    assert_eq!(screen.pending_config.as_ref().unwrap().bitrate, bitrate);
+
+    update_setting(&mut screen, &mut ctx, ConfigField::Bitrate, "2500000");
+    assert_eq!(screen.pending_config.as_ref().unwrap().bitrate, 2_500_000);
+    // Nothing takes effect until saved.
+    assert_eq!(ctx.config.bitrate, bitrate);
+}
+
+#[test]
+fn bandwidth_cap_is_cleared_when_left_empty() {
+    let (mut screen, mut ctx) = settings();
+    update_setting(&mut screen, &mut ctx, ConfigField::BandwidthCap, "1500000");
+    assert_eq!(screen.pending_config.as_ref().unwrap().bandwidth_cap, Some(1_500_000));
+
+    update_setting(&mut screen, &mut ctx, ConfigField::BandwidthCap, "lots");
+    assert_eq!(screen.pending_config.as_ref().unwrap().bandwidth_cap, Some(1_500_000));
+
+    update_setting(&mut screen, &mut ctx, ConfigField::BandwidthCap, " ");
+    assert_eq!(screen.pending_config.as_ref().unwrap().bandwidth_cap, None);
+}
+
+#[test]
+fn saving_settings_applies_and_stores_them() {
+    let (mut screen, mut ctx) = settings();
+    update_setting(&mut screen, &mut ctx, ConfigField::ServerUrl, "wss://example.com");
+    let _ = screen.update(&mut ctx, Message::Settings(SettingsMessage::SaveConfig));
+
+    assert_eq!(ctx.config.server_url, "wss://example.com");
+    assert_eq!(ctx.store.saves(), 1);
+    assert_eq!(notifications(&ctx), [(NotificationKind::Success, "Config saved!".to_owned())]);
+}
+
+#[test]
+fn call_rings_connects_and_reconnects() {
+    let (mut screen, mut ctx) = call_with("peer");
+    assert!(matches!(screen.phase(), CallPhase::Ringing { .. }));
+    assert!(matches!(
+        event(&mut screen, &mut ctx, WebRTCEvent::Answered(CallMode::ShareAndView)),
+        CallPhase::Connecting { .. }
+    ));
+
+    let CallPhase::Connected { since } = event(&mut screen, &mut ctx, WebRTCEvent::Connected)
+    else {
+        panic!("Should be connected");
+    };
+    assert!(matches!(
+        event(&mut screen, &mut ctx, WebRTCEvent::Interrupted),
+        CallPhase::Reconnecting { connected_since, .. } if connected_since == since
+    ));
+    // Carries on as the same call.
+    assert_eq!(
+        event(&mut screen, &mut ctx, WebRTCEvent::Connected),
+        CallPhase::Connected { since }
+    );
+}
+
+#[test]
+fn call_the_peer_left_goes_to_the_history() {
+    let (mut screen, mut ctx) = call_with("peer");
+    event(&mut screen, &mut ctx, WebRTCEvent::Connected);
+    assert_eq!(
+        event(&mut screen, &mut ctx, WebRTCEvent::Disconnected),
+        CallPhase::Ended(EndReason::PeerLeft)
+    );
+
+    assert_eq!(ctx.call_history.entries().len(), 1);
+    assert_eq!(ctx.call_history.entries()[0].peer.as_deref(), Some("peer"));
+    assert!(ctx.last_call_summary.is_some());
+    assert_eq!(ctx.store.saves(), 1);
+
+    // Nothing more happens to a call that ended.
+    assert_eq!(
+        event(&mut screen, &mut ctx, WebRTCEvent::Connected),
+        CallPhase::Ended(EndReason::PeerLeft)
+    );
+}
+
+#[test]
+fn unanswered_call_leaves_no_history() {
+    let (mut screen, mut ctx) = call_with("peer");
+    assert_eq!(
+        event(&mut screen, &mut ctx, WebRTCEvent::Disconnected),
+        CallPhase::Ended(EndReason::Unreachable)
+    );
+    assert!(ctx.call_history.entries().is_empty());
+    assert_eq!(ctx.store.saves(), 0);
+}
+
+#[test]
+fn ending_the_call_closes_the_popout() {
+    let (mut screen, mut ctx) = call_with("peer");
+    ctx.windows.popout_id = Some(iced::window::Id::unique());
+    event(&mut screen, &mut ctx, WebRTCEvent::Connected);
+    event(&mut screen, &mut ctx, WebRTCEvent::Disconnected);
+    assert_eq!(ctx.windows.popout_id, None);
+}
+
+fn peer_recording(screen: &mut CallScreen, ctx: &mut AppContext, active: bool) {
+    let state = ControlMessage::RecordingState(RecordingState { active });
+    event(screen, ctx, WebRTCEvent::Control(state));
+}
+
+#[test]
+fn peer_recording_is_shown_until_it_stops() {
+    let (mut screen, mut ctx) = call_with("peer");
+    event(&mut screen, &mut ctx, WebRTCEvent::Connected);
+
+    peer_recording(&mut screen, &mut ctx, true);
+    assert!(screen.remote_recording);
+    // Saying so again doesn't notify twice.
+    peer_recording(&mut screen, &mut ctx, true);
+    assert_eq!(
+        notifications(&ctx),
+        [(NotificationKind::Info, "The other side started recording the call.".to_owned())]
+    );
+
+    peer_recording(&mut screen, &mut ctx, false);
+    assert!(!screen.remote_recording);
+    // Replaces the one about starting.
+    assert_eq!(
+        notifications(&ctx),
+        [(NotificationKind::Info, "The other side stopped recording the call.".to_owned())]
+    );
+
+    event(&mut screen, &mut ctx, WebRTCEvent::Disconnected);
+    assert!(ctx.call_history.entries()[0].was_recorded);
+}
+
+#[test]
+fn call_recorded_by_neither_side_says_so() {
+    let (mut screen, mut ctx) = call_with("peer");
+    event(&mut screen, &mut ctx, WebRTCEvent::Connected);
+    event(&mut screen, &mut ctx, WebRTCEvent::Disconnected);
+    assert!(!ctx.call_history.entries()[0].was_recorded);
+}
+
+#[test]
+fn own_recording_goes_in_the_summary() {
+    let (mut screen, mut ctx) = call_with("peer");
+    event(&mut screen, &mut ctx, WebRTCEvent::Connected);
+    let _ = screen.update(&mut ctx, Message::Call(CallMessage::RecordingChanged(true)));
+    let _ = screen.update(&mut ctx, Message::Call(CallMessage::RecordingChanged(false)));
+    event(&mut screen, &mut ctx, WebRTCEvent::Disconnected);
+    assert!(ctx.call_history.entries()[0].was_recorded);
+}
+
+#[test]
+fn view_only_call_never_reaches_for_the_capture() {
+    let (mut screen, mut ctx) = home();
+    let _ = screen.update(&mut ctx, Message::Home(HomeMessage::ViewOnlyToggled(true)));
+    assert_eq!(ctx.call.mode, CallMode::ViewOnly);
+
+    ctx.call.target_id = Some("peer".to_owned());
+    let mut call = CallScreen::new(&mut ctx);
+    assert_eq!(call.mode(), CallMode::ViewOnly);
+    let _ = call.update(&mut ctx, Message::Call(CallMessage::OpenSourcePicker));
+    assert_eq!(
+        notifications(&ctx),
+        [(
+            NotificationKind::Info,
+            "You are only viewing in this call, so your screen can't be shared.".to_owned()
+        )]
+    );
+    assert!(matches!(ctx.capture, CaptureProviderState::NotStarted));
+}
+
+#[test]
+fn sharing_call_starts_the_capture_on_the_first_share() {
+    let (mut screen, mut ctx) = call_with("peer");
+    assert_eq!(screen.mode(), CallMode::ShareAndView);
+    assert!(matches!(ctx.capture, CaptureProviderState::NotStarted));
+
+    let _ = screen.update(&mut ctx, Message::Call(CallMessage::OpenSourcePicker));
+    assert!(matches!(ctx.capture, CaptureProviderState::Pending));
+    // The share goes on once the provider is ready, so there is nothing to tell the user.
+    assert_eq!(notifications(&ctx), []);
+}
+
+#[test]
+fn sharing_call_says_when_there_is_no_capture() {
+    let (mut screen, mut ctx) = call_with("peer");
+    ctx.capture = CaptureProviderState::Unavailable;
+    let _ = screen.update(&mut ctx, Message::Call(CallMessage::OpenSourcePicker));
+    assert_eq!(
+        notifications(&ctx),
+        [(
+            NotificationKind::Error,
+            "Screen sharing is unavailable because no graphics device could be initialized."
+                .to_owned()
+        )]
+    );
+}
+
+// Every route. The match doesn't compile once there is another, so it can't be left out here.
+fn routes() -> [Route; 4] {
+    let _ = |route: Route| match route {
+        Route::Home | Route::Call | Route::Settings | Route::Diagnostics => (),
+    };
+    [Route::Home, Route::Call, Route::Settings, Route::Diagnostics]
+}
+
+#[test]
+fn every_route_leads_to_its_screen() {
+    for route in routes() {
+        let mut ctx = mock_context(Config::default());
+        let screen = ActiveScreen::from_route(&mut ctx, route);
+        let expected = match route {
+            Route::Home => matches!(screen, ActiveScreen::Home(_)),
+            Route::Call => matches!(screen, ActiveScreen::Call(_)),
+            Route::Settings => matches!(screen, ActiveScreen::Settings(_)),
+            Route::Diagnostics => matches!(screen, ActiveScreen::Diagnostics(_)),
+        };
+        assert!(expected, "{route:?} led to {screen:?}");
+        assert_eq!(screen.route(), Some(route));
+    }
+}
+
+#[test]
+fn call_route_takes_the_call_screen_from_the_back_queue() {
+    let (mut screen, mut ctx) = call_with("peer");
+    event(&mut screen, &mut ctx, WebRTCEvent::Connected);
+    let home = ActiveScreen::Home(HomeScreen::new(&mut ctx));
+    ctx.back_queue.push_back(home);
+    ctx.back_queue.push_back(ActiveScreen::Call(Box::new(screen)));
+
+    let ActiveScreen::Call(call) = ActiveScreen::from_route(&mut ctx, Route::Call) else {
+        panic!("the call route didn't lead to the call screen");
+    };
+    // The same call, not a new one.
+    assert!(matches!(call.phase(), CallPhase::Connected { .. }));
+    assert_eq!(ctx.back_queue.len(), 1);
+    assert!(matches!(ctx.back_queue[0], ActiveScreen::Home(_)));
+    // Without one queued, there is a new one.
+    let ActiveScreen::Call(call) = ActiveScreen::from_route(&mut ctx, Route::Call) else {
+        panic!("the call route didn't lead to the call screen");
+    };
+    assert!(!matches!(call.phase(), CallPhase::Connected { .. }));
+}
@@ -0,0 +1,100 @@
+//! Calls whose signaling is sealed, over connections that only reach each other.
+
+use std::time::Duration;
+
+use fjarsyn::{
+    networking::{
+        signaling::LOOPBACK_IDS,
+        webrtc::{WebRTC, WebRTCEvent},
+    },
+    session::{CallChannels, CallOptions},
+};
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn loopback() -> [(WebRTC, CallChannels); 2] {
+    let options = CallOptions::default();
+    let [(caller, caller_receivers), (callee, callee_receivers)] = WebRTC::loopback(
+        options.max_depacket_latency,
+        options.auto_depacket_latency,
+        options.receive_buffer,
+        options.transcoding_type,
+    )
+    .await
+    .unwrap();
+    [(caller, caller_receivers.into()), (callee, callee_receivers.into())]
+}
+
+async fn next_event(channels: &CallChannels) -> WebRTCEvent {
+    let events = channels.events();
+    let event = tokio::time::timeout(RECV_TIMEOUT, events.lock().await.recv()).await.unwrap();
+    event.expect("the events ended").event
+}
+
+// Answers the call, and waits for both ends to connect.
+async fn connect(
+    caller: &WebRTC,
+    caller_channels: &CallChannels,
+    callee: &WebRTC,
+    callee_channels: &CallChannels,
+) {
+    caller.create_offer(LOOPBACK_IDS[1].to_owned()).await.unwrap();
+    let event = next_event(callee_channels).await;
+    assert!(matches!(event, WebRTCEvent::IncomingCall(..)), "unexpected event: {:?}", event);
+    callee.accept_call().await.unwrap();
+    for channels in [caller_channels, callee_channels] {
+        loop {
+            match next_event(channels).await {
+                WebRTCEvent::Connected => break,
+                WebRTCEvent::Disconnected | WebRTCEvent::SignalingRejected(_) => {
+                    panic!("the call didn't connect")
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn connects_with_a_shared_passphrase() {
+    let [(caller, caller_channels), (callee, callee_channels)] = loopback().await;
+    caller.set_signaling_passphrase("correct horse battery staple");
+    callee.set_signaling_passphrase("correct horse battery staple");
+
+    connect(&caller, &caller_channels, &callee, &callee_channels).await;
+    assert!(caller.is_signaling_sealed() && callee.is_signaling_sealed());
+
+    caller.shutdown().await.unwrap();
+    callee.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn connects_with_a_key_exchange() {
+    let [(caller, caller_channels), (callee, callee_channels)] = loopback().await;
+    caller.set_signaling_key_exchange(true);
+    callee.set_signaling_key_exchange(true);
+
+    connect(&caller, &caller_channels, &callee, &callee_channels).await;
+    assert!(caller.is_signaling_sealed() && callee.is_signaling_sealed());
+
+    caller.shutdown().await.unwrap();
+    callee.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_peer_without_the_key_exchange_is_told() {
+    let [(caller, _caller_channels), (callee, callee_channels)] = loopback().await;
+    caller.set_signaling_key_exchange(true);
+
+    let offered = tokio::spawn({
+        let caller = caller.clone();
+        async move { caller.create_offer(LOOPBACK_IDS[1].to_owned()).await }
+    });
+    let event = next_event(&callee_channels).await;
+    assert!(matches!(event, WebRTCEvent::SignalingRejected(_)), "unexpected event: {:?}", event);
+    // Nothing answers the key, so no offer goes out.
+    assert!(offered.await.unwrap().is_err());
+
+    caller.shutdown().await.unwrap();
+    callee.shutdown().await.unwrap();
+}
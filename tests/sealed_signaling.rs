@@ -0,0 +1,199 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use fjarsyn::networking::{
+    sealed_signaling::{KeyExchange, SignalingSeal},
+    sealed_signaling_error::SealedSignalingError,
+};
+use fjarsyn_shared::{PROTOCOL_VERSION, SignalingMessage, SignalingType};
+
+const PASSPHRASE: &str = "correct horse battery staple";
+const SALT: [u8; SignalingSeal::SALT_LEN] = *b"fjarsyn-testsalt";
+const PEER: &str = "9a0b";
+
+// Sealed by an earlier build, which later ones have to keep opening.
+const SEALED_ANSWER: &str = "AWZqYXJzeW4tdGVzdHNhbHT4R2HRP7gW7PNw91F+NoPducKMM0vuckq/kto3kgnOyv0=";
+
+// The secrets of both ends of a key exchange, and what one of them sealed with the key they agreed on.
+const CALLER_SECRET: [u8; 32] = [0x11; 32];
+const CALLEE_SECRET: [u8; 32] = [0x22; 32];
+const EXCHANGED_OFFER: &str =
+    "Adz43H+b0peyRhj/1inYzIla1Duh/7AZR4GZIfWHlRziMMdS3mQ6d6HWKN1IHpsoIEw=";
+
+fn message(sig_type: SignalingType, data: &str) -> SignalingMessage {
+    SignalingMessage {
+        to: "3f1c".to_owned(),
+        from: PEER.to_owned(),
+        sig_type,
+        data: data.to_owned(),
+        protocol_version: PROTOCOL_VERSION,
+        sealed: false,
+        mode: None,
+    }
+}
+
+#[tokio::test]
+async fn opens_what_it_sealed() {
+    let sender = SignalingSeal::new(PASSPHRASE);
+    let receiver = SignalingSeal::new(PASSPHRASE);
+    let sealed = sender.seal(&SignalingType::Offer, "v=0\r\n").await.unwrap();
+
+    assert_ne!(sealed, "v=0\r\n");
+    assert_eq!(receiver.open(PEER, &SignalingType::Offer, &sealed).await.unwrap(), "v=0\r\n");
+}
+
+#[tokio::test]
+async fn opens_the_known_vector() {
+    let seal = SignalingSeal::new(PASSPHRASE);
+    assert_eq!(seal.open(PEER, &SignalingType::Answer, SEALED_ANSWER).await.unwrap(), "v=0\r\n");
+}
+
+#[tokio::test]
+async fn seals_the_same_data_differently_each_time() {
+    let seal = SignalingSeal::with_salt(PASSPHRASE, SALT);
+    let first = seal.seal(&SignalingType::Candidate, "candidate").await.unwrap();
+    let second = seal.seal(&SignalingType::Candidate, "candidate").await.unwrap();
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn rejects_another_passphrase() {
+    let sealed =
+        SignalingSeal::with_salt(PASSPHRASE, SALT).seal(&SignalingType::Offer, "sdp").await;
+    let other = SignalingSeal::with_salt("wrong passphrase", SALT);
+    assert!(matches!(
+        other.open(PEER, &SignalingType::Offer, &sealed.unwrap()).await,
+        Err(SealedSignalingError::OpenFailed)
+    ));
+}
+
+#[tokio::test]
+async fn rejects_tampered_data() {
+    let seal = SignalingSeal::with_salt(PASSPHRASE, SALT);
+    let sealed = seal.seal(&SignalingType::Offer, "sdp").await.unwrap();
+    let mut tampered = STANDARD.decode(sealed).unwrap();
+    *tampered.last_mut().unwrap() ^= 1;
+    let tampered = STANDARD.encode(tampered);
+
+    assert!(matches!(
+        seal.open(PEER, &SignalingType::Offer, &tampered).await,
+        Err(SealedSignalingError::OpenFailed)
+    ));
+    assert!(matches!(
+        seal.open(PEER, &SignalingType::Offer, "not base64!").await,
+        Err(SealedSignalingError::Malformed)
+    ));
+}
+
+#[tokio::test]
+async fn rejects_data_passed_off_as_another_type() {
+    let seal = SignalingSeal::with_salt(PASSPHRASE, SALT);
+    let sealed = seal.seal(&SignalingType::Candidate, "candidate").await.unwrap();
+    assert!(matches!(
+        seal.open(PEER, &SignalingType::Offer, &sealed).await,
+        Err(SealedSignalingError::OpenFailed)
+    ));
+}
+
+#[tokio::test]
+async fn caps_the_salts_of_each_peer() {
+    let receiver = SignalingSeal::new(PASSPHRASE);
+    let mut senders = (0..=SignalingSeal::MAX_SALTS_PER_PEER as u8)
+        .map(|n| SignalingSeal::with_salt(PASSPHRASE, [n; SignalingSeal::SALT_LEN]));
+    for sender in senders.by_ref().take(SignalingSeal::MAX_SALTS_PER_PEER) {
+        let sealed = sender.seal(&SignalingType::Offer, "sdp").await.unwrap();
+        assert_eq!(receiver.open(PEER, &SignalingType::Offer, &sealed).await.unwrap(), "sdp");
+    }
+
+    let one_too_many = senders.next().unwrap().seal(&SignalingType::Offer, "sdp").await.unwrap();
+    assert!(matches!(
+        receiver.open(PEER, &SignalingType::Offer, &one_too_many).await,
+        Err(SealedSignalingError::TooManySalts)
+    ));
+    // Another peer has salts of its own, and the salts already known still open.
+    assert_eq!(receiver.open("5e7d", &SignalingType::Offer, &one_too_many).await.unwrap(), "sdp");
+    let known = SignalingSeal::with_salt(PASSPHRASE, [0; SignalingSeal::SALT_LEN]);
+    let sealed = known.seal(&SignalingType::Offer, "sdp").await.unwrap();
+    assert_eq!(receiver.open(PEER, &SignalingType::Offer, &sealed).await.unwrap(), "sdp");
+}
+
+#[tokio::test]
+async fn seals_only_what_describes_the_connection() {
+    let seal = SignalingSeal::with_salt(PASSPHRASE, SALT);
+    let offer =
+        SignalingSeal::seal_message(Some(&seal), message(SignalingType::Offer, "sdp")).await;
+    let offer = offer.unwrap();
+    assert!(offer.sealed);
+    assert_ne!(offer.data, "sdp");
+
+    let bye = SignalingSeal::seal_message(Some(&seal), message(SignalingType::Bye, "")).await;
+    assert!(!bye.unwrap().sealed);
+
+    let opened = SignalingSeal::open_message(Some(&seal), offer).await.unwrap();
+    assert_eq!(opened.data, "sdp");
+}
+
+#[tokio::test]
+async fn rejects_peers_that_seal_differently() {
+    let seal = SignalingSeal::with_salt(PASSPHRASE, SALT);
+    let sealed = SignalingSeal::seal_message(Some(&seal), message(SignalingType::Answer, "sdp"));
+    assert!(matches!(
+        SignalingSeal::open_message(None, sealed.await.unwrap()).await,
+        Err(SealedSignalingError::PeerRequiresEncryption)
+    ));
+
+    // A server stripping the seal can't make the call go without.
+    assert!(matches!(
+        SignalingSeal::open_message(Some(&seal), message(SignalingType::Answer, "sdp")).await,
+        Err(SealedSignalingError::PeerNotEncrypted)
+    ));
+
+    let plain = SignalingSeal::open_message(None, message(SignalingType::Answer, "sdp")).await;
+    assert_eq!(plain.unwrap().data, "sdp");
+}
+
+#[tokio::test]
+async fn both_ends_of_a_key_exchange_agree() {
+    let caller = KeyExchange::new();
+    let callee = KeyExchange::new();
+    let caller_seal = caller.agree(&callee.public_key()).unwrap();
+    let callee_seal = callee.agree(&caller.public_key()).unwrap();
+    assert_eq!(caller_seal.passphrase(), None);
+
+    let sealed = caller_seal.seal(&SignalingType::Offer, "v=0\r\n").await.unwrap();
+    assert_eq!(callee_seal.open(PEER, &SignalingType::Offer, &sealed).await.unwrap(), "v=0\r\n");
+    let sealed = callee_seal.seal(&SignalingType::Answer, "v=0\r\n").await.unwrap();
+    assert_eq!(caller_seal.open(PEER, &SignalingType::Answer, &sealed).await.unwrap(), "v=0\r\n");
+}
+
+#[tokio::test]
+async fn opens_the_known_key_exchange_vector() {
+    let caller = KeyExchange::with_secret(CALLER_SECRET);
+    let callee = KeyExchange::with_secret(CALLEE_SECRET);
+    let seal = callee.agree(&caller.public_key()).unwrap();
+    assert_eq!(seal.open(PEER, &SignalingType::Offer, EXCHANGED_OFFER).await.unwrap(), "v=0\r\n");
+}
+
+#[tokio::test]
+async fn a_third_key_exchange_cant_open_it() {
+    let caller = KeyExchange::new();
+    let callee = KeyExchange::new();
+    let relay = KeyExchange::new();
+    let caller_seal = caller.agree(&callee.public_key()).unwrap();
+    let sealed = caller_seal.seal(&SignalingType::Offer, "sdp").await.unwrap();
+    let relay_seal = relay.agree(&caller.public_key()).unwrap();
+    assert!(matches!(
+        relay_seal.open(PEER, &SignalingType::Offer, &sealed).await,
+        Err(SealedSignalingError::OpenFailed)
+    ));
+}
+
+#[test]
+fn rejects_invalid_public_keys() {
+    let exchange = KeyExchange::new();
+    for invalid in ["not base64!", "c2hvcnQ=", &STANDARD.encode([0; KeyExchange::PUBLIC_KEY_LEN])] {
+        assert!(
+            matches!(exchange.agree(invalid), Err(SealedSignalingError::InvalidPublicKey)),
+            "{} was taken",
+            invalid
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use fjarsyn::{
+    capture_providers::shared::MonitorGeometry, ui::sharing_indicator::SharingIndicator,
+    utils::rect::Rect,
+};
+use iced::window;
+
+#[test]
+fn sits_at_the_top_center_of_the_monitor() {
+    let monitor = MonitorGeometry { bounds: Rect::new(0, 0, 1920, 1080), scale_factor: 1.0 };
+    let position = SharingIndicator::position(&monitor);
+    assert_eq!(position.x, (1920.0 - SharingIndicator::SIZE.width) / 2.0);
+    assert!(position.y >= 0.0 && position.y < 20.0, "{:?}", position);
+}
+
+#[test]
+fn places_itself_on_a_secondary_monitor_in_logical_pixels() {
+    // Left of the primary one, at 150%.
+    let monitor = MonitorGeometry { bounds: Rect::new(-3840, -200, 3840, 2160), scale_factor: 1.5 };
+    let position = SharingIndicator::position(&monitor);
+    let left = -3840.0 / 1.5;
+    let width = 3840.0 / 1.5;
+    assert_eq!(position.x, left + (width - SharingIndicator::SIZE.width) / 2.0);
+    assert!(position.y >= -200.0 / 1.5, "{:?}", position);
+}
+
+#[test]
+fn stays_on_top_without_decorations() {
+    let monitor = MonitorGeometry { bounds: Rect::new(0, 0, 1920, 1080), scale_factor: 1.0 };
+    let settings = SharingIndicator::window_settings(Some(&monitor));
+    assert_eq!(settings.level, window::Level::AlwaysOnTop);
+    assert!(!settings.decorations);
+    assert!(settings.platform_specific.skip_taskbar);
+    assert!(matches!(
+        settings.position,
+        window::Position::Specific(position) if position == SharingIndicator::position(&monitor)
+    ));
+
+    let settings = SharingIndicator::window_settings(None);
+    assert!(matches!(settings.position, window::Position::Default));
+}
+
+#[test]
+fn pulse_fades_and_comes_back() {
+    let full = SharingIndicator::pulse_at(Duration::ZERO);
+    let faded = SharingIndicator::pulse_at(Duration::from_millis(800));
+    let back = SharingIndicator::pulse_at(Duration::from_millis(1600));
+    assert!((full - 1.0).abs() < 1e-4, "{}", full);
+    assert!((faded - 1.0 / 3.0).abs() < 1e-4, "{}", faded);
+    assert!((back - 1.0).abs() < 1e-4, "{}", back);
+
+    for millis in (0..3200).step_by(50) {
+        let pulse = SharingIndicator::pulse_at(Duration::from_millis(millis));
+        assert!((1.0 / 3.0 - 1e-4..=1.0 + 1e-4).contains(&pulse), "{}", pulse);
+    }
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use std::{
+    fs,
+    io::Write,
+    net::{Ipv4Addr, TcpListener},
+    path::Path,
+    time::Duration,
+};
+
+use common::test_dir::TestDir;
+use fjarsyn::platform::single_instance::{
+    self, Instance, InstanceListener, InstanceMessage, lock_path,
+};
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn primary(dir: &Path) -> InstanceListener {
+    match single_instance::acquire(dir, &InstanceMessage::Focus).unwrap() {
+        Instance::Primary(listener) => listener,
+        Instance::Secondary => panic!("Should be the first instance"),
+    }
+}
+
+fn lock_port(dir: &Path) -> Option<u16> {
+    fs::read_to_string(lock_path(dir)).ok()?.trim().parse().ok()
+}
+
+#[test]
+fn messages_survive_the_line() {
+    for message in [InstanceMessage::Focus, InstanceMessage::Call("ab12-cd34".to_owned())] {
+        assert_eq!(InstanceMessage::parse(&format!("{}\n", message.to_line())), Some(message));
+    }
+    for line in ["", "\n", "call", "call  \n", "call a b", "open fjarsyn://call/x", "focus now"] {
+        assert_eq!(InstanceMessage::parse(line), None, "{:?}", line);
+    }
+}
+
+#[test]
+fn call_links_are_found_in_the_arguments() {
+    let args = ["--reprobe", "fjarsyn://call/ab12"].map(str::to_owned);
+    assert_eq!(InstanceMessage::from_args(args), InstanceMessage::Call("ab12".to_owned()));
+    let args = ["--allow-multiple"].map(str::to_owned);
+    assert_eq!(InstanceMessage::from_args(args), InstanceMessage::Focus);
+}
+
+#[tokio::test]
+async fn second_instance_hands_over_to_the_first() {
+    let test_dir = TestDir::new("hand-over");
+    let dir = test_dir.path();
+    let first = primary(dir);
+    assert_eq!(lock_port(dir), Some(first.port()));
+    let messages = first.spawn();
+
+    let call = InstanceMessage::Call("ab12".to_owned());
+    for message in [InstanceMessage::Focus, call.clone()] {
+        assert!(matches!(single_instance::acquire(dir, &message).unwrap(), Instance::Secondary));
+    }
+
+    let receiver = messages.receiver();
+    let mut receiver = receiver.lock().await;
+    for expected in [InstanceMessage::Focus, call] {
+        let received = tokio::time::timeout(RECV_TIMEOUT, receiver.recv()).await.unwrap();
+        assert_eq!(received, Some(expected));
+    }
+}
+
+#[test]
+fn lock_of_an_instance_that_is_gone_is_taken_over() {
+    let test_dir = TestDir::new("stale");
+    let dir = test_dir.path();
+    // Nothing listens on the port once the listener is dropped.
+    let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+    fs::create_dir_all(dir).unwrap();
+    fs::write(lock_path(dir), format!("{}\n", port)).unwrap();
+
+    let listener = primary(dir);
+    assert_eq!(lock_port(dir), Some(listener.port()));
+}
+
+#[test]
+fn port_reused_by_something_else_is_taken_over() {
+    let test_dir = TestDir::new("reused");
+    let dir = test_dir.path();
+    let other = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = other.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        if let Ok((mut stream, _)) = other.accept() {
+            let _ = writeln!(stream, "SSH-2.0-OpenSSH");
+        }
+    });
+    fs::create_dir_all(dir).unwrap();
+    fs::write(lock_path(dir), format!("{}\n", port)).unwrap();
+
+    let listener = primary(dir);
+    assert_ne!(listener.port(), port);
+}
+
+#[test]
+fn lock_is_released_with_the_instance() {
+    let test_dir = TestDir::new("release");
+    let dir = test_dir.path();
+    let messages = primary(dir).spawn();
+    let copy = messages.clone();
+    drop(messages);
+    assert!(lock_path(dir).exists(), "Released while still in use");
+
+    drop(copy);
+    assert!(!lock_path(dir).exists());
+    // Which lets the next one start as the first.
+    primary(dir);
+}
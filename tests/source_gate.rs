@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::{
+    media::nal::NalCodec,
+    networking::webrtc::{Admit, SourceGate},
+};
+
+// SPS, PPS and an IDR slice, the way the encoders start a keyframe.
+const KEYFRAME: &[u8] = &[
+    0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1F, //
+    0, 0, 0, 1, 0x68, 0xCE, 0x3C, 0x80, //
+    0, 0, 1, 0x65, 0x88, 0x84, 0x00,
+];
+const DELTA: &[u8] = &[0, 0, 0, 1, 0x41, 0x9A, 0x02, 0x03];
+const FRAME: Duration = Duration::from_millis(33);
+
+const WRITE: Admit = Admit::Write { gap: None };
+
+#[test]
+fn lets_everything_through_until_restarted() {
+    let mut gate = SourceGate::new(Some(NalCodec::H264));
+    let start = Instant::now();
+    assert_eq!(gate.admit(DELTA, FRAME, start), WRITE);
+    assert_eq!(gate.admit(KEYFRAME, FRAME, start + FRAME), WRITE);
+    // However late, as long as the source didn't change.
+    assert_eq!(gate.admit(DELTA, FRAME, start + Duration::from_secs(5)), WRITE);
+}
+
+#[test]
+fn a_restarted_source_starts_from_a_keyframe() {
+    let mut gate = SourceGate::new(Some(NalCodec::H264));
+    let start = Instant::now();
+    assert_eq!(gate.admit(KEYFRAME, FRAME, start), WRITE);
+
+    gate.restart();
+    assert!(gate.is_restarting());
+    let resumed = start + FRAME;
+    assert_eq!(gate.admit(DELTA, FRAME, resumed), Admit::Drop);
+    assert_eq!(gate.admit(DELTA, FRAME, resumed), Admit::Drop);
+    assert_eq!(gate.admit(KEYFRAME, FRAME, resumed), WRITE);
+    assert!(!gate.is_restarting());
+    assert_eq!(gate.admit(DELTA, FRAME, resumed + FRAME), WRITE);
+}
+
+#[test]
+fn the_timestamps_jump_by_the_time_the_video_was_gone() {
+    let mut gate = SourceGate::new(Some(NalCodec::H264));
+    let start = Instant::now();
+    gate.admit(KEYFRAME, FRAME, start);
+
+    gate.restart();
+    let resumed = start + FRAME + Duration::from_secs(4);
+    assert_eq!(
+        gate.admit(KEYFRAME, FRAME, resumed),
+        Admit::Write { gap: Some(Duration::from_secs(4)) }
+    );
+    // Only once, as the new source carries on from there.
+    assert_eq!(gate.admit(DELTA, FRAME, resumed + Duration::from_secs(1)), WRITE);
+}
+
+#[test]
+fn a_first_source_has_no_gap_to_make_up() {
+    let mut gate = SourceGate::new(Some(NalCodec::H264));
+    gate.restart();
+    assert_eq!(gate.admit(KEYFRAME, FRAME, Instant::now()), WRITE);
+}
+
+#[test]
+fn samples_that_cant_be_told_apart_are_not_held_back() {
+    let mut gate = SourceGate::new(None);
+    gate.restart();
+    assert_eq!(gate.admit(DELTA, FRAME, Instant::now()), WRITE);
+}
@@ -0,0 +1,63 @@
+//! Which events a throttle lets through.
+
+use std::time::{Duration, Instant};
+
+use fjarsyn::utils::throttle::Throttle;
+
+const INTERVAL: Duration = Duration::from_millis(100);
+
+// Which of the events, at these milliseconds after the start, pass.
+fn passed(throttle: &mut Throttle, millis: &[u64]) -> Vec<bool> {
+    let start = Instant::now();
+    millis.iter().map(|&at| throttle.pass(start + Duration::from_millis(at))).collect()
+}
+
+#[test]
+fn the_first_event_passes() {
+    assert_eq!(passed(&mut Throttle::new(INTERVAL), &[0]), [true]);
+    assert_eq!(passed(&mut Throttle::new(Duration::from_secs(3600)), &[0]), [true]);
+}
+
+#[test]
+fn lets_one_event_through_per_interval() {
+    let mut throttle = Throttle::new(INTERVAL);
+    let events = [0, 10, 99, 100, 150, 199, 200, 450];
+    assert_eq!(
+        passed(&mut throttle, &events),
+        [true, false, false, true, false, false, true, true]
+    );
+}
+
+#[test]
+fn the_interval_counts_from_the_last_event_that_passed() {
+    let mut throttle = Throttle::new(INTERVAL);
+    // Held back events don't push the next one out, and a late one starts the interval over from itself.
+    let events = [0, 90, 130, 180, 229, 230];
+    assert_eq!(passed(&mut throttle, &events), [true, false, true, false, false, true]);
+}
+
+#[test]
+fn an_event_from_before_the_last_one_is_held_back() {
+    let mut throttle = Throttle::new(INTERVAL);
+    let start = Instant::now() + INTERVAL;
+    assert!(throttle.pass(start));
+    assert!(!throttle.pass(start - Duration::from_millis(50)));
+    assert!(throttle.pass(start + INTERVAL));
+}
+
+#[test]
+fn a_zero_interval_lets_everything_through() {
+    assert_eq!(passed(&mut Throttle::default(), &[0, 0, 0, 1]), [true; 4]);
+    assert_eq!(passed(&mut Throttle::per_second(0), &[0, 0, 1]), [true; 3]);
+}
+
+#[test]
+fn per_second_spreads_the_events_over_the_second() {
+    let mut throttle = Throttle::per_second(10);
+    let at: Vec<_> = (0..1000).step_by(10).collect();
+    let count = passed(&mut throttle, &at).into_iter().filter(|&passed| passed).count();
+    assert_eq!(count, 10);
+
+    let mut throttle = Throttle::per_second(4);
+    assert_eq!(passed(&mut throttle, &[0, 249, 250, 499, 500]), [true, false, true, false, true]);
+}
@@ -0,0 +1,145 @@
+//! Calls where one end only views, over connections that only reach each other.
+
+use std::time::Duration;
+
+use fjarsyn::{
+    config::Config,
+    media::{
+        encoder_worker::EncoderConfig,
+        ffmpeg::{DecodeAccel, FFmpegDecoder},
+    },
+    networking::{
+        signaling::LOOPBACK_IDS,
+        webrtc::{TrackId, WebRTC, WebRTCEvent},
+    },
+    session::{CallChannels, CallOptions, CaptureSession},
+    utils::{
+        test_support::{FramePattern, SyntheticFrames},
+        vector2::Vector2,
+    },
+};
+use fjarsyn_shared::CallMode;
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn next_event(channels: &CallChannels) -> WebRTCEvent {
+    let events = channels.events();
+    let event = tokio::time::timeout(RECV_TIMEOUT, events.lock().await.recv()).await.unwrap();
+    event.expect("the events ended").event
+}
+
+async fn wait_connected(channels: &CallChannels) {
+    loop {
+        match next_event(channels).await {
+            WebRTCEvent::Connected => return,
+            WebRTCEvent::Disconnected => panic!("the call ended before connecting"),
+            _ => (),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn view_only_caller_receives_video_without_sending_any() {
+    let options = CallOptions::default();
+    let [(viewer, viewer_receivers), (sharer, sharer_receivers)] = WebRTC::loopback(
+        options.max_depacket_latency,
+        options.auto_depacket_latency,
+        options.receive_buffer,
+        options.transcoding_type,
+    )
+    .await
+    .unwrap();
+    let viewer_channels = CallChannels::from(viewer_receivers);
+    let sharer_channels = CallChannels::from(sharer_receivers);
+    viewer.set_call_mode(CallMode::ViewOnly);
+    viewer.create_offer(LOOPBACK_IDS[1].to_owned()).await.unwrap();
+
+    // The offer only asks for video, and says so.
+    let offer = viewer.peer_connection().local_description().await.unwrap();
+    assert!(offer.sdp.contains("a=recvonly"), "the offer sends video: {}", offer.sdp);
+    assert_eq!(CallMode::from_sdp(&offer.sdp), CallMode::ViewOnly);
+    let event = next_event(&sharer_channels).await;
+    assert!(
+        matches!(&event, WebRTCEvent::IncomingCall(from, CallMode::ViewOnly) if from == LOOPBACK_IDS[0]),
+        "unexpected event: {:?}",
+        event
+    );
+    sharer.accept_call().await.unwrap();
+    let event = next_event(&viewer_channels).await;
+    assert!(
+        matches!(event, WebRTCEvent::Answered(CallMode::ShareAndView)),
+        "unexpected event: {:?}",
+        event
+    );
+    wait_connected(&viewer_channels).await;
+    wait_connected(&sharer_channels).await;
+
+    let size = Vector2::new(320, 240);
+    let config = EncoderConfig::from_config(&Config::default());
+    let frames = SyntheticFrames::new(size, config.input_format, FramePattern::Gradient);
+    let share = CaptureSession::start(frames.into_stream(30.0), config, sharer.clone()).unwrap();
+
+    let packets = viewer_channels.packets(TrackId::default());
+    let mut decoder = FFmpegDecoder::new(config.transcoding_type, DecodeAccel::Software).unwrap();
+    let frame = tokio::time::timeout(RECV_TIMEOUT, async {
+        loop {
+            let packet = packets.lock().await.recv().await.expect("the packets ended");
+            if let Some(frame) = decoder.decode(&packet).unwrap() {
+                return frame;
+            }
+        }
+    })
+    .await
+    .expect("nothing was decoded");
+    assert_eq!(frame.size, size);
+
+    // Nothing was ever set up to send video from the viewing end.
+    for sender in viewer.peer_connection().get_senders().await {
+        assert!(sender.track().await.is_none());
+    }
+
+    share.stop().await.unwrap();
+    viewer.shutdown().await.unwrap();
+    sharer.shutdown().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn view_only_callee_answers_without_a_track() {
+    let options = CallOptions::default();
+    let [(sharer, sharer_receivers), (viewer, viewer_receivers)] = WebRTC::loopback(
+        options.max_depacket_latency,
+        options.auto_depacket_latency,
+        options.receive_buffer,
+        options.transcoding_type,
+    )
+    .await
+    .unwrap();
+    let sharer_channels = CallChannels::from(sharer_receivers);
+    let viewer_channels = CallChannels::from(viewer_receivers);
+    viewer.set_call_mode(CallMode::ViewOnly);
+    sharer.create_offer(LOOPBACK_IDS[1].to_owned()).await.unwrap();
+
+    let event = next_event(&viewer_channels).await;
+    assert!(
+        matches!(event, WebRTCEvent::IncomingCall(_, CallMode::ShareAndView)),
+        "unexpected event: {:?}",
+        event
+    );
+    viewer.accept_call().await.unwrap();
+    let event = next_event(&sharer_channels).await;
+    assert!(
+        matches!(event, WebRTCEvent::Answered(CallMode::ViewOnly)),
+        "unexpected event: {:?}",
+        event
+    );
+    let answer = viewer.peer_connection().local_description().await.unwrap();
+    assert_eq!(CallMode::from_sdp(&answer.sdp), CallMode::ViewOnly);
+    wait_connected(&viewer_channels).await;
+
+    for sender in viewer.peer_connection().get_senders().await {
+        assert!(sender.track().await.is_none());
+    }
+
+    viewer.shutdown().await.unwrap();
+    sharer.shutdown().await.unwrap();
+}
@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use fjarsyn::media::watchdog::{Heartbeat, WatchdogError, Watched};
+
+// Stands in for an encoder whose driver can hang partway through a stream.
+struct FakeEncoder {
+    encoded: u32,
+    hang_at: Option<u32>,
+}
+
+impl FakeEncoder {
+    fn new(hang_at: Option<u32>) -> Self {
+        Self { encoded: 0, hang_at }
+    }
+
+    fn encode(&mut self, frame: u32) -> u32 {
+        if self.hang_at == Some(frame) {
+            loop {
+                std::thread::park();
+            }
+        }
+        self.encoded += 1;
+        frame * 2
+    }
+}
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+#[tokio::test]
+async fn calls_return_their_results() {
+    let encoder = Watched::spawn("test-encoder", FakeEncoder::new(None), TIMEOUT);
+    for frame in 0..5 {
+        assert_eq!(encoder.call(move |encoder| encoder.encode(frame)).await.unwrap(), frame * 2);
+    }
+    assert_eq!(encoder.call(|encoder| encoder.encoded).await.unwrap(), 5);
+    assert_eq!(encoder.busy_for(), None);
+}
+
+#[tokio::test]
+async fn stuck_call_is_given_up_on() {
+    let encoder = Watched::spawn("test-encoder", FakeEncoder::new(Some(2)), TIMEOUT);
+    assert_eq!(encoder.call(|encoder| encoder.encode(1)).await.unwrap(), 2);
+
+    let start = Instant::now();
+    let result = encoder.call(|encoder| encoder.encode(2)).await;
+    let waited = start.elapsed();
+
+    let Err(WatchdogError::Wedged(stuck_for)) = result else {
+        panic!("expected the call to be wedged, got {:?}", result.map(|_| ()));
+    };
+    assert!(stuck_for >= TIMEOUT);
+    assert!(waited >= TIMEOUT);
+    // Noticed within a check interval or so of the timeout, not whenever the call returns.
+    assert!(waited < TIMEOUT * 4, "took {:?} to notice", waited);
+    assert!(encoder.busy_for().is_some());
+}
+
+#[tokio::test]
+async fn replacement_works_after_abandoning_a_stuck_one() {
+    let mut encoder = Watched::spawn("test-encoder", FakeEncoder::new(Some(0)), TIMEOUT);
+    assert!(matches!(
+        encoder.call(|encoder| encoder.encode(0)).await,
+        Err(WatchdogError::Wedged(_))
+    ));
+
+    // What the encoder worker does: leave the stuck thread behind, and start over with a new one.
+    encoder = Watched::spawn("test-encoder", FakeEncoder::new(None), TIMEOUT);
+    assert_eq!(encoder.call(|encoder| encoder.encode(0)).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn panicking_call_is_reported_and_the_thread_survives() {
+    let encoder = Watched::spawn("test-encoder", FakeEncoder::new(None), TIMEOUT);
+    let result = encoder.call(|_: &mut FakeEncoder| -> u32 { panic!("driver exploded") }).await;
+    assert!(
+        matches!(result, Err(WatchdogError::Panicked(message)) if message == "driver exploded")
+    );
+    assert_eq!(encoder.call(|encoder| encoder.encode(3)).await.unwrap(), 6);
+}
+
+#[test]
+fn heartbeat_tracks_the_call_in_flight() {
+    let heartbeat = Heartbeat::new();
+    assert_eq!(heartbeat.busy_for(), None);
+
+    heartbeat.begin();
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(heartbeat.busy_for().is_some_and(|busy_for| busy_for >= Duration::from_millis(20)));
+
+    heartbeat.end();
+    assert_eq!(heartbeat.busy_for(), None);
+}
@@ -0,0 +1,61 @@
+//! Connections to a server on a local port, each delivering only to its own receivers.
+
+use std::time::Duration;
+
+use bifrost::SignalingServer;
+use fjarsyn::{
+    networking::webrtc::{PeerEvent, TrackId, WebRTCEvent},
+    session::{CallOptions, CallSession},
+};
+use fjarsyn_shared::CallMode;
+use tokio::net::TcpListener;
+
+const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { SignalingServer::new().serve(listener).await });
+    format!("ws://{}/ws", addr)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replaced_connection_does_not_deliver_to_the_new_one() {
+    let url = start_server().await;
+    let first = CallSession::listen(url.clone(), CallOptions::default()).await.unwrap();
+    let second = CallSession::listen(url.clone(), CallOptions::default()).await.unwrap();
+    let caller = CallSession::listen(url, CallOptions::default()).await.unwrap();
+
+    // What the app does when it reconnects: shut the old connection down, then read from the new one.
+    let first_channels = first.channels().clone();
+    let first_id = first.webrtc().peer_id();
+    assert_ne!(first_id, second.webrtc().peer_id());
+    first.close().await.unwrap();
+
+    // With its tasks ended and its handlers dropped, nothing can send to the old receivers anymore.
+    let first_events = first_channels.events();
+    tokio::time::timeout(RECV_TIMEOUT, async {
+        while let Some(PeerEvent { peer, event }) = first_events.lock().await.recv().await {
+            assert_eq!(peer, first_id);
+            assert!(
+                matches!(event, WebRTCEvent::Disconnected),
+                "unexpected event after shutdown: {:?}",
+                event
+            );
+        }
+    })
+    .await
+    .expect("the old events were still open after shutdown");
+    assert!(first_channels.packets(TrackId::default()).lock().await.recv().await.is_none());
+
+    let second_id = second.webrtc().get_local_id().unwrap();
+    let caller_id = caller.webrtc().get_local_id().unwrap();
+    caller.call(&second_id).await.unwrap();
+    let event = tokio::time::timeout(RECV_TIMEOUT, second.next_event()).await.unwrap();
+    assert!(
+        matches!(event, Some(WebRTCEvent::IncomingCall(from, CallMode::ShareAndView)) if from == caller_id)
+    );
+
+    caller.close().await.unwrap();
+    second.close().await.unwrap();
+}
@@ -0,0 +1,44 @@
+use fjarsyn::{capture_providers::shared::WindowIcon, utils::vector2::Vector2};
+
+const SIZE: Vector2<i32> = Vector2 { x: 2, y: 1 };
+
+#[test]
+fn swaps_bgra_to_rgba() {
+    let color = [10, 20, 30, 255, 40, 50, 60, 128];
+    let icon = WindowIcon::from_bitmaps(SIZE, &color, None).expect("Should convert");
+    assert_eq!(icon.size, SIZE);
+    assert_eq!(&*icon.rgba, &[30, 20, 10, 255, 60, 50, 40, 128]);
+}
+
+#[test]
+fn keeps_the_alpha_over_the_mask() {
+    // Transparent where the alpha says so, whatever the mask.
+    let color = [1, 2, 3, 0, 4, 5, 6, 200];
+    let mask = [0, 0, 0, 0, 255, 255, 255, 255];
+    let icon = WindowIcon::from_bitmaps(SIZE, &color, Some(&mask)).expect("Should convert");
+    assert_eq!(&*icon.rgba, &[3, 2, 1, 0, 6, 5, 4, 200]);
+}
+
+#[test]
+fn takes_transparency_from_the_mask_without_alpha() {
+    let color = [1, 2, 3, 0, 4, 5, 6, 0];
+    let mask = [255, 255, 255, 0, 0, 0, 0, 0];
+    let icon = WindowIcon::from_bitmaps(SIZE, &color, Some(&mask)).expect("Should convert");
+    assert_eq!(&*icon.rgba, &[3, 2, 1, 0, 6, 5, 4, 255]);
+}
+
+#[test]
+fn opaque_without_alpha_or_mask() {
+    let icon = WindowIcon::from_bitmaps(SIZE, &[0; 8], None).expect("Should convert");
+    assert_eq!(&*icon.rgba, &[0, 0, 0, 255, 0, 0, 0, 255]);
+}
+
+#[test]
+fn rejects_bitmaps_that_dont_match() {
+    assert_eq!(WindowIcon::from_bitmaps(SIZE, &[0; 4], None), None);
+    assert_eq!(WindowIcon::from_bitmaps(SIZE, &[0; 8], Some(&[0; 4])), None);
+    assert_eq!(WindowIcon::from_bitmaps(Vector2::new(0, 1), &[], None), None);
+
+    let huge = Vector2::new(WindowIcon::MAX_SIZE + 1, 1);
+    assert_eq!(WindowIcon::from_bitmaps(huge, &vec![0; huge.x as usize * 4], None), None);
+}